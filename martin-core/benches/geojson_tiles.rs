@@ -2,6 +2,7 @@ use std::f64::consts::PI;
 use std::hint::black_box;
 use std::io::Write as _;
 use std::num::NonZeroU32;
+use std::sync::Arc;
 
 use criterion::{Criterion, criterion_group, criterion_main};
 use geo_types::{Coord, LineString, Polygon};
@@ -10,6 +11,7 @@ use martin_core::CacheZoomRange;
 use martin_core::tiles::Source as _;
 use martin_core::tiles::geojson::source::GeoJsonSource;
 use martin_tile_utils::TileCoord;
+use martin_tile_utils::tms::TileMatrixSet;
 use tokio::runtime::Runtime;
 
 /// Number of polygon features in the synthetic dataset.
@@ -77,6 +79,7 @@ fn bench_geojson(c: &mut Criterion) {
                 "bench".to_owned(),
                 path.clone(),
                 CacheZoomRange::default(),
+                Arc::new(TileMatrixSet::web_mercator_quad()),
                 extent,
                 64,
             )
@@ -92,6 +95,7 @@ fn bench_geojson(c: &mut Criterion) {
             "bench".to_owned(),
             path.clone(),
             CacheZoomRange::default(),
+            Arc::new(TileMatrixSet::web_mercator_quad()),
             extent,
             64,
         ))
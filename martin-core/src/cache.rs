@@ -3,10 +3,13 @@
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
+use moka::Expiry;
 use moka::future::Cache;
-use tracing::{info, trace};
+use moka::ops::compute::{CompResult, Op};
+use tracing::{Span, info, instrument, trace};
 
 /// A cache key for [`ResourceCache`].
 pub trait CacheKey: Hash + Eq + Send + Sync + Clone + Debug + 'static {
@@ -26,6 +29,39 @@ pub trait Cacheable: Send + Sync + Clone + 'static {
     /// Approximate byte size, used as the moka eviction weight. Saturates at
     /// [`u32::MAX`].
     fn weight(&self) -> u32;
+
+    /// How long this value may be cached, overriding the configured expiry.
+    /// `None` uses the configured expiry.
+    fn expires_after(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Expires entries after [`Cacheable::expires_after`], or the configured expiry.
+struct ValueExpiry {
+    expiry: Option<Duration>,
+}
+
+impl ValueExpiry {
+    fn duration<V: Cacheable>(&self, value: &V) -> Option<Duration> {
+        value.expires_after().or(self.expiry)
+    }
+}
+
+impl<K, V: Cacheable> Expiry<K, V> for ValueExpiry {
+    fn expire_after_create(&self, _key: &K, value: &V, _created_at: Instant) -> Option<Duration> {
+        self.duration(value)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &K,
+        value: &V,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        self.duration(value)
+    }
 }
 
 /// In-memory cache backed by [`moka::future::Cache`]. The concrete sprite,
@@ -33,6 +69,8 @@ pub trait Cacheable: Send + Sync + Clone + 'static {
 #[derive(Clone)]
 pub struct ResourceCache<K: CacheKey, V: Cacheable> {
     inner: Cache<K, V>,
+    /// Incremented by every invalidation, so that values computed before it are not cached
+    generation: Arc<AtomicU64>,
 }
 
 impl<K: CacheKey, V: Cacheable> Debug for ResourceCache<K, V> {
@@ -41,7 +79,7 @@ impl<K: CacheKey, V: Cacheable> Debug for ResourceCache<K, V> {
             .field("name", &K::CACHE_NAME)
             .field("entry_count", &self.inner.entry_count())
             .field("weighted_size", &self.inner.weighted_size())
-            .finish()
+            .finish_non_exhaustive()
     }
 }
 
@@ -57,33 +95,79 @@ impl<K: CacheKey, V: Cacheable> ResourceCache<K, V> {
             .name(K::CACHE_NAME)
             .weigher(|_key: &K, value: &V| value.weight())
             .max_capacity(max_size_bytes)
-            .support_invalidation_closures();
-        if let Some(ttl) = expiry {
-            builder = builder.time_to_live(ttl);
-        }
+            .support_invalidation_closures()
+            .expire_after(ValueExpiry { expiry });
         if let Some(tti) = idle_timeout {
             builder = builder.time_to_idle(tti);
         }
         Self {
             inner: builder.build(),
+            generation: Arc::default(),
         }
     }
 
     /// Gets a cached value or computes one.
+    ///
+    /// Concurrent calls for the same key wait for each other, so a key is computed only once.
+    /// A value whose computation overlapped an invalidation is returned, but not cached,
+    /// because it may predate the change the invalidation was made for.
+    #[instrument(
+        level = "debug",
+        skip_all,
+        fields(cache.name = K::CACHE_NAME, cache.hit = tracing::field::Empty),
+    )]
     pub async fn get_or_insert<F, Fut, E>(&self, key: K, compute: F) -> Result<V, Arc<E>>
     where
         F: FnOnce() -> Fut,
         Fut: Future<Output = Result<V, E>>,
         E: Send + Sync + 'static,
     {
-        let entry = self
+        let mut started = 0;
+        let mut uncached = None;
+        let outcome = self
             .inner
             .entry(key.clone())
-            .or_try_insert_with(async move { compute().await })
-            .await?;
+            .and_compute_with(|entry| {
+                let (started, uncached) = (&mut started, &mut uncached);
+                async move {
+                    if entry.is_some() {
+                        return Op::Nop;
+                    }
+                    *started = self.generation.load(Ordering::Acquire);
+                    match compute().await {
+                        Ok(value) if !self.invalidated_since(*started) => Op::Put(value),
+                        result => {
+                            *uncached = Some(result);
+                            Op::Nop
+                        }
+                    }
+                }
+            })
+            .await;
+
+        let (value, hit) = match outcome {
+            CompResult::Unchanged(entry) => (entry.into_value(), true),
+            CompResult::Inserted(entry) | CompResult::ReplacedWith(entry) => {
+                // An invalidation may have run between the check above and the insert
+                if self.invalidated_since(started) {
+                    self.inner.invalidate(&key).await;
+                }
+                (entry.into_value(), false)
+            }
+            CompResult::StillNone(_) | CompResult::Removed(_) => {
+                let result = uncached.expect("a computed value is either cached or kept");
+                (result.map_err(Arc::new)?, false)
+            }
+        };
 
-        let hit = !entry.is_fresh();
         key.record_outcome(hit);
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_cache_size(
+            K::CACHE_NAME,
+            self.inner.entry_count(),
+            self.inner.weighted_size(),
+        );
+        Span::current().record("cache.hit", hit);
         if hit {
             trace!(
                 "{} cache HIT for {key:?} (entries={entries}, size={size}B)",
@@ -95,7 +179,16 @@ impl<K: CacheKey, V: Cacheable> ResourceCache<K, V> {
             trace!("{} cache MISS for {key:?}", K::CACHE_NAME);
         }
 
-        Ok(entry.into_value())
+        Ok(value)
+    }
+
+    /// Whether any invalidation ran since `generation` was read.
+    fn invalidated_since(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::Acquire) != generation
+    }
+
+    fn next_generation(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
     }
 
     /// Invalidates entries whose key matches `source_id`.
@@ -103,6 +196,7 @@ impl<K: CacheKey, V: Cacheable> ResourceCache<K, V> {
     /// Flush the cache via [`Self::run_pending_tasks`].
     pub fn invalidate_source(&self, source_id: &str) {
         let source_id_owned = source_id.to_owned();
+        self.next_generation();
         self.inner
             .invalidate_entries_if(move |key, _| key.matches_source(&source_id_owned))
             .expect("invalidate_entries_if predicate should not error");
@@ -112,10 +206,21 @@ impl<K: CacheKey, V: Cacheable> ResourceCache<K, V> {
         );
     }
 
+    /// Invalidates entries whose key matches `predicate`.
+    /// Eviction is asynchronous.
+    /// Flush the cache via [`Self::run_pending_tasks`].
+    pub(crate) fn invalidate_if(&self, predicate: impl Fn(&K) -> bool + Send + Sync + 'static) {
+        self.next_generation();
+        self.inner
+            .invalidate_entries_if(move |key, _| predicate(key))
+            .expect("invalidate_entries_if predicate should not error");
+    }
+
     /// Invalidates every entry.
     /// Eviction is asynchronous.
     /// Flush the cache via [`Self::run_pending_tasks`].
     pub fn invalidate_all(&self) {
+        self.next_generation();
         self.inner.invalidate_all();
         info!("Invalidated all {} cache entries", K::CACHE_NAME);
     }
@@ -168,8 +273,11 @@ pub const fn hit_miss_label(hit: bool) -> &'static str {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::pin::pin;
+    use std::sync::atomic::AtomicU32;
 
+    use futures::channel::oneshot;
+    use futures::poll;
     use rstest::rstest;
 
     use super::*;
@@ -326,6 +434,38 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn value_computed_across_invalidation_is_not_cached() {
+        let cache = cache();
+        let key = TestKey::new("a");
+        let (finish, finished) = oneshot::channel::<()>();
+
+        let mut read = pin!(cache.get_or_insert(key.clone(), || async {
+            finished.await.expect("the test finishes the read");
+            Ok::<_, std::convert::Infallible>(vec![1])
+        }));
+        assert!(poll!(read.as_mut()).is_pending());
+
+        // A write lands while the read is still computing the old value
+        cache.invalidate_source("a");
+        finish.send(()).unwrap();
+        assert_eq!(read.await.unwrap(), vec![1]);
+        cache.run_pending_tasks().await;
+        assert!(
+            !cache.contains_key(&key),
+            "the old value must not be cached"
+        );
+
+        let value = cache
+            .get_or_insert(key.clone(), || async {
+                Ok::<_, std::convert::Infallible>(vec![2])
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, vec![2]);
+        assert!(cache.contains_key(&key));
+    }
+
     #[test]
     fn vec_u8_weight_is_length_saturating() {
         assert_eq!(Cacheable::weight(&vec![]), 0);
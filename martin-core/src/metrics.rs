@@ -1,8 +1,18 @@
 //! Prometheus metrics shared across Martin's internal components.
 
-use std::sync::LazyLock;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, PoisonError, RwLock};
+#[cfg(feature = "_tiles")]
+use std::time::Duration;
 
-use prometheus::{IntCounterVec, register_int_counter_vec};
+use prometheus::{
+    HistogramVec, IntCounterVec, IntGaugeVec, exponential_buckets, register_histogram_vec,
+    register_int_counter_vec, register_int_gauge_vec,
+};
+
+#[cfg(feature = "_tiles")]
+use crate::tiles::{MartinCoreResult, Tile};
 
 /// Cache lookups for caches without a zoom dimension (`sprite`, `font`).
 pub static CACHE_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
@@ -26,8 +36,234 @@ pub static TILE_CACHE_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(||
     .expect("static tile cache metric definition is valid")
 });
 
+/// Tile requests that waited for a concurrent request of the same tile instead of
+/// generating it again, broken down by zoom level.
+pub static TILE_REQUESTS_COALESCED_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "martin_tile_requests_coalesced_total",
+        "Martin tile requests served by a concurrent request for the same tile, labeled by zoom",
+        &["zoom"]
+    )
+    .expect("static coalesced tile metric definition is valid")
+});
+
+/// Approximate number of entries in each cache, updated on every lookup.
+pub static CACHE_ENTRIES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "martin_cache_entries",
+        "Approximate number of entries in the Martin caches, labeled by cache type",
+        &["cache"]
+    )
+    .expect("static cache entries metric definition is valid")
+});
+
+/// Approximate size of each cache in bytes, updated on every lookup.
+pub static CACHE_SIZE_BYTES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "martin_cache_size_bytes",
+        "Approximate size of the Martin caches in bytes, labeled by cache type",
+        &["cache"]
+    )
+    .expect("static cache size metric definition is valid")
+});
+
+/// Time to generate a tile that was not cached, including its format conversion.
+pub static TILE_GENERATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "martin_tile_generation_seconds",
+        "Time to generate the tiles that were not cached, labeled by source",
+        &["source"],
+        exponential_buckets(0.001, 2.0, 15).expect("buckets are valid")
+    )
+    .expect("static tile generation metric definition is valid")
+});
+
+/// Size of the generated non-empty tiles.
+pub static TILE_SIZE_BYTES: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "martin_tile_size_bytes",
+        "Size of the generated non-empty tiles in bytes, labeled by source",
+        &["source"],
+        exponential_buckets(256.0, 4.0, 9).expect("buckets are valid")
+    )
+    .expect("static tile size metric definition is valid")
+});
+
+/// Generated tiles without any data.
+pub static EMPTY_TILES_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "martin_empty_tiles_total",
+        "Generated tiles without any data, labeled by source",
+        &["source"]
+    )
+    .expect("static empty tile metric definition is valid")
+});
+
+/// Tiles that could not be generated, labeled by [`MartinCoreError::kind`](crate::tiles::MartinCoreError::kind).
+pub static TILE_ERRORS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "martin_tile_errors_total",
+        "Tiles that could not be generated, labeled by source and error kind",
+        &["source", "kind"]
+    )
+    .expect("static tile error metric definition is valid")
+});
+
+/// Time to convert tiles between formats, e.g. from `mvt` to `mlt`.
+pub static TILE_CONVERSION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "martin_tile_conversion_seconds",
+        "Time to convert tiles between formats, labeled by input and output format",
+        &["from", "to"],
+        exponential_buckets(0.0001, 2.0, 15).expect("buckets are valid")
+    )
+    .expect("static tile conversion metric definition is valid")
+});
+
+/// Time to get a connection from a `PostgreSQL` pool.
+pub static POSTGRES_POOL_WAIT_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "martin_postgres_pool_wait_seconds",
+        "Time to get a connection from the PostgreSQL pools, labeled by pool",
+        &["pool"],
+        exponential_buckets(0.0001, 4.0, 10).expect("buckets are valid")
+    )
+    .expect("static pool wait metric definition is valid")
+});
+
+/// Bytes read from object stores, local files or HTTP servers: headers, directories and tiles.
+pub static OBJECT_STORE_FETCHED_BYTES_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "martin_object_store_fetched_bytes_total",
+        "Bytes read from object stores, including PMTiles headers and directories, labeled by source",
+        &["source"]
+    )
+    .expect("static object store metric definition is valid")
+});
+
+/// Label of the sources beyond the [maximum number](set_max_source_labels) of source labels
+pub const OTHER_SOURCES_LABEL: &str = "_other";
+
+/// Default maximum number of distinct `source` label values
+pub const DEFAULT_MAX_SOURCE_LABELS: usize = 100;
+
+/// Limits the number of distinct `source` label values, so that thousands of
+/// auto-discovered sources do not create thousands of time series.
+#[derive(Debug)]
+struct SourceLabels {
+    max: AtomicUsize,
+    seen: RwLock<HashSet<String>>,
+}
+
+impl SourceLabels {
+    fn new(max: usize) -> Self {
+        Self {
+            max: AtomicUsize::new(max),
+            seen: RwLock::default(),
+        }
+    }
+
+    fn get<'a>(&self, id: &'a str) -> &'a str {
+        let max = self.max.load(Ordering::Relaxed);
+        {
+            let seen = self.seen.read().unwrap_or_else(PoisonError::into_inner);
+            if seen.contains(id) {
+                return id;
+            }
+            if seen.len() >= max {
+                return OTHER_SOURCES_LABEL;
+            }
+        }
+        let mut seen = self.seen.write().unwrap_or_else(PoisonError::into_inner);
+        if seen.len() < max {
+            seen.insert(id.to_owned());
+            id
+        } else if seen.contains(id) {
+            id
+        } else {
+            OTHER_SOURCES_LABEL
+        }
+    }
+}
+
+static SOURCE_LABELS: LazyLock<SourceLabels> =
+    LazyLock::new(|| SourceLabels::new(DEFAULT_MAX_SOURCE_LABELS));
+
+/// Sets the maximum number of distinct `source` label values.
+///
+/// The first `max` sources that record a metric are labeled with their ID, all
+/// others with [`OTHER_SOURCES_LABEL`]. With `0`, no source gets its own label.
+pub fn set_max_source_labels(max: usize) {
+    SOURCE_LABELS.max.store(max, Ordering::Relaxed);
+}
+
+/// The `source` label value of the source with the given ID
+#[must_use]
+pub fn source_label(id: &str) -> &str {
+    SOURCE_LABELS.get(id)
+}
+
+/// Records the size of a cache after a lookup
+pub fn record_cache_size(cache: &str, entries: u64, size_bytes: u64) {
+    CACHE_ENTRIES
+        .with_label_values(&[cache])
+        .set(i64::try_from(entries).unwrap_or(i64::MAX));
+    CACHE_SIZE_BYTES
+        .with_label_values(&[cache])
+        .set(i64::try_from(size_bytes).unwrap_or(i64::MAX));
+}
+
+/// Records the outcome of generating a tile that was not cached
+#[cfg(feature = "_tiles")]
+pub fn record_tile_generation(
+    source_id: &str,
+    duration: Duration,
+    result: &MartinCoreResult<Tile>,
+) {
+    let source = source_label(source_id);
+    match result {
+        Ok(tile) => {
+            TILE_GENERATION_SECONDS
+                .with_label_values(&[source])
+                .observe(duration.as_secs_f64());
+            if tile.is_empty() {
+                EMPTY_TILES_TOTAL.with_label_values(&[source]).inc();
+            } else {
+                let size = u32::try_from(tile.data.len()).unwrap_or(u32::MAX);
+                TILE_SIZE_BYTES
+                    .with_label_values(&[source])
+                    .observe(f64::from(size));
+            }
+        }
+        Err(e) => TILE_ERRORS_TOTAL
+            .with_label_values(&[source, e.kind()])
+            .inc(),
+    }
+}
+
 /// Pre-rendered zoom labels indexed by zoom level
 pub const ZOOM_LABELS: [&str; 31] = [
     "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16",
     "17", "18", "19", "20", "21", "22", "23", "24", "25", "26", "27", "28", "29", "30",
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_labels_are_limited() {
+        let labels = SourceLabels::new(2);
+        assert_eq!(labels.get("a"), "a");
+        assert_eq!(labels.get("b"), "b");
+        assert_eq!(labels.get("c"), OTHER_SOURCES_LABEL);
+        assert_eq!(labels.get("a"), "a");
+
+        labels.max.store(0, Ordering::Relaxed);
+        assert_eq!(labels.get("b"), "b");
+        assert_eq!(labels.get("d"), OTHER_SOURCES_LABEL);
+
+        let labels = SourceLabels::new(0);
+        assert_eq!(labels.get("a"), OTHER_SOURCES_LABEL);
+    }
+}
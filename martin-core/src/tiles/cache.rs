@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use martin_tile_utils::{Format, TileCoord};
 
 use crate::cache::{CacheKey, Cacheable, ResourceCache};
@@ -45,6 +47,45 @@ impl TileCacheKey {
     }
 }
 
+impl TileCache {
+    /// Invalidates every cached variant of the tile `xyz` of `source_id`,
+    /// including the tiles within it at higher zoom levels, which overzoom may have cut out of it.
+    /// Eviction is asynchronous.
+    /// Flush the cache via [`Self::run_pending_tasks`].
+    pub fn invalidate_tile(&self, source_id: &str, xyz: TileCoord) {
+        let source_id = source_id.to_owned();
+        self.invalidate_if(move |key| key.is_within(&source_id, xyz));
+    }
+}
+
+impl TileCacheKey {
+    /// Whether this is a variant of the tile `xyz` of `source_id`, or of a tile within it.
+    pub(crate) fn is_within(&self, source_id: &str, xyz: TileCoord) -> bool {
+        self.source_id == source_id && is_within(self.xyz, xyz)
+    }
+}
+
+/// Whether `tile` is `parent`, or lies within it at a higher zoom level.
+fn is_within(tile: TileCoord, parent: TileCoord) -> bool {
+    let Some(dz) = tile.z.checked_sub(parent.z) else {
+        return false;
+    };
+    u32::from(dz) < u32::BITS && tile.x >> dz == parent.x && tile.y >> dz == parent.y
+}
+
+impl TileCacheKey {
+    /// Records a request served by a concurrent generation of the same tile,
+    /// see [`TileFlights`](crate::tiles::TileFlights).
+    #[cfg_attr(not(feature = "metrics"), expect(clippy::unused_self))]
+    pub(crate) fn record_coalesced(&self) {
+        #[cfg(feature = "metrics")]
+        crate::metrics::TILE_REQUESTS_COALESCED_TOTAL
+            .with_label_values(&[crate::metrics::ZOOM_LABELS[self.xyz.z as usize]])
+            .inc();
+        hotpath::gauge!("tile_requests_coalesced").inc(1.0);
+    }
+}
+
 impl CacheKey for TileCacheKey {
     const CACHE_NAME: &'static str = "tile";
 
@@ -74,4 +115,48 @@ impl Cacheable for Tile {
     fn weight(&self) -> u32 {
         self.data.len().try_into().unwrap_or(u32::MAX)
     }
+
+    fn expires_after(&self) -> Option<Duration> {
+        self.hints.remaining()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{Encoding, TileInfo};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn invalidate_tile() {
+        let cache = TileCache::new(1_000_000, None, None);
+        let tile = |z, x, y| TileCoord { z, x, y };
+        let key =
+            |source: &str, xyz, format| TileCacheKey::new(source.to_owned(), xyz, None, format);
+        let keys = [
+            key("src", tile(3, 2, 5), None),
+            key("src", tile(3, 2, 5), Some(Format::Mlt)),
+            key("src", tile(5, 8, 21), None),
+            key("src", tile(3, 2, 4), None),
+            key("src", tile(2, 1, 2), None),
+            key("other", tile(3, 2, 5), None),
+        ];
+        for key in &keys {
+            cache
+                .get_or_insert(key.clone(), || async {
+                    Ok::<_, std::convert::Infallible>(Tile::new_hash_etag(
+                        vec![0],
+                        TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                    ))
+                })
+                .await
+                .unwrap();
+        }
+
+        cache.invalidate_tile("src", tile(3, 2, 5));
+        cache.run_pending_tasks().await;
+
+        let remaining: Vec<_> = keys.iter().map(|k| cache.contains_key(k)).collect();
+        assert_eq!(remaining, vec![false, false, false, true, true, true]);
+    }
 }
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use martin_tile_utils::TileCoord;
+use tokio::sync::OnceCell;
+
+use crate::tiles::{MartinCoreError, Tile, TileCacheKey};
+
+/// Result of a tile generation, shared between all the requests waiting for it.
+pub type SharedTileResult = Result<Tile, Arc<MartinCoreError>>;
+
+/// A tile being generated, and the number of requests generating or waiting for it
+#[derive(Debug, Default)]
+struct Flight {
+    result: Arc<OnceCell<SharedTileResult>>,
+    requests: usize,
+}
+
+type InFlight = HashMap<TileCacheKey, Flight>;
+
+/// Single-flight deduplication of concurrent requests for the same tile.
+///
+/// The first request for a [`TileCacheKey`] generates the tile, and requests for the same key
+/// arriving before it finishes wait for its result instead of generating the tile again.
+/// Errors are shared the same way.
+/// If the generating request is cancelled, one of the waiting requests takes over.
+///
+/// Results are not kept once the generation finishes,
+/// see [`TileCache`](crate::tiles::TileCache) for that.
+/// After a tile changes, [`Self::invalidate_tile`] keeps new requests from waiting for
+/// a generation that may still return the old tile.
+#[derive(Debug, Clone, Default)]
+pub struct TileFlights {
+    in_flight: Arc<Mutex<InFlight>>,
+}
+
+impl TileFlights {
+    /// Creates an empty set of in-flight tile generations.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generates the tile with `compute`, unless a concurrent request for the same key already
+    /// does, in which case its result is returned.
+    pub async fn run<F, Fut>(&self, key: TileCacheKey, compute: F) -> SharedTileResult
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = SharedTileResult>,
+    {
+        let request = self.join(key);
+
+        let mut computed = false;
+        let result = request
+            .result
+            .get_or_init(|| {
+                computed = true;
+                compute()
+            })
+            .await
+            .clone();
+
+        if !computed {
+            request.key.record_coalesced();
+        }
+        result
+    }
+
+    fn join(&self, key: TileCacheKey) -> FlightRequest<'_> {
+        let mut in_flight = self.lock();
+        let flight = in_flight.entry(key.clone()).or_default();
+        flight.requests += 1;
+        FlightRequest {
+            result: Arc::clone(&flight.result),
+            flights: self,
+            key,
+        }
+    }
+
+    /// Detaches the running generations of every variant of the tile `xyz` of `source_id`,
+    /// including the tiles within it at higher zoom levels.
+    ///
+    /// Requests already waiting still get the result of their generation,
+    /// while later requests start a new one.
+    pub fn invalidate_tile(&self, source_id: &str, xyz: TileCoord) {
+        self.lock().retain(|key, _| !key.is_within(source_id, xyz));
+    }
+
+    /// Number of tiles being generated right now
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no tiles are being generated right now
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InFlight> {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A request taking part in a [`Flight`].
+///
+/// Dropping it, also when the request is cancelled, removes the flight once the tile is generated,
+/// or once no request is left to generate it.
+struct FlightRequest<'a> {
+    flights: &'a TileFlights,
+    key: TileCacheKey,
+    result: Arc<OnceCell<SharedTileResult>>,
+}
+
+impl Drop for FlightRequest<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.flights.lock();
+        let Some(flight) = in_flight.get_mut(&self.key) else {
+            return;
+        };
+        // The flight may already have been replaced by a later one
+        if !Arc::ptr_eq(&flight.result, &self.result) {
+            return;
+        }
+        flight.requests -= 1;
+        // Later requests for this key must generate the tile again
+        if flight.requests == 0 || self.result.initialized() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::pending;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use futures::channel::oneshot;
+    use futures::future::join_all;
+    use futures::poll;
+    use martin_tile_utils::{Encoding, Format, TileInfo};
+
+    use super::*;
+
+    fn key(z: u8) -> TileCacheKey {
+        TileCacheKey::new("src".to_owned(), TileCoord { z, x: 0, y: 0 }, None, None)
+    }
+
+    fn tile(data: &[u8]) -> Tile {
+        Tile::new_hash_etag(
+            data.to_vec(),
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+        )
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_one_generation() {
+        let flights = TileFlights::new();
+        let calls = AtomicU32::new(0);
+        let compute = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            // let the other requests start waiting
+            tokio::task::yield_now().await;
+            Ok::<_, Arc<MartinCoreError>>(tile(&[1, 2, 3]))
+        };
+
+        let results = join_all((0..10).map(|_| flights.run(key(0), compute))).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        for result in results {
+            assert_eq!(result.unwrap().data, vec![1, 2, 3]);
+        }
+        assert!(flights.is_empty());
+    }
+
+    #[tokio::test]
+    async fn errors_are_shared() {
+        let flights = TileFlights::new();
+        let compute = || async {
+            tokio::task::yield_now().await;
+            Err::<Tile, _>(Arc::new(MartinCoreError::SourceNeedsReload))
+        };
+
+        let results = join_all((0..3).map(|_| flights.run(key(0), compute))).await;
+        for result in results {
+            assert!(matches!(
+                result.unwrap_err().as_ref(),
+                MartinCoreError::SourceNeedsReload
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn finished_generations_are_not_reused() {
+        let flights = TileFlights::new();
+        let calls = AtomicU32::new(0);
+        let compute = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, Arc<MartinCoreError>>(tile(&[1]))
+        };
+
+        flights.run(key(0), compute).await.unwrap();
+        flights.run(key(0), compute).await.unwrap();
+        flights.run(key(1), compute).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(flights.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_generations_are_removed() {
+        let flights = TileFlights::new();
+        let mut leader = Box::pin(flights.run(key(0), pending));
+        assert!(poll!(leader.as_mut()).is_pending());
+        assert_eq!(flights.len(), 1);
+
+        // e.g. the client disconnected
+        drop(leader);
+        assert!(flights.is_empty());
+    }
+
+    #[tokio::test]
+    async fn waiting_request_takes_over_cancelled_generation() {
+        let flights = TileFlights::new();
+        let mut leader = Box::pin(flights.run(key(0), pending));
+        assert!(poll!(leader.as_mut()).is_pending());
+        let mut waiter = pin!(flights.run(key(0), || async {
+            Ok::<_, Arc<MartinCoreError>>(tile(&[1]))
+        }));
+        assert!(poll!(waiter.as_mut()).is_pending());
+
+        drop(leader);
+        assert_eq!(flights.len(), 1);
+        assert_eq!(waiter.await.unwrap().data, vec![1]);
+        assert!(flights.is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalidated_generations_are_not_joined() {
+        let flights = TileFlights::new();
+        let (finish, finished) = oneshot::channel::<()>();
+        let mut old = pin!(flights.run(key(0), || async {
+            finished
+                .await
+                .expect("the test finishes the old generation");
+            Ok::<_, Arc<MartinCoreError>>(tile(&[1]))
+        }));
+        assert!(poll!(old.as_mut()).is_pending());
+
+        // The tile was written while the old generation was running
+        flights.invalidate_tile("src", TileCoord { z: 0, x: 0, y: 0 });
+        assert!(flights.is_empty());
+        let new = flights.run(key(0), || async {
+            Ok::<_, Arc<MartinCoreError>>(tile(&[2]))
+        });
+        assert_eq!(new.await.unwrap().data, vec![2]);
+
+        finish.send(()).unwrap();
+        assert_eq!(old.await.unwrap().data, vec![1]);
+        assert!(flights.is_empty());
+    }
+}
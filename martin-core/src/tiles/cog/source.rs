@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 use std::vec;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use martin_tile_utils::{
     EARTH_CIRCUMFERENCE, Encoding, MAX_ZOOM, TileCoord, TileData, TileInfo, webmercator_to_wgs84,
 };
@@ -18,7 +19,7 @@ use crate::CacheZoomRange;
 use crate::tiles::cog::CogError;
 use crate::tiles::cog::image::{COMPRESSION_WEBP, Image};
 use crate::tiles::cog::model::ModelInfo;
-use crate::tiles::{MartinCoreResult, Source, UrlQuery};
+use crate::tiles::{MartinCoreResult, Source, UrlQuery, file_modified};
 
 /// Maximum allowed relative error (as a fraction) when matching a resolution to a `WebMercatorQuad`
 /// tile matrix zoom level. 1e-3 = 0.1%.
@@ -40,6 +41,7 @@ pub struct CogSource {
     tilejson: TileJSON,
     tileinfo: TileInfo,
     cache_zoom: CacheZoomRange,
+    last_modified: Option<DateTime<Utc>>,
 }
 
 impl CogSource {
@@ -168,6 +170,7 @@ impl CogSource {
 
         Ok(Self {
             id,
+            last_modified: file_modified(&path),
             path,
             min_zoom,
             max_zoom,
@@ -222,6 +225,10 @@ impl Source for CogSource {
         false
     }
 
+    fn get_last_modified(&self) -> Option<DateTime<Utc>> {
+        self.last_modified
+    }
+
     fn cache_zoom(&self) -> CacheZoomRange {
         self.cache_zoom
     }
@@ -107,6 +107,16 @@ impl DuckDBPool {
             .map_err(|e| DuckDBError::DuckDBTaskJoinError(e, "using DuckDB connection"))?
     }
 
+    /// Checks that a connection can be acquired.
+    /// Idle connections are verified with `SELECT 1` by the pool before they are handed out.
+    pub async fn check_health(&self) -> DuckDBResult<()> {
+        self.pool
+            .get()
+            .await
+            .map_err(|e| DuckDBError::DuckDBPoolConnError(e, self.id.clone()))?;
+        Ok(())
+    }
+
     #[must_use]
     /// ID under which this [`DuckDBPool`] is identified externally
     pub fn get_id(&self) -> &str {
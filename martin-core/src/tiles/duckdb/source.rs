@@ -73,6 +73,10 @@ impl Source for DuckDBSource {
         self.cache_zoom
     }
 
+    async fn check_health(&self) -> MartinCoreResult<()> {
+        Ok(self.pool.check_health().await?)
+    }
+
     #[instrument(
         level = "debug",
         skip_all,
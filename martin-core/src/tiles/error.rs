@@ -63,3 +63,28 @@ pub enum MartinCoreError {
 
 /// A convenience [`Result`] for tiles coming from `martin-core`.
 pub type MartinCoreResult<T> = Result<T, MartinCoreError>;
+
+impl MartinCoreError {
+    /// Short name of the error variant, e.g. for the `kind` label of metrics
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "mbtiles")]
+            Self::MbtilesError(_) => "mbtiles",
+            #[cfg(feature = "postgres")]
+            Self::PostgresError(_) => "postgres",
+            #[cfg(feature = "unstable-duckdb")]
+            Self::DuckDBError(_) => "duckdb",
+            #[cfg(feature = "pmtiles")]
+            Self::PmtilesError(_) => "pmtiles",
+            #[cfg(feature = "passthrough")]
+            Self::PassthroughError(_) => "passthrough",
+            #[cfg(feature = "unstable-cog")]
+            Self::CogError(_) => "cog",
+            Self::SourceNeedsReload => "source_needs_reload",
+            #[cfg(feature = "geojson")]
+            Self::GeoJsonError(_) => "geojson",
+            Self::OtherError(_) => "other",
+        }
+    }
+}
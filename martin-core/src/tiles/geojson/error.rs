@@ -22,6 +22,12 @@ pub enum GeoJsonError {
     #[error("GeoJSON property {0} cannot be represented as an MVT value")]
     UnsupportedProperty(String),
 
+    /// Features cannot be projected into the coordinate reference system of the tile matrix set
+    #[error(
+        "GeoJSON cannot be projected into tile matrix set {0} with CRS {1}, only Web Mercator and WGS84 are supported"
+    )]
+    UnsupportedTileMatrixSet(String, String),
+
     /// More features than can be spatially indexed
     #[error("GeoJSON has too many features to index: {0} exceeds u32::MAX")]
     TooManyFeatures(usize),
@@ -4,7 +4,7 @@ use geo::{BoundingRect as _, MapCoords as _};
 use geo_index::rtree::sort::HilbertSort;
 use geo_index::rtree::{RTree, RTreeBuilder};
 use geojson::{GeoJson, JsonValue};
-use martin_tile_utils::{EARTH_CIRCUMFERENCE, wgs84_to_webmercator};
+use martin_tile_utils::tms::TileMatrixSet;
 use mlt_core::fast_mvt::{MvtFeatureBuilder, MvtValue};
 use serde_json::Map;
 
@@ -13,7 +13,7 @@ use crate::tiles::geojson::error::GeoJsonError;
 /// A feature ready to be served: a geometry plus its `GeoJSON` properties.
 ///
 /// The coordinate type `T` tracks which space the geometry lives in: `f64` for the preprocessed
-/// Web Mercator (EPSG:3857) features, and `i32` once clipped and snapped to the MVT tile grid.
+/// features projected into the tile matrix set coordinate reference system, and `i32` once clipped and snapped to the MVT tile grid.
 #[derive(Clone)]
 pub struct PreparedFeature<T: geo_types::CoordNum = f64> {
     /// Feature geometry.
@@ -24,11 +24,11 @@ pub struct PreparedFeature<T: geo_types::CoordNum = f64> {
 
 /// A `GeoJSON` document turned into everything the source needs to answer tile and `TileJSON` requests.
 pub struct Preprocessed {
-    /// Features ready to serve, in Web Mercator.
+    /// Features ready to serve, in the tile matrix set coordinate reference system.
     pub(crate) features: Vec<PreparedFeature>,
     /// Spatial index over every feature's bounding box.
     pub(crate) rtree: RTree<f64>,
-    /// The data bounding box in Web Mercator, `None` when no feature contributed a geometry.
+    /// The data bounding box in the tile matrix set coordinate reference system, `None` when no feature contributed a geometry.
     pub(crate) bounds: Option<geo_types::Rect<f64>>,
     /// Every property name the features carry, mapped to the MVT type it encodes as.
     pub(crate) fields: BTreeMap<String, String>,
@@ -49,10 +49,21 @@ const fn field_type(value: &JsonValue) -> Option<&'static str> {
 /// Preprocess a parsed `GeoJSON` document into features ready to serve.
 ///
 /// 1. Keep only features that carry a geometry.
-/// 2. Reproject geometries from WGS84 to Web Mercator.
+/// 2. Reproject geometries from WGS84 to the coordinate reference system of the tile matrix set.
 /// 3. Index every geometry's bounding box in a packed Hilbert R-tree.
 /// 4. Collect the property names across all features, so `TileJSON` can advertise them.
-pub fn preprocess_geojson(geojson: GeoJson) -> Result<Preprocessed, GeoJsonError> {
+pub fn preprocess_geojson(
+    geojson: GeoJson,
+    tms: &TileMatrixSet,
+) -> Result<Preprocessed, GeoJsonError> {
+    // Fail early instead of for every coordinate
+    if tms.wgs84_to_crs(0.0, 0.0).is_none() {
+        return Err(GeoJsonError::UnsupportedTileMatrixSet(
+            tms.id.clone(),
+            tms.crs.clone(),
+        ));
+    }
+
     let raw = match geojson {
         GeoJson::FeatureCollection(fc) => fc
             .features
@@ -70,7 +81,7 @@ pub fn preprocess_geojson(geojson: GeoJson) -> Result<Preprocessed, GeoJsonError
         let geom = geo_types::Geometry::<f64>::try_from(geometry.value)
             .map_err(|e| GeoJsonError::GeoJsonError(Box::new(e)))?;
         let geom = geom.map_coords(|c| {
-            let (x, y) = wgs84_to_webmercator(c.x, c.y);
+            let (x, y) = tms.wgs84_to_crs(c.x, c.y).unwrap_or((c.x, c.y));
             geo_types::Coord { x, y }
         });
         // An empty geometry has no extent to index or serve.
@@ -126,10 +137,6 @@ pub fn preprocess_geojson(geojson: GeoJson) -> Result<Preprocessed, GeoJsonError
     })
 }
 
-pub fn tile_length_from_zoom(zoom: u8) -> f64 {
-    EARTH_CIRCUMFERENCE / f64::from(1_u32 << zoom)
-}
-
 /// Copy `GeoJSON` properties onto an MVT feature as attribute tags.
 /// Geometries carry no attributes once converted to `geo_types`, so this is the only feature
 /// metadata copied through to the encoder. Null-valued properties are omitted; arrays and objects
@@ -5,12 +5,13 @@ use geo::bool_ops::FillRule;
 use geo::orient::Direction;
 use geo::{BooleanOps as _, MapCoords as _, Orient as _, Validation as _, unary_union};
 use geo_types::{Coord, Geometry, GeometryCollection, MultiLineString, MultiPoint, Point, Polygon};
-use martin_tile_utils::tile_bbox;
+use martin_tile_utils::TileCoord;
+use martin_tile_utils::tms::TileMatrixSet;
 
 use crate::tiles::geojson::convert::validate_and_simplify;
-use crate::tiles::geojson::process::tile_length_from_zoom;
 
-/// A single tile in Web Mercator space, carrying the MVT resolution it is rendered at.
+/// A single tile in the coordinate reference system of its tile matrix set,
+/// carrying the MVT resolution it is rendered at.
 #[derive(Debug, Clone)]
 pub struct Rect {
     pub(crate) min_x: f64,
@@ -31,17 +32,22 @@ impl Rect {
         x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
     }
 
-    pub(crate) fn from_xyz(x: u32, y: u32, zoom: u8, extent: NonZeroU32, buffer: u32) -> Self {
-        let tile_length = tile_length_from_zoom(zoom);
-        let [min_x, min_y, max_x, max_y] = tile_bbox(x, y, tile_length);
-        Self {
+    /// The tile `xyz` of the tile matrix set, `None` if the set has no such zoom level.
+    pub(crate) fn from_xyz(
+        tms: &TileMatrixSet,
+        xyz: TileCoord,
+        extent: NonZeroU32,
+        buffer: u32,
+    ) -> Option<Self> {
+        let [min_x, min_y, max_x, max_y] = tms.tile_bounds(xyz)?;
+        Some(Self {
             min_x,
             min_y,
             max_x,
             max_y,
             extent,
             buffer,
-        }
+        })
     }
 
     /// The clip margin as a fraction of the tile width, e.g. `64 / 4096`.
@@ -61,7 +67,7 @@ impl Rect {
         self.max_y += buffer_y;
     }
 
-    /// The (buffered) tile rectangle as a clip polygon in Web Mercator coordinates.
+    /// The (buffered) tile rectangle as a clip polygon in the tile matrix set coordinates.
     fn clip_polygon(&self) -> Polygon<f64> {
         geo_types::Rect::new(
             Coord {
@@ -113,7 +119,7 @@ impl Rect {
         validate_and_simplify(tile_space.into())
     }
 
-    /// Clip a Web Mercator geometry to this (buffered) tile, snap it to the integer MVT grid, and
+    /// Clip a projected geometry to this (buffered) tile, snap it to the integer MVT grid, and
     /// validate; `None` when nothing of the geometry remains inside the tile.
     pub(crate) fn clip_transform_validate_geometry(
         &self,
@@ -147,13 +153,13 @@ impl Rect {
         }
     }
 
-    /// Transform a Web Mercator coordinate into the integer-snapped MVT tile grid.
+    /// Transform a projected coordinate into the integer-snapped MVT tile grid.
     fn to_tile_coord(&self, c: Coord<f64>) -> Coord<f64> {
         let [x, y] = self.transform_to_tile_coordinates(&[c.x, c.y]);
         Coord { x, y }
     }
 
-    /// Transform from EPSG:3857 to local MVT tile coordinates
+    /// Transform from the tile matrix set coordinates to local MVT tile coordinates
     fn transform_to_tile_coordinates(&self, point: &[f64]) -> [f64; 2] {
         let x = point[0];
         let y = point[1];
@@ -188,7 +194,9 @@ mod tests {
     fn transform_to_tile_coordinates() {
         let point = [1_962_772.0, 6_300_000.0];
         let extent = NonZeroU32::new(4096).expect("4096 is non-zero");
-        let mut rect = Rect::from_xyz(70, 43, 7, extent, 256);
+        let tms = TileMatrixSet::web_mercator_quad_ref();
+        let xyz = TileCoord { z: 7, x: 70, y: 43 };
+        let mut rect = Rect::from_xyz(tms, xyz, extent, 256).expect("zoom 7 exists");
         rect.add_buffer();
         let transformed_point = rect.transform_to_tile_coordinates(&point);
         // `transform_to_tile_coordinates` floors to integer tile coordinates, so the
@@ -2,13 +2,16 @@ use std::collections::BTreeMap;
 use std::fmt::{Debug, Formatter};
 use std::num::NonZeroU32;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use geo::MapCoords as _;
 use geo_index::rtree::{RTree, RTreeIndex as _};
 use geo_types::{Coord, Geometry};
 use geojson::GeoJson;
-use martin_tile_utils::{Encoding, Format, TileCoord, TileData, TileInfo, webmercator_to_wgs84};
+use martin_tile_utils::tms::TileMatrixSet;
+use martin_tile_utils::{Encoding, Format, TileCoord, TileData, TileInfo};
 use mlt_core::fast_mvt::{MvtExtent, MvtGeometry, MvtTileBuilder};
 use rayon::prelude::*;
 use tilejson::{Bounds, Center, TileJSON, VectorLayer};
@@ -21,13 +24,15 @@ use crate::tiles::geojson::process::{
     PreparedFeature, Preprocessed, add_properties, preprocess_geojson,
 };
 use crate::tiles::geojson::rect::Rect;
-use crate::tiles::{BoxedSource, MartinCoreError, MartinCoreResult, Source, UrlQuery};
+use crate::tiles::{
+    BoxedSource, MartinCoreError, MartinCoreResult, Source, UrlQuery, file_modified,
+};
 
 /// A source for `GeoJSON` files
 ///
 /// Steps to pre-process `GeoJSON` features that have a geometry:
 ///
-/// 1. Convert from WGS84 EPSG:4326 to Web Mercator EPSG:3857
+/// 1. Convert from WGS84 EPSG:4326 to the tile matrix set CRS, e.g. Web Mercator EPSG:3857
 /// 2. Create spatial index using a packed Hilbert R-Tree
 ///
 /// This data source will be used to query features that overlap with a given tile:
@@ -43,21 +48,26 @@ pub struct GeoJsonSource {
     tilejson: TileJSON,
     tile_info: TileInfo,
     cache_zoom: CacheZoomRange,
+    tms: Arc<TileMatrixSet>,
     /// Side length of the MVT tile coordinate grid every tile is encoded into.
     extent: NonZeroU32,
     /// Clip margin kept around each tile edge, in tile units (a fraction of `extent`).
     buffer: u32,
+    last_modified: Option<DateTime<Utc>>,
 }
 
 impl GeoJsonSource {
-    /// Create a new `GeoJSON` source rendering tiles at the given MVT `extent` and clip `buffer`.
+    /// Create a new `GeoJSON` source rendering tiles of the tile matrix set `tms`
+    /// at the given MVT `extent` and clip `buffer`.
     pub async fn new(
         id: String,
         path: PathBuf,
         cache_zoom: CacheZoomRange,
+        tms: Arc<TileMatrixSet>,
         extent: NonZeroU32,
         buffer: u32,
     ) -> Result<Self, GeoJsonError> {
+        let last_modified = file_modified(&path);
         let geojson_str = fs::read_to_string(&path)
             .await
             .map_err(|err| GeoJsonError::IoError(err, path))?;
@@ -70,7 +80,7 @@ impl GeoJsonSource {
             rtree,
             bounds,
             fields,
-        } = preprocess_geojson(geojson)?;
+        } = preprocess_geojson(geojson, &tms)?;
 
         // Every tile is encoded as a single layer named after the source, so that is the one
         // vector layer to advertise. Clients such as maplibre-gl-inspect build their layers
@@ -84,11 +94,14 @@ impl GeoJsonSource {
             other: BTreeMap::default(),
         };
 
-        // The data bounding box is in Web Mercator; reproject its corners back to WGS84
+        // The data bounding box is in the tile matrix set CRS; reproject its corners back to WGS84
         // so TileJSON advertises the area covered. An empty source has no bounds.
-        let tilejson = if let Some(bounds) = bounds {
-            let (min_lng, min_lat) = webmercator_to_wgs84(bounds.min().x, bounds.min().y);
-            let (max_lng, max_lat) = webmercator_to_wgs84(bounds.max().x, bounds.max().y);
+        let wgs84_bounds = bounds.and_then(|bounds| {
+            let min = tms.crs_to_wgs84(bounds.min().x, bounds.min().y)?;
+            let max = tms.crs_to_wgs84(bounds.max().x, bounds.max().y)?;
+            Some((min, max))
+        });
+        let tilejson = if let Some(((min_lng, min_lat), (max_lng, max_lat))) = wgs84_bounds {
             tilejson::tilejson! {
                 tiles: vec![],
                 vector_layers: vec![layer],
@@ -113,8 +126,10 @@ impl GeoJsonSource {
             tilejson,
             tile_info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
             cache_zoom,
+            tms,
             extent,
             buffer,
+            last_modified,
         })
     }
 }
@@ -145,6 +160,11 @@ impl Source for GeoJsonSource {
     fn clone_source(&self) -> BoxedSource {
         Box::new(self.clone())
     }
+
+    fn get_tile_matrix_set(&self) -> &TileMatrixSet {
+        &self.tms
+    }
+
     fn get_version(&self) -> Option<String> {
         self.tilejson.version.clone()
     }
@@ -153,6 +173,10 @@ impl Source for GeoJsonSource {
         true
     }
 
+    fn get_last_modified(&self) -> Option<DateTime<Utc>> {
+        self.last_modified
+    }
+
     fn cache_zoom(&self) -> CacheZoomRange {
         self.cache_zoom
     }
@@ -162,7 +186,9 @@ impl Source for GeoJsonSource {
         xyz: TileCoord,
         _url_query: Option<&UrlQuery>,
     ) -> MartinCoreResult<TileData> {
-        let mut rect = Rect::from_xyz(xyz.x, xyz.y, xyz.z, self.extent, self.buffer);
+        let Some(mut rect) = Rect::from_xyz(&self.tms, xyz, self.extent, self.buffer) else {
+            return Ok(Vec::new());
+        };
         rect.add_buffer();
 
         let indices = self
@@ -302,6 +328,7 @@ mod tests {
             "test-source-1".to_owned(),
             path,
             CacheZoomRange::default(),
+            Arc::new(TileMatrixSet::web_mercator_quad()),
             extent,
             64,
         )
@@ -343,6 +370,7 @@ mod tests {
             "bare".to_owned(),
             path,
             CacheZoomRange::default(),
+            Arc::new(TileMatrixSet::web_mercator_quad()),
             extent,
             64,
         )
@@ -369,6 +397,7 @@ mod tests {
             "test-source-1".to_owned(),
             path,
             CacheZoomRange::default(),
+            Arc::new(TileMatrixSet::web_mercator_quad()),
             extent,
             64,
         )
@@ -396,6 +425,7 @@ mod tests {
             "props".to_owned(),
             path,
             CacheZoomRange::default(),
+            Arc::new(TileMatrixSet::web_mercator_quad()),
             extent,
             64,
         )
@@ -433,6 +463,7 @@ mod tests {
             "empty".to_owned(),
             path,
             CacheZoomRange::default(),
+            Arc::new(TileMatrixSet::web_mercator_quad()),
             extent,
             64,
         )
@@ -453,6 +484,7 @@ mod tests {
     fn empty_feature_collection_has_no_bounds() {
         // No feature contributes a geometry, so there is no extent to advertise.
         let geojson = r#"{"type":"FeatureCollection","features":[]}"#.parse::<GeoJson>().unwrap();
-        assert!(preprocess_geojson(geojson).unwrap().bounds.is_none());
+        let tms = TileMatrixSet::web_mercator_quad_ref();
+        assert!(preprocess_geojson(geojson, tms).unwrap().bounds.is_none());
     }
 }
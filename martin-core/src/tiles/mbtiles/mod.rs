@@ -1,5 +1,11 @@
 mod error;
 pub use error::MbtilesError;
 
+mod patches;
+pub use patches::{PatchChain, PatchFeed, PatchFeeds, PatchInfo};
+
 mod source;
 pub use source::MbtSource;
+
+mod writer;
+pub use writer::{MbtWriter, MbtWriters};
@@ -0,0 +1,351 @@
+//! Update feeds serving `MBTiles` patch files, as created by `mbtiles diff`, to clients.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+use mbtiles::Mbtiles;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::tiles::mbtiles::MbtilesError;
+
+/// A patch file which turns a tileset with one `agg_tiles_hash` into one with another.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PatchInfo {
+    /// File name of the patch, relative to the patches directory
+    pub file: String,
+    /// `agg_tiles_hash` of the tileset the patch applies to
+    pub before: String,
+    /// `agg_tiles_hash` of the tileset after applying the patch
+    pub after: String,
+    /// `whole`, `bin-diff-gz` or `bin-diff-raw`
+    pub patch_type: String,
+    /// Size of the patch file in bytes
+    pub size: u64,
+}
+
+/// Patches to apply in order to get from one `agg_tiles_hash` to another.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PatchChain {
+    /// `agg_tiles_hash` of the client's copy
+    pub from: String,
+    /// `agg_tiles_hash` of the served file
+    pub to: String,
+    /// Patches to apply in order, empty if the copy is up to date
+    pub patches: Vec<PatchInfo>,
+}
+
+/// How long the index of the patches directory and the `agg_tiles_hash` of the source are reused
+/// at most. They are refreshed sooner if the directory or the file is modified.
+const INDEX_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct IndexedFile {
+    modified: Option<SystemTime>,
+    size: u64,
+    patch: Option<PatchInfo>,
+}
+
+#[derive(Debug, Default)]
+struct FeedState {
+    /// Patch files of the last scan, so unchanged files are not opened again
+    files: HashMap<PathBuf, IndexedFile>,
+    /// Valid patches of the last scan, sorted by file name
+    patches: Arc<[PatchInfo]>,
+    /// When the directory was last scanned, and its modification time at that point
+    scanned: Option<(Instant, Option<SystemTime>)>,
+    /// When the `agg_tiles_hash` of the source was read, the file's modification time at that point, and the hash
+    hash: Option<(Instant, SystemTime, Option<String>)>,
+}
+
+/// Index of the patch files of one `MBTiles` source.
+///
+/// The index is rescanned when the patches directory changes, or at least every [`INDEX_TTL`],
+/// and only new or modified files are opened, so patches can be added while the server is running.
+/// The `agg_tiles_hash` of the source is read again under the same conditions.
+#[derive(Debug)]
+pub struct PatchFeed {
+    source_file: PathBuf,
+    patches_dir: PathBuf,
+    state: Mutex<FeedState>,
+}
+
+impl PatchFeed {
+    /// Creates a feed for the `MBTiles` file `source_file`, serving patches from `patches_dir`.
+    #[must_use]
+    pub fn new(source_file: PathBuf, patches_dir: PathBuf) -> Self {
+        Self {
+            source_file,
+            patches_dir,
+            state: Mutex::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, FeedState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The `agg_tiles_hash` of the currently served `MBTiles` file.
+    pub async fn current_hash(&self) -> Result<Option<String>, MbtilesError> {
+        let modified = tokio::fs::metadata(&self.source_file)
+            .await
+            .and_then(|meta| meta.modified())
+            .ok();
+        if let (Some(modified), Some((read_at, read_modified, hash))) =
+            (modified, &self.state().hash)
+            && read_at.elapsed() < INDEX_TTL
+            && modified == *read_modified
+        {
+            return Ok(hash.clone());
+        }
+
+        let read_at = Instant::now();
+        let mbt = Mbtiles::new(&self.source_file)?;
+        let mut conn = mbt.open_readonly().await?;
+        let hash = mbt.get_agg_tiles_hash(&mut conn).await?;
+        if let Some(modified) = modified {
+            self.state().hash = Some((read_at, modified, hash.clone()));
+        }
+        Ok(hash)
+    }
+
+    /// All valid patch files in the patches directory, sorted by file name.
+    pub async fn patches(&self) -> Result<Arc<[PatchInfo]>, MbtilesError> {
+        let io_err = |e| MbtilesError::IoError(e, self.patches_dir.clone());
+        let dir_modified = tokio::fs::metadata(&self.patches_dir)
+            .await
+            .map_err(io_err)?
+            .modified()
+            .ok();
+        {
+            let state = self.state();
+            if let Some((scanned_at, scanned_modified)) = state.scanned
+                && scanned_at.elapsed() < INDEX_TTL
+                && dir_modified.is_some()
+                && scanned_modified == dir_modified
+            {
+                return Ok(Arc::clone(&state.patches));
+            }
+        }
+
+        let scanned_at = Instant::now();
+        let mut files = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.patches_dir)
+            .await
+            .map_err(io_err)?;
+        while let Some(entry) = dir.next_entry().await.map_err(io_err)? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "mbtiles") {
+                continue;
+            }
+            let meta = entry.metadata().await.map_err(io_err)?;
+            if meta.is_file() {
+                files.push((path, meta.modified().ok(), meta.len()));
+            }
+        }
+
+        let mut index = HashMap::with_capacity(files.len());
+        for (path, modified, size) in files {
+            let cached = self.state().files.remove(&path);
+            let file = match cached {
+                Some(file) if file.modified == modified && file.size == size => file,
+                _ => IndexedFile {
+                    modified,
+                    size,
+                    patch: read_patch_info(&path, size).await,
+                },
+            };
+            index.insert(path, file);
+        }
+
+        let mut patches: Vec<_> = index.values().filter_map(|f| f.patch.clone()).collect();
+        patches.sort_by(|a, b| a.file.cmp(&b.file));
+        let patches: Arc<[PatchInfo]> = Arc::from(patches);
+        let mut state = self.state();
+        state.files = index;
+        state.patches = Arc::clone(&patches);
+        state.scanned = Some((scanned_at, dir_modified));
+        Ok(patches)
+    }
+
+    /// The shortest chain of patches from `from_hash` to the currently served file.
+    ///
+    /// Returns `None` if the patches cannot get a tileset with `from_hash` there,
+    /// e.g. because the client is older than the oldest patch.
+    pub async fn chain(&self, from_hash: &str) -> Result<Option<PatchChain>, MbtilesError> {
+        let Some(to_hash) = self.current_hash().await? else {
+            warn!(
+                "MBTiles file {} has no agg_tiles_hash, no patches can be served for it",
+                self.source_file.display()
+            );
+            return Ok(None);
+        };
+        let patches = self.patches().await?;
+        Ok(
+            find_chain(&patches, from_hash, &to_hash).map(|chain| PatchChain {
+                from: from_hash.to_owned(),
+                to: to_hash,
+                patches: chain.into_iter().cloned().collect(),
+            }),
+        )
+    }
+
+    /// The path of the patch file `file`, if it is a valid patch in the patches directory.
+    pub async fn patch_path(&self, file: &str) -> Result<Option<PathBuf>, MbtilesError> {
+        let patches = self.patches().await?;
+        Ok(patches
+            .iter()
+            .any(|p| p.file == file)
+            .then(|| self.patches_dir.join(file)))
+    }
+}
+
+async fn read_patch_info(path: &Path, size: u64) -> Option<PatchInfo> {
+    let info = async {
+        let mbt = Mbtiles::new(path)?;
+        let mut conn = mbt.open_readonly().await?;
+        mbt.examine_diff(&mut conn).await
+    }
+    .await;
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            warn!("Ignoring patch file {}: {e}", path.display());
+            return None;
+        }
+    };
+    let (Some(before), Some(after)) = (
+        info.agg_tiles_hash_before_apply,
+        info.agg_tiles_hash_after_apply,
+    ) else {
+        warn!(
+            "Ignoring {} because it is not a patch file with agg_tiles_hash_before_apply and agg_tiles_hash_after_apply metadata",
+            path.display()
+        );
+        return None;
+    };
+    Some(PatchInfo {
+        file: path.file_name()?.to_str()?.to_owned(),
+        before,
+        after,
+        patch_type: info
+            .patch_type
+            .map_or_else(|| "whole".to_owned(), |t| t.to_string()),
+        size,
+    })
+}
+
+/// Finds the shortest chain of patches from `from` to `to`, preferring earlier patches on ties.
+fn find_chain<'a>(patches: &'a [PatchInfo], from: &str, to: &str) -> Option<Vec<&'a PatchInfo>> {
+    let mut previous: HashMap<&str, &PatchInfo> = HashMap::new();
+    let mut visited = HashSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(hash) = queue.pop_front() {
+        if hash == to {
+            let mut chain = Vec::new();
+            let mut hash = to;
+            while hash != from {
+                let patch = previous.get(hash)?;
+                chain.push(*patch);
+                hash = patch.before.as_str();
+            }
+            chain.reverse();
+            return Some(chain);
+        }
+        for patch in patches.iter().filter(|p| p.before == hash) {
+            if visited.insert(patch.after.as_str()) {
+                previous.insert(patch.after.as_str(), patch);
+                queue.push_back(patch.after.as_str());
+            }
+        }
+    }
+    None
+}
+
+/// Patch feeds of all `MBTiles` sources that have a patches directory, by source ID.
+#[derive(Debug, Clone, Default)]
+pub struct PatchFeeds(Arc<HashMap<String, PatchFeed>>);
+
+impl PatchFeeds {
+    /// Creates the patch feeds from a map of source IDs to feeds.
+    #[must_use]
+    pub fn new(feeds: HashMap<String, PatchFeed>) -> Self {
+        Self(Arc::new(feeds))
+    }
+
+    /// The patch feed of the source `source_id`.
+    #[must_use]
+    pub fn get(&self, source_id: &str) -> Option<&PatchFeed> {
+        self.0.get(source_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(file: &str, before: &str, after: &str) -> PatchInfo {
+        PatchInfo {
+            file: file.to_owned(),
+            before: before.to_owned(),
+            after: after.to_owned(),
+            patch_type: "whole".to_owned(),
+            size: 0,
+        }
+    }
+
+    fn files(chain: Option<Vec<&PatchInfo>>) -> Option<Vec<&str>> {
+        chain.map(|c| c.iter().map(|p| p.file.as_str()).collect())
+    }
+
+    #[test]
+    fn finds_chain() {
+        let patches = [
+            patch("1-2", "h1", "h2"),
+            patch("2-3", "h2", "h3"),
+            patch("3-4", "h3", "h4"),
+        ];
+        assert_eq!(
+            files(find_chain(&patches, "h1", "h4")),
+            Some(vec!["1-2", "2-3", "3-4"])
+        );
+        assert_eq!(files(find_chain(&patches, "h3", "h4")), Some(vec!["3-4"]));
+        assert_eq!(files(find_chain(&patches, "h4", "h4")), Some(vec![]));
+        assert_eq!(files(find_chain(&patches, "h0", "h4")), None);
+        assert_eq!(files(find_chain(&patches, "h4", "h1")), None);
+    }
+
+    #[test]
+    fn prefers_squashed_patches() {
+        let patches = [
+            patch("1-2", "h1", "h2"),
+            patch("1-3", "h1", "h3"),
+            patch("2-3", "h2", "h3"),
+            patch("3-4", "h3", "h4"),
+        ];
+        assert_eq!(
+            files(find_chain(&patches, "h1", "h4")),
+            Some(vec!["1-3", "3-4"])
+        );
+        assert_eq!(
+            files(find_chain(&patches, "h2", "h4")),
+            Some(vec!["2-3", "3-4"])
+        );
+    }
+
+    #[test]
+    fn ignores_cycles() {
+        let patches = [
+            patch("1-2", "h1", "h2"),
+            patch("2-1", "h2", "h1"),
+            patch("2-3", "h2", "h3"),
+        ];
+        assert_eq!(
+            files(find_chain(&patches, "h1", "h3")),
+            Some(vec!["1-2", "2-3"])
+        );
+        assert_eq!(files(find_chain(&patches, "h1", "h4")), None);
+    }
+}
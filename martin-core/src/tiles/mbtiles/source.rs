@@ -7,8 +7,10 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use backon::{FibonacciBuilder, Retryable as _};
+use chrono::{DateTime, Utc};
 use derive_debug::Dbg;
 use martin_tile_utils::{TileCoord, TileData, TileInfo};
+use mbtiles::sqlx;
 use mbtiles::sqlx::error::DatabaseError;
 use mbtiles::{MbtError, MbtilesPool};
 use tilejson::TileJSON;
@@ -16,7 +18,7 @@ use tracing::{trace, warn};
 
 use crate::CacheZoomRange;
 use crate::tiles::mbtiles::MbtilesError;
-use crate::tiles::{BoxedSource, MartinCoreResult, Source, UrlQuery};
+use crate::tiles::{BoxedSource, MartinCoreResult, Source, UrlQuery, file_modified};
 
 /// Tile source that reads from `MBTiles` files.
 #[derive(Clone, Dbg)]
@@ -30,6 +32,11 @@ pub struct MbtSource {
     tile_info: TileInfo,
     #[dbg(skip)]
     cache_zoom: CacheZoomRange,
+    #[dbg(skip)]
+    last_modified: Option<DateTime<Utc>>,
+    /// The file, if its modification time is read on every request
+    #[dbg(skip)]
+    live_modified: Option<PathBuf>,
 }
 
 // SQLITE_BUSY (code: 5)
@@ -79,6 +86,7 @@ impl MbtSource {
             .await
             .map_err(|e| MbtilesError::InvalidMetadata(e.to_string(), path.clone()))?;
 
+        let last_modified = file_modified(&path);
         let tile_info = mbt
             .detect_format(&meta.tilejson)
             .await
@@ -91,8 +99,18 @@ impl MbtSource {
             tilejson: meta.tilejson,
             tile_info,
             cache_zoom,
+            last_modified,
+            live_modified: None,
         })
     }
+
+    /// Reads the modification time of the file on every request instead of once,
+    /// because the file changes while it is served, e.g. by tile writes.
+    #[must_use]
+    pub fn with_live_modified(mut self, path: PathBuf) -> Self {
+        self.live_modified = Some(path);
+        self
+    }
 }
 
 #[async_trait]
@@ -122,10 +140,27 @@ impl Source for MbtSource {
         false
     }
 
+    fn get_last_modified(&self) -> Option<DateTime<Utc>> {
+        match &self.live_modified {
+            Some(path) => file_modified(path),
+            None => self.last_modified,
+        }
+    }
+
     fn cache_zoom(&self) -> CacheZoomRange {
         self.cache_zoom
     }
 
+    async fn check_health(&self) -> MartinCoreResult<()> {
+        match self.mbtiles.get_metadata().await {
+            Ok(_) => Ok(()),
+            Err(e @ MbtError::SqlxError(sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed)) => {
+                Err(MbtilesError::AcquireConnError(self.id.clone(), Box::new(e)).into())
+            }
+            Err(e) => Err(MbtilesError::MbtilesLibraryError(e).into()),
+        }
+    }
+
     async fn get_tile(
         &self,
         xyz: TileCoord,
@@ -0,0 +1,186 @@
+//! Writing single tiles into `MBTiles` sources, e.g. to replace tiles with corrected versions.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use derive_debug::Dbg;
+use martin_tile_utils::TileCoord;
+use mbtiles::MbtilesPool;
+use tracing::{debug, warn};
+
+use crate::tiles::mbtiles::MbtilesError;
+
+/// How long after a write the `agg_tiles_hash` is recomputed,
+/// so that a batch of writes recomputes it once instead of after every tile.
+const HASH_UPDATE_DELAY: Duration = Duration::from_secs(5);
+
+/// Read-write connection to the file of an `MBTiles` source.
+///
+/// Writes go straight into the file, so the source serves them without being reloaded.
+/// The `agg_tiles_hash` of the file is removed by every write, and recomputed
+/// a few seconds after the first write of a batch.
+#[derive(Clone, Debug)]
+pub struct MbtWriter {
+    path: PathBuf,
+    pool: MbtilesPool,
+    /// Whether a recomputation of `agg_tiles_hash` is scheduled
+    hash_update_scheduled: Arc<AtomicBool>,
+    hash_update_delay: Duration,
+}
+
+impl MbtWriter {
+    /// Opens the `MBTiles` file `path` for writing.
+    pub async fn open(path: PathBuf) -> Result<Self, MbtilesError> {
+        let pool = MbtilesPool::open_readwrite(&path)
+            .await
+            .map_err(|e| MbtilesError::AcquireConnError(path.display().to_string(), Box::new(e)))?;
+        Ok(Self {
+            path,
+            pool,
+            hash_update_scheduled: Arc::default(),
+            hash_update_delay: HASH_UPDATE_DELAY,
+        })
+    }
+
+    /// The path of the `MBTiles` file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the tile `xyz`, replacing any existing tile with these coordinates.
+    pub async fn put_tile(&self, xyz: TileCoord, data: &[u8]) -> Result<(), MbtilesError> {
+        self.pool.put_tile(xyz.z, xyz.x, xyz.y, data).await?;
+        self.schedule_hash_update();
+        Ok(())
+    }
+
+    /// Deletes the tile `xyz`, and returns `false` if there was no such tile.
+    pub async fn delete_tile(&self, xyz: TileCoord) -> Result<bool, MbtilesError> {
+        let deleted = self.pool.delete_tile(xyz.z, xyz.x, xyz.y).await?;
+        if deleted {
+            self.schedule_hash_update();
+        }
+        Ok(deleted)
+    }
+
+    /// Recomputes the `agg_tiles_hash` of the file right away, reading all of its tiles.
+    pub async fn update_agg_tiles_hash(&self) -> Result<String, MbtilesError> {
+        Ok(self.pool.update_agg_tiles_hash().await?)
+    }
+
+    /// Recomputes `agg_tiles_hash` after [`HASH_UPDATE_DELAY`], unless that is already scheduled.
+    /// Writes during the delay are included in the new hash.
+    fn schedule_hash_update(&self) {
+        if self.hash_update_scheduled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let writer = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(writer.hash_update_delay).await;
+            // Writes from now on need another update
+            writer.hash_update_scheduled.store(false, Ordering::Release);
+            match writer.update_agg_tiles_hash().await {
+                Ok(hash) => debug!(
+                    "Updated agg_tiles_hash of {} to {hash}",
+                    writer.path.display()
+                ),
+                Err(e) => warn!(
+                    "Failed to update agg_tiles_hash of {}: {e}",
+                    writer.path.display()
+                ),
+            }
+        });
+    }
+}
+
+/// Writers of the `MBTiles` sources which accept tile writes, by source ID,
+/// together with the token that authorizes the writes.
+#[derive(Clone, Default, Dbg)]
+pub struct MbtWriters {
+    writers: Arc<HashMap<String, MbtWriter>>,
+    #[dbg(skip)]
+    token: Arc<str>,
+}
+
+impl MbtWriters {
+    /// Creates the writers from a map of source IDs to writers, and the token of the writes.
+    #[must_use]
+    pub fn new(writers: HashMap<String, MbtWriter>, token: &str) -> Self {
+        Self {
+            writers: Arc::new(writers),
+            token: token.into(),
+        }
+    }
+
+    /// The writer of the source `source_id`, if it accepts tile writes.
+    #[must_use]
+    pub fn get(&self, source_id: &str) -> Option<&MbtWriter> {
+        self.writers.get(source_id)
+    }
+
+    /// Whether `token` is the token of the writes.
+    ///
+    /// Compares in constant time, so that response times do not reveal the token.
+    /// An empty token never authorizes writes.
+    #[must_use]
+    pub fn is_authorized(&self, token: &str) -> bool {
+        let (expected, actual) = (self.token.as_bytes(), token.as_bytes());
+        !expected.is_empty()
+            && expected.len() == actual.len()
+            && expected
+                .iter()
+                .zip(actual)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mbtiles::{MbtType, Mbtiles, init_mbtiles_schema};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn updates_hash_once_per_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tiles.mbtiles");
+        let mbt = Mbtiles::new(&path).unwrap();
+        let mut conn = mbt.open_or_new().await.unwrap();
+        init_mbtiles_schema(&mut conn, MbtType::Flat, true)
+            .await
+            .unwrap();
+        mbt.update_agg_tiles_hash(&mut conn).await.unwrap();
+
+        let mut writer = MbtWriter::open(path).await.unwrap();
+        writer.hash_update_delay = Duration::from_millis(50);
+        for x in 0..2 {
+            writer
+                .put_tile(TileCoord { z: 1, x, y: 0 }, b"tile")
+                .await
+                .unwrap();
+        }
+        // The stale hash is removed right away
+        assert_eq!(mbt.get_agg_tiles_hash(&mut conn).await.unwrap(), None);
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(mbt.get_agg_tiles_hash(&mut conn).await.unwrap().is_some());
+        mbt.check_agg_tiles_hashes(&mut conn).await.unwrap();
+    }
+
+    #[test]
+    fn authorizes_token() {
+        let writers = MbtWriters::new(HashMap::new(), "secret");
+        assert!(writers.is_authorized("secret"));
+        assert!(!writers.is_authorized("secreT"));
+        assert!(!writers.is_authorized("secret2"));
+        assert!(!writers.is_authorized(""));
+
+        let writers = MbtWriters::default();
+        assert!(!writers.is_authorized(""));
+    }
+}
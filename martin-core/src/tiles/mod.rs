@@ -38,17 +38,22 @@ pub mod duckdb;
 pub mod passthrough;
 
 mod source;
+#[cfg(any(feature = "mbtiles", feature = "unstable-cog", feature = "geojson"))]
+pub(crate) use source::file_modified;
 pub use source::{BoxedSource, Source, UrlQuery};
 
 mod error;
 pub use error::{MartinCoreError, MartinCoreResult};
 
 mod tile;
-pub use tile::Tile;
+pub use tile::{Tile, TileCacheHints};
 
 mod cache;
 pub use cache::{NO_TILE_CACHE, OptTileCache, TileCache, TileCacheKey};
 
+mod coalesce;
+pub use coalesce::{SharedTileResult, TileFlights};
+
 #[cfg(feature = "geojson")]
 /// Implementation of `GeoJSON`' [`Source`]
 pub mod geojson;
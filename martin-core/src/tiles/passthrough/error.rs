@@ -1,5 +1,11 @@
 //! Error types for the `passthrough` HTTP upstream source.
 
+use std::sync::Arc;
+
+use martin_tile_utils::Format;
+
+use crate::tiles::passthrough::{MAX_METATILE, MAX_METATILE_PIXELS, MAX_TILE_SIZE};
+
 /// Errors that can occur when proxying tiles from an upstream HTTP tile server.
 #[non_exhaustive]
 #[derive(thiserror::Error, Debug)]
@@ -83,4 +89,115 @@ pub enum PassthroughError {
         /// The HTTP status code returned by the upstream.
         status: u16,
     },
+
+    /// Every upstream of the source failed repeatedly, so requests to them fail fast for a while.
+    #[error("Circuit breaker of upstream {0} is open after repeated failures")]
+    CircuitOpen(String),
+
+    /// A configured WMS or `ArcGIS` REST service URL could not be parsed.
+    #[error("Invalid map service URL {url}: {source}")]
+    InvalidServiceUrl {
+        /// The URL as written in the config.
+        url: String,
+        /// Why the URL was rejected.
+        #[source]
+        source: url::ParseError,
+    },
+
+    /// The image format cannot be requested from the map service.
+    #[error(
+        "Map service upstreams cannot request {0} tiles, use png or jpeg (or webp for WMS) as format"
+    )]
+    UnsupportedServiceFormat(Format),
+
+    /// The configured tile size of a map service is out of range.
+    #[error(
+        "Invalid map service tile size {0}, it must be between 1 and {max}",
+        max = MAX_TILE_SIZE
+    )]
+    InvalidTileSize(u32),
+
+    /// The configured metatile size is out of range.
+    #[error("Invalid metatile size {0}, it must be between 1 and {max}", max = MAX_METATILE)]
+    InvalidMetatile(u32),
+
+    /// The metatile images would be too large.
+    #[error(
+        "Metatiles of {metatile} tiles of {tile_size} pixels are too large, the metatile image may be at most {max} pixels wide",
+        max = MAX_METATILE_PIXELS
+    )]
+    MetatileTooLarge {
+        /// The configured tiles per metatile side.
+        metatile: u32,
+        /// The configured tile size.
+        tile_size: u32,
+    },
+
+    /// A map service answered with something else than an image, usually an error report.
+    #[error("Map service {url} returned {content_type} instead of an image: {message}")]
+    ServiceException {
+        /// The image URL that was requested.
+        url: String,
+        /// The `Content-Type` of the response.
+        content_type: String,
+        /// The beginning of the response body.
+        message: String,
+    },
+
+    /// A metatile image could not be decoded or a tile cut from it could not be encoded.
+    #[error("Failed to process metatile image: {0}")]
+    Image(#[from] image::ImageError),
+
+    /// Decoding or encoding a metatile image panicked or was cancelled.
+    #[error("Metatile image processing failed: {0}")]
+    ImageTask(#[from] tokio::task::JoinError),
+
+    /// A metatile image does not have the requested size.
+    #[error(
+        "Metatile image is {}x{} pixels instead of {}x{}",
+        actual.0, actual.1, expected.0, expected.1
+    )]
+    UnexpectedImageSize {
+        /// The requested width and height.
+        expected: (u32, u32),
+        /// The width and height of the received image.
+        actual: (u32, u32),
+    },
+
+    /// The failure of a metatile fetch, shared by all the tiles requested meanwhile.
+    #[error(transparent)]
+    Shared(Arc<Self>),
+}
+
+impl PassthroughError {
+    /// Whether the failure is likely transient: a network error, a timeout, a server error, or
+    /// rate limiting. Only these are retried, failed over to mirrors, and counted by the
+    /// circuit breakers.
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Http(e) => !e.is_builder(),
+            Self::UnexpectedStatus { status, .. } => *status >= 500 || matches!(status, 408 | 429),
+            Self::Shared(e) => e.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_statuses() {
+        let status = |status| PassthroughError::UnexpectedStatus {
+            url: String::new(),
+            status,
+        };
+        assert!(status(500).is_transient());
+        assert!(status(503).is_transient());
+        assert!(status(429).is_transient());
+        assert!(!status(403).is_transient());
+        assert!(!PassthroughError::CircuitOpen(String::new()).is_transient());
+    }
 }
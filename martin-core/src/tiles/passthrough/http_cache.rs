@@ -0,0 +1,181 @@
+//! HTTP caching semantics of upstream responses: freshness from `Cache-Control`/`Expires`,
+//! and the `ETag`/`Last-Modified` validators used to revalidate a kept tile.
+//!
+//! Everything here is side-effect free so it can be unit-tested without a network.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::RequestBuilder;
+use reqwest::header::{
+    AGE, CACHE_CONTROL, ETAG, EXPIRES, HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+
+use crate::tiles::TileCacheHints;
+
+/// Longest freshness lifetime, larger values are capped as required by RFC 9111.
+const MAX_AGE_SECS: i64 = 2_147_483_648;
+
+/// The raw `ETag` and `Last-Modified` headers of a response, sent back to the upstream as
+/// `If-None-Match` and `If-Modified-Since` to revalidate the response.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Validators {
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+}
+
+impl Validators {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        Self {
+            etag: header(headers, ETAG).map(str::to_owned),
+            last_modified: header(headers, LAST_MODIFIED).map(str::to_owned),
+        }
+    }
+
+    /// Makes the request conditional on the response having changed.
+    pub(crate) fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, modified);
+        }
+        request
+    }
+
+    /// Validators of a response that was revalidated with a `304 Not Modified`,
+    /// which only repeats the validators that changed.
+    pub(crate) fn refreshed(&self, not_modified: Self) -> Self {
+        Self {
+            etag: not_modified.etag.or_else(|| self.etag.clone()),
+            last_modified: not_modified
+                .last_modified
+                .or_else(|| self.last_modified.clone()),
+        }
+    }
+}
+
+/// Freshness and modification time of a response, as received at `now`.
+///
+/// Martin is a shared cache, so `s-maxage` takes precedence over `max-age`, and
+/// `private`, `no-cache` and `no-store` responses are stale right away.
+/// `max-age` takes precedence over `Expires`, and an invalid `Expires` means already expired.
+/// Lifetimes are capped at [`MAX_AGE_SECS`].
+pub(crate) fn cache_hints(headers: &HeaderMap, now: DateTime<Utc>) -> TileCacheHints {
+    TileCacheHints {
+        fresh_until: fresh_until(headers, now),
+        last_modified: header(headers, LAST_MODIFIED).and_then(parse_http_date),
+    }
+}
+
+fn fresh_until(headers: &HeaderMap, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let mut max_age = None;
+    let mut s_maxage = None;
+    for directive in headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+    {
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+        if ["no-store", "no-cache", "private"]
+            .iter()
+            .any(|d| name.eq_ignore_ascii_case(d))
+        {
+            return Some(now);
+        }
+        let seconds = value.and_then(|v| v.parse::<i64>().ok());
+        if name.eq_ignore_ascii_case("s-maxage") {
+            s_maxage = seconds;
+        } else if name.eq_ignore_ascii_case("max-age") {
+            max_age = seconds;
+        }
+    }
+
+    if let Some(seconds) = s_maxage.or(max_age) {
+        let age = header(headers, AGE)
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        let remaining = seconds.min(MAX_AGE_SECS).saturating_sub(age).max(0);
+        return Some(now + TimeDelta::seconds(remaining));
+    }
+    let expires = header(headers, EXPIRES)?;
+    let latest = now + TimeDelta::seconds(MAX_AGE_SECS);
+    Some(parse_http_date(expires).map_or(now, |expires| expires.min(latest)))
+}
+
+/// Parse an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<&str> {
+    let value = headers.get(name)?;
+    value.to_str().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn now() -> DateTime<Utc> {
+        parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap()
+    }
+
+    #[rstest]
+    #[case::none(&[], None)]
+    #[case::max_age(&[("cache-control", "public, max-age=600")], Some(600))]
+    #[case::s_maxage_wins(&[("cache-control", "max-age=600, s-maxage=60")], Some(60))]
+    #[case::age_is_subtracted(&[("cache-control", "max-age=600"), ("age", "100")], Some(500))]
+    #[case::too_old(&[("cache-control", "max-age=60"), ("age", "100")], Some(0))]
+    #[case::no_cache(&[("cache-control", "max-age=600"), ("cache-control", "no-cache")], Some(0))]
+    #[case::no_store(&[("cache-control", "No-Store")], Some(0))]
+    #[case::expires(&[("expires", "Wed, 21 Oct 2015 07:38:00 GMT")], Some(600))]
+    #[case::max_age_wins(
+        &[("cache-control", "max-age=60"), ("expires", "Wed, 21 Oct 2015 07:38:00 GMT")],
+        Some(60)
+    )]
+    #[case::invalid_expires(&[("expires", "0")], Some(0))]
+    #[case::capped(&[("cache-control", "max-age=99999999999")], Some(MAX_AGE_SECS))]
+    fn freshness(#[case] pairs: &[(&'static str, &'static str)], #[case] secs: Option<i64>) {
+        let hints = cache_hints(&headers(pairs), now());
+        assert_eq!(
+            hints.fresh_until,
+            secs.map(|s| now() + TimeDelta::seconds(s))
+        );
+    }
+
+    #[test]
+    fn last_modified() {
+        let hints = cache_hints(
+            &headers(&[("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")]),
+            now(),
+        );
+        assert_eq!(hints.last_modified, Some(now()));
+    }
+
+    #[test]
+    fn refreshed_validators() {
+        let kept = Validators::from_headers(&headers(&[
+            ("etag", "\"a\""),
+            ("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+        ]));
+        let refreshed = kept.refreshed(Validators::from_headers(&headers(&[("etag", "\"b\"")])));
+        assert_eq!(refreshed.etag.as_deref(), Some("\"b\""));
+        assert_eq!(refreshed.last_modified, kept.last_modified);
+    }
+}
@@ -0,0 +1,574 @@
+//! WMS and `ArcGIS` REST upstreams: each tile (or metatile of several tiles) is requested as an
+//! image of its `EPSG:3857` bounding box, and metatile images are cut back into tiles.
+
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use image::{DynamicImage, GenericImageView as _, ImageFormat};
+use martin_tile_utils::{Format, TileCoord, TileInfo, xyz_to_webmercator_bbox};
+use moka::Expiry;
+use moka::future::Cache;
+use url::Url;
+
+use crate::tiles::passthrough::http_cache::Validators;
+use crate::tiles::passthrough::resilience::FetchedTile;
+use crate::tiles::passthrough::{PassthroughError, TemplateMeta};
+
+/// Largest number of tiles per metatile side.
+pub const MAX_METATILE: u32 = 16;
+
+/// Largest tile width and height in pixels.
+pub const MAX_TILE_SIZE: u32 = 4096;
+
+/// Largest metatile image width and height in pixels, i.e. `metatile` × `tile_size`.
+pub const MAX_METATILE_PIXELS: u32 = 8192;
+
+/// How long the tiles cut from a metatile wait for their own requests.
+const METATILE_TTL: Duration = Duration::from_mins(1);
+
+/// Total size of the tiles cut from metatiles kept per source.
+const METATILE_CACHE_BYTES: u64 = 32 * 1000 * 1000;
+
+/// The kind of map service an upstream is, with its service-specific request parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapService {
+    /// An OGC Web Map Service, requested with `GetMap`.
+    Wms {
+        /// Layers to render, in drawing order.
+        layers: Vec<String>,
+        /// Styles of the layers, empty for the default styles.
+        styles: Vec<String>,
+        /// WMS version, e.g. `1.3.0` or `1.1.1`.
+        version: String,
+    },
+    /// An `ArcGIS` REST `MapServer`, requested with `export`.
+    ArcGis {
+        /// The `layers` parameter verbatim, e.g. `show:0,2`. All visible layers if `None`.
+        layers: Option<String>,
+    },
+}
+
+/// Image request parameters shared by all map services.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapServiceParams {
+    /// The service and its specific parameters.
+    pub service: MapService,
+    /// Requested image format, `png`, `jpeg` or `webp` (WMS only).
+    pub format: Format,
+    /// Whether to request a transparent background.
+    pub transparent: bool,
+    /// Width and height of a tile in pixels.
+    pub tile_size: u32,
+    /// Tiles per side requested at once, `1` disables metatiling.
+    pub metatile: u32,
+}
+
+/// A non-empty set of map service URLs (mirrors of the same service) and their request
+/// parameters and metadata.
+#[derive(Clone, Debug)]
+pub struct MapServiceSet {
+    urls: Vec<String>,
+    params: MapServiceParams,
+    meta: TemplateMeta,
+}
+
+impl MapServiceSet {
+    /// Build a map service set, validating the URLs and parameters.
+    pub fn new(
+        urls: Vec<String>,
+        params: MapServiceParams,
+        meta: TemplateMeta,
+    ) -> Result<Self, PassthroughError> {
+        if urls.is_empty() {
+            return Err(PassthroughError::EmptyUrlList);
+        }
+        for url in &urls {
+            Url::parse(url).map_err(|source| PassthroughError::InvalidServiceUrl {
+                url: url.clone(),
+                source,
+            })?;
+        }
+        let supported = match params.service {
+            MapService::Wms { .. } => [Format::Png, Format::Jpeg, Format::Webp].as_slice(),
+            MapService::ArcGis { .. } => [Format::Png, Format::Jpeg].as_slice(),
+        };
+        if !supported.contains(&params.format) {
+            return Err(PassthroughError::UnsupportedServiceFormat(params.format));
+        }
+        if !(1..=MAX_TILE_SIZE).contains(&params.tile_size) {
+            return Err(PassthroughError::InvalidTileSize(params.tile_size));
+        }
+        if !(1..=MAX_METATILE).contains(&params.metatile) {
+            return Err(PassthroughError::InvalidMetatile(params.metatile));
+        }
+        if params.metatile * params.tile_size > MAX_METATILE_PIXELS {
+            return Err(PassthroughError::MetatileTooLarge {
+                metatile: params.metatile,
+                tile_size: params.tile_size,
+            });
+        }
+        Ok(Self { urls, params, meta })
+    }
+
+    /// The configured service URLs, guaranteed non-empty.
+    #[must_use]
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// The request parameters.
+    #[must_use]
+    pub const fn params(&self) -> &MapServiceParams {
+        &self.params
+    }
+
+    /// The operator-declared metadata.
+    #[must_use]
+    pub const fn meta(&self) -> &TemplateMeta {
+        &self.meta
+    }
+
+    /// The image request of `area` to the service at `base`.
+    pub(crate) fn request_url(
+        &self,
+        base: &str,
+        area: MetaTile,
+    ) -> Result<String, PassthroughError> {
+        let mut url = Url::parse(base).map_err(|source| PassthroughError::InvalidServiceUrl {
+            url: base.to_owned(),
+            source,
+        })?;
+        let bbox = area.webmercator_bbox().map(|v| v.to_string()).join(",");
+        let width = (area.cols * self.params.tile_size).to_string();
+        let height = (area.rows * self.params.tile_size).to_string();
+        let format = self.params.format;
+        let transparent = self.params.transparent;
+
+        match &self.params.service {
+            MapService::Wms {
+                layers,
+                styles,
+                version,
+            } => {
+                // WMS 1.3.0 renamed SRS to CRS
+                let crs = if version.starts_with("1.1") || version.starts_with("1.0") {
+                    "SRS"
+                } else {
+                    "CRS"
+                };
+                url.query_pairs_mut()
+                    .append_pair("SERVICE", "WMS")
+                    .append_pair("REQUEST", "GetMap")
+                    .append_pair("VERSION", version)
+                    .append_pair("LAYERS", &layers.join(","))
+                    .append_pair("STYLES", &styles.join(","))
+                    .append_pair(crs, "EPSG:3857")
+                    .append_pair("BBOX", &bbox)
+                    .append_pair("WIDTH", &width)
+                    .append_pair("HEIGHT", &height)
+                    .append_pair("FORMAT", format.content_type())
+                    .append_pair("TRANSPARENT", if transparent { "TRUE" } else { "FALSE" });
+            }
+            MapService::ArcGis { layers } => {
+                if !url.path().ends_with("/export") {
+                    let path = format!("{}/export", url.path().trim_end_matches('/'));
+                    url.set_path(&path);
+                }
+                let image_format = match format {
+                    // png32 is the only PNG variant with a full alpha channel
+                    Format::Png if transparent => "png32",
+                    Format::Jpeg => "jpg",
+                    _ => "png",
+                };
+                let mut query = url.query_pairs_mut();
+                query
+                    .append_pair("bbox", &bbox)
+                    .append_pair("bboxSR", "3857")
+                    .append_pair("imageSR", "3857")
+                    .append_pair("size", &format!("{width},{height}"))
+                    .append_pair("format", image_format)
+                    .append_pair("transparent", if transparent { "true" } else { "false" })
+                    .append_pair("f", "image");
+                if let Some(layers) = layers {
+                    query.append_pair("layers", layers);
+                }
+            }
+        }
+        Ok(url.into())
+    }
+
+    /// Cut a metatile image into the tiles of `area`, in the order of [`MetaTile::tiles`].
+    ///
+    /// An empty image (the service has no data there) becomes empty tiles.
+    /// Decoding and encoding the images runs on the blocking thread pool.
+    pub(crate) async fn split(
+        &self,
+        image: FetchedTile,
+        area: MetaTile,
+    ) -> Result<Vec<FetchedTile>, PassthroughError> {
+        let (format, size) = (self.params.format, self.params.tile_size);
+        tokio::task::spawn_blocking(move || split_image(&image, area, format, size)).await?
+    }
+}
+
+/// Cut a metatile image of `size` pixels large tiles into the tiles of `area`.
+fn split_image(
+    image: &FetchedTile,
+    area: MetaTile,
+    format: Format,
+    size: u32,
+) -> Result<Vec<FetchedTile>, PassthroughError> {
+    let info = TileInfo::from(format);
+    let tile = |data| FetchedTile {
+        data,
+        info,
+        etag: None,
+        hints: image.hints,
+        validators: Validators::default(),
+    };
+    if image.data.is_empty() {
+        return Ok(area.tiles().map(|_| tile(Vec::new())).collect());
+    }
+
+    let decoded = image::load_from_memory(&image.data)?;
+    let expected = (area.cols * size, area.rows * size);
+    if decoded.dimensions() != expected {
+        return Err(PassthroughError::UnexpectedImageSize {
+            expected,
+            actual: decoded.dimensions(),
+        });
+    }
+    // JPEG has no alpha channel
+    let decoded = if format == Format::Jpeg {
+        DynamicImage::ImageRgb8(decoded.to_rgb8())
+    } else {
+        decoded
+    };
+    let image_format = match format {
+        Format::Jpeg => ImageFormat::Jpeg,
+        Format::Webp => ImageFormat::WebP,
+        _ => ImageFormat::Png,
+    };
+
+    let mut tiles = Vec::with_capacity(area.len());
+    for row in 0..area.rows {
+        for col in 0..area.cols {
+            let mut data = Cursor::new(Vec::new());
+            decoded
+                .crop_imm(col * size, row * size, size, size)
+                .write_to(&mut data, image_format)?;
+            tiles.push(tile(data.into_inner()));
+        }
+    }
+    Ok(tiles)
+}
+
+/// A block of tiles of one zoom level requested as a single image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct MetaTile {
+    /// The top-left tile.
+    pub(crate) origin: TileCoord,
+    pub(crate) cols: u32,
+    pub(crate) rows: u32,
+}
+
+impl MetaTile {
+    /// A metatile of just this tile.
+    pub(crate) const fn single(xyz: TileCoord) -> Self {
+        Self {
+            origin: xyz,
+            cols: 1,
+            rows: 1,
+        }
+    }
+
+    /// The metatile of up to `size` × `size` tiles that contains `xyz`,
+    /// clipped to the tiles of the zoom level.
+    pub(crate) fn containing(xyz: TileCoord, size: u32) -> Self {
+        let zoom_tiles = 1_u32 << xyz.z;
+        let x = xyz.x / size * size;
+        let y = xyz.y / size * size;
+        Self {
+            origin: TileCoord::new_unchecked(xyz.z, x, y),
+            cols: size.min(zoom_tiles.saturating_sub(x)).max(1),
+            rows: size.min(zoom_tiles.saturating_sub(y)).max(1),
+        }
+    }
+
+    /// `[min_x, min_y, max_x, max_y]` in `EPSG:3857` meters.
+    pub(crate) fn webmercator_bbox(self) -> [f64; 4] {
+        let TileCoord { z, x, y } = self.origin;
+        xyz_to_webmercator_bbox(z, x, y, x + self.cols - 1, y + self.rows - 1)
+    }
+
+    pub(crate) fn len(self) -> usize {
+        usize::try_from(self.cols * self.rows).unwrap_or(usize::MAX)
+    }
+
+    /// The tiles in row-major order.
+    pub(crate) fn tiles(self) -> impl Iterator<Item = TileCoord> {
+        let TileCoord { z, x, y } = self.origin;
+        (y..y + self.rows).flat_map(move |row| {
+            (x..x + self.cols).map(move |col| TileCoord::new_unchecked(z, col, row))
+        })
+    }
+
+    /// Position of `xyz` in [`tiles`](Self::tiles), `xyz` must be part of the metatile.
+    pub(crate) fn index_of(self, xyz: TileCoord) -> usize {
+        let row = xyz.y - self.origin.y;
+        let col = xyz.x - self.origin.x;
+        usize::try_from(row * self.cols + col).unwrap_or(usize::MAX)
+    }
+}
+
+impl Display for MetaTile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.cols == 1 && self.rows == 1 {
+            write!(f, "{}", self.origin)
+        } else {
+            write!(f, "{} (+{}x{})", self.origin, self.cols, self.rows)
+        }
+    }
+}
+
+/// Expire the tiles of a metatile when the first of them gets stale, or after [`METATILE_TTL`].
+struct MetaTileExpiry;
+
+impl Expiry<MetaTile, Arc<[FetchedTile]>> for MetaTileExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &MetaTile,
+        value: &Arc<[FetchedTile]>,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        let fresh = value.first().and_then(|t| t.hints.remaining());
+        Some(fresh.map_or(METATILE_TTL, |d| d.min(METATILE_TTL)))
+    }
+}
+
+/// The tiles cut from recently fetched metatiles, so requesting the other tiles of a metatile
+/// does not fetch it again. Concurrent requests for tiles of the same metatile share one fetch.
+#[derive(Clone, Debug)]
+pub(crate) struct MetaTiles {
+    size: u32,
+    tiles: Cache<MetaTile, Arc<[FetchedTile]>>,
+}
+
+impl MetaTiles {
+    pub(crate) fn new(size: u32) -> Self {
+        Self {
+            size,
+            tiles: Cache::builder()
+                .name("passthrough_metatiles")
+                .weigher(|_key: &MetaTile, tiles: &Arc<[FetchedTile]>| {
+                    let bytes: usize = tiles.iter().map(|t| t.data.len()).sum();
+                    u32::try_from(bytes).unwrap_or(u32::MAX).max(1)
+                })
+                .max_capacity(METATILE_CACHE_BYTES)
+                .expire_after(MetaTileExpiry)
+                .build(),
+        }
+    }
+
+    /// The metatile containing `xyz`.
+    pub(crate) fn area(&self, xyz: TileCoord) -> MetaTile {
+        MetaTile::containing(xyz, self.size)
+    }
+
+    /// The tile `xyz`, cut from its metatile which is fetched with `fetch` unless it is known.
+    pub(crate) async fn get<F>(
+        &self,
+        xyz: TileCoord,
+        fetch: F,
+    ) -> Result<FetchedTile, PassthroughError>
+    where
+        F: Future<Output = Result<Vec<FetchedTile>, PassthroughError>>,
+    {
+        let area = self.area(xyz);
+        let tiles = self
+            .tiles
+            .try_get_with(area, async { fetch.await.map(Arc::from) })
+            .await
+            .map_err(PassthroughError::Shared)?;
+        // `split` returns a tile for each tile of the area
+        Ok(tiles[area.index_of(xyz)].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::tiles::TileCacheHints;
+
+    fn coord(z: u8, x: u32, y: u32) -> TileCoord {
+        TileCoord::new_unchecked(z, x, y)
+    }
+
+    fn set(service: MapService, format: Format, metatile: u32) -> MapServiceSet {
+        MapServiceSet::new(
+            vec!["https://maps.example.org/service?map=roads".to_owned()],
+            MapServiceParams {
+                service,
+                format,
+                transparent: true,
+                tile_size: 256,
+                metatile,
+            },
+            TemplateMeta {
+                minzoom: None,
+                maxzoom: None,
+                bounds: None,
+                attribution: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn wms(version: &str) -> MapService {
+        MapService::Wms {
+            layers: vec!["roads".to_owned(), "labels".to_owned()],
+            styles: Vec::new(),
+            version: version.to_owned(),
+        }
+    }
+
+    #[rstest]
+    #[case::z0(coord(0, 0, 0), 4, coord(0, 0, 0), 1)]
+    #[case::inside(coord(3, 5, 6), 4, coord(3, 4, 4), 4)]
+    #[case::clipped(coord(1, 1, 1), 4, coord(1, 0, 0), 2)]
+    #[case::uneven(coord(2, 3, 3), 3, coord(2, 3, 3), 1)]
+    fn metatile_containing(
+        #[case] xyz: TileCoord,
+        #[case] size: u32,
+        #[case] origin: TileCoord,
+        #[case] side: u32,
+    ) {
+        let area = MetaTile::containing(xyz, size);
+        assert_eq!(area.origin, origin);
+        assert_eq!((area.cols, area.rows), (side, side));
+        assert_eq!(area.tiles().nth(area.index_of(xyz)), Some(xyz));
+        assert_eq!(area.tiles().count(), area.len());
+    }
+
+    #[test]
+    fn wms_request() {
+        let url = set(wms("1.3.0"), Format::Png, 1)
+            .request_url(
+                "https://maps.example.org/service?map=roads",
+                MetaTile::single(coord(1, 0, 0)),
+            )
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://maps.example.org/service?map=roads&SERVICE=WMS&REQUEST=GetMap&VERSION=1.3.0\
+             &LAYERS=roads%2Clabels&STYLES=&CRS=EPSG%3A3857\
+             &BBOX=-20037508.34278925%2C0%2C0%2C20037508.34278925&WIDTH=256&HEIGHT=256\
+             &FORMAT=image%2Fpng&TRANSPARENT=TRUE"
+        );
+        let url = set(wms("1.1.1"), Format::Png, 1)
+            .request_url("https://m/wms", MetaTile::single(coord(0, 0, 0)))
+            .unwrap();
+        assert!(url.contains("&SRS=EPSG%3A3857&"), "{url}");
+    }
+
+    #[test]
+    fn arcgis_request() {
+        let service = MapService::ArcGis {
+            layers: Some("show:0,2".to_owned()),
+        };
+        let url = set(service, Format::Png, 2)
+            .request_url(
+                "https://maps.example.org/arcgis/rest/services/Roads/MapServer/",
+                MetaTile::containing(coord(1, 1, 1), 2),
+            )
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://maps.example.org/arcgis/rest/services/Roads/MapServer/export\
+             ?bbox=-20037508.34278925%2C-20037508.34278925%2C20037508.34278925%2C20037508.34278925\
+             &bboxSR=3857&imageSR=3857&size=512%2C512&format=png32&transparent=true&f=image\
+             &layers=show%3A0%2C2"
+        );
+    }
+
+    #[rstest]
+    #[case::mvt(wms("1.3.0"), Format::Mvt, 256, 1, "format")]
+    #[case::arcgis_webp(MapService::ArcGis { layers: None }, Format::Webp, 256, 1, "format")]
+    #[case::metatile(wms("1.3.0"), Format::Png, 256, MAX_METATILE + 1, "metatile")]
+    #[case::metatile_pixels(wms("1.3.0"), Format::Png, 1024, 16, "pixels")]
+    fn rejects_invalid_params(
+        #[case] service: MapService,
+        #[case] format: Format,
+        #[case] tile_size: u32,
+        #[case] metatile: u32,
+        #[case] what: &str,
+    ) {
+        let err = MapServiceSet::new(
+            vec!["https://m/wms".to_owned()],
+            MapServiceParams {
+                service,
+                format,
+                transparent: false,
+                tile_size,
+                metatile,
+            },
+            TemplateMeta {
+                minzoom: None,
+                maxzoom: None,
+                bounds: None,
+                attribution: None,
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains(what), "{err}");
+    }
+
+    #[tokio::test]
+    async fn splits_metatiles() {
+        let mut img = image::RgbaImage::new(512, 256);
+        for (x, _, pixel) in img.enumerate_pixels_mut() {
+            *pixel = if x < 256 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 255, 128])
+            };
+        }
+        let mut data = Cursor::new(Vec::new());
+        img.write_to(&mut data, ImageFormat::Png).unwrap();
+        let fetched = FetchedTile {
+            data: data.into_inner(),
+            info: TileInfo::from(Format::Png),
+            etag: None,
+            hints: TileCacheHints::default(),
+            validators: Validators::default(),
+        };
+        let area = MetaTile {
+            origin: coord(1, 0, 1),
+            cols: 2,
+            rows: 1,
+        };
+
+        let tiles = set(wms("1.3.0"), Format::Png, 2)
+            .split(fetched.clone(), area)
+            .await
+            .unwrap();
+        assert_eq!(tiles.len(), 2);
+        for (tile, color) in tiles.iter().zip([[255, 0, 0, 255], [0, 0, 255, 128]]) {
+            let decoded = image::load_from_memory(&tile.data).unwrap().to_rgba8();
+            assert_eq!(decoded.dimensions(), (256, 256));
+            assert_eq!(decoded.get_pixel(100, 100).0, color);
+        }
+
+        let wrong_area = MetaTile::containing(coord(2, 0, 0), 2);
+        assert!(matches!(
+            set(wms("1.3.0"), Format::Png, 2)
+                .split(fetched, wrong_area)
+                .await,
+            Err(PassthroughError::UnexpectedImageSize { .. })
+        ));
+    }
+}
@@ -1,11 +1,25 @@
 //! Proxies tiles from an upstream HTTP tile server through Martin's pipeline.
 //!
 //! A `passthrough` source fetches tiles from an operator-configured upstream URL (a
-//! `{z}/{x}/{y}` template, a list of templates, or a `TileJSON` document URL) and serves the
-//! bytes verbatim, preserving the upstream `Content-Encoding`. The shared server pipeline then
-//! applies MVT<->MLT conversion and caching on top, exactly as it does for any other [`Source`].
+//! `{z}/{x}/{y}` template, a list of templates, a `TileJSON` document URL, or a WMS or `ArcGIS`
+//! REST map service) and serves the bytes verbatim, preserving the upstream `Content-Encoding`.
+//! The shared server pipeline then applies MVT<->MLT conversion and caching on top, exactly as it
+//! does for any other [`Source`].
+//!
+//! Map services are asked for an image of the `EPSG:3857` bounding box of each tile. With
+//! metatiling, a block of tiles is requested as one image and cut into tiles
+//! (see [`MapServiceSet`]).
+//!
+//! Failing fetches are retried and failed over to mirror templates, upstreams that keep failing
+//! are skipped by a circuit breaker, and the last good tiles can be served when all of them fail
+//! (see [`Resilience`]).
+//!
+//! The upstream `Cache-Control`, `Expires` and `Last-Modified` headers become the
+//! [`TileCacheHints`] of the tiles, and kept tiles are revalidated with `If-None-Match` and
+//! `If-Modified-Since` instead of being downloaded again.
 //!
 //! [`Source`]: crate::tiles::Source
+//! [`TileCacheHints`]: crate::tiles::TileCacheHints
 
 mod error;
 pub use error::PassthroughError;
@@ -13,5 +27,15 @@ pub use error::PassthroughError;
 mod url;
 pub use url::UrlTemplate;
 
+mod http_cache;
+
+mod map_service;
+pub use map_service::{
+    MAX_METATILE, MAX_METATILE_PIXELS, MAX_TILE_SIZE, MapService, MapServiceParams, MapServiceSet,
+};
+
+mod resilience;
+pub use resilience::{CircuitBreakerPolicy, Resilience, RetryPolicy, StalePolicy};
+
 mod source;
 pub use source::{PassthroughSource, TemplateMeta, TemplateSet, Transport, Upstream};
@@ -0,0 +1,216 @@
+//! Keeps a passthrough source serving while its upstreams misbehave: retry backoff for failed
+//! fetches, a circuit breaker per upstream URL template, and a store of the last good tiles that
+//! are served when every upstream fails, and revalidated once they expire.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use martin_tile_utils::{TileCoord, TileData, TileInfo};
+use moka::future::Cache;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::tiles::TileCacheHints;
+use crate::tiles::passthrough::http_cache::Validators;
+
+/// How failed tile fetches are retried.
+///
+/// A fetch fails over to the other mirrors right away; the retries repeat the whole round of
+/// mirrors after an exponentially growing, jittered delay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Rounds repeated after the first one, `0` disables retries.
+    pub max_retries: u32,
+    /// Delay before the first retry.
+    pub min_delay: Duration,
+    /// Upper bound of the delay between retries.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            min_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// When an upstream is considered down, and for how long requests to it fail fast.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitBreakerPolicy {
+    /// Consecutive failed requests that open the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a single probe request is let through.
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Limits of the last good tiles kept to be served when the upstreams fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StalePolicy {
+    /// Total size of the kept tiles.
+    pub max_size_bytes: u64,
+    /// Tiles older than this are not served anymore.
+    pub max_age: Duration,
+}
+
+/// How a passthrough source deals with failing upstreams.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resilience {
+    /// Retries of failed fetches.
+    pub retry: RetryPolicy,
+    /// Circuit breaker of each upstream URL template, `None` disables it.
+    pub circuit_breaker: Option<CircuitBreakerPolicy>,
+    /// Serving of stale tiles on upstream errors, `None` disables it.
+    pub serve_stale: Option<StalePolicy>,
+}
+
+impl Default for Resilience {
+    /// Retries and circuit breakers are on, stale serving is off because it needs memory.
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::default(),
+            circuit_breaker: Some(CircuitBreakerPolicy::default()),
+            serve_stale: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    /// Requests are rejected until this time. Once it passed, the circuit is half-open:
+    /// the next request is let through as a probe and the circuit is re-opened meanwhile.
+    open_until: Option<Instant>,
+}
+
+/// Circuit breaker of one upstream URL template.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    policy: CircuitBreakerPolicy,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(policy: CircuitBreakerPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns `false` if requests to the upstream should fail fast.
+    pub(crate) fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.open_until {
+            None => true,
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                // Only one probe per `open_duration`, even if it never completes.
+                state.open_until = Some(Instant::now() + self.policy.open_duration);
+                true
+            }
+        }
+    }
+
+    pub(crate) fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.consecutive_failures = 0;
+        state.open_until = None;
+    }
+
+    pub(crate) fn record_failure(&self, url: &str) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.consecutive_failures >= self.policy.failure_threshold {
+            if state.open_until.is_none() {
+                warn!(
+                    "Upstream {url} failed {} times in a row, rejecting its requests for {:?}",
+                    state.consecutive_failures, self.policy.open_duration
+                );
+            }
+            state.open_until = Some(Instant::now() + self.policy.open_duration);
+        }
+    }
+}
+
+/// A tile fetched from the upstream, before it is wrapped into a [`Tile`](crate::tiles::Tile).
+#[derive(Clone, Debug)]
+pub(crate) struct FetchedTile {
+    pub(crate) data: TileData,
+    pub(crate) info: TileInfo,
+    /// The upstream `ETag` header verbatim, if any (so large tiles are not re-hashed).
+    pub(crate) etag: Option<String>,
+    pub(crate) hints: TileCacheHints,
+    pub(crate) validators: Validators,
+}
+
+/// The last good tiles of a source, served when all upstreams fail.
+/// Their validators are also used to revalidate them with the upstream.
+#[derive(Clone, Debug)]
+pub(crate) struct StaleTiles(Cache<TileCoord, FetchedTile>);
+
+impl StaleTiles {
+    pub(crate) fn new(policy: StalePolicy) -> Self {
+        Self(
+            Cache::builder()
+                .name("passthrough_stale")
+                .weigher(|_key: &TileCoord, tile: &FetchedTile| {
+                    u32::try_from(tile.data.len()).unwrap_or(u32::MAX).max(1)
+                })
+                .max_capacity(policy.max_size_bytes)
+                .time_to_live(policy.max_age)
+                .build(),
+        )
+    }
+
+    pub(crate) async fn insert(&self, xyz: TileCoord, tile: FetchedTile) {
+        self.0.insert(xyz, tile).await;
+    }
+
+    pub(crate) async fn get(&self, xyz: TileCoord) -> Option<FetchedTile> {
+        self.0.get(&xyz).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn breaker_opens_and_probes() {
+        let breaker = CircuitBreaker::new(CircuitBreakerPolicy {
+            failure_threshold: 2,
+            open_duration: Duration::from_secs(10),
+        });
+        breaker.record_failure("u");
+        assert!(breaker.allow(), "one failure keeps the circuit closed");
+        breaker.record_failure("u");
+        assert!(!breaker.allow());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(breaker.allow(), "a probe is let through");
+        assert!(!breaker.allow(), "only a single probe");
+
+        breaker.record_failure("u");
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(!breaker.allow(), "a failed probe re-opens the circuit");
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert!(breaker.allow());
+        breaker.record_failure("u");
+        assert!(breaker.allow(), "a success resets the failure count");
+    }
+}
@@ -1,21 +1,30 @@
 //! The [`PassthroughSource`] [`Source`] implementation and its HTTP fetch logic.
 
+use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
+use backon::{ExponentialBuilder, Retryable as _};
+use chrono::Utc;
 use martin_tile_utils::{Encoding, Format, TileCoord, TileData, TileInfo};
 use reqwest::StatusCode;
 use reqwest::header::{
     CONTENT_ENCODING, CONTENT_TYPE, ETAG, HeaderMap, HeaderName, HeaderValue, USER_AGENT,
 };
 use tilejson::{Bounds, TileJSON, tilejson};
+use tracing::{debug, warn};
 
 use crate::CacheZoomRange;
-use crate::tiles::passthrough::PassthroughError;
+use crate::tiles::passthrough::http_cache::{Validators, cache_hints};
+use crate::tiles::passthrough::map_service::{MetaTile, MetaTiles};
+use crate::tiles::passthrough::resilience::{CircuitBreaker, FetchedTile, StaleTiles};
 use crate::tiles::passthrough::url::{
-    UrlTemplate, derive_format, is_template, select_url, substitute,
+    UrlTemplate, derive_format, failover_order, is_template, substitute,
+};
+use crate::tiles::passthrough::{MapServiceSet, PassthroughError, Resilience};
+use crate::tiles::{
+    BoxedSource, MartinCoreError, MartinCoreResult, Source, Tile, TileCacheHints, UrlQuery,
 };
-use crate::tiles::{BoxedSource, MartinCoreError, MartinCoreResult, Source, Tile, UrlQuery};
 
 /// HTTP transport settings applied to both `TileJSON` discovery and per-tile fetches.
 #[derive(Clone, Debug)]
@@ -24,6 +33,9 @@ pub struct Transport {
     pub headers: HeaderMap,
     /// Per-request timeout.
     pub timeout: Duration,
+    /// Whether the `Cache-Control`, `Expires` and `Last-Modified` headers of the upstream
+    /// are passed on as [`TileCacheHints`].
+    pub honor_cache_headers: bool,
 }
 
 impl Transport {
@@ -33,6 +45,7 @@ impl Transport {
         Self {
             headers: HeaderMap::new(),
             timeout,
+            honor_cache_headers: true,
         }
     }
 
@@ -66,6 +79,7 @@ impl Transport {
         Ok(Self {
             headers: header_map,
             timeout,
+            honor_cache_headers: true,
         })
     }
 }
@@ -136,6 +150,8 @@ pub enum Upstream {
         /// Explicit format override; otherwise derived from the document.
         format: Option<Format>,
     },
+    /// One or more WMS or `ArcGIS` REST service URLs, asked for an image of each tile's bounds.
+    MapService(MapServiceSet),
 }
 
 impl Upstream {
@@ -174,7 +190,8 @@ impl Upstream {
 pub struct PassthroughSource {
     id: String,
     client: reqwest::Client,
-    /// Resolved `{z}/{x}/{y}` templates (for `TileJSON` sources, taken from `tiles[]`).
+    /// Resolved `{z}/{x}/{y}` templates (for `TileJSON` sources, taken from `tiles[]`),
+    /// or the map service URLs.
     urls: Vec<String>,
     tilejson: TileJSON,
     tile_info: TileInfo,
@@ -183,14 +200,13 @@ pub struct PassthroughSource {
     upstream: Upstream,
     /// Kept so [`try_reload`](Source::try_reload) can rebuild the HTTP client.
     transport: Transport,
-}
-
-/// A tile fetched from the upstream, before it is wrapped into a [`Tile`].
-struct FetchedTile {
-    data: TileData,
-    info: TileInfo,
-    /// The upstream `ETag` header verbatim, if any (so large tiles are not re-hashed).
-    etag: Option<String>,
+    resilience: Resilience,
+    /// One per entry of `urls`, empty if circuit breaking is disabled.
+    /// Shared by all clones of the source.
+    breakers: Arc<[CircuitBreaker]>,
+    stale: Option<StaleTiles>,
+    /// Tiles cut from recent metatiles, if the map service upstream uses metatiling.
+    metatiles: Option<MetaTiles>,
 }
 
 impl PassthroughSource {
@@ -199,6 +215,7 @@ impl PassthroughSource {
         id: String,
         upstream: Upstream,
         transport: Transport,
+        resilience: Resilience,
         cache_zoom: CacheZoomRange,
     ) -> Result<Self, PassthroughError> {
         let client = build_client(&transport)?;
@@ -219,6 +236,23 @@ impl PassthroughSource {
                 let format = derive_format(&id, *format, first, tj_format)?;
                 (templates, upstream_tj, TileInfo::from(format))
             }
+            Upstream::MapService(set) => {
+                let urls = set.urls().to_vec();
+                let tilejson = build_template_tilejson(&urls, set.meta());
+                (urls, tilejson, TileInfo::from(set.params().format))
+            }
+        };
+
+        let breakers: Arc<[CircuitBreaker]> = match resilience.circuit_breaker {
+            Some(policy) => urls.iter().map(|_| CircuitBreaker::new(policy)).collect(),
+            None => Arc::new([]),
+        };
+        let stale = resilience.serve_stale.map(StaleTiles::new);
+        let metatiles = match &upstream {
+            Upstream::MapService(set) if set.params().metatile > 1 => {
+                Some(MetaTiles::new(set.params().metatile))
+            }
+            _ => None,
         };
 
         Ok(Self {
@@ -230,21 +264,166 @@ impl PassthroughSource {
             cache_zoom,
             upstream,
             transport,
+            resilience,
+            breakers,
+            stale,
+            metatiles,
         })
     }
 
-    /// Fetch a single tile from the upstream, mapping its status into the cache contract:
-    /// 404/204 -> empty tile, 5xx/other non-success -> error, 2xx -> bytes plus detected info/etag.
+    /// Fetch a tile, revalidating its last good copy if there is one,
+    /// and falling back to that copy if no upstream delivers the tile.
     async fn fetch(&self, xyz: TileCoord) -> Result<FetchedTile, PassthroughError> {
-        let url = substitute(select_url(&self.urls, xyz), xyz);
-        let response = self.client.get(&url).send().await?;
+        let Some(stale) = &self.stale else {
+            return self.fetch_fresh(xyz, None).await;
+        };
+        let kept = stale.get(xyz).await;
+        match self.fetch_fresh(xyz, kept.as_ref()).await {
+            Ok(tile) => {
+                stale.insert(xyz, tile.clone()).await;
+                Ok(tile)
+            }
+            Err(e) => match kept {
+                Some(mut tile) => {
+                    warn!(
+                        "Serving stale tile {xyz} of passthrough source {}: {e}",
+                        self.id
+                    );
+                    // ask the upstream again on the next request
+                    tile.hints.fresh_until = Some(Utc::now());
+                    Ok(tile)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Fetch a tile from the upstream, or cut it from its metatile if the upstream uses metatiling.
+    /// Metatiles are not revalidated, as the `kept` tile has no validators of its metatile.
+    async fn fetch_fresh(
+        &self,
+        xyz: TileCoord,
+        kept: Option<&FetchedTile>,
+    ) -> Result<FetchedTile, PassthroughError> {
+        let (Some(metatiles), Upstream::MapService(set)) = (&self.metatiles, &self.upstream) else {
+            return self.fetch_with_retries(MetaTile::single(xyz), kept).await;
+        };
+        let area = metatiles.area(xyz);
+        metatiles
+            .get(xyz, async {
+                let image = self.fetch_with_retries(area, None).await?;
+                set.split(image, area).await
+            })
+            .await
+    }
+
+    /// Fetch a tile or metatile from the mirrors, repeating the round with a jittered backoff
+    /// while the failures are transient.
+    async fn fetch_with_retries(
+        &self,
+        area: MetaTile,
+        kept: Option<&FetchedTile>,
+    ) -> Result<FetchedTile, PassthroughError> {
+        let retry = self.resilience.retry;
+        (|| self.fetch_from_mirrors(area, kept))
+            .retry(
+                ExponentialBuilder::default()
+                    .with_min_delay(retry.min_delay)
+                    .with_max_delay(retry.max_delay)
+                    .with_max_times(usize::try_from(retry.max_retries).unwrap_or(usize::MAX))
+                    .with_jitter(),
+            )
+            .sleep(tokio::time::sleep)
+            .when(PassthroughError::is_transient)
+            .notify(|e, delay| {
+                debug!(
+                    "Fetching tile {area} of passthrough source {} failed, retrying in {delay:?}: {e}",
+                    self.id
+                );
+            })
+            .await
+    }
+
+    /// Fetch a tile from the template picked for it, failing over to the other templates when
+    /// the failure is transient. Templates whose circuit is open are skipped.
+    async fn fetch_from_mirrors(
+        &self,
+        area: MetaTile,
+        kept: Option<&FetchedTile>,
+    ) -> Result<FetchedTile, PassthroughError> {
+        let mut last_error = None;
+        for (idx, template) in failover_order(&self.urls, area.origin) {
+            let breaker = self.breakers.get(idx);
+            if breaker.is_some_and(|b| !b.allow()) {
+                continue;
+            }
+            let result = self.fetch_once(template, area, kept).await;
+            // any other response than a transient failure means the upstream is up
+            let transient = result.as_ref().is_err_and(PassthroughError::is_transient);
+            if let Some(breaker) = breaker {
+                if transient {
+                    breaker.record_failure(template);
+                } else {
+                    breaker.record_success();
+                }
+            }
+            if !transient {
+                return result;
+            }
+            last_error = Some(result);
+        }
+        last_error.unwrap_or_else(|| {
+            let primary = failover_order(&self.urls, area.origin)
+                .next()
+                .map_or("", |(_, u)| u);
+            Err(PassthroughError::CircuitOpen(primary.to_owned()))
+        })
+    }
+
+    /// Fetch a single tile from one upstream template, mapping its status into the cache contract:
+    /// 404/204 -> empty tile, 5xx/other non-success -> error, 2xx -> bytes plus detected info/etag.
+    /// For a map service, `template` is the service URL, and `area` may span several tiles.
+    ///
+    /// With a `kept` copy of the tile, the request is conditional, and a `304 Not Modified`
+    /// returns the kept copy with the refreshed caching information.
+    async fn fetch_once(
+        &self,
+        template: &str,
+        area: MetaTile,
+        kept: Option<&FetchedTile>,
+    ) -> Result<FetchedTile, PassthroughError> {
+        let url = match &self.upstream {
+            Upstream::MapService(set) => set.request_url(template, area)?,
+            _ => substitute(template, area.origin),
+        };
+        let mut request = self.client.get(&url);
+        if let Some(kept) = kept {
+            request = kept.validators.apply(request);
+        }
+        let response = request.send().await?;
         let status = response.status();
+        let hints = self.cache_hints(response.headers());
+        let validators = Validators::from_headers(response.headers());
 
+        if let Some(kept) = kept
+            && status == StatusCode::NOT_MODIFIED
+        {
+            return Ok(FetchedTile {
+                hints: TileCacheHints {
+                    last_modified: hints.last_modified.or(kept.hints.last_modified),
+                    ..hints
+                },
+                validators: kept.validators.refreshed(validators),
+                ..kept.clone()
+            });
+        }
         if status == StatusCode::NOT_FOUND || status == StatusCode::NO_CONTENT {
             return Ok(FetchedTile {
                 data: TileData::new(),
                 info: self.tile_info,
                 etag: None,
+                hints,
+                validators,
             });
         }
         if !status.is_success() {
@@ -256,6 +435,17 @@ impl PassthroughSource {
 
         let etag = header_str(response.headers(), &ETAG).and_then(|raw| usable_strong_etag(&raw));
         let content_type = header_str(response.headers(), &CONTENT_TYPE);
+        if matches!(self.upstream, Upstream::MapService(_))
+            && let Some(content_type) = content_type.as_ref().filter(|t| !t.starts_with("image/"))
+        {
+            // WMS and ArcGIS report errors as XML or JSON documents with a 200 status
+            let body = response.text().await.unwrap_or_default();
+            return Err(PassthroughError::ServiceException {
+                url,
+                content_type: content_type.clone(),
+                message: body.chars().take(500).collect(),
+            });
+        }
         let content_encoding = header_str(response.headers(), &CONTENT_ENCODING);
         let data = response.bytes().await?.to_vec();
         let info = response_tile_info(
@@ -264,7 +454,21 @@ impl PassthroughSource {
             content_encoding.as_deref(),
             &data,
         );
-        Ok(FetchedTile { data, info, etag })
+        Ok(FetchedTile {
+            data,
+            info,
+            etag,
+            hints,
+            validators,
+        })
+    }
+
+    fn cache_hints(&self, headers: &HeaderMap) -> TileCacheHints {
+        if self.transport.honor_cache_headers {
+            cache_hints(headers, Utc::now())
+        } else {
+            TileCacheHints::default()
+        }
     }
 }
 
@@ -294,6 +498,15 @@ impl Source for PassthroughSource {
         self.cache_zoom
     }
 
+    /// Fetches the first tile of the lowest zoom level, a missing tile also counts as reachable.
+    /// Stale tiles are not used, so that the check reflects the upstreams.
+    async fn check_health(&self) -> MartinCoreResult<()> {
+        let z = self.tilejson.minzoom.unwrap_or(0);
+        self.fetch_with_retries(MetaTile::single(TileCoord { z, x: 0, y: 0 }), None)
+            .await?;
+        Ok(())
+    }
+
     async fn get_tile(
         &self,
         xyz: TileCoord,
@@ -314,7 +527,7 @@ impl Source for PassthroughSource {
             }
             _ => Tile::new_hash_etag(fetched.data, fetched.info),
         };
-        Ok(tile)
+        Ok(tile.with_hints(fetched.hints))
     }
 
     async fn try_reload(&self) -> MartinCoreResult<BoxedSource> {
@@ -322,6 +535,7 @@ impl Source for PassthroughSource {
             self.id.clone(),
             self.upstream.clone(),
             self.transport.clone(),
+            self.resilience,
             self.cache_zoom,
         )
         .await
@@ -1,5 +1,6 @@
 //! Pure URL handling for the passthrough source: template validation, `{z}/{x}/{y}`
-//! substitution, deterministic per-tile template selection, and layered format derivation.
+//! substitution, deterministic per-tile template selection and failover order, and layered
+//! format derivation.
 //!
 //! Everything here is side-effect free so it can be unit-tested without a network.
 
@@ -52,16 +53,23 @@ pub fn substitute(template: &str, xyz: TileCoord) -> String {
 ///
 /// Assumes `urls` is non-empty; the modulo keeps the index in range.
 #[must_use]
-pub fn select_url(urls: &[String], xyz: TileCoord) -> &str {
-    if let [single] = urls {
-        return single;
+pub fn select_index(urls: &[String], xyz: TileCoord) -> usize {
+    if urls.len() < 2 {
+        return 0;
     }
     let mut hasher = Xxh3::new();
     xyz.z.hash(&mut hasher);
     xyz.x.hash(&mut hasher);
     xyz.y.hash(&mut hasher);
-    let idx = usize::try_from(hasher.finish() % urls.len() as u64).unwrap_or(0);
-    urls.get(idx).map_or(&urls[0], |u| u)
+    usize::try_from(hasher.finish() % urls.len() as u64).unwrap_or(0)
+}
+
+/// The templates to try for a tile together with their indexes: the one picked by
+/// [`select_index`] first, then the others in order, as failover mirrors.
+pub fn failover_order(urls: &[String], xyz: TileCoord) -> impl Iterator<Item = (usize, &str)> {
+    let first = select_index(urls, xyz);
+    let indexed = urls.iter().map(String::as_str).enumerate();
+    indexed.clone().skip(first).chain(indexed.take(first))
 }
 
 /// Derive the source-level tile [`Format`] from the configured layers, most-specific first:
@@ -127,18 +135,17 @@ mod tests {
     }
 
     #[test]
-    fn select_url_is_deterministic_and_in_range() {
+    fn select_index_is_deterministic_and_in_range() {
         let urls = vec![
             "https://a/{z}/{x}/{y}".to_owned(),
             "https://b/{z}/{x}/{y}".to_owned(),
             "https://c/{z}/{x}/{y}".to_owned(),
         ];
-        let first = select_url(&urls, coord(5, 10, 20));
-        assert_eq!(first, select_url(&urls, coord(5, 10, 20)));
+        let first = select_index(&urls, coord(5, 10, 20));
+        assert_eq!(first, select_index(&urls, coord(5, 10, 20)));
         for z in 0..8u8 {
             for x in 0..16u32 {
-                let picked = select_url(&urls, coord(z, x, x));
-                assert!(urls.iter().any(|u| u == picked));
+                assert!(select_index(&urls, coord(z, x, x)) < urls.len());
             }
         }
     }
@@ -146,7 +153,20 @@ mod tests {
     #[test]
     fn single_url_always_selected() {
         let urls = vec!["https://only/{z}/{x}/{y}".to_owned()];
-        assert_eq!(select_url(&urls, coord(7, 3, 9)), urls[0]);
+        assert_eq!(select_index(&urls, coord(7, 3, 9)), 0);
+    }
+
+    #[test]
+    fn failover_order_starts_with_selected_and_visits_all() {
+        let urls: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+        let xyz = coord(4, 2, 9);
+        let order: Vec<(usize, &str)> = failover_order(&urls, xyz).collect();
+        let first = select_index(&urls, xyz);
+        let expected: Vec<usize> = (first..3).chain(0..first).collect();
+        assert_eq!(order.iter().map(|(i, _)| *i).collect::<Vec<_>>(), expected);
+        for (i, url) in order {
+            assert_eq!(url, urls[i]);
+        }
     }
 
     #[rstest]
@@ -2,10 +2,10 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
 use moka::future::Cache;
-use tracing::info;
+use tracing::{Instrument as _, debug_span, info};
 
 #[cfg(feature = "metrics")]
-use crate::metrics::{TILE_CACHE_REQUESTS_TOTAL, ZOOM_LABELS};
+use crate::metrics::{TILE_CACHE_REQUESTS_TOTAL, ZOOM_LABELS, record_cache_size};
 
 /// Optional wrapper for `PmtCache`.
 pub type OptPmtCache = Option<PmtCache>;
@@ -127,7 +127,15 @@ impl pmtiles::DirectoryCache for PmtCacheInstance {
             .cache
             .0
             .entry(key)
-            .or_try_insert_with(fetcher)
+            // the span is only created if the directory is fetched
+            .or_try_insert_with(async move {
+                fetcher
+                    .instrument(debug_span!(
+                        "pmtiles_directory_fetch",
+                        pmtiles.offset = offset
+                    ))
+                    .await
+            })
             .await
             .map_err(|e| {
                 pmtiles::PmtError::DirectoryCacheError(format!("Moka cache fetch error: {e}"))
@@ -139,6 +147,11 @@ impl pmtiles::DirectoryCache for PmtCacheInstance {
             TILE_CACHE_REQUESTS_TOTAL
                 .with_label_values(&["pmtiles_directory", result, zoom])
                 .inc();
+            record_cache_size(
+                "pmtiles_directory",
+                self.cache.0.entry_count(),
+                self.cache.0.weighted_size(),
+            );
         }
         Ok(entry.into_value().find_tile_id(tile_id).cloned())
     }
@@ -14,6 +14,10 @@ pub enum PmtilesError {
     #[error(r"PMTiles error {0} processing {1}")]
     PmtErrorWithCtx(#[source] PmtError, String),
 
+    /// The file cannot be accessed in its object store.
+    #[error(r"Unable to access {1}: {0}")]
+    ObjectStoreError(#[source] object_store::Error, String),
+
     /// Invalid or unparsable metadata in the `PMTiles` source.
     #[error(r"Unable to parse metadata in file {1}: {0}")]
     InvalidMetadata(String, object_store::path::Path),
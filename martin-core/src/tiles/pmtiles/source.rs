@@ -3,10 +3,14 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use derive_debug::Dbg;
 use martin_tile_utils::{Encoding, Format, TileCoord, TileData, TileInfo};
-use object_store::ObjectStore;
-use pmtiles::{AsyncPmTilesReader, Compression, ObjectStoreBackend, PmtError, TileType};
+use object_store::{ObjectStore, ObjectStoreExt as _};
+use pmtiles::{
+    AsyncBackend, AsyncPmTilesReader, BackendResponse, Compression, ObjectStoreBackend, PmtError,
+    PmtResult, TileType,
+};
 use tilejson::TileJSON;
 use tracing::{trace, warn};
 
@@ -15,12 +19,31 @@ use crate::tiles::pmtiles::PmtCacheInstance;
 use crate::tiles::pmtiles::PmtilesError::{self, InvalidMetadata};
 use crate::tiles::{BoxedSource, MartinCoreError, MartinCoreResult, Source, UrlQuery};
 
+/// Reads a `PMTiles` file from an [`ObjectStore`], counting the bytes read for the header,
+/// the directories and the tiles alike.
+struct MeteredBackend {
+    inner: ObjectStoreBackend,
+    #[cfg(feature = "metrics")]
+    source_id: String,
+}
+
+impl AsyncBackend for MeteredBackend {
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<BackendResponse> {
+        let response = self.inner.read(offset, length).await?;
+        #[cfg(feature = "metrics")]
+        crate::metrics::OBJECT_STORE_FETCHED_BYTES_TOTAL
+            .with_label_values(&[crate::metrics::source_label(&self.source_id)])
+            .inc_by(response.bytes.len() as u64);
+        Ok(response)
+    }
+}
+
 /// A source for `PMTiles` files using `ObjectStoreBackend`
 #[derive(Clone, Dbg)]
 pub struct PmtilesSource {
     id: String,
     #[dbg(skip)]
-    pmtiles: Arc<AsyncPmTilesReader<ObjectStoreBackend, PmtCacheInstance>>,
+    pmtiles: Arc<AsyncPmTilesReader<MeteredBackend, PmtCacheInstance>>,
     #[dbg(skip)]
     tilejson: TileJSON,
     #[dbg(skip)]
@@ -34,6 +57,8 @@ pub struct PmtilesSource {
     path: object_store::path::Path,
     #[dbg(skip)]
     pmt_cache: PmtCacheInstance,
+    #[dbg(skip)]
+    last_modified: Option<DateTime<Utc>>,
 }
 
 impl PmtilesSource {
@@ -49,7 +74,11 @@ impl PmtilesSource {
         // Wrap in Arc so we can clone the store cheaply for try_reload.
         let store: Arc<dyn ObjectStore> = Arc::from(store);
         let store_to_string = store.to_string();
-        let backend = ObjectStoreBackend::new(Box::new(Arc::clone(&store)), path.clone());
+        let backend = MeteredBackend {
+            inner: ObjectStoreBackend::new(Box::new(Arc::clone(&store)), path.clone()),
+            #[cfg(feature = "metrics")]
+            source_id: id.clone(),
+        };
         let reader = AsyncPmTilesReader::try_from_cached_source(backend, cache.clone())
             .await
             .map_err(|e| PmtilesError::PmtErrorWithCtx(e, store_to_string.clone()))?;
@@ -100,6 +129,9 @@ impl PmtilesSource {
             }
         };
 
+        // Not all stores report it, e.g. HTTP servers without `Last-Modified` headers
+        let last_modified = store.head(&path).await.ok().map(|meta| meta.last_modified);
+
         let tilejson = reader.parse_tilejson(Vec::new()).await.unwrap_or_else(|e| {
             warn!(path = %path, error = ?e, "Unable to parse metadata");
             hdr.get_tilejson(Vec::new())
@@ -114,6 +146,7 @@ impl PmtilesSource {
             store,
             path,
             pmt_cache: cache,
+            last_modified,
         })
     }
 }
@@ -155,10 +188,22 @@ impl Source for PmtilesSource {
         .map_err(MartinCoreError::from)
     }
 
+    fn get_last_modified(&self) -> Option<DateTime<Utc>> {
+        self.last_modified
+    }
+
     fn cache_zoom(&self) -> CacheZoomRange {
         self.cache_zoom
     }
 
+    async fn check_health(&self) -> MartinCoreResult<()> {
+        self.store
+            .head(&self.path)
+            .await
+            .map_err(|e| PmtilesError::ObjectStoreError(e, self.path.to_string()))?;
+        Ok(())
+    }
+
     async fn get_tile(
         &self,
         xyz: TileCoord,
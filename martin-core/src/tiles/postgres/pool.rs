@@ -68,6 +68,8 @@ impl PostgresPool {
             .max_size(pool_size)
             .build()
             .map_err(|e| PostgresPoolBuildError(e, id.clone()))?;
+        #[cfg(feature = "metrics")]
+        pool_metrics::register(&id, &pool);
         let mut res = Self {
             id: id.clone(),
             pool,
@@ -178,12 +180,29 @@ impl PostgresPool {
     ///
     /// See [`PostgresPoolConnError`] for details.
     pub async fn get(&self) -> PostgresResult<Object> {
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::POSTGRES_POOL_WAIT_SECONDS
+            .with_label_values(&[&self.id])
+            .start_timer();
         self.pool
             .get()
             .await
             .map_err(|e| PostgresPoolConnError(e, self.id.clone()))
     }
 
+    /// Checks that the database is reachable, using a connection of this pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no connection can be acquired, or the database does not respond.
+    pub async fn check_health(&self) -> PostgresResult<()> {
+        let conn = self.get().await?;
+        conn.simple_query("SELECT 1")
+            .await
+            .map_err(|e| PostgresError(e, "checking the connection"))?;
+        Ok(())
+    }
+
     /// ID under which this [`PostgresPool`] is identified externally
     #[must_use]
     pub fn get_id(&self) -> &str {
@@ -352,6 +371,90 @@ impl Drop for ActiveQueryGuard {
     }
 }
 
+/// Reports the connections of all pools whenever the metrics are scraped,
+/// so that the values do not go stale while the pools are idle.
+#[cfg(feature = "metrics")]
+mod pool_metrics {
+    use std::collections::HashMap;
+    use std::sync::{LazyLock, Mutex, PoisonError};
+
+    use deadpool_postgres::Pool;
+    use prometheus::core::{Collector, Desc};
+    use prometheus::proto::MetricFamily;
+    use prometheus::{IntGaugeVec, Opts};
+    use tracing::warn;
+
+    /// The latest pool of each ID, a reloaded pool replaces the previous one
+    static POOLS: LazyLock<Mutex<HashMap<String, Pool>>> = LazyLock::new(|| {
+        if let Err(e) = prometheus::register(Box::new(PoolCollector::new())) {
+            warn!("Unable to register the PostgreSQL pool metrics: {e}");
+        }
+        Mutex::default()
+    });
+
+    pub(super) fn register(id: &str, pool: &Pool) {
+        POOLS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id.to_owned(), pool.clone());
+    }
+
+    struct PoolCollector {
+        connections: IntGaugeVec,
+        max_connections: IntGaugeVec,
+    }
+
+    impl PoolCollector {
+        fn new() -> Self {
+            Self {
+                connections: IntGaugeVec::new(
+                    Opts::new(
+                        "martin_postgres_pool_connections",
+                        "Connections of the PostgreSQL pools, labeled by pool and state (in_use, idle)",
+                    ),
+                    &["pool", "state"],
+                )
+                .expect("static pool connections metric definition is valid"),
+                max_connections: IntGaugeVec::new(
+                    Opts::new(
+                        "martin_postgres_pool_max_connections",
+                        "Maximum number of connections of the PostgreSQL pools, labeled by pool",
+                    ),
+                    &["pool"],
+                )
+                .expect("static pool size metric definition is valid"),
+            }
+        }
+    }
+
+    impl Collector for PoolCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            let mut desc = self.connections.desc();
+            desc.extend(self.max_connections.desc());
+            desc
+        }
+
+        fn collect(&self) -> Vec<MetricFamily> {
+            let to_gauge = |v: usize| i64::try_from(v).unwrap_or(i64::MAX);
+            for (id, pool) in POOLS.lock().unwrap_or_else(PoisonError::into_inner).iter() {
+                let status = pool.status();
+                self.connections
+                    .with_label_values(&[id.as_str(), "in_use"])
+                    .set(to_gauge(status.size.saturating_sub(status.available)));
+                self.connections
+                    .with_label_values(&[id.as_str(), "idle"])
+                    .set(to_gauge(status.available));
+                self.max_connections
+                    .with_label_values(&[id.as_str()])
+                    .set(to_gauge(status.max_size));
+            }
+            let mut families = self.connections.collect();
+            families.extend(self.max_connections.collect());
+            families
+        }
+    }
+}
+
 #[cfg(test)]
 mod version_parsing_tests {
     use rstest::rstest;
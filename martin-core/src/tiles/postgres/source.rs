@@ -1,8 +1,11 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use deadpool_postgres::tokio_postgres::types::{ToSql, Type};
+use martin_tile_utils::tms::TileMatrixSet;
 use martin_tile_utils::{TileCoord, TileData, TileInfo};
 use tilejson::TileJSON;
-use tracing::{debug, instrument};
+use tracing::{Instrument as _, debug, debug_span, instrument};
 
 use crate::CacheZoomRange;
 use crate::tiles::postgres::PostgresError::{
@@ -21,10 +24,13 @@ pub struct PostgresSource {
     tilejson: TileJSON,
     tile_info: TileInfo,
     cache_zoom: CacheZoomRange,
+    tms: Arc<TileMatrixSet>,
 }
 
 impl PostgresSource {
     /// Creates a new `PostgreSQL` tile source.
+    ///
+    /// The SQL query must return tiles of the given tile matrix set.
     #[must_use]
     pub const fn new(
         id: String,
@@ -33,6 +39,7 @@ impl PostgresSource {
         pool: PostgresPool,
         tile_info: TileInfo,
         cache_zoom: CacheZoomRange,
+        tms: Arc<TileMatrixSet>,
     ) -> Self {
         Self {
             id,
@@ -41,6 +48,7 @@ impl PostgresSource {
             tilejson,
             tile_info,
             cache_zoom,
+            tms,
         }
     }
 }
@@ -63,6 +71,10 @@ impl Source for PostgresSource {
         Box::new(self.clone())
     }
 
+    fn get_tile_matrix_set(&self) -> &TileMatrixSet {
+        &self.tms
+    }
+
     fn support_url_query(&self) -> bool {
         self.info.use_url_query
     }
@@ -80,6 +92,14 @@ impl Source for PostgresSource {
         Some(self.pool.active_query_registry().clone())
     }
 
+    async fn check_health(&self) -> MartinCoreResult<()> {
+        Ok(self.pool.check_health().await?)
+    }
+
+    fn health_check_key(&self) -> Option<String> {
+        Some(format!("postgres:{}", self.pool.get_id()))
+    }
+
     #[instrument(
         level = "debug",
         skip_all,
@@ -110,8 +130,14 @@ impl Source for PostgresSource {
         };
 
         let sql = &self.info.sql_query;
+        let query_span = debug_span!(
+            "postgres_query",
+            db.system = "postgresql",
+            db.query.text = %sql,
+        );
         let prep_query = conn
             .prepare_typed_cached(sql, param_types)
+            .instrument(query_span.clone())
             .await
             .map_err(|e| PrepareQueryError {
                 source: e,
@@ -129,13 +155,16 @@ impl Source for PostgresSource {
                 &i64::from(xyz.y),
                 &json,
             ];
-            conn.query_opt(&prep_query, params).await
+            conn.query_opt(&prep_query, params)
+                .instrument(query_span)
+                .await
         } else {
             debug!("SQL: {sql} [{xyz}]");
             conn.query_opt(
                 &prep_query,
                 &[&i16::from(xyz.z), &i64::from(xyz.x), &i64::from(xyz.y)],
             )
+            .instrument(query_span)
             .await
         };
 
@@ -1,7 +1,11 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
+#[cfg(any(feature = "mbtiles", feature = "unstable-cog", feature = "geojson"))]
+use std::path::Path;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use martin_tile_utils::tms::TileMatrixSet;
 use martin_tile_utils::{TileCoord, TileData, TileInfo};
 use tilejson::TileJSON;
 
@@ -31,6 +35,11 @@ pub trait Source: Send + Sync + Debug {
     /// Creates a boxed clone for trait object storage.
     fn clone_source(&self) -> BoxedSource;
 
+    /// Tile matrix set the `z/x/y` tile coordinates refer to. Default: `WebMercatorQuad`.
+    fn get_tile_matrix_set(&self) -> &TileMatrixSet {
+        TileMatrixSet::web_mercator_quad_ref()
+    }
+
     /// A version string for this source, if available. Default: None.
     /// If available, this string is appended to tile URLs as a query parameter,
     /// invalidating caches.
@@ -38,6 +47,12 @@ pub trait Source: Send + Sync + Debug {
         None
     }
 
+    /// When the tiles of this source were last modified, e.g. the modification time of its file.
+    /// Used for `Last-Modified` response headers. Default: None.
+    fn get_last_modified(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
     /// Whether this source accepts URL query parameters. Default: false.
     fn support_url_query(&self) -> bool {
         false
@@ -94,6 +109,22 @@ pub trait Source: Send + Sync + Debug {
             && tj.maxzoom.is_none_or(|maxzoom| zoom <= maxzoom)
     }
 
+    /// Actively checks that the backend of this source is reachable,
+    /// e.g. its database or upstream server. Used by readiness checks.
+    ///
+    /// The default implementation reports the source as healthy.
+    async fn check_health(&self) -> MartinCoreResult<()> {
+        Ok(())
+    }
+
+    /// Identifies the backend checked by [`Self::check_health`], if other sources share it,
+    /// e.g. a database connection pool. Readiness checks check it once for all these sources.
+    ///
+    /// The default implementation checks every source on its own.
+    fn health_check_key(&self) -> Option<String> {
+        None
+    }
+
     /// Attempts to create a fresh instance of this source.
     ///
     /// Sources that return a `MartinCoreError::SourceNeedReload` from `get_tile()` must also
@@ -123,6 +154,15 @@ pub trait Source: Send + Sync + Debug {
     }
 }
 
+/// Modification time of a file, for [`Source::get_last_modified`] of file-based sources
+#[cfg(any(feature = "mbtiles", feature = "unstable-cog", feature = "geojson"))]
+pub(crate) fn file_modified(path: &Path) -> Option<DateTime<Utc>> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(DateTime::from)
+}
+
 /// Boxed tile source trait object for storage in collections.
 pub type BoxedSource = Box<dyn Source>;
 
@@ -1,7 +1,53 @@
+use std::time::Duration;
+
 use base64::Engine as _;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
 use martin_tile_utils::{TileData, TileInfo};
 
+/// Caching information a tile came with, e.g. from the HTTP headers of an upstream tile server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TileCacheHints {
+    /// Until when the tile may be served without asking the source again.
+    /// `None` if the source does not know, in which case the configured cache expiry applies.
+    pub fresh_until: Option<DateTime<Utc>>,
+    /// When the tile was last modified, if the source knows.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl TileCacheHints {
+    /// How much longer the tile stays fresh, zero if it is already stale.
+    #[must_use]
+    pub fn remaining(&self) -> Option<Duration> {
+        let fresh_until = self.fresh_until?;
+        Some(
+            (fresh_until - Utc::now())
+                .to_std()
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    /// Hints of a tile combined from several tiles: it is fresh while all of them are,
+    /// and was last modified with the latest of them, if all of them know.
+    #[must_use]
+    pub fn combine(hints: impl IntoIterator<Item = Self>) -> Self {
+        let mut hints = hints.into_iter();
+        let Some(first) = hints.next() else {
+            return Self::default();
+        };
+        hints.fold(first, |acc, h| Self {
+            fresh_until: match (acc.fresh_until, h.fresh_until) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+            last_modified: acc
+                .last_modified
+                .zip(h.last_modified)
+                .map(|(a, b)| a.max(b)),
+        })
+    }
+}
+
 /// Represents a single map tile with its raw data and metadata.
 ///
 /// Combines tile data (as raw bytes) with format and encoding information.
@@ -25,6 +71,8 @@ pub struct Tile {
     pub info: TileInfo,
     /// Pre-computed etag/hash for the tile data (empty for empty tiles)
     pub etag: String,
+    /// Caching information from the source, if any
+    pub hints: TileCacheHints,
 }
 
 impl Tile {
@@ -39,13 +87,28 @@ impl Tile {
             xxhash_rust::xxh3::xxh3_128(&data)
         };
         let etag = URL_SAFE_NO_PAD.encode(etag.to_ne_bytes());
-        Self { data, info, etag }
+        Self::new_with_etag(data, info, etag)
     }
 
     /// Creates a new tile with the given tile data, metadata, and etag.
     #[must_use]
     pub const fn new_with_etag(data: TileData, info: TileInfo, etag: String) -> Self {
-        Self { data, info, etag }
+        Self {
+            data,
+            info,
+            etag,
+            hints: TileCacheHints {
+                fresh_until: None,
+                last_modified: None,
+            },
+        }
+    }
+
+    /// Sets the caching information of the tile.
+    #[must_use]
+    pub const fn with_hints(mut self, hints: TileCacheHints) -> Self {
+        self.hints = hints;
+        self
     }
 
     /// Returns true if the tile data is empty.
@@ -54,3 +117,40 @@ impl Tile {
         self.data.is_empty()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn combined_hints() {
+        let now = Utc::now();
+        let early = now + TimeDelta::seconds(10);
+        let late = now + TimeDelta::seconds(60);
+        let hints = TileCacheHints::combine([
+            TileCacheHints {
+                fresh_until: Some(late),
+                last_modified: Some(early),
+            },
+            TileCacheHints {
+                fresh_until: Some(early),
+                last_modified: Some(late),
+            },
+            TileCacheHints::default(),
+        ]);
+        assert_eq!(hints.fresh_until, Some(early));
+        assert_eq!(hints.last_modified, None, "one of the tiles does not know");
+        assert_eq!(
+            TileCacheHints::combine([]).remaining(),
+            None,
+            "no freshness without tiles"
+        );
+        let stale = TileCacheHints {
+            fresh_until: Some(now - TimeDelta::seconds(1)),
+            last_modified: None,
+        };
+        assert_eq!(stale.remaining(), Some(Duration::ZERO));
+    }
+}
@@ -3,6 +3,7 @@
 
 use std::io::Write as _;
 use std::num::NonZeroU32;
+use std::sync::Arc;
 
 use geo_types::{Coord, Geometry, LineString, Polygon};
 use geojson::{
@@ -10,8 +11,10 @@ use geojson::{
 };
 use martin_core::CacheZoomRange;
 use martin_core::tiles::Source as _;
+use martin_core::tiles::geojson::GeoJsonError;
 use martin_core::tiles::geojson::source::GeoJsonSource;
 use martin_tile_utils::TileCoord;
+use martin_tile_utils::tms::TileMatrixSet;
 use mlt_core::fast_mvt::{MvtFeature, MvtReaderRef, MvtTile, MvtValue};
 use serde_json::{Map, json};
 
@@ -92,6 +95,19 @@ async fn source(id: &str, gj: &GeoJson) -> GeoJsonSource {
 
 /// Like [`source`] but with an explicit MVT extent and clip buffer.
 async fn source_with(id: &str, gj: &GeoJson, extent: NonZeroU32, buffer: u32) -> GeoJsonSource {
+    try_source_in(id, gj, TileMatrixSet::web_mercator_quad(), extent, buffer)
+        .await
+        .unwrap()
+}
+
+/// Build a source rendering tiles of the given tile matrix set.
+async fn try_source_in(
+    id: &str,
+    gj: &GeoJson,
+    tms: TileMatrixSet,
+    extent: NonZeroU32,
+    buffer: u32,
+) -> Result<GeoJsonSource, GeoJsonError> {
     let mut tmp = tempfile::Builder::new()
         .suffix(".geojson")
         .tempfile()
@@ -103,11 +119,11 @@ async fn source_with(id: &str, gj: &GeoJson, extent: NonZeroU32, buffer: u32) ->
         id.to_owned(),
         tmp.path().to_path_buf(),
         CacheZoomRange::default(),
+        Arc::new(tms),
         extent,
         buffer,
     )
     .await
-    .unwrap()
 }
 
 fn xyz(z: u8, x: u32, y: u32) -> TileCoord {
@@ -504,3 +520,40 @@ async fn buffer_includes_geometry_just_outside_the_tile() {
     let bytes = unbuffered.get_tile(xyz(1, 1, 0), None).await.unwrap();
     assert!(bytes.is_empty(), "zero buffer drops the just-outside point");
 }
+
+/// Tiles of a geographic tile matrix set are cut in longitude/latitude, without reprojection.
+#[tokio::test]
+async fn world_crs84_quad_tiles() {
+    let pt = GeoJson::Geometry(gj_point(90.0, 45.0));
+    let extent = NonZeroU32::new(4096).unwrap();
+    let src = try_source_in("crs84", &pt, TileMatrixSet::world_crs84_quad(), extent, 0)
+        .await
+        .unwrap();
+    assert_eq!(src.get_tile_matrix_set().id, "WorldCRS84Quad");
+
+    let bounds = src.get_tilejson().bounds.unwrap();
+    assert_eq!((bounds.left, bounds.top), (90.0, 45.0));
+
+    // z0/1/0 is the eastern hemisphere: lng 0..180, lat -90..90
+    let tile = decode(&src.get_tile(xyz(0, 1, 0), None).await.unwrap());
+    let coords = all_coords(&tile.layers[0].features[0].geometry);
+    assert_eq!(coords, [Coord { x: 2048, y: 1024 }]);
+
+    let bytes = src.get_tile(xyz(0, 0, 0), None).await.unwrap();
+    assert!(bytes.is_empty(), "the western hemisphere has no data");
+}
+
+/// `GeoJSON` can only be projected into Web Mercator and WGS84 tile matrix sets.
+#[tokio::test]
+async fn unsupported_tile_matrix_set_crs() {
+    let pt = GeoJson::Geometry(gj_point(8.0, 47.0));
+    let tms = TileMatrixSet {
+        id: "SwissGrid".to_owned(),
+        crs: "http://www.opengis.net/def/crs/EPSG/0/2056".to_owned(),
+        ..TileMatrixSet::web_mercator_quad()
+    };
+    let err = try_source_in("swiss", &pt, tms, NonZeroU32::new(4096).unwrap(), 0)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, GeoJsonError::UnsupportedTileMatrixSet(..)));
+}
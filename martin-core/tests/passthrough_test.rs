@@ -4,11 +4,14 @@
 use std::time::Duration;
 
 use martin_core::CacheZoomRange;
-use martin_core::tiles::Source as _;
-use martin_core::tiles::passthrough::{PassthroughSource, TemplateMeta, Transport, Upstream};
+use martin_core::tiles::passthrough::{
+    CircuitBreakerPolicy, MapService, MapServiceParams, MapServiceSet, PassthroughError,
+    PassthroughSource, Resilience, RetryPolicy, StalePolicy, TemplateMeta, Transport, Upstream,
+};
+use martin_core::tiles::{MartinCoreError, Source as _, TileCacheHints};
 use martin_tile_utils::{Encoding, Format, TileCoord};
 use rstest::rstest;
-use wiremock::matchers::{method, path};
+use wiremock::matchers::{header, method, path, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 fn coord(z: u8, x: u32, y: u32) -> TileCoord {
@@ -36,16 +39,30 @@ fn templates(server: &MockServer, format: Option<Format>) -> Upstream {
 }
 
 async fn build(id: &str, upstream: Upstream) -> PassthroughSource {
+    build_with(id, upstream, Resilience::default()).await
+}
+
+async fn build_with(id: &str, upstream: Upstream, resilience: Resilience) -> PassthroughSource {
     PassthroughSource::new(
         id.into(),
         upstream,
         Transport::new(Duration::from_secs(30)),
+        resilience,
         CacheZoomRange::default(),
     )
     .await
     .unwrap()
 }
 
+/// Retries without noticeable delays.
+fn fast_retries(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        min_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(1),
+    }
+}
+
 #[tokio::test]
 async fn serves_tile_bytes_with_detected_format() {
     let server = MockServer::start().await;
@@ -215,3 +232,324 @@ async fn issues_exactly_one_request_per_tile() {
     let src = build("t", templates(&server, Some(Format::Mvt))).await;
     src.get_tile(coord(0, 0, 0), None).await.unwrap();
 }
+
+#[tokio::test]
+async fn retries_transient_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"x".as_ref()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let resilience = Resilience {
+        retry: fast_retries(2),
+        ..Resilience::default()
+    };
+    let src = build_with("t", templates(&server, Some(Format::Mvt)), resilience).await;
+    assert_eq!(src.get_tile(coord(0, 0, 0), None).await.unwrap(), b"x");
+}
+
+#[tokio::test]
+async fn does_not_retry_client_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(403))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let resilience = Resilience {
+        retry: fast_retries(2),
+        ..Resilience::default()
+    };
+    let src = build_with("t", templates(&server, Some(Format::Mvt)), resilience).await;
+    src.get_tile(coord(0, 0, 0), None).await.unwrap_err();
+}
+
+#[tokio::test]
+async fn fails_over_to_mirror() {
+    let down = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(502))
+        .mount(&down)
+        .await;
+    let up = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"x".as_ref()))
+        .mount(&up)
+        .await;
+    let upstream = Upstream::from_config(
+        "t",
+        &[
+            format!("{}/{{z}}/{{x}}/{{y}}.pbf", down.uri()),
+            format!("{}/{{z}}/{{x}}/{{y}}.pbf", up.uri()),
+        ],
+        None,
+        empty_meta(),
+    )
+    .unwrap();
+    let resilience = Resilience {
+        retry: fast_retries(0),
+        ..Resilience::default()
+    };
+    let src = build_with("t", upstream, resilience).await;
+
+    // whichever mirror a tile is assigned to, it is served by the one that is up
+    for x in 0..4 {
+        assert_eq!(src.get_tile(coord(2, x, 0), None).await.unwrap(), b"x");
+    }
+}
+
+#[tokio::test]
+async fn open_circuit_fails_fast() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(2)
+        .mount(&server)
+        .await;
+    let resilience = Resilience {
+        retry: fast_retries(0),
+        circuit_breaker: Some(CircuitBreakerPolicy {
+            failure_threshold: 2,
+            open_duration: Duration::from_mins(1),
+        }),
+        serve_stale: None,
+    };
+    let src = build_with("t", templates(&server, Some(Format::Mvt)), resilience).await;
+    for _ in 0..2 {
+        src.get_tile(coord(0, 0, 0), None).await.unwrap_err();
+    }
+    let err = src.get_tile(coord(0, 0, 0), None).await.unwrap_err();
+    assert!(matches!(
+        err,
+        MartinCoreError::PassthroughError(PassthroughError::CircuitOpen(_))
+    ));
+    // clones share the state of the circuit
+    let err = src.clone_source().check_health().await.unwrap_err();
+    assert!(matches!(
+        err,
+        MartinCoreError::PassthroughError(PassthroughError::CircuitOpen(_))
+    ));
+}
+
+#[tokio::test]
+async fn serves_stale_tile_on_upstream_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"fresh".as_ref()))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    let resilience = Resilience {
+        retry: fast_retries(0),
+        circuit_breaker: None,
+        serve_stale: Some(StalePolicy {
+            max_size_bytes: 1024 * 1024,
+            max_age: Duration::from_mins(1),
+        }),
+    };
+    let src = build_with("t", templates(&server, Some(Format::Mvt)), resilience).await;
+    assert_eq!(src.get_tile(coord(0, 0, 0), None).await.unwrap(), b"fresh");
+    assert_eq!(src.get_tile(coord(0, 0, 0), None).await.unwrap(), b"fresh");
+
+    // tiles that were never fetched successfully still fail, and so does the health check
+    src.get_tile(coord(1, 0, 0), None).await.unwrap_err();
+    src.check_health().await.unwrap_err();
+}
+
+#[rstest]
+#[case::honored(true)]
+#[case::ignored(false)]
+#[tokio::test]
+async fn tile_hints_follow_cache_headers(#[case] honor_cache_headers: bool) {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "max-age=600")
+                .insert_header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+                .set_body_bytes(b"a".as_ref()),
+        )
+        .mount(&server)
+        .await;
+    let mut transport = Transport::new(Duration::from_secs(30));
+    transport.honor_cache_headers = honor_cache_headers;
+    let src = PassthroughSource::new(
+        "t".into(),
+        templates(&server, Some(Format::Mvt)),
+        transport,
+        Resilience::default(),
+        CacheZoomRange::default(),
+    )
+    .await
+    .unwrap();
+
+    let hints = src
+        .get_tile_with_etag(coord(0, 0, 0), None)
+        .await
+        .unwrap()
+        .hints;
+    if honor_cache_headers {
+        let remaining = hints.remaining().unwrap();
+        assert!(remaining > Duration::from_secs(590) && remaining <= Duration::from_mins(10));
+        assert!(hints.last_modified.is_some());
+    } else {
+        assert_eq!(hints, TileCacheHints::default());
+    }
+}
+
+#[tokio::test]
+async fn not_modified_refreshes_kept_tile() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304).insert_header("cache-control", "max-age=600"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .insert_header("cache-control", "no-cache")
+                .set_body_bytes(b"kept".as_ref()),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    let resilience = Resilience {
+        serve_stale: Some(StalePolicy {
+            max_size_bytes: 1024 * 1024,
+            max_age: Duration::from_mins(1),
+        }),
+        ..Resilience::default()
+    };
+    let src = build_with("t", templates(&server, Some(Format::Mvt)), resilience).await;
+
+    let first = src.get_tile_with_etag(coord(0, 0, 0), None).await.unwrap();
+    assert_eq!(first.hints.remaining(), Some(Duration::ZERO));
+    let revalidated = src.get_tile_with_etag(coord(0, 0, 0), None).await.unwrap();
+    assert_eq!(revalidated.data, b"kept");
+    assert_eq!(revalidated.etag, "v1");
+    assert!(revalidated.hints.remaining().unwrap() > Duration::from_secs(590));
+}
+
+fn map_service(server: &MockServer, service: MapService, metatile: u32) -> Upstream {
+    let set = MapServiceSet::new(
+        vec![format!("{}/service", server.uri())],
+        MapServiceParams {
+            service,
+            format: Format::Png,
+            transparent: true,
+            tile_size: 256,
+            metatile,
+        },
+        empty_meta(),
+    )
+    .unwrap();
+    Upstream::MapService(set)
+}
+
+/// A PNG image of `width` × `height` pixels.
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut data = std::io::Cursor::new(Vec::new());
+    image::RgbaImage::from_pixel(width, height, image::Rgba([10, 20, 30, 255]))
+        .write_to(&mut data, image::ImageFormat::Png)
+        .unwrap();
+    data.into_inner()
+}
+
+#[tokio::test]
+async fn wms_requests_tile_bbox() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/service"))
+        .and(query_param("REQUEST", "GetMap"))
+        .and(query_param("LAYERS", "roads"))
+        .and(query_param("CRS", "EPSG:3857"))
+        .and(query_param(
+            "BBOX",
+            "0,-20037508.34278925,20037508.34278925,0",
+        ))
+        .and(query_param("WIDTH", "256"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "image/png")
+                .set_body_bytes(png(256, 256)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    let service = MapService::Wms {
+        layers: vec!["roads".to_owned()],
+        styles: Vec::new(),
+        version: "1.3.0".to_owned(),
+    };
+    let src = build("t", map_service(&server, service, 1)).await;
+
+    let tile = src.get_tile_with_etag(coord(1, 1, 1), None).await.unwrap();
+    assert_eq!(tile.info.format, Format::Png);
+    assert_eq!(tile.data, png(256, 256));
+}
+
+#[tokio::test]
+async fn metatile_is_fetched_once() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/service/export"))
+        .and(query_param("size", "512,512"))
+        .and(query_param("f", "image"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "image/png")
+                .set_body_bytes(png(512, 512)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    let service = MapService::ArcGis { layers: None };
+    let src = build("t", map_service(&server, service, 2)).await;
+
+    let tiles = futures::future::join_all(
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| src.get_tile(coord(1, x, y), None)),
+    )
+    .await;
+    for tile in tiles {
+        let tile = image::load_from_memory(&tile.unwrap()).unwrap();
+        assert_eq!((tile.width(), tile.height()), (256, 256));
+    }
+}
+
+#[tokio::test]
+async fn service_exception_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/vnd.ogc.se_xml")
+                .set_body_string("<ServiceException>Layer roads is not defined</ServiceException>"),
+        )
+        .mount(&server)
+        .await;
+    let service = MapService::Wms {
+        layers: vec!["roads".to_owned()],
+        styles: Vec::new(),
+        version: "1.3.0".to_owned(),
+    };
+    let src = build("t", map_service(&server, service, 1)).await;
+
+    let err = src.get_tile(coord(0, 0, 0), None).await.unwrap_err();
+    assert!(
+        err.to_string().contains("Layer roads is not defined"),
+        "{err}"
+    );
+}
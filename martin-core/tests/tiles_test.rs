@@ -5,7 +5,8 @@
 use std::convert::Infallible;
 use std::time::Duration;
 
-use martin_core::tiles::{Tile, TileCache, TileCacheKey};
+use chrono::{TimeDelta, Utc};
+use martin_core::tiles::{Tile, TileCache, TileCacheHints, TileCacheKey};
 use martin_tile_utils::{Encoding, Format, TileCoord, TileInfo};
 
 const CACHE_SIZE: u64 = 10 * 1024 * 1024;
@@ -100,6 +101,35 @@ async fn query_params_create_separate_cache_entries_with_same_ttl() {
     assert_miss(&cache, "src", ORIGIN, Some("filter=foo"), b"filtered-new").await;
 }
 
+#[tokio::test]
+async fn tile_freshness_overrides_ttl() {
+    let ttl = Duration::from_millis(25);
+    let cache = TileCache::new(CACHE_SIZE, Some(ttl), None);
+    let fresh_for = |duration| TileCacheHints {
+        fresh_until: Some(Utc::now() + duration),
+        last_modified: None,
+    };
+    let long = ORIGIN;
+    let short = TileCoord { z: 1, x: 0, y: 0 };
+    for (xyz, hints) in [
+        (long, fresh_for(TimeDelta::hours(1))),
+        (short, fresh_for(TimeDelta::zero())),
+    ] {
+        let tile = test_tile(b"upstream").with_hints(hints);
+        cache
+            .get_or_insert(key("src", xyz, None, None), async || {
+                Ok::<_, Infallible>(tile)
+            })
+            .await
+            .unwrap();
+    }
+
+    wait_and_flush(&cache, ttl + Duration::from_millis(25)).await;
+
+    assert_hit(&cache, "src", long).await;
+    assert_miss(&cache, "src", short, None, b"refreshed").await;
+}
+
 fn test_tile(data: &[u8]) -> Tile {
     Tile::new_hash_etag(
         data.to_vec(),
@@ -19,8 +19,10 @@ pub const MAX_ZOOM: u8 = 30;
 
 mod decoders;
 pub use decoders::*;
+pub mod protobuf;
 mod rectangle;
 pub use rectangle::{TileRect, append_rect};
+pub mod tms;
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub struct TileCoord {
@@ -286,7 +288,11 @@ impl TileInfo {
     #[must_use]
     fn detect_vectorish_format(value: &[u8]) -> Format {
         match value {
-            v if decode_7bit_length_and_tag(v, &[0x1]).is_ok() => Format::Mlt,
+            v if decode_varint_length_and_tag(v, &[0x1]).is_ok()
+                || decode_7bit_length_and_tag(v, &[0x1]).is_ok() =>
+            {
+                Format::Mlt
+            }
             v if is_valid_json(v) => Format::Json,
             // If we can't detect the format, we assume MVT.
             // Reasoning:
@@ -406,6 +412,51 @@ fn decode_7bit_length_and_tag(tile: &[u8], versions: &[u8]) -> Result<(), SevenB
     Ok(())
 }
 
+/// Tries to validate that the tile consists of a valid concatenation of (`size_varint`, `one_of_expected_version`, `data`),
+/// the layer framing written by `mlt-core`: the size is a little-endian base-128 varint counting the version and data bytes.
+fn decode_varint_length_and_tag(tile: &[u8], versions: &[u8]) -> Result<(), SevenBitDecodingError> {
+    if tile.is_empty() {
+        return Err(SevenBitDecodingError::TruncatedSize);
+    }
+    let mut rest = tile;
+    while !rest.is_empty() {
+        let mut size = 0_u64;
+        let mut shift = 0;
+        loop {
+            let Some((&b, tail)) = rest.split_first() else {
+                return Err(SevenBitDecodingError::TruncatedSize);
+            };
+            rest = tail;
+            if shift + 7 > 64 {
+                return Err(SevenBitDecodingError::SizeOverflow);
+            }
+            size |= u64::from(b & 0x7f) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        let Some((tag, tail)) = rest.split_first() else {
+            return Err(SevenBitDecodingError::TruncatedTag);
+        };
+        if !versions.contains(tag) {
+            return Err(SevenBitDecodingError::UnexpectedTag(*tag));
+        }
+        let payload_len = size
+            .checked_sub(1)
+            .ok_or(SevenBitDecodingError::SizeUnderflow)?;
+        let available = tail.len() as u64;
+        if available < payload_len {
+            return Err(SevenBitDecodingError::TruncatedData {
+                expected: payload_len,
+                actual: available,
+            });
+        }
+        rest = &tail[usize::try_from(payload_len).unwrap_or(usize::MAX)..];
+    }
+    Ok(())
+}
+
 /// Detects if the given tile is a valid JSON tile.
 ///
 /// The check for a dictionary is used to speed up the validation process.
@@ -436,16 +487,38 @@ pub fn tile_index(lng: f64, lat: f64, zoom: u8) -> (u32, u32) {
 /// Panics if `zoom` is greater than [`MAX_ZOOM`].
 #[must_use]
 pub fn xyz_to_bbox(zoom: u8, min_x: u32, min_y: u32, max_x: u32, max_y: u32) -> [f64; 4] {
+    let [left, bottom, right, top] = xyz_to_webmercator_bbox(zoom, min_x, min_y, max_x, max_y);
+    let (min_lng, min_lat) = webmercator_to_wgs84(left, bottom);
+    let (max_lng, max_lat) = webmercator_to_wgs84(right, top);
+    [min_lng, min_lat, max_lng, max_lat]
+}
+
+/// Convert min/max XYZ tile coordinates to a [`WebMercator`](https://epsg.io/3857) bounding box.
+///
+/// The result is `[min_x, min_y, max_x, max_y]` in meters.
+///
+/// # Panics
+/// Panics if `zoom` is greater than [`MAX_ZOOM`].
+#[must_use]
+pub fn xyz_to_webmercator_bbox(
+    zoom: u8,
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
+) -> [f64; 4] {
     assert!(zoom <= MAX_ZOOM, "zoom {zoom} must be <= {MAX_ZOOM}");
 
     let tile_length = EARTH_CIRCUMFERENCE / f64::from(1_u32 << zoom);
 
     let left_down_bbox = tile_bbox(min_x, max_y, tile_length);
     let right_top_bbox = tile_bbox(max_x, min_y, tile_length);
-
-    let (min_lng, min_lat) = webmercator_to_wgs84(left_down_bbox[0], left_down_bbox[1]);
-    let (max_lng, max_lat) = webmercator_to_wgs84(right_top_bbox[2], right_top_bbox[3]);
-    [min_lng, min_lat, max_lng, max_lat]
+    [
+        left_down_bbox[0],
+        left_down_bbox[1],
+        right_top_bbox[2],
+        right_top_bbox[3],
+    ]
 }
 
 #[expect(clippy::cast_lossless)]
@@ -648,6 +721,35 @@ mod tests {
         assert_eq!(decoded, expected, "can decode two layers correctly");
     }
 
+    #[rstest]
+    #[case::minimal_tile(&[0x01, 0x01], Ok(()))]
+    #[case::one_byte_length(&[0x02, 0x01, 0xaa], Ok(()))]
+    #[case::two_byte_length(&[0x82, 0x00, 0x01, 0xaa], Ok(()))]
+    #[case::little_endian_length(&[0x82, 0x01, 0x01], Err(SevenBitDecodingError::TruncatedData { expected: 129, actual: 0 }))]
+    #[case::wrong_version(&[0x02, 0x02, 0xaa], Err(SevenBitDecodingError::UnexpectedTag(0x02)))]
+    #[case::empty_input(&[], Err(SevenBitDecodingError::TruncatedSize))]
+    #[case::size_overflow(&[0xFF; 64], Err(SevenBitDecodingError::SizeOverflow))]
+    #[case::size_underflow(&[0x00, 0x01], Err(SevenBitDecodingError::SizeUnderflow))]
+    #[case::unterminated_length(&[0x80], Err(SevenBitDecodingError::TruncatedSize))]
+    #[case::missing_version_byte(&[0x05], Err(SevenBitDecodingError::TruncatedTag))]
+    #[case::wrong_length(&[0x02, 0x01], Err(SevenBitDecodingError::TruncatedData { expected: 1, actual: 0 }))]
+    fn test_decode_varint_length_and_tag(
+        #[case] tile: &[u8],
+        #[case] expected: Result<(), SevenBitDecodingError>,
+    ) {
+        let allowed_versions = &[0x01_u8];
+        let decoded = decode_varint_length_and_tag(tile, allowed_versions);
+        assert_eq!(decoded, expected, "can decode one layer correctly");
+
+        if tile.is_empty() {
+            return;
+        }
+        let mut tile_with_two_layers = vec![0x01, 0x01];
+        tile_with_two_layers.extend_from_slice(tile);
+        let decoded = decode_varint_length_and_tag(&tile_with_two_layers, allowed_versions);
+        assert_eq!(decoded, expected, "can decode two layers correctly");
+    }
+
     #[rstest]
     #[case(-180.0, 85.0511, 0, (0,0))]
     #[case(-180.0, 85.0511, 1, (0,0))]
@@ -692,6 +794,24 @@ mod tests {
         assert_relative_eq!(bbox[3], expected[3], epsilon = f64::EPSILON * 2.0);
     }
 
+    #[rstest]
+    #[case(0, 0, 0, 0, 0, [-20_037_508.342_789_25, -20_037_508.342_789_25, 20_037_508.342_789_25, 20_037_508.342_789_25])]
+    #[case(2, 0, 0, 1, 1, [-20_037_508.342_789_25, 0.0, 0.0, 20_037_508.342_789_25])]
+    #[case(2, 2, 3, 3, 3, [0.0, -20_037_508.342_789_25, 20_037_508.342_789_25, -10_018_754.171_394_625])]
+    fn test_xyz_to_webmercator_bbox(
+        #[case] zoom: u8,
+        #[case] min_x: u32,
+        #[case] min_y: u32,
+        #[case] max_x: u32,
+        #[case] max_y: u32,
+        #[case] expected: [f64; 4],
+    ) {
+        let bbox = xyz_to_webmercator_bbox(zoom, min_x, min_y, max_x, max_y);
+        for (actual, expected) in bbox.into_iter().zip(expected) {
+            assert_relative_eq!(actual, expected, epsilon = f64::EPSILON * 2.0);
+        }
+    }
+
     #[rstest]
     #[case(0, 0, 0, [-20_037_508.342_789_25, -20_037_508.342_789_25, 20_037_508.342_789_25, 20_037_508.342_789_25])]
     #[case(1, 0, 0, [-20_037_508.342_789_25, 0.0, 0.0, 20_037_508.342_789_25])]
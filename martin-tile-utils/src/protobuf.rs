@@ -0,0 +1,182 @@
+//! Just enough of the [protobuf wire format](https://protobuf.dev/programming-guides/encoding/)
+//! to walk the messages of vector tiles field by field, and to write them back.
+//!
+//! Fields are returned together with their encoded bytes,
+//! so that the fields a caller does not rewrite can be copied verbatim.
+
+/// Wire type of varint fields.
+pub const WIRE_VARINT: u64 = 0;
+/// Wire type of 64-bit fields.
+pub const WIRE_FIXED64: u64 = 1;
+/// Wire type of length-delimited fields: strings, bytes, embedded messages and packed fields.
+pub const WIRE_LEN: u64 = 2;
+/// Wire type of 32-bit fields.
+pub const WIRE_FIXED32: u64 = 5;
+
+/// A malformed protobuf message, with the reason it could not be read.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("{0}")]
+pub struct ProtobufError(pub &'static str);
+
+/// A field value of a protobuf message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Value<'a> {
+    /// A varint field.
+    Varint(u64),
+    /// A length-delimited field.
+    Bytes(&'a [u8]),
+    /// A fixed-size field, whose value is not needed by any caller.
+    Fixed,
+}
+
+/// A field of a protobuf message: its number, value and encoded bytes.
+pub type Field<'a> = (u32, Value<'a>, &'a [u8]);
+
+/// Reads protobuf fields and varints, also used for the varint-prefixed layers of MLT tiles.
+#[derive(Clone, Debug)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Reads `data` from its start.
+    #[must_use]
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Whether everything has been read.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// Number of bytes read so far.
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Reads a varint.
+    pub fn varint(&mut self) -> Result<u64, ProtobufError> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or(ProtobufError("truncated varint"))?;
+            self.pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ProtobufError("varint is too long"))
+    }
+
+    /// Reads the next `len` bytes.
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], ProtobufError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(ProtobufError("truncated field"))?;
+        let value = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(value)
+    }
+
+    /// Reads the next field, returning its number, value and encoded bytes.
+    pub fn next_field(&mut self) -> Result<Option<Field<'a>>, ProtobufError> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let start = self.pos;
+        let key = self.varint()?;
+        let field =
+            u32::try_from(key >> 3).map_err(|_err| ProtobufError("field number is too large"))?;
+        let value = match key & 0x7 {
+            WIRE_VARINT => Value::Varint(self.varint()?),
+            WIRE_FIXED64 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            WIRE_LEN => {
+                let len = usize::try_from(self.varint()?)
+                    .map_err(|_err| ProtobufError("field is too long"))?;
+                Value::Bytes(self.take(len)?)
+            }
+            WIRE_FIXED32 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            _ => return Err(ProtobufError("unsupported wire type")),
+        };
+        Ok(Some((field, value, &self.data[start..self.pos])))
+    }
+}
+
+/// Appends `value` as a varint.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "only the low 7 bits of each byte are kept"
+)]
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Appends a varint field.
+pub fn write_varint_field(out: &mut Vec<u8>, field: u32, value: u64) {
+    write_varint(out, (u64::from(field) << 3) | WIRE_VARINT);
+    write_varint(out, value);
+}
+
+/// Appends a length-delimited field.
+pub fn write_bytes(out: &mut Vec<u8>, field: u32, value: &[u8]) {
+    write_varint(out, (u64::from(field) << 3) | WIRE_LEN);
+    write_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_fields() {
+        let mut out = Vec::new();
+        write_varint_field(&mut out, 1, 300);
+        write_bytes(&mut out, 2, b"abc");
+
+        let mut reader = Reader::new(&out);
+        assert_eq!(
+            reader.next_field().unwrap(),
+            Some((1, Value::Varint(300), &out[..3]))
+        );
+        assert_eq!(
+            reader.next_field().unwrap(),
+            Some((2, Value::Bytes(b"abc"), &out[3..]))
+        );
+        assert_eq!(reader.next_field().unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        let mut out = Vec::new();
+        write_bytes(&mut out, 2, b"abc");
+        out.pop();
+        assert_eq!(
+            Reader::new(&out).next_field(),
+            Err(ProtobufError("truncated field"))
+        );
+        assert_eq!(
+            Reader::new(&[0x80]).varint(),
+            Err(ProtobufError("truncated varint"))
+        );
+    }
+}
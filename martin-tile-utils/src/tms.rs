@@ -0,0 +1,582 @@
+//! Tile matrix set definitions following the
+//! [OGC Two Dimensional Tile Matrix Set](https://docs.ogc.org/is/17-083r4/17-083r4.html) standard.
+//!
+//! The structures (de)serialize to the OGC TMS 2.0 JSON encoding, so they can be loaded from
+//! standard definition files and served as-is by OGC API - Tiles endpoints.
+//!
+//! Tile coordinates use the index of the tile matrix in [`TileMatrixSet::tile_matrices`] as the zoom level,
+//! the tile column as `x`, and the tile row as `y`.
+//! For [`WEB_MERCATOR_QUAD`] this is the usual XYZ tiling scheme.
+
+use std::ops::RangeInclusive;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    EARTH_CIRCUMFERENCE, MAX_ZOOM, TileCoord, TileRect, webmercator_to_wgs84, wgs84_to_webmercator,
+};
+
+/// Size of a rendering pixel in meters, as defined by the OGC standards (0.28mm)
+pub const OGC_PIXEL_SIZE: f64 = 0.000_28;
+
+/// Identifier of the spherical mercator tile matrix set used by most web maps
+pub const WEB_MERCATOR_QUAD: &str = "WebMercatorQuad";
+
+/// Identifier of the geographic (longitude/latitude) tile matrix set with two tiles at zoom 0
+pub const WORLD_CRS84_QUAD: &str = "WorldCRS84Quad";
+
+/// URI of the WGS84 longitude/latitude coordinate reference system
+pub const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+
+/// Highest latitude representable in Web Mercator
+const MAX_MERCATOR_LAT: f64 = 85.051_128_779_806_59;
+
+static WEB_MERCATOR_QUAD_TMS: LazyLock<TileMatrixSet> =
+    LazyLock::new(TileMatrixSet::web_mercator_quad);
+
+/// Errors of custom tile matrix set definitions
+#[derive(thiserror::Error, Debug)]
+pub enum TileMatrixSetError {
+    #[error("Unable to parse tile matrix set: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Tile matrix set {0} has no tile matrices")]
+    NoTileMatrices(String),
+    #[error("Tile matrix set {0} has {1} tile matrices, at most {max} are supported", max = MAX_ZOOM + 1)]
+    TooManyTileMatrices(String, usize),
+    #[error(
+        "Tile matrix {1} of tile matrix set {0} must have a positive cell size and tile and matrix dimensions"
+    )]
+    InvalidTileMatrix(String, String),
+}
+
+/// A tile matrix set: a coordinate reference system and a list of tile matrices, one per zoom level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TileMatrixSet {
+    /// Short identifier, e.g. `WebMercatorQuad`
+    pub id: String,
+    /// Human readable title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Canonical URI of the tile matrix set definition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    /// Coordinate reference system URI
+    #[serde(deserialize_with = "deserialize_crs")]
+    pub crs: String,
+    /// Axis names in the order used by `point_of_origin`
+    #[serde(default)]
+    pub ordered_axes: Vec<String>,
+    /// Well known scale set this tile matrix set is compatible with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub well_known_scale_set: Option<String>,
+    /// Tile matrices, ordered from the coarsest to the finest
+    pub tile_matrices: Vec<TileMatrix>,
+}
+
+/// A single zoom level of a [`TileMatrixSet`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TileMatrix {
+    /// Identifier of the matrix, equal to the zoom level for the built-in sets
+    pub id: String,
+    /// Scale denominator assuming the standardized 0.28mm pixel size
+    pub scale_denominator: f64,
+    /// Size of a pixel in CRS units
+    pub cell_size: f64,
+    /// Corner of the matrix where tile row and column numbering starts
+    #[serde(default)]
+    pub corner_of_origin: CornerOfOrigin,
+    /// Position of the corner of origin in CRS units, in the order of [`TileMatrixSet::ordered_axes`]
+    pub point_of_origin: [f64; 2],
+    /// Width of a tile in pixels
+    pub tile_width: u32,
+    /// Height of a tile in pixels
+    pub tile_height: u32,
+    /// Number of tile columns
+    pub matrix_width: u32,
+    /// Number of tile rows
+    pub matrix_height: u32,
+}
+
+/// Corner of a [`TileMatrix`] used as the origin of tile rows and columns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CornerOfOrigin {
+    /// Rows grow downwards from the top left corner
+    #[default]
+    TopLeft,
+    /// Rows grow upwards from the bottom left corner
+    BottomLeft,
+}
+
+/// TMS 2.0 allows the CRS to be either a URI string or an object with a `uri` member
+fn deserialize_crs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Crs {
+        Uri(String),
+        Obj { uri: String },
+    }
+    Ok(match Crs::deserialize(deserializer)? {
+        Crs::Uri(uri) | Crs::Obj { uri } => uri,
+    })
+}
+
+impl TileMatrix {
+    /// Width of a tile in CRS units
+    #[must_use]
+    pub fn tile_span_x(&self) -> f64 {
+        self.cell_size * f64::from(self.tile_width)
+    }
+
+    /// Height of a tile in CRS units
+    #[must_use]
+    pub fn tile_span_y(&self) -> f64 {
+        self.cell_size * f64::from(self.tile_height)
+    }
+}
+
+impl TileMatrixSet {
+    /// The `WebMercatorQuad` tile matrix set (EPSG:3857, 256px tiles),
+    /// with a tile matrix for every zoom level up to [`MAX_ZOOM`].
+    ///
+    /// Tile matrix `z`, row `y`, column `x` is the same tile as `z/x/y` in the XYZ scheme.
+    #[must_use]
+    pub fn web_mercator_quad() -> Self {
+        let origin = EARTH_CIRCUMFERENCE / 2.0;
+        let tile_matrices = (0..=MAX_ZOOM)
+            .map(|zoom| {
+                let size = 1_u32 << zoom;
+                let cell_size = EARTH_CIRCUMFERENCE / 256.0 / f64::from(size);
+                TileMatrix {
+                    id: zoom.to_string(),
+                    scale_denominator: cell_size / OGC_PIXEL_SIZE,
+                    cell_size,
+                    corner_of_origin: CornerOfOrigin::TopLeft,
+                    point_of_origin: [-origin, origin],
+                    tile_width: 256,
+                    tile_height: 256,
+                    matrix_width: size,
+                    matrix_height: size,
+                }
+            })
+            .collect();
+
+        Self {
+            id: WEB_MERCATOR_QUAD.to_owned(),
+            title: Some("Google Maps Compatible for the World".to_owned()),
+            uri: Some(
+                "http://www.opengis.net/def/tilematrixset/OGC/1.0/WebMercatorQuad".to_owned(),
+            ),
+            crs: "http://www.opengis.net/def/crs/EPSG/0/3857".to_owned(),
+            ordered_axes: vec!["X".to_owned(), "Y".to_owned()],
+            well_known_scale_set: Some(
+                "http://www.opengis.net/def/wkss/OGC/1.0/GoogleMapsCompatible".to_owned(),
+            ),
+            tile_matrices,
+        }
+    }
+
+    /// The `WorldCRS84Quad` tile matrix set (CRS84 longitude/latitude, 256px tiles),
+    /// with two tiles covering the world at zoom 0 and a tile matrix for every zoom level up to [`MAX_ZOOM`].
+    #[must_use]
+    pub fn world_crs84_quad() -> Self {
+        let meters_per_degree = EARTH_CIRCUMFERENCE / 360.0;
+        let tile_matrices = (0..=MAX_ZOOM)
+            .map(|zoom| {
+                let size = 1_u32 << zoom;
+                let cell_size = 180.0 / 256.0 / f64::from(size);
+                TileMatrix {
+                    id: zoom.to_string(),
+                    scale_denominator: cell_size * meters_per_degree / OGC_PIXEL_SIZE,
+                    cell_size,
+                    corner_of_origin: CornerOfOrigin::TopLeft,
+                    point_of_origin: [-180.0, 90.0],
+                    tile_width: 256,
+                    tile_height: 256,
+                    matrix_width: size * 2,
+                    matrix_height: size,
+                }
+            })
+            .collect();
+
+        Self {
+            id: WORLD_CRS84_QUAD.to_owned(),
+            title: Some("CRS84 for the World".to_owned()),
+            uri: Some("http://www.opengis.net/def/tilematrixset/OGC/1.0/WorldCRS84Quad".to_owned()),
+            crs: CRS84.to_owned(),
+            ordered_axes: vec!["Lon".to_owned(), "Lat".to_owned()],
+            well_known_scale_set: None,
+            tile_matrices,
+        }
+    }
+
+    /// A shared instance of [`Self::web_mercator_quad`], the tile matrix set used unless configured otherwise
+    #[must_use]
+    pub fn web_mercator_quad_ref() -> &'static Self {
+        &WEB_MERCATOR_QUAD_TMS
+    }
+
+    /// Get one of the built-in tile matrix sets by its identifier
+    #[must_use]
+    pub fn builtin(id: &str) -> Option<Self> {
+        match id {
+            WEB_MERCATOR_QUAD => Some(Self::web_mercator_quad()),
+            WORLD_CRS84_QUAD => Some(Self::world_crs84_quad()),
+            _ => None,
+        }
+    }
+
+    /// Parse and validate an OGC TMS 2.0 JSON definition
+    pub fn from_json(json: &str) -> Result<Self, TileMatrixSetError> {
+        let tms: Self = serde_json::from_str(json)?;
+        tms.validate()?;
+        Ok(tms)
+    }
+
+    /// Make sure tile coordinates can be computed for every tile matrix
+    pub fn validate(&self) -> Result<(), TileMatrixSetError> {
+        if self.tile_matrices.is_empty() {
+            return Err(TileMatrixSetError::NoTileMatrices(self.id.clone()));
+        }
+        if self.tile_matrices.len() > usize::from(MAX_ZOOM) + 1 {
+            return Err(TileMatrixSetError::TooManyTileMatrices(
+                self.id.clone(),
+                self.tile_matrices.len(),
+            ));
+        }
+        for m in &self.tile_matrices {
+            if !m.cell_size.is_finite()
+                || m.cell_size <= 0.0
+                || m.tile_width == 0
+                || m.tile_height == 0
+                || m.matrix_width == 0
+                || m.matrix_height == 0
+            {
+                return Err(TileMatrixSetError::InvalidTileMatrix(
+                    self.id.clone(),
+                    m.id.clone(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up a tile matrix by its identifier
+    #[must_use]
+    pub fn tile_matrix(&self, id: &str) -> Option<&TileMatrix> {
+        self.tile_matrices.iter().find(|m| m.id == id)
+    }
+
+    /// Look up the tile matrix of a zoom level
+    #[must_use]
+    pub fn tile_matrix_at(&self, zoom: u8) -> Option<&TileMatrix> {
+        self.tile_matrices.get(usize::from(zoom))
+    }
+
+    /// The highest zoom level of this tile matrix set
+    #[must_use]
+    pub fn max_zoom(&self) -> u8 {
+        u8::try_from(self.tile_matrices.len().saturating_sub(1)).unwrap_or(MAX_ZOOM)
+    }
+
+    /// Check if a tile exists in this tile matrix set
+    #[must_use]
+    pub fn contains(&self, xyz: TileCoord) -> bool {
+        self.tile_matrix_at(xyz.z)
+            .is_some_and(|m| xyz.x < m.matrix_width && xyz.y < m.matrix_height)
+    }
+
+    /// EPSG code of the coordinate reference system, if it can be determined from its URI.
+    /// CRS84 is reported as 4326.
+    #[must_use]
+    pub fn srid(&self) -> Option<i32> {
+        if self.crs.ends_with("CRS84") {
+            return Some(4326);
+        }
+        if !self.crs.contains("EPSG") {
+            return None;
+        }
+        self.crs
+            .rsplit(['/', ':'])
+            .next()
+            .and_then(|code| code.parse().ok())
+    }
+
+    /// Whether the coordinate reference system is in longitude/latitude degrees
+    #[must_use]
+    pub fn is_geographic(&self) -> bool {
+        self.srid() == Some(4326)
+    }
+
+    /// Whether the first axis of `point_of_origin` is the northing, e.g. for EPSG:4326 (latitude, longitude)
+    fn is_northing_first(&self) -> bool {
+        self.ordered_axes.first().is_some_and(|axis| {
+            ["lat", "latitude", "n", "northing"]
+                .iter()
+                .any(|name| axis.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// Corner of origin of a tile matrix as `(easting, northing)`
+    fn origin(&self, matrix: &TileMatrix) -> (f64, f64) {
+        let [a, b] = matrix.point_of_origin;
+        if self.is_northing_first() {
+            (b, a)
+        } else {
+            (a, b)
+        }
+    }
+
+    /// Project a WGS84 coordinate into the coordinate reference system of this tile matrix set,
+    /// as `(easting, northing)`.
+    ///
+    /// Only Web Mercator and geographic coordinate reference systems are supported,
+    /// `None` is returned for any other.
+    #[must_use]
+    pub fn wgs84_to_crs(&self, lon: f64, lat: f64) -> Option<(f64, f64)> {
+        match self.srid()? {
+            3857 => Some(wgs84_to_webmercator(
+                lon,
+                lat.clamp(-MAX_MERCATOR_LAT, MAX_MERCATOR_LAT),
+            )),
+            4326 => Some((lon, lat)),
+            _ => None,
+        }
+    }
+
+    /// Inverse of [`Self::wgs84_to_crs`]
+    #[must_use]
+    pub fn crs_to_wgs84(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        match self.srid()? {
+            3857 => Some(webmercator_to_wgs84(x, y)),
+            4326 => Some((x, y)),
+            _ => None,
+        }
+    }
+
+    /// Bounds of a tile as `[min_x, min_y, max_x, max_y]` in the coordinate reference system
+    /// of this tile matrix set, with the easting first. `None` if the zoom level does not exist.
+    #[must_use]
+    pub fn tile_bounds(&self, xyz: TileCoord) -> Option<[f64; 4]> {
+        let m = self.tile_matrix_at(xyz.z)?;
+        let (ox, oy) = self.origin(m);
+        let (sx, sy) = (m.tile_span_x(), m.tile_span_y());
+        let min_x = f64::from(xyz.x).mul_add(sx, ox);
+        Some(match m.corner_of_origin {
+            CornerOfOrigin::TopLeft => {
+                let max_y = f64::from(xyz.y).mul_add(-sy, oy);
+                [min_x, max_y - sy, min_x + sx, max_y]
+            }
+            CornerOfOrigin::BottomLeft => {
+                let min_y = f64::from(xyz.y).mul_add(sy, oy);
+                [min_x, min_y, min_x + sx, min_y + sy]
+            }
+        })
+    }
+
+    /// Tiles covering a WGS84 bounding box `[left, bottom, right, top]` for each zoom level in `zooms`.
+    ///
+    /// Zoom levels without a tile matrix in this set are skipped.
+    /// Tile rows are counted from the corner of origin, matching the OGC `TileMatrixSetLimits`.
+    /// If the bounding box cannot be projected into the coordinate reference system
+    /// (see [`Self::wgs84_to_crs`]), the whole tile matrix is returned.
+    #[must_use]
+    pub fn limits(&self, bbox: [f64; 4], zooms: RangeInclusive<u8>) -> Vec<TileRect> {
+        let [left, bottom, right, top] = bbox;
+        let projected = self
+            .wgs84_to_crs(left, bottom)
+            .zip(self.wgs84_to_crs(right, top));
+        zooms
+            .filter_map(|zoom| {
+                let m = self.tile_matrix_at(zoom)?;
+                let Some(((min_x, min_y), (max_x, max_y))) = projected else {
+                    return Some(TileRect::new(
+                        zoom,
+                        0,
+                        0,
+                        m.matrix_width - 1,
+                        m.matrix_height - 1,
+                    ));
+                };
+                let (ox, oy) = self.origin(m);
+                let (sx, sy) = (m.tile_span_x(), m.tile_span_y());
+                let (row_min, row_max) = match m.corner_of_origin {
+                    CornerOfOrigin::TopLeft => ((oy - max_y) / sy, (oy - min_y) / sy),
+                    CornerOfOrigin::BottomLeft => ((min_y - oy) / sy, (max_y - oy) / sy),
+                };
+                Some(TileRect::new(
+                    zoom,
+                    tile_index((min_x - ox) / sx, m.matrix_width),
+                    tile_index(row_min, m.matrix_height),
+                    tile_index((max_x - ox) / sx, m.matrix_width),
+                    tile_index(row_max, m.matrix_height),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Index of the tile containing a fractional tile position, clamped to the matrix
+#[expect(clippy::cast_possible_truncation)]
+#[expect(clippy::cast_sign_loss)]
+fn tile_index(position: f64, size: u32) -> u32 {
+    if position.is_nan() || position <= 0.0 {
+        0
+    } else {
+        position.floor().min(f64::from(size - 1)) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn web_mercator_quad() {
+        let tms = TileMatrixSet::web_mercator_quad();
+        assert_eq!(tms.tile_matrices.len(), usize::from(MAX_ZOOM) + 1);
+
+        let z0 = tms.tile_matrix("0").unwrap();
+        assert_eq!((z0.matrix_width, z0.matrix_height), (1, 1));
+        assert_relative_eq!(z0.cell_size, 156_543.033_928_041, epsilon = 1e-6);
+        assert_relative_eq!(z0.scale_denominator, 559_082_264.028_717_8, epsilon = 1e-3);
+        assert_relative_eq!(
+            z0.point_of_origin[0],
+            -20_037_508.342_789_25,
+            epsilon = 1e-6
+        );
+
+        let z10 = tms.tile_matrix("10").unwrap();
+        assert_eq!((z10.matrix_width, z10.matrix_height), (1024, 1024));
+        assert!(tms.tile_matrix("31").is_none());
+        assert_eq!(tms.srid(), Some(3857));
+        assert_eq!(tms.max_zoom(), MAX_ZOOM);
+    }
+
+    #[test]
+    fn web_mercator_quad_json() {
+        let tms = TileMatrixSet::web_mercator_quad();
+        let json = serde_json::to_value(&tms).unwrap();
+        assert_eq!(json["id"], "WebMercatorQuad");
+        assert_eq!(json["orderedAxes"], serde_json::json!(["X", "Y"]));
+        assert_eq!(json["tileMatrices"][1]["cornerOfOrigin"], "topLeft");
+        assert_eq!(json["tileMatrices"][1]["matrixWidth"], 2);
+
+        let parsed = TileMatrixSet::from_json(&json.to_string()).unwrap();
+        assert_eq!(parsed.tile_matrices.len(), tms.tile_matrices.len());
+        assert_eq!(parsed.tile_matrices[7].id, "7");
+        assert_relative_eq!(
+            parsed.tile_matrices[7].cell_size,
+            tms.tile_matrices[7].cell_size
+        );
+    }
+
+    #[test]
+    fn web_mercator_quad_limits() {
+        let tms = TileMatrixSet::web_mercator_quad();
+        let limits = tms.limits([-180.0, -85.0, 180.0, 85.0], 0..=2);
+        assert_eq!(
+            limits,
+            [
+                TileRect::new(0, 0, 0, 0, 0),
+                TileRect::new(1, 0, 0, 1, 1),
+                TileRect::new(2, 0, 0, 3, 3),
+            ]
+        );
+
+        // western Europe
+        let limits = tms.limits([-5.0, 40.0, 15.0, 55.0], 6..=6);
+        assert_eq!(limits, [TileRect::new(6, 31, 20, 34, 24)]);
+
+        // the poles are outside of Web Mercator, but still map to the edge tiles
+        let limits = tms.limits([-180.0, -90.0, 180.0, 90.0], 3..=3);
+        assert_eq!(limits, [TileRect::new(3, 0, 0, 7, 7)]);
+
+        assert_eq!(tms.limits([0.0, 0.0, 1.0, 1.0], 30..=40).len(), 1);
+    }
+
+    #[test]
+    fn web_mercator_quad_tile_bounds() {
+        let tms = TileMatrixSet::web_mercator_quad();
+        let half = EARTH_CIRCUMFERENCE / 2.0;
+        let bounds = tms.tile_bounds(TileCoord { z: 1, x: 1, y: 0 }).unwrap();
+        assert_relative_eq!(bounds[..], [0.0, 0.0, half, half][..], epsilon = 1e-6);
+        assert!(tms.tile_bounds(TileCoord { z: 31, x: 0, y: 0 }).is_none());
+    }
+
+    #[test]
+    fn world_crs84_quad() {
+        let tms = TileMatrixSet::world_crs84_quad();
+        let z0 = tms.tile_matrix_at(0).unwrap();
+        assert_eq!((z0.matrix_width, z0.matrix_height), (2, 1));
+        assert_relative_eq!(z0.scale_denominator, 279_541_132.014_358_9, epsilon = 1e-3);
+        assert_eq!(tms.srid(), Some(4326));
+        assert!(tms.is_geographic());
+
+        assert!(tms.contains(TileCoord { z: 0, x: 1, y: 0 }));
+        assert!(!tms.contains(TileCoord { z: 0, x: 0, y: 1 }));
+
+        let bounds = tms.tile_bounds(TileCoord { z: 1, x: 3, y: 1 }).unwrap();
+        assert_relative_eq!(bounds[..], [90.0, -90.0, 180.0, 0.0][..]);
+
+        let limits = tms.limits([-5.0, 40.0, 15.0, 55.0], 2..=3);
+        assert_eq!(
+            limits,
+            [TileRect::new(2, 3, 0, 4, 1), TileRect::new(3, 7, 1, 8, 2)]
+        );
+    }
+
+    #[test]
+    fn custom_tms() {
+        // A grid with a bottom-left origin and latitude/longitude axis order
+        let tms = TileMatrixSet::from_json(
+            r#"{
+                "id": "Custom",
+                "crs": {"uri": "http://www.opengis.net/def/crs/EPSG/0/4326"},
+                "orderedAxes": ["Lat", "Lon"],
+                "tileMatrices": [{
+                    "id": "a",
+                    "scaleDenominator": 1000,
+                    "cellSize": 1.0,
+                    "cornerOfOrigin": "bottomLeft",
+                    "pointOfOrigin": [40, 10],
+                    "tileWidth": 10,
+                    "tileHeight": 10,
+                    "matrixWidth": 3,
+                    "matrixHeight": 2
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(tms.srid(), Some(4326));
+        assert_eq!(tms.max_zoom(), 0);
+        let bounds = tms.tile_bounds(TileCoord { z: 0, x: 1, y: 1 }).unwrap();
+        assert_relative_eq!(bounds[..], [20.0, 50.0, 30.0, 60.0][..]);
+        assert_eq!(
+            tms.limits([21.0, 41.0, 22.0, 55.0], 0..=1),
+            [TileRect::new(0, 1, 0, 1, 1)]
+        );
+
+        // a national grid cannot be projected to, so limits cover the whole matrix
+        let national = TileMatrixSet {
+            crs: "urn:ogc:def:crs:EPSG::2056".to_owned(),
+            ..tms
+        };
+        assert_eq!(national.srid(), Some(2056));
+        assert!(national.wgs84_to_crs(8.0, 47.0).is_none());
+        assert_eq!(
+            national.limits([8.0, 47.0, 9.0, 48.0], 0..=0),
+            [TileRect::new(0, 0, 0, 2, 1)]
+        );
+
+        let err =
+            TileMatrixSet::from_json(r#"{"id": "Empty", "crs": "EPSG:3857", "tileMatrices": []}"#)
+                .unwrap_err();
+        assert!(matches!(err, TileMatrixSetError::NoTileMatrices(_)));
+    }
+}
@@ -40,12 +40,15 @@ use martin_core::tiles::BoxedSource;
 use martin_core::tiles::mbtiles::MbtilesError;
 #[cfg(feature = "postgres")]
 use martin_core::tiles::postgres::ActiveQueryRegistry;
-use martin_tile_utils::{TileCoord, TileData, TileInfo, TileRect, append_rect, bbox_to_xyz};
+use martin_tile_utils::tms::WEB_MERCATOR_QUAD;
+use martin_tile_utils::{
+    Format, TileCoord, TileData, TileInfo, TileRect, append_rect, bbox_to_xyz,
+};
 use mbtiles::UpdateZoomType::GrowOnly;
 use mbtiles::sqlx::SqliteConnection;
 use mbtiles::{
-    CopyDuplicateMode, MbtError, MbtType, MbtTypeCli, Mbtiles, init_mbtiles_schema,
-    is_empty_database,
+    CopyDuplicateMode, LayerFilter, MbtError, MbtType, MbtTypeCli, Mbtiles, init_mbtiles_schema,
+    is_empty_database, parse_layer_attributes, parse_layer_zooms,
 };
 use tilejson::Bounds;
 use tokio::sync::mpsc::channel;
@@ -141,6 +144,34 @@ pub struct CopyArgs {
     /// Set additional metadata values. Must be set as `"key=value"` pairs. Can be specified multiple times.
     #[arg(long, value_name="KEY=VALUE", value_parser = parse_key_value)]
     pub set_meta: Vec<(String, String)>,
+    /// Only copy these vector tile layers, as a comma-separated list. Can be specified multiple times.
+    #[arg(long, value_name = "LAYER", value_delimiter = ',')]
+    pub include_layers: Vec<String>,
+    /// Remove these vector tile layers, as a comma-separated list. Can be specified multiple times.
+    #[arg(long, value_name = "LAYER", value_delimiter = ',')]
+    pub exclude_layers: Vec<String>,
+    /// Only keep these attributes of a layer's features, e.g. `roads=name,class`. Can be specified multiple times.
+    #[arg(long, value_name = "LAYER=ATTRS", value_parser = parse_layer_attributes)]
+    pub include_attributes: Vec<(String, Vec<String>)>,
+    /// Remove these attributes of a layer's features, e.g. `pois=wikidata`. Can be specified multiple times.
+    #[arg(long, value_name = "LAYER=ATTRS", value_parser = parse_layer_attributes)]
+    pub exclude_attributes: Vec<(String, Vec<String>)>,
+    /// Only keep a layer in tiles of these zoom levels, e.g. `buildings=13-` or `roads=4-14`. Can be specified multiple times.
+    #[arg(long, value_name = "LAYER=MIN-MAX", value_parser = parse_layer_zooms)]
+    pub layer_zoom: Vec<(String, RangeInclusive<u8>)>,
+}
+
+impl CopyArgs {
+    /// The vector tile layers and attributes to keep.
+    fn layer_filter(&self) -> LayerFilter {
+        LayerFilter::new(
+            self.include_layers.clone(),
+            self.exclude_layers.clone(),
+            self.include_attributes.clone(),
+            self.exclude_attributes.clone(),
+            self.layer_zoom.clone(),
+        )
+    }
 }
 
 impl Default for CopyArgs {
@@ -159,6 +190,11 @@ impl Default for CopyArgs {
             zoom_levels: Vec::new(),
             skip_agg_tiles_hash: true,
             set_meta: Vec::new(),
+            include_layers: Vec::new(),
+            exclude_layers: Vec::new(),
+            include_attributes: Vec::new(),
+            exclude_attributes: Vec::new(),
+            layer_zoom: Vec::new(),
         }
     }
 }
@@ -309,6 +345,12 @@ enum MartinCpError {
         "{0} of bounding box '{1}' must fit into {2:?}. Please check that your bounding box is in the `min_lon,min_lat,max_lon,max_lat` format."
     )]
     InvalidBoundingBox(&'static str, Bounds, RangeInclusive<f64>),
+    #[error(
+        "Source {0} uses the {1} tile matrix set, but MBTiles can only store WebMercatorQuad tiles"
+    )]
+    UnsupportedTileMatrixSet(String, String),
+    #[error("Source {0} has {1} tiles, but only the layers of MVT and MLT tiles can be filtered")]
+    UnsupportedLayerFilter(String, TileInfo),
 }
 
 /// Given a list of tile ranges, iterate over all tiles in the ranges
@@ -420,10 +462,12 @@ async fn write_tiles_to_mbtiles(
 }
 
 /// Fetches tiles concurrently and sends them to the consumer via `tx`.
+/// Layers and attributes not kept by `layer_filter` are removed from the tiles.
 async fn produce_tiles(
     src: &DynTileSource<'_>,
     tiles: Vec<TileRect>,
     concurrency: usize,
+    layer_filter: &LayerFilter,
     tx: Sender<TileXyz>,
 ) -> MartinResult<()> {
     stream::iter(iterate_tiles(tiles))
@@ -435,12 +479,16 @@ async fn produce_tiles(
                     .get_tile_content(xyz)
                     .await
                     .map_err(|e| std::io::Error::other(e.to_string()))?;
-                tx.send(TileXyz {
-                    xyz,
-                    data: tile.data,
-                })
-                .await
-                .expect("The receive half of the channel is not closed");
+                let data = if tile.data.is_empty() || layer_filter.is_empty() {
+                    tile.data
+                } else {
+                    layer_filter
+                        .apply(tile.data, tile.info, xyz.z)
+                        .map_err(|e| std::io::Error::other(format!("Tile {xyz}: {e}")))?
+                };
+                tx.send(TileXyz { xyz, data })
+                    .await
+                    .expect("The receive half of the channel is not closed");
                 Ok(())
             }
         })
@@ -519,6 +567,17 @@ where
             ..Default::default()
         },
     )?;
+    let tms = src.tile_matrix_set();
+    if tms.id != WEB_MERCATOR_QUAD {
+        return Err(MartinCpError::UnsupportedTileMatrixSet(
+            source_id,
+            tms.id.clone(),
+        ));
+    }
+    let layer_filter = args.layer_filter();
+    if !layer_filter.is_empty() && !matches!(src.info.format, Format::Mvt | Format::Mlt) {
+        return Err(MartinCpError::UnsupportedLayerFilter(source_id, src.info));
+    }
 
     // Track in-flight postgres queries so ctrl+c can abort them.
     #[cfg(feature = "postgres")]
@@ -551,7 +610,15 @@ where
     // parallel async below uses move, so we must only use copyable types
     let src = &src;
     let just_sources: Vec<_> = src.sources.iter().map(|(s, _)| s.clone()).collect();
-    let mbt_type = init_schema(&mbt, &mut conn, &just_sources, src.info, &args).await?;
+    let mbt_type = init_schema(
+        &mbt,
+        &mut conn,
+        &just_sources,
+        src.info,
+        &layer_filter,
+        &args,
+    )
+    .await?;
     let total_size = tiles.iter().map(TileRect::size).sum();
     // Shared with the spawned consumer (updates) and this task (finish / stats).
     let progress = Arc::new(TileCopyProgress::new(total_size));
@@ -574,7 +641,7 @@ where
     ));
 
     // 5. Producer: concurrently fetch all tiles or stop early on interrupt.
-    let produce = produce_tiles(src, tiles, concurrency, tx.clone());
+    let produce = produce_tiles(src, tiles, concurrency, &layer_filter, tx.clone());
     tokio::pin!(produce);
     tokio::pin!(interrupt);
     let interrupted = match select_future(produce, interrupt).await {
@@ -637,6 +704,7 @@ async fn init_schema(
     conn: &mut SqliteConnection,
     sources: &[BoxedSource],
     tile_info: TileInfo,
+    layer_filter: &LayerFilter,
     args: &CopyArgs,
 ) -> Result<MbtType, MartinError> {
     Ok(
@@ -657,6 +725,9 @@ async fn init_schema(
                 .await
                 .map_err(MbtilesError::from)?;
             let mut tj = merge_tilejson(sources, String::new());
+            if let Some(vector_layers) = &mut tj.vector_layers {
+                layer_filter.filter_vector_layers(vector_layers);
+            }
             tj.other.insert(
                 "format".to_owned(),
                 serde_json::Value::String(tile_info.format.metadata_format_value().to_owned()),
@@ -691,7 +762,7 @@ async fn main() {
     init_tracing(&filter, log_format, true);
 
     let args = CopierArgs::parse();
-    if let Err(e) = start(args).await {
+    if let Err(e) = Box::pin(start(args)).await {
         let rendered: String = match e {
             MartinCpError::Martin(martin_err) => martin_err.render_diagnostic_with(log_format),
             other => format!("{other}"),
@@ -717,6 +788,8 @@ mod tests {
     use martin::TileSourceManager;
     use martin::config::file::{OnInvalid, ProcessConfig, ServerState};
     use martin_core::CacheZoomRange;
+    #[cfg(feature = "mbtiles")]
+    use martin_core::tiles::mbtiles::{MbtWriters, PatchFeeds};
     use martin_core::tiles::{MartinCoreResult, Source, UrlQuery};
     use martin_tile_utils::{Encoding, Format};
     use mbtiles::Mbtiles;
@@ -794,6 +867,10 @@ mod tests {
             font_cache: None,
             #[cfg(feature = "styles")]
             styles: martin_core::styles::StyleSources::default(),
+            #[cfg(feature = "mbtiles")]
+            mbtiles_patches: PatchFeeds::default(),
+            #[cfg(feature = "mbtiles")]
+            mbtiles_writers: MbtWriters::default(),
         }
     }
 
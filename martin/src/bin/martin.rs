@@ -4,14 +4,13 @@
 )]
 
 use std::env;
+use std::process::ExitCode;
 
 use clap::Parser as _;
 use martin::MartinResult;
 use martin::config::args::Args;
 #[cfg(all(feature = "webui", not(docsrs)))]
 use martin::config::args::WebUiMode;
-#[cfg(any(feature = "mbtiles", feature = "pmtiles", feature = "postgres"))]
-use martin::config::file::ProcessConfig;
 #[cfg(feature = "unstable-cog")]
 use martin::config::file::reload::cog::CogReloader;
 #[cfg(feature = "geojson")]
@@ -26,6 +25,8 @@ use martin::config::file::{Config, read_config};
 #[cfg(feature = "_tiles")]
 use martin::config::primitives::IdResolver;
 use martin::config::primitives::env::OsEnv;
+#[cfg(feature = "opentelemetry")]
+use martin::logging::otel;
 use martin::logging::{LogFormat, ensure_martin_core_log_level_matches, init_tracing};
 #[cfg(feature = "_tiles")]
 use martin::srv::RESERVED_KEYWORDS;
@@ -57,6 +58,17 @@ async fn start(args: Args) -> MartinResult<()> {
     config.finalize().await?;
     config.warn_unrecognized_keys();
 
+    #[cfg(feature = "opentelemetry")]
+    if let Some(tracing_config) = config
+        .srv
+        .observability
+        .as_ref()
+        .and_then(|o| o.tracing.as_ref())
+    {
+        otel::init(tracing_config)?;
+        info!("Exporting traces via OpenTelemetry");
+    }
+
     // Snapshot the PostgreSQL config before `resolve()` rewrites its resolved tables/functions
     // back into it, so each reloader re-derives discovery from the same inputs startup used.
     #[cfg(feature = "postgres")]
@@ -83,17 +95,14 @@ async fn start(args: Args) -> MartinResult<()> {
     ))]
     let mgr = sources.tile_manager.clone();
 
-    #[cfg(any(feature = "mbtiles", feature = "pmtiles", feature = "postgres"))]
-    let global_pc = {
-        #[cfg(feature = "mlt")]
-        let pc = ProcessConfig {
-            convert_to_mlt: config.convert_to_mlt.clone(),
-            convert_to_mvt: config.convert_to_mvt.clone(),
-        };
-        #[cfg(not(feature = "mlt"))]
-        let pc = ProcessConfig::default();
-        pc
-    };
+    #[cfg(any(
+        feature = "mbtiles",
+        feature = "unstable-cog",
+        feature = "geojson",
+        feature = "pmtiles",
+        feature = "postgres"
+    ))]
+    let global_pc = config.global_process_config();
 
     #[cfg(feature = "mbtiles")]
     {
@@ -105,14 +114,15 @@ async fn start(args: Args) -> MartinResult<()> {
     }
     #[cfg(feature = "unstable-cog")]
     {
-        let reloader = CogReloader::new(mgr.clone(), resolver.clone(), &config.cog);
+        let reloader = CogReloader::new(mgr.clone(), resolver.clone(), &config.cog, &global_pc);
         if let Err(e) = reloader.start() {
             tracing::warn!("failed to start CogReloader {e:?}");
         }
     }
     #[cfg(feature = "geojson")]
     {
-        let reloader = GeoJsonReloader::new(mgr.clone(), resolver.clone(), &config.geojson);
+        let reloader =
+            GeoJsonReloader::new(mgr.clone(), resolver.clone(), &config.geojson, &global_pc);
         if let Err(e) = reloader.start() {
             tracing::warn!("failed to start GeoJsonReloader {e:?}");
         }
@@ -147,15 +157,15 @@ async fn start(args: Args) -> MartinResult<()> {
     let web_ui_mode = config.srv.web_ui.unwrap_or_default();
 
     let route_prefix = config.srv.route_prefix.clone();
-    let (server, listen_addresses) = new_server(
+    let (server, server_url) = new_server(
         config.srv,
         #[cfg(feature = "_catalog")]
         sources,
     )?;
     let base_url = if let Some(ref prefix) = route_prefix {
-        format!("http://{listen_addresses}{prefix}/")
+        format!("{server_url}{prefix}/")
     } else {
-        format!("http://{listen_addresses}/")
+        format!("{server_url}/")
     };
 
     #[cfg(all(feature = "webui", not(docsrs)))]
@@ -170,24 +180,36 @@ async fn start(args: Args) -> MartinResult<()> {
     #[cfg(not(all(feature = "webui", not(docsrs))))]
     info!("Martin server is now active. See {base_url}catalog to see available services");
 
-    server.await
+    let result = server.await;
+    #[cfg(feature = "opentelemetry")]
+    otel::shutdown();
+    result
+}
+
+fn main() -> ExitCode {
+    // Martin does not use the names of the sockets passed by systemd. The socket handoff removes
+    // `LISTEN_FDS` and `LISTEN_PID`, so drop the names too rather than leak them to other code.
+    // SAFETY: no other threads exist yet, the async runtime is started afterwards
+    unsafe { env::remove_var("LISTEN_FDNAMES") };
+    run()
 }
 
 #[tokio::main]
 #[hotpath::main]
-async fn main() {
+async fn run() -> ExitCode {
     let filter = ensure_martin_core_log_level_matches(env::var("RUST_LOG").ok(), "martin=");
     let log_format = LogFormat::from_env();
     init_tracing(&filter, log_format, false);
 
     let args = Args::parse();
-    if let Err(e) = start(args).await {
+    if let Err(e) = Box::pin(start(args)).await {
         let rendered = e.render_diagnostic_with(log_format);
         if tracing::event_enabled!(tracing::Level::ERROR) {
             error!("{rendered}");
         } else {
             eprintln!("{rendered}");
         }
-        std::process::exit(1);
+        return ExitCode::FAILURE;
     }
+    ExitCode::SUCCESS
 }
@@ -9,6 +9,7 @@ use crate::config::file::UnrecognizedValues;
 use crate::config::file::postgres::{
     DEFAULT_POOL_SIZE, DEFAULT_RELOAD_INTERVAL, PostgresConfig, PostgresSslCerts,
 };
+use crate::config::file::tile_matrix_sets::TileMatrixSets;
 use crate::config::primitives::env::Env;
 use crate::config::primitives::{OptBoolObj, OptOneMany};
 
@@ -64,7 +65,9 @@ impl PostgresArgs {
                 convert_to_mlt: None,
                 #[cfg(all(feature = "mlt", feature = "_tiles"))]
                 convert_to_mvt: None,
+                cache_control: None,
                 unrecognized: UnrecognizedValues::default(),
+                tile_matrix_sets: TileMatrixSets::default(),
             })
             .collect();
 
@@ -359,6 +359,15 @@ mod tests {
         assert_eq!(config4.unwrap().0.srv.preferred_encoding, None);
     }
 
+    #[test]
+    fn cli_tls_arguments() {
+        let (config, _) =
+            parse(&["martin", "--tls-cert", "cert.pem", "--tls-key", "key.pem"]).unwrap();
+        let tls = config.srv.tls.unwrap();
+        assert_eq!(tls.cert, PathBuf::from("cert.pem"));
+        assert_eq!(tls.key, PathBuf::from("key.pem"));
+    }
+
     #[cfg(any(feature = "unstable-cog", feature = "mbtiles", feature = "pmtiles"))]
     #[test]
     fn detects_file_scheme_uri() {
@@ -391,6 +400,7 @@ mod tests {
         for params in [
             ["martin", "--config", "c.toml", "--tmp"].as_slice(),
             ["martin", "--config", "c.toml", "-c", "t.toml"].as_slice(),
+            ["martin", "--tls-cert", "cert.pem"].as_slice(),
         ] {
             let res = Args::try_parse_from(params);
             assert!(res.is_err(), "Expected error, got: {res:?} for {params:?}");
@@ -1,9 +1,11 @@
+use std::path::PathBuf;
 use std::time::Duration;
 
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
 use crate::config::file::srv::{DEFAULT_KEEP_ALIVE, DEFAULT_LISTEN_ADDRESSES, SrvConfig};
+use crate::config::file::tls::TlsConfig;
 
 #[allow(
     clippy::doc_markdown,
@@ -14,7 +16,7 @@ use crate::config::file::srv::{DEFAULT_KEEP_ALIVE, DEFAULT_LISTEN_ADDRESSES, Srv
 pub struct SrvArgs {
     #[arg(help = format!("Connection keep alive timeout. [DEFAULT: {DEFAULT_KEEP_ALIVE}]"), short, long)]
     pub keep_alive: Option<u64>,
-    #[arg(help = format!("The socket address to bind. [DEFAULT: {DEFAULT_LISTEN_ADDRESSES}]"), short, long)]
+    #[arg(help = format!("The socket address to bind, or `unix:/path` for a Unix domain socket. [DEFAULT: {DEFAULT_LISTEN_ADDRESSES}]"), short, long)]
     pub listen_addresses: Option<String>,
     /// Set URL path prefix for all API routes.
     ///
@@ -64,6 +66,12 @@ pub struct SrvArgs {
     /// Maximum idle time before cache entries are evicted (e.g. "15m", "1h")
     #[arg(long, value_parser = parse_duration)]
     pub cache_idle_timeout: Option<Duration>,
+    /// Serve HTTPS using this PEM certificate chain. Requires `--tls-key`.
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of the `--tls-cert` certificate.
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
 }
 
 fn parse_duration(s: &str) -> Result<Duration, String> {
@@ -129,5 +137,13 @@ impl SrvArgs {
         if self.tilejson_url_version_param.is_some() {
             srv_config.tilejson_url_version_param = self.tilejson_url_version_param;
         }
+        if let (Some(cert), Some(key)) = (self.tls_cert, self.tls_key) {
+            if let Some(tls) = &mut srv_config.tls {
+                tls.cert = cert;
+                tls.key = key;
+            } else {
+                srv_config.tls = Some(TlsConfig::new(cert, key));
+            }
+        }
     }
 }
@@ -0,0 +1,143 @@
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::file::rate_limit::ProxyNet;
+use crate::config::file::{CollectUnrecognizedKeys, UnrecognizedKeys, UnrecognizedValues};
+
+/// Rotate the access log file once it grows past this size
+pub const DEFAULT_MAX_SIZE_MB: u64 = 100;
+
+/// Number of rotated access log files kept next to the current one
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// Write one line per HTTP request, including the tile-specific details of tile requests.
+///
+/// ```yaml
+/// access_log:
+///   format: json
+///   path: /var/log/martin/access.log
+///   sample_ratio: 0.1
+///   exclude_paths: [/health, /_/metrics]
+/// ```
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct AccessLogConfig {
+    /// Format of the lines, `combined` or `json` \[default: `combined`\]
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    /// Write to this file instead of the standard output.
+    /// The file is rotated once it grows past `max_size_mb`.
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(example = &"/var/log/martin/access.log")
+    )]
+    pub path: Option<PathBuf>,
+    /// Size of the log file in megabytes at which it is rotated \[default: 100\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &100u64))]
+    pub max_size_mb: Option<NonZeroU64>,
+    /// Number of rotated files to keep, named `<path>.1` (newest) to `<path>.<max_files>`.
+    /// With `0`, the file is truncated instead.
+    /// \[default: 5\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &5usize))]
+    pub max_files: Option<usize>,
+    /// Fraction of requests to log, between `0.0` and `1.0` \[default: `1.0`\]
+    ///
+    /// Responses with a `5xx` status are always logged.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &0.1))]
+    pub sample_ratio: Option<f64>,
+    /// Requests to these paths are not logged.
+    /// A trailing `*` matches all paths starting with the rest, e.g. `/_/*`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &["/health"]))]
+    pub exclude_paths: Vec<String>,
+    /// Log the client address from the `Forwarded` or `X-Forwarded-For` headers
+    /// of requests from `trusted_proxies`, as clients can forge them.
+    /// \[default: false\]
+    pub trust_forwarded: Option<bool>,
+    /// Addresses or networks of the reverse proxies allowed to set the forwarding headers,
+    /// e.g. `10.0.0.5` or `10.0.0.0/8`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Vec<String>", example = &["10.0.0.0/8"])
+    )]
+    pub trusted_proxies: Vec<ProxyNet>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+/// Format of the access log lines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// The Apache/nginx combined log format, followed by the tile details as `key=value` pairs
+    #[default]
+    Combined,
+    /// One JSON object per line
+    Json,
+}
+
+impl CollectUnrecognizedKeys for AccessLogFormat {
+    fn collect_unrecognized(&self, _path: &str, _out: &mut UnrecognizedKeys) {}
+}
+
+impl AccessLogConfig {
+    /// Whether requests to `path` are not logged
+    #[must_use]
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.exclude_paths
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == pattern,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn parse_access_log_config() {
+        let cfg: AccessLogConfig = serde_saphyr::from_str(indoc! {"
+            format: json
+            path: /var/log/martin/access.log
+            max_files: 0
+            sample_ratio: 0.5
+            exclude_paths: [/health, /_/*]
+        "})
+        .unwrap();
+        assert_eq!(cfg.format, AccessLogFormat::Json);
+        assert_eq!(cfg.path, Some(PathBuf::from("/var/log/martin/access.log")));
+        assert_eq!(cfg.max_size_mb, None);
+        assert_eq!(cfg.max_files, Some(0));
+        assert_eq!(cfg.sample_ratio, Some(0.5));
+
+        assert!(cfg.is_excluded("/health"));
+        assert!(cfg.is_excluded("/_/metrics"));
+        assert!(!cfg.is_excluded("/health/other"));
+        assert!(!cfg.is_excluded("/catalog"));
+
+        let yaml = serde_saphyr::to_string(&cfg).unwrap();
+        assert_eq!(
+            serde_saphyr::from_str::<AccessLogConfig>(&yaml).unwrap(),
+            cfg
+        );
+    }
+
+    #[test]
+    fn default_format_is_combined() {
+        let cfg: AccessLogConfig = serde_saphyr::from_str("sample_ratio: 1.0").unwrap();
+        assert_eq!(cfg.format, AccessLogFormat::Combined);
+        serde_saphyr::from_str::<AccessLogConfig>("format: common").unwrap_err();
+    }
+}
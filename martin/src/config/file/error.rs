@@ -4,6 +4,8 @@ use std::path::{Path, PathBuf};
 use martin_core::fonts::FontError;
 #[cfg(feature = "postgres")]
 use martin_core::tiles::postgres::PostgresError;
+#[cfg(feature = "_tiles")]
+use martin_tile_utils::tms::TileMatrixSetError;
 use miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode};
 
 pub type ConfigFileResult<T> = Result<T, ConfigFileError>;
@@ -38,6 +40,14 @@ pub enum ConfigFileError {
     #[error("Source {0} uses bad file {1}")]
     InvalidSourceFilePath(String, PathBuf),
 
+    #[cfg(feature = "mbtiles")]
+    #[error("Patches of source {0} must be in a directory, but {1} is not a directory")]
+    InvalidPatchesDirectory(String, PathBuf),
+
+    #[cfg(feature = "mbtiles")]
+    #[error("mbtiles.write.token must be set to accept tile writes")]
+    MissingMbtilesWriteToken,
+
     #[cfg(feature = "passthrough")]
     #[error(
         "Passthrough source {source_id} has an unknown tile format {tile_format:?}; expected one of pbf/mvt, mlt, png, jpg, webp, json, gif, avif"
@@ -47,6 +57,28 @@ pub enum ConfigFileError {
         tile_format: String,
     },
 
+    #[cfg(feature = "passthrough")]
+    #[error("Passthrough source {0} can either be a WMS or an ArcGIS REST upstream, not both")]
+    ConflictingPassthroughServices(String),
+
+    #[cfg(feature = "_tiles")]
+    #[error("Invalid tile matrix set file {1}: {0}")]
+    InvalidTileMatrixSet(#[source] TileMatrixSetError, PathBuf),
+
+    #[cfg(feature = "_tiles")]
+    #[error("Tile matrix set {0} is built-in and cannot be redefined")]
+    BuiltinTileMatrixSet(String),
+
+    #[cfg(feature = "_tiles")]
+    #[error("Unknown tile matrix set {0}, it must be built-in or configured in tile_matrix_sets")]
+    UnknownTileMatrixSet(String),
+
+    #[cfg(feature = "postgres")]
+    #[error(
+        "Tile matrix set {0} must use an EPSG coordinate reference system to generate tiles in PostGIS"
+    )]
+    TileMatrixSetWithoutSrid(String),
+
     #[error("At least one 'origin' must be specified in the 'cors' configuration")]
     CorsNoOriginsConfigured,
 
@@ -234,8 +266,24 @@ impl Diagnostic for ConfigFileError {
             Self::InvalidSourceUrl(..) => "martin::config::invalid_source_url",
             Self::PathNotConvertibleToUrl(_) => "martin::config::path_not_url",
             Self::InvalidSourceFilePath(..) => "martin::config::invalid_source_file_path",
+            #[cfg(feature = "mbtiles")]
+            Self::InvalidPatchesDirectory(..) => "martin::config::mbtiles::patches_directory",
+            #[cfg(feature = "mbtiles")]
+            Self::MissingMbtilesWriteToken => "martin::config::mbtiles::write_token",
             #[cfg(feature = "passthrough")]
             Self::InvalidPassthroughFormat { .. } => "martin::config::passthrough::invalid_format",
+            #[cfg(feature = "passthrough")]
+            Self::ConflictingPassthroughServices(_) => {
+                "martin::config::passthrough::conflicting_services"
+            }
+            #[cfg(feature = "_tiles")]
+            Self::InvalidTileMatrixSet(..) => "martin::config::tile_matrix_sets::invalid",
+            #[cfg(feature = "_tiles")]
+            Self::BuiltinTileMatrixSet(_) => "martin::config::tile_matrix_sets::builtin",
+            #[cfg(feature = "_tiles")]
+            Self::UnknownTileMatrixSet(_) => "martin::config::tile_matrix_sets::unknown",
+            #[cfg(feature = "postgres")]
+            Self::TileMatrixSetWithoutSrid(_) => "martin::config::postgres::tile_matrix_set_srid",
             Self::CorsNoOriginsConfigured => "martin::config::cors::no_origins",
             #[cfg(feature = "styles")]
             Self::DirectoryWalking(..) => "martin::config::styles::walk",
@@ -21,6 +21,7 @@ use tracing::{info, warn};
 #[cfg(feature = "_tiles")]
 use url::Url;
 
+use crate::config::file::srv::CacheControlPolicy;
 use crate::config::file::{
     CollectUnrecognizedKeys, ConfigFileError, ConfigFileResult, UnrecognizedValues,
 };
@@ -379,6 +380,13 @@ pub struct FileConfigSource {
     #[serde(default, skip_serializing_if = "CachePolicy::is_empty")]
     #[cfg_attr(feature = "unstable-schemas", schemars(with = "CachePolicyShape"))]
     pub cache: CachePolicy,
+    /// `Cache-Control` header of the tiles of this source.
+    /// Overrides source-type and global `cache_control`.
+    pub cache_control: Option<CacheControlPolicy>,
+    /// Serve tiles up to this zoom level, beyond the `maxzoom` of this source, by cutting them out
+    /// of the tiles at its `maxzoom`. Used by `MBTiles` and `PMTiles` sources.
+    /// Overrides source-type `overzoom`.
+    pub overzoom: Option<u8>,
 }
 
 #[cfg(feature = "_tiles")]
@@ -0,0 +1,78 @@
+use std::num::NonZeroU64;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::file::{CollectUnrecognizedKeys, UnrecognizedValues};
+
+/// Time allowed for the check of a single source
+pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+/// The results of the checks are reused for this long
+pub const DEFAULT_INTERVAL_SECS: u64 = 10;
+
+/// Configure the backend checks of the `/ready` and `/_/status` endpoints.
+///
+/// ```yaml
+/// health:
+///   timeout_ms: 2000
+///   interval_secs: 10
+///   critical_sources: [roads, buildings]
+/// ```
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct HealthConfig {
+    /// Time in milliseconds after which the check of a source fails \[default: 5000\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &5000u64))]
+    pub timeout_ms: Option<NonZeroU64>,
+    /// Reuse the results of the checks for this many seconds, so that frequent probes
+    /// do not put load on the backends. \[default: 10\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &10u64))]
+    pub interval_secs: Option<u64>,
+    /// IDs of the sources that must be healthy for `/ready` to succeed.
+    /// All sources are critical if this is not set, and none with an empty list.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &["roads"]))]
+    pub critical_sources: Option<Vec<String>>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl HealthConfig {
+    /// Whether `/ready` fails while the source `id` is unhealthy
+    #[must_use]
+    pub fn is_critical(&self, id: &str) -> bool {
+        self.critical_sources
+            .as_ref()
+            .is_none_or(|ids| ids.iter().any(|v| v == id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn parse_health_config() {
+        let cfg: HealthConfig = serde_saphyr::from_str(indoc! {"
+            timeout_ms: 2000
+            critical_sources: [roads]
+        "})
+        .unwrap();
+        assert_eq!(cfg.timeout_ms, NonZeroU64::new(2000));
+        assert_eq!(cfg.interval_secs, None);
+        assert!(cfg.is_critical("roads"));
+        assert!(!cfg.is_critical("buildings"));
+
+        let cfg = HealthConfig::default();
+        assert!(cfg.is_critical("buildings"));
+
+        let cfg: HealthConfig = serde_saphyr::from_str("critical_sources: []").unwrap();
+        assert!(!cfg.is_critical("roads"));
+
+        serde_saphyr::from_str::<HealthConfig>("timeout_ms: 0").unwrap_err();
+    }
+}
@@ -1,9 +1,13 @@
+#[cfg(feature = "_tiles")]
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::LazyLock;
 
 use clap::ValueEnum;
 #[cfg(feature = "_tiles")]
 use martin_core::tiles::BoxedSource;
+#[cfg(feature = "mbtiles")]
+use martin_core::tiles::mbtiles::{MbtWriters, PatchFeeds};
 use serde::{Deserialize, Serialize};
 use tracing::{error, instrument, warn};
 
@@ -76,6 +80,11 @@ pub struct ServerState {
 
     #[cfg(feature = "styles")]
     pub styles: martin_core::styles::StyleSources,
+
+    #[cfg(feature = "mbtiles")]
+    pub mbtiles_patches: PatchFeeds,
+    #[cfg(feature = "mbtiles")]
+    pub mbtiles_writers: MbtWriters,
 }
 
 #[serde_with::skip_serializing_none]
@@ -106,6 +115,13 @@ pub struct Config {
     #[serde(flatten)]
     pub srv: SrvConfig,
 
+    /// Additional tile matrix sets that tile sources can select with `tile_matrix_set`,
+    /// as a map of tile matrix set ID to an [OGC TMS 2.0](https://docs.ogc.org/is/17-083r4/17-083r4.html) JSON file.
+    /// `WebMercatorQuad` (the default) and `WorldCRS84Quad` are always available.
+    #[cfg(feature = "_tiles")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tile_matrix_sets: BTreeMap<String, PathBuf>,
+
     /// Database configuration
     ///
     /// This can also be a list of PG configs, for example:
@@ -36,12 +36,19 @@ use crate::MartinResult;
 use crate::config::file::ConfigurationLivecycleHooks;
 #[cfg(any(
     feature = "pmtiles",
+    feature = "geojson",
     feature = "sprites",
     feature = "fonts",
-    all(feature = "mlt", feature = "mbtiles"),
+    feature = "mbtiles",
+    feature = "unstable-cog",
 ))]
 use crate::config::file::FileConfigEnum;
-#[cfg(all(feature = "mlt", any(feature = "pmtiles", feature = "mbtiles")))]
+#[cfg(any(
+    feature = "pmtiles",
+    feature = "mbtiles",
+    feature = "unstable-cog",
+    feature = "geojson"
+))]
 use crate::config::file::FileConfigSrc;
 #[cfg(any(feature = "_tiles", feature = "sprites", feature = "fonts"))]
 use crate::config::file::cache::{CacheConfig, SubCacheSetting};
@@ -54,6 +61,8 @@ use crate::config::file::process::ProcessConfig;
     feature = "geojson"
 ))]
 use crate::config::file::resolve_files;
+#[cfg(feature = "_tiles")]
+use crate::config::file::tile_matrix_sets::TileMatrixSets;
 use crate::config::file::{CollectUnrecognizedKeys as _, ConfigFileError, ConfigFileResult};
 #[cfg(feature = "_tiles")]
 use crate::config::primitives::IdResolver;
@@ -75,8 +84,21 @@ impl Config {
         if let Some(path) = &self.srv.base_path {
             self.srv.base_path = Some(parse_base_path(path)?);
         }
+
+        // Sources resolve their `tile_matrix_set` IDs against these
+        #[cfg(feature = "_tiles")]
+        #[cfg_attr(
+            not(any(feature = "postgres", feature = "geojson")),
+            expect(
+                unused_variables,
+                reason = "only postgres and geojson sources support tile matrix sets"
+            )
+        )]
+        let tile_matrix_sets = TileMatrixSets::load(&self.tile_matrix_sets)?;
+
         #[cfg(feature = "postgres")]
         for pg in self.postgres.iter_mut() {
+            pg.tile_matrix_sets = tile_matrix_sets.clone();
             pg.finalize().await?;
         }
 
@@ -103,7 +125,12 @@ impl Config {
         self.duckdb.finalize().await?;
 
         #[cfg(feature = "geojson")]
-        self.geojson.finalize().await?;
+        {
+            if let FileConfigEnum::Config(file_config) = &mut self.geojson {
+                file_config.custom.tile_matrix_sets = tile_matrix_sets;
+            }
+            self.geojson.finalize().await?;
+        }
 
         #[cfg(feature = "sprites")]
         self.sprites.finalize().await?;
@@ -199,7 +226,12 @@ impl Config {
         #[cfg(feature = "_tiles")]
         let tile_sources_with_process = {
             let process_map = self.build_process_config_map();
-            let global_process = ProcessConfig::default();
+            // Sources missing from the map, e.g. ones passed on the command line,
+            // still get the global `cache_control`
+            let global_process = ProcessConfig {
+                cache_control: self.srv.cache_control.clone(),
+                ..ProcessConfig::default()
+            };
             tile_sources
                 .into_iter()
                 .map(|group| {
@@ -237,6 +269,11 @@ impl Config {
 
             #[cfg(feature = "styles")]
             styles: self.styles.resolve()?,
+
+            #[cfg(feature = "mbtiles")]
+            mbtiles_patches: self.mbtiles.resolve_patches()?,
+            #[cfg(feature = "mbtiles")]
+            mbtiles_writers: self.mbtiles.resolve_writers().await?,
         })
     }
 
@@ -441,106 +478,144 @@ impl Config {
         ))
     }
 
+    /// The process config of sources without more specific settings.
+    #[cfg(feature = "_tiles")]
+    #[must_use]
+    pub fn global_process_config(&self) -> ProcessConfig {
+        ProcessConfig {
+            #[cfg(feature = "mlt")]
+            convert_to_mlt: self.convert_to_mlt.clone(),
+            #[cfg(feature = "mlt")]
+            convert_to_mvt: self.convert_to_mvt.clone(),
+            cache_control: self.srv.cache_control.clone(),
+            overzoom: None,
+        }
+    }
+
     /// Build a map from source ID -> resolved [`ProcessConfig`].
     ///
-    /// Uses full-override semantics: per-source > source-type > global > default.
+    /// Layers per-source settings over the source-type and global ones,
+    /// see [`resolve_process_config`](crate::config::file::process::resolve_process_config).
     #[cfg(feature = "_tiles")]
     fn build_process_config_map(&self) -> HashMap<String, ProcessConfig> {
         #[allow(unused_mut)]
         let mut map = HashMap::new();
+        #[allow(unused_variables)]
+        let global = self.global_process_config();
 
-        #[cfg(all(
-            feature = "mlt",
-            any(
-                feature = "postgres",
-                feature = "pmtiles",
-                feature = "mbtiles",
-                feature = "passthrough"
-            )
-        ))]
-        {
-            let global = ProcessConfig {
-                convert_to_mlt: self.convert_to_mlt.clone(),
-                convert_to_mvt: self.convert_to_mvt.clone(),
+        #[cfg(feature = "postgres")]
+        for pg in self.postgres.iter() {
+            let source_type = ProcessConfig {
+                #[cfg(feature = "mlt")]
+                convert_to_mlt: pg.convert_to_mlt.clone(),
+                #[cfg(feature = "mlt")]
+                convert_to_mvt: pg.convert_to_mvt.clone(),
+                cache_control: pg.cache_control.clone(),
+                overzoom: None,
             };
-
-            #[cfg(feature = "postgres")]
-            for pg in self.postgres.iter() {
-                let source_type = ProcessConfig {
-                    convert_to_mlt: pg.convert_to_mlt.clone(),
-                    convert_to_mvt: pg.convert_to_mvt.clone(),
-                };
-                if let Some(tables) = &pg.tables {
-                    Self::insert_source_configs(&mut map, &global, &source_type, tables, |info| {
-                        ProcessConfig {
-                            convert_to_mlt: info.convert_to_mlt.clone(),
-                            convert_to_mvt: info.convert_to_mvt.clone(),
-                        }
-                    });
-                }
-                if let Some(functions) = &pg.functions {
-                    Self::insert_source_configs(
-                        &mut map,
-                        &global,
-                        &source_type,
-                        functions,
-                        |info| ProcessConfig {
-                            convert_to_mlt: info.convert_to_mlt.clone(),
-                            convert_to_mvt: info.convert_to_mvt.clone(),
-                        },
-                    );
-                }
+            if let Some(tables) = &pg.tables {
+                Self::insert_source_configs(&mut map, &global, &source_type, tables, |info| {
+                    ProcessConfig {
+                        #[cfg(feature = "mlt")]
+                        convert_to_mlt: info.convert_to_mlt.clone(),
+                        #[cfg(feature = "mlt")]
+                        convert_to_mvt: info.convert_to_mvt.clone(),
+                        cache_control: info.cache_control.clone(),
+                        overzoom: None,
+                    }
+                });
             }
-
-            #[cfg(feature = "pmtiles")]
-            Self::insert_file_source_configs(&mut map, &global, &self.pmtiles, |c| ProcessConfig {
-                convert_to_mlt: c.convert_to_mlt.clone(),
-                convert_to_mvt: c.convert_to_mvt.clone(),
-            });
-
-            #[cfg(feature = "mbtiles")]
-            Self::insert_file_source_configs(&mut map, &global, &self.mbtiles, |c| ProcessConfig {
-                convert_to_mlt: c.convert_to_mlt.clone(),
-                convert_to_mvt: c.convert_to_mvt.clone(),
-            });
-
-            #[cfg(feature = "passthrough")]
-            if let Some(sources) = &self.passthrough.sources {
-                use crate::config::file::passthrough::PassthroughSrc;
-
-                let source_type = ProcessConfig {
-                    convert_to_mlt: self.passthrough.convert_to_mlt.clone(),
-                    convert_to_mvt: self.passthrough.convert_to_mvt.clone(),
-                };
-                Self::insert_source_configs(&mut map, &global, &source_type, sources, |src| {
-                    match src {
-                        PassthroughSrc::Detailed(obj) => ProcessConfig {
-                            convert_to_mlt: obj.convert_to_mlt.clone(),
-                            convert_to_mvt: obj.convert_to_mvt.clone(),
-                        },
-                        PassthroughSrc::Shorthand(_) => ProcessConfig::default(),
+            if let Some(functions) = &pg.functions {
+                Self::insert_source_configs(&mut map, &global, &source_type, functions, |info| {
+                    ProcessConfig {
+                        #[cfg(feature = "mlt")]
+                        convert_to_mlt: info.convert_to_mlt.clone(),
+                        #[cfg(feature = "mlt")]
+                        convert_to_mvt: info.convert_to_mvt.clone(),
+                        cache_control: info.cache_control.clone(),
+                        overzoom: None,
                     }
                 });
             }
         }
 
+        #[cfg(feature = "pmtiles")]
+        Self::insert_file_source_configs(&mut map, &global, &self.pmtiles, |c| ProcessConfig {
+            #[cfg(feature = "mlt")]
+            convert_to_mlt: c.convert_to_mlt.clone(),
+            #[cfg(feature = "mlt")]
+            convert_to_mvt: c.convert_to_mvt.clone(),
+            cache_control: c.cache_control.clone(),
+            overzoom: c.overzoom,
+        });
+
+        #[cfg(feature = "mbtiles")]
+        Self::insert_file_source_configs(&mut map, &global, &self.mbtiles, |c| ProcessConfig {
+            #[cfg(feature = "mlt")]
+            convert_to_mlt: c.convert_to_mlt.clone(),
+            #[cfg(feature = "mlt")]
+            convert_to_mvt: c.convert_to_mvt.clone(),
+            cache_control: c.cache_control.clone(),
+            overzoom: c.overzoom,
+        });
+
         // COG sources produce raster tiles (TIFF), not vector tiles (MVT),
-        // so process config (MLT conversion, compression) does not apply.
-        // They fall through to the global default, which is a no-op for raster formats.
+        // so format conversions do not apply to them.
+        #[cfg(feature = "unstable-cog")]
+        Self::insert_file_source_configs(&mut map, &global, &self.cog, |c| ProcessConfig {
+            cache_control: c.cache_control.clone(),
+            ..ProcessConfig::default()
+        });
+
+        #[cfg(feature = "geojson")]
+        Self::insert_file_source_configs(&mut map, &global, &self.geojson, |c| ProcessConfig {
+            cache_control: c.cache_control.clone(),
+            ..ProcessConfig::default()
+        });
+
+        #[cfg(feature = "passthrough")]
+        if let Some(sources) = &self.passthrough.sources {
+            use crate::config::file::passthrough::PassthroughSrc;
+
+            let source_type = ProcessConfig {
+                #[cfg(feature = "mlt")]
+                convert_to_mlt: self.passthrough.convert_to_mlt.clone(),
+                #[cfg(feature = "mlt")]
+                convert_to_mvt: self.passthrough.convert_to_mvt.clone(),
+                cache_control: self.passthrough.cache_control.clone(),
+                overzoom: None,
+            };
+            Self::insert_source_configs(
+                &mut map,
+                &global,
+                &source_type,
+                sources,
+                |src| match src {
+                    PassthroughSrc::Detailed(obj) => ProcessConfig {
+                        #[cfg(feature = "mlt")]
+                        convert_to_mlt: obj.convert_to_mlt.clone(),
+                        #[cfg(feature = "mlt")]
+                        convert_to_mvt: obj.convert_to_mvt.clone(),
+                        cache_control: obj.cache_control.clone(),
+                        overzoom: None,
+                    },
+                    PassthroughSrc::Shorthand(_) => ProcessConfig::default(),
+                },
+            );
+        }
 
         map
     }
 
     /// Resolve and insert the effective [`ProcessConfig`] for each source in a map, layering
     /// per-source settings over the source-type and global defaults.
-    #[cfg(all(
-        feature = "mlt",
-        any(
-            feature = "postgres",
-            feature = "pmtiles",
-            feature = "mbtiles",
-            feature = "passthrough"
-        )
+    #[cfg(any(
+        feature = "postgres",
+        feature = "pmtiles",
+        feature = "mbtiles",
+        feature = "unstable-cog",
+        feature = "geojson",
+        feature = "passthrough"
     ))]
     fn insert_source_configs<'a, S: 'a>(
         map: &mut HashMap<String, ProcessConfig>,
@@ -559,8 +634,13 @@ impl Config {
         }
     }
 
-    /// Helper to resolve process configs for file-based source types (pmtiles, mbtiles).
-    #[cfg(all(feature = "mlt", any(feature = "pmtiles", feature = "mbtiles")))]
+    /// Helper to resolve process configs for file-based source types.
+    #[cfg(any(
+        feature = "pmtiles",
+        feature = "mbtiles",
+        feature = "unstable-cog",
+        feature = "geojson"
+    ))]
     fn insert_file_source_configs<T: ConfigurationLivecycleHooks>(
         map: &mut HashMap<String, ProcessConfig>,
         global: &ProcessConfig,
@@ -572,8 +652,12 @@ impl Config {
             if let Some(sources) = &cfg.sources {
                 Self::insert_source_configs(map, global, &source_type, sources, |src| match src {
                     FileConfigSrc::Obj(obj) => ProcessConfig {
+                        #[cfg(feature = "mlt")]
                         convert_to_mlt: obj.convert_to_mlt.clone(),
+                        #[cfg(feature = "mlt")]
                         convert_to_mvt: obj.convert_to_mvt.clone(),
+                        cache_control: obj.cache_control.clone(),
+                        overzoom: obj.overzoom,
                     },
                     FileConfigSrc::Path(_) => ProcessConfig::default(),
                 });
@@ -6,18 +6,25 @@ pub use collect_unrecognized::*;
 
 mod main;
 pub use main::*;
+pub mod access_log;
 pub mod cache;
 pub mod cors;
+pub mod health;
+pub mod rate_limit;
 pub mod srv;
+pub mod tls;
 
 mod error;
 pub use error::{ConfigFileError, ConfigFileResult};
 
 pub mod process;
 pub use process::ProcessConfig;
-#[cfg(all(
-    feature = "mlt",
-    any(feature = "mbtiles", feature = "pmtiles", feature = "postgres")
+#[cfg(any(
+    feature = "mbtiles",
+    feature = "pmtiles",
+    feature = "postgres",
+    feature = "unstable-cog",
+    feature = "geojson"
 ))]
 pub(crate) use process::resolve_process_config;
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
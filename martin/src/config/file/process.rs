@@ -2,7 +2,11 @@
 use mlt_core::encoder::EncoderConfig;
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "_tiles")]
+use tilejson::TileJSON;
 
+#[cfg(feature = "_tiles")]
+use crate::config::file::srv::CacheControlPolicy;
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 use crate::config::file::{CollectUnrecognizedKeys, UnrecognizedKeys, UnrecognizedValues};
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
@@ -10,13 +14,55 @@ use crate::config::primitives::AutoOption;
 
 /// Internal carrier for resolved per-source processing settings.
 ///
-/// Not serialized directly - config files use `convert_to_mlt` / `convert_to_mvt`.
+/// Not serialized directly - config files use `convert_to_mlt` / `convert_to_mvt` / `cache_control` / `overzoom`.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ProcessConfig {
     #[cfg(all(feature = "mlt", feature = "_tiles"))]
     pub convert_to_mlt: Option<MltProcessConfig>,
     #[cfg(all(feature = "mlt", feature = "_tiles"))]
     pub convert_to_mvt: Option<MvtProcessConfig>,
+    /// `Cache-Control` header of the tile responses
+    #[cfg(feature = "_tiles")]
+    pub cache_control: Option<CacheControlPolicy>,
+    /// Highest zoom level served by cutting tiles out of the tiles at the source's `maxzoom`
+    #[cfg(feature = "_tiles")]
+    pub overzoom: Option<u8>,
+}
+
+impl ProcessConfig {
+    /// The zoom level to cut a tile of `zoom` from, if it is above the `maxzoom` of the source
+    /// and within the configured `overzoom`.
+    #[cfg(feature = "_tiles")]
+    #[must_use]
+    pub fn overzoom_from(&self, tilejson: &TileJSON, zoom: u8) -> Option<u8> {
+        let maxzoom = tilejson.maxzoom?;
+        let overzoom = self.overzoom?;
+        (maxzoom < zoom && zoom <= overzoom).then_some(maxzoom)
+    }
+
+    /// The `maxzoom` of the source as advertised to clients, raised to `overzoom` if configured.
+    #[cfg(feature = "_tiles")]
+    #[must_use]
+    pub fn maxzoom(&self, tilejson: &TileJSON) -> Option<u8> {
+        let maxzoom = tilejson.maxzoom?;
+        Some(
+            self.overzoom
+                .map_or(maxzoom, |overzoom| overzoom.max(maxzoom)),
+        )
+    }
+
+    /// Whether any format conversion is configured
+    #[cfg_attr(
+        not(all(feature = "mlt", feature = "_tiles")),
+        expect(clippy::unused_self)
+    )]
+    fn has_conversion(&self) -> bool {
+        #[cfg(all(feature = "mlt", feature = "_tiles"))]
+        if self.convert_to_mlt.is_some() || self.convert_to_mvt.is_some() {
+            return true;
+        }
+        false
+    }
 }
 
 /// Configuration for MVT-to-MLT format conversion.
@@ -125,27 +171,41 @@ impl From<MltEncoderConfig> for EncoderConfig {
     }
 }
 
-/// Resolve effective process config using full-override semantics:
-/// per-source > source-type > global > default.
+/// Resolve effective process config.
+///
+/// Format conversions use full-override semantics: per-source > source-type > global > default.
+/// `cache_control` is layered instead, so a source only setting some zoom ranges keeps the
+/// values of its source type and the global config for the other zoom levels.
+/// `overzoom` is taken from the most specific level that sets it.
 #[must_use]
 pub fn resolve_process_config(
     global: &ProcessConfig,
     source_type: &ProcessConfig,
     per_source: &ProcessConfig,
 ) -> ProcessConfig {
-    let default = ProcessConfig::default();
-    if *per_source != default {
-        per_source.clone()
-    } else if *source_type != default {
-        source_type.clone()
-    } else {
-        global.clone()
+    #[cfg_attr(not(feature = "_tiles"), expect(unused_mut))]
+    let mut resolved = [per_source, source_type]
+        .into_iter()
+        .find(|pc| pc.has_conversion())
+        .unwrap_or(global)
+        .clone();
+    #[cfg(feature = "_tiles")]
+    {
+        resolved.cache_control = [per_source, source_type, global]
+            .into_iter()
+            .filter_map(|pc| pc.cache_control.clone())
+            .reduce(|policy, fallback| policy.or(&fallback));
+        resolved.overzoom = per_source
+            .overzoom
+            .or(source_type.overzoom)
+            .or(global.overzoom);
     }
+    resolved
 }
 
 #[cfg(test)]
 mod tests {
-    #[cfg(all(feature = "mlt", feature = "_tiles"))]
+    #[cfg(feature = "_tiles")]
     use indoc::indoc;
 
     use super::*;
@@ -301,10 +361,14 @@ mod tests {
         let global = ProcessConfig {
             convert_to_mlt: Some(MltProcessConfig::Auto),
             convert_to_mvt: None,
+            cache_control: None,
+            overzoom: None,
         };
         let per_source = ProcessConfig {
             convert_to_mlt: Some(MltProcessConfig::Disabled),
             convert_to_mvt: None,
+            cache_control: None,
+            overzoom: None,
         };
         let resolved = resolve_process_config(&global, &ProcessConfig::default(), &per_source);
         assert_eq!(resolved.convert_to_mlt, Some(MltProcessConfig::Disabled));
@@ -316,10 +380,14 @@ mod tests {
         let global = ProcessConfig {
             convert_to_mlt: Some(MltProcessConfig::Auto),
             convert_to_mvt: None,
+            cache_control: None,
+            overzoom: None,
         };
         let source_type = ProcessConfig {
             convert_to_mlt: None,
             convert_to_mvt: Some(MvtProcessConfig::Auto),
+            cache_control: None,
+            overzoom: None,
         };
         let per_source = ProcessConfig {
             convert_to_mlt: Some(MltProcessConfig::Explicit(MltEncoderConfig {
@@ -327,6 +395,8 @@ mod tests {
                 ..Default::default()
             })),
             convert_to_mvt: None,
+            cache_control: None,
+            overzoom: None,
         };
 
         let resolved = resolve_process_config(&global, &source_type, &per_source);
@@ -339,10 +409,14 @@ mod tests {
         let global = ProcessConfig {
             convert_to_mlt: Some(MltProcessConfig::Auto),
             convert_to_mvt: None,
+            cache_control: None,
+            overzoom: None,
         };
         let source_type = ProcessConfig {
             convert_to_mlt: None,
             convert_to_mvt: Some(MvtProcessConfig::Auto),
+            cache_control: None,
+            overzoom: None,
         };
 
         let resolved = resolve_process_config(&global, &source_type, &ProcessConfig::default());
@@ -355,6 +429,8 @@ mod tests {
         let global = ProcessConfig {
             convert_to_mlt: Some(MltProcessConfig::Auto),
             convert_to_mvt: None,
+            cache_control: None,
+            overzoom: None,
         };
 
         let resolved = resolve_process_config(
@@ -365,6 +441,41 @@ mod tests {
         assert_eq!(resolved, global);
     }
 
+    #[cfg(feature = "_tiles")]
+    #[test]
+    fn resolve_cache_control_layers_zooms_over_defaults() {
+        let parse = |yaml: &str| ProcessConfig {
+            cache_control: Some(serde_saphyr::from_str(yaml).unwrap()),
+            ..ProcessConfig::default()
+        };
+        let global = parse("public, max-age=60");
+        let source_type = parse(indoc! {"
+            zooms:
+              - maxzoom: 5
+                value: public, max-age=31536000
+        "});
+        let per_source = parse(indoc! {"
+            zooms:
+              - minzoom: 14
+                value: public, max-age=3600, stale-while-revalidate=60
+        "});
+
+        let resolved = resolve_process_config(&global, &source_type, &per_source);
+        let policy = resolved.cache_control.unwrap();
+        let at = |zoom| policy.for_zoom(zoom).map(ToString::to_string);
+        assert_eq!(at(0).as_deref(), Some("public, max-age=31536000"));
+        assert_eq!(at(10).as_deref(), Some("public, max-age=60"));
+        assert_eq!(
+            at(14).as_deref(),
+            Some("public, max-age=3600, stale-while-revalidate=60")
+        );
+
+        // a per-source default shadows everything below it
+        let per_source = parse("public, immutable");
+        let resolved = resolve_process_config(&global, &source_type, &per_source);
+        assert_eq!(resolved.cache_control, per_source.cache_control);
+    }
+
     #[test]
     fn resolve_default_when_all_none() {
         let resolved = resolve_process_config(
@@ -514,4 +625,39 @@ mod tests {
             "expected $ref to MltEncoderConfig: {schema}"
         );
     }
+
+    #[cfg(feature = "_tiles")]
+    #[test]
+    fn resolve_overzoom_from_most_specific_level() {
+        let source_type = ProcessConfig {
+            overzoom: Some(18),
+            ..ProcessConfig::default()
+        };
+        let per_source = ProcessConfig {
+            overzoom: Some(20),
+            ..ProcessConfig::default()
+        };
+        let global = ProcessConfig::default();
+        let resolved = resolve_process_config(&global, &source_type, &ProcessConfig::default());
+        assert_eq!(resolved.overzoom, Some(18));
+        let resolved = resolve_process_config(&global, &source_type, &per_source);
+        assert_eq!(resolved.overzoom, Some(20));
+    }
+
+    #[cfg(feature = "_tiles")]
+    #[test]
+    fn overzoom_zooms() {
+        let tilejson = tilejson::tilejson! { tiles: vec![], minzoom: 0, maxzoom: 14 };
+        let pc = ProcessConfig {
+            overzoom: Some(16),
+            ..ProcessConfig::default()
+        };
+        assert_eq!(pc.overzoom_from(&tilejson, 14), None);
+        assert_eq!(pc.overzoom_from(&tilejson, 15), Some(14));
+        assert_eq!(pc.overzoom_from(&tilejson, 16), Some(14));
+        assert_eq!(pc.overzoom_from(&tilejson, 17), None);
+        assert_eq!(pc.maxzoom(&tilejson), Some(16));
+        assert_eq!(ProcessConfig::default().overzoom_from(&tilejson, 15), None);
+        assert_eq!(ProcessConfig::default().maxzoom(&tilejson), Some(14));
+    }
 }
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::time::Duration;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::config::file::{CollectUnrecognizedKeys, UnrecognizedKeys, UnrecognizedValues};
+
+/// Admission control for incoming requests.
+///
+/// Clients are identified by their IP address, or by the value of `client_header`
+/// if the request comes from one of the `trusted_proxies`.
+/// ```yaml
+/// rate_limit:
+///   client_header: X-Api-Key
+///   trusted_proxies: [10.0.0.0/8]
+///   requests_per_second: 50
+///   burst: 100
+///   source:
+///     max_in_flight: 32
+///   sources:
+///     buildings:
+///       requests_per_second: 10
+///       max_in_flight: 8
+///       queue_timeout: 2s
+/// ```
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct RateLimitConfig {
+    /// Identify clients by this request header, e.g. an API key, instead of their IP address.
+    /// The header is only used for requests from `trusted_proxies`, as clients could
+    /// avoid their limits by sending a different value with every request.
+    /// Other requests, and requests without the header, are identified by their IP address.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &"X-Api-Key"))]
+    pub client_header: Option<String>,
+    /// Addresses or networks of the reverse proxies allowed to set `client_header`
+    /// and the forwarding headers, e.g. `10.0.0.5` or `10.0.0.0/8`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Vec<String>", example = &["10.0.0.0/8"])
+    )]
+    pub trusted_proxies: Vec<ProxyNet>,
+    /// Take the client IP address from the `Forwarded` or `X-Forwarded-For` headers
+    /// of requests from `trusted_proxies`, as clients can forge them.
+    /// \[default: false\]
+    pub trust_forwarded: Option<bool>,
+    /// Sustained number of requests per second each client may make to the whole server.
+    /// Requests above this rate are rejected with `429 Too Many Requests`.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &50u32))]
+    pub requests_per_second: Option<NonZeroU32>,
+    /// Number of requests a client may make at once before `requests_per_second` applies
+    /// \[default: `requests_per_second`\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &100u32))]
+    pub burst: Option<NonZeroU32>,
+    /// Limits applied to each tile source, unless the source has its own in `sources`
+    pub source: Option<SourceLimitConfig>,
+    /// Limits of individual tile sources, by source ID.
+    /// Settings missing here are taken from `source`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, SourceLimitConfig>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+/// Address or network of a reverse proxy, e.g. `10.0.0.5` or `10.0.0.0/8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ProxyNet(IpNet);
+
+impl TryFrom<String> for ProxyNet {
+    type Error = ipnet::AddrParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .parse::<IpNet>()
+            .or_else(|e| value.parse::<IpAddr>().map(IpNet::from).map_err(|_err| e))
+            .map(Self)
+    }
+}
+
+/// Whether `addr` is one of the `trusted_proxies`
+#[must_use]
+pub fn is_trusted_proxy(trusted_proxies: &[ProxyNet], addr: IpAddr) -> bool {
+    // `::ffff:10.0.0.1` is `10.0.0.1` on a dual-stack socket
+    let addr = addr.to_canonical();
+    trusted_proxies.iter().any(|net| net.0.contains(&addr))
+}
+
+impl CollectUnrecognizedKeys for ProxyNet {
+    fn collect_unrecognized(&self, _path: &str, _out: &mut UnrecognizedKeys) {}
+}
+
+impl From<ProxyNet> for String {
+    fn from(value: ProxyNet) -> Self {
+        value.0.to_string()
+    }
+}
+
+/// Limits of tile requests to one source
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct SourceLimitConfig {
+    /// Sustained number of tile requests per second each client may make to the source.
+    /// Requests above this rate are rejected with `429 Too Many Requests`.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &10u32))]
+    pub requests_per_second: Option<NonZeroU32>,
+    /// Number of tile requests a client may make at once before `requests_per_second` applies
+    /// \[default: `requests_per_second`\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &20u32))]
+    pub burst: Option<NonZeroU32>,
+    /// Maximum number of tile requests to the source processed at the same time, from all clients
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &16usize))]
+    pub max_in_flight: Option<NonZeroUsize>,
+    /// How long a request waits for one of the `max_in_flight` slots
+    /// before it is rejected with `503 Service Unavailable`.
+    ///
+    /// Supports human-readable formats: "500ms", "2s".
+    /// Defaults to "0s", rejecting requests right away.
+    #[serde(default, with = "humantime_serde")]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Option<String>", example = &"2s")
+    )]
+    pub queue_timeout: Option<Duration>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl SourceLimitConfig {
+    /// Fills the settings missing in this config from `fallback`
+    #[must_use]
+    pub fn or(&self, fallback: &Self) -> Self {
+        Self {
+            requests_per_second: self.requests_per_second.or(fallback.requests_per_second),
+            burst: self.burst.or(fallback.burst),
+            max_in_flight: self.max_in_flight.or(fallback.max_in_flight),
+            queue_timeout: self.queue_timeout.or(fallback.queue_timeout),
+            unrecognized: UnrecognizedValues::default(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Whether `client_header` and the forwarding headers are used for requests from `addr`
+    #[must_use]
+    pub fn is_trusted_proxy(&self, addr: IpAddr) -> bool {
+        is_trusted_proxy(&self.trusted_proxies, addr)
+    }
+
+    /// The limits of the source with the given ID
+    #[must_use]
+    pub fn for_source(&self, id: &str) -> SourceLimitConfig {
+        let default = self.source.clone().unwrap_or_default();
+        self.sources
+            .get(id)
+            .map_or(default.clone(), |limits| limits.or(&default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn parse_rate_limit_config() {
+        let cfg: RateLimitConfig = serde_saphyr::from_str(indoc! {"
+            client_header: X-Api-Key
+            trusted_proxies: [10.0.0.0/8, 192.168.1.1]
+            requests_per_second: 50
+            source:
+              requests_per_second: 20
+              max_in_flight: 32
+            sources:
+              buildings:
+                max_in_flight: 8
+                queue_timeout: 2s
+        "})
+        .unwrap();
+        assert_eq!(cfg.client_header.as_deref(), Some("X-Api-Key"));
+        assert!(cfg.is_trusted_proxy("10.1.2.3".parse().unwrap()));
+        assert!(cfg.is_trusted_proxy("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(cfg.is_trusted_proxy("192.168.1.1".parse().unwrap()));
+        assert!(!cfg.is_trusted_proxy("192.168.1.2".parse().unwrap()));
+        assert_eq!(cfg.requests_per_second, NonZeroU32::new(50));
+        assert_eq!(cfg.burst, None);
+
+        let buildings = cfg.for_source("buildings");
+        assert_eq!(buildings.requests_per_second, NonZeroU32::new(20));
+        assert_eq!(buildings.max_in_flight, NonZeroUsize::new(8));
+        assert_eq!(buildings.queue_timeout, Some(Duration::from_secs(2)));
+
+        let other = cfg.for_source("roads");
+        assert_eq!(other.max_in_flight, NonZeroUsize::new(32));
+        assert_eq!(other.queue_timeout, None);
+
+        let yaml = serde_saphyr::to_string(&cfg).unwrap();
+        assert_eq!(
+            serde_saphyr::from_str::<RateLimitConfig>(&yaml).unwrap(),
+            cfg
+        );
+    }
+
+    #[test]
+    fn reject_invalid_proxy() {
+        serde_saphyr::from_str::<RateLimitConfig>("trusted_proxies: [10.0.0.0/33]").unwrap_err();
+        serde_saphyr::from_str::<RateLimitConfig>("trusted_proxies: [proxy]").unwrap_err();
+    }
+
+    #[test]
+    fn reject_zero_rate() {
+        serde_saphyr::from_str::<RateLimitConfig>("requests_per_second: 0").unwrap_err();
+    }
+}
@@ -3,19 +3,28 @@ use std::collections::HashMap;
 use std::fmt;
 
 use actix_web::http::header::{CacheDirective, HeaderValue, from_comma_delimited};
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{MapAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 
 use crate::config::args::PreferredEncoding;
 #[cfg(all(feature = "webui", not(docsrs)))]
 use crate::config::args::WebUiMode;
-#[cfg(feature = "metrics")]
 use crate::config::file::UnrecognizedValues;
+use crate::config::file::access_log::AccessLogConfig;
 use crate::config::file::cors::CorsConfig;
+use crate::config::file::health::HealthConfig;
+use crate::config::file::rate_limit::RateLimitConfig;
+use crate::config::file::tls::TlsConfig;
 use crate::config::file::{CollectUnrecognizedKeys, ConfigurationLivecycleHooks, UnrecognizedKeys};
 
 pub const DEFAULT_KEEP_ALIVE: u64 = 75;
 pub const DEFAULT_LISTEN_ADDRESSES: &str = "0.0.0.0:3000";
 
+/// `Cache-Control` extensions ([RFC 5861](https://www.rfc-editor.org/rfc/rfc5861))
+/// whose value must be a number of seconds
+const STALE_DIRECTIVES: &[&str] = &["stale-while-revalidate", "stale-if-error"];
+
 /// A syntactically and semantically validated `Cache-Control` header value.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CacheControlHeader(HeaderValue);
@@ -58,10 +67,206 @@ impl<'de> Deserialize<'de> for CacheControlHeader {
                 "invalid Cache-Control header value '{raw}': no valid directives"
             )));
         }
+        for directive in &directives {
+            if let CacheDirective::Extension(name, seconds) = directive
+                && STALE_DIRECTIVES.contains(&name.to_ascii_lowercase().as_str())
+                && seconds.as_deref().is_none_or(|s| s.parse::<u32>().is_err())
+            {
+                return Err(de::Error::custom(format_args!(
+                    "invalid Cache-Control header value '{raw}': {name} requires a number of seconds"
+                )));
+            }
+        }
         Ok(Self(value))
     }
 }
 
+/// Permissions of a Unix domain socket file, written in octal like `660`, `0660` or `0o660`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnixSocketMode(u32);
+
+impl UnixSocketMode {
+    /// The permission bits, e.g. `0o660`
+    #[must_use]
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl CollectUnrecognizedKeys for UnixSocketMode {
+    fn collect_unrecognized(&self, _path: &str, _out: &mut UnrecognizedKeys) {}
+}
+
+impl std::str::FromStr for UnixSocketMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        let digits = mode.strip_prefix("0o").unwrap_or(mode);
+        u32::from_str_radix(digits, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o777)
+            .map(Self)
+            .ok_or_else(|| {
+                format!("invalid unix_socket_mode '{mode}', expected octal permissions like 660")
+            })
+    }
+}
+
+impl Serialize for UnixSocketMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:o}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for UnixSocketMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// `Cache-Control` header of tile responses.
+///
+/// Either a single header value for all zoom levels:
+/// ```yaml
+/// cache_control: public, max-age=3600, stale-while-revalidate=60
+/// ```
+///
+/// Or different values for zoom ranges, checked in order:
+/// ```yaml
+/// cache_control:
+///   default: public, max-age=3600
+///   zooms:
+///     - maxzoom: 8
+///       value: public, max-age=2592000
+///     - minzoom: 14
+///       value: public, max-age=300, stale-while-revalidate=60, stale-if-error=86400
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum CacheControlPolicy {
+    Header(#[cfg_attr(feature = "unstable-schemas", schemars(with = "String"))] CacheControlHeader),
+    Zooms(Box<CacheControlZooms>),
+}
+
+/// `Cache-Control` header values for zoom ranges
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct CacheControlZooms {
+    /// Used for zoom levels outside all the `zooms` ranges.
+    /// If not set, the value of the source type or the global `cache_control` is used.
+    #[cfg_attr(feature = "unstable-schemas", schemars(with = "Option<String>"))]
+    pub default: Option<CacheControlHeader>,
+    /// Header values for zoom ranges. The first range containing the zoom level is used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub zooms: Vec<ZoomCacheControl>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+/// `Cache-Control` header value for a range of zoom levels
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct ZoomCacheControl {
+    /// Lowest zoom level of the range (inclusive) \[default: 0\]
+    pub minzoom: Option<u8>,
+    /// Highest zoom level of the range (inclusive) \[default: no limit\]
+    pub maxzoom: Option<u8>,
+    /// `Cache-Control` header value for these zoom levels
+    #[cfg_attr(feature = "unstable-schemas", schemars(with = "String"))]
+    pub value: CacheControlHeader,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl ZoomCacheControl {
+    #[must_use]
+    pub fn contains(&self, zoom: u8) -> bool {
+        self.minzoom.is_none_or(|min| min <= zoom) && self.maxzoom.is_none_or(|max| zoom <= max)
+    }
+}
+
+impl CacheControlPolicy {
+    /// The value used for zoom levels outside all the zoom ranges
+    #[must_use]
+    pub fn default_header(&self) -> Option<&CacheControlHeader> {
+        match self {
+            Self::Header(header) => Some(header),
+            Self::Zooms(zooms) => zooms.default.as_ref(),
+        }
+    }
+
+    #[must_use]
+    pub fn zooms(&self) -> &[ZoomCacheControl] {
+        match self {
+            Self::Header(_) => &[],
+            Self::Zooms(zooms) => &zooms.zooms,
+        }
+    }
+
+    /// The header value for tiles of this zoom level
+    #[must_use]
+    pub fn for_zoom(&self, zoom: u8) -> Option<&CacheControlHeader> {
+        self.zooms()
+            .iter()
+            .find(|z| z.contains(zoom))
+            .map(|z| &z.value)
+            .or_else(|| self.default_header())
+    }
+
+    /// Layers this policy over a less specific one, e.g. a source over its source type.
+    ///
+    /// Zoom levels this policy has no value for use the zoom ranges and the default of `fallback`.
+    #[must_use]
+    pub fn or(self, fallback: &Self) -> Self {
+        if self.default_header().is_some() {
+            return self;
+        }
+        let mut zooms = self.zooms().to_vec();
+        zooms.extend_from_slice(fallback.zooms());
+        Self::Zooms(Box::new(CacheControlZooms {
+            default: fallback.default_header().cloned(),
+            zooms,
+            unrecognized: UnrecognizedValues::default(),
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for CacheControlPolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CacheControlPolicyVisitor;
+
+        impl<'de> Visitor<'de> for CacheControlPolicyVisitor {
+            type Value = CacheControlPolicy;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a Cache-Control header value or a map with `default` and `zooms`")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<CacheControlPolicy, E> {
+                let header =
+                    CacheControlHeader::deserialize(de::value::StrDeserializer::new(value))?;
+                Ok(CacheControlPolicy::Header(header))
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, map: M) -> Result<CacheControlPolicy, M::Error> {
+                let zooms = CacheControlZooms::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(CacheControlPolicy::Zooms(Box::new(zooms)))
+            }
+        }
+
+        deserializer.deserialize_any(CacheControlPolicyVisitor)
+    }
+}
+
 #[serde_with::skip_serializing_none]
 #[derive(
     Clone,
@@ -79,8 +284,18 @@ pub struct SrvConfig {
     #[cfg_attr(feature = "unstable-schemas", schemars(example = &75u64))]
     pub keep_alive: Option<u64>,
     /// The socket address to bind \[default: `0.0.0.0:3000`\]
+    ///
+    /// Use `unix:/path/to/martin.sock` to listen on a Unix domain socket instead.
+    /// Sockets passed by systemd socket activation (`LISTEN_FDS`) are used instead of this address.
     #[cfg_attr(feature = "unstable-schemas", schemars(example = &"0.0.0.0:3000"))]
     pub listen_addresses: Option<String>,
+    /// Permissions of the Unix domain socket file in octal, e.g. `660`.
+    /// By default, the permissions depend on the umask of the process.
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Option<String>", example = &"660")
+    )]
+    pub unix_socket_mode: Option<UnixSocketMode>,
     /// Set the URL path prefix for all API routes.
     /// When set, Martin will serve all endpoints under this path prefix.
     /// This allows Martin to be served under a subpath when behind a reverse proxy (e.g., Traefik).
@@ -109,21 +324,34 @@ pub struct SrvConfig {
     /// The value is used for responses that do not define a more specific cache policy.
     /// For example: `public, max-age=3600`.
     /// Endpoints with an explicit policy, such as the health check, keep their own header.
-    #[cfg_attr(feature = "unstable-schemas", schemars(with = "Option<String>"))]
-    pub cache_control: Option<CacheControlHeader>,
+    ///
+    /// Tile responses can use different values per zoom range, see [`CacheControlPolicy`],
+    /// and each source type and source can override it with its own `cache_control`.
+    pub cache_control: Option<CacheControlPolicy>,
     /// Enable or disable Martin web UI. \[default: disable\]
     ///
     /// At the moment, only allows `enable-for-all`, which enables the web UI for all connections.
     /// This may be undesirable in a production environment
     #[cfg(all(feature = "webui", not(docsrs)))]
     pub web_ui: Option<WebUiMode>,
+    /// Serve HTTPS with HTTP/2 using this certificate.
+    /// Certificates renewed on disk are picked up without a restart.
+    pub tls: Option<TlsConfig>,
     /// CORS Configuration
     ///
     /// Defaults to `cors: true`, which allows all origins.
     /// Sending/Acting on CORS headers can be completely disabled via `cors: false`
     pub cors: Option<CorsConfig>,
+    /// Limit the request rate of each client, and the concurrent requests to each tile source.
+    ///
+    /// Rejected requests get a `429` or `503` response with a `Retry-After` header.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Write an access log line for each request, to the standard output or a rotating file
+    pub access_log: Option<AccessLogConfig>,
+    /// Configure the backend checks of the `/ready` and `/_/status` endpoints
+    pub health: Option<HealthConfig>,
     /// Advanced monitoring options
-    #[cfg(feature = "metrics")]
+    #[cfg(any(feature = "metrics", feature = "opentelemetry"))]
     pub observability: Option<ObservabilityConfig>,
     /// If set, the version of the tileset (as specified in the `MBTiles` or `PMTiles` metadata)
     /// will be embedded in the `TileJSON` `tiles` URL, with the set identifier.
@@ -151,6 +379,7 @@ impl SrvConfig {
     pub(crate) fn cache_control_header(&self) -> Option<HeaderValue> {
         self.cache_control
             .as_ref()
+            .and_then(CacheControlPolicy::default_header)
             .map(CacheControlHeader::header_value)
     }
 
@@ -168,7 +397,7 @@ impl SrvConfig {
 }
 
 /// More advanced monitoring options
-#[cfg(feature = "metrics")]
+#[cfg(any(feature = "metrics", feature = "opentelemetry"))]
 #[serde_with::skip_serializing_none]
 #[derive(
     Clone,
@@ -183,7 +412,11 @@ impl SrvConfig {
 #[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
 pub struct ObservabilityConfig {
     /// Configure metrics reported under `/_/metrics`
+    #[cfg(feature = "metrics")]
     pub metrics: Option<MetricsConfig>,
+    /// Export tracing spans to an OpenTelemetry collector via OTLP
+    #[cfg(feature = "opentelemetry")]
+    pub tracing: Option<TracingConfig>,
 
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
@@ -192,6 +425,7 @@ pub struct ObservabilityConfig {
 
 /// Configure metrics reported under `/_/metrics`
 #[cfg(feature = "metrics")]
+#[serde_with::skip_serializing_none]
 #[derive(
     Clone,
     Debug,
@@ -208,6 +442,74 @@ pub struct MetricsConfig {
     /// Example: `{ env: prod, server: martin }`
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub add_labels: HashMap<String, String>,
+    /// Maximum number of sources labeled with their ID in the per-source metrics.
+    /// Further sources are counted under `source="_other"`, and `0` disables the per-source labels.
+    /// \[default: 100\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &100usize))]
+    pub max_source_labels: Option<usize>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+/// Transport used to send spans to the OpenTelemetry collector
+#[cfg(feature = "opentelemetry")]
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, CollectUnrecognizedKeys,
+)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub enum OtlpProtocol {
+    /// OTLP over gRPC, usually on port 4317
+    #[default]
+    #[serde(rename = "grpc")]
+    Grpc,
+    /// OTLP over HTTP with protobuf payloads, usually on port 4318
+    #[serde(rename = "http")]
+    Http,
+}
+
+/// Export tracing spans to an OpenTelemetry collector via OTLP.
+///
+/// The standard `OTEL_EXPORTER_OTLP_*` environment variables, e.g. for headers or timeouts,
+/// are honored for settings not configured here.
+#[cfg(feature = "opentelemetry")]
+#[serde_with::skip_serializing_none]
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Default,
+    CollectUnrecognizedKeys,
+    ConfigurationLivecycleHooks,
+)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct TracingConfig {
+    /// URL of the collector, e.g. `http://localhost:4317` for gRPC
+    /// or `http://localhost:4318/v1/traces` for HTTP.
+    ///
+    /// Defaults to `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` or `OTEL_EXPORTER_OTLP_ENDPOINT`,
+    /// or the local collector if neither is set.
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(example = &"http://localhost:4317")
+    )]
+    pub endpoint: Option<String>,
+    /// Transport to the collector, `grpc` or `http` \[default: `grpc`\]
+    #[serde(default)]
+    pub protocol: OtlpProtocol,
+    /// Name of the service reported with every span.
+    ///
+    /// Defaults to `OTEL_SERVICE_NAME`, or `martin` if not set.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &"martin"))]
+    pub service_name: Option<String>,
+    /// Fraction of requests to trace, between `0.0` and `1.0` \[default: `1.0`\]
+    ///
+    /// Requests with a `traceparent` header follow the sampling decision of the caller instead.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &0.1))]
+    pub sample_ratio: Option<f64>,
 
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
@@ -273,6 +575,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_unix_socket_mode() {
+        for (mode, bits) in [("660", 0o660), ("0660", 0o660), ("0o777", 0o777)] {
+            assert_eq!(mode.parse::<UnixSocketMode>().unwrap().bits(), bits);
+        }
+        for mode in ["", "rw", "888", "1777", "-1"] {
+            assert!(mode.parse::<UnixSocketMode>().is_err(), "{mode}");
+        }
+
+        let config = serde_saphyr::from_str::<SrvConfig>("unix_socket_mode: '0660'").unwrap();
+        assert_eq!(
+            config.unix_socket_mode.map(UnixSocketMode::bits),
+            Some(0o660)
+        );
+        let err = serde_saphyr::from_str::<SrvConfig>("unix_socket_mode: '999'").unwrap_err();
+        assert!(
+            err.to_string().contains("invalid unix_socket_mode '999'"),
+            "{err}"
+        );
+    }
+
     #[test]
     fn parse_cache_control() {
         let config = serde_saphyr::from_str::<SrvConfig>(indoc! {"
@@ -281,7 +604,11 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            config.cache_control.as_ref().map(ToString::to_string),
+            config
+                .cache_control
+                .as_ref()
+                .and_then(CacheControlPolicy::default_header)
+                .map(ToString::to_string),
             Some("public, max-age=3600, stale-while-revalidate=60".to_owned())
         );
         assert_eq!(
@@ -290,6 +617,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_cache_control_zooms() {
+        let config = serde_saphyr::from_str::<SrvConfig>(indoc! {"
+            cache_control:
+              default: public, max-age=3600
+              zooms:
+                - maxzoom: 6
+                  value: public, max-age=31536000, immutable
+                - minzoom: 14
+                  value: public, max-age=300, stale-if-error=86400
+        "})
+        .unwrap();
+        let policy = config.cache_control.as_ref().unwrap();
+        let at = |zoom| policy.for_zoom(zoom).map(ToString::to_string);
+
+        assert_eq!(
+            at(0).as_deref(),
+            Some("public, max-age=31536000, immutable")
+        );
+        assert_eq!(
+            at(6).as_deref(),
+            Some("public, max-age=31536000, immutable")
+        );
+        assert_eq!(at(7).as_deref(), Some("public, max-age=3600"));
+        assert_eq!(
+            at(20).as_deref(),
+            Some("public, max-age=300, stale-if-error=86400")
+        );
+        // non-tile endpoints only use the default
+        assert_eq!(
+            config.cache_control_header().unwrap(),
+            "public, max-age=3600"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_stale_directive() {
+        insta::assert_snapshot!(
+            render_failure(indoc::indoc! {"
+                cache_control: public, stale-while-revalidate=soon
+            "}),
+            @r"
+martin::config::yaml (https://maplibre.org/martin/config-file/)
+
+  × invalid Cache-Control header value 'public, stale-while-revalidate=soon':
+  │ stale-while-revalidate requires a number of seconds
+  help: Check the highlighted token in your YAML. The error usually indicates
+        a mismatched type or an unexpected shape.
+");
+    }
+
     #[test]
     fn reject_invalid_cache_control_header() {
         insta::assert_snapshot!(
@@ -381,4 +759,35 @@ martin::config::yaml (https://maplibre.org/martin/config-file/)
             }
         );
     }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn parse_config_tracing() {
+        let config = serde_saphyr::from_str::<SrvConfig>(indoc! {"
+            observability:
+              tracing:
+                endpoint: http://localhost:4318/v1/traces
+                protocol: http
+                sample_ratio: 0.25
+        "})
+        .unwrap();
+        assert_eq!(
+            config.observability.unwrap().tracing.unwrap(),
+            TracingConfig {
+                endpoint: Some("http://localhost:4318/v1/traces".to_owned()),
+                protocol: OtlpProtocol::Http,
+                sample_ratio: Some(0.25),
+                ..Default::default()
+            }
+        );
+
+        let config = serde_saphyr::from_str::<SrvConfig>(indoc! {"
+            observability:
+              tracing: {}
+        "})
+        .unwrap();
+        let tracing = config.observability.unwrap().tracing.unwrap();
+        assert_eq!(tracing.protocol, OtlpProtocol::Grpc);
+        assert_eq!(tracing.endpoint, None);
+    }
 }
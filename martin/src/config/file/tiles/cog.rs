@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use url::Url;
 
 use crate::MartinResult;
+use crate::config::file::srv::CacheControlPolicy;
 use crate::config::file::{
     CachePolicy, CollectUnrecognizedKeys, ConfigurationLivecycleHooks, TileSourceConfiguration,
     UnrecognizedValues,
@@ -24,6 +25,11 @@ use crate::config::file::{
 )]
 #[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
 pub struct CogConfig {
+    /// `Cache-Control` header of the tiles of all COG sources.
+    /// Overrides global; overridden by per-source `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
@@ -8,6 +8,8 @@ use serde::{Deserialize, Serialize};
 use url::Url;
 
 use crate::MartinResult;
+use crate::config::file::srv::CacheControlPolicy;
+use crate::config::file::tile_matrix_sets::TileMatrixSets;
 use crate::config::file::{
     CachePolicy, CollectUnrecognizedKeys, ConfigurationLivecycleHooks, TileSourceConfiguration,
     UnrecognizedValues,
@@ -58,9 +60,24 @@ pub struct GeoJsonConfig {
     #[serde(default = "default_buffer", skip_serializing_if = "is_default_buffer")]
     pub buffer: u32,
 
+    /// Tile matrix set of the generated tiles, defaulting to `WebMercatorQuad`.
+    /// Must be a tile matrix set in the Web Mercator or WGS84 coordinate reference system.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tile_matrix_set: Option<String>,
+
+    /// `Cache-Control` header of the tiles of all `GeoJSON` sources.
+    /// Overrides global; overridden by per-source `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
+
+    /// All known tile matrix sets (internal state, not serialized)
+    #[serde(skip)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub tile_matrix_sets: TileMatrixSets,
 }
 
 impl Default for GeoJsonConfig {
@@ -68,7 +85,10 @@ impl Default for GeoJsonConfig {
         Self {
             extent: default_extent(),
             buffer: default_buffer(),
+            tile_matrix_set: None,
+            cache_control: None,
             unrecognized: UnrecognizedValues::default(),
+            tile_matrix_sets: TileMatrixSets::default(),
         }
     }
 }
@@ -84,8 +104,9 @@ impl TileSourceConfiguration for GeoJsonConfig {
         path: PathBuf,
         cache: CachePolicy,
     ) -> MartinResult<BoxedSource> {
+        let tms = self.tile_matrix_sets.get(self.tile_matrix_set.as_deref())?;
         let geojson_source =
-            GeoJsonSource::new(id, path, cache.zoom(), self.extent, self.buffer).await?;
+            GeoJsonSource::new(id, path, cache.zoom(), tms, self.extent, self.buffer).await?;
         Ok(Box::new(geojson_source))
     }
 
@@ -166,6 +187,8 @@ mod tests {
                         #[cfg(all(feature = "mlt", feature = "_tiles"))]
                         convert_to_mvt: None,
                         cache: CachePolicy::default(),
+                        cache_control: None,
+                        overzoom: None,
                     })
                 ),
                 (
@@ -181,6 +204,8 @@ mod tests {
                         #[cfg(all(feature = "mlt", feature = "_tiles"))]
                         convert_to_mvt: None,
                         cache: CachePolicy::default(),
+                        cache_control: None,
+                        overzoom: None,
                     })
                 ),
             ]))
@@ -1,15 +1,18 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 use std::path::PathBuf;
 
 use martin_core::tiles::BoxedSource;
-use martin_core::tiles::mbtiles::MbtSource;
+use martin_core::tiles::mbtiles::{MbtSource, MbtWriter, MbtWriters, PatchFeed, PatchFeeds};
 use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 use url::Url;
 
 use crate::MartinResult;
+use crate::config::file::srv::CacheControlPolicy;
 use crate::config::file::{
-    CachePolicy, CollectUnrecognizedKeys, ConfigurationLivecycleHooks, TileSourceConfiguration,
-    UnrecognizedValues,
+    CachePolicy, CollectUnrecognizedKeys, ConfigFileError, ConfigFileResult,
+    ConfigurationLivecycleHooks, FileConfigEnum, TileSourceConfiguration, UnrecognizedValues,
 };
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 use crate::config::file::{MltProcessConfig, MvtProcessConfig};
@@ -39,6 +42,43 @@ pub struct MbtConfig {
     #[serde(default)]
     pub convert_to_mvt: Option<MvtProcessConfig>,
 
+    /// `Cache-Control` header of the tiles of all `MBTiles` sources.
+    /// Overrides global; overridden by per-source `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
+    /// Serve tiles of all `MBTiles` sources up to this zoom level, beyond their `maxzoom`,
+    /// by cutting them out of the tiles at their `maxzoom`. Overridden by per-source `overzoom`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overzoom: Option<u8>,
+
+    /// Directories with patch files created by `mbtiles diff`, by source ID.
+    /// Clients can download the patches which update their copy of a source to the served version.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub patches: BTreeMap<String, PathBuf>,
+
+    /// Accept authenticated `PUT` and `DELETE` requests which replace or delete single tiles
+    /// of some `MBTiles` sources. Disabled by default.
+    pub write: Option<MbtWriteConfig>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+/// Writing single tiles into `MBTiles` sources.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct MbtWriteConfig {
+    /// IDs of the sources whose files are opened read-write
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Token that writes must send as `Authorization: Bearer <token>`.
+    /// Use `${ENV_VAR}` substitution to keep it out of the config file.
+    #[serde(default)]
+    pub token: String,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
@@ -54,7 +94,17 @@ impl TileSourceConfiguration for MbtConfig {
         path: PathBuf,
         cache: CachePolicy,
     ) -> MartinResult<BoxedSource> {
-        Ok(Box::new(MbtSource::new(id, path, cache.zoom()).await?))
+        // Writes change the file while it is served
+        let writable = self
+            .write
+            .as_ref()
+            .is_some_and(|write| write.sources.contains(&id));
+        let source = MbtSource::new(id, path.clone(), cache.zoom()).await?;
+        Ok(Box::new(if writable {
+            source.with_live_modified(path)
+        } else {
+            source
+        }))
     }
 
     #[expect(
@@ -71,18 +121,72 @@ impl TileSourceConfiguration for MbtConfig {
     }
 }
 
+impl FileConfigEnum<MbtConfig> {
+    /// Creates the patch feeds of the sources with a `patches` directory.
+    ///
+    /// Must be called after the tile sources are resolved, so that `sources` includes the discovered files.
+    pub fn resolve_patches(&self) -> ConfigFileResult<PatchFeeds> {
+        let Self::Config(cfg) = self else {
+            return Ok(PatchFeeds::default());
+        };
+        let mut feeds = HashMap::new();
+        for (id, dir) in &cfg.custom.patches {
+            let Some(source) = cfg.sources.as_ref().and_then(|s| s.get(id)) else {
+                warn!("Ignoring patches of {id} because there is no such MBTiles source");
+                continue;
+            };
+            let dir = dir
+                .canonicalize()
+                .map_err(|e| ConfigFileError::IoError(e, dir.clone()))?;
+            if !dir.is_dir() {
+                return Err(ConfigFileError::InvalidPatchesDirectory(id.clone(), dir));
+            }
+            info!("Serving patches of {id} from {}", dir.display());
+            feeds.insert(id.clone(), PatchFeed::new(source.get_path().clone(), dir));
+        }
+        Ok(PatchFeeds::new(feeds))
+    }
+
+    /// Opens the files of the sources listed in `write` for writing.
+    ///
+    /// Must be called after the tile sources are resolved, so that `sources` includes the discovered files.
+    pub async fn resolve_writers(&self) -> MartinResult<MbtWriters> {
+        let Self::Config(cfg) = self else {
+            return Ok(MbtWriters::default());
+        };
+        let Some(write) = &cfg.custom.write else {
+            return Ok(MbtWriters::default());
+        };
+        if write.token.is_empty() {
+            return Err(ConfigFileError::MissingMbtilesWriteToken.into());
+        }
+        let mut writers = HashMap::new();
+        for id in &write.sources {
+            let Some(source) = cfg.sources.as_ref().and_then(|s| s.get(id)) else {
+                warn!("Ignoring writes to {id} because there is no such MBTiles source");
+                continue;
+            };
+            info!("Accepting tile writes to {id}");
+            let writer = MbtWriter::open(source.get_path().clone()).await?;
+            writers.insert(id.clone(), writer);
+        }
+        Ok(MbtWriters::new(writers, &write.token))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
     use std::path::PathBuf;
 
-    use indoc::indoc;
+    use indoc::{formatdoc, indoc};
     use martin_core::CacheZoomRange;
 
+    use crate::MartinError;
     use crate::config::file::mbtiles::MbtConfig;
     use crate::config::file::{
-        CachePolicy, CollectUnrecognizedKeys as _, ConfigurationLivecycleHooks as _,
-        FileConfigEnum, FileConfigSource, FileConfigSrc,
+        CachePolicy, CollectUnrecognizedKeys as _, ConfigFileError,
+        ConfigurationLivecycleHooks as _, FileConfigEnum, FileConfigSource, FileConfigSrc,
     };
 
     #[tokio::test]
@@ -140,6 +244,8 @@ mod tests {
                         #[cfg(all(feature = "mlt", feature = "_tiles"))]
                         convert_to_mvt: None,
                         cache: CachePolicy::default(),
+                        cache_control: None,
+                        overzoom: None,
                     })
                 ),
                 (
@@ -155,6 +261,8 @@ mod tests {
                         #[cfg(all(feature = "mlt", feature = "_tiles"))]
                         convert_to_mvt: None,
                         cache: CachePolicy::default(),
+                        cache_control: None,
+                        overzoom: None,
                     })
                 ),
                 (
@@ -166,9 +274,84 @@ mod tests {
                         #[cfg(all(feature = "mlt", feature = "_tiles"))]
                         convert_to_mvt: None,
                         cache: CachePolicy::new(CacheZoomRange::new(Some(0), Some(6))),
+                        cache_control: None,
+                        overzoom: None,
                     })
                 ),
             ]))
         );
     }
+
+    #[test]
+    fn resolve_patches() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = serde_saphyr::from_str::<FileConfigEnum<MbtConfig>>(&formatdoc! {"
+            sources:
+                src1: /tmp/file.mbtiles
+            patches:
+                src1: {dir}
+                unknown: {dir}
+            ",
+            dir = dir.path().display()
+        })
+        .unwrap();
+        let feeds = cfg.resolve_patches().unwrap();
+        assert!(feeds.get("src1").is_some());
+        assert!(feeds.get("unknown").is_none());
+
+        let cfg = serde_saphyr::from_str::<FileConfigEnum<MbtConfig>>(&formatdoc! {"
+            sources:
+                src1: /tmp/file.mbtiles
+            patches:
+                src1: {file}
+            ",
+            file = dir.path().join("missing").display()
+        })
+        .unwrap();
+        assert!(matches!(
+            cfg.resolve_patches(),
+            Err(ConfigFileError::IoError(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file.mbtiles");
+        let mbt = mbtiles::Mbtiles::new(&file).unwrap();
+        let mut conn = mbt.open_or_new().await.unwrap();
+        mbtiles::init_mbtiles_schema(&mut conn, mbtiles::MbtType::Flat, true)
+            .await
+            .unwrap();
+        let cfg = serde_saphyr::from_str::<FileConfigEnum<MbtConfig>>(&formatdoc! {"
+            sources:
+                src1: {file}
+                src2: {file}
+            write:
+                sources: [src1, unknown]
+                token: secret
+            ",
+            file = file.display()
+        })
+        .unwrap();
+        let writers = cfg.resolve_writers().await.unwrap();
+        assert!(writers.get("src1").is_some());
+        assert!(writers.get("src2").is_none());
+        assert!(writers.get("unknown").is_none());
+        assert!(writers.is_authorized("secret"));
+
+        let cfg = serde_saphyr::from_str::<FileConfigEnum<MbtConfig>>(indoc! {"
+            sources:
+                src1: /tmp/file.mbtiles
+            write:
+                sources: [src1]
+            "})
+        .unwrap();
+        assert!(matches!(
+            cfg.resolve_writers().await,
+            Err(MartinError::ConfigFileError(
+                ConfigFileError::MissingMbtilesWriteToken
+            ))
+        ));
+    }
 }
@@ -17,5 +17,7 @@ pub mod postgres;
 pub mod discovery;
 #[cfg(feature = "_tiles")]
 pub mod driver;
+#[cfg(feature = "_tiles")]
+pub mod tile_matrix_sets;
 
 pub mod reload;
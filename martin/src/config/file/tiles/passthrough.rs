@@ -1,9 +1,13 @@
 use std::collections::BTreeMap;
 use std::fmt;
+use std::num::NonZeroU64;
 use std::time::Duration;
 
 use martin_core::tiles::BoxedSource;
-use martin_core::tiles::passthrough::{PassthroughSource, TemplateMeta, Transport, Upstream};
+use martin_core::tiles::passthrough::{
+    CircuitBreakerPolicy, MapService, MapServiceParams, MapServiceSet, PassthroughSource,
+    Resilience, RetryPolicy, StalePolicy, TemplateMeta, Transport, Upstream,
+};
 use martin_tile_utils::Format;
 use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
 use serde::de::{self, MapAccess, SeqAccess, Visitor};
@@ -12,6 +16,7 @@ use tilejson::Bounds;
 use tracing::info;
 
 use crate::MartinResult;
+use crate::config::file::srv::CacheControlPolicy;
 use crate::config::file::{
     CachePolicy, CollectUnrecognizedKeys, ConfigFileError, ConfigurationLivecycleHooks,
     ResolutionResult, TileSourceWarning, UnrecognizedValues,
@@ -20,6 +25,18 @@ use crate::config::file::{
 use crate::config::file::{MltProcessConfig, MvtProcessConfig};
 use crate::config::primitives::{IdResolver, OptOneMany};
 
+/// Default size of the stale tiles kept per source.
+const DEFAULT_STALE_MAX_SIZE_MB: u64 = 64;
+
+/// Default age after which stale tiles are not served anymore.
+const DEFAULT_STALE_MAX_AGE: Duration = Duration::from_hours(24);
+
+/// Default WMS version of `GetMap` requests.
+const DEFAULT_WMS_VERSION: &str = "1.3.0";
+
+/// Default width and height of map service tiles.
+const DEFAULT_MAP_TILE_SIZE: u32 = 256;
+
 /// Default per-request timeout for upstream fetches.
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -43,7 +60,15 @@ fn passthrough_sources_example() -> serde_json::Value {
             "headers": { "Authorization": "${API_TOKEN}" },
             "format": "mvt",
             "minzoom": 0,
-            "maxzoom": 14
+            "maxzoom": 14,
+            "retry": { "max_retries": 2 },
+            "circuit_breaker": { "failure_threshold": 5, "open_duration": "30s" },
+            "serve_stale": { "max_size_mb": 64, "max_age": "1d" }
+        },
+        "parcels": {
+            "url": "https://maps.example.org/wms",
+            "wms": { "layers": ["parcels", "buildings"], "transparent": true, "metatile": 4 },
+            "format": "png"
         }
     })
 }
@@ -66,14 +91,22 @@ pub struct PassthroughConfig {
     #[serde(default)]
     pub convert_to_mvt: Option<MvtProcessConfig>,
 
+    /// `Cache-Control` header of the tiles of all passthrough sources.
+    /// Overrides global; overridden by per-source `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
     /// Upstream tile servers to proxy, keyed by the source ID Martin serves them under.
     ///
     /// Each value is one of:
     /// - a `{z}/{x}/{y}` URL template, e.g. `https://tile.openstreetmap.org/{z}/{x}/{y}.png`
     /// - a `TileJSON` document URL; its tile URLs, zoom range, and bounds are read from the document
     /// - a list of URL templates, to spread requests across mirror upstreams
-    /// - an object with `url` plus any of `headers` (e.g. for auth), `timeout`, `format`,
-    ///   `minzoom`/`maxzoom`/`bounds`/`attribution`, `cache`, and `convert_to_mlt`/`convert_to_mvt`
+    /// - an object with `url` plus any of `wms` or `arcgis` (for map service upstreams),
+    ///   `headers` (e.g. for auth), `timeout`, `format`,
+    ///   `minzoom`/`maxzoom`/`bounds`/`attribution`, `cache`, `cache_control`,
+    ///   `honor_cache_headers`, `retry`, `circuit_breaker`, `serve_stale`,
+    ///   and `convert_to_mlt`/`convert_to_mvt`
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[cfg_attr(feature = "unstable-schemas", schemars(example = &passthrough_sources_example()))]
     pub sources: Option<BTreeMap<String, PassthroughSrc>>,
@@ -88,6 +121,7 @@ impl PassthroughConfig {
     #[must_use]
     pub fn is_empty(&self) -> bool {
         let empty = self.sources.as_ref().is_none_or(BTreeMap::is_empty)
+            && self.cache_control.is_none()
             && self.get_unrecognized_keys().is_empty();
         #[cfg(all(feature = "mlt", feature = "_tiles"))]
         let empty = empty && self.convert_to_mlt.is_none() && self.convert_to_mvt.is_none();
@@ -209,9 +243,16 @@ impl<'de> Deserialize<'de> for PassthroughSrc {
 #[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
 pub struct PassthroughSourceConfig {
     /// Upstream tile-URL template(s) (`{z}/{x}/{y}`) or a single `TileJSON` document URL.
+    /// With `wms` or `arcgis`, the URL(s) of the map service.
     #[serde(default, skip_serializing_if = "OptOneMany::is_none")]
     pub url: OptOneMany<String>,
 
+    /// Request the tiles from a WMS with `GetMap` requests of their `EPSG:3857` bounding box.
+    pub wms: Option<WmsConfig>,
+    /// Request the tiles from an `ArcGIS` REST `MapServer` with `export` requests of their
+    /// `EPSG:3857` bounding box.
+    pub arcgis: Option<ArcGisConfig>,
+
     /// HTTP headers sent with every upstream request (e.g. `Authorization`).
     /// Values support `${ENV_VAR}` substitution via the config loader.
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
@@ -228,6 +269,7 @@ pub struct PassthroughSourceConfig {
 
     /// Explicit tile format override (e.g. `mvt`, `png`). When unset, the format is detected
     /// from the URL extension, the upstream `TileJSON`, or the response.
+    /// For `wms` and `arcgis` upstreams, the requested image format \[default: png\]
     pub format: Option<String>,
 
     /// Minimum zoom level advertised in the served `TileJSON` (template sources only).
@@ -259,15 +301,232 @@ pub struct PassthroughSourceConfig {
     #[serde(default)]
     pub convert_to_mvt: Option<MvtProcessConfig>,
 
+    /// `Cache-Control` header of the tiles of this source.
+    /// Overrides source-type and global `cache_control`, and the upstream caching headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
+    /// Use the `Cache-Control`, `Expires` and `Last-Modified` headers of the upstream for
+    /// how long tiles are cached, and for the caching headers sent to clients \[default: true\]
+    pub honor_cache_headers: Option<bool>,
+
+    /// Retries of tile fetches that failed with a network error, a timeout,
+    /// or a `5xx`/`408`/`429` status. Enabled by default.
+    pub retry: Option<RetryConfig>,
+    /// Fail fast on an upstream URL that keeps failing. Enabled by default.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Serve the last good copy of a tile if no upstream can deliver it,
+    /// and revalidate the kept copies with the upstream instead of downloading them again.
+    /// Disabled unless set.
+    pub serve_stale: Option<ServeStaleConfig>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+/// Retries of failed upstream fetches.
+///
+/// A failed fetch first fails over to the other URLs of the source, and the retries repeat
+/// that round after an exponentially growing, jittered delay.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct RetryConfig {
+    /// Number of retries, `0` disables them \[default: 2\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &2u32))]
+    pub max_retries: Option<u32>,
+    /// Delay before the first retry. Defaults to "100ms".
+    #[serde(default, with = "humantime_serde")]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Option<String>", example = &"100ms")
+    )]
+    pub min_delay: Option<Duration>,
+    /// Upper bound of the delay between retries. Defaults to "2s".
+    #[serde(default, with = "humantime_serde")]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Option<String>", example = &"2s")
+    )]
+    pub max_delay: Option<Duration>,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
 }
 
+impl RetryConfig {
+    fn policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: self.max_retries.unwrap_or(default.max_retries),
+            min_delay: self.min_delay.unwrap_or(default.min_delay),
+            max_delay: self.max_delay.unwrap_or(default.max_delay),
+        }
+    }
+}
+
+/// Circuit breaker of each upstream URL of a source.
+///
+/// After `failure_threshold` consecutive failures, requests to the URL fail right away
+/// (or go to a mirror) for `open_duration`, then a single request probes whether it recovered.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit, `0` disables the circuit breaker \[default: 5\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &5u32))]
+    pub failure_threshold: Option<u32>,
+    /// How long requests fail fast before the upstream is probed again. Defaults to "30s".
+    #[serde(default, with = "humantime_serde")]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Option<String>", example = &"30s")
+    )]
+    pub open_duration: Option<Duration>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl CircuitBreakerConfig {
+    fn policy(&self) -> Option<CircuitBreakerPolicy> {
+        let default = CircuitBreakerPolicy::default();
+        let failure_threshold = self.failure_threshold.unwrap_or(default.failure_threshold);
+        (failure_threshold > 0).then(|| CircuitBreakerPolicy {
+            failure_threshold,
+            open_duration: self.open_duration.unwrap_or(default.open_duration),
+        })
+    }
+}
+
+/// Last good tiles of a source, kept in memory to be served when its upstreams fail.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct ServeStaleConfig {
+    /// Memory used for the kept tiles \[default: 64\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &64u64))]
+    pub max_size_mb: Option<NonZeroU64>,
+    /// Tiles older than this are not served anymore. Defaults to "1d".
+    #[serde(default, with = "humantime_serde")]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "Option<String>", example = &"1d")
+    )]
+    pub max_age: Option<Duration>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl ServeStaleConfig {
+    fn policy(&self) -> StalePolicy {
+        let size_mb = self
+            .max_size_mb
+            .map_or(DEFAULT_STALE_MAX_SIZE_MB, NonZeroU64::get);
+        StalePolicy {
+            max_size_bytes: size_mb * 1000 * 1000,
+            max_age: self.max_age.unwrap_or(DEFAULT_STALE_MAX_AGE),
+        }
+    }
+}
+
+/// A WMS upstream.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct WmsConfig {
+    /// Layers to render, in drawing order.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &["parcels", "buildings"]))]
+    pub layers: OptOneMany<String>,
+    /// Styles of the layers, the default styles of the service if unset.
+    #[serde(default, skip_serializing_if = "OptOneMany::is_none")]
+    pub styles: OptOneMany<String>,
+    /// WMS version of the requests \[default: 1.3.0\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &"1.3.0"))]
+    pub version: Option<String>,
+    /// Request images with a transparent background \[default: false\]
+    pub transparent: Option<bool>,
+    /// Width and height of the tiles in pixels \[default: 256\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &256u32))]
+    pub tile_size: Option<u32>,
+    /// Request blocks of `metatile` × `metatile` tiles as one image and cut it into tiles,
+    /// so fewer requests reach the service and labels are not cut at tile edges \[default: 1\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &4u32))]
+    pub metatile: Option<u32>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl WmsConfig {
+    fn params(&self, format: Option<Format>) -> MapServiceParams {
+        MapServiceParams {
+            service: MapService::Wms {
+                layers: self.layers.iter().cloned().collect(),
+                styles: self.styles.iter().cloned().collect(),
+                version: self
+                    .version
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_WMS_VERSION.to_owned()),
+            },
+            format: format.unwrap_or(Format::Png),
+            transparent: self.transparent.unwrap_or_default(),
+            tile_size: self.tile_size.unwrap_or(DEFAULT_MAP_TILE_SIZE),
+            metatile: self.metatile.unwrap_or(1),
+        }
+    }
+}
+
+/// An `ArcGIS` REST `MapServer` upstream.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct ArcGisConfig {
+    /// The `layers` parameter of the `export` requests, e.g. `show:0,2`.
+    /// All visible layers if unset.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &"show:0,2"))]
+    pub layers: Option<String>,
+    /// Request images with a transparent background \[default: false\]
+    pub transparent: Option<bool>,
+    /// Width and height of the tiles in pixels \[default: 256\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &256u32))]
+    pub tile_size: Option<u32>,
+    /// Request blocks of `metatile` × `metatile` tiles as one image and cut it into tiles,
+    /// so fewer requests reach the service and labels are not cut at tile edges \[default: 1\]
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &4u32))]
+    pub metatile: Option<u32>,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl ArcGisConfig {
+    fn params(&self, format: Option<Format>) -> MapServiceParams {
+        MapServiceParams {
+            service: MapService::ArcGis {
+                layers: self.layers.clone(),
+            },
+            format: format.unwrap_or(Format::Png),
+            transparent: self.transparent.unwrap_or_default(),
+            tile_size: self.tile_size.unwrap_or(DEFAULT_MAP_TILE_SIZE),
+            metatile: self.metatile.unwrap_or(1),
+        }
+    }
+}
+
 impl Default for PassthroughSourceConfig {
     fn default() -> Self {
         Self {
             url: OptOneMany::default(),
+            wms: None,
+            arcgis: None,
             headers: BTreeMap::default(),
             timeout: DEFAULT_TIMEOUT,
             format: None,
@@ -280,6 +539,11 @@ impl Default for PassthroughSourceConfig {
             convert_to_mlt: None,
             #[cfg(all(feature = "mlt", feature = "_tiles"))]
             convert_to_mvt: None,
+            cache_control: None,
+            honor_cache_headers: None,
+            retry: None,
+            circuit_breaker: None,
+            serve_stale: None,
             unrecognized: UnrecognizedValues::default(),
         }
     }
@@ -305,15 +569,45 @@ impl PassthroughSourceConfig {
             attribution: self.attribution.clone(),
         };
         let urls = self.url.as_slice().to_vec();
-        let upstream = Upstream::from_config(&id, &urls, format, meta)?;
-        let transport = Transport::from_string_headers(
+        let upstream = match (&self.wms, &self.arcgis) {
+            (Some(_), Some(_)) => {
+                return Err(ConfigFileError::ConflictingPassthroughServices(id).into());
+            }
+            (Some(wms), None) => {
+                Upstream::MapService(MapServiceSet::new(urls, wms.params(format), meta)?)
+            }
+            (None, Some(arcgis)) => {
+                Upstream::MapService(MapServiceSet::new(urls, arcgis.params(format), meta)?)
+            }
+            (None, None) => Upstream::from_config(&id, &urls, format, meta)?,
+        };
+        let mut transport = Transport::from_string_headers(
             self.timeout,
             self.headers.iter().map(|(k, v)| (k.as_str(), v.as_str())),
         )?;
+        transport.honor_cache_headers = self.honor_cache_headers.unwrap_or(true);
         let cache = self.cache.or(default_cache);
-        let source = PassthroughSource::new(id, upstream, transport, cache.zoom()).await?;
+        let source =
+            PassthroughSource::new(id, upstream, transport, self.resilience(), cache.zoom())
+                .await?;
         Ok(Box::new(source))
     }
+
+    /// Resolve the retry, circuit breaker, and stale serving settings, applying the defaults.
+    fn resilience(&self) -> Resilience {
+        let default = Resilience::default();
+        Resilience {
+            retry: self
+                .retry
+                .as_ref()
+                .map_or(default.retry, RetryConfig::policy),
+            circuit_breaker: self
+                .circuit_breaker
+                .as_ref()
+                .map_or(default.circuit_breaker, CircuitBreakerConfig::policy),
+            serve_stale: self.serve_stale.as_ref().map(ServeStaleConfig::policy),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -399,6 +693,104 @@ mod tests {
         assert_eq!(obj.timeout, Duration::from_secs(30));
     }
 
+    #[test]
+    fn resilience_settings() {
+        let cfg = parse(indoc! {"
+            sources:
+              defaults: https://e.example.com/{z}/{x}/{y}.pbf
+              tuned:
+                url: https://e.example.com/{z}/{x}/{y}.pbf
+                retry:
+                  max_retries: 0
+                circuit_breaker:
+                  open_duration: 1m
+                serve_stale:
+                  max_size_mb: 10
+              no_breaker:
+                url: https://e.example.com/{z}/{x}/{y}.pbf
+                circuit_breaker:
+                  failure_threshold: 0
+        "});
+        let sources = cfg.sources.as_ref().unwrap();
+
+        let defaults = sources["defaults"].to_config().resilience();
+        assert_eq!(defaults, Resilience::default());
+        assert!(defaults.serve_stale.is_none());
+
+        let tuned = sources["tuned"].to_config().resilience();
+        assert_eq!(tuned.retry.max_retries, 0);
+        assert_eq!(tuned.retry.min_delay, RetryPolicy::default().min_delay);
+        let breaker = tuned.circuit_breaker.unwrap();
+        assert_eq!(breaker.open_duration, Duration::from_mins(1));
+        assert_eq!(
+            breaker.failure_threshold,
+            CircuitBreakerPolicy::default().failure_threshold
+        );
+        assert_eq!(
+            tuned.serve_stale,
+            Some(StalePolicy {
+                max_size_bytes: 10_000_000,
+                max_age: DEFAULT_STALE_MAX_AGE,
+            })
+        );
+
+        let no_breaker = sources["no_breaker"].to_config().resilience();
+        assert!(no_breaker.circuit_breaker.is_none());
+    }
+
+    #[test]
+    fn map_service_settings() {
+        let cfg = parse(indoc! {"
+            sources:
+              parcels:
+                url: https://maps.example.org/wms
+                format: jpeg
+                wms:
+                  layers: [parcels, buildings]
+                  version: 1.1.1
+                  metatile: 4
+              hydro:
+                url: https://maps.example.org/arcgis/rest/services/Hydro/MapServer
+                arcgis:
+                  layers: show:0,2
+                  transparent: true
+                  tile_size: 512
+        "});
+        let sources = cfg.sources.as_ref().unwrap();
+
+        let parcels = sources["parcels"].to_config();
+        let wms = parcels.wms.as_ref().unwrap();
+        assert_eq!(
+            wms.params(Some(Format::Jpeg)),
+            MapServiceParams {
+                service: MapService::Wms {
+                    layers: vec!["parcels".to_owned(), "buildings".to_owned()],
+                    styles: Vec::new(),
+                    version: "1.1.1".to_owned(),
+                },
+                format: Format::Jpeg,
+                transparent: false,
+                tile_size: 256,
+                metatile: 4,
+            }
+        );
+
+        let hydro = sources["hydro"].to_config();
+        assert!(hydro.wms.is_none());
+        assert_eq!(
+            hydro.arcgis.as_ref().unwrap().params(None),
+            MapServiceParams {
+                service: MapService::ArcGis {
+                    layers: Some("show:0,2".to_owned()),
+                },
+                format: Format::Png,
+                transparent: true,
+                tile_size: 512,
+                metatile: 1,
+            }
+        );
+    }
+
     #[test]
     fn unrecognized_per_source_key_is_reported() {
         let cfg = parse(indoc! {"
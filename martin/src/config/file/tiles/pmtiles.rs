@@ -19,6 +19,7 @@ use tracing::{trace, warn};
 use url::Url;
 
 use crate::MartinResult;
+use crate::config::file::srv::CacheControlPolicy;
 use crate::config::file::{
     CachePolicy, CacheSizeConfig, CollectUnrecognizedKeys, ConfigFileError, ConfigFileResult,
     ConfigurationLivecycleHooks, TileSourceConfiguration, UnrecognizedValues,
@@ -103,6 +104,16 @@ pub struct PmtConfig {
     #[serde(default)]
     pub convert_to_mvt: Option<MvtProcessConfig>,
 
+    /// `Cache-Control` header of the tiles of all `PMTiles` sources.
+    /// Overrides global; overridden by per-source `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
+    /// Serve tiles of all `PMTiles` sources up to this zoom level, beyond their `maxzoom`,
+    /// by cutting them out of the tiles at their `maxzoom`. Overridden by per-source `overzoom`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overzoom: Option<u8>,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
@@ -133,6 +144,8 @@ impl Default for PmtConfig {
             convert_to_mlt: None,
             #[cfg(all(feature = "mlt", feature = "_tiles"))]
             convert_to_mvt: None,
+            cache_control: None,
+            overzoom: None,
             unrecognized: UnrecognizedValues::default(),
             pmtiles_directory_cache: PmtCache::default(),
             aws_credentials: None,
@@ -148,6 +161,8 @@ impl PartialEq for PmtConfig {
             && self.reload_interval == other.reload_interval
             && self.profile == other.profile
             && self.options == other.options
+            && self.cache_control == other.cache_control
+            && self.overzoom == other.overzoom
             && self.unrecognized == other.unrecognized;
         #[cfg(all(feature = "mlt", feature = "_tiles"))]
         let base = base
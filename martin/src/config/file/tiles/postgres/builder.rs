@@ -1,12 +1,15 @@
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::num::NonZeroU32;
+use std::sync::Arc;
 
 use itertools::Itertools as _;
 use martin_core::tiles::BoxedSource;
 use martin_core::tiles::postgres::{PostgresPool, PostgresResult, PostgresSource, PostgresSqlInfo};
+use martin_tile_utils::tms::TileMatrixSet;
 use tracing::{error, info, trace, warn};
 
+use crate::MartinResult;
 use crate::config::args::BoundsCalcType;
 use crate::config::file::postgres::resolver::{
     query_available_function, query_available_tables, query_schemas, table_to_query,
@@ -18,6 +21,7 @@ use crate::config::file::postgres::{
     DEFAULT_POOL_SIZE, FuncInfoSources, FunctionInfo, PostgresCfgPublish, PostgresCfgPublishFuncs,
     PostgresConfig, PostgresInfo, SourceSpec, TableInfo, TableInfoSources,
 };
+use crate::config::file::tile_matrix_sets::TileMatrixSets;
 use crate::config::file::{CachePolicy, ConfigFileError, ConfigFileResult, TileSourceWarning};
 use crate::config::primitives::IdResolver;
 use crate::config::primitives::OptBoolObj::{Bool, NoValue, Object};
@@ -47,6 +51,8 @@ pub struct PostgresAutoDiscoveryBuilder {
     /// Associative arrays of table sources
     tables: TableInfoSources,
     functions: FuncInfoSources,
+    /// Tile matrix sets the sources can select by ID
+    tile_matrix_sets: TileMatrixSets,
 }
 
 /// Configuration for auto-discovering `PostgreSQL` functions.
@@ -125,6 +131,7 @@ impl PostgresAutoDiscoveryBuilder {
             functions: config.functions.clone().unwrap_or_default(),
             auto_functions,
             auto_tables,
+            tile_matrix_sets: config.tile_matrix_sets.clone(),
         })
     }
 
@@ -312,26 +319,29 @@ impl PostgresAutoDiscoveryBuilder {
         &self,
         id: &str,
         spec: SourceSpec,
-    ) -> PostgresResult<(BoxedSource, SourceSpec)> {
+    ) -> MartinResult<(BoxedSource, SourceSpec)> {
         match spec {
             SourceSpec::Table(info) => {
+                let tms = self.tile_matrix_sets.get(info.tile_matrix_set.as_deref())?;
                 let (id, pg_sql, info) = table_to_query(
                     id.to_owned(),
                     info,
                     self.pool.clone(),
                     self.auto_bounds,
                     self.max_feature_count,
+                    &tms,
                 )
                 .await?;
                 trace!(source.id = %id, sql = %pg_sql.sql_query, "source SQL query");
                 let cache = info.cache.unwrap_or_default();
-                let source = self.build_source(id, &info, pg_sql, cache);
+                let source = self.build_source(id, &info, pg_sql, cache, tms);
                 Ok((source, SourceSpec::Table(info)))
             }
             SourceSpec::Function(info, pg_sql) => {
+                let tms = self.tile_matrix_sets.get(info.tile_matrix_set.as_deref())?;
                 trace!(source.id = %id, sql = %pg_sql.sql_query, "source SQL query");
                 let cache = info.cache.unwrap_or_default();
-                let source = self.build_source(id.to_owned(), &info, pg_sql.clone(), cache);
+                let source = self.build_source(id.to_owned(), &info, pg_sql.clone(), cache, tms);
                 Ok((source, SourceSpec::Function(info, pg_sql)))
             }
         }
@@ -420,6 +430,7 @@ impl PostgresAutoDiscoveryBuilder {
         pg_info: &impl PostgresInfo,
         sql_info: PostgresSqlInfo,
         cache: CachePolicy,
+        tms: Arc<TileMatrixSet>,
     ) -> BoxedSource {
         let tilejson = pg_info.to_tilejson(id.clone());
         let tile_info = pg_info.tile_info();
@@ -431,6 +442,7 @@ impl PostgresAutoDiscoveryBuilder {
             self.pool.clone(),
             tile_info,
             cache.zoom(),
+            tms,
         ))
     }
 
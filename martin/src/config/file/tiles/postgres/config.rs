@@ -13,6 +13,8 @@ use tracing::{info, warn};
 use super::{FuncInfoSources, TableInfoSources};
 use crate::config::args::{BoundsCalcType, DEFAULT_BOUNDS_TIMEOUT};
 use crate::config::file::postgres::{PostgresAutoDiscoveryBuilder, SourceSpec};
+use crate::config::file::srv::CacheControlPolicy;
+use crate::config::file::tile_matrix_sets::TileMatrixSets;
 use crate::config::file::{
     CachePolicy, CollectUnrecognizedKeys, ConfigFileError, ConfigFileResult,
     ConfigurationLivecycleHooks, ResolutionResult, TileSourceWarning, UnrecognizedValues,
@@ -151,9 +153,19 @@ pub struct PostgresConfig {
     #[serde(default)]
     pub convert_to_mvt: Option<MvtProcessConfig>,
 
+    /// `Cache-Control` header of the tiles of all sources from this connection.
+    /// Overrides global; overridden by per-source `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
+
+    /// All known tile matrix sets (internal state, not serialized)
+    #[serde(skip)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub tile_matrix_sets: TileMatrixSets,
 }
 
 /// Default connection pool size.
@@ -179,7 +191,9 @@ impl Default for PostgresConfig {
             convert_to_mlt: None,
             #[cfg(all(feature = "mlt", feature = "_tiles"))]
             convert_to_mvt: None,
+            cache_control: None,
             unrecognized: UnrecognizedValues::default(),
+            tile_matrix_sets: TileMatrixSets::default(),
         }
     }
 }
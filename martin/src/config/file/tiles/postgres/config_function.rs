@@ -9,6 +9,7 @@ use super::config::PostgresInfo;
 #[cfg(feature = "unstable-schemas")]
 use crate::config::file::postgres::config_table::bounds_world_example;
 use crate::config::file::postgres::utils::patch_json;
+use crate::config::file::srv::CacheControlPolicy;
 use crate::config::file::{CachePolicy, CollectUnrecognizedKeys, UnrecognizedValues};
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 use crate::config::file::{MltProcessConfig, MvtProcessConfig};
@@ -50,6 +51,12 @@ pub struct FunctionInfo {
     )]
     pub cache: Option<CachePolicy>,
 
+    /// ID of the tile matrix set to generate tiles in, defaulting to `WebMercatorQuad`.
+    /// Must be one of the built-in tile matrix sets or configured in `tile_matrix_sets`.
+    /// The function must return tiles of that tile matrix set for the given `z`, `x` and `y`.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &"WorldCRS84Quad"))]
+    pub tile_matrix_set: Option<String>,
+
     /// `TileJSON` provided by the SQL function comment. Not serialized.
     #[serde(skip)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
@@ -79,6 +86,11 @@ pub struct FunctionInfo {
     #[serde(default)]
     pub convert_to_mvt: Option<MvtProcessConfig>,
 
+    /// `Cache-Control` header of the tiles of this source.
+    /// Overrides source-type and global `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
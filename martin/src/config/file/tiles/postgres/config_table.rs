@@ -8,6 +8,7 @@ use tracing::{info, warn};
 
 use super::PostgresInfo;
 use crate::config::file::postgres::utils::{normalize_key, patch_json};
+use crate::config::file::srv::CacheControlPolicy;
 use crate::config::file::{CachePolicy, CollectUnrecognizedKeys, UnrecognizedValues};
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 use crate::config::file::{MltProcessConfig, MvtProcessConfig};
@@ -104,6 +105,11 @@ pub struct TableInfo {
     )]
     pub cache: Option<CachePolicy>,
 
+    /// ID of the tile matrix set to generate tiles in, defaulting to `WebMercatorQuad`.
+    /// Must be one of the built-in tile matrix sets or configured in `tile_matrix_sets`.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &"WorldCRS84Quad"))]
+    pub tile_matrix_set: Option<String>,
+
     /// List of columns, that should be encoded as tile properties (required)
     ///
     /// Keys and values are the names and descriptions of attributes available in this layer.
@@ -134,6 +140,11 @@ pub struct TableInfo {
     #[serde(default)]
     pub convert_to_mvt: Option<MvtProcessConfig>,
 
+    /// `Cache-Control` header of the tiles of this source.
+    /// Overrides source-type and global `cache_control`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControlPolicy>,
+
     #[serde(flatten, skip_serializing)]
     #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
     pub unrecognized: UnrecognizedValues,
@@ -1,12 +1,14 @@
 //! `PostgreSQL` table discovery and validation.
 
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
 use std::num::NonZeroU32;
 
 use futures::pin_mut;
 use martin_core::tiles::postgres::PostgresError::PostgresError;
 use martin_core::tiles::postgres::{PostgresPool, PostgresResult, PostgresSqlInfo};
-use martin_tile_utils::EARTH_CIRCUMFERENCE_DEGREES;
+use martin_tile_utils::tms::{CornerOfOrigin, TileMatrixSet, WEB_MERCATOR_QUAD};
+use martin_tile_utils::{EARTH_CIRCUMFERENCE_DEGREES, MAX_ZOOM, TileCoord};
 use postgis::ewkb;
 use postgres_protocol::escape::{escape_identifier, escape_literal};
 use serde_json::Value;
@@ -14,7 +16,9 @@ use tilejson::Bounds;
 use tokio::time::timeout;
 use tracing::{debug, warn};
 
+use crate::MartinResult;
 use crate::config::args::{BoundsCalcType, DEFAULT_BOUNDS_TIMEOUT};
+use crate::config::file::ConfigFileError;
 use crate::config::file::postgres::{PostgresInfo as _, TableInfo};
 
 /// Map of `PostgreSQL` tables organized by schema, table, and geometry column.
@@ -134,8 +138,18 @@ pub async fn table_to_query(
     pool: PostgresPool,
     bounds_type: BoundsCalcType,
     max_feature_count: Option<usize>,
-) -> PostgresResult<(String, PostgresSqlInfo, TableInfo)> {
+    tms: &TileMatrixSet,
+) -> MartinResult<(String, PostgresSqlInfo, TableInfo)> {
     let srid = info.srid;
+    // WebMercatorQuad tiles are generated with ST_TileEnvelope, other tile matrix sets need their CRS
+    let tms_srid = if tms.id == WEB_MERCATOR_QUAD {
+        None
+    } else {
+        Some(
+            tms.srid()
+                .ok_or_else(|| ConfigFileError::TileMatrixSetWithoutSrid(tms.id.clone()))?,
+        )
+    };
 
     if info.bounds.is_none() {
         match bounds_type {
@@ -197,6 +211,68 @@ pub async fn table_to_query(
     let buffer = info.buffer.unwrap_or(DEFAULT_BUFFER);
     let margin = f64::from(buffer) / f64::from(extent);
 
+    let limit_clause = max_feature_count.map_or(String::new(), |v| format!("LIMIT {v}"));
+    let layer_id = escape_literal(info.layer_id.as_ref().unwrap_or(&id));
+    let clip_geom = info.clip_geom.unwrap_or(DEFAULT_CLIP_GEOM);
+    let schema = escape_identifier(&info.schema);
+    let table = escape_identifier(&info.table);
+    let geometry_column = escape_identifier(&info.geometry_column);
+
+    if let Some(tms_srid) = tms_srid {
+        let tile_matrix = tile_matrix_sql(tms);
+        let bbox_search = if buffer == 0 {
+            format!("ST_Transform((SELECT envelope FROM tile_envelope), {srid})")
+        } else {
+            format!(
+                "ST_Transform((SELECT ST_Expand(envelope, {margin} * span_x, {margin} * span_y) FROM tile_envelope), {srid})"
+            )
+        };
+        let query = format!(
+            r"
+WITH tile_matrix AS (
+  SELECT
+    {tile_matrix}
+), tile_envelope AS (
+  SELECT
+    ST_MakeEnvelope(
+        origin_x + $2::integer * span_x,
+        origin_y + $3::integer * step_y,
+        origin_x + ($2::integer + 1) * span_x,
+        origin_y + $3::integer * step_y + span_y,
+        {tms_srid}
+    ) AS envelope,
+    span_x,
+    span_y
+  FROM tile_matrix
+)
+SELECT
+  ST_AsMVT(tile, {layer_id}, {extent}, 'geom'{id_name})
+FROM (
+  SELECT
+    ST_AsMVTGeom(
+        ST_Transform(ST_CurveToLine({geometry_column}::geometry), {tms_srid}),
+        (SELECT envelope FROM tile_envelope),
+        {extent}, {buffer}, {clip_geom}
+    ) AS geom
+    {id_field}{properties}
+  FROM
+    {schema}.{table}
+  WHERE
+    {geometry_column} && {bbox_search}
+  {limit_clause}
+) AS tile;
+"
+        )
+        .trim()
+        .to_owned();
+
+        return Ok((
+            id,
+            PostgresSqlInfo::new(query, false, info.format_id()),
+            info,
+        ));
+    }
+
     // When calculating the bounding box to search within, a few considerations must be made when
     // using a margin. The ST_TileEnvelope margin parameter is for use with SRID 3857.
     // For SRID 4326, ST_Expand is used and provided with SRID 4326 specific units (degrees).
@@ -223,12 +299,6 @@ pub async fn table_to_query(
         format!("ST_Transform(ST_TileEnvelope($1::integer, $2::integer, $3::integer), {srid})")
     };
 
-    let limit_clause = max_feature_count.map_or(String::new(), |v| format!("LIMIT {v}"));
-    let layer_id = escape_literal(info.layer_id.as_ref().unwrap_or(&id));
-    let clip_geom = info.clip_geom.unwrap_or(DEFAULT_CLIP_GEOM);
-    let schema = escape_identifier(&info.schema);
-    let table = escape_identifier(&info.table);
-    let geometry_column = escape_identifier(&info.geometry_column);
     let query = format!(
         r"
 SELECT
@@ -259,6 +329,57 @@ FROM (
     ))
 }
 
+/// SQL select list with the geometry of tile matrix `$1` of a tile matrix set.
+///
+/// The values of every tile matrix are inlined as arrays indexed by the zoom level:
+/// the bottom left corner of tile `0/0` (`origin_x`, `origin_y`), the tile size in CRS units
+/// (`span_x`, `span_y`), and the signed distance between two tile rows (`step_y`),
+/// so that the bottom of row `y` is always at `origin_y + y * step_y`.
+fn tile_matrix_sql(tms: &TileMatrixSet) -> String {
+    let mut origin_x = Vec::new();
+    let mut origin_y = Vec::new();
+    let mut span_x = Vec::new();
+    let mut span_y = Vec::new();
+    let mut step_y = Vec::new();
+    for (zoom, matrix) in (0..=MAX_ZOOM).zip(&tms.tile_matrices) {
+        let Some([min_x, min_y, max_x, max_y]) = tms.tile_bounds(TileCoord {
+            z: zoom,
+            x: 0,
+            y: 0,
+        }) else {
+            continue;
+        };
+        origin_x.push(min_x);
+        origin_y.push(min_y);
+        span_x.push(max_x - min_x);
+        span_y.push(max_y - min_y);
+        step_y.push(match matrix.corner_of_origin {
+            CornerOfOrigin::TopLeft => min_y - max_y,
+            CornerOfOrigin::BottomLeft => max_y - min_y,
+        });
+    }
+
+    let mut sql = String::new();
+    for (name, values) in [
+        ("origin_x", origin_x),
+        ("origin_y", origin_y),
+        ("span_x", span_x),
+        ("span_y", span_y),
+        ("step_y", step_y),
+    ] {
+        if !sql.is_empty() {
+            sql.push_str(",\n    ");
+        }
+        let values = values.iter().map(f64::to_string).collect::<Vec<_>>();
+        let _ = write!(
+            sql,
+            "(ARRAY[{}]::float8[])[$1::integer + 1] AS {name}",
+            values.join(",")
+        );
+    }
+    sql
+}
+
 /// How [`calc_bounds`] should compute a table's geometry bounds.
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum BoundsCalcMode {
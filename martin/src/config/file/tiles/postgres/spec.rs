@@ -39,6 +39,7 @@ impl SourceSpec {
                 info.clip_geom.hash(&mut hasher);
                 info.geometry_type.hash(&mut hasher);
                 info.properties.hash(&mut hasher);
+                info.tile_matrix_set.hash(&mut hasher);
                 hash_tilejson(info.tilejson.as_ref(), &mut hasher);
 
                 let mut prop_mapping: Vec<_> = info.prop_mapping.iter().collect();
@@ -51,6 +52,7 @@ impl SourceSpec {
                 info.function.hash(&mut hasher);
                 info.minzoom.hash(&mut hasher);
                 info.maxzoom.hash(&mut hasher);
+                info.tile_matrix_set.hash(&mut hasher);
                 hash_tilejson(info.tilejson.as_ref(), &mut hasher);
                 sql.sql_query.hash(&mut hasher);
                 sql.signature.hash(&mut hasher);
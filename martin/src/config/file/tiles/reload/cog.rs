@@ -4,6 +4,7 @@ use martin_core::tiles::cog::CogSource;
 use crate::config::file::FileConfigEnum;
 use crate::config::file::cog::CogConfig;
 use crate::config::file::process::ProcessConfig;
+use crate::config::file::resolve_process_config;
 use crate::config::file::tiles::discovery::{FsDiscovery, FsSourceBuilder};
 use crate::config::file::tiles::driver::{Baseline, NotifyTrigger, ReloadDriver};
 use crate::config::primitives::IdResolver;
@@ -16,12 +17,24 @@ pub struct CogReloader {
 }
 
 impl CogReloader {
+    /// Resolves the process config (source-type > global > default) for discovered sources.
     #[must_use]
     pub fn new(
         tsm: TileSourceManager,
         id_resolver: IdResolver,
         config: &FileConfigEnum<CogConfig>,
+        global_process: &ProcessConfig,
     ) -> Self {
+        let source_type = match config {
+            FileConfigEnum::Config(cfg) => ProcessConfig {
+                cache_control: cfg.custom.cache_control.clone(),
+                ..ProcessConfig::default()
+            },
+            _ => ProcessConfig::default(),
+        };
+        let process =
+            resolve_process_config(global_process, &source_type, &ProcessConfig::default());
+
         // See `MbtilesReloader::new`: both boxes erase per-kind types to a shared shape.
         // This builder captures nothing, but is `Box::new`d to share the boxed `FsSourceBuilder` type.
         let build: FsSourceBuilder = Box::new(|id, path, policy| {
@@ -30,13 +43,8 @@ impl CogReloader {
                 Ok(Box::new(src) as BoxedSource)
             })
         });
-        let discovery = FsDiscovery::from_config(
-            config,
-            &["tif", "tiff"],
-            id_resolver,
-            ProcessConfig::default(),
-            build,
-        );
+        let discovery =
+            FsDiscovery::from_config(config, &["tif", "tiff"], id_resolver, process, build);
         Self {
             tile_source_manager: tsm,
             discovery,
@@ -1,5 +1,6 @@
 use crate::config::file::geojson::GeoJsonConfig;
 use crate::config::file::process::ProcessConfig;
+use crate::config::file::resolve_process_config;
 use crate::config::file::tiles::discovery::{FsDiscovery, FsSourceBuilder};
 use crate::config::file::tiles::driver::{Baseline, NotifyTrigger, ReloadDriver};
 use crate::config::file::{FileConfigEnum, TileSourceConfiguration as _};
@@ -13,12 +14,24 @@ pub struct GeoJsonReloader {
 }
 
 impl GeoJsonReloader {
+    /// Resolves the process config (source-type > global > default) for discovered sources.
     #[must_use]
     pub fn new(
         tsm: TileSourceManager,
         id_resolver: IdResolver,
         config: &FileConfigEnum<GeoJsonConfig>,
+        global_process: &ProcessConfig,
     ) -> Self {
+        let source_type = match config {
+            FileConfigEnum::Config(cfg) => ProcessConfig {
+                cache_control: cfg.custom.cache_control.clone(),
+                ..ProcessConfig::default()
+            },
+            _ => ProcessConfig::default(),
+        };
+        let process =
+            resolve_process_config(global_process, &source_type, &ProcessConfig::default());
+
         // Discovered files inherit the configured extent and buffer, so the builder closes over the
         // custom config and delegates to its `new_sources` (see `PmtilesReloader::new`).
         let geojson_config = match config {
@@ -29,13 +42,8 @@ impl GeoJsonReloader {
             let config = geojson_config.clone();
             Box::pin(async move { config.new_sources(id, path, policy).await })
         });
-        let discovery = FsDiscovery::from_config(
-            config,
-            &["json", "geojson"],
-            id_resolver,
-            ProcessConfig::default(),
-            build,
-        );
+        let discovery =
+            FsDiscovery::from_config(config, &["json", "geojson"], id_resolver, process, build);
         Self {
             tile_source_manager: tsm,
             discovery,
@@ -4,7 +4,6 @@ use martin_core::tiles::mbtiles::MbtSource;
 use crate::config::file::FileConfigEnum;
 use crate::config::file::mbtiles::MbtConfig;
 use crate::config::file::process::ProcessConfig;
-#[cfg(all(feature = "mlt", feature = "_tiles"))]
 use crate::config::file::resolve_process_config;
 use crate::config::file::tiles::discovery::{FsDiscovery, FsSourceBuilder};
 use crate::config::file::tiles::driver::{Baseline, NotifyTrigger, ReloadDriver};
@@ -26,22 +25,19 @@ impl MbtilesReloader {
         config: &FileConfigEnum<MbtConfig>,
         global_process: &ProcessConfig,
     ) -> Self {
-        #[cfg(all(feature = "mlt", feature = "_tiles"))]
-        let process = {
-            let source_type = match config {
-                FileConfigEnum::Config(cfg) => ProcessConfig {
-                    convert_to_mlt: cfg.custom.convert_to_mlt.clone(),
-                    convert_to_mvt: cfg.custom.convert_to_mvt.clone(),
-                },
-                _ => ProcessConfig::default(),
-            };
-            resolve_process_config(global_process, &source_type, &ProcessConfig::default())
-        };
-        #[cfg(not(feature = "mlt"))]
-        let process = {
-            let _ = (config, global_process);
-            ProcessConfig::default()
+        let source_type = match config {
+            FileConfigEnum::Config(cfg) => ProcessConfig {
+                #[cfg(feature = "mlt")]
+                convert_to_mlt: cfg.custom.convert_to_mlt.clone(),
+                #[cfg(feature = "mlt")]
+                convert_to_mvt: cfg.custom.convert_to_mvt.clone(),
+                cache_control: cfg.custom.cache_control.clone(),
+                overzoom: cfg.custom.overzoom,
+            },
+            _ => ProcessConfig::default(),
         };
+        let process =
+            resolve_process_config(global_process, &source_type, &ProcessConfig::default());
 
         // One `FsDiscovery` serves every file kind, so the two boxes erase per-kind types.
         // `Box::pin(async {..})` erases the future to `BoxFuture`.
@@ -1,6 +1,5 @@
 use crate::config::file::pmtiles::PmtConfig;
 use crate::config::file::process::ProcessConfig;
-#[cfg(all(feature = "mlt", feature = "_tiles"))]
 use crate::config::file::resolve_process_config;
 use crate::config::file::tiles::discovery::{FsDiscovery, FsSourceBuilder, ObjectStoreDiscovery};
 use crate::config::file::tiles::driver::{Baseline, NotifyTrigger, PollTrigger, ReloadDriver};
@@ -29,22 +28,19 @@ impl PmtilesReloader {
         config: &FileConfigEnum<PmtConfig>,
         global_process: &ProcessConfig,
     ) -> Self {
-        #[cfg(all(feature = "mlt", feature = "_tiles"))]
-        let process = {
-            let source_type = match config {
-                FileConfigEnum::Config(cfg) => ProcessConfig {
-                    convert_to_mlt: cfg.custom.convert_to_mlt.clone(),
-                    convert_to_mvt: cfg.custom.convert_to_mvt.clone(),
-                },
-                _ => ProcessConfig::default(),
-            };
-            resolve_process_config(global_process, &source_type, &ProcessConfig::default())
-        };
-        #[cfg(not(feature = "mlt"))]
-        let process = {
-            let _ = global_process;
-            ProcessConfig::default()
+        let source_type = match config {
+            FileConfigEnum::Config(cfg) => ProcessConfig {
+                #[cfg(feature = "mlt")]
+                convert_to_mlt: cfg.custom.convert_to_mlt.clone(),
+                #[cfg(feature = "mlt")]
+                convert_to_mvt: cfg.custom.convert_to_mvt.clone(),
+                cache_control: cfg.custom.cache_control.clone(),
+                overzoom: cfg.custom.overzoom,
+            },
+            _ => ProcessConfig::default(),
         };
+        let process =
+            resolve_process_config(global_process, &source_type, &ProcessConfig::default());
 
         let pmt_config = match config {
             FileConfigEnum::Config(cfg) => cfg.custom.clone(),
@@ -211,6 +207,8 @@ mod tests {
             FileConfigSrc::Obj(FileConfigSource {
                 path: PathBuf::from("s3://bucket/file.pmtiles"),
                 cache: CachePolicy::default(),
+                cache_control: None,
+                overzoom: None,
                 #[cfg(all(feature = "mlt", feature = "_tiles"))]
                 convert_to_mlt: None,
                 #[cfg(all(feature = "mlt", feature = "_tiles"))]
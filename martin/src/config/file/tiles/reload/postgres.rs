@@ -6,7 +6,6 @@ use crate::TileSourceManager;
 use crate::config::file::CachePolicy;
 use crate::config::file::postgres::PostgresConfig;
 use crate::config::file::process::ProcessConfig;
-#[cfg(all(feature = "mlt", feature = "_tiles"))]
 use crate::config::file::resolve_process_config;
 use crate::config::file::tiles::discovery::PostgresDiscovery;
 use crate::config::file::tiles::driver::{Baseline, PollTrigger, ReloadDriver};
@@ -33,19 +32,16 @@ impl PostgresReloader {
         default_cache: CachePolicy,
         global_process: &ProcessConfig,
     ) -> Self {
-        #[cfg(all(feature = "mlt", feature = "_tiles"))]
-        let process = {
-            let source_type = ProcessConfig {
-                convert_to_mlt: config.convert_to_mlt.clone(),
-                convert_to_mvt: config.convert_to_mvt.clone(),
-            };
-            resolve_process_config(global_process, &source_type, &ProcessConfig::default())
-        };
-        #[cfg(not(all(feature = "mlt", feature = "_tiles")))]
-        let process = {
-            let _ = global_process;
-            ProcessConfig::default()
+        let source_type = ProcessConfig {
+            #[cfg(feature = "mlt")]
+            convert_to_mlt: config.convert_to_mlt.clone(),
+            #[cfg(feature = "mlt")]
+            convert_to_mvt: config.convert_to_mvt.clone(),
+            cache_control: config.cache_control.clone(),
+            overzoom: None,
         };
+        let process =
+            resolve_process_config(global_process, &source_type, &ProcessConfig::default());
 
         let discovery = PostgresDiscovery::new(config, id_resolver, default_cache, process);
         Self {
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use martin_tile_utils::tms::{TileMatrixSet, WEB_MERCATOR_QUAD, WORLD_CRS84_QUAD};
+use tracing::info;
+
+use crate::config::file::{ConfigFileError, ConfigFileResult};
+
+/// All tile matrix sets sources can refer to by ID:
+/// the built-in `WebMercatorQuad` and `WorldCRS84Quad`, plus the ones configured in `tile_matrix_sets`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileMatrixSets(BTreeMap<String, Arc<TileMatrixSet>>);
+
+impl Default for TileMatrixSets {
+    fn default() -> Self {
+        Self(
+            [
+                TileMatrixSet::web_mercator_quad(),
+                TileMatrixSet::world_crs84_quad(),
+            ]
+            .into_iter()
+            .map(|tms| (tms.id.clone(), Arc::new(tms)))
+            .collect(),
+        )
+    }
+}
+
+impl TileMatrixSets {
+    /// Load the built-in tile matrix sets and the OGC TMS 2.0 JSON files configured as `id: path`.
+    ///
+    /// The configured ID replaces the `id` in the file, so that it matches the URLs.
+    pub fn load(files: &BTreeMap<String, PathBuf>) -> ConfigFileResult<Self> {
+        let mut sets = Self::default();
+        for (id, path) in files {
+            if id == WEB_MERCATOR_QUAD || id == WORLD_CRS84_QUAD {
+                return Err(ConfigFileError::BuiltinTileMatrixSet(id.clone()));
+            }
+            let json = std::fs::read_to_string(path)
+                .map_err(|e| ConfigFileError::IoError(e, path.clone()))?;
+            let mut tms = TileMatrixSet::from_json(&json)
+                .map_err(|e| ConfigFileError::InvalidTileMatrixSet(e, path.clone()))?;
+            info!(
+                "Loaded tile matrix set {id} with {} zoom levels from {}",
+                tms.tile_matrices.len(),
+                path.display()
+            );
+            tms.id.clone_from(id);
+            sets.0.insert(id.clone(), Arc::new(tms));
+        }
+        Ok(sets)
+    }
+
+    /// Get a tile matrix set by ID, defaulting to `WebMercatorQuad`
+    pub fn get(&self, id: Option<&str>) -> ConfigFileResult<Arc<TileMatrixSet>> {
+        let id = id.unwrap_or(WEB_MERCATOR_QUAD);
+        self.0
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ConfigFileError::UnknownTileMatrixSet(id.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::*;
+
+    #[test]
+    fn load_custom_tile_matrix_set() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut custom = TileMatrixSet::world_crs84_quad();
+        custom.id = "FromFile".to_owned();
+        custom.tile_matrices.truncate(3);
+        serde_json::to_writer(&mut file, &custom).unwrap();
+        file.flush().unwrap();
+
+        let files = BTreeMap::from([("Custom".to_owned(), file.path().to_path_buf())]);
+        let sets = TileMatrixSets::load(&files).unwrap();
+        let tms = sets.get(Some("Custom")).unwrap();
+        assert_eq!(tms.id, "Custom");
+        assert_eq!(tms.max_zoom(), 2);
+
+        assert_eq!(sets.get(None).unwrap().id, WEB_MERCATOR_QUAD);
+        assert_eq!(
+            sets.get(Some(WORLD_CRS84_QUAD)).unwrap().id,
+            WORLD_CRS84_QUAD
+        );
+        let err = sets.get(Some("Missing")).unwrap_err();
+        assert!(matches!(err, ConfigFileError::UnknownTileMatrixSet(_)));
+
+        let files = BTreeMap::from([(WEB_MERCATOR_QUAD.to_owned(), file.path().to_path_buf())]);
+        let err = TileMatrixSets::load(&files).unwrap_err();
+        assert!(matches!(err, ConfigFileError::BuiltinTileMatrixSet(_)));
+    }
+}
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::file::{CollectUnrecognizedKeys, UnrecognizedKeys, UnrecognizedValues};
+
+/// Default interval for checking the certificate and private key files for changes
+pub const DEFAULT_CERT_RELOAD_INTERVAL: Duration = Duration::from_mins(1);
+
+fn default_reload_interval() -> Duration {
+    DEFAULT_CERT_RELOAD_INTERVAL
+}
+
+fn is_default_reload_interval(v: &Duration) -> bool {
+    *v == DEFAULT_CERT_RELOAD_INTERVAL
+}
+
+/// Lowest TLS protocol version accepted from clients
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub enum TlsVersion {
+    /// TLS 1.2 and 1.3
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    /// TLS 1.3 only
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl CollectUnrecognizedKeys for TlsVersion {
+    fn collect_unrecognized(&self, _path: &str, _out: &mut UnrecognizedKeys) {}
+}
+
+/// Serve HTTPS with the given certificate.
+///
+/// HTTP/2 is negotiated with clients via ALPN, falling back to HTTP/1.1.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, CollectUnrecognizedKeys)]
+#[cfg_attr(feature = "unstable-schemas", derive(schemars::JsonSchema))]
+pub struct TlsConfig {
+    /// Path to the PEM file with the certificate chain, starting with the server certificate
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(example = &"/etc/letsencrypt/live/tiles.example.com/fullchain.pem")
+    )]
+    pub cert: PathBuf,
+    /// Path to the PEM file with the private key (PKCS#8, PKCS#1 or SEC1)
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(example = &"/etc/letsencrypt/live/tiles.example.com/privkey.pem")
+    )]
+    pub key: PathBuf,
+    /// The socket address to bind for HTTPS.
+    ///
+    /// If set, plain HTTP is still served on `listen_addresses`, and HTTPS on this address.
+    /// Otherwise `listen_addresses` only serves HTTPS.
+    #[cfg_attr(feature = "unstable-schemas", schemars(example = &"0.0.0.0:3443"))]
+    pub listen_addresses: Option<String>,
+    /// Lowest TLS version accepted from clients, `1.2` or `1.3` \[default: `1.2`\]
+    #[serde(default)]
+    pub min_version: TlsVersion,
+    /// How often the certificate and key files are checked for changes.
+    /// Renewed certificates are used for new connections without restarting Martin.
+    ///
+    /// Supports human-readable formats: "1m", "1h", "30s".
+    /// Defaults to "1m". Set to "0s" to disable reloading.
+    #[serde(
+        default = "default_reload_interval",
+        skip_serializing_if = "is_default_reload_interval",
+        with = "humantime_serde"
+    )]
+    #[cfg_attr(
+        feature = "unstable-schemas",
+        schemars(with = "String", example = &"1m")
+    )]
+    pub reload_interval: Duration,
+
+    #[serde(flatten, skip_serializing)]
+    #[cfg_attr(feature = "unstable-schemas", schemars(skip))]
+    pub unrecognized: UnrecognizedValues,
+}
+
+impl TlsConfig {
+    /// TLS settings with the default options for the given certificate and key files
+    #[must_use]
+    pub fn new(cert: PathBuf, key: PathBuf) -> Self {
+        Self {
+            cert,
+            key,
+            listen_addresses: None,
+            min_version: TlsVersion::default(),
+            reload_interval: DEFAULT_CERT_RELOAD_INTERVAL,
+            unrecognized: UnrecognizedValues::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn parse_tls_config() {
+        let cfg: TlsConfig = serde_saphyr::from_str(indoc! {"
+            cert: /certs/fullchain.pem
+            key: /certs/privkey.pem
+        "})
+        .unwrap();
+        assert_eq!(
+            cfg,
+            TlsConfig::new("/certs/fullchain.pem".into(), "/certs/privkey.pem".into())
+        );
+
+        let cfg: TlsConfig = serde_saphyr::from_str(indoc! {"
+            cert: /certs/fullchain.pem
+            key: /certs/privkey.pem
+            listen_addresses: 0.0.0.0:3443
+            min_version: '1.3'
+            reload_interval: 0s
+        "})
+        .unwrap();
+        assert_eq!(cfg.listen_addresses.as_deref(), Some("0.0.0.0:3443"));
+        assert_eq!(cfg.min_version, TlsVersion::Tls13);
+        assert_eq!(cfg.reload_interval, Duration::ZERO);
+
+        let yaml = serde_saphyr::to_string(&cfg).unwrap();
+        assert_eq!(serde_saphyr::from_str::<TlsConfig>(&yaml).unwrap(), cfg);
+    }
+}
@@ -15,6 +15,8 @@ use martin_core::tiles::pmtiles::PmtilesError;
 use martin_core::tiles::postgres::PostgresError;
 
 use crate::config::file::ConfigFileError;
+#[cfg(feature = "opentelemetry")]
+use crate::logging::otel::OtelError;
 
 /// A convenience [`Result`] for Martin crate.
 pub type MartinResult<T> = Result<T, MartinError>;
@@ -51,6 +53,12 @@ pub enum MartinError {
     #[error("Unable to bind to {1}: {0}")]
     BindingError(#[source] io::Error, String),
 
+    #[error(transparent)]
+    TlsError(#[from] crate::srv::TlsError),
+
+    #[error(transparent)]
+    ListenError(#[from] crate::srv::ListenError),
+
     #[error("Base path must be a valid URL path, and must begin with a '/' symbol, but is '{0}'")]
     BasePathError(String),
 
@@ -99,6 +107,10 @@ pub enum MartinError {
     #[error("could not initialize metrics: {0}")]
     MetricsIntialisationError(#[source] Box<dyn std::error::Error + Send + Sync>),
 
+    #[cfg(feature = "opentelemetry")]
+    #[error(transparent)]
+    OtelError(#[from] OtelError),
+
     #[error("warnings issued during tile source resolution")]
     TileResolutionWarningsIssued,
 
@@ -12,6 +12,8 @@ use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::layer::SubscriberExt as _;
 
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
 pub mod progress;
 
 /// Log output format options.
@@ -39,41 +41,33 @@ pub enum LogFormat {
 
 impl LogFormat {
     /// Initialize logging according to the selected format.
+    ///
+    /// With the `opentelemetry` feature, spans can also be exported, see [`otel::init`].
     pub fn init(self, env_filter: EnvFilter) {
-        let dispatch = match self {
-            Self::Full => tracing_subscriber::fmt()
-                .with_span_events(FmtSpan::NONE)
-                .with_env_filter(env_filter)
-                .finish()
-                .into(),
-            Self::Compact => tracing_subscriber::fmt()
+        use tracing_subscriber::Layer as _;
+        use tracing_subscriber::fmt::layer as fmt_layer;
+
+        let fmt_layer = match self {
+            Self::Full => fmt_layer().with_span_events(FmtSpan::NONE).boxed(),
+            Self::Compact => fmt_layer()
                 .compact()
                 .with_span_events(FmtSpan::NONE)
-                .with_env_filter(env_filter)
-                .finish()
-                .into(),
-            Self::Pretty => tracing_subscriber::fmt()
-                .pretty()
-                .with_env_filter(env_filter)
-                .finish()
-                .into(),
-            Self::Bare => tracing_subscriber::fmt()
+                .boxed(),
+            Self::Pretty => fmt_layer().pretty().boxed(),
+            Self::Bare => fmt_layer()
                 .compact()
                 .with_span_events(FmtSpan::NONE)
                 .without_time()
                 .with_target(false)
                 .with_ansi(false)
-                .with_env_filter(env_filter)
-                .finish()
-                .into(),
-            Self::Json => tracing_subscriber::fmt()
-                .json()
-                .with_span_events(FmtSpan::NONE)
-                .with_env_filter(env_filter)
-                .finish()
-                .into(),
+                .boxed(),
+            Self::Json => fmt_layer().json().with_span_events(FmtSpan::NONE).boxed(),
         };
-        tracing::dispatcher::set_global_default(dispatch)
+        // the filter only applies to the log output, exported spans are filtered separately
+        let registry = tracing_subscriber::registry().with(fmt_layer.with_filter(env_filter));
+        #[cfg(feature = "opentelemetry")]
+        let registry = registry.with(otel::layer());
+        tracing::dispatcher::set_global_default(registry.into())
             .expect("failed to set global default subscriber");
     }
     /// Initialize logging according to the selected format with a progress bar.
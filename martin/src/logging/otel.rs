@@ -0,0 +1,240 @@
+//! Export of `tracing` spans to an OpenTelemetry collector via OTLP.
+//!
+//! The tracing subscriber is installed before the configuration is read,
+//! so [`layer`] is always part of it, but only exports spans once [`init`] was called.
+//! Until then, its filter disables everything, and the layer costs nothing.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, OnceLock};
+use std::time::Duration;
+
+use opentelemetry::trace::{Link, SpanKind, TraceId, TraceState, TracerProvider as _};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig as _};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{
+    BatchSpanProcessor, Sampler, SamplingDecision, SamplingResult, SdkTracer, SdkTracerProvider,
+    ShouldSample, Span, SpanData, SpanProcessor,
+};
+use tracing::level_filters::LevelFilter;
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::{Context as LayerContext, Filter};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::file::srv::{OtlpProtocol, TracingConfig};
+
+/// Service name reported if neither the configuration nor `OTEL_SERVICE_NAME` set one
+const DEFAULT_SERVICE_NAME: &str = "martin";
+
+/// Crates whose spans and events are exported.
+/// Others, e.g. the HTTP clients used by the exporter itself, are not.
+const EXPORTED_TARGETS: &[&str] = &["martin", "martin_core", "mbtiles", "tracing_actix_web"];
+
+/// Errors setting up the export of spans
+#[derive(thiserror::Error, Debug)]
+pub enum OtelError {
+    #[error("Unable to create the OTLP span exporter: {0}")]
+    Exporter(#[from] ExporterBuildError),
+
+    #[error("OpenTelemetry tracing can only be initialized once")]
+    AlreadyInitialized,
+}
+
+/// Set once the exporter is configured
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PROCESSOR: OnceLock<BatchSpanProcessor> = OnceLock::new();
+static SAMPLER: OnceLock<Sampler> = OnceLock::new();
+static PROVIDER: LazyLock<SdkTracerProvider> = LazyLock::new(|| {
+    SdkTracerProvider::builder()
+        .with_span_processor(DeferredProcessor)
+        .with_sampler(DeferredSampler)
+        .build()
+});
+
+/// The layer exporting spans, to be added to the tracing subscriber
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    OpenTelemetryLayer::<S, SdkTracer>::new(PROVIDER.tracer("martin")).with_filter(ExportFilter)
+}
+
+/// Start exporting spans as configured.
+///
+/// Must be called within a Tokio runtime, which the gRPC exporter uses for its connections.
+pub fn init(config: &TracingConfig) -> Result<(), OtelError> {
+    let exporter = SpanExporter::builder();
+    let exporter = match config.protocol {
+        OtlpProtocol::Grpc => {
+            let builder = exporter.with_tonic();
+            match &config.endpoint {
+                Some(endpoint) => builder.with_endpoint(endpoint).build()?,
+                None => builder.build()?,
+            }
+        }
+        OtlpProtocol::Http => {
+            let builder = exporter.with_http();
+            match &config.endpoint {
+                Some(endpoint) => builder.with_endpoint(endpoint).build()?,
+                None => builder.build()?,
+            }
+        }
+    };
+
+    let mut resource = Resource::builder();
+    if let Some(name) = &config.service_name {
+        resource = resource.with_service_name(name.clone());
+    } else if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(DEFAULT_SERVICE_NAME);
+    }
+    let mut processor = BatchSpanProcessor::builder(exporter).build();
+    processor.set_resource(&resource.build());
+
+    let ratio = config.sample_ratio.unwrap_or(1.0);
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio)));
+    if PROCESSOR.set(processor).is_err() || SAMPLER.set(sampler).is_err() {
+        return Err(OtelError::AlreadyInitialized);
+    }
+
+    // `traceparent` headers of incoming requests are extracted by `TracingLogger`
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    ENABLED.store(true, Ordering::Release);
+    // Callsites registered so far were disabled for the layer
+    tracing::callsite::rebuild_interest_cache();
+    Ok(())
+}
+
+/// Export the spans that are still buffered, e.g. before exiting
+pub fn shutdown() {
+    if ENABLED.load(Ordering::Acquire)
+        && let Err(e) = PROVIDER.force_flush()
+    {
+        tracing::warn!("Unable to export the remaining spans: {e}");
+    }
+}
+
+/// Passes spans to the processor created by [`init`], dropping them until then
+#[derive(Debug)]
+struct DeferredProcessor;
+
+impl SpanProcessor for DeferredProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        if let Some(processor) = PROCESSOR.get() {
+            processor.on_start(span, cx);
+        }
+    }
+
+    fn on_end(&self, span: SpanData) {
+        if let Some(processor) = PROCESSOR.get() {
+            processor.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        PROCESSOR.get().map_or(Ok(()), SpanProcessor::force_flush)
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        PROCESSOR
+            .get()
+            .map_or(Ok(()), |p| p.shutdown_with_timeout(timeout))
+    }
+}
+
+/// Samples spans as configured by [`init`], dropping them until then
+#[derive(Clone, Debug)]
+struct DeferredSampler;
+
+impl ShouldSample for DeferredSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        match SAMPLER.get() {
+            Some(sampler) => {
+                sampler.should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+            }
+            None => SamplingResult {
+                decision: SamplingDecision::Drop,
+                attributes: Vec::new(),
+                trace_state: TraceState::default(),
+            },
+        }
+    }
+}
+
+/// Exports spans of Martin's own crates from the debug level, and their warnings and errors
+/// as span events. Everything is disabled until [`init`] was called.
+struct ExportFilter;
+
+impl ExportFilter {
+    fn is_exported(meta: &Metadata<'_>) -> bool {
+        ENABLED.load(Ordering::Acquire) && exports(meta.target(), *meta.level(), meta.is_span())
+    }
+}
+
+impl<S> Filter<S> for ExportFilter {
+    fn enabled(&self, meta: &Metadata<'_>, _cx: &LayerContext<'_, S>) -> bool {
+        Self::is_exported(meta)
+    }
+
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        if Self::is_exported(meta) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        if ENABLED.load(Ordering::Acquire) {
+            Some(LevelFilter::DEBUG)
+        } else {
+            Some(LevelFilter::OFF)
+        }
+    }
+}
+
+fn exports(target: &str, level: Level, is_span: bool) -> bool {
+    let max_level = if is_span { Level::DEBUG } else { Level::WARN };
+    level <= max_level
+        && EXPORTED_TARGETS.iter().any(|crate_name| {
+            target
+                .strip_prefix(crate_name)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exported_spans_and_events() {
+        assert!(exports("martin::srv::tiles::content", Level::DEBUG, true));
+        assert!(exports("martin_core", Level::INFO, true));
+        assert!(exports(
+            "tracing_actix_web::root_span_builder",
+            Level::INFO,
+            true
+        ));
+        assert!(!exports("martin", Level::TRACE, true));
+        assert!(!exports("martin_tile_utils", Level::INFO, true));
+        assert!(!exports("hyper::client", Level::INFO, true));
+
+        assert!(exports("martin::srv", Level::ERROR, false));
+        assert!(exports("mbtiles", Level::WARN, false));
+        assert!(!exports("martin", Level::INFO, false));
+        assert!(!exports("tonic::transport", Level::ERROR, false));
+    }
+}
@@ -32,15 +32,21 @@ pub fn config_json_schema() -> serde_json::Value {
     ),
     paths(
         crate::srv::get_health,
+        crate::srv::get_ready,
         crate::srv::get_catalog,
         crate::srv::get_source_info,
         crate::srv::get_tile,
+        crate::srv::get_tile_matrix_set_tile,
         crate::srv::get_sprite_png,
         crate::srv::get_sprite_sdf_png,
         crate::srv::get_sprite_json,
         crate::srv::get_sprite_sdf_json,
         crate::srv::get_font,
         crate::srv::get_style_json,
+        crate::srv::get_patch_chain,
+        crate::srv::get_patch_file,
+        crate::srv::put_tile,
+        crate::srv::delete_tile,
     )
 )]
 pub struct MartinOpenApi;
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use actix_web::error::{ErrorBadRequest, ErrorNotFound};
@@ -5,6 +6,7 @@ use dashmap::DashMap;
 use martin_core::tiles::catalog::TileCatalog;
 use martin_core::tiles::{BoxedSource, Source};
 use martin_tile_utils::TileInfo;
+use martin_tile_utils::tms::TileMatrixSet;
 use tracing::debug;
 
 use crate::config::file::ProcessConfig;
@@ -18,6 +20,8 @@ pub struct ResolvedSources {
     pub sources: Vec<(BoxedSource, ProcessConfig)>,
     pub use_url_query: bool,
     pub info: TileInfo,
+    /// ID of the tile matrix set shared by all the sources
+    pub tile_matrix_set: String,
 }
 
 /// Thread-safe registry of tile sources indexed by ID.
@@ -76,6 +80,12 @@ impl TileSources {
             .collect()
     }
 
+    /// Returns all sources.
+    #[must_use]
+    pub fn sources(&self) -> Vec<BoxedSource> {
+        self.0.iter().map(|v| v.value().0.clone()).collect()
+    }
+
     /// Returns all source IDs.
     #[must_use]
     pub fn source_names(&self) -> Vec<String> {
@@ -95,7 +105,7 @@ impl TileSources {
     /// Gets multiple sources for composite tiles, ensuring format compatibility.
     ///
     /// Parses comma-separated source IDs and validates all sources have matching
-    /// format/encoding. Optionally filters by zoom level support.
+    /// format/encoding and tile matrix set. Optionally filters by zoom level support.
     ///
     #[hotpath::measure]
     pub fn get_sources(
@@ -113,6 +123,7 @@ impl TileSources {
 
         let mut sources = Vec::new();
         let mut info: Option<TileInfo> = None;
+        let mut tile_matrix_set: Option<String> = None;
         let mut use_url_query = false;
 
         for id in ids {
@@ -132,9 +143,21 @@ impl TileSources {
                 None => info = Some(src_inf),
             }
 
+            // tiles of different tile matrix sets cover different areas and cannot be merged
+            let src_tms = &src.get_tile_matrix_set().id;
+            match &tile_matrix_set {
+                Some(tms) if tms == src_tms => {}
+                Some(tms) => {
+                    return Err(ErrorNotFound(format!(
+                        "Cannot merge sources in tile matrix set {tms} with {src_tms}"
+                    )));
+                }
+                None => tile_matrix_set = Some(src_tms.clone()),
+            }
+
             // TODO: Use chained-if-let once available
             if match zoom {
-                Some(zoom) if Self::check_zoom(&*src, id, zoom) => true,
+                Some(zoom) if Self::check_zoom(&*src, &pc, id, zoom) => true,
                 None => true,
                 _ => false,
             } {
@@ -146,13 +169,15 @@ impl TileSources {
             sources,
             use_url_query,
             info: info.expect("at least one source must be present"),
+            tile_matrix_set: tile_matrix_set.expect("at least one source must be present"),
         })
     }
 
-    /// Validates zoom level support for a source
+    /// Validates zoom level support for a source, including the zoom levels it is overzoomed to
     #[must_use]
-    pub fn check_zoom(src: &dyn Source, id: &str, zoom: u8) -> bool {
-        let is_valid = src.is_valid_zoom(zoom);
+    pub fn check_zoom(src: &dyn Source, pc: &ProcessConfig, id: &str, zoom: u8) -> bool {
+        let is_valid =
+            src.is_valid_zoom(zoom) || pc.overzoom_from(src.get_tilejson(), zoom).is_some();
         if !is_valid {
             let tilejson = src.get_tilejson();
             debug!(
@@ -166,6 +191,25 @@ impl TileSources {
         is_valid
     }
 
+    /// Returns the built-in tile matrix sets and all the ones used by the sources, sorted by ID.
+    #[must_use]
+    pub fn tile_matrix_sets(&self) -> Vec<TileMatrixSet> {
+        let mut sets: BTreeMap<String, TileMatrixSet> = [
+            TileMatrixSet::web_mercator_quad(),
+            TileMatrixSet::world_crs84_quad(),
+        ]
+        .into_iter()
+        .map(|tms| (tms.id.clone(), tms))
+        .collect();
+        for entry in self.0.iter() {
+            let tms = entry.value().0.get_tile_matrix_set();
+            if !sets.contains_key(&tms.id) {
+                sets.insert(tms.id.clone(), tms.clone());
+            }
+        }
+        sets.into_values().collect()
+    }
+
     /// Returns if any source benefits from concurrent scraping by martin-cp
     #[must_use]
     pub fn benefits_from_concurrent_scraping(&self) -> bool {
@@ -0,0 +1,503 @@
+//! Access log with one line per request, including the tile details of tile requests.
+//!
+//! Lines are written by a background thread, so that slow disks do not delay the responses.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use actix_web::HttpResponse;
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{CONTENT_ENCODING, HeaderMap, HeaderName, REFERER, USER_AGENT};
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::file::access_log::{
+    AccessLogConfig, AccessLogFormat, DEFAULT_MAX_FILES, DEFAULT_MAX_SIZE_MB,
+};
+use crate::config::file::{ConfigFileError, ConfigFileResult};
+use crate::srv::limits::client_addr;
+
+/// Lines waiting to be written before new ones are dropped
+const QUEUE_CAPACITY: usize = 16 * 1024;
+
+/// Whether a tile was served from the tile cache
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStatus {
+    /// Served from the cache, or shared with a concurrent request for the same tile
+    Hit,
+    /// Queried from the source
+    Miss,
+    /// The cache is disabled, or does not cache tiles of this zoom
+    Bypass,
+}
+
+impl CacheStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Hit => "hit",
+            Self::Miss => "miss",
+            Self::Bypass => "bypass",
+        }
+    }
+}
+
+/// Tile details of a response, added to its extensions by the tile handlers
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TileAccess {
+    /// IDs of the sources, separated by commas for composite sources
+    pub source: String,
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+    pub cache: CacheStatus,
+}
+
+/// One line of the access log
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize)]
+struct AccessRecord {
+    time: DateTime<Utc>,
+    client: String,
+    method: String,
+    uri: String,
+    protocol: String,
+    status: u16,
+    bytes: Option<u64>,
+    duration_ms: f64,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    encoding: Option<String>,
+    #[serde(flatten)]
+    tile: Option<TileAccess>,
+}
+
+impl AccessRecord {
+    fn new(req: &ServiceRequest, config: &AccessLogConfig) -> Self {
+        let client = client_addr(
+            req,
+            config.trust_forwarded.unwrap_or_default(),
+            &config.trusted_proxies,
+        );
+        Self {
+            time: DateTime::from(SystemTime::now()),
+            client: client.unwrap_or_else(|| "-".to_owned()),
+            method: req.method().to_string(),
+            uri: req
+                .uri()
+                .path_and_query()
+                .map_or_else(|| req.path().to_owned(), ToString::to_string),
+            protocol: format!("{:?}", req.version()),
+            status: 0,
+            bytes: None,
+            duration_ms: 0.0,
+            referer: header(req.headers(), &REFERER),
+            user_agent: header(req.headers(), &USER_AGENT),
+            encoding: None,
+            tile: None,
+        }
+    }
+
+    fn with_response<B: MessageBody>(self, response: &HttpResponse<B>, duration: Duration) -> Self {
+        Self {
+            status: response.status().as_u16(),
+            bytes: match response.body().size() {
+                BodySize::Sized(size) if size > 0 => Some(size),
+                _ => None,
+            },
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            encoding: header(response.headers(), &CONTENT_ENCODING),
+            tile: response.extensions().get::<TileAccess>().cloned(),
+            ..self
+        }
+    }
+
+    /// The combined log format, followed by the tile details.
+    /// Missing values are logged as `-`, so that every line has the same fields.
+    fn combined(&self) -> String {
+        let tile = match &self.tile {
+            Some(t) => format!(
+                "source={} tile={}/{}/{} cache={}",
+                t.source,
+                t.z,
+                t.x,
+                t.y,
+                t.cache.as_str()
+            ),
+            None => "source=- tile=- cache=-".to_owned(),
+        };
+        format!(
+            r#"{client} - - [{time}] "{method} {uri} {protocol}" {status} {bytes} "{referer}" "{user_agent}" {tile} encoding={encoding} duration_ms={duration_ms:.3}"#,
+            client = self.client,
+            time = self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            method = self.method,
+            uri = self.uri,
+            protocol = self.protocol,
+            status = self.status,
+            bytes = self.bytes.map_or_else(|| "-".to_owned(), |b| b.to_string()),
+            referer = escape(self.referer.as_deref()),
+            user_agent = escape(self.user_agent.as_deref()),
+            encoding = self.encoding.as_deref().unwrap_or("-"),
+            duration_ms = self.duration_ms,
+        )
+    }
+}
+
+fn header(headers: &HeaderMap, name: &HeaderName) -> Option<String> {
+    Some(headers.get(name)?.to_str().ok()?.to_owned())
+}
+
+/// Escapes a value logged in double quotes
+fn escape(value: Option<&str>) -> String {
+    value.map_or_else(
+        || "-".to_owned(),
+        |v| v.replace('\\', "\\\\").replace('"', "\\\""),
+    )
+}
+
+/// Picks an evenly spread `ratio` of the calls
+#[derive(Debug)]
+struct Sampler {
+    ratio: f64,
+    credit: Mutex<f64>,
+}
+
+impl Sampler {
+    fn new(ratio: Option<f64>) -> Self {
+        Self {
+            ratio: ratio.unwrap_or(1.0).clamp(0.0, 1.0),
+            credit: Mutex::new(0.0),
+        }
+    }
+
+    fn sample(&self) -> bool {
+        if self.ratio >= 1.0 {
+            return true;
+        }
+        let mut credit = self.credit.lock().unwrap_or_else(PoisonError::into_inner);
+        *credit += self.ratio;
+        if *credit >= 1.0 {
+            *credit -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A log file renamed to `<path>.1` once it grows past `max_size`,
+/// shifting the older files up to `<path>.<max_files>`
+struct RotatingFile {
+    path: PathBuf,
+    file: BufWriter<File>,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            remove_if_exists(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                rename_if_exists(&self.rotated(n), &self.rotated(n + 1))?;
+            }
+            rename_if_exists(&self.path, &self.rotated(1))?;
+        }
+        *self = Self::open(self.path.clone(), self.max_size, self.max_files)?;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = u64::try_from(buf.len()).unwrap_or(u64::MAX);
+        if self.size > 0 && self.size.saturating_add(len) > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += u64::try_from(written).unwrap_or(u64::MAX);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Writes the queued lines until the [`AccessLog`] is dropped
+fn write_lines(lines: &Receiver<String>, mut output: Box<dyn Write + Send>, dropped: &AtomicU64) {
+    while let Ok(line) = lines.recv() {
+        let mut result = output.write_all(line.as_bytes());
+        // Flush once the queue is empty rather than after every line
+        for line in lines.try_iter() {
+            result = result.and_then(|()| output.write_all(line.as_bytes()));
+        }
+        if let Err(e) = result.and_then(|()| output.flush()) {
+            warn!("Unable to write the access log: {e}");
+        }
+        let dropped = dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("Dropped {dropped} access log lines, requests were logged faster than written");
+        }
+    }
+}
+
+/// The access log shared by all the server workers
+#[derive(Debug)]
+pub struct AccessLog {
+    config: AccessLogConfig,
+    sampler: Sampler,
+    lines: SyncSender<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl AccessLog {
+    /// Opens the log file, or the standard output, and starts writing to it
+    pub fn new(config: AccessLogConfig) -> ConfigFileResult<Self> {
+        if config.trust_forwarded.unwrap_or_default() && config.trusted_proxies.is_empty() {
+            warn!(
+                "access_log.trust_forwarded is ignored, because access_log.trusted_proxies is empty"
+            );
+        }
+        let destination = config
+            .path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("<stdout>"));
+        let output: Box<dyn Write + Send> = match &config.path {
+            Some(path) => {
+                let max_size_mb = config
+                    .max_size_mb
+                    .map_or(DEFAULT_MAX_SIZE_MB, NonZeroU64::get);
+                let file = RotatingFile::open(
+                    path.clone(),
+                    max_size_mb.saturating_mul(1000 * 1000),
+                    config.max_files.unwrap_or(DEFAULT_MAX_FILES),
+                )
+                .map_err(|e| ConfigFileError::IoError(e, destination.clone()))?;
+                Box::new(file)
+            }
+            None => Box::new(BufWriter::new(io::stdout())),
+        };
+
+        let (lines, receiver) = sync_channel(QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let dropped_lines = Arc::clone(&dropped);
+        thread::Builder::new()
+            .name("access-log".to_owned())
+            .spawn(move || write_lines(&receiver, output, &dropped_lines))
+            .map_err(|e| ConfigFileError::IoError(e, destination))?;
+
+        Ok(Self {
+            sampler: Sampler::new(config.sample_ratio),
+            config,
+            lines,
+            dropped,
+        })
+    }
+
+    fn log(&self, record: &AccessRecord) {
+        if record.status < 500 && !self.sampler.sample() {
+            return;
+        }
+        let mut line = match self.config.format {
+            AccessLogFormat::Combined => record.combined(),
+            AccessLogFormat::Json => {
+                serde_json::to_string(record).expect("access log records serialize to JSON")
+            }
+        };
+        line.push('\n');
+        if let Err(TrySendError::Full(_)) = self.lines.try_send(line) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Middleware writing an [`AccessLog`] line for each response, if the access log is enabled
+pub async fn log_access(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(log) = req.app_data::<Data<AccessLog>>().cloned() else {
+        return next.call(req).await;
+    };
+    if log.config.is_excluded(req.path()) {
+        return next.call(req).await;
+    }
+
+    let start = Instant::now();
+    let record = AccessRecord::new(&req, &log.config);
+    let result = next.call(req).await;
+    match &result {
+        Ok(res) => log.log(&record.with_response(res.response(), start.elapsed())),
+        Err(e) => log.log(&record.with_response(&e.error_response(), start.elapsed())),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    fn record() -> AccessRecord {
+        let req = TestRequest::get()
+            .uri("/src/1/2/3?version=1")
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .insert_header((USER_AGENT, r#"Test "agent""#))
+            .to_srv_request();
+        let mut response = HttpResponse::Ok()
+            .insert_header((CONTENT_ENCODING, "gzip"))
+            .body(vec![0; 42]);
+        response.extensions_mut().insert(TileAccess {
+            source: "src".to_owned(),
+            z: 1,
+            x: 2,
+            y: 3,
+            cache: CacheStatus::Miss,
+        });
+        AccessRecord {
+            time: Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap(),
+            ..AccessRecord::new(&req, &AccessLogConfig::default())
+                .with_response(&response, Duration::from_millis(1500))
+        }
+    }
+
+    #[test]
+    fn combined_format() {
+        assert_eq!(
+            record().combined(),
+            r#"10.0.0.1 - - [02/Jan/2025:03:04:05 +0000] "GET /src/1/2/3?version=1 HTTP/1.1" 200 42 "-" "Test \"agent\"" source=src tile=1/2/3 cache=miss encoding=gzip duration_ms=1500.000"#
+        );
+
+        let req = TestRequest::get().uri("/catalog").to_srv_request();
+        let line = AccessRecord::new(&req, &AccessLogConfig::default())
+            .with_response(&HttpResponse::NoContent().finish(), Duration::ZERO)
+            .combined();
+        assert!(
+            line.ends_with(
+                r#" 204 - "-" "-" source=- tile=- cache=- encoding=- duration_ms=0.000"#
+            ),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn forwarded_client_only_from_trusted_proxies() {
+        let config = AccessLogConfig {
+            trust_forwarded: Some(true),
+            trusted_proxies: vec!["10.0.0.0/8".to_owned().try_into().unwrap()],
+            ..Default::default()
+        };
+        let client = |peer: &str| {
+            let req = TestRequest::get()
+                .peer_addr(peer.parse().unwrap())
+                .insert_header(("X-Forwarded-For", "203.0.113.7"))
+                .to_srv_request();
+            AccessRecord::new(&req, &config).client
+        };
+        assert_eq!(client("10.1.2.3:1234"), "203.0.113.7");
+        assert_eq!(client("192.168.1.1:1234"), "192.168.1.1");
+    }
+
+    #[test]
+    fn json_format() {
+        let value = serde_json::to_value(record()).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "time": "2025-01-02T03:04:05Z",
+                "client": "10.0.0.1",
+                "method": "GET",
+                "uri": "/src/1/2/3?version=1",
+                "protocol": "HTTP/1.1",
+                "status": 200,
+                "bytes": 42,
+                "duration_ms": 1500.0,
+                "user_agent": r#"Test "agent""#,
+                "encoding": "gzip",
+                "source": "src",
+                "z": 1,
+                "x": 2,
+                "y": 3,
+                "cache": "miss",
+            })
+        );
+    }
+
+    #[test]
+    fn sampling_is_evenly_spread() {
+        let sampler = Sampler::new(Some(0.25));
+        let picks: Vec<bool> = (0..8).map(|_| sampler.sample()).collect();
+        assert_eq!(
+            picks,
+            [false, false, false, true, false, false, false, true]
+        );
+        assert!(Sampler::new(None).sample());
+        assert!(!Sampler::new(Some(0.0)).sample());
+    }
+
+    #[test]
+    fn file_is_rotated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("access.log"), "fourth\n");
+        assert_eq!(read("access.log.1"), "third\n");
+        assert_eq!(read("access.log.2"), "second\n");
+        assert!(!dir.path().join("access.log.3").exists());
+    }
+}
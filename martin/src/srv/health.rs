@@ -0,0 +1,451 @@
+//! Readiness checks of the tile source backends, served at `/ready` and `/_/status`.
+//!
+//! The checks run when a probe arrives and their results are reused for `interval_secs`,
+//! so that frequent probes from several load balancers do not put load on the backends.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::web::Data;
+use actix_web::{HttpResponse, route};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "_tiles")]
+use futures::stream::{self, StreamExt as _};
+#[cfg(feature = "_tiles")]
+use martin_core::tiles::BoxedSource;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+#[cfg(feature = "_tiles")]
+use tracing::warn;
+
+use crate::config::file::health::{DEFAULT_INTERVAL_SECS, DEFAULT_TIMEOUT_MS, HealthConfig};
+#[cfg(feature = "_tiles")]
+use crate::tile_source_manager::TileSourceManager;
+
+/// Sources checked at the same time
+#[cfg(feature = "_tiles")]
+const MAX_CONCURRENT_CHECKS: usize = 16;
+
+/// Overall state of the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerStatus {
+    /// All sources are healthy
+    Ok,
+    /// Some sources are unhealthy, but none of them is critical
+    Degraded,
+    /// A critical source is unhealthy, `/ready` fails
+    Unavailable,
+}
+
+/// Result of the last check of a source
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceStatus {
+    Ok,
+    Error,
+    Timeout,
+}
+
+/// The most recent failed check of a source, kept after the source recovered
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LastError {
+    pub message: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SourceHealth {
+    pub status: SourceStatus,
+    pub critical: bool,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<LastError>,
+}
+
+/// Results of checking all sources, served as JSON at `/_/status`
+#[derive(Clone, Debug, Serialize)]
+pub struct HealthReport {
+    pub status: ServerStatus,
+    pub checked_at: DateTime<Utc>,
+    pub sources: BTreeMap<String, SourceHealth>,
+}
+
+impl HealthReport {
+    /// IDs of the unhealthy critical sources
+    fn failed_critical(&self) -> impl Iterator<Item = &str> {
+        self.sources
+            .iter()
+            .filter(|(_, s)| s.critical && s.status != SourceStatus::Ok)
+            .map(|(id, _)| id.as_str())
+    }
+}
+
+#[derive(Default)]
+struct CheckState {
+    report: Option<(Instant, Arc<HealthReport>)>,
+    last_errors: HashMap<String, LastError>,
+}
+
+/// Checks the backends of all tile sources, shared by all workers
+pub struct HealthChecker {
+    config: HealthConfig,
+    /// Also makes concurrent probes wait for a single run of the checks
+    state: Mutex<CheckState>,
+}
+
+impl HealthChecker {
+    #[must_use]
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::default(),
+        }
+    }
+
+    #[cfg(feature = "_tiles")]
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.config.timeout_ms.map_or(DEFAULT_TIMEOUT_MS, u64::from))
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS))
+    }
+
+    /// Returns the results of the last checks, or checks the sources again if they are outdated
+    pub async fn report(
+        &self,
+        #[cfg(feature = "_tiles")] sources: Vec<BoxedSource>,
+    ) -> Arc<HealthReport> {
+        let mut state = self.state.lock().await;
+        if let Some((checked, report)) = &state.report
+            && checked.elapsed() < self.interval()
+        {
+            return Arc::clone(report);
+        }
+
+        let checked = Instant::now();
+        #[cfg_attr(not(feature = "_tiles"), expect(unused_mut))]
+        let mut sources_health = BTreeMap::new();
+        #[cfg(feature = "_tiles")]
+        {
+            let timeout = self.timeout();
+            let results: Vec<_> = stream::iter(check_groups(sources))
+                .map(|(src, ids)| async move {
+                    let started = Instant::now();
+                    let result = tokio::time::timeout(timeout, src.check_health())
+                        .await
+                        .ok()
+                        .map(|result| result.map_err(|e| e.to_string()));
+                    (ids, result, started.elapsed())
+                })
+                .buffer_unordered(MAX_CONCURRENT_CHECKS)
+                .collect()
+                .await;
+
+            state
+                .last_errors
+                .retain(|id, _| results.iter().any(|(ids, ..)| ids.contains(id)));
+            for (ids, result, duration) in results {
+                for id in ids {
+                    let status = match &result {
+                        Some(Ok(())) => SourceStatus::Ok,
+                        Some(Err(e)) => {
+                            warn!("Health check of source {id} failed: {e}");
+                            state.last_errors.insert(
+                                id.clone(),
+                                LastError {
+                                    message: e.clone(),
+                                    at: Utc::now(),
+                                },
+                            );
+                            SourceStatus::Error
+                        }
+                        None => {
+                            warn!("Health check of source {id} timed out after {timeout:?}");
+                            state.last_errors.insert(
+                                id.clone(),
+                                LastError {
+                                    message: format!("Timed out after {timeout:?}"),
+                                    at: Utc::now(),
+                                },
+                            );
+                            SourceStatus::Timeout
+                        }
+                    };
+                    let health = SourceHealth {
+                        status,
+                        critical: self.config.is_critical(&id),
+                        duration_ms: duration.as_secs_f64() * 1000.0,
+                        last_error: state.last_errors.get(&id).cloned(),
+                    };
+                    sources_health.insert(id, health);
+                }
+            }
+        }
+
+        let mut status = ServerStatus::Ok;
+        for health in sources_health.values() {
+            if health.status != SourceStatus::Ok {
+                if health.critical {
+                    status = ServerStatus::Unavailable;
+                    break;
+                }
+                status = ServerStatus::Degraded;
+            }
+        }
+        let report = Arc::new(HealthReport {
+            status,
+            checked_at: Utc::now(),
+            sources: sources_health,
+        });
+        state.report = Some((checked, Arc::clone(&report)));
+        report
+    }
+}
+
+/// Groups the sources sharing a backend, see
+/// [`Source::health_check_key`](martin_core::tiles::Source::health_check_key),
+/// returning the source to check for each group, and the IDs of all sources in it.
+#[cfg(feature = "_tiles")]
+fn check_groups(sources: Vec<BoxedSource>) -> Vec<(BoxedSource, Vec<String>)> {
+    let mut groups: Vec<(BoxedSource, Vec<String>)> = Vec::new();
+    let mut keys: HashMap<String, usize> = HashMap::new();
+    for src in sources {
+        let id = src.get_id().to_owned();
+        if let Some(key) = src.health_check_key() {
+            if let Some(&idx) = keys.get(&key) {
+                groups[idx].1.push(id);
+                continue;
+            }
+            keys.insert(key, groups.len());
+        }
+        groups.push((src, vec![id]));
+    }
+    groups
+}
+
+async fn get_report(
+    checker: &HealthChecker,
+    #[cfg(feature = "_tiles")] tile_manager: &TileSourceManager,
+) -> Arc<HealthReport> {
+    checker
+        .report(
+            #[cfg(feature = "_tiles")]
+            tile_manager.tile_sources().sources(),
+        )
+        .await
+}
+
+/// Return 200 OK if all critical sources are healthy, and 503 otherwise.
+/// Used for readiness probes.
+#[cfg_attr(
+    feature = "unstable-schemas",
+    utoipa::path(
+        get,
+        path = "/ready",
+        responses(
+            (status = 200, description = "All critical sources are healthy", body = String),
+            (status = 503, description = "Some critical sources are unhealthy", body = String),
+        ),
+    )
+)]
+#[route("/ready", method = "GET", method = "HEAD")]
+pub async fn get_ready(
+    checker: Data<HealthChecker>,
+    #[cfg(feature = "_tiles")] tile_manager: Data<TileSourceManager>,
+) -> HttpResponse {
+    let report = get_report(
+        &checker,
+        #[cfg(feature = "_tiles")]
+        &tile_manager,
+    )
+    .await;
+    let failed: Vec<&str> = report.failed_critical().collect();
+    if failed.is_empty() {
+        HttpResponse::Ok()
+            .insert_header((CACHE_CONTROL, "no-cache"))
+            .body("OK")
+    } else {
+        HttpResponse::ServiceUnavailable()
+            .insert_header((CACHE_CONTROL, "no-cache"))
+            .body(format!("Unhealthy sources: {}", failed.join(", ")))
+    }
+}
+
+/// Status of every source, as of the last check
+#[route("/_/status", method = "GET", method = "HEAD")]
+pub async fn get_status(
+    checker: Data<HealthChecker>,
+    #[cfg(feature = "_tiles")] tile_manager: Data<TileSourceManager>,
+) -> HttpResponse {
+    let report = get_report(
+        &checker,
+        #[cfg(feature = "_tiles")]
+        &tile_manager,
+    )
+    .await;
+    HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .json(report.as_ref())
+}
+
+#[cfg(all(test, feature = "_tiles"))]
+mod tests {
+    use std::num::NonZeroU64;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use async_trait::async_trait;
+    use martin_core::CacheZoomRange;
+    use martin_core::tiles::{MartinCoreError, MartinCoreResult, Source, UrlQuery};
+    use martin_tile_utils::{Encoding, Format, TileCoord, TileData, TileInfo};
+    use tilejson::{TileJSON, tilejson};
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Backend {
+        Up,
+        Down,
+        Hanging,
+    }
+
+    #[derive(Debug, Clone)]
+    struct CheckedSource {
+        id: &'static str,
+        tj: TileJSON,
+        backend: Backend,
+        pool: Option<&'static str>,
+        checks: Arc<AtomicU32>,
+    }
+
+    fn source(id: &'static str, backend: Backend) -> BoxedSource {
+        pooled_source(id, backend, None, &Arc::default())
+    }
+
+    fn pooled_source(
+        id: &'static str,
+        backend: Backend,
+        pool: Option<&'static str>,
+        checks: &Arc<AtomicU32>,
+    ) -> BoxedSource {
+        Box::new(CheckedSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            backend,
+            pool,
+            checks: Arc::clone(checks),
+        })
+    }
+
+    #[async_trait]
+    impl Source for CheckedSource {
+        fn get_id(&self) -> &str {
+            self.id
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            &self.tj
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        }
+
+        fn clone_source(&self) -> BoxedSource {
+            Box::new(self.clone())
+        }
+
+        fn cache_zoom(&self) -> CacheZoomRange {
+            CacheZoomRange::default()
+        }
+
+        async fn get_tile(
+            &self,
+            _xyz: TileCoord,
+            _url_query: Option<&UrlQuery>,
+        ) -> MartinCoreResult<TileData> {
+            Ok(Vec::new())
+        }
+
+        async fn check_health(&self) -> MartinCoreResult<()> {
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            match self.backend {
+                Backend::Up => Ok(()),
+                Backend::Down => Err(MartinCoreError::OtherError("connection refused".into())),
+                Backend::Hanging => std::future::pending().await,
+            }
+        }
+
+        fn health_check_key(&self) -> Option<String> {
+            self.pool.map(str::to_owned)
+        }
+    }
+
+    fn sources(down: Backend) -> Vec<BoxedSource> {
+        vec![
+            source("roads", Backend::Up),
+            source("buildings", down),
+            source("pois", Backend::Hanging),
+        ]
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_sources() {
+        let checker = HealthChecker::new(HealthConfig {
+            timeout_ms: NonZeroU64::new(100),
+            critical_sources: Some(vec!["roads".to_owned(), "buildings".to_owned()]),
+            ..HealthConfig::default()
+        });
+
+        let report = checker.report(sources(Backend::Down)).await;
+        assert_eq!(report.status, ServerStatus::Unavailable);
+        assert_eq!(report.failed_critical().collect::<Vec<_>>(), ["buildings"]);
+        assert_eq!(report.sources["roads"].status, SourceStatus::Ok);
+        assert!(report.sources["roads"].last_error.is_none());
+        let buildings = &report.sources["buildings"];
+        assert_eq!(buildings.status, SourceStatus::Error);
+        assert_eq!(
+            buildings.last_error.as_ref().unwrap().message,
+            "connection refused"
+        );
+        let pois = &report.sources["pois"];
+        assert_eq!(pois.status, SourceStatus::Timeout);
+        assert!(!pois.critical);
+
+        // the results are reused until the interval has passed
+        let cached = checker.report(sources(Backend::Up)).await;
+        assert!(Arc::ptr_eq(&report, &cached));
+
+        tokio::time::advance(Duration::from_secs(DEFAULT_INTERVAL_SECS)).await;
+        let report = checker.report(sources(Backend::Up)).await;
+        assert_eq!(report.status, ServerStatus::Degraded);
+        let buildings = &report.sources["buildings"];
+        assert_eq!(buildings.status, SourceStatus::Ok);
+        assert!(buildings.last_error.is_some(), "the last error is kept");
+    }
+
+    #[tokio::test]
+    async fn checks_shared_backends_once() {
+        let checker = HealthChecker::new(HealthConfig::default());
+        let checks = Arc::default();
+        let report = checker
+            .report(vec![
+                pooled_source("roads", Backend::Down, Some("db"), &checks),
+                pooled_source("buildings", Backend::Down, Some("db"), &checks),
+                pooled_source("pois", Backend::Up, None, &checks),
+                pooled_source("water", Backend::Up, None, &checks),
+            ])
+            .await;
+
+        assert_eq!(checks.load(Ordering::SeqCst), 3);
+        assert_eq!(report.sources.len(), 4);
+        assert_eq!(report.sources["roads"].status, SourceStatus::Error);
+        assert_eq!(report.sources["buildings"].status, SourceStatus::Error);
+        assert!(report.sources["buildings"].last_error.is_some());
+        assert_eq!(report.sources["water"].status, SourceStatus::Ok);
+    }
+}
@@ -0,0 +1,466 @@
+//! Admission control: per-client token buckets for the whole server and for each tile source,
+//! and a limit of the tile requests each source processes at the same time.
+
+use std::num::NonZeroU32;
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CACHE_CONTROL, RETRY_AFTER};
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use actix_web::{HttpMessage as _, HttpRequest, HttpResponse, ResponseError};
+use dashmap::DashMap;
+use moka::ops::compute::Op;
+use moka::sync::Cache;
+#[cfg(feature = "metrics")]
+use prometheus::{IntCounterVec, register_int_counter_vec};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+use tokio::time::timeout;
+use tracing::warn;
+
+use crate::config::file::rate_limit::{
+    ProxyNet, RateLimitConfig, SourceLimitConfig, is_trusted_proxy,
+};
+
+/// Suggested wait before retrying a request rejected because a source was busy
+const BUSY_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Number of clients tracked by each set of token buckets, the least recently seen are dropped
+const MAX_TRACKED_CLIENTS: u64 = 100_000;
+
+/// `source` label of rejections by the server-wide client limit
+const ALL_SOURCES: &str = "*";
+
+#[cfg(feature = "metrics")]
+static REJECTED_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "martin_rejected_requests_total",
+        "Requests rejected by rate or concurrency limits, labeled by source and limit",
+        &["source", "limit"]
+    )
+    .expect("static rejected requests metric definition is valid")
+});
+
+/// A request rejected by one of the configured limits
+#[derive(thiserror::Error, Debug)]
+pub enum LimitError {
+    #[error("Too many requests, retry in {}s", retry_after_secs(*.0))]
+    ClientRate(Duration),
+
+    #[error("Too many requests to source {0}, retry in {secs}s", secs = retry_after_secs(*.1))]
+    SourceRate(String, Duration),
+
+    #[error("Source {0} is busy, retry later")]
+    SourceBusy(String),
+}
+
+impl LimitError {
+    /// Value of the `limit` metric label
+    #[cfg_attr(not(feature = "metrics"), expect(dead_code))]
+    fn limit(&self) -> &'static str {
+        match self {
+            Self::ClientRate(_) | Self::SourceRate(..) => "rate",
+            Self::SourceBusy(_) => "in_flight",
+        }
+    }
+
+    fn retry_after(&self) -> Duration {
+        match self {
+            Self::ClientRate(wait) | Self::SourceRate(_, wait) => *wait,
+            Self::SourceBusy(_) => BUSY_RETRY_AFTER,
+        }
+    }
+}
+
+impl ResponseError for LimitError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::ClientRate(_) | Self::SourceRate(..) => StatusCode::TOO_MANY_REQUESTS,
+            Self::SourceBusy(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header((RETRY_AFTER, retry_after_secs(self.retry_after())))
+            .insert_header((CACHE_CONTROL, "no-store"))
+            .body(self.to_string())
+    }
+}
+
+/// Whole seconds to wait, as `Retry-After` has no smaller unit
+fn retry_after_secs(wait: Duration) -> u64 {
+    (wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1)
+}
+
+#[cfg_attr(not(feature = "metrics"), expect(unused_variables))]
+fn rejected(source: &str, error: LimitError) -> LimitError {
+    #[cfg(feature = "metrics")]
+    REJECTED_REQUESTS_TOTAL
+        .with_label_values(&[source, error.limit()])
+        .inc();
+    error
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// One token bucket per client, refilled at `rate` tokens per second up to `burst` tokens
+#[derive(Debug)]
+struct TokenBuckets {
+    rate: f64,
+    burst: f64,
+    clients: Cache<String, Bucket>,
+}
+
+impl TokenBuckets {
+    fn new(rate: NonZeroU32, burst: Option<NonZeroU32>) -> Self {
+        let burst = f64::from(burst.unwrap_or(rate).get());
+        let rate = f64::from(rate.get());
+        Self {
+            rate,
+            burst,
+            // An idle client's bucket is full again after `burst / rate` seconds,
+            // and a full bucket is the same as a missing one
+            clients: Cache::builder()
+                .max_capacity(MAX_TRACKED_CLIENTS)
+                .time_to_idle(Duration::from_secs_f64(burst / rate))
+                .build(),
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+
+    /// Takes a token from the bucket of the client, or returns how long until one is available
+    fn check(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut result = Ok(());
+        self.clients.entry_by_ref(client).and_compute_with(|entry| {
+            let mut bucket = entry.map_or(
+                Bucket {
+                    tokens: self.burst,
+                    updated: now,
+                },
+                moka::Entry::into_value,
+            );
+            bucket.tokens = self.refilled(&bucket, now);
+            bucket.updated = now;
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+            } else {
+                result = Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate));
+            }
+            Op::Put(bucket)
+        });
+        result
+    }
+}
+
+/// Limits of the tile requests to one source
+#[derive(Debug)]
+struct SourceLimits {
+    buckets: Option<TokenBuckets>,
+    in_flight: Option<Arc<Semaphore>>,
+    queue_timeout: Duration,
+}
+
+impl SourceLimits {
+    fn new(config: &SourceLimitConfig) -> Self {
+        Self {
+            buckets: config
+                .requests_per_second
+                .map(|rate| TokenBuckets::new(rate, config.burst)),
+            in_flight: config
+                .max_in_flight
+                .map(|max| Arc::new(Semaphore::new(max.get()))),
+            queue_timeout: config.queue_timeout.unwrap_or_default(),
+        }
+    }
+
+    /// Takes one of the `max_in_flight` slots, waiting up to `queue_timeout` for it
+    async fn acquire(&self, id: &str) -> Result<Option<OwnedSemaphorePermit>, LimitError> {
+        let Some(in_flight) = &self.in_flight else {
+            return Ok(None);
+        };
+        let permit = match Arc::clone(in_flight).try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(TryAcquireError::NoPermits) if !self.queue_timeout.is_zero() => {
+                timeout(self.queue_timeout, Arc::clone(in_flight).acquire_owned())
+                    .await
+                    .ok()
+                    .and_then(Result::ok)
+            }
+            Err(TryAcquireError::NoPermits | TryAcquireError::Closed) => None,
+        };
+        match permit {
+            Some(permit) => Ok(Some(permit)),
+            None => Err(rejected(id, LimitError::SourceBusy(id.to_owned()))),
+        }
+    }
+}
+
+/// IP address of the client of a request.
+///
+/// With `trust_forwarded`, the address is taken from the `Forwarded` or `X-Forwarded-For` headers
+/// of requests from `trusted_proxies`, as any other client could forge them.
+pub(crate) fn client_addr(
+    req: &ServiceRequest,
+    trust_forwarded: bool,
+    trusted_proxies: &[ProxyNet],
+) -> Option<String> {
+    let info = req.connection_info();
+    let forwarded = trust_forwarded
+        && req
+            .peer_addr()
+            .is_some_and(|addr| is_trusted_proxy(trusted_proxies, addr.ip()));
+    let addr = if forwarded {
+        info.realip_remote_addr()
+    } else {
+        info.peer_addr()
+    };
+    addr.map(ToOwned::to_owned)
+}
+
+/// Identifies the client of a request for rate limiting, see [`RateLimitConfig::client_header`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientId(String);
+
+/// Rate and concurrency limits shared by all the server workers
+#[derive(Debug)]
+pub struct RateLimits {
+    config: RateLimitConfig,
+    clients: Option<TokenBuckets>,
+    sources: DashMap<String, Arc<SourceLimits>>,
+}
+
+impl RateLimits {
+    #[must_use]
+    pub fn new(config: RateLimitConfig) -> Self {
+        if let Some(name) = &config.client_header
+            && config.trusted_proxies.is_empty()
+        {
+            warn!(
+                "rate_limit.client_header {name} is ignored, because rate_limit.trusted_proxies is empty"
+            );
+        }
+        if config.trust_forwarded.unwrap_or_default() && config.trusted_proxies.is_empty() {
+            warn!(
+                "rate_limit.trust_forwarded is ignored, because rate_limit.trusted_proxies is empty"
+            );
+        }
+        Self {
+            clients: config
+                .requests_per_second
+                .map(|rate| TokenBuckets::new(rate, config.burst)),
+            config,
+            sources: DashMap::new(),
+        }
+    }
+
+    fn client_id(&self, req: &ServiceRequest) -> ClientId {
+        // Only proxies may set the header, otherwise clients could change it with every request
+        if let Some(name) = &self.config.client_header
+            && req
+                .peer_addr()
+                .is_some_and(|addr| self.config.is_trusted_proxy(addr.ip()))
+            && let Some(value) = req.headers().get(name.as_str())
+            && let Ok(value) = value.to_str()
+        {
+            return ClientId(format!("header:{value}"));
+        }
+        let ip = client_addr(
+            req,
+            self.config.trust_forwarded.unwrap_or_default(),
+            &self.config.trusted_proxies,
+        );
+        ClientId(format!("ip:{}", ip.as_deref().unwrap_or("unknown")))
+    }
+
+    fn check_client(&self, client: &ClientId) -> Result<(), LimitError> {
+        match &self.clients {
+            Some(buckets) => buckets
+                .check(&client.0)
+                .map_err(|wait| rejected(ALL_SOURCES, LimitError::ClientRate(wait))),
+            None => Ok(()),
+        }
+    }
+
+    /// Sources are added on first use, so that sources discovered at runtime are limited too
+    fn source(&self, id: &str) -> Arc<SourceLimits> {
+        if let Some(limits) = self.sources.get(id) {
+            return Arc::clone(&limits);
+        }
+        let limits = self
+            .sources
+            .entry(id.to_owned())
+            .or_insert_with(|| Arc::new(SourceLimits::new(&self.config.for_source(id))));
+        Arc::clone(&limits)
+    }
+}
+
+/// Allows the client of a request to get tiles from sources within their limits
+#[derive(Clone, Debug)]
+pub struct Admission {
+    limits: Data<RateLimits>,
+    client: ClientId,
+}
+
+impl Admission {
+    /// The admission of a request that passed [`limit_requests`], if rate limiting is enabled
+    #[must_use]
+    pub fn from_request(req: &HttpRequest) -> Option<Self> {
+        Some(Self {
+            limits: req.app_data::<Data<RateLimits>>()?.clone(),
+            client: req.extensions().get::<ClientId>()?.clone(),
+        })
+    }
+
+    /// Checks the rate limits of the sources, and takes a `max_in_flight` slot of each of them.
+    /// The slots are released when the returned permits are dropped.
+    pub async fn admit<'a>(
+        &self,
+        source_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<OwnedSemaphorePermit>, LimitError> {
+        let mut ids: Vec<&str> = source_ids.into_iter().collect();
+        // Taking the slots in the same order keeps composite requests from blocking each other
+        ids.sort_unstable();
+        ids.dedup();
+        let sources: Vec<_> = ids
+            .into_iter()
+            .map(|id| (id, self.limits.source(id)))
+            .collect();
+
+        for (id, limits) in &sources {
+            if let Some(buckets) = &limits.buckets {
+                buckets
+                    .check(&self.client.0)
+                    .map_err(|wait| rejected(id, LimitError::SourceRate((*id).to_owned(), wait)))?;
+            }
+        }
+        let mut permits = Vec::new();
+        for (id, limits) in &sources {
+            permits.extend(limits.acquire(id).await?);
+        }
+        Ok(permits)
+    }
+}
+
+fn is_health_check(path: &str) -> bool {
+    path.ends_with("/health") || path.ends_with("/ready")
+}
+
+/// Middleware rejecting the requests of clients above the server-wide rate limit.
+///
+/// It also identifies the client for the per-source limits, checked by [`Admission::admit`].
+/// Health checks are never limited.
+pub async fn limit_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(limits) = req.app_data::<Data<RateLimits>>().cloned() {
+        let client = limits.client_id(&req);
+        if !is_health_check(req.path())
+            && let Err(error) = limits.check_client(&client)
+        {
+            return Ok(req.error_response(error).map_into_right_body());
+        }
+        req.extensions_mut().insert(client);
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+
+    #[test]
+    fn token_bucket_allows_burst() {
+        let buckets = TokenBuckets::new(NonZeroU32::new(1).unwrap(), NonZeroU32::new(3));
+        for _ in 0..3 {
+            buckets.check("a").unwrap();
+        }
+        let wait = buckets.check("a").unwrap_err();
+        assert!(wait <= Duration::from_secs(1), "{wait:?}");
+        // other clients have their own bucket
+        buckets.check("b").unwrap();
+    }
+
+    #[test]
+    fn client_header_only_from_trusted_proxies() {
+        use actix_web::test::TestRequest;
+
+        let limits = RateLimits::new(RateLimitConfig {
+            client_header: Some("X-Api-Key".to_owned()),
+            trusted_proxies: vec!["10.0.0.0/8".to_owned().try_into().unwrap()],
+            ..Default::default()
+        });
+        let client = |peer: &str| {
+            let req = TestRequest::default()
+                .peer_addr(peer.parse().unwrap())
+                .insert_header(("X-Api-Key", "key"))
+                .to_srv_request();
+            limits.client_id(&req).0
+        };
+        assert_eq!(client("10.1.2.3:1234"), "header:key");
+        assert_eq!(client("192.168.1.1:1234"), "ip:192.168.1.1");
+    }
+
+    #[test]
+    fn forwarded_only_from_trusted_proxies() {
+        use actix_web::test::TestRequest;
+
+        let limits = RateLimits::new(RateLimitConfig {
+            trust_forwarded: Some(true),
+            trusted_proxies: vec!["10.0.0.0/8".to_owned().try_into().unwrap()],
+            ..Default::default()
+        });
+        let client = |peer: &str| {
+            let req = TestRequest::default()
+                .peer_addr(peer.parse().unwrap())
+                .insert_header(("X-Forwarded-For", "203.0.113.7"))
+                .to_srv_request();
+            limits.client_id(&req).0
+        };
+        assert_eq!(client("10.1.2.3:1234"), "ip:203.0.113.7");
+        assert_eq!(client("192.168.1.1:1234"), "ip:192.168.1.1");
+    }
+
+    #[test]
+    fn retry_after_rounds_up() {
+        assert_eq!(retry_after_secs(Duration::ZERO), 1);
+        assert_eq!(retry_after_secs(Duration::from_millis(200)), 1);
+        assert_eq!(retry_after_secs(Duration::from_secs(2)), 2);
+        assert_eq!(retry_after_secs(Duration::from_millis(2001)), 3);
+    }
+
+    #[tokio::test]
+    async fn source_busy_after_max_in_flight() {
+        let limits = SourceLimits::new(&SourceLimitConfig {
+            max_in_flight: NonZeroUsize::new(1),
+            queue_timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+        let permit = limits.acquire("src").await.unwrap();
+        assert!(permit.is_some());
+        let error = limits.acquire("src").await.unwrap_err();
+        assert_eq!(error.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+
+        drop(permit);
+        assert!(limits.acquire("src").await.unwrap().is_some());
+    }
+}
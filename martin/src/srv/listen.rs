@@ -0,0 +1,350 @@
+//! Sockets the server accepts connections on: TCP addresses, Unix domain sockets,
+//! and sockets passed in by systemd socket activation.
+
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::io;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use tracing::info;
+
+use crate::config::file::srv::UnixSocketMode;
+use crate::config::file::tls::TlsConfig;
+use crate::srv::tls;
+use crate::{MartinError, MartinResult};
+
+/// Prefix of `listen_addresses` for binding a Unix domain socket
+pub const UNIX_SOCKET_PREFIX: &str = "unix:";
+
+/// Errors setting up the sockets to listen on
+#[derive(thiserror::Error, Debug)]
+pub enum ListenError {
+    #[error("Unable to set the permissions of the Unix domain socket {1}: {0}")]
+    UnixSocketPermissions(#[source] io::Error, PathBuf),
+
+    #[error("Unix domain sockets are not supported on this platform")]
+    UnixSocketsUnsupported,
+
+    #[error(
+        "TLS is not supported on the Unix domain socket {0}. Set tls.listen_addresses to serve HTTPS on a TCP address."
+    )]
+    UnixSocketTls(String),
+
+    #[error(
+        "Unable to use the socket passed by systemd, it must be a TCP or Unix stream socket: {0}"
+    )]
+    SystemdSocket(#[source] io::Error),
+}
+
+/// A socket to accept connections on
+#[derive(Debug)]
+pub enum Listener {
+    /// TCP socket address(es) to bind, e.g. `0.0.0.0:3000`
+    Address(String),
+    /// Bound TCP socket, passed in by systemd
+    Tcp(TcpListener),
+    /// Bound Unix domain socket, with its path if it has one
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>),
+}
+
+impl Listener {
+    /// The URL of the listener, unless it is an address that is yet to be resolved
+    fn url(&self, scheme: &str) -> Option<String> {
+        match self {
+            Self::Address(_) => None,
+            Self::Tcp(_) => Some(format!("{scheme}://{self}")),
+            #[cfg(unix)]
+            Self::Unix(..) => Some(self.to_string()),
+        }
+    }
+}
+
+impl Display for Listener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(addresses) => write!(f, "{addresses}"),
+            Self::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{addr}"),
+                Err(_) => write!(f, "(systemd socket)"),
+            },
+            #[cfg(unix)]
+            Self::Unix(_, Some(path)) => write!(f, "{UNIX_SOCKET_PREFIX}{}", path.display()),
+            #[cfg(unix)]
+            Self::Unix(_, None) => write!(f, "{UNIX_SOCKET_PREFIX}(unnamed)"),
+        }
+    }
+}
+
+/// Sockets to listen on, as plain HTTP and as HTTPS
+pub struct Listeners {
+    pub http: Vec<Listener>,
+    pub https: Option<(Vec<Listener>, rustls::ServerConfig)>,
+    /// Checks the certificate files for renewals while the server runs
+    pub cert_reloader: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl Listeners {
+    /// Sockets passed by systemd replace `listen_addresses`.
+    /// With TLS, these serve HTTPS unless a separate HTTPS address is configured.
+    pub fn new(
+        listen_addresses: String,
+        unix_socket_mode: Option<UnixSocketMode>,
+        tls_config: Option<TlsConfig>,
+    ) -> MartinResult<Self> {
+        let listeners = primary_listeners(listen_addresses, unix_socket_mode)?;
+        let Some(tls_config) = tls_config else {
+            return Ok(Self {
+                http: listeners,
+                https: None,
+                cert_reloader: None,
+            });
+        };
+        let (rustls_config, resolver) = tls::server_config(&tls_config)?;
+        let interval = tls_config.reload_interval;
+        let (http, https) = if let Some(https_addresses) = tls_config.listen_addresses {
+            (listeners, vec![Listener::Address(https_addresses)])
+        } else {
+            #[cfg(unix)]
+            if let Some(unix) = listeners.iter().find(|l| matches!(l, Listener::Unix(..))) {
+                return Err(ListenError::UnixSocketTls(unix.to_string()).into());
+            }
+            (Vec::new(), listeners)
+        };
+        Ok(Self {
+            http,
+            https: Some((https, rustls_config)),
+            cert_reloader: (!interval.is_zero())
+                .then(|| Box::pin(resolver.watch(interval)) as Pin<Box<_>>),
+        })
+    }
+
+    /// The URL of the first socket, unless it is an address that is yet to be resolved
+    #[must_use]
+    pub fn url(&self) -> Option<String> {
+        if let Some(listener) = self.http.first() {
+            listener.url("http")
+        } else {
+            self.https.as_ref()?.0.first()?.url("https")
+        }
+    }
+}
+
+fn primary_listeners(
+    listen_addresses: String,
+    unix_socket_mode: Option<UnixSocketMode>,
+) -> MartinResult<Vec<Listener>> {
+    #[cfg(unix)]
+    {
+        let listeners = systemd::take_listeners()?;
+        if !listeners.is_empty() {
+            info!(
+                "Using {} socket(s) passed by systemd instead of {listen_addresses}",
+                listeners.len()
+            );
+            return Ok(listeners);
+        }
+    }
+    match listen_addresses.strip_prefix(UNIX_SOCKET_PREFIX) {
+        #[cfg(unix)]
+        Some(path) => Ok(vec![bind_unix_socket(
+            PathBuf::from(path),
+            unix_socket_mode,
+        )?]),
+        #[cfg(not(unix))]
+        Some(_) => Err(ListenError::UnixSocketsUnsupported.into()),
+        None => Ok(vec![Listener::Address(listen_addresses)]),
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix_socket(path: PathBuf, mode: Option<UnixSocketMode>) -> MartinResult<Listener> {
+    let binding_error = |e| MartinError::BindingError(e, path.display().to_string());
+    remove_stale_socket(&path).map_err(binding_error)?;
+    let listener = match mode {
+        Some(mode) => bind_with_mode(&path, mode)?,
+        None => UnixListener::bind(&path).map_err(binding_error)?,
+    };
+    Ok(Listener::Unix(listener, Some(path)))
+}
+
+/// Remove a socket file left behind by a previous run, which would make binding fail.
+/// A socket that still accepts connections belongs to a running server and is kept.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    use std::fs;
+    use std::os::unix::fs::FileTypeExt as _;
+    use std::os::unix::net::UnixStream;
+
+    if !fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        return Ok(());
+    }
+    match UnixStream::connect(path) {
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            info!("Removing the stale Unix domain socket {}", path.display());
+            fs::remove_file(path)
+        }
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another server is listening on this socket",
+        )),
+        // Binding reports why the socket cannot be used
+        Err(_) => Ok(()),
+    }
+}
+
+/// Bind the socket in a private directory and link it to `path` once it has its permissions,
+/// so that it is never reachable with the permissions given by the umask.
+#[cfg(unix)]
+fn bind_with_mode(path: &Path, mode: UnixSocketMode) -> MartinResult<UnixListener> {
+    use std::fs::{self, DirBuilder, Permissions};
+    use std::os::unix::fs::{DirBuilderExt as _, PermissionsExt as _};
+
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let dir = parent.join(format!(".martin-{}.sock.d", std::process::id()));
+    let binding_error = |e| MartinError::BindingError(e, path.display().to_string());
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .map_err(binding_error)?;
+    let private = dir.join("martin.sock");
+    let result = UnixListener::bind(&private)
+        .map_err(binding_error)
+        .and_then(|listener| {
+            fs::set_permissions(&private, Permissions::from_mode(mode.bits()))
+                .map_err(|e| ListenError::UnixSocketPermissions(e, path.to_path_buf()))?;
+            // Unlike renaming, linking never replaces a socket another server created meanwhile
+            fs::hard_link(&private, path).map_err(binding_error)?;
+            Ok(listener)
+        });
+    let _ = fs::remove_file(&private);
+    let _ = fs::remove_dir(&dir);
+    result
+}
+
+/// [systemd socket activation](https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html)
+#[cfg(unix)]
+mod systemd {
+    use std::path::Path;
+
+    use listenfd::ListenFd;
+
+    use super::{ListenError, Listener};
+
+    /// Take over the listening sockets passed to this process, if any.
+    ///
+    /// `LISTEN_FDS` and `LISTEN_PID` are removed from the environment,
+    /// so the sockets are only taken once, and the sockets are closed on `exec`.
+    pub fn take_listeners() -> Result<Vec<Listener>, ListenError> {
+        let mut fds = ListenFd::from_env();
+        (0..fds.len())
+            .filter_map(|idx| take_listener(&mut fds, idx).transpose())
+            .collect()
+    }
+
+    fn take_listener(fds: &mut ListenFd, idx: usize) -> Result<Option<Listener>, ListenError> {
+        // Each fails without taking the socket if it has another address family or type
+        if let Ok(tcp) = fds.take_tcp_listener(idx) {
+            return Ok(tcp.map(Listener::Tcp));
+        }
+        let Some(unix) = fds
+            .take_unix_listener(idx)
+            .map_err(ListenError::SystemdSocket)?
+        else {
+            return Ok(None);
+        };
+        let path = unix
+            .local_addr()
+            .ok()
+            .and_then(|addr| addr.as_pathname().map(Path::to_path_buf));
+        Ok(Some(Listener::Unix(unix, path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn binds_unix_socket() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("martin.sock");
+        let listeners = Listeners::new(
+            format!("{UNIX_SOCKET_PREFIX}{}", path.display()),
+            Some("600".parse().unwrap()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            listeners.url(),
+            Some(format!("{UNIX_SOCKET_PREFIX}{}", path.display()))
+        );
+        let mode = path.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // a socket another server still listens on is kept
+        let Err(err) = Listeners::new(
+            format!("{UNIX_SOCKET_PREFIX}{}", path.display()),
+            None,
+            None,
+        ) else {
+            panic!("a socket in use must not be replaced");
+        };
+        assert!(matches!(err, MartinError::BindingError(..)), "{err}");
+
+        // a socket left behind by a previous run is replaced
+        drop(listeners);
+        Listeners::new(
+            format!("{UNIX_SOCKET_PREFIX}{}", path.display()),
+            None,
+            None,
+        )
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_requires_tls_address() {
+        let dir = tempfile::tempdir().unwrap();
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+        std::fs::write(&cert, generated.cert.pem()).unwrap();
+        std::fs::write(&key, generated.signing_key.serialize_pem()).unwrap();
+        let socket = format!(
+            "{UNIX_SOCKET_PREFIX}{}",
+            dir.path().join("martin.sock").display()
+        );
+
+        let Err(err) = Listeners::new(
+            socket.clone(),
+            None,
+            Some(TlsConfig::new(cert.clone(), key.clone())),
+        ) else {
+            panic!("TLS must not be served on a Unix domain socket");
+        };
+        assert!(
+            matches!(err, MartinError::ListenError(ListenError::UnixSocketTls(_))),
+            "{err}"
+        );
+
+        let mut tls = TlsConfig::new(cert, key);
+        tls.listen_addresses = Some("127.0.0.1:0".to_owned());
+        let listeners = Listeners::new(socket, None, Some(tls)).unwrap();
+        assert!(matches!(listeners.http[..], [Listener::Unix(..)]));
+        assert!(matches!(
+            listeners.https.unwrap().0[..],
+            [Listener::Address(_)]
+        ));
+    }
+}
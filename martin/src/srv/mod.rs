@@ -16,6 +16,23 @@ mod server;
 pub use server::{__path_get_health, get_health};
 pub use server::{RESERVED_KEYWORDS, new_server, router};
 
+mod tls;
+pub use tls::TlsError;
+
+mod listen;
+pub use listen::{ListenError, UNIX_SOCKET_PREFIX};
+
+mod limits;
+pub use limits::{Admission, LimitError, RateLimits, limit_requests};
+
+mod access_log;
+pub use access_log::{AccessLog, CacheStatus, TileAccess, log_access};
+
+mod health;
+#[cfg(feature = "unstable-schemas")]
+pub use health::{__path_get_ready, get_ready};
+pub use health::{HealthChecker, HealthReport};
+
 mod admin;
 pub use admin::Catalog;
 #[cfg(feature = "unstable-schemas")]
@@ -25,12 +42,27 @@ pub use admin::{__path_get_catalog, get_catalog};
 pub(crate) mod tiles;
 #[cfg(all(feature = "_tiles", feature = "unstable-schemas"))]
 pub use tiles::content::{__path_get_tile, get_tile};
+#[cfg(all(feature = "_tiles", feature = "unstable-schemas"))]
+pub use tiles::content::{__path_get_tile_matrix_set_tile, get_tile_matrix_set_tile};
 #[cfg(feature = "_tiles")]
 pub use tiles::content::{DynTileSource, TileRequestHeaders};
 #[cfg(feature = "_tiles")]
 pub use tiles::metadata::merge_tilejson;
 #[cfg(all(feature = "_tiles", feature = "unstable-schemas"))]
 pub use tiles::metadata::{__path_get_source_info, get_source_info};
+#[cfg(all(feature = "mbtiles", feature = "unstable-schemas"))]
+pub use tiles::write::{__path_delete_tile, __path_put_tile, delete_tile, put_tile};
+
+#[cfg(feature = "ogcapi")]
+mod ogcapi;
+
+#[cfg(feature = "wmts")]
+mod wmts;
+
+#[cfg(feature = "mbtiles")]
+mod patches;
+#[cfg(all(feature = "mbtiles", feature = "unstable-schemas"))]
+pub use patches::{__path_get_patch_chain, __path_get_patch_file, get_patch_chain, get_patch_file};
 
 #[cfg(feature = "sprites")]
 mod sprites;
@@ -0,0 +1,463 @@
+//! OGC API - Features: a read-only `items` endpoint for vector tile sources.
+//!
+//! Features are decoded from the tiles covering the requested bounding box,
+//! so geometries are clipped to tile boundaries and quantized to the tile extent.
+
+use std::collections::HashSet;
+
+use actix_web::error::{ErrorBadRequest, ErrorNotFound};
+use actix_web::web::{Data, Path, Query};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, route};
+use futures::stream::{self, StreamExt as _, TryStreamExt as _};
+use martin_core::tiles::Tile;
+use martin_tile_utils::{
+    EARTH_CIRCUMFERENCE, Format, MAX_ZOOM, TileCoord, bbox_to_xyz, tile_bbox, webmercator_to_wgs84,
+};
+use mlt_core::geo_types::{Coord, Geometry, LineString, Polygon};
+use mlt_core::mvt::mvt_to_tile_layers;
+use mlt_core::{PropValue, TileLayer};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value, json};
+use tilejson::Bounds;
+
+use super::{GEOJSON, base_url, get_collection_source, has_items, source_bounds};
+use crate::config::file::srv::SrvConfig;
+use crate::srv::limits::Admission;
+use crate::srv::server::map_internal_error;
+use crate::srv::tiles::content::{self, DynTileSource, TileRequestHeaders};
+use crate::tile_source_manager::TileSourceManager;
+
+/// Number of features returned when the request has no `limit`
+const DEFAULT_LIMIT: usize = 10;
+/// Largest accepted `limit`
+const MAX_LIMIT: usize = 10_000;
+/// Largest number of tiles decoded to answer a single request
+const MAX_TILES: u64 = 16;
+/// Maximum number of tiles fetched concurrently for one request
+const MAX_CONCURRENT_TILE_FETCHES: usize = 8;
+/// Coordinates are rounded to 7 decimal places (about 1cm)
+const COORD_PRECISION: f64 = 10_000_000.0;
+/// Name of the property holding the vector tile layer of each feature
+const LAYER_PROPERTY: &str = "_layer";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ItemsQuery {
+    bbox: Option<String>,
+    #[serde(rename = "bbox-crs")]
+    bbox_crs: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    zoom: Option<u8>,
+}
+
+impl ItemsQuery {
+    fn bounds(&self) -> ActixResult<Option<Bounds>> {
+        if let Some(crs) = &self.bbox_crs
+            && crs != super::CRS84
+        {
+            return Err(ErrorBadRequest(format!(
+                "Unsupported bbox-crs {crs}, only {} is supported",
+                super::CRS84
+            )));
+        }
+        let Some(bbox) = &self.bbox else {
+            return Ok(None);
+        };
+        let values = bbox
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ErrorBadRequest(format!("Invalid bbox {bbox}: {e}")))?;
+        let [left, bottom, right, top] = values[..] else {
+            return Err(ErrorBadRequest(format!(
+                "Invalid bbox {bbox}: expected minx,miny,maxx,maxy"
+            )));
+        };
+        if left > right || bottom > top {
+            return Err(ErrorBadRequest(format!(
+                "Invalid bbox {bbox}: minimum is larger than maximum"
+            )));
+        }
+        Ok(Some(Bounds::new(left, bottom, right, top)))
+    }
+}
+
+#[route("/collections/{collection_id}/items", method = "GET", method = "HEAD")]
+pub async fn get_items(
+    req: HttpRequest,
+    path: Path<String>,
+    query: Query<ItemsQuery>,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let collection_id = path.as_str();
+    let src = get_collection_source(&manager, collection_id)?;
+    if !has_items(&src) {
+        return Err(ErrorNotFound(format!(
+            "Collection {collection_id} is not a WebMercatorQuad vector source and has no items"
+        )));
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or_default();
+    let requested = query.bounds()?;
+    let bbox = intersect(requested, source_bounds(&src));
+    let tj = src.get_tilejson();
+    let min_zoom = tj.minzoom.unwrap_or(0);
+    let max_zoom = tj.maxzoom.unwrap_or(MAX_ZOOM);
+    let zoom = if let Some(zoom) = query.zoom {
+        if !(min_zoom..=max_zoom).contains(&zoom) {
+            return Err(ErrorBadRequest(format!(
+                "Zoom {zoom} is outside of the {min_zoom}-{max_zoom} range of {collection_id}"
+            )));
+        }
+        if tile_count(bbox, zoom) > MAX_TILES {
+            return Err(ErrorBadRequest(format!(
+                "The bbox covers more than {MAX_TILES} tiles at zoom {zoom}, use a smaller bbox or zoom"
+            )));
+        }
+        zoom
+    } else {
+        pick_zoom(bbox, min_zoom, max_zoom)
+    };
+
+    // Tiles are always decoded as MVT, MLT sources are converted by the pre-cache processors
+    let headers = TileRequestHeaders {
+        accepted_formats: Some(vec![Format::Mvt]),
+        admission: Admission::from_request(&req),
+        ..TileRequestHeaders::default()
+    };
+    let dyn_src = DynTileSource::new(&manager, collection_id, Some(zoom), "", headers)?;
+    // The whole request counts once toward the source limits, however many tiles it decodes
+    let _permits = dyn_src.admit().await?;
+    let (min_x, min_y, max_x, max_y) =
+        bbox_to_xyz(bbox.left, bbox.bottom, bbox.right, bbox.top, zoom);
+    let coords = (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| TileCoord { z: zoom, x, y }))
+        .collect::<Vec<_>>();
+    let tiles: Vec<(TileCoord, Tile)> = stream::iter(coords)
+        .map(|xyz| {
+            let dyn_src = &dyn_src;
+            async move { Ok::<_, actix_web::Error>((xyz, dyn_src.get_tile_content(xyz).await?)) }
+        })
+        .buffered(MAX_CONCURRENT_TILE_FETCHES)
+        .try_collect()
+        .await?;
+
+    let mut collector = FeatureCollector::new(requested, offset, limit);
+    for (xyz, tile) in tiles {
+        if tile.data.is_empty() {
+            continue;
+        }
+        // Sources may store gzip or other compressed tiles, which the MVT decoder cannot read
+        let tile = content::decode(tile)?;
+        let layers = mvt_to_tile_layers(&tile.data).map_err(map_internal_error)?;
+        for layer in &layers {
+            collector.add_layer(xyz, layer);
+        }
+    }
+
+    let base = base_url(&req, &srv_config);
+    Ok(HttpResponse::Ok()
+        .content_type(GEOJSON)
+        .json(collector.finish(&base, collection_id, &req, zoom)))
+}
+
+/// Intersection of the requested bbox with the source bounds, clamped to the Web Mercator range
+fn intersect(requested: Option<Bounds>, source: Bounds) -> Bounds {
+    let world = Bounds::MAX_TILED;
+    let b = requested.unwrap_or(source);
+    let left = b.left.max(source.left).max(world.left);
+    let bottom = b.bottom.max(source.bottom).max(world.bottom);
+    let right = b.right.min(source.right).min(world.right);
+    let top = b.top.min(source.top).min(world.top);
+    // disjoint boxes collapse to a point, returning no or very few features
+    Bounds::new(left, bottom, right.max(left), top.max(bottom))
+}
+
+fn tile_count(bbox: Bounds, zoom: u8) -> u64 {
+    let (min_x, min_y, max_x, max_y) =
+        bbox_to_xyz(bbox.left, bbox.bottom, bbox.right, bbox.top, zoom);
+    u64::from(max_x - min_x + 1) * u64::from(max_y - min_y + 1)
+}
+
+/// The most detailed zoom at which the bbox is covered by at most [`MAX_TILES`] tiles
+fn pick_zoom(bbox: Bounds, min_zoom: u8, max_zoom: u8) -> u8 {
+    (min_zoom..=max_zoom)
+        .rev()
+        .find(|&zoom| tile_count(bbox, zoom) <= MAX_TILES)
+        .unwrap_or(min_zoom)
+}
+
+/// Collects features from decoded tiles, applying the bbox filter and paging.
+///
+/// Only a requested bbox filters features: the source bounds are only used to select tiles,
+/// because quantized features near the edge of the data may fall slightly outside of them.
+struct FeatureCollector {
+    bbox: Option<Bounds>,
+    offset: usize,
+    limit: usize,
+    matched: usize,
+    seen: HashSet<(String, u64)>,
+    features: Vec<Value>,
+}
+
+impl FeatureCollector {
+    fn new(bbox: Option<Bounds>, offset: usize, limit: usize) -> Self {
+        Self {
+            bbox,
+            offset,
+            limit,
+            matched: 0,
+            seen: HashSet::new(),
+            features: Vec::new(),
+        }
+    }
+
+    fn add_layer(&mut self, xyz: TileCoord, layer: &TileLayer) {
+        let projection = TileProjection::new(xyz, layer.extent().get());
+        for feature in layer.features() {
+            let geometry = feature.geometry();
+            // points in the tile buffer belong to the neighbouring tile
+            if let Geometry::Point(p) = geometry
+                && !projection.contains(p.0)
+            {
+                continue;
+            }
+            let mut extent = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+            let Some(geojson) = projection.geometry(geometry, &mut extent) else {
+                continue;
+            };
+            if let Some(bbox) = self.bbox
+                && (extent[0] > bbox.right
+                    || extent[2] < bbox.left
+                    || extent[1] > bbox.top
+                    || extent[3] < bbox.bottom)
+            {
+                continue;
+            }
+            // features crossing tile boundaries are only returned once, from the first tile
+            if let Some(id) = feature.id()
+                && !self.seen.insert((layer.name().to_owned(), id))
+            {
+                continue;
+            }
+
+            self.matched += 1;
+            if self.matched <= self.offset || self.features.len() >= self.limit {
+                continue;
+            }
+
+            let mut properties = Map::new();
+            properties.insert(LAYER_PROPERTY.to_owned(), layer.name().into());
+            for (name, value) in layer.property_names().iter().zip(feature.properties()) {
+                if let Some(value) = prop_to_json(value) {
+                    properties.insert(name.clone(), value);
+                }
+            }
+            let mut item = json!({
+                "type": "Feature",
+                "geometry": geojson,
+                "properties": properties,
+            });
+            if let Some(id) = feature.id() {
+                item["id"] = id.into();
+            }
+            self.features.push(item);
+        }
+    }
+
+    fn finish(self, base: &str, collection_id: &str, req: &HttpRequest, zoom: u8) -> Value {
+        let href = format!("{base}/collections/{collection_id}/items");
+        let self_href = if req.query_string().is_empty() {
+            href.clone()
+        } else {
+            format!("{href}?{}", req.query_string())
+        };
+        let mut links = vec![json!({"href": self_href, "rel": "self", "type": GEOJSON})];
+        let returned = self.features.len();
+        if self.offset + returned < self.matched {
+            let mut query = url::form_urlencoded::Serializer::new(String::new());
+            if let Some(b) = self.bbox {
+                query.append_pair(
+                    "bbox",
+                    &format!("{},{},{},{}", b.left, b.bottom, b.right, b.top),
+                );
+            }
+            query
+                .append_pair("zoom", &zoom.to_string())
+                .append_pair("limit", &self.limit.to_string())
+                .append_pair("offset", &(self.offset + returned).to_string());
+            links.push(json!({
+                "href": format!("{href}?{}", query.finish()),
+                "rel": "next",
+                "type": GEOJSON,
+            }));
+        }
+
+        json!({
+            "type": "FeatureCollection",
+            "numberMatched": self.matched,
+            "numberReturned": returned,
+            "features": self.features,
+            "links": links,
+        })
+    }
+}
+
+/// Converts tile-local integer coordinates to longitude/latitude
+struct TileProjection {
+    extent: i32,
+    min_x: f64,
+    max_y: f64,
+    scale: f64,
+}
+
+impl TileProjection {
+    fn new(xyz: TileCoord, extent: u32) -> Self {
+        let tile_length = EARTH_CIRCUMFERENCE / f64::from(1_u32 << xyz.z);
+        let [min_x, _, _, max_y] = tile_bbox(xyz.x, xyz.y, tile_length);
+        Self {
+            extent: i32::try_from(extent).unwrap_or(i32::MAX),
+            min_x,
+            max_y,
+            scale: tile_length / f64::from(extent),
+        }
+    }
+
+    fn contains(&self, c: Coord<i32>) -> bool {
+        (0..self.extent).contains(&c.x) && (0..self.extent).contains(&c.y)
+    }
+
+    fn coord(&self, c: Coord<i32>, extent: &mut [f64; 4]) -> Value {
+        let (lng, lat) = webmercator_to_wgs84(
+            f64::from(c.x).mul_add(self.scale, self.min_x),
+            f64::from(c.y).mul_add(-self.scale, self.max_y),
+        );
+        let (lng, lat) = (round(lng), round(lat));
+        extent[0] = extent[0].min(lng);
+        extent[1] = extent[1].min(lat);
+        extent[2] = extent[2].max(lng);
+        extent[3] = extent[3].max(lat);
+        json!([lng, lat])
+    }
+
+    fn line(&self, line: &LineString<i32>, extent: &mut [f64; 4]) -> Value {
+        Value::Array(line.0.iter().map(|c| self.coord(*c, extent)).collect())
+    }
+
+    fn polygon(&self, polygon: &Polygon<i32>, extent: &mut [f64; 4]) -> Value {
+        Value::Array(
+            std::iter::once(polygon.exterior())
+                .chain(polygon.interiors())
+                .map(|ring| self.line(ring, extent))
+                .collect(),
+        )
+    }
+
+    /// `GeoJSON` geometry object, or `None` for geometry types that cannot appear in vector tiles
+    fn geometry(&self, geometry: &Geometry<i32>, extent: &mut [f64; 4]) -> Option<Value> {
+        let (kind, coordinates) = match geometry {
+            Geometry::Point(p) => ("Point", self.coord(p.0, extent)),
+            Geometry::MultiPoint(mp) => (
+                "MultiPoint",
+                Value::Array(mp.0.iter().map(|p| self.coord(p.0, extent)).collect()),
+            ),
+            Geometry::LineString(l) => ("LineString", self.line(l, extent)),
+            Geometry::MultiLineString(ml) => (
+                "MultiLineString",
+                Value::Array(ml.0.iter().map(|l| self.line(l, extent)).collect()),
+            ),
+            Geometry::Polygon(p) => ("Polygon", self.polygon(p, extent)),
+            Geometry::MultiPolygon(mp) => (
+                "MultiPolygon",
+                Value::Array(mp.0.iter().map(|p| self.polygon(p, extent)).collect()),
+            ),
+            _ => return None,
+        };
+        Some(json!({"type": kind, "coordinates": coordinates}))
+    }
+}
+
+fn round(v: f64) -> f64 {
+    (v * COORD_PRECISION).round() / COORD_PRECISION
+}
+
+fn prop_to_json(value: &PropValue) -> Option<Value> {
+    Some(match value {
+        PropValue::Bool(v) => Value::Bool((*v)?),
+        PropValue::I8(v) => (*v)?.into(),
+        PropValue::U8(v) => (*v)?.into(),
+        PropValue::I32(v) => (*v)?.into(),
+        PropValue::U32(v) => (*v)?.into(),
+        PropValue::I64(v) => (*v)?.into(),
+        PropValue::U64(v) => (*v)?.into(),
+        PropValue::F32(v) => Number::from_f64(f64::from((*v)?)).map_or(Value::Null, Value::Number),
+        PropValue::F64(v) => Number::from_f64((*v)?).map_or(Value::Null, Value::Number),
+        PropValue::Str(v) => Value::String(v.clone()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bbox() {
+        let q = ItemsQuery {
+            bbox: Some("-10, 40.5,10,50".to_owned()),
+            ..ItemsQuery::default()
+        };
+        assert_eq!(
+            q.bounds().unwrap(),
+            Some(Bounds::new(-10.0, 40.5, 10.0, 50.0))
+        );
+
+        for bad in ["1,2,3", "a,b,c,d", "10,0,0,10"] {
+            let q = ItemsQuery {
+                bbox: Some(bad.to_owned()),
+                ..ItemsQuery::default()
+            };
+            q.bounds().unwrap_err();
+        }
+
+        let q = ItemsQuery {
+            bbox_crs: Some("http://www.opengis.net/def/crs/EPSG/0/3857".to_owned()),
+            ..ItemsQuery::default()
+        };
+        q.bounds().unwrap_err();
+    }
+
+    #[test]
+    fn zoom_selection() {
+        let world = Bounds::MAX_TILED;
+        assert_eq!(pick_zoom(world, 0, 14), 2);
+        assert_eq!(pick_zoom(world, 5, 14), 5);
+        let paris = Bounds::new(2.3, 48.8, 2.4, 48.9);
+        assert_eq!(pick_zoom(paris, 0, 6), 6);
+        assert_eq!(tile_count(paris, 6), 1);
+    }
+
+    #[test]
+    fn clamp_to_source() {
+        let src = Bounds::new(-10.0, -10.0, 10.0, 10.0);
+        assert_eq!(intersect(None, src), src);
+        assert_eq!(
+            intersect(Some(Bounds::new(0.0, 0.0, 50.0, 50.0)), src),
+            Bounds::new(0.0, 0.0, 10.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn project_tile_coords() {
+        let proj = TileProjection::new(TileCoord { z: 1, x: 1, y: 0 }, 4096);
+        let mut extent = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+        let value = proj.coord(Coord { x: 0, y: 4096 }, &mut extent);
+        assert_eq!(value, json!([0.0, 0.0]));
+        let value = proj.coord(Coord { x: 4096, y: 0 }, &mut extent);
+        assert_eq!(value, json!([180.0, 85.051_128_8]));
+        assert!(proj.contains(Coord { x: 0, y: 0 }));
+        assert!(!proj.contains(Coord { x: -1, y: 10 }));
+        assert!(!proj.contains(Coord { x: 4096, y: 10 }));
+    }
+}
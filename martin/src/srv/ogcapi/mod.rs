@@ -0,0 +1,281 @@
+//! [OGC API](https://ogcapi.ogc.org/) endpoints mounted under `/ogc`.
+//!
+//! Every tile source is exposed as a collection with a tileset in its tile matrix set
+//! ([OGC API - Tiles](https://docs.ogc.org/is/20-057/20-057.html)).
+//! `WebMercatorQuad` vector sources additionally expose a read-only `items` endpoint
+//! ([OGC API - Features](https://docs.ogc.org/is/17-069r4/17-069r4.html))
+//! that decodes tiles to answer bounding box queries.
+
+mod features;
+mod tiles;
+
+use actix_web::web::{Data, Path};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, Scope, route, web};
+use martin_core::tiles::BoxedSource;
+use martin_tile_utils::Format;
+use martin_tile_utils::tms::WEB_MERCATOR_QUAD;
+use serde::Serialize;
+use tilejson::Bounds;
+
+use crate::config::file::srv::SrvConfig;
+use crate::srv::server::path_prefix;
+use crate::tile_source_manager::TileSourceManager;
+
+const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+const JSON: &str = "application/json";
+const GEOJSON: &str = "application/geo+json";
+
+const CONFORMANCE: &[&str] = &[
+    "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/json",
+    "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/landing-page",
+    "http://www.opengis.net/spec/ogcapi-common-2/1.0/conf/collections",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tileset",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tilesets-list",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/geodata-tilesets",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/mvt",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/png",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/jpeg",
+    "http://www.opengis.net/spec/tms/2.0/conf/json-tilematrixset",
+    "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/geojson",
+];
+
+/// All OGC API routes, to be registered before the generic `/{source_ids}/...` routes.
+pub fn scope() -> Scope {
+    web::scope("/ogc")
+        .service(get_landing_page)
+        .service(get_conformance)
+        .service(get_collections)
+        .service(get_collection)
+        .service(tiles::get_tilesets)
+        .service(tiles::get_tileset)
+        .service(tiles::get_tile)
+        .service(tiles::get_tile_matrix_sets)
+        .service(tiles::get_tile_matrix_set)
+        .service(features::get_items)
+}
+
+#[derive(Debug, Serialize)]
+struct Link {
+    href: String,
+    rel: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    templated: bool,
+}
+
+impl Link {
+    fn new(href: String, rel: &str, media_type: &str) -> Self {
+        Self {
+            href,
+            rel: rel.to_owned(),
+            media_type: Some(media_type.to_owned()),
+            title: None,
+            templated: false,
+        }
+    }
+
+    fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LandingPage {
+    title: &'static str,
+    description: &'static str,
+    links: Vec<Link>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Conformance {
+    conforms_to: &'static [&'static str],
+}
+
+#[derive(Debug, Serialize)]
+struct Collections {
+    links: Vec<Link>,
+    collections: Vec<Collection>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Collection {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attribution: Option<String>,
+    extent: Extent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_type: Option<&'static str>,
+    crs: Vec<&'static str>,
+    data_type: &'static str,
+    links: Vec<Link>,
+}
+
+#[derive(Debug, Serialize)]
+struct Extent {
+    spatial: SpatialExtent,
+}
+
+#[derive(Debug, Serialize)]
+struct SpatialExtent {
+    bbox: Vec<[f64; 4]>,
+    crs: &'static str,
+}
+
+/// Absolute URL of the `/ogc` root as seen by the client
+fn base_url(req: &HttpRequest, srv_config: &SrvConfig) -> String {
+    let prefix = path_prefix(req, srv_config);
+    let info = req.connection_info();
+    format!("{}://{}{prefix}/ogc", info.scheme(), info.host())
+}
+
+/// Vector tile sources are served as feature collections, everything else as maps
+fn is_vector(src: &BoxedSource) -> bool {
+    matches!(src.get_tile_info().format, Format::Mvt | Format::Mlt)
+}
+
+/// Features are decoded from `WebMercatorQuad` vector tiles, so other tile matrix sets have no items
+fn has_items(src: &BoxedSource) -> bool {
+    is_vector(src) && src.get_tile_matrix_set().id == WEB_MERCATOR_QUAD
+}
+
+/// Data type of a tileset as defined by OGC API - Tiles
+fn data_type(src: &BoxedSource) -> &'static str {
+    if is_vector(src) { "vector" } else { "map" }
+}
+
+/// Source bounds, defaulting to the whole world
+fn source_bounds(src: &BoxedSource) -> Bounds {
+    src.get_tilejson().bounds.unwrap_or_default()
+}
+
+fn get_collection_source(manager: &TileSourceManager, id: &str) -> ActixResult<BoxedSource> {
+    Ok(manager.tile_sources().get_source(id)?.0)
+}
+
+fn collection_info(base: &str, id: &str, src: &BoxedSource) -> Collection {
+    let tj = src.get_tilejson();
+    let bounds = source_bounds(src);
+    let vector = is_vector(src);
+    let href = format!("{base}/collections/{id}");
+
+    let mut links = vec![
+        Link::new(href.clone(), "self", JSON).title(format!("Collection {id}")),
+        Link::new(
+            format!("{href}/tiles"),
+            if vector {
+                "http://www.opengis.net/def/rel/ogc/1.0/tilesets-vector"
+            } else {
+                "http://www.opengis.net/def/rel/ogc/1.0/tilesets-map"
+            },
+            JSON,
+        )
+        .title("Tilesets"),
+    ];
+    if has_items(src) {
+        links.push(Link::new(format!("{href}/items"), "items", GEOJSON).title("Features"));
+    }
+
+    Collection {
+        id: id.to_owned(),
+        title: tj.name.clone(),
+        description: tj.description.clone(),
+        attribution: tj.attribution.clone(),
+        extent: Extent {
+            spatial: SpatialExtent {
+                bbox: vec![[bounds.left, bounds.bottom, bounds.right, bounds.top]],
+                crs: CRS84,
+            },
+        },
+        item_type: has_items(src).then_some("feature"),
+        crs: vec![CRS84],
+        data_type: data_type(src),
+        links,
+    }
+}
+
+#[route("", method = "GET", method = "HEAD")]
+async fn get_landing_page(req: HttpRequest, srv_config: Data<SrvConfig>) -> HttpResponse {
+    let base = base_url(&req, &srv_config);
+    HttpResponse::Ok().json(LandingPage {
+        title: "Martin",
+        description: "Tiles and features served by Martin",
+        links: vec![
+            Link::new(base.clone(), "self", JSON).title("Landing page"),
+            Link::new(
+                format!("{base}/conformance"),
+                "http://www.opengis.net/def/rel/ogc/1.0/conformance",
+                JSON,
+            )
+            .title("Conformance classes"),
+            Link::new(
+                format!("{base}/collections"),
+                "http://www.opengis.net/def/rel/ogc/1.0/data",
+                JSON,
+            )
+            .title("Collections"),
+            Link::new(
+                format!("{base}/tileMatrixSets"),
+                "http://www.opengis.net/def/rel/ogc/1.0/tiling-schemes",
+                JSON,
+            )
+            .title("Tile matrix sets"),
+        ],
+    })
+}
+
+#[route("/conformance", method = "GET", method = "HEAD")]
+async fn get_conformance() -> HttpResponse {
+    HttpResponse::Ok().json(Conformance {
+        conforms_to: CONFORMANCE,
+    })
+}
+
+#[route("/collections", method = "GET", method = "HEAD")]
+async fn get_collections(
+    req: HttpRequest,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let base = base_url(&req, &srv_config);
+    let sources = manager.tile_sources();
+    let mut ids = sources.source_names();
+    ids.sort_unstable();
+
+    let mut collections = Vec::with_capacity(ids.len());
+    for id in ids {
+        // a source may have been removed by a concurrent reload
+        if let Ok((src, _)) = sources.get_source(&id) {
+            collections.push(collection_info(&base, &id, &src));
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(Collections {
+        links: vec![Link::new(format!("{base}/collections"), "self", JSON)],
+        collections,
+    }))
+}
+
+#[route("/collections/{collection_id}", method = "GET", method = "HEAD")]
+async fn get_collection(
+    req: HttpRequest,
+    path: Path<String>,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let src = get_collection_source(&manager, &path)?;
+    let base = base_url(&req, &srv_config);
+    Ok(HttpResponse::Ok().json(collection_info(&base, &path, &src)))
+}
@@ -0,0 +1,275 @@
+//! OGC API - Tiles: tilesets, tiles, and tile matrix sets.
+
+use actix_web::error::ErrorNotFound;
+use actix_web::web::{Data, Path};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, route};
+use martin_core::tiles::BoxedSource;
+use martin_tile_utils::TileCoord;
+use martin_tile_utils::tms::TileMatrixSet;
+use serde::{Deserialize, Serialize};
+
+use super::{JSON, Link, base_url, data_type, get_collection_source, source_bounds};
+use crate::config::file::ProcessConfig;
+use crate::config::file::srv::SrvConfig;
+use crate::srv::tiles::content::{DynTileSource, TileRequestHeaders};
+use crate::srv::tiles::metadata::source_zoom_range;
+use crate::tile_source_manager::TileSourceManager;
+
+const TILING_SCHEME_REL: &str = "http://www.opengis.net/def/rel/ogc/1.0/tiling-scheme";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TileSet {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    data_type: &'static str,
+    crs: String,
+    #[serde(rename = "tileMatrixSetURI", skip_serializing_if = "Option::is_none")]
+    tile_matrix_set_uri: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tile_matrix_set_limits: Vec<TileMatrixLimits>,
+    links: Vec<Link>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TileMatrixLimits {
+    tile_matrix: String,
+    min_tile_row: u32,
+    max_tile_row: u32,
+    min_tile_col: u32,
+    max_tile_col: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct TileSets {
+    tilesets: Vec<TileSet>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TileMatrixSets {
+    tile_matrix_sets: Vec<TileMatrixSetRef>,
+}
+
+#[derive(Debug, Serialize)]
+struct TileMatrixSetRef {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+    links: Vec<Link>,
+}
+
+#[derive(Deserialize)]
+pub struct TilesetRequest {
+    collection_id: String,
+    tms_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct OgcTileRequest {
+    collection_id: String,
+    tms_id: String,
+    tile_matrix: String,
+    tile_row: u32,
+    tile_col: u32,
+}
+
+/// Look up a built-in tile matrix set, or one used by any of the sources
+fn get_tms(manager: &TileSourceManager, tms_id: &str) -> ActixResult<TileMatrixSet> {
+    manager
+        .tile_sources()
+        .tile_matrix_sets()
+        .into_iter()
+        .find(|tms| tms.id == tms_id)
+        .ok_or_else(|| ErrorNotFound(format!("Tile matrix set {tms_id} does not exist")))
+}
+
+/// Each source is served in exactly one tile matrix set
+fn check_source_tms(collection_id: &str, src: &BoxedSource, tms_id: &str) -> ActixResult<()> {
+    let tms = src.get_tile_matrix_set();
+    if tms.id == tms_id {
+        Ok(())
+    } else {
+        Err(ErrorNotFound(format!(
+            "Collection {collection_id} is only available in the {} tile matrix set",
+            tms.id
+        )))
+    }
+}
+
+fn tile_matrix_limits(
+    src: &BoxedSource,
+    pc: &ProcessConfig,
+    tms: &TileMatrixSet,
+) -> Vec<TileMatrixLimits> {
+    let b = source_bounds(src);
+    let (min_zoom, max_zoom) = source_zoom_range(src, pc);
+    tms.limits([b.left, b.bottom, b.right, b.top], min_zoom..=max_zoom)
+        .into_iter()
+        .filter_map(|rect| {
+            Some(TileMatrixLimits {
+                tile_matrix: tms.tile_matrix_at(rect.zoom)?.id.clone(),
+                min_tile_row: rect.min_y,
+                max_tile_row: rect.max_y,
+                min_tile_col: rect.min_x,
+                max_tile_col: rect.max_x,
+            })
+        })
+        .collect()
+}
+
+fn tileset(
+    base: &str,
+    collection_id: &str,
+    src: &BoxedSource,
+    pc: &ProcessConfig,
+    tms: &TileMatrixSet,
+    with_limits: bool,
+) -> TileSet {
+    let href = format!("{base}/collections/{collection_id}/tiles/{}", tms.id);
+    let tms_href = format!("{base}/tileMatrixSets/{}", tms.id);
+    let mut links = vec![
+        Link::new(href.clone(), "self", JSON).title(format!("{} tileset", tms.id)),
+        Link::new(tms_href, TILING_SCHEME_REL, JSON).title(format!("{} definition", tms.id)),
+    ];
+    if with_limits {
+        let mut tile_link = Link::new(
+            format!("{href}/{{tileMatrix}}/{{tileRow}}/{{tileCol}}"),
+            "item",
+            src.get_tile_info().format.content_type(),
+        )
+        .title("Tiles");
+        tile_link.templated = true;
+        links.push(tile_link);
+    }
+
+    TileSet {
+        title: src.get_tilejson().name.clone(),
+        data_type: data_type(src),
+        crs: tms.crs.clone(),
+        tile_matrix_set_uri: tms.uri.clone(),
+        tile_matrix_set_limits: if with_limits {
+            tile_matrix_limits(src, pc, tms)
+        } else {
+            Vec::new()
+        },
+        links,
+    }
+}
+
+#[route("/collections/{collection_id}/tiles", method = "GET", method = "HEAD")]
+pub async fn get_tilesets(
+    req: HttpRequest,
+    path: Path<String>,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let (src, pc) = manager.tile_sources().get_source(&path)?;
+    let base = base_url(&req, &srv_config);
+    let tms = src.get_tile_matrix_set();
+    Ok(HttpResponse::Ok().json(TileSets {
+        tilesets: vec![tileset(&base, &path, &src, &pc, tms, false)],
+    }))
+}
+
+#[route(
+    "/collections/{collection_id}/tiles/{tms_id}",
+    method = "GET",
+    method = "HEAD"
+)]
+pub async fn get_tileset(
+    req: HttpRequest,
+    path: Path<TilesetRequest>,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let (src, pc) = manager.tile_sources().get_source(&path.collection_id)?;
+    check_source_tms(&path.collection_id, &src, &path.tms_id)?;
+    let base = base_url(&req, &srv_config);
+    let tms = src.get_tile_matrix_set();
+    Ok(HttpResponse::Ok().json(tileset(&base, &path.collection_id, &src, &pc, tms, true)))
+}
+
+/// Serve a tile addressed by OGC tile matrix, row and column.
+/// The position of the tile matrix in the tile matrix set is the `z` of the source,
+/// so in `WebMercatorQuad` these are the same as `z`, `y` and `x`.
+#[route(
+    "/collections/{collection_id}/tiles/{tms_id}/{tile_matrix}/{tile_row}/{tile_col}",
+    method = "GET",
+    method = "HEAD"
+)]
+pub async fn get_tile(
+    req: HttpRequest,
+    path: Path<OgcTileRequest>,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    // Composite ids are not collections, so reject them before resolving sources
+    let collection = get_collection_source(&manager, &path.collection_id)?;
+    check_source_tms(&path.collection_id, &collection, &path.tms_id)?;
+    let tms = collection.get_tile_matrix_set();
+    let xyz = tms
+        .tile_matrices
+        .iter()
+        .position(|m| m.id == path.tile_matrix)
+        .and_then(|z| u8::try_from(z).ok())
+        .map(|z| TileCoord {
+            z,
+            x: path.tile_col,
+            y: path.tile_row,
+        })
+        .filter(|xyz| tms.contains(*xyz))
+        .ok_or_else(|| {
+            ErrorNotFound(format!(
+                "Tile {}/{}/{} is outside of the tile matrix set",
+                path.tile_matrix, path.tile_row, path.tile_col
+            ))
+        })?;
+
+    let headers = TileRequestHeaders::from_request(&req, &srv_config)?;
+    let src = DynTileSource::new(
+        &manager,
+        &path.collection_id,
+        Some(xyz.z),
+        req.query_string(),
+        headers,
+    )?;
+    src.get_http_response(xyz).await
+}
+
+#[route("/tileMatrixSets", method = "GET", method = "HEAD")]
+pub async fn get_tile_matrix_sets(
+    req: HttpRequest,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> HttpResponse {
+    let base = base_url(&req, &srv_config);
+    HttpResponse::Ok().json(TileMatrixSets {
+        tile_matrix_sets: manager
+            .tile_sources()
+            .tile_matrix_sets()
+            .into_iter()
+            .map(|tms| TileMatrixSetRef {
+                links: vec![Link::new(
+                    format!("{base}/tileMatrixSets/{}", tms.id),
+                    TILING_SCHEME_REL,
+                    JSON,
+                )],
+                id: tms.id,
+                title: tms.title,
+                uri: tms.uri,
+            })
+            .collect(),
+    })
+}
+
+#[route("/tileMatrixSets/{tms_id}", method = "GET", method = "HEAD")]
+pub async fn get_tile_matrix_set(
+    path: Path<String>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(get_tms(&manager, &path)?))
+}
@@ -0,0 +1,115 @@
+use actix_files::NamedFile;
+use actix_web::http::header::{ContentDisposition, ContentType};
+use actix_web::web::{Data, Path};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, route};
+use martin_core::tiles::mbtiles::PatchFeeds;
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::srv::server::map_internal_error;
+
+const SQLITE_MIME: &str = "application/vnd.sqlite3";
+
+#[derive(Deserialize, Debug)]
+#[cfg_attr(feature = "unstable-schemas", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "unstable-schemas", into_params(parameter_in = Path))]
+struct PatchChainRequest {
+    /// ID of the `MBTiles` source
+    source_id: String,
+    /// `agg_tiles_hash` of the client's copy of the source
+    agg_tiles_hash: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[cfg_attr(feature = "unstable-schemas", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "unstable-schemas", into_params(parameter_in = Path))]
+struct PatchFileRequest {
+    /// ID of the `MBTiles` source
+    source_id: String,
+    /// File name of the patch, as listed in the patch chain
+    file: String,
+}
+
+/// List the patches which update a copy of an `MBTiles` source to the served version.
+#[cfg_attr(
+    feature = "unstable-schemas",
+    utoipa::path(
+        get,
+        path = "/patch/{source_id}/{agg_tiles_hash}",
+        params(PatchChainRequest),
+        responses(
+            (status = 200, description = "Patches to apply in order, empty if the copy is up to date", content_type = "application/json"),
+            (status = 404, description = "No patches for this source, or no chain of patches from this hash"),
+        ),
+    )
+)]
+#[route("/patch/{source_id}/{agg_tiles_hash}", method = "GET", method = "HEAD")]
+#[hotpath::measure]
+#[instrument(level = "debug", skip_all, fields(source.id = %path.source_id))]
+pub async fn get_patch_chain(
+    path: Path<PatchChainRequest>,
+    feeds: Data<PatchFeeds>,
+) -> ActixResult<HttpResponse> {
+    let Some(feed) = feeds.get(&path.source_id) else {
+        return Ok(not_found("No patches are available for this source"));
+    };
+    let chain = feed
+        .chain(&path.agg_tiles_hash)
+        .await
+        .map_err(map_internal_error)?;
+    Ok(match chain {
+        Some(chain) => HttpResponse::Ok().json(chain),
+        None => {
+            not_found("No chain of patches leads from this agg_tiles_hash to the served version")
+        }
+    })
+}
+
+/// Download a patch file of an `MBTiles` source.
+#[cfg_attr(
+    feature = "unstable-schemas",
+    utoipa::path(
+        get,
+        path = "/patch/{source_id}/file/{file}",
+        params(PatchFileRequest),
+        responses(
+            (status = 200, description = "The patch file", content_type = "application/vnd.sqlite3"),
+            (status = 404, description = "No such patch file"),
+        ),
+    )
+)]
+#[route("/patch/{source_id}/file/{file}", method = "GET", method = "HEAD")]
+#[hotpath::measure]
+#[instrument(level = "debug", skip_all, fields(source.id = %path.source_id))]
+pub async fn get_patch_file(
+    req: HttpRequest,
+    path: Path<PatchFileRequest>,
+    feeds: Data<PatchFeeds>,
+) -> ActixResult<HttpResponse> {
+    let Some(feed) = feeds.get(&path.source_id) else {
+        return Ok(not_found("No patches are available for this source"));
+    };
+    // Only files listed in the index are served, so `file` cannot escape the patches directory
+    let Some(file_path) = feed
+        .patch_path(&path.file)
+        .await
+        .map_err(map_internal_error)?
+    else {
+        return Ok(not_found("No such patch file"));
+    };
+    let Ok(file) = NamedFile::open_async(&file_path).await else {
+        // the file was likely deleted after the index was refreshed
+        return Ok(not_found("No such patch file"));
+    };
+    // Streams the file, and handles range and conditional requests
+    Ok(file
+        .set_content_type(SQLITE_MIME.parse().expect("the SQLite MIME type is valid"))
+        .set_content_disposition(ContentDisposition::attachment(path.file.clone()))
+        .into_response(&req))
+}
+
+fn not_found(message: &'static str) -> HttpResponse {
+    HttpResponse::NotFound()
+        .content_type(ContentType::plaintext())
+        .body(message)
+}
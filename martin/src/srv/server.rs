@@ -16,6 +16,7 @@ use crate::config::args::WebUiMode;
 #[cfg(feature = "_catalog")]
 use crate::config::file::ServerState;
 use crate::config::file::srv::{DEFAULT_KEEP_ALIVE, DEFAULT_LISTEN_ADDRESSES, SrvConfig};
+use crate::srv::access_log::{AccessLog, log_access};
 #[cfg(any(not(feature = "webui"), docsrs))]
 use crate::srv::admin::get_index_no_ui;
 use crate::srv::admin::{Catalog, get_catalog};
@@ -23,6 +24,13 @@ use crate::srv::admin::{Catalog, get_catalog};
 use crate::srv::admin::{get_index_ui_disabled, webui};
 #[cfg(feature = "fonts")]
 use crate::srv::fonts;
+use crate::srv::health::{HealthChecker, get_ready, get_status};
+use crate::srv::limits::{RateLimits, limit_requests};
+use crate::srv::listen::{ListenError, Listener, Listeners};
+#[cfg(feature = "ogcapi")]
+use crate::srv::ogcapi;
+#[cfg(feature = "mbtiles")]
+use crate::srv::patches;
 #[cfg(feature = "sprites")]
 use crate::srv::sprites;
 #[cfg(feature = "styles")]
@@ -33,14 +41,16 @@ use crate::srv::styles_rendering;
 use crate::srv::styles_static;
 #[cfg(feature = "_tiles")]
 use crate::srv::tiles;
+#[cfg(feature = "wmts")]
+use crate::srv::wmts;
 use crate::{MartinError, MartinResult};
 
 /// List of keywords that cannot be used as source IDs. Some of these are reserved for future use.
 /// Reserved keywords must never end in a "dot number" (e.g. ".1").
 /// This list is documented in the `docs/content/using.md` file, which should be kept in sync.
 pub const RESERVED_KEYWORDS: &[&str] = &[
-    "_", "catalog", "config", "font", "health", "help", "index", "manifest", "metrics", "refresh",
-    "reload", "sprite", "status",
+    "_", "catalog", "config", "font", "health", "help", "index", "manifest", "metrics", "ogc",
+    "patch", "ready", "refresh", "reload", "sprite", "status", "wmts",
 ];
 
 #[cfg(any(feature = "_tiles", feature = "fonts", feature = "sprites"))]
@@ -49,6 +59,29 @@ pub fn map_internal_error<T: std::fmt::Display>(e: T) -> actix_web::Error {
     actix_web::error::ErrorInternalServerError(e.to_string())
 }
 
+/// Resolve the URL path prefix under which Martin is publicly served.
+///
+/// Returns an empty string when no prefix applies, otherwise a leading-slash
+/// path with no trailing slash (e.g. `/tiles`).
+///
+/// Note: `X-Rewrite-URL` is intentionally not honored here. Unlike the
+/// `TileJSON` case where the header's full path can be used directly, for
+/// styles, OGC API and WMTS documents the header would contain the full request path
+/// (e.g. `/tiles/style/foo/style.json`), which isn't a usable prefix.
+#[cfg(any(feature = "styles", feature = "ogcapi", feature = "wmts"))]
+pub(crate) fn path_prefix(req: &actix_web::HttpRequest, srv_config: &SrvConfig) -> String {
+    let Some(prefix) = srv_config.public_path_prefix() else {
+        return req
+            .headers()
+            .get("X-Forwarded-Prefix")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<actix_web::http::Uri>().ok())
+            .map(|v| v.path().trim_end_matches('/').to_owned())
+            .unwrap_or_default();
+    };
+    prefix.to_owned()
+}
+
 /// Helper struct for debounced warning messages in redirect handlers.
 /// Ensures warnings are logged no more than once per hour to avoid log spam.
 #[cfg(feature = "_catalog")]
@@ -105,7 +138,7 @@ pub fn router(cfg: &mut web::ServiceConfig, usr_cfg: &SrvConfig) {
                 usr_cfg,
             );
         }));
-        cfg.service(get_health);
+        cfg.service(get_health).service(get_ready);
     } else {
         register_services(
             cfg,
@@ -120,7 +153,26 @@ fn register_services(
     cfg: &mut web::ServiceConfig,
     #[cfg(all(feature = "webui", not(docsrs)))] usr_cfg: &SrvConfig,
 ) {
-    cfg.service(get_health).service(get_catalog);
+    cfg.service(get_health)
+        .service(get_ready)
+        .service(get_status)
+        .service(get_catalog);
+
+    // OGC API and WMTS paths overlap with the generic `/{source_ids}/{z}/{x}/{y}` pattern,
+    // so they must be registered before the tile routes
+    #[cfg(feature = "ogcapi")]
+    cfg.service(ogcapi::scope());
+    #[cfg(feature = "wmts")]
+    cfg.service(wmts::scope());
+
+    // `/patch/{source_id}/file/{file}` would also match `/{source_ids}/{z}/{x}/{y}`
+    #[cfg(feature = "mbtiles")]
+    cfg.service(patches::get_patch_file)
+        .service(patches::get_patch_chain);
+    // Tile writes share the tile path, but only match `PUT` and `DELETE` requests
+    #[cfg(feature = "mbtiles")]
+    cfg.service(tiles::write::put_tile)
+        .service(tiles::write::delete_tile);
 
     #[cfg(feature = "_tiles")]
     {
@@ -132,6 +184,10 @@ fn register_services(
 
         // Register /tiles/ prefix redirect after main tile route
         cfg.service(tiles::content::redirect_tiles);
+
+        // `/{source_ids}/{tms_id}/{z}/{x}/{y}` would also match `/tiles/{source_ids}/{z}/{x}/{y}`,
+        // so it must come after the redirect
+        cfg.service(tiles::content::get_tile_matrix_set_tile);
     }
 
     #[cfg(feature = "sprites")]
@@ -185,8 +241,9 @@ fn cache_control_middleware(value: Option<HeaderValue>) -> middleware::Condition
     middleware::Condition::new(enabled, DefaultHeaders::new().add((CACHE_CONTROL, value)))
 }
 
-/// Create a future for an Actix web server together with the listening address.
+/// Create a future for an Actix web server together with the base URL it listens on.
 #[hotpath::measure]
+#[expect(clippy::too_many_lines)]
 pub fn new_server(
     config: SrvConfig,
     #[cfg(feature = "_catalog")] state: ServerState,
@@ -194,6 +251,17 @@ pub fn new_server(
     let cache_control = config.cache_control_header();
     #[cfg(feature = "metrics")]
     let prometheus = {
+        let metrics_config = config
+            .observability
+            .clone()
+            .unwrap_or_default()
+            .metrics
+            .unwrap_or_default();
+        martin_core::metrics::set_max_source_labels(
+            metrics_config
+                .max_source_labels
+                .unwrap_or(martin_core::metrics::DEFAULT_MAX_SOURCE_LABELS),
+        );
         let metrics_endpoint = if let Some(prefix) = &config.route_prefix {
             format!("{prefix}/_/metrics")
         } else {
@@ -204,15 +272,7 @@ pub fn new_server(
             .endpoint(&metrics_endpoint)
             // `endpoint="UNKNOWN"` instead of `endpoint="/foo/bar"`
             .mask_unmatched_patterns("UNKNOWN")
-            .const_labels(
-                config
-                    .observability
-                    .clone()
-                    .unwrap_or_default()
-                    .metrics
-                    .unwrap_or_default()
-                    .add_labels,
-            )
+            .const_labels(metrics_config.add_labels)
             .build()
             .map_err(MartinError::MetricsIntialisationError)?
     };
@@ -228,16 +288,35 @@ pub fn new_server(
         .clone()
         .unwrap_or_else(|| DEFAULT_LISTEN_ADDRESSES.to_owned());
 
+    let tls_config = config.tls.clone();
+    let unix_socket_mode = config.unix_socket_mode;
+
     let cors_config = config.cors.clone().unwrap_or_default();
     cors_config.validate()?;
     cors_config.log_current_configuration();
 
+    // Created outside the factory, so that all workers share the same limits
+    let rate_limits = config
+        .rate_limit
+        .clone()
+        .map(|rate_limit| Data::new(RateLimits::new(rate_limit)));
+    let access_log = config
+        .access_log
+        .clone()
+        .map(AccessLog::new)
+        .transpose()?
+        .map(Data::new);
+    let health_checker = Data::new(HealthChecker::new(
+        config.health.clone().unwrap_or_default(),
+    ));
+
     let factory = move || {
         let cors_middleware = cors_config.make_cors_middleware();
 
         let app = App::new()
             .app_data(Data::new(catalog.clone()))
-            .app_data(Data::new(config.clone()));
+            .app_data(Data::new(config.clone()))
+            .app_data(health_checker.clone());
 
         #[cfg(feature = "_tiles")]
         let app = app.app_data(Data::new(state.tile_manager.clone()));
@@ -255,15 +334,34 @@ pub fn new_server(
         #[cfg(feature = "styles")]
         let app = app.app_data(Data::new(state.styles.clone()));
 
-        let app = app.wrap(middleware::Condition::new(
-            cors_middleware.is_some(),
-            cors_middleware.unwrap_or_default(),
-        ));
+        #[cfg(feature = "mbtiles")]
+        let app = app
+            .app_data(Data::new(state.mbtiles_patches.clone()))
+            .app_data(Data::new(state.mbtiles_writers.clone()));
+
+        let app = match &rate_limits {
+            Some(rate_limits) => app.app_data(rate_limits.clone()),
+            None => app,
+        };
+        let app = match &access_log {
+            Some(access_log) => app.app_data(access_log.clone()),
+            None => app,
+        };
+
+        // Rejected requests still get CORS headers, and are logged and counted in the metrics
+        let app = app
+            .wrap(middleware::from_fn(limit_requests))
+            .wrap(middleware::Condition::new(
+                cors_middleware.is_some(),
+                cors_middleware.unwrap_or_default(),
+            ));
 
         #[cfg(feature = "metrics")]
         let app = app.wrap(prometheus.clone());
 
-        app.wrap(TracingLogger::default())
+        // Wraps the rate limits, so that rejected requests are logged too
+        app.wrap(middleware::from_fn(log_access))
+            .wrap(TracingLogger::default())
             .wrap(cache_control_middleware(cache_control.clone()))
             .wrap(NormalizePath::new(TrailingSlash::MergeOnly))
             .configure(|c| router(c, &config))
@@ -275,14 +373,42 @@ pub fn new_server(
         return Ok((Box::pin(server), "(aws lambda)".into()));
     }
 
-    let server = HttpServer::new(factory)
-        .bind(listen_addresses.clone())
-        .map_err(|e| MartinError::BindingError(e, listen_addresses.clone()))?;
+    let listeners = Listeners::new(listen_addresses.clone(), unix_socket_mode, tls_config)?;
+    let base_url = listeners.url();
+    let mut server = HttpServer::new(factory);
+    for listener in listeners.http {
+        let name = listener.to_string();
+        server = match listener {
+            Listener::Address(addresses) => server.bind(addresses),
+            Listener::Tcp(listener) => server.listen(listener),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => server.listen_uds(listener),
+        }
+        .map_err(|e| MartinError::BindingError(e, name))?;
+    }
+    if let Some((https, rustls_config)) = listeners.https {
+        for listener in https {
+            let name = listener.to_string();
+            server = match listener {
+                Listener::Address(addresses) => {
+                    server.bind_rustls_0_23(addresses, rustls_config.clone())
+                }
+                Listener::Tcp(listener) => {
+                    server.listen_rustls_0_23(listener, rustls_config.clone())
+                }
+                #[cfg(unix)]
+                Listener::Unix(..) => return Err(ListenError::UnixSocketTls(name).into()),
+            }
+            .map_err(|e| MartinError::BindingError(e, name))?;
+        }
+    }
 
-    let listen_addresses = server
-        .addrs()
-        .first()
-        .map_or(listen_addresses, ToString::to_string);
+    let base_url = base_url.unwrap_or_else(|| {
+        server.addrs_with_scheme().first().map_or_else(
+            || format!("http://{listen_addresses}"),
+            |(addr, scheme)| format!("{scheme}://{addr}"),
+        )
+    });
 
     let server = server
         .keep_alive(keep_alive)
@@ -291,5 +417,20 @@ pub fn new_server(
         .run()
         .err_into();
 
-    Ok((Box::pin(server), listen_addresses))
+    Ok((with_reloader(server, listeners.cert_reloader), base_url))
+}
+
+/// Run the certificate reloader in the background until the server stops
+fn with_reloader(
+    server: impl Future<Output = MartinResult<()>> + 'static,
+    reloader: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+) -> Server {
+    Box::pin(async move {
+        let reloader = reloader.map(tokio::spawn);
+        let result = server.await;
+        if let Some(reloader) = reloader {
+            reloader.abort();
+        }
+        result
+    })
 }
@@ -1,5 +1,4 @@
 use actix_middleware_etag::Etag;
-use actix_web::http::Uri;
 use actix_web::http::header::{ContentType, LOCATION};
 use actix_web::middleware::Compress;
 use actix_web::web::{Data, Path};
@@ -10,7 +9,7 @@ use tracing::{error, instrument, warn};
 
 use crate::config::file::srv::SrvConfig;
 use crate::maplibre_style::Style;
-use crate::srv::server::DebouncedWarning;
+use crate::srv::server::{DebouncedWarning, path_prefix};
 
 #[derive(Deserialize, Debug)]
 #[cfg_attr(feature = "unstable-schemas", derive(utoipa::IntoParams))]
@@ -91,28 +90,6 @@ pub async fn get_style_json(
     }
 }
 
-/// Resolve the URL path prefix under which Martin is publicly served.
-///
-/// Returns an empty string when no prefix applies, otherwise a leading-slash
-/// path with no trailing slash (e.g. `/tiles`).
-///
-/// Note: `X-Rewrite-URL` is intentionally not honored here. Unlike the
-/// `TileJSON` case where the header's full path can be used directly, for
-/// styles the header would contain the full style request path
-/// (e.g. `/tiles/style/foo/style.json`), which isn't a usable prefix.
-fn path_prefix(req: &HttpRequest, srv_config: &SrvConfig) -> String {
-    let Some(prefix) = srv_config.public_path_prefix() else {
-        return req
-            .headers()
-            .get("X-Forwarded-Prefix")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.parse::<Uri>().ok())
-            .map(|v| v.path().trim_end_matches('/').to_owned())
-            .unwrap_or_default();
-    };
-    prefix.to_owned()
-}
-
 /// Redirect `/styles/{style_id}` to `/style/{style_id}` (HTTP 301)
 /// This handles common pluralization mistakes
 #[route("/styles/{style_id}", method = "GET", method = "HEAD")]
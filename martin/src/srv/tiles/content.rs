@@ -1,30 +1,45 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+use std::time::{Duration, SystemTime};
 
 use actix_http::ContentEncoding;
 use actix_http::header::Quality;
 use actix_web::error::{ErrorBadRequest, ErrorNotAcceptable, ErrorNotFound};
 use actix_web::http::header::{
-    Accept, AcceptEncoding, CONTENT_ENCODING, ETAG, Encoding as HeaderEnc, EntityTag, IfNoneMatch,
-    LOCATION, Preference,
+    ACCEPT_ENCODING, Accept, AcceptEncoding, CACHE_CONTROL, CONTENT_ENCODING, ETAG,
+    Encoding as HeaderEnc, EntityTag, HeaderValue, HttpDate, IfModifiedSince, IfNoneMatch,
+    LOCATION, LastModified, Preference, VARY,
 };
 use actix_web::web::{Data, Path, Query};
-use actix_web::{HttpMessage as _, HttpRequest, HttpResponse, Result as ActixResult, route};
+use actix_web::{
+    HttpMessage as _, HttpRequest, HttpResponse, HttpResponseBuilder, Result as ActixResult, route,
+};
+use chrono::{DateTime, Utc};
 use futures::stream::{self, StreamExt as _, TryStreamExt as _};
-use martin_core::tiles::{BoxedSource, MartinCoreError, Tile, TileCache, UrlQuery};
+use martin_core::tiles::{
+    BoxedSource, MartinCoreError, MartinCoreResult, Tile, TileCache, TileCacheHints, TileCacheKey,
+    UrlQuery,
+};
+use martin_tile_utils::tms::{TileMatrixSet, WEB_MERCATOR_QUAD};
 use martin_tile_utils::{
     Encoding, Format, TileCoord, TileInfo, decode_brotli, decode_gzip, decode_zlib, decode_zstd,
     encode_brotli_with_quality, encode_gzip, encode_zlib, encode_zstd,
 };
 use serde::Deserialize;
-use tracing::{instrument, warn};
+use tokio::sync::OwnedSemaphorePermit;
+use tracing::{Instrument as _, debug_span, instrument, warn};
 
 use crate::config::args::PreferredEncoding;
 use crate::config::file::ProcessConfig;
 use crate::config::file::driver::Sink as _;
 use crate::config::file::srv::SrvConfig;
 use crate::reload::{NewSource, ReloadAdvisory};
+use crate::srv::access_log::{CacheStatus, TileAccess};
+use crate::srv::limits::Admission;
 use crate::srv::server::{DebouncedWarning, map_internal_error};
-use crate::srv::tiles::process::apply_pre_cache_processors;
+use crate::srv::tiles::process::{ancestor, apply_pre_cache_processors, overzoom_tile};
 use crate::tile_source_manager::TileSourceManager;
 
 /// Maximum number of source tiles fetched concurrently for one composite response.
@@ -82,12 +97,77 @@ pub async fn get_tile(
     path: Path<TileRequest>,
     manager: Data<TileSourceManager>,
 ) -> ActixResult<HttpResponse> {
-    let headers = TileRequestHeaders {
-        accepted_formats: parse_accept(req.get_header::<Accept>())?,
-        accept_enc: req.get_header::<AcceptEncoding>(),
-        if_none_match: req.get_header::<IfNoneMatch>(),
-        preferred_enc: srv_config.preferred_encoding,
-    };
+    let headers = TileRequestHeaders::from_request(&req, &srv_config)?;
+    let src = DynTileSource::new(
+        &manager,
+        &path.source_ids,
+        Some(path.z),
+        req.query_string(),
+        headers,
+    )?;
+
+    src.get_http_response(TileCoord {
+        z: path.z,
+        x: path.x,
+        y: path.y,
+    })
+    .await
+}
+
+#[derive(Deserialize, Clone)]
+#[cfg_attr(feature = "unstable-schemas", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "unstable-schemas", into_params(parameter_in = Path))]
+pub struct TileMatrixSetTileRequest {
+    source_ids: String,
+    tms_id: String,
+    z: u8,
+    x: u32,
+    y: u32,
+}
+
+/// Same as [`get_tile`], but the tile matrix set ID is part of the URL and must match the sources.
+///
+/// Numeric-only `z/x/y` keeps this from shadowing other five-segment routes like `/style/{style_id}/{z}/{x}/{y}.{format}`.
+#[cfg_attr(
+    feature = "unstable-schemas",
+    utoipa::path(
+        get,
+        path = "/{source_ids}/{tms_id}/{z}/{x}/{y}",
+        params(TileMatrixSetTileRequest),
+        responses(
+            (status = 200, description = "Encoded vector or raster tile"),
+            (status = 204, description = "Source(s) returned an empty tile"),
+            (status = 304, description = "ETag matched If-None-Match"),
+            (status = 404, description = "No matching source, or the sources use another tile matrix set"),
+            (status = 406, description = "No supported tile format in Accept header"),
+        ),
+    )
+)]
+#[route(
+    "/{source_ids}/{tms_id}/{z:\\d+}/{x:\\d+}/{y:\\d+}",
+    method = "GET",
+    method = "HEAD"
+)]
+#[hotpath::measure]
+#[instrument(
+    level = "debug",
+    skip_all,
+    fields(
+        source.ids = %path.source_ids,
+        tile.tms = %path.tms_id,
+        tile.z = path.z,
+        tile.x = path.x,
+        tile.y = path.y,
+    ),
+    err(Debug),
+)]
+pub async fn get_tile_matrix_set_tile(
+    req: HttpRequest,
+    srv_config: Data<SrvConfig>,
+    path: Path<TileMatrixSetTileRequest>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let headers = TileRequestHeaders::from_request(&req, &srv_config)?;
     let src = DynTileSource::new(
         &manager,
         &path.source_ids,
@@ -95,6 +175,14 @@ pub async fn get_tile(
         req.query_string(),
         headers,
     )?;
+    if src.tile_matrix_set().id != path.tms_id {
+        return Err(ErrorNotFound(format!(
+            "Source {} uses tile matrix set {}, not {}",
+            path.source_ids,
+            src.tile_matrix_set().id,
+            path.tms_id
+        )));
+    }
 
     src.get_http_response(TileCoord {
         z: path.z,
@@ -113,7 +201,25 @@ pub struct TileRequestHeaders {
     pub accepted_formats: Option<Vec<Format>>,
     pub accept_enc: Option<AcceptEncoding>,
     pub if_none_match: Option<IfNoneMatch>,
+    /// Only used if there is no `If-None-Match` header
+    pub if_modified_since: Option<IfModifiedSince>,
     pub preferred_enc: Option<PreferredEncoding>,
+    /// Per-source limits of the client, if rate limiting is enabled
+    pub admission: Option<Admission>,
+}
+
+impl TileRequestHeaders {
+    /// Extracts the tile negotiation headers from an incoming request.
+    pub fn from_request(req: &HttpRequest, srv_config: &SrvConfig) -> ActixResult<Self> {
+        Ok(Self {
+            accepted_formats: parse_accept(req.get_header::<Accept>())?,
+            accept_enc: req.get_header::<AcceptEncoding>(),
+            if_none_match: req.get_header::<IfNoneMatch>(),
+            if_modified_since: req.get_header::<IfModifiedSince>(),
+            preferred_enc: srv_config.preferred_encoding,
+            admission: Admission::from_request(req),
+        })
+    }
 }
 
 /// Parse the `Accept` header into a flat list of [`Format`] values.
@@ -255,6 +361,25 @@ fn redirect_tile_with_query(
         .finish()
 }
 
+/// Whether the tiles of a request came from the tile cache, see [`CacheStatus`]
+#[derive(Debug, Default)]
+struct CacheOutcome {
+    looked_up: AtomicBool,
+    generated: AtomicBool,
+}
+
+impl CacheOutcome {
+    fn status(&self) -> CacheStatus {
+        if !self.looked_up.load(Ordering::Relaxed) {
+            CacheStatus::Bypass
+        } else if self.generated.load(Ordering::Relaxed) {
+            CacheStatus::Miss
+        } else {
+            CacheStatus::Hit
+        }
+    }
+}
+
 pub struct DynTileSource<'a> {
     pub sources: Vec<(BoxedSource, ProcessConfig)>,
     pub info: TileInfo,
@@ -265,6 +390,7 @@ pub struct DynTileSource<'a> {
     pub headers: TileRequestHeaders,
     pub cache: Option<&'a TileCache>,
     pub manager: &'a TileSourceManager,
+    cache_outcome: CacheOutcome,
 }
 
 impl<'a> DynTileSource<'a> {
@@ -321,9 +447,16 @@ impl<'a> DynTileSource<'a> {
             headers,
             cache,
             manager,
+            cache_outcome: CacheOutcome::default(),
         })
     }
 
+    /// The tile matrix set shared by all the sources.
+    #[must_use]
+    pub fn tile_matrix_set(&self) -> &TileMatrixSet {
+        self.sources[0].0.get_tile_matrix_set()
+    }
+
     /// Checks the pre-parsed accepted formats against the source format. The
     /// pre-cache pipeline can transcode between MVT and MLT in either
     /// direction, so when the Accept header lists the opposite vector format
@@ -361,31 +494,140 @@ impl<'a> DynTileSource<'a> {
         err(Debug),
     )]
     pub async fn get_http_response(&self, xyz: TileCoord) -> ActixResult<HttpResponse> {
+        // WebMercatorQuad sources keep accepting any zoom the source allows
+        let tms = self.tile_matrix_set();
+        if tms.id != WEB_MERCATOR_QUAD && !tms.contains(xyz) {
+            return Err(ErrorNotFound(format!(
+                "Tile {xyz} is outside of the {} tile matrix set",
+                tms.id
+            )));
+        }
+        // Holds the `max_in_flight` slots of the sources until the tile is ready
+        let _permits = self.admit().await?;
         let tile = self.get_tile_content(xyz).await?;
+        let last_modified = self.last_modified().or(tile.hints.last_modified);
+        let fresh_for = tile.hints.remaining();
         if tile.data.is_empty() {
-            return Ok(HttpResponse::NoContent().finish());
+            let mut response = HttpResponse::NoContent();
+            self.insert_cache_headers(&mut response, xyz.z, last_modified, fresh_for);
+            response.extensions_mut().insert(self.tile_access(xyz));
+            return Ok(response.finish());
         }
         let etag = EntityTag::new_strong(tile.etag.clone());
 
-        if let Some(if_none_match) = &self.headers.if_none_match {
-            let dominated_by = match if_none_match {
-                IfNoneMatch::Any => true,
-                IfNoneMatch::Items(items) => items.iter().any(|e| e.strong_eq(&etag)),
-            };
-            if dominated_by {
-                return Ok(HttpResponse::NotModified().finish());
-            }
+        if self.is_not_modified(&etag, last_modified) {
+            let mut response = HttpResponse::NotModified();
+            self.insert_cache_headers(&mut response, xyz.z, last_modified, fresh_for);
+            response.extensions_mut().insert(self.tile_access(xyz));
+            return Ok(response.finish());
         }
 
         let mut response = HttpResponse::Ok();
+        response.extensions_mut().insert(self.tile_access(xyz));
         response.content_type(tile.info.format.content_type());
         response.insert_header((ETAG, etag));
         if let Some(val) = tile.info.encoding.compression() {
             response.insert_header((CONTENT_ENCODING, val));
         }
+        self.insert_cache_headers(&mut response, xyz.z, last_modified, fresh_for);
         Ok(response.body(tile.data))
     }
 
+    /// Tile details of the response for the access log
+    fn tile_access(&self, xyz: TileCoord) -> TileAccess {
+        TileAccess {
+            source: self
+                .sources
+                .iter()
+                .map(|(s, _)| s.get_id())
+                .collect::<Vec<_>>()
+                .join(","),
+            z: xyz.z,
+            x: xyz.x,
+            y: xyz.y,
+            cache: self.cache_outcome.status(),
+        }
+    }
+
+    /// Checks the limits of the client for these sources, see [`Admission::admit`].
+    /// The returned permits must be held while the sources are queried.
+    pub async fn admit(&self) -> ActixResult<Vec<OwnedSemaphorePermit>> {
+        let Some(admission) = &self.headers.admission else {
+            return Ok(Vec::new());
+        };
+        Ok(admission
+            .admit(self.sources.iter().map(|(s, _)| s.get_id()))
+            .await?)
+    }
+
+    /// Whether the client's cached copy is still valid.
+    /// `If-Modified-Since` is ignored if the request has an `If-None-Match` header.
+    fn is_not_modified(&self, etag: &EntityTag, last_modified: Option<DateTime<Utc>>) -> bool {
+        if let Some(if_none_match) = &self.headers.if_none_match {
+            return match if_none_match {
+                IfNoneMatch::Any => true,
+                IfNoneMatch::Items(items) => items.iter().any(|e| e.strong_eq(etag)),
+            };
+        }
+        match (&self.headers.if_modified_since, last_modified) {
+            // HTTP dates have a resolution of seconds
+            (Some(IfModifiedSince(since)), Some(modified)) => {
+                DateTime::<Utc>::from(SystemTime::from(*since)).timestamp() >= modified.timestamp()
+            }
+            _ => false,
+        }
+    }
+
+    /// When the tiles of all the sources were last modified, unless any of them does not know
+    fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.sources
+            .iter()
+            .map(|(s, _)| s.get_last_modified())
+            .try_fold(DateTime::<Utc>::MIN_UTC, |latest, modified| {
+                Some(latest.max(modified?))
+            })
+    }
+
+    /// The configured `Cache-Control` header for tiles of this zoom level.
+    /// Composite sources only get one if all the sources agree on it.
+    fn cache_control(&self, zoom: u8) -> Option<HeaderValue> {
+        let mut values = self
+            .sources
+            .iter()
+            .map(|(_, pc)| pc.cache_control.as_ref()?.for_zoom(zoom));
+        let first = values.next()??;
+        values
+            .all(|value| value == Some(first))
+            .then(|| first.header_value())
+    }
+
+    /// Sets the caching headers of a tile response.
+    /// `Cache-Control` is the configured header, or else the remaining freshness of the tile
+    /// if its sources know it (e.g. from upstream headers), or else the global default header.
+    fn insert_cache_headers(
+        &self,
+        response: &mut HttpResponseBuilder,
+        zoom: u8,
+        last_modified: Option<DateTime<Utc>>,
+        fresh_for: Option<Duration>,
+    ) {
+        if let Some(cache_control) = self.cache_control(zoom) {
+            response.insert_header((CACHE_CONTROL, cache_control));
+        } else if let Some(fresh_for) = fresh_for {
+            response.insert_header((CACHE_CONTROL, format!("max-age={}", fresh_for.as_secs())));
+        }
+        if let Some(modified) = last_modified {
+            response.insert_header(LastModified(HttpDate::from(SystemTime::from(modified))));
+        }
+        // The response depends on these request headers, shared caches must not mix them up
+        #[cfg(all(feature = "mlt", feature = "_tiles"))]
+        if matches!(self.info.format, Format::Mvt | Format::Mlt) {
+            response.insert_header((VARY, HeaderValue::from_static("Accept, Accept-Encoding")));
+            return;
+        }
+        response.insert_header((VARY, ACCEPT_ENCODING.as_str()));
+    }
+
     #[hotpath::measure]
     #[instrument(
         level = "debug",
@@ -405,7 +647,8 @@ impl<'a> DynTileSource<'a> {
             .try_collect()
             .await?;
 
-        self.merge_tiles(tiles)
+        let hints = TileCacheHints::combine(tiles.iter().map(|t| t.hints));
+        Ok(self.merge_tiles(tiles)?.with_hints(hints))
     }
 
     async fn get_tile_content_from_one_source(
@@ -450,10 +693,6 @@ impl<'a> DynTileSource<'a> {
         }
     }
 
-    #[cfg_attr(
-        not(all(feature = "mlt", feature = "_tiles")),
-        expect(unused_variables)
-    )]
     async fn fetch_tile_content_with_cache(
         &self,
         s: &BoxedSource,
@@ -463,34 +702,66 @@ impl<'a> DynTileSource<'a> {
         let cache_zoom = s.cache_zoom().contains(xyz.z);
         let src_id = s.get_id().to_owned();
         let src = s.clone_source();
+        // Zoom levels beyond the source's maxzoom are cut out of the tiles at maxzoom
+        let overzoom_from = pc.overzoom_from(src.get_tilejson(), xyz.z);
         let compute = || async move {
-            let t = src
-                .get_tile_with_etag(xyz, self.query.as_ref().map(|q| &q.1))
-                .await?;
-            apply_pre_cache_processors(
-                t,
-                #[cfg(all(feature = "mlt", feature = "_tiles"))]
-                pc,
-                #[cfg(all(feature = "mlt", feature = "_tiles"))]
-                self.accepted_format,
-            )
-            .map_err(|e| MartinCoreError::OtherError(Box::new(e)))
-        };
-        if let (Some(cache), true) = (self.cache, cache_zoom) {
-            cache
-                .get_or_insert(
-                    martin_core::tiles::TileCacheKey::new(
-                        src_id,
-                        xyz,
-                        self.query.as_ref().map(|q| q.0.to_owned()),
-                        self.accepted_format,
-                    ),
-                    compute,
+            self.cache_outcome.generated.store(true, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            let started = Instant::now();
+            let result: MartinCoreResult<Tile> = async {
+                let span = debug_span!(
+                    "source_get_tile",
+                    source.id = src.get_id(),
+                    tile.z = xyz.z,
+                    tile.x = xyz.x,
+                    tile.y = xyz.y,
+                );
+                let src_xyz = overzoom_from.map_or(xyz, |zoom| ancestor(xyz, zoom));
+                let mut t = src
+                    .get_tile_with_etag(src_xyz, self.query.as_ref().map(|q| &q.1))
+                    .instrument(span)
+                    .await?;
+                if let Some(zoom) = overzoom_from {
+                    // Decoding, clipping and resizing tiles is CPU-bound
+                    t = tokio::task::spawn_blocking(move || overzoom_tile(t, xyz, zoom))
+                        .await
+                        .map_err(|e| MartinCoreError::OtherError(Box::new(e)))?
+                        .map_err(|e| MartinCoreError::OtherError(Box::new(e)))?;
+                }
+                apply_pre_cache_processors(
+                    t,
+                    #[cfg(all(feature = "mlt", feature = "_tiles"))]
+                    pc,
+                    #[cfg(all(feature = "mlt", feature = "_tiles"))]
+                    self.accepted_format,
                 )
-                .await
-        } else {
-            compute().await.map_err(Arc::new)
+                .map_err(|e| MartinCoreError::OtherError(Box::new(e)))
+            }
+            .await;
+            #[cfg(feature = "metrics")]
+            martin_core::metrics::record_tile_generation(src.get_id(), started.elapsed(), &result);
+            result
+        };
+        let key = TileCacheKey::new(
+            src_id,
+            xyz,
+            self.query.as_ref().map(|q| q.0.to_owned()),
+            self.accepted_format,
+        );
+        let cache = self.cache.filter(|_| cache_zoom);
+        if cache.is_some() {
+            self.cache_outcome.looked_up.store(true, Ordering::Relaxed);
         }
+        // Concurrent requests for the same tile share one cache lookup and source query
+        self.manager
+            .tile_flights()
+            .run(key.clone(), || async move {
+                match cache {
+                    Some(cache) => cache.get_or_insert(key, compute).await,
+                    None => compute().await.map_err(Arc::new),
+                }
+            })
+            .await
     }
 
     fn merge_tiles(&self, mut tiles: Vec<Tile>) -> ActixResult<Tile> {
@@ -736,13 +1007,15 @@ pub fn to_encoding(val: ContentEncoding) -> Option<Encoding> {
 #[cfg(test)]
 mod tests {
     use actix_http::header::TryIntoHeaderValue as _;
-    use actix_web::http::header::QualityItem;
+    use actix_web::http::header::{LAST_MODIFIED, QualityItem};
     use rstest::rstest;
     use tilejson::tilejson;
 
     use super::*;
     use crate::config::file::OnInvalid;
-    use crate::srv::tiles::tests::{CompressedTestSource, SourceNeedsReloadTestSource, TestSource};
+    use crate::srv::tiles::tests::{
+        CompressedTestSource, ModifiedTestSource, SourceNeedsReloadTestSource, TestSource,
+    };
 
     fn test_manager(sources: Vec<Vec<BoxedSource>>) -> TileSourceManager {
         let sources = sources
@@ -830,6 +1103,159 @@ mod tests {
         );
     }
 
+    #[actix_rt::test]
+    async fn cache_control_per_zoom() {
+        let source = TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            data: vec![1_u8, 2, 3],
+            format: Format::Mvt,
+        };
+        let process = ProcessConfig {
+            cache_control: Some(
+                serde_saphyr::from_str(indoc::indoc! {"
+                    default: public, max-age=3600
+                    zooms:
+                      - maxzoom: 4
+                        value: public, max-age=86400, stale-while-revalidate=3600
+                "})
+                .unwrap(),
+            ),
+            ..ProcessConfig::default()
+        };
+        let mgr = TileSourceManager::from_sources(
+            None,
+            OnInvalid::Abort,
+            vec![vec![(Box::new(source) as BoxedSource, process)]],
+        );
+        let src = DynTileSource::new(&mgr, "src", None, "", TileRequestHeaders::default()).unwrap();
+
+        for (z, expected) in [
+            (2, "public, max-age=86400, stale-while-revalidate=3600"),
+            (10, "public, max-age=3600"),
+        ] {
+            let resp = src
+                .get_http_response(TileCoord { z, x: 0, y: 0 })
+                .await
+                .unwrap();
+            assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), expected);
+            assert!(resp.headers().get(LAST_MODIFIED).is_none());
+            #[cfg(feature = "mlt")]
+            assert_eq!(resp.headers().get(VARY).unwrap(), "Accept, Accept-Encoding");
+            #[cfg(not(feature = "mlt"))]
+            assert_eq!(resp.headers().get(VARY).unwrap(), "Accept-Encoding");
+        }
+    }
+
+    #[cfg(feature = "raster-overzoom")]
+    #[actix_rt::test]
+    async fn overzoom_beyond_maxzoom() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(8, 8, image::Rgba([10, 20, 30, 255]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let source = TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 2 },
+            data: png.into_inner(),
+            format: Format::Png,
+        };
+        let process = ProcessConfig {
+            overzoom: Some(4),
+            ..ProcessConfig::default()
+        };
+        let mgr = TileSourceManager::from_sources(
+            None,
+            OnInvalid::Abort,
+            vec![vec![(Box::new(source) as BoxedSource, process)]],
+        );
+        let src = DynTileSource::new(&mgr, "src", None, "", TileRequestHeaders::default()).unwrap();
+
+        let original = src
+            .get_tile_content(TileCoord { z: 2, x: 1, y: 1 })
+            .await
+            .unwrap();
+        let tile = src
+            .get_tile_content(TileCoord { z: 4, x: 5, y: 6 })
+            .await
+            .unwrap();
+        assert_eq!(tile.info.format, Format::Png);
+        assert_ne!(tile.etag, original.etag);
+        let image = image::load_from_memory(&tile.data).unwrap();
+        assert_eq!((image.width(), image.height()), (8, 8));
+    }
+
+    #[actix_rt::test]
+    async fn tile_access_cache_status() {
+        let source = || TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            data: vec![1_u8, 2, 3],
+            format: Format::Mvt,
+        };
+        let access = |resp: &HttpResponse| resp.extensions().get::<TileAccess>().cloned();
+        let xyz = TileCoord { z: 1, x: 0, y: 1 };
+
+        let mgr = TileSourceManager::from_sources(
+            Some(TileCache::new(1_000_000, None, None)),
+            OnInvalid::Abort,
+            vec![vec![(
+                Box::new(source()) as BoxedSource,
+                ProcessConfig::default(),
+            )]],
+        );
+        for expected in [CacheStatus::Miss, CacheStatus::Hit] {
+            let src =
+                DynTileSource::new(&mgr, "src", None, "", TileRequestHeaders::default()).unwrap();
+            let resp = src.get_http_response(xyz).await.unwrap();
+            assert_eq!(
+                access(&resp),
+                Some(TileAccess {
+                    source: "src".to_owned(),
+                    z: 1,
+                    x: 0,
+                    y: 1,
+                    cache: expected,
+                })
+            );
+        }
+
+        let mgr = test_manager(vec![vec![Box::new(source())]]);
+        let src = DynTileSource::new(&mgr, "src", None, "", TileRequestHeaders::default()).unwrap();
+        let resp = src.get_http_response(xyz).await.unwrap();
+        assert_eq!(access(&resp).unwrap().cache, CacheStatus::Bypass);
+    }
+
+    #[rstest]
+    #[case(None, 200)]
+    #[case(Some("Sun, 01 Mar 2026 11:59:59 GMT"), 200)]
+    #[case(Some("Sun, 01 Mar 2026 12:00:00 GMT"), 304)]
+    #[case(Some("Mon, 02 Mar 2026 00:00:00 GMT"), 304)]
+    #[actix_rt::test]
+    async fn if_modified_since(#[case] since: Option<&str>, #[case] expected_status: u16) {
+        let source = ModifiedTestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            data: vec![1_u8, 2, 3],
+            last_modified: "2026-03-01T12:00:00.250Z".parse().unwrap(),
+        };
+        let mgr = test_manager(vec![vec![Box::new(source)]]);
+        let headers = TileRequestHeaders {
+            if_modified_since: since.map(|s| IfModifiedSince(s.parse().unwrap())),
+            ..Default::default()
+        };
+        let src = DynTileSource::new(&mgr, "src", None, "", headers).unwrap();
+        let resp = src
+            .get_http_response(TileCoord { z: 0, x: 0, y: 0 })
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), expected_status);
+        assert_eq!(
+            resp.headers().get(LAST_MODIFIED).unwrap(),
+            "Sun, 01 Mar 2026 12:00:00 GMT"
+        );
+    }
+
     #[actix_rt::test]
     async fn tile_content() {
         let non_empty_source = TestSource {
@@ -8,10 +8,13 @@ use actix_web::web::{Data, Path};
 use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, route};
 use itertools::Itertools as _;
 use martin_core::tiles::BoxedSource;
+use martin_tile_utils::tms::WEB_MERCATOR_QUAD;
 use serde::Deserialize;
 use tilejson::{TileJSON, tilejson};
 use url::form_urlencoded;
 
+#[cfg(any(feature = "ogcapi", feature = "wmts"))]
+use crate::config::file::ProcessConfig;
 use crate::config::file::srv::SrvConfig;
 use crate::tile_source_manager::TileSourceManager;
 
@@ -92,10 +95,16 @@ pub async fn get_source_info(
     }
     let query = query.finish();
 
+    // Sources outside of WebMercatorQuad advertise the explicit tile matrix set route
+    let tms_path = if resolved.tile_matrix_set == WEB_MERCATOR_QUAD {
+        String::new()
+    } else {
+        format!("/{}", resolved.tile_matrix_set)
+    };
     let path_and_query = if query.is_empty() {
-        format!("{tiles_path}/{{z}}/{{x}}/{{y}}")
+        format!("{tiles_path}{tms_path}/{{z}}/{{x}}/{{y}}")
     } else {
-        format!("{tiles_path}/{{z}}/{{x}}/{{y}}?{query}")
+        format!("{tiles_path}{tms_path}/{{z}}/{{x}}/{{y}}?{query}")
     };
 
     // Construct a tiles URL from the request info, including the query string if present.
@@ -108,8 +117,16 @@ pub async fn get_source_info(
         .map(|tiles_url| tiles_url.to_string())
         .map_err(|e| ErrorBadRequest(format!("Can't build tiles URL: {e}")))?;
 
+    // Overzoomed sources serve tiles beyond their own maxzoom
+    let maxzoom = resolved
+        .sources
+        .iter()
+        .filter_map(|(s, pc)| pc.maxzoom(s.get_tilejson()))
+        .max();
     let just_sources: Vec<_> = resolved.sources.into_iter().map(|(s, _)| s).collect();
-    Ok(HttpResponse::Ok().json(merge_tilejson(&just_sources, tiles_url)))
+    let mut tj = merge_tilejson(&just_sources, tiles_url);
+    tj.maxzoom = tj.maxzoom.max(maxzoom);
+    Ok(HttpResponse::Ok().json(tj))
 }
 
 #[must_use]
@@ -205,6 +222,19 @@ pub fn merge_tilejson(sources: &[BoxedSource], tiles_url: String) -> TileJSON {
     result
 }
 
+/// Zoom range of a source, as advertised in its `TileJSON` and raised to its `overzoom`,
+/// clamped to [`MAX_ZOOM`]
+#[cfg(any(feature = "ogcapi", feature = "wmts"))]
+pub(crate) fn source_zoom_range(src: &BoxedSource, pc: &ProcessConfig) -> (u8, u8) {
+    let tj = src.get_tilejson();
+    let min = tj.minzoom.unwrap_or(0);
+    let max = pc
+        .maxzoom(tj)
+        .unwrap_or(martin_tile_utils::MAX_ZOOM)
+        .min(martin_tile_utils::MAX_ZOOM);
+    (min, max.max(min))
+}
+
 #[cfg(test)]
 pub mod tests {
     use std::collections::BTreeMap;
@@ -1,10 +1,13 @@
 pub mod content;
 pub mod metadata;
 pub mod process;
+#[cfg(feature = "mbtiles")]
+pub mod write;
 
 #[cfg(test)]
 pub mod tests {
     use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
     use martin_core::CacheZoomRange;
     use martin_core::tiles::{BoxedSource, MartinCoreError, MartinCoreResult, Source, UrlQuery};
     use martin_tile_utils::{Encoding, Format, TileCoord, TileData, TileInfo};
@@ -153,4 +156,48 @@ pub mod tests {
             Ok(self.data.clone())
         }
     }
+
+    /// A test source that reports a fixed modification time, like file-backed sources do.
+    #[derive(Debug, Clone)]
+    pub struct ModifiedTestSource {
+        pub id: &'static str,
+        pub tj: TileJSON,
+        pub data: TileData,
+        pub last_modified: DateTime<Utc>,
+    }
+
+    #[async_trait]
+    impl Source for ModifiedTestSource {
+        fn get_id(&self) -> &str {
+            self.id
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            &self.tj
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        }
+
+        fn clone_source(&self) -> BoxedSource {
+            Box::new(self.clone())
+        }
+
+        fn get_last_modified(&self) -> Option<DateTime<Utc>> {
+            Some(self.last_modified)
+        }
+
+        fn cache_zoom(&self) -> CacheZoomRange {
+            CacheZoomRange::default()
+        }
+
+        async fn get_tile(
+            &self,
+            _xyz: TileCoord,
+            _url_query: Option<&UrlQuery>,
+        ) -> MartinCoreResult<TileData> {
+            Ok(self.data.clone())
+        }
+    }
 }
@@ -1,3 +1,5 @@
+#[cfg(feature = "_tiles")]
+mod overzoom;
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 mod to_mlt;
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
@@ -7,6 +9,8 @@ use martin_core::tiles::Tile;
 use martin_tile_utils::Format;
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 use mlt_core::encoder::EncoderConfig;
+#[cfg(feature = "_tiles")]
+pub use overzoom::{ancestor, overzoom_tile};
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
 use to_mlt::convert_mvt_to_mlt;
 #[cfg(all(feature = "mlt", feature = "_tiles"))]
@@ -29,6 +33,9 @@ pub enum ProcessError {
     MvtConversion(String),
     #[error("Tile decompression failed: {0}")]
     DecompressionFailed(String),
+    #[cfg(feature = "_tiles")]
+    #[error("Overzooming tile failed: {0}")]
+    Overzoom(String),
 }
 
 impl From<ProcessError> for actix_web::Error {
@@ -60,6 +67,7 @@ pub fn apply_pre_cache_processors(
     if tile.data.is_empty() {
         return Ok(tile);
     }
+    let hints = tile.hints;
 
     #[cfg(all(feature = "mlt", feature = "_tiles"))]
     let tile = if accepted == Some(Format::Mlt) && tile.info.format == Format::Mvt {
@@ -86,7 +94,8 @@ pub fn apply_pre_cache_processors(
         tile
     };
 
-    Ok(tile)
+    // The converted tile is as fresh as the source tile
+    Ok(tile.with_hints(hints))
 }
 
 #[cfg(test)]
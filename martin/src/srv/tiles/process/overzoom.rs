@@ -0,0 +1,677 @@
+//! Serves tiles beyond the `maxzoom` of a source by cutting them out of its tiles at `maxzoom`.
+//!
+//! Vector tiles are decoded just enough to rewrite the geometry of their features:
+//! coordinates are scaled to the requested tile and clipped to its extent plus a small buffer.
+//! Tags, keys and values are kept as their encoded protobuf messages.
+//! Raster tiles are cropped to the requested tile and scaled back up to the tile size,
+//! with the `raster-overzoom` feature.
+
+#[cfg(feature = "raster-overzoom")]
+use std::io::Cursor;
+
+#[cfg(feature = "raster-overzoom")]
+use image::imageops::FilterType;
+#[cfg(feature = "raster-overzoom")]
+use image::{GenericImageView as _, ImageFormat};
+use martin_core::tiles::Tile;
+use martin_tile_utils::protobuf::{ProtobufError, Reader, Value, write_bytes, write_varint};
+use martin_tile_utils::{Encoding, Format, TileCoord, TileInfo};
+use tracing::instrument;
+
+use crate::srv::tiles::content;
+use crate::srv::tiles::process::ProcessError;
+
+/// Extent of layers that do not set one, as defined by the vector tile specification.
+const DEFAULT_EXTENT: u64 = 4096;
+/// Features are clipped to the tile plus `extent / BUFFER_DIVISOR` on each side,
+/// so that lines and polygon outlines do not end right at the tile edges.
+const BUFFER_DIVISOR: i64 = 64;
+
+// Field numbers of the `Tile`, `Layer` and `Feature` messages
+const TILE_LAYERS: u32 = 3;
+const LAYER_FEATURES: u32 = 2;
+const LAYER_EXTENT: u32 = 5;
+const FEATURE_TYPE: u32 = 3;
+const FEATURE_GEOMETRY: u32 = 4;
+
+// Geometry types
+const POINT: u64 = 1;
+const LINESTRING: u64 = 2;
+const POLYGON: u64 = 3;
+
+// Geometry commands
+const MOVE_TO: u64 = 1;
+const LINE_TO: u64 = 2;
+const CLOSE_PATH: u64 = 7;
+
+/// The tile at `zoom` that contains `xyz`, which must be at `zoom` or above.
+#[must_use]
+pub fn ancestor(xyz: TileCoord, zoom: u8) -> TileCoord {
+    let dz = xyz.z - zoom;
+    TileCoord {
+        z: zoom,
+        x: xyz.x >> dz,
+        y: xyz.y >> dz,
+    }
+}
+
+/// Cuts the tile `xyz` out of `tile`, the tile containing it at zoom `from_zoom`.
+///
+/// Vector tiles are returned as uncompressed MVT, raster tiles in the format of `tile`.
+/// The result keeps the etag of `tile` with the coordinates of `xyz` appended.
+#[instrument(level = "debug", skip_all, fields(tile.z = xyz.z, tile.x = xyz.x, tile.y = xyz.y), err(Debug))]
+pub fn overzoom_tile(tile: Tile, xyz: TileCoord, from_zoom: u8) -> Result<Tile, ProcessError> {
+    if tile.data.is_empty() {
+        return Ok(tile);
+    }
+    let hints = tile.hints;
+    let etag = format!("{}+{}-{}-{}", tile.etag, xyz.z, xyz.x, xyz.y);
+    let cut = Cut::new(xyz, from_zoom);
+    let (data, info) = match tile.info.format {
+        Format::Mvt => {
+            let tile = content::decode(tile)
+                .map_err(|e| ProcessError::DecompressionFailed(e.to_string()))?;
+            (
+                cut.vector_tile(&tile.data)?,
+                TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            )
+        }
+        #[cfg(feature = "raster-overzoom")]
+        Format::Png | Format::Jpeg | Format::Webp => {
+            (cut.raster_tile(&tile.data, tile.info.format)?, tile.info)
+        }
+        _ => {
+            return Err(ProcessError::Overzoom(format!(
+                "{} tiles cannot be overzoomed",
+                tile.info
+            )));
+        }
+    };
+    if data.is_empty() {
+        return Ok(Tile::new_hash_etag(data, info).with_hints(hints));
+    }
+    Ok(Tile::new_with_etag(data, info, etag).with_hints(hints))
+}
+
+/// Position of a tile within a tile of a lower zoom level.
+#[derive(Clone, Copy, Debug)]
+struct Cut {
+    /// Number of tiles along each axis of the lower zoom tile
+    scale: i64,
+    /// Column and row of the tile within the lower zoom tile
+    col: i64,
+    row: i64,
+}
+
+impl Cut {
+    fn new(xyz: TileCoord, from_zoom: u8) -> Self {
+        let dz = xyz.z - from_zoom;
+        let mask = (1_u32 << dz) - 1;
+        Self {
+            scale: 1 << dz,
+            col: i64::from(xyz.x & mask),
+            row: i64::from(xyz.y & mask),
+        }
+    }
+
+    /// Crops the tile out of a raster image and scales it back up to the size of the image.
+    #[cfg(feature = "raster-overzoom")]
+    fn raster_tile(self, data: &[u8], format: Format) -> Result<Vec<u8>, ProcessError> {
+        let image = image::load_from_memory(data).map_err(|e| overzoom_error(&e))?;
+        let (width, height) = image.dimensions();
+        let (x, w) = self.span(self.col, width);
+        let (y, h) = self.span(self.row, height);
+        let image = image
+            .crop_imm(x, y, w, h)
+            .resize_exact(width, height, FilterType::CatmullRom);
+        let image_format = match format {
+            Format::Jpeg => ImageFormat::Jpeg,
+            Format::Webp => ImageFormat::WebP,
+            _ => ImageFormat::Png,
+        };
+        let mut data = Cursor::new(Vec::new());
+        image
+            .write_to(&mut data, image_format)
+            .map_err(|e| overzoom_error(&e))?;
+        Ok(data.into_inner())
+    }
+
+    /// First pixel and number of pixels of the tile at `index` along an axis of `size` pixels,
+    /// at least one pixel.
+    #[cfg(feature = "raster-overzoom")]
+    fn span(self, index: i64, size: u32) -> (u32, u32) {
+        let size_i = i64::from(size);
+        let start = index * size_i / self.scale;
+        let end = ((index + 1) * size_i + self.scale - 1) / self.scale;
+        let start = u32::try_from(start).unwrap_or_default().min(size - 1);
+        let len = u32::try_from(end).unwrap_or(size).min(size) - start;
+        (start, len.max(1))
+    }
+
+    /// Rewrites the features of all layers of an uncompressed vector tile.
+    /// Layers without features left are dropped.
+    fn vector_tile(self, data: &[u8]) -> Result<Vec<u8>, ProcessError> {
+        let mut out = Vec::with_capacity(data.len());
+        let mut reader = Reader::new(data);
+        while let Some((field, value, raw)) = reader.next_field()? {
+            match (field, value) {
+                (TILE_LAYERS, Value::Bytes(layer)) => {
+                    if let Some(layer) = self.layer(layer)? {
+                        write_bytes(&mut out, TILE_LAYERS, &layer);
+                    }
+                }
+                _ => out.extend_from_slice(raw),
+            }
+        }
+        Ok(out)
+    }
+
+    fn layer(self, data: &[u8]) -> Result<Option<Vec<u8>>, ProcessError> {
+        // The extent may come after the features
+        let mut extent = DEFAULT_EXTENT;
+        let mut reader = Reader::new(data);
+        while let Some((field, value, _)) = reader.next_field()? {
+            if let (LAYER_EXTENT, Value::Varint(value)) = (field, value) {
+                extent = value;
+            }
+        }
+        let extent = i64::try_from(extent).map_err(|_err| invalid("layer extent is too large"))?;
+
+        let mut out = Vec::with_capacity(data.len());
+        let mut has_features = false;
+        let mut reader = Reader::new(data);
+        while let Some((field, value, raw)) = reader.next_field()? {
+            match (field, value) {
+                (LAYER_FEATURES, Value::Bytes(feature)) => {
+                    if let Some(feature) = self.feature(feature, extent)? {
+                        write_bytes(&mut out, LAYER_FEATURES, &feature);
+                        has_features = true;
+                    }
+                }
+                _ => out.extend_from_slice(raw),
+            }
+        }
+        Ok(has_features.then_some(out))
+    }
+
+    /// Rewrites the geometry of a feature, or returns `None` if nothing of it is left in the tile.
+    fn feature(self, data: &[u8], extent: i64) -> Result<Option<Vec<u8>>, ProcessError> {
+        let mut geom_type = 0;
+        let mut geometry = None;
+        let mut reader = Reader::new(data);
+        while let Some((field, value, _)) = reader.next_field()? {
+            match (field, value) {
+                (FEATURE_TYPE, Value::Varint(value)) => geom_type = value,
+                (FEATURE_GEOMETRY, Value::Bytes(value)) => geometry = Some(value),
+                _ => {}
+            }
+        }
+        let Some(geometry) = geometry else {
+            return Ok(None);
+        };
+
+        let parts: Vec<Vec<[i64; 2]>> = decode_geometry(geometry)?
+            .into_iter()
+            .map(|part| {
+                part.into_iter()
+                    .map(|[x, y]| {
+                        [
+                            x * self.scale - self.col * extent,
+                            y * self.scale - self.row * extent,
+                        ]
+                    })
+                    .collect()
+            })
+            .collect();
+        let buffer = extent / BUFFER_DIVISOR;
+        let bounds = Bounds {
+            min: -buffer,
+            max: extent + buffer,
+        };
+        let parts = match geom_type {
+            POINT => parts
+                .into_iter()
+                .flatten()
+                .filter(|point| bounds.contains(*point))
+                .map(|point| vec![point])
+                .collect(),
+            LINESTRING => parts
+                .iter()
+                .flat_map(|line| bounds.clip_line(line))
+                .collect(),
+            POLYGON => bounds.clip_polygon(&parts),
+            _ => return Ok(None),
+        };
+        if parts.is_empty() {
+            return Ok(None);
+        }
+
+        let mut out = Vec::with_capacity(data.len());
+        let mut reader = Reader::new(data);
+        while let Some((field, _, raw)) = reader.next_field()? {
+            if field == FEATURE_GEOMETRY {
+                write_bytes(
+                    &mut out,
+                    FEATURE_GEOMETRY,
+                    &encode_geometry(geom_type, &parts),
+                );
+            } else {
+                out.extend_from_slice(raw);
+            }
+        }
+        Ok(Some(out))
+    }
+}
+
+/// The square that features are clipped to, in tile coordinates.
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    min: i64,
+    max: i64,
+}
+
+impl Bounds {
+    fn contains(self, [x, y]: [i64; 2]) -> bool {
+        (self.min..=self.max).contains(&x) && (self.min..=self.max).contains(&y)
+    }
+
+    /// Splits a line into the parts of it within the bounds.
+    fn clip_line(self, line: &[[i64; 2]]) -> Vec<Vec<[i64; 2]>> {
+        let mut lines = Vec::new();
+        let mut current: Vec<[f64; 2]> = Vec::new();
+        for segment in line.windows(2) {
+            let Some((start, end, entered, left)) = self.clip_segment(segment[0], segment[1])
+            else {
+                lines.push(std::mem::take(&mut current));
+                continue;
+            };
+            if current.is_empty() || entered {
+                lines.push(std::mem::take(&mut current));
+                current.push(start);
+            }
+            current.push(end);
+            if left {
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+        lines.push(current);
+        lines
+            .into_iter()
+            .map(|line| round_points(&line))
+            .filter(|line| line.len() >= 2)
+            .collect()
+    }
+
+    /// Clips a segment with the Liang-Barsky algorithm.
+    /// Returns the clipped segment and whether its start or end were moved to the bounds.
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "tile coordinates are far smaller than 2^52"
+    )]
+    fn clip_segment(
+        self,
+        [x0, y0]: [i64; 2],
+        [x1, y1]: [i64; 2],
+    ) -> Option<([f64; 2], [f64; 2], bool, bool)> {
+        let (min, max) = (self.min as f64, self.max as f64);
+        let (x0, y0, x1, y1) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let mut t0 = 0.0_f64;
+        let mut t1 = 1.0_f64;
+        for (p, q) in [
+            (-dx, x0 - min),
+            (dx, max - x0),
+            (-dy, y0 - min),
+            (dy, max - y0),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else {
+                let t = q / p;
+                if p < 0.0 {
+                    t0 = t0.max(t);
+                } else {
+                    t1 = t1.min(t);
+                }
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+        Some((
+            [x0 + t0 * dx, y0 + t0 * dy],
+            [x0 + t1 * dx, y0 + t1 * dy],
+            t0 > 0.0,
+            t1 < 1.0,
+        ))
+    }
+
+    /// Clips the rings of a polygon with the Sutherland-Hodgman algorithm.
+    /// Interior rings are dropped along with the exterior ring they belong to.
+    fn clip_polygon(self, rings: &[Vec<[i64; 2]>]) -> Vec<Vec<[i64; 2]>> {
+        let mut clipped = Vec::new();
+        let mut keep_interior = false;
+        for ring in rings {
+            let area = ring_area(ring);
+            let is_exterior = area > 0;
+            if !is_exterior && !keep_interior {
+                continue;
+            }
+            let mut ring = round_points(&self.clip_ring(ring));
+            if ring.len() > 1 && ring.first() == ring.last() {
+                ring.pop();
+            }
+            // Rounding may collapse the ring or flip its orientation
+            let keep = ring.len() >= 3 && ring_area(&ring).signum() == area.signum();
+            if is_exterior {
+                keep_interior = keep;
+            }
+            if keep {
+                clipped.push(ring);
+            }
+        }
+        clipped
+    }
+
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "tile coordinates are far smaller than 2^52"
+    )]
+    fn clip_ring(self, ring: &[[i64; 2]]) -> Vec<[f64; 2]> {
+        let (min, max) = (self.min as f64, self.max as f64);
+        let mut points: Vec<[f64; 2]> = ring.iter().map(|&[x, y]| [x as f64, y as f64]).collect();
+        for (axis, bound, keep_above) in [
+            (0, min, true),
+            (0, max, false),
+            (1, min, true),
+            (1, max, false),
+        ] {
+            let Some(&last) = points.last() else {
+                break;
+            };
+            let inside = |p: [f64; 2]| {
+                if keep_above {
+                    p[axis] >= bound
+                } else {
+                    p[axis] <= bound
+                }
+            };
+            let mut clipped = Vec::with_capacity(points.len() + 4);
+            let mut prev = last;
+            for &point in &points {
+                if inside(point) {
+                    if !inside(prev) {
+                        clipped.push(intersect(prev, point, axis, bound));
+                    }
+                    clipped.push(point);
+                } else if inside(prev) {
+                    clipped.push(intersect(prev, point, axis, bound));
+                }
+                prev = point;
+            }
+            points = clipped;
+        }
+        points
+    }
+}
+
+/// The point where the segment from `a` to `b` crosses `bound` along `axis`.
+fn intersect(a: [f64; 2], b: [f64; 2], axis: usize, bound: f64) -> [f64; 2] {
+    let t = (bound - a[axis]) / (b[axis] - a[axis]);
+    let mut point = [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+    point[axis] = bound;
+    point
+}
+
+/// Rounds points to tile coordinates, dropping repeated points.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "clipped coordinates are within the bounds of the tile"
+)]
+fn round_points(points: &[[f64; 2]]) -> Vec<[i64; 2]> {
+    let mut rounded: Vec<[i64; 2]> = Vec::with_capacity(points.len());
+    for &[x, y] in points {
+        let point = [x.round() as i64, y.round() as i64];
+        if rounded.last() != Some(&point) {
+            rounded.push(point);
+        }
+    }
+    rounded
+}
+
+/// Twice the area of a ring, positive for exterior rings as defined by the specification.
+fn ring_area(ring: &[[i64; 2]]) -> i64 {
+    let mut area = 0;
+    for (i, &[x0, y0]) in ring.iter().enumerate() {
+        let [x1, y1] = ring[(i + 1) % ring.len()];
+        area += x0 * y1 - x1 * y0;
+    }
+    area
+}
+
+/// Decodes the geometry commands of a feature into its points, lines or rings.
+/// Every point of a `MoveTo` starts a new part.
+fn decode_geometry(data: &[u8]) -> Result<Vec<Vec<[i64; 2]>>, ProcessError> {
+    let mut parts: Vec<Vec<[i64; 2]>> = Vec::new();
+    let mut reader = Reader::new(data);
+    let (mut x, mut y) = (0_i64, 0_i64);
+    while !reader.is_empty() {
+        let command = reader.varint()?;
+        let count = command >> 3;
+        match command & 0x7 {
+            MOVE_TO | LINE_TO => {
+                for _ in 0..count {
+                    x += zigzag_decode(reader.varint()?);
+                    y += zigzag_decode(reader.varint()?);
+                    if command & 0x7 == MOVE_TO {
+                        parts.push(vec![[x, y]]);
+                    } else {
+                        parts
+                            .last_mut()
+                            .ok_or_else(|| invalid("LineTo without MoveTo"))?
+                            .push([x, y]);
+                    }
+                }
+            }
+            CLOSE_PATH => {}
+            _ => return Err(invalid("unknown geometry command")),
+        }
+    }
+    Ok(parts)
+}
+
+/// Encodes points, lines or rings as geometry commands.
+fn encode_geometry(geom_type: u64, parts: &[Vec<[i64; 2]>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut cursor = [0, 0];
+    let mut write_point = |out: &mut Vec<u8>, point: [i64; 2]| {
+        write_varint(out, zigzag_encode(point[0] - cursor[0]));
+        write_varint(out, zigzag_encode(point[1] - cursor[1]));
+        cursor = point;
+    };
+    if geom_type == POINT {
+        write_varint(&mut out, command(MOVE_TO, parts.len()));
+        for part in parts {
+            write_point(&mut out, part[0]);
+        }
+        return out;
+    }
+    for part in parts {
+        write_varint(&mut out, command(MOVE_TO, 1));
+        write_point(&mut out, part[0]);
+        write_varint(&mut out, command(LINE_TO, part.len() - 1));
+        for &point in &part[1..] {
+            write_point(&mut out, point);
+        }
+        if geom_type == POLYGON {
+            write_varint(&mut out, command(CLOSE_PATH, 1));
+        }
+    }
+    out
+}
+
+fn command(id: u64, count: usize) -> u64 {
+    id | (count as u64) << 3
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    (value >> 1).cast_signed() ^ -(value & 1).cast_signed()
+}
+
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)).cast_unsigned()
+}
+
+fn invalid(reason: &str) -> ProcessError {
+    ProcessError::Overzoom(format!("invalid vector tile: {reason}"))
+}
+
+#[cfg(feature = "raster-overzoom")]
+fn overzoom_error(e: &image::ImageError) -> ProcessError {
+    ProcessError::Overzoom(e.to_string())
+}
+
+impl From<ProtobufError> for ProcessError {
+    fn from(e: ProtobufError) -> Self {
+        invalid(e.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::protobuf::write_varint_field;
+
+    use super::*;
+
+    /// Encodes a tile with a single layer holding one feature.
+    fn vector_tile(geom_type: u64, parts: &[Vec<[i64; 2]>]) -> Vec<u8> {
+        let mut feature = Vec::new();
+        write_varint_field(&mut feature, FEATURE_TYPE, geom_type);
+        write_bytes(
+            &mut feature,
+            FEATURE_GEOMETRY,
+            &encode_geometry(geom_type, parts),
+        );
+        let mut layer = Vec::new();
+        write_bytes(&mut layer, 1, b"layer");
+        write_bytes(&mut layer, LAYER_FEATURES, &feature);
+        let mut tile = Vec::new();
+        write_bytes(&mut tile, TILE_LAYERS, &layer);
+        tile
+    }
+
+    /// Decodes the geometry of the first feature of each layer.
+    fn geometries(tile: &[u8]) -> Vec<Vec<Vec<[i64; 2]>>> {
+        let mut geometries = Vec::new();
+        let mut reader = Reader::new(tile);
+        while let Some((_, Value::Bytes(layer), _)) = reader.next_field().unwrap() {
+            let mut reader = Reader::new(layer);
+            while let Some((field, value, _)) = reader.next_field().unwrap() {
+                if let (LAYER_FEATURES, Value::Bytes(feature)) = (field, value) {
+                    let mut reader = Reader::new(feature);
+                    while let Some((field, value, _)) = reader.next_field().unwrap() {
+                        if let (FEATURE_GEOMETRY, Value::Bytes(geometry)) = (field, value) {
+                            geometries.push(decode_geometry(geometry).unwrap());
+                        }
+                    }
+                }
+            }
+        }
+        geometries
+    }
+
+    fn mvt(data: Vec<u8>) -> Tile {
+        Tile::new_hash_etag(data, TileInfo::new(Format::Mvt, Encoding::Uncompressed))
+    }
+
+    #[test]
+    fn ancestor_tile() {
+        let xyz = TileCoord { z: 5, x: 13, y: 22 };
+        assert_eq!(ancestor(xyz, 3), TileCoord { z: 3, x: 3, y: 5 });
+        assert_eq!(ancestor(xyz, 5), xyz);
+    }
+
+    #[test]
+    fn clips_lines_to_child_tile() {
+        // A horizontal line across the whole parent tile
+        let data = vector_tile(LINESTRING, &[vec![[0, 1000], [4096, 1000]]]);
+        let xyz = TileCoord { z: 1, x: 1, y: 0 };
+        let tile = overzoom_tile(mvt(data), xyz, 0).unwrap();
+        assert_eq!(
+            tile.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+        assert!(tile.etag.ends_with("+1-1-0"));
+        assert_eq!(
+            geometries(&tile.data),
+            vec![vec![vec![[-64, 2000], [4096, 2000]]]]
+        );
+
+        // The line is entirely in the top half of the parent tile
+        let xyz = TileCoord { z: 1, x: 1, y: 1 };
+        let data = vector_tile(LINESTRING, &[vec![[0, 1000], [4096, 1000]]]);
+        assert!(overzoom_tile(mvt(data), xyz, 0).unwrap().data.is_empty());
+    }
+
+    #[test]
+    fn drops_points_outside_child_tile() {
+        let data = vector_tile(POINT, &[vec![[100, 100]], vec![[3000, 100]]]);
+        let xyz = TileCoord { z: 1, x: 0, y: 0 };
+        let tile = overzoom_tile(mvt(data), xyz, 0).unwrap();
+        assert_eq!(geometries(&tile.data), vec![vec![vec![[200, 200]]]]);
+    }
+
+    #[test]
+    fn clips_polygons_to_child_tile() {
+        // A square covering the parent tile, with a hole in its bottom right quarter
+        let exterior = vec![[0, 0], [4096, 0], [4096, 4096], [0, 4096]];
+        let interior = vec![[3000, 3000], [3000, 3500], [3500, 3500], [3500, 3000]];
+        let data = vector_tile(POLYGON, &[exterior, interior]);
+
+        let tile = overzoom_tile(mvt(data.clone()), TileCoord { z: 1, x: 0, y: 0 }, 0).unwrap();
+        let rings = &geometries(&tile.data)[0];
+        assert_eq!(rings.len(), 1);
+        assert!(
+            rings[0]
+                .iter()
+                .all(|&[x, y]| (-64..=4160).contains(&x) && (-64..=4160).contains(&y))
+        );
+
+        let tile = overzoom_tile(mvt(data), TileCoord { z: 1, x: 1, y: 1 }, 0).unwrap();
+        assert_eq!(geometries(&tile.data)[0].len(), 2);
+    }
+
+    #[cfg(feature = "raster-overzoom")]
+    #[test]
+    fn crops_raster_tiles() {
+        let mut image = image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 0, 255]));
+        image.put_pixel(3, 3, image::Rgba([255, 255, 255, 255]));
+        let mut png = Cursor::new(Vec::new());
+        image.write_to(&mut png, ImageFormat::Png).unwrap();
+        let tile = Tile::new_hash_etag(
+            png.into_inner(),
+            TileInfo::new(Format::Png, Encoding::Internal),
+        );
+
+        let tile = overzoom_tile(tile, TileCoord { z: 2, x: 3, y: 3 }, 0).unwrap();
+        assert_eq!(tile.info.format, Format::Png);
+        let image = image::load_from_memory(&tile.data).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (4, 4));
+        assert!(
+            image
+                .pixels()
+                .all(|p| *p == image::Rgba([255, 255, 255, 255]))
+        );
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let tile = Tile::new_hash_etag(
+            vec![1, 2, 3],
+            TileInfo::new(Format::Json, Encoding::Uncompressed),
+        );
+        overzoom_tile(tile, TileCoord { z: 1, x: 0, y: 0 }, 0).unwrap_err();
+    }
+}
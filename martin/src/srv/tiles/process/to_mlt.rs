@@ -1,6 +1,7 @@
 use martin_core::tiles::Tile;
 use martin_tile_utils::Format;
 use mlt_core::encoder::EncoderConfig;
+use tracing::instrument;
 
 use crate::srv::tiles::content;
 use crate::srv::tiles::process::ProcessError;
@@ -14,9 +15,14 @@ use crate::srv::tiles::process::ProcessError;
 /// re-hashing the (potentially large) converted bytes. This keeps the converted
 /// etag distinct from the original so the client->martin 304 path stays correct
 /// while passthrough sources can surface an upstream `ETag` verbatim.
+#[instrument(level = "debug", skip_all, fields(tile.size = tile.data.len()), err(Debug))]
 pub fn convert_mvt_to_mlt(tile: Tile, cfg: EncoderConfig) -> Result<Tile, ProcessError> {
     use martin_tile_utils::{Encoding, TileInfo};
 
+    #[cfg(feature = "metrics")]
+    let _timer = martin_core::metrics::TILE_CONVERSION_SECONDS
+        .with_label_values(&["mvt", "mlt"])
+        .start_timer();
     let etag = format!("{}+mlt", tile.etag);
     let decoded =
         content::decode(tile).map_err(|e| ProcessError::DecompressionFailed(e.to_string()))?;
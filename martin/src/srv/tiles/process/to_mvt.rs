@@ -2,6 +2,7 @@ use martin_core::tiles::Tile;
 use martin_tile_utils::{Encoding, Format, TileInfo};
 use mlt_core::mvt::tile_layers_to_mvt;
 use mlt_core::{Decoder, Layer, Parser};
+use tracing::instrument;
 
 use crate::srv::tiles::content;
 use crate::srv::tiles::process::ProcessError;
@@ -13,7 +14,12 @@ use crate::srv::tiles::process::ProcessError;
 ///
 /// The output keeps the source tile's etag with a `+mvt` suffix rather than
 /// re-hashing the converted bytes, mirroring [`convert_mvt_to_mlt`](super::to_mlt::convert_mvt_to_mlt).
+#[instrument(level = "debug", skip_all, fields(tile.size = tile.data.len()), err(Debug))]
 pub fn convert_mlt_to_mvt(tile: Tile) -> Result<Tile, ProcessError> {
+    #[cfg(feature = "metrics")]
+    let _timer = martin_core::metrics::TILE_CONVERSION_SECONDS
+        .with_label_values(&["mlt", "mvt"])
+        .start_timer();
     let etag = format!("{}+mvt", tile.etag);
     let mlt =
         content::decode(tile).map_err(|e| ProcessError::DecompressionFailed(e.to_string()))?;
@@ -0,0 +1,172 @@
+use actix_web::error::{ErrorBadRequest, ErrorNotFound, ErrorPayloadTooLarge, InternalError};
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::web::{Data, Path, Payload};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, route};
+use martin_core::tiles::mbtiles::{MbtWriter, MbtWriters};
+use martin_tile_utils::{TileCoord, TileInfo};
+use serde::Deserialize;
+use tracing::{info, instrument};
+
+use crate::srv::server::map_internal_error;
+use crate::tile_source_manager::TileSourceManager;
+
+/// Largest tile accepted by [`put_tile`].
+const MAX_TILE_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Deserialize, Debug)]
+#[cfg_attr(feature = "unstable-schemas", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "unstable-schemas", into_params(parameter_in = Path))]
+pub struct TileWriteRequest {
+    /// ID of the `MBTiles` source
+    source_id: String,
+    z: u8,
+    x: u32,
+    y: u32,
+}
+
+/// Replace or add a single tile of a writable `MBTiles` source.
+#[cfg_attr(
+    feature = "unstable-schemas",
+    utoipa::path(
+        put,
+        path = "/{source_id}/{z}/{x}/{y}",
+        params(TileWriteRequest),
+        request_body(content = Vec<u8>, description = "Tile in the format and encoding of the source", content_type = "application/octet-stream"),
+        responses(
+            (status = 204, description = "The tile was written"),
+            (status = 400, description = "Invalid tile coordinates, or a tile in another format than the source"),
+            (status = 401, description = "Missing or wrong bearer token"),
+            (status = 404, description = "The source does not accept tile writes"),
+            (status = 413, description = "The tile is too large"),
+        ),
+    )
+)]
+#[route("/{source_id}/{z}/{x}/{y}", method = "PUT")]
+#[hotpath::measure]
+#[instrument(
+    level = "debug",
+    skip_all,
+    fields(source.id = %path.source_id, tile.z = path.z, tile.x = path.x, tile.y = path.y),
+)]
+pub async fn put_tile(
+    req: HttpRequest,
+    path: Path<TileWriteRequest>,
+    body: Payload,
+    writers: Data<MbtWriters>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let (writer, xyz) = authorize(&req, &path, &writers)?;
+    let data = body
+        .to_bytes_limited(MAX_TILE_SIZE)
+        .await
+        .map_err(|_err| {
+            ErrorPayloadTooLarge(format!("Tiles are limited to {MAX_TILE_SIZE} bytes"))
+        })??;
+    if data.is_empty() {
+        return Err(ErrorBadRequest(
+            "The tile is empty, use DELETE to remove it",
+        ));
+    }
+
+    let (source, _) = manager.tile_sources().get_source(&path.source_id)?;
+    let expected = source.get_tile_info();
+    let info = TileInfo::detect(&data);
+    if info != expected {
+        return Err(ErrorBadRequest(format!(
+            "The tile is {info}, but source {} has {expected} tiles",
+            path.source_id
+        )));
+    }
+
+    writer
+        .put_tile(xyz, &data)
+        .await
+        .map_err(map_internal_error)?;
+    invalidate_tile(&manager, &path.source_id, xyz).await;
+    info!("Wrote tile {xyz:#} of {}", path.source_id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Delete a single tile of a writable `MBTiles` source.
+#[cfg_attr(
+    feature = "unstable-schemas",
+    utoipa::path(
+        delete,
+        path = "/{source_id}/{z}/{x}/{y}",
+        params(TileWriteRequest),
+        responses(
+            (status = 204, description = "The tile was deleted"),
+            (status = 400, description = "Invalid tile coordinates"),
+            (status = 401, description = "Missing or wrong bearer token"),
+            (status = 404, description = "No such tile, or the source does not accept tile writes"),
+        ),
+    )
+)]
+#[route("/{source_id}/{z}/{x}/{y}", method = "DELETE")]
+#[hotpath::measure]
+#[instrument(
+    level = "debug",
+    skip_all,
+    fields(source.id = %path.source_id, tile.z = path.z, tile.x = path.x, tile.y = path.y),
+)]
+pub async fn delete_tile(
+    req: HttpRequest,
+    path: Path<TileWriteRequest>,
+    writers: Data<MbtWriters>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let (writer, xyz) = authorize(&req, &path, &writers)?;
+    if !writer.delete_tile(xyz).await.map_err(map_internal_error)? {
+        return Err(ErrorNotFound(format!("Tile {xyz:#} does not exist")));
+    }
+    invalidate_tile(&manager, &path.source_id, xyz).await;
+    info!("Deleted tile {xyz:#} of {}", path.source_id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Checks the bearer token, and finds the writer of the source.
+fn authorize<'a>(
+    req: &HttpRequest,
+    path: &TileWriteRequest,
+    writers: &'a MbtWriters,
+) -> ActixResult<(&'a MbtWriter, TileCoord)> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim());
+    if !token.is_some_and(|token| writers.is_authorized(token)) {
+        return Err(InternalError::from_response(
+            "Missing or wrong bearer token",
+            HttpResponse::Unauthorized()
+                .insert_header((WWW_AUTHENTICATE, "Bearer"))
+                .body("Missing or wrong bearer token"),
+        )
+        .into());
+    }
+    let Some(writer) = writers.get(&path.source_id) else {
+        return Err(ErrorNotFound(format!(
+            "Source {} does not accept tile writes",
+            path.source_id
+        )));
+    };
+    let xyz = TileCoord::new_checked(path.z, path.x, path.y)
+        .ok_or_else(|| ErrorBadRequest("Invalid tile coordinates"))?;
+    Ok((writer, xyz))
+}
+
+/// Drops the cached and in-flight versions of the tile, so that the next request reads the new one.
+///
+/// Must run after the write is committed: reads started before that may still return the old tile,
+/// but neither cache it nor share it with later requests.
+async fn invalidate_tile(manager: &TileSourceManager, source_id: &str, xyz: TileCoord) {
+    if let Some(cache) = manager.tile_cache() {
+        cache.invalidate_tile(source_id, xyz);
+    }
+    manager.tile_flights().invalidate_tile(source_id, xyz);
+    if let Some(cache) = manager.tile_cache() {
+        cache.run_pending_tasks().await;
+    }
+}
@@ -0,0 +1,276 @@
+//! HTTPS support: a rustls server configuration whose certificate is reloaded
+//! from disk when the certificate or private key files change.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rustls::crypto::{CryptoProvider, aws_lc_rs};
+use rustls::pki_types::pem::{self, PemObject as _};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::version::{TLS12, TLS13};
+use rustls::{ServerConfig, SupportedProtocolVersion};
+use tracing::{info, warn};
+
+use crate::config::file::tls::{TlsConfig, TlsVersion};
+
+/// Errors loading the TLS certificate and private key
+#[derive(thiserror::Error, Debug)]
+pub enum TlsError {
+    #[error("Unable to read {1}: {0}")]
+    Io(#[source] io::Error, PathBuf),
+
+    #[error("Invalid PEM file {1}: {0}")]
+    Pem(#[source] pem::Error, PathBuf),
+
+    #[error("No certificates found in {0}")]
+    NoCertificates(PathBuf),
+
+    #[error("No private key found in {0}")]
+    NoPrivateKey(PathBuf),
+
+    #[error("Invalid TLS certificate or private key: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// Modification times of the certificate and key files, used to detect renewals
+type FileTimes = (Option<SystemTime>, Option<SystemTime>);
+
+/// Serves the certificate loaded from `cert` and `key`, swapping it when the files change.
+#[derive(Debug)]
+pub struct ReloadingCertResolver {
+    cert: PathBuf,
+    key: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    loaded: Mutex<FileTimes>,
+}
+
+impl ReloadingCertResolver {
+    /// Load the certificate chain and private key, failing if either is invalid
+    pub fn new(cert: &Path, key: &Path, provider: Arc<CryptoProvider>) -> Result<Self, TlsError> {
+        let loaded = file_times(cert, key);
+        let current = load_certified_key(cert, key, &provider)?;
+        Ok(Self {
+            cert: cert.to_path_buf(),
+            key: key.to_path_buf(),
+            provider,
+            current: RwLock::new(Arc::new(current)),
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    /// Reload the certificate if the files were modified since they were last loaded.
+    ///
+    /// Returns `Ok(true)` if a new certificate is now in use.
+    /// On error, the previous certificate stays in use.
+    pub fn reload_if_changed(&self) -> Result<bool, TlsError> {
+        let times = file_times(&self.cert, &self.key);
+        let mut loaded = self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+        if *loaded == times {
+            return Ok(false);
+        }
+        // Remember the attempt even if it fails, so a broken file is only reported once
+        *loaded = times;
+        let certified_key = load_certified_key(&self.cert, &self.key, &self.provider)?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(certified_key);
+        Ok(true)
+    }
+
+    /// Periodically check the files for changes. Never returns.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        // the first tick completes immediately, the certificate was just loaded
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match self.reload_if_changed() {
+                Ok(true) => info!("Reloaded TLS certificate from {}", self.cert.display()),
+                Ok(false) => {}
+                Err(e) => warn!("Keeping the previous TLS certificate: {e}"),
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        )
+    }
+}
+
+/// Build the rustls server configuration and the resolver serving its certificate
+pub fn server_config(
+    tls: &TlsConfig,
+) -> Result<(ServerConfig, Arc<ReloadingCertResolver>), TlsError> {
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let resolver = Arc::new(ReloadingCertResolver::new(
+        &tls.cert,
+        &tls.key,
+        Arc::clone(&provider),
+    )?);
+    let versions: &[&SupportedProtocolVersion] = match tls.min_version {
+        TlsVersion::Tls12 => &[&TLS13, &TLS12],
+        TlsVersion::Tls13 => &[&TLS13],
+    };
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(versions)?
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::clone(&resolver) as Arc<dyn ResolvesServerCert>);
+    Ok((config, resolver))
+}
+
+fn file_times(cert: &Path, key: &Path) -> FileTimes {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    (modified(cert), modified(key))
+}
+
+fn pem_error(error: pem::Error, path: &Path) -> TlsError {
+    match error {
+        pem::Error::Io(e) => TlsError::Io(e, path.to_path_buf()),
+        e => TlsError::Pem(e, path.to_path_buf()),
+    }
+}
+
+fn load_certified_key(
+    cert: &Path,
+    key: &Path,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, TlsError> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_err(|e| pem_error(e, cert))?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates(cert.to_path_buf()));
+    }
+    let private_key = PrivateKeyDer::from_pem_file(key).map_err(|e| match e {
+        pem::Error::NoItemsFound => TlsError::NoPrivateKey(key.to_path_buf()),
+        e => pem_error(e, key),
+    })?;
+    let signing_key = provider.key_provider.load_private_key(private_key)?;
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    certified_key.keys_match()?;
+    Ok(certified_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rcgen::{CertifiedKey as GeneratedCert, generate_simple_self_signed};
+
+    use super::*;
+
+    fn generate(name: &str) -> GeneratedCert<rcgen::KeyPair> {
+        generate_simple_self_signed(vec![name.to_owned()]).unwrap()
+    }
+
+    fn write(dir: &Path, generated: &GeneratedCert<rcgen::KeyPair>) -> (PathBuf, PathBuf) {
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+        fs::write(&cert, generated.cert.pem()).unwrap();
+        fs::write(&key, generated.signing_key.serialize_pem()).unwrap();
+        (cert, key)
+    }
+
+    fn served_cert(resolver: &ReloadingCertResolver) -> CertificateDer<'static> {
+        resolver.current.read().unwrap().cert[0].clone()
+    }
+
+    #[test]
+    fn reloads_renewed_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = generate("localhost");
+        let (cert, key) = write(dir.path(), &first);
+        let tls = TlsConfig::new(cert.clone(), key.clone());
+        let (_config, resolver) = server_config(&tls).unwrap();
+        assert_eq!(served_cert(&resolver), *first.cert.der());
+        assert!(!resolver.reload_if_changed().unwrap());
+
+        let second = generate("localhost");
+        write(dir.path(), &second);
+        // make sure the change is detected even on filesystems with coarse timestamps
+        *resolver.loaded.lock().unwrap() = (None, None);
+        assert!(resolver.reload_if_changed().unwrap());
+        assert_eq!(served_cert(&resolver), *second.cert.der());
+
+        // a broken renewal keeps the previous certificate
+        fs::write(&key, "not a key").unwrap();
+        *resolver.loaded.lock().unwrap() = (None, None);
+        let err = resolver.reload_if_changed().unwrap_err();
+        assert!(matches!(err, TlsError::NoPrivateKey(_)), "{err}");
+        assert_eq!(served_cert(&resolver), *second.cert.der());
+    }
+
+    #[test]
+    fn rejects_mismatched_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = write(dir.path(), &generate("localhost"));
+        fs::write(&key, generate("other").signing_key.serialize_pem()).unwrap();
+        let err = server_config(&TlsConfig::new(cert, key)).unwrap_err();
+        assert!(matches!(err, TlsError::Rustls(_)), "{err}");
+    }
+
+    #[test]
+    fn rejects_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        let err = server_config(&TlsConfig::new(missing.clone(), missing)).unwrap_err();
+        assert!(matches!(err, TlsError::Io(..)), "{err}");
+    }
+
+    /// Run a handshake in memory with a client only offering the given TLS versions
+    fn handshake(
+        server_config: ServerConfig,
+        cert: &CertificateDer<'static>,
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> Result<(), rustls::Error> {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert.clone()).unwrap();
+        let client_config =
+            rustls::ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+                .with_protocol_versions(versions)
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        let server_name = "localhost".try_into().unwrap();
+        let mut client = rustls::ClientConnection::new(Arc::new(client_config), server_name)?;
+        let mut server = rustls::ServerConnection::new(Arc::new(server_config))?;
+        while client.is_handshaking() || server.is_handshaking() {
+            let mut buf = Vec::new();
+            client.write_tls(&mut buf).unwrap();
+            server.read_tls(&mut buf.as_slice()).unwrap();
+            server.process_new_packets()?;
+            let mut buf = Vec::new();
+            server.write_tls(&mut buf).unwrap();
+            client.read_tls(&mut buf.as_slice()).unwrap();
+            client.process_new_packets()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn min_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let generated = generate("localhost");
+        let (cert, key) = write(dir.path(), &generated);
+        let mut tls = TlsConfig::new(cert, key);
+
+        let (config, _) = server_config(&tls).unwrap();
+        handshake(config, generated.cert.der(), &[&TLS12]).unwrap();
+
+        tls.min_version = TlsVersion::Tls13;
+        let (config, _) = server_config(&tls).unwrap();
+        handshake(config.clone(), generated.cert.der(), &[&TLS13]).unwrap();
+        let err = handshake(config, generated.cert.der(), &[&TLS12]).unwrap_err();
+        assert!(matches!(err, rustls::Error::PeerIncompatible(_)), "{err}");
+    }
+}
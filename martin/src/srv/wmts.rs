@@ -0,0 +1,584 @@
+//! [OGC WMTS 1.0](https://www.ogc.org/standard/wmts/) endpoints mounted under `/wmts`.
+//!
+//! The capabilities document is generated from the tile catalog, with every source
+//! published as a layer in its tile matrix set. `WebMercatorQuad` is published as `GoogleMapsCompatible`.
+//! WMTS 1.0 only supports top-left tile origins, so sources in other tile matrix sets are left out.
+//! `GetTile` requests, both KVP and REST, are served by the regular tile handler.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+use actix_web::http::header::ContentType;
+use actix_web::web::{Data, Path};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, Scope, route, web};
+use martin_core::tiles::BoxedSource;
+use martin_tile_utils::tms::{CornerOfOrigin, TileMatrixSet, WEB_MERCATOR_QUAD};
+use martin_tile_utils::{Format, TileCoord};
+use serde::Deserialize;
+
+use crate::config::file::ProcessConfig;
+use crate::config::file::srv::SrvConfig;
+use crate::srv::server::path_prefix;
+use crate::srv::tiles::content::{DynTileSource, TileRequestHeaders};
+use crate::srv::tiles::metadata::source_zoom_range;
+use crate::tile_source_manager::TileSourceManager;
+
+/// Identifier of `WebMercatorQuad`, as defined by the WMTS standard (Annex E.4)
+const GOOGLE_MAPS_COMPATIBLE: &str = "GoogleMapsCompatible";
+/// The only style of every layer
+const DEFAULT_STYLE: &str = "default";
+
+/// All WMTS routes, to be registered before the generic `/{source_ids}/...` routes.
+pub fn scope() -> Scope {
+    web::scope("/wmts")
+        .service(get_kvp)
+        .service(get_capabilities_rest)
+        .service(get_tile_rest)
+}
+
+/// Errors reported as an OWS `ExceptionReport` document
+#[derive(Debug)]
+struct OwsException {
+    code: &'static str,
+    locator: Option<&'static str>,
+    text: String,
+}
+
+impl OwsException {
+    fn missing(param: &'static str) -> Self {
+        Self {
+            code: "MissingParameterValue",
+            locator: Some(param),
+            text: format!("Missing parameter {param}"),
+        }
+    }
+
+    fn invalid(param: &'static str, text: String) -> Self {
+        Self {
+            code: "InvalidParameterValue",
+            locator: Some(param),
+            text,
+        }
+    }
+
+    fn response(&self) -> HttpResponse {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        xml.push_str(
+            r#"<ows:ExceptionReport xmlns:ows="http://www.opengis.net/ows/1.1" version="2.0.0">"#,
+        );
+        let _ = write!(xml, r#"<ows:Exception exceptionCode="{}""#, self.code);
+        if let Some(locator) = self.locator {
+            let _ = write!(xml, r#" locator="{locator}""#);
+        }
+        let _ = write!(
+            xml,
+            "><ows:ExceptionText>{}</ows:ExceptionText></ows:Exception></ows:ExceptionReport>",
+            escape(&self.text)
+        );
+        let mut response = if self.code == "TileOutOfRange" {
+            HttpResponse::NotFound()
+        } else {
+            HttpResponse::BadRequest()
+        };
+        response.content_type(ContentType::xml()).body(xml)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WmtsTileRequest {
+    layer: String,
+    style: String,
+    tms: String,
+    tile_matrix: String,
+    tile_row: String,
+    tile_col: String,
+}
+
+/// KVP endpoint: `/wmts?SERVICE=WMTS&REQUEST=GetCapabilities|GetTile&...`
+///
+/// Parameter names are case-insensitive, as required by OWS Common.
+#[route("", method = "GET", method = "HEAD")]
+async fn get_kvp(
+    req: HttpRequest,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    let params: HashMap<String, String> =
+        url::form_urlencoded::parse(req.query_string().as_bytes())
+            .map(|(k, v)| (k.to_ascii_lowercase(), v.into_owned()))
+            .collect();
+    match parse_kvp(&params) {
+        Ok(None) => Ok(capabilities_response(&req, &srv_config, &manager)),
+        Ok(Some(tile)) => {
+            let format = params.get("format").map(String::as_str);
+            get_tile(&req, &srv_config, &manager, &tile, format).await
+        }
+        Err(e) => Ok(e.response()),
+    }
+}
+
+/// Parse a KVP request: `None` for `GetCapabilities`, the tile parameters for `GetTile`
+fn parse_kvp(params: &HashMap<String, String>) -> Result<Option<WmtsTileRequest>, OwsException> {
+    let get = |name: &'static str| {
+        params
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+            .ok_or_else(|| OwsException::missing(name))
+    };
+
+    let service = get("SERVICE")?;
+    if !service.eq_ignore_ascii_case("WMTS") {
+        return Err(OwsException::invalid(
+            "SERVICE",
+            format!("Unsupported service {service}, only WMTS is supported"),
+        ));
+    }
+    match get("REQUEST")? {
+        request if request.eq_ignore_ascii_case("GetCapabilities") => Ok(None),
+        request if request.eq_ignore_ascii_case("GetTile") => Ok(Some(WmtsTileRequest {
+            layer: get("LAYER")?.to_owned(),
+            style: get("STYLE")?.to_owned(),
+            tms: get("TILEMATRIXSET")?.to_owned(),
+            tile_matrix: get("TILEMATRIX")?.to_owned(),
+            tile_row: get("TILEROW")?.to_owned(),
+            tile_col: get("TILECOL")?.to_owned(),
+        })),
+        other => Err(OwsException {
+            code: "OperationNotSupported",
+            locator: Some("REQUEST"),
+            text: format!("Operation {other} is not supported"),
+        }),
+    }
+}
+
+/// Capabilities document at its REST location
+#[route("/1.0.0/WMTSCapabilities.xml", method = "GET", method = "HEAD")]
+async fn get_capabilities_rest(
+    req: HttpRequest,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> HttpResponse {
+    capabilities_response(&req, &srv_config, &manager)
+}
+
+/// REST `GetTile`, following the `ResourceURL` template of the capabilities document
+#[route(
+    "/1.0.0/{layer}/{style}/{tms}/{tile_matrix}/{tile_row}/{tile_col}",
+    method = "GET",
+    method = "HEAD"
+)]
+async fn get_tile_rest(
+    req: HttpRequest,
+    path: Path<WmtsTileRequest>,
+    srv_config: Data<SrvConfig>,
+    manager: Data<TileSourceManager>,
+) -> ActixResult<HttpResponse> {
+    get_tile(&req, &srv_config, &manager, &path, None).await
+}
+
+async fn get_tile(
+    req: &HttpRequest,
+    srv_config: &SrvConfig,
+    manager: &TileSourceManager,
+    tile: &WmtsTileRequest,
+    format: Option<&str>,
+) -> ActixResult<HttpResponse> {
+    let xyz = match parse_tile(manager, tile) {
+        Ok(xyz) => xyz,
+        Err(e) => return Ok(e.response()),
+    };
+
+    let mut headers = TileRequestHeaders::from_request(req, srv_config)?;
+    if let Some(format) = format {
+        let parsed = format
+            .split_once('/')
+            .and_then(|(t, s)| Format::from_content_type(t, s));
+        let Some(parsed) = parsed else {
+            return Ok(
+                OwsException::invalid("FORMAT", format!("Unsupported format {format}")).response(),
+            );
+        };
+        headers.accepted_formats = Some(vec![parsed]);
+    }
+
+    let src = DynTileSource::new(manager, &tile.layer, Some(xyz.z), "", headers)?;
+    src.get_http_response(xyz).await
+}
+
+/// Validate the `GetTile` parameters and convert them to a tile coordinate
+fn parse_tile(
+    manager: &TileSourceManager,
+    tile: &WmtsTileRequest,
+) -> Result<TileCoord, OwsException> {
+    // Composite sources are not layers
+    let Ok((src, _)) = manager.tile_sources().get_source(&tile.layer) else {
+        return Err(OwsException::invalid(
+            "LAYER",
+            format!("Unknown layer {}", tile.layer),
+        ));
+    };
+    if tile.style != DEFAULT_STYLE {
+        return Err(OwsException::invalid(
+            "STYLE",
+            format!(
+                "Unknown style {}, only {DEFAULT_STYLE} is supported",
+                tile.style
+            ),
+        ));
+    }
+    let tms = src.get_tile_matrix_set();
+    let tms_id = wmts_tms_id(tms);
+    if !is_wmts_compatible(tms) || tile.tms != tms_id {
+        return Err(OwsException::invalid(
+            "TILEMATRIXSET",
+            format!(
+                "Unknown tile matrix set {} for layer {}",
+                tile.tms, tile.layer
+            ),
+        ));
+    }
+    let z = tms
+        .tile_matrices
+        .iter()
+        .position(|m| m.id == tile.tile_matrix)
+        .and_then(|z| u8::try_from(z).ok())
+        .ok_or_else(|| {
+            OwsException::invalid(
+                "TILEMATRIX",
+                format!("Unknown tile matrix {} in {tms_id}", tile.tile_matrix),
+            )
+        })?;
+    let y = tile.tile_row.parse::<u32>().map_err(|e| {
+        OwsException::invalid(
+            "TILEROW",
+            format!("Invalid tile row {}: {e}", tile.tile_row),
+        )
+    })?;
+    let x = tile.tile_col.parse::<u32>().map_err(|e| {
+        OwsException::invalid(
+            "TILECOL",
+            format!("Invalid tile column {}: {e}", tile.tile_col),
+        )
+    })?;
+    let xyz = TileCoord { z, x, y };
+    if tms.contains(xyz) {
+        Ok(xyz)
+    } else {
+        Err(OwsException {
+            code: "TileOutOfRange",
+            locator: Some("TILEROW"),
+            text: format!(
+                "Tile {}/{y}/{x} is outside of the tile matrix",
+                tile.tile_matrix
+            ),
+        })
+    }
+}
+
+/// Identifier of a tile matrix set in WMTS documents
+fn wmts_tms_id(tms: &TileMatrixSet) -> &str {
+    if tms.id == WEB_MERCATOR_QUAD {
+        GOOGLE_MAPS_COMPATIBLE
+    } else {
+        &tms.id
+    }
+}
+
+/// WMTS 1.0 tile matrices are always addressed from their top-left corner
+fn is_wmts_compatible(tms: &TileMatrixSet) -> bool {
+    tms.tile_matrices
+        .iter()
+        .all(|m| m.corner_of_origin == CornerOfOrigin::TopLeft)
+}
+
+/// WMTS 1.0 uses URNs for coordinate reference systems, e.g. `urn:ogc:def:crs:EPSG::3857`
+fn crs_urn(crs: &str) -> String {
+    match crs.strip_prefix("http://www.opengis.net/def/crs/") {
+        Some(path) => {
+            let mut parts = path.split('/');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(authority), Some(version), Some(code)) => {
+                    let version = if version == "0" { "" } else { version };
+                    format!("urn:ogc:def:crs:{authority}:{version}:{code}")
+                }
+                _ => crs.to_owned(),
+            }
+        }
+        None => crs.to_owned(),
+    }
+}
+
+fn capabilities_response(
+    req: &HttpRequest,
+    srv_config: &SrvConfig,
+    manager: &TileSourceManager,
+) -> HttpResponse {
+    let prefix = path_prefix(req, srv_config);
+    let info = req.connection_info();
+    let base = format!("{}://{}{prefix}/wmts", info.scheme(), info.host());
+
+    let sources = manager.tile_sources();
+    let mut ids = sources.source_names();
+    ids.sort_unstable();
+    let layers: Vec<(String, BoxedSource, ProcessConfig)> = ids
+        .into_iter()
+        .filter_map(|id| sources.get_source(&id).ok().map(|(src, pc)| (id, src, pc)))
+        .filter(|(_, src, _)| is_wmts_compatible(src.get_tile_matrix_set()))
+        .collect();
+
+    HttpResponse::Ok()
+        .content_type(ContentType::xml())
+        .body(capabilities(&base, &layers))
+}
+
+/// Build the WMTS capabilities XML document
+fn capabilities(base: &str, layers: &[(String, BoxedSource, ProcessConfig)]) -> String {
+    // Every tile matrix set used by a layer, with the highest zoom of its layers
+    let mut tile_matrix_sets: BTreeMap<&str, (&TileMatrixSet, u8)> = BTreeMap::new();
+    for (_, src, pc) in layers {
+        let tms = src.get_tile_matrix_set();
+        let max_zoom = source_zoom_range(src, pc).1;
+        tile_matrix_sets
+            .entry(wmts_tms_id(tms))
+            .and_modify(|(_, zoom)| *zoom = (*zoom).max(max_zoom))
+            .or_insert((tms, max_zoom));
+    }
+
+    let mut xml = String::with_capacity(4096);
+    xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+<Capabilities xmlns="http://www.opengis.net/wmts/1.0" xmlns:ows="http://www.opengis.net/ows/1.1" xmlns:xlink="http://www.w3.org/1999/xlink" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:gml="http://www.opengis.net/gml" xsi:schemaLocation="http://www.opengis.net/wmts/1.0 http://schemas.opengis.net/wmts/1.0/wmtsGetCapabilities_response.xsd" version="1.0.0">
+  <ows:ServiceIdentification>
+    <ows:Title>Martin</ows:Title>
+    <ows:ServiceType>OGC WMTS</ows:ServiceType>
+    <ows:ServiceTypeVersion>1.0.0</ows:ServiceTypeVersion>
+  </ows:ServiceIdentification>
+  <ows:OperationsMetadata>
+"#);
+    for operation in ["GetCapabilities", "GetTile"] {
+        let _ = write!(
+            xml,
+            r#"    <ows:Operation name="{operation}">
+      <ows:DCP>
+        <ows:HTTP>
+          <ows:Get xlink:href="{base}?">
+            <ows:Constraint name="GetEncoding">
+              <ows:AllowedValues>
+                <ows:Value>KVP</ows:Value>
+              </ows:AllowedValues>
+            </ows:Constraint>
+          </ows:Get>
+          <ows:Get xlink:href="{base}/1.0.0/">
+            <ows:Constraint name="GetEncoding">
+              <ows:AllowedValues>
+                <ows:Value>RESTful</ows:Value>
+              </ows:AllowedValues>
+            </ows:Constraint>
+          </ows:Get>
+        </ows:HTTP>
+      </ows:DCP>
+    </ows:Operation>
+"#,
+            base = escape(base),
+        );
+    }
+    xml.push_str("  </ows:OperationsMetadata>\n  <Contents>\n");
+
+    for (id, src, pc) in layers {
+        write_layer(&mut xml, base, id, src, pc);
+    }
+
+    for (tms_id, (tms, max_zoom)) in tile_matrix_sets {
+        write_tile_matrix_set(&mut xml, tms_id, tms, max_zoom);
+    }
+    let _ = write!(
+        xml,
+        r#"  </Contents>
+  <ServiceMetadataURL xlink:href="{}/1.0.0/WMTSCapabilities.xml"/>
+</Capabilities>
+"#,
+        escape(base)
+    );
+    xml
+}
+
+fn write_tile_matrix_set(xml: &mut String, tms_id: &str, tms: &TileMatrixSet, max_zoom: u8) {
+    let _ = write!(
+        xml,
+        r"    <TileMatrixSet>
+      <ows:Identifier>{}</ows:Identifier>
+      <ows:SupportedCRS>{}</ows:SupportedCRS>
+",
+        escape(tms_id),
+        escape(&crs_urn(&tms.crs)),
+    );
+    if tms_id == GOOGLE_MAPS_COMPATIBLE {
+        xml.push_str(
+            "      <WellKnownScaleSet>urn:ogc:def:wkss:OGC:1.0:GoogleMapsCompatible</WellKnownScaleSet>\n",
+        );
+    }
+    for matrix in tms.tile_matrices.iter().take(usize::from(max_zoom) + 1) {
+        let _ = write!(
+            xml,
+            r"      <TileMatrix>
+        <ows:Identifier>{}</ows:Identifier>
+        <ScaleDenominator>{}</ScaleDenominator>
+        <TopLeftCorner>{} {}</TopLeftCorner>
+        <TileWidth>{}</TileWidth>
+        <TileHeight>{}</TileHeight>
+        <MatrixWidth>{}</MatrixWidth>
+        <MatrixHeight>{}</MatrixHeight>
+      </TileMatrix>
+",
+            escape(&matrix.id),
+            matrix.scale_denominator,
+            matrix.point_of_origin[0],
+            matrix.point_of_origin[1],
+            matrix.tile_width,
+            matrix.tile_height,
+            matrix.matrix_width,
+            matrix.matrix_height,
+        );
+    }
+    xml.push_str("    </TileMatrixSet>\n");
+}
+
+fn write_layer(xml: &mut String, base: &str, id: &str, src: &BoxedSource, pc: &ProcessConfig) {
+    let tj = src.get_tilejson();
+    let bounds = tj.bounds.unwrap_or_default();
+    let (min_zoom, max_zoom) = source_zoom_range(src, pc);
+    let format = src.get_tile_info().format;
+    let content_type = format.content_type();
+    let tms = src.get_tile_matrix_set();
+    let tms_id = escape(wmts_tms_id(tms));
+    let title = escape(tj.name.as_deref().unwrap_or(id));
+    let id = escape(id);
+
+    xml.push_str("    <Layer>\n");
+    let _ = writeln!(xml, "      <ows:Title>{title}</ows:Title>");
+    if let Some(description) = &tj.description {
+        let _ = writeln!(
+            xml,
+            "      <ows:Abstract>{}</ows:Abstract>",
+            escape(description)
+        );
+    }
+    let _ = write!(
+        xml,
+        r#"      <ows:WGS84BoundingBox>
+        <ows:LowerCorner>{} {}</ows:LowerCorner>
+        <ows:UpperCorner>{} {}</ows:UpperCorner>
+      </ows:WGS84BoundingBox>
+      <ows:Identifier>{id}</ows:Identifier>
+      <Style isDefault="true">
+        <ows:Identifier>{DEFAULT_STYLE}</ows:Identifier>
+      </Style>
+      <Format>{content_type}</Format>
+      <TileMatrixSetLink>
+        <TileMatrixSet>{tms_id}</TileMatrixSet>
+        <TileMatrixSetLimits>
+"#,
+        bounds.left, bounds.bottom, bounds.right, bounds.top,
+    );
+    for limit in tms.limits(
+        [bounds.left, bounds.bottom, bounds.right, bounds.top],
+        min_zoom..=max_zoom,
+    ) {
+        let Some(matrix) = tms.tile_matrix_at(limit.zoom) else {
+            continue;
+        };
+        let _ = write!(
+            xml,
+            r"          <TileMatrixLimits>
+            <TileMatrix>{}</TileMatrix>
+            <MinTileRow>{}</MinTileRow>
+            <MaxTileRow>{}</MaxTileRow>
+            <MinTileCol>{}</MinTileCol>
+            <MaxTileCol>{}</MaxTileCol>
+          </TileMatrixLimits>
+",
+            escape(&matrix.id),
+            limit.min_y,
+            limit.max_y,
+            limit.min_x,
+            limit.max_x,
+        );
+    }
+    let _ = write!(
+        xml,
+        r#"        </TileMatrixSetLimits>
+      </TileMatrixSetLink>
+      <ResourceURL format="{content_type}" resourceType="tile" template="{}/1.0.0/{id}/{{Style}}/{{TileMatrixSet}}/{{TileMatrix}}/{{TileRow}}/{{TileCol}}"/>
+    </Layer>
+"#,
+        escape(base),
+    );
+}
+
+/// Escape text for use in XML content and attribute values
+fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::Format;
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::srv::tiles::tests::TestSource;
+
+    fn kvp(query: &str) -> HashMap<String, String> {
+        url::form_urlencoded::parse(query.as_bytes())
+            .map(|(k, v)| (k.to_ascii_lowercase(), v.into_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn request_is_case_insensitive() {
+        let capabilities = kvp("service=wmts&request=getcapabilities");
+        assert!(parse_kvp(&capabilities).unwrap().is_none());
+
+        let tile = kvp(
+            "SERVICE=WMTS&REQUEST=gettile&LAYER=roads&STYLE=default&TILEMATRIXSET=WebMercatorQuad&TILEMATRIX=1&TILEROW=0&TILECOL=1",
+        );
+        let tile = parse_kvp(&tile).unwrap().unwrap();
+        assert_eq!(tile.layer, "roads");
+        assert_eq!(tile.tile_col, "1");
+
+        let err = parse_kvp(&kvp("SERVICE=WMTS&REQUEST=GetFeatureInfo")).unwrap_err();
+        assert_eq!(err.code, "OperationNotSupported");
+    }
+
+    #[test]
+    fn layer_is_escaped_once() {
+        let src: BoxedSource = Box::new(TestSource {
+            id: "a&b",
+            tj: tilejson! { tiles: vec![] },
+            data: Vec::new(),
+            format: Format::Png,
+        });
+        let mut xml = String::new();
+        write_layer(&mut xml, "", "a&b", &src, &ProcessConfig::default());
+        assert!(xml.contains("<ows:Title>a&amp;b</ows:Title>"), "{xml}");
+        assert!(
+            xml.contains("<ows:Identifier>a&amp;b</ows:Identifier>"),
+            "{xml}"
+        );
+        assert!(!xml.contains("&amp;amp;"), "{xml}");
+    }
+}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use dashmap::DashMap;
-use martin_core::tiles::{BoxedSource, OptTileCache};
+use martin_core::tiles::{BoxedSource, OptTileCache, TileFlights};
 use tracing::{info, warn};
 
 use crate::MartinResult;
@@ -15,12 +15,13 @@ use crate::source::TileSources;
 /// A broad lock is not needed because each reloader manages a mutually exclusive
 /// set of sources, and [`DashMap`] provides atomic per-key operations.
 ///
-/// `TileCache` has an inner `Arc`, so the whole
+/// `TileCache` and `TileFlights` have an inner `Arc`, so the whole
 /// `TileSourceManager` is cheap to clone.
 #[derive(Clone)]
 pub struct TileSourceManager {
     tile_sources: Arc<DashMap<String, (BoxedSource, ProcessConfig)>>,
     tile_cache: OptTileCache,
+    tile_flights: TileFlights,
     on_invalid: OnInvalid,
 }
 
@@ -31,6 +32,7 @@ impl TileSourceManager {
         Self {
             tile_sources: Arc::new(DashMap::new()),
             tile_cache,
+            tile_flights: TileFlights::new(),
             on_invalid,
         }
     }
@@ -52,6 +54,7 @@ impl TileSourceManager {
         Self {
             tile_sources: Arc::new(map),
             tile_cache,
+            tile_flights: TileFlights::new(),
             on_invalid,
         }
     }
@@ -67,6 +70,12 @@ impl TileSourceManager {
     pub fn tile_cache(&self) -> &OptTileCache {
         &self.tile_cache
     }
+
+    /// Returns the tiles being generated right now, shared by concurrent requests for them.
+    #[must_use]
+    pub fn tile_flights(&self) -> &TileFlights {
+        &self.tile_flights
+    }
 }
 
 impl Sink for TileSourceManager {
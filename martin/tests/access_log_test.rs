@@ -0,0 +1,148 @@
+#![cfg(feature = "mbtiles")]
+
+use std::path::Path;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{TestRequest, call_service};
+use indoc::formatdoc;
+use martin::config::file::access_log::AccessLogConfig;
+use martin::config::file::srv::SrvConfig;
+use mbtiles::temp_named_mbtiles;
+use serde_json::Value;
+
+pub mod utils;
+pub use utils::*;
+
+macro_rules! create_app {
+    ($sources:expr, $access_log:expr) => {{
+        let state = mock_sources(mock_cfg($sources).await).await.0;
+        let srv_config = SrvConfig::default();
+        let access_log: AccessLogConfig = serde_saphyr::from_str(&$access_log).unwrap();
+        ::actix_web::test::init_service(
+            ::actix_web::App::new()
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::Catalog::new(
+                        #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                        &state,
+                    )
+                    .unwrap(),
+                ))
+                .app_data(actix_web::web::Data::new(state.tile_manager))
+                .app_data(actix_web::web::Data::new(srv_config.clone()))
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::AccessLog::new(access_log).unwrap(),
+                ))
+                .wrap(::actix_web::middleware::from_fn(::martin::srv::log_access))
+                .configure(|c| ::martin::srv::router(c, &srv_config)),
+        )
+        .await
+    }};
+}
+
+async fn config(test_name: &str) -> (String, (mbtiles::Mbtiles, mbtiles::sqlx::SqliteConnection)) {
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (mbt, conn, file) = temp_named_mbtiles(&format!("{test_name}_mvt"), script).await;
+    (
+        formatdoc! {"
+            mbtiles:
+                sources:
+                    m_mvt: {mvt}
+            ",
+            mvt = file.display(),
+        },
+        (mbt, conn),
+    )
+}
+
+/// Waits for the background thread to write `count` lines
+async fn read_lines(path: &Path, count: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for _ in 0..100 {
+        let content = std::fs::read_to_string(path).unwrap_or_default();
+        lines = content.lines().map(ToOwned::to_owned).collect();
+        if lines.len() >= count {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(
+        lines.len() >= count,
+        "expected {count} lines in {}",
+        path.display()
+    );
+    lines
+}
+
+#[actix_rt::test]
+async fn json_access_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("access.log");
+    let (config, _conn) = config("json_access_log").await;
+    let app = create_app!(
+        &config,
+        formatdoc! {"
+            format: json
+            path: {path}
+            exclude_paths: [/health]
+            ",
+            path = path.display(),
+        }
+    );
+
+    for uri in ["/health", "/m_mvt/0/0/0", "/catalog"] {
+        let req = TestRequest::get()
+            .uri(uri)
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let lines = read_lines(&path, 2).await;
+    assert_eq!(lines.len(), 2, "{lines:?}");
+    let tile: Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(tile["client"], "10.0.0.1");
+    assert_eq!(tile["method"], "GET");
+    assert_eq!(tile["uri"], "/m_mvt/0/0/0");
+    assert_eq!(tile["status"], 200);
+    assert_eq!(tile["source"], "m_mvt");
+    assert_eq!(tile["z"], 0);
+    assert_eq!(tile["x"], 0);
+    assert_eq!(tile["y"], 0);
+    assert!(tile["bytes"].as_u64().unwrap() > 0);
+    assert!(tile["cache"].is_string());
+    assert!(tile["duration_ms"].is_f64());
+
+    let catalog: Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(catalog["uri"], "/catalog");
+    assert!(catalog.get("source").is_none());
+}
+
+#[actix_rt::test]
+async fn combined_access_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("access.log");
+    let (config, _conn) = config("combined_access_log").await;
+    let app = create_app!(&config, format!("path: {}", path.display()));
+
+    let req = TestRequest::get()
+        .uri("/m_mvt/0/0/0")
+        .peer_addr("10.0.0.1:1234".parse().unwrap())
+        .insert_header(("User-Agent", "test"))
+        .to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let lines = read_lines(&path, 1).await;
+    let line = &lines[0];
+    assert!(line.starts_with("10.0.0.1 - - ["), "{line}");
+    assert!(
+        line.contains(r#"] "GET /m_mvt/0/0/0 HTTP/1.1" 200 "#),
+        "{line}"
+    );
+    assert!(
+        line.contains(r#" "-" "test" source=m_mvt tile=0/0/0 cache="#),
+        "{line}"
+    );
+}
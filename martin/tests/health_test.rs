@@ -0,0 +1,107 @@
+#![cfg(all(feature = "mbtiles", feature = "passthrough"))]
+
+use actix_web::http::StatusCode;
+use actix_web::test::{TestRequest, call_service, read_body, read_body_json};
+use indoc::formatdoc;
+use martin::config::file::health::HealthConfig;
+use martin::config::file::srv::SrvConfig;
+use martin::srv::HealthChecker;
+use mbtiles::temp_named_mbtiles;
+use serde_json::Value;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub mod utils;
+pub use utils::*;
+
+macro_rules! create_app {
+    ($sources:expr, $health:expr) => {{
+        let state = mock_sources(mock_cfg($sources).await).await.0;
+        let srv_config = SrvConfig::default();
+        let health: HealthConfig = serde_saphyr::from_str($health).unwrap();
+        ::actix_web::test::init_service(
+            ::actix_web::App::new()
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::Catalog::new(
+                        #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                        &state,
+                    )
+                    .unwrap(),
+                ))
+                .app_data(actix_web::web::Data::new(state.tile_manager))
+                .app_data(actix_web::web::Data::new(srv_config.clone()))
+                .app_data(actix_web::web::Data::new(HealthChecker::new(health)))
+                .configure(|c| ::martin::srv::router(c, &srv_config)),
+        )
+        .await
+    }};
+}
+
+/// Config with a healthy `MBTiles` source `mbt` and a passthrough source `proxy`
+/// to an upstream that fails with `500`
+async fn config(
+    test_name: &str,
+) -> (
+    String,
+    MockServer,
+    (mbtiles::Mbtiles, mbtiles::sqlx::SqliteConnection),
+) {
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (mbt, conn, file) = temp_named_mbtiles(test_name, script).await;
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    let config = formatdoc! {"
+        mbtiles:
+            sources:
+                mbt: {mbt}
+        passthrough:
+            sources:
+                proxy:
+                    url: \"{upstream}/{{z}}/{{x}}/{{y}}.pbf\"
+        ",
+        mbt = file.display(),
+        upstream = server.uri(),
+    };
+    (config, server, (mbt, conn))
+}
+
+#[actix_rt::test]
+async fn ready_fails_with_critical_source_down() {
+    let (config, _server, _conn) = config("ready_fails_with_critical_source_down").await;
+    let app = create_app!(&config, "{}");
+
+    let req = TestRequest::get().uri("/ready").to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = read_body(response).await;
+    assert_eq!(body, "Unhealthy sources: proxy");
+
+    let req = TestRequest::get().uri("/_/status").to_request();
+    let status: Value = read_body_json(call_service(&app, req).await).await;
+    assert_eq!(status["status"], "unavailable");
+    assert_eq!(status["sources"]["mbt"]["status"], "ok");
+    assert_eq!(status["sources"]["proxy"]["status"], "error");
+    assert_eq!(status["sources"]["proxy"]["critical"], true);
+    let message = status["sources"]["proxy"]["last_error"]["message"]
+        .as_str()
+        .unwrap();
+    assert!(message.contains("500"), "{message}");
+}
+
+#[actix_rt::test]
+async fn ready_ignores_non_critical_sources() {
+    let (config, _server, _conn) = config("ready_ignores_non_critical_sources").await;
+    let app = create_app!(&config, "critical_sources: [mbt]");
+
+    let req = TestRequest::get().uri("/ready").to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = TestRequest::get().uri("/_/status").to_request();
+    let status: Value = read_body_json(call_service(&app, req).await).await;
+    assert_eq!(status["status"], "degraded");
+    assert_eq!(status["sources"]["proxy"]["critical"], false);
+}
@@ -1,7 +1,7 @@
 #![cfg(feature = "mbtiles")]
 #![expect(clippy::print_stdout, reason = "test diagnostics on failure")]
 
-use actix_web::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+use actix_web::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, RANGE};
 use actix_web::test::{TestRequest, call_service, read_body, read_body_json};
 use indoc::formatdoc;
 #[cfg(all(feature = "rendering", target_os = "linux"))]
@@ -421,3 +421,214 @@ async fn mbt_get_json_gzip() {
     let body = decode_gzip(&body).unwrap();
     assert_eq!(body.len(), 13);
 }
+
+/// serve the patches which update an older copy of a source
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn mbt_get_patches() {
+    use mbtiles::sqlx::Executor as _;
+    use mbtiles::{MbtTypeCli, MbtilesCopier};
+
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (_mbt, _conn, src_file) = temp_named_mbtiles("mbt_get_patches", script).await;
+    let dir = tempfile::tempdir().unwrap();
+    let patches = dir.path().join("patches");
+    std::fs::create_dir_all(&patches).unwrap();
+    let (v1, v2) = (dir.path().join("v1.mbtiles"), dir.path().join("v2.mbtiles"));
+    for dst_file in [&v1, &v2] {
+        MbtilesCopier {
+            src_file: src_file.clone(),
+            dst_file: dst_file.clone(),
+            dst_type_cli: Some(MbtTypeCli::Flat),
+            ..Default::default()
+        }
+        .run()
+        .await
+        .unwrap();
+    }
+    let mbt = Mbtiles::new(&v2).unwrap();
+    let mut conn = mbt.open().await.unwrap();
+    conn.execute("DELETE FROM tiles WHERE rowid IN (SELECT rowid FROM tiles LIMIT 1)")
+        .await
+        .unwrap();
+    mbt.update_agg_tiles_hash(&mut conn).await.unwrap();
+    let v2_hash = mbt.get_agg_tiles_hash(&mut conn).await.unwrap().unwrap();
+    let mbt = Mbtiles::new(&v1).unwrap();
+    let mut conn = mbt.open().await.unwrap();
+    let v1_hash = mbt.get_agg_tiles_hash(&mut conn).await.unwrap().unwrap();
+    MbtilesCopier {
+        src_file: v1.clone(),
+        dst_file: patches.join("v1-v2.mbtiles"),
+        diff_with_file: Some((v2.clone(), None)),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+
+    let config = formatdoc! {"
+        mbtiles:
+            sources:
+                basemap: {v2}
+            patches:
+                basemap: {patches}
+        ",
+        v2 = v2.display(),
+        patches = patches.display(),
+    };
+    let state = mock_sources(mock_cfg(&config).await).await.0;
+    let app = ::actix_web::test::init_service(
+        ::actix_web::App::new()
+            .app_data(actix_web::web::Data::new(state.tile_manager))
+            .app_data(actix_web::web::Data::new(state.mbtiles_patches))
+            .app_data(actix_web::web::Data::new(SrvConfig::default()))
+            .configure(|c| ::martin::srv::router(c, &SrvConfig::default())),
+    )
+    .await;
+
+    let req = test_get(&format!("/patch/basemap/{v1_hash}")).to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let chain: serde_json::Value = read_body_json(response).await;
+    assert_eq!(chain["from"], v1_hash.as_str());
+    assert_eq!(chain["to"], v2_hash.as_str());
+    assert_eq!(chain["patches"][0]["file"], "v1-v2.mbtiles");
+    assert_eq!(chain["patches"][0]["patch_type"], "whole");
+    assert_eq!(chain["patches"].as_array().unwrap().len(), 1);
+
+    let req = test_get(&format!("/patch/basemap/{v2_hash}")).to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let chain: serde_json::Value = read_body_json(response).await;
+    assert_eq!(chain["patches"].as_array().unwrap().len(), 0);
+
+    let req = test_get("/patch/basemap/file/v1-v2.mbtiles").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/vnd.sqlite3"
+    );
+    let body = read_body(response).await;
+    assert!(body.starts_with(b"SQLite format 3"));
+
+    let req = test_get("/patch/basemap/file/v1-v2.mbtiles")
+        .insert_header((RANGE, "bytes=0-14"))
+        .to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), 206);
+    assert_eq!(read_body(response).await, &b"SQLite format 3"[..]);
+
+    for path in [
+        "/patch/basemap/0123456789ABCDEF0123456789ABCDEF".to_owned(),
+        format!("/patch/m_json/{v1_hash}"),
+        "/patch/basemap/file/v2.mbtiles".to_owned(),
+        "/patch/basemap/file/..%2Fv2.mbtiles".to_owned(),
+    ] {
+        let response = call_service(&app, test_get(&path).to_request()).await;
+        assert_eq!(response.status(), 404, "{path}");
+    }
+}
+
+/// write and delete tiles of a writable source
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn mbt_write_tiles() {
+    use actix_web::http::header::AUTHORIZATION;
+    use mbtiles::{MbtTypeCli, MbtilesCopier};
+
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (_mbt, _conn, src_file) = temp_named_mbtiles("mbt_write_tiles", script).await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("basemap.mbtiles");
+    MbtilesCopier {
+        src_file,
+        dst_file: file.clone(),
+        dst_type_cli: Some(MbtTypeCli::Flat),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+
+    let config = formatdoc! {"
+        mbtiles:
+            sources:
+                basemap: {file}
+            write:
+                sources: [basemap]
+                token: secret
+        ",
+        file = file.display(),
+    };
+    let state = mock_sources(mock_cfg(&config).await).await.0;
+    let app = ::actix_web::test::init_service(
+        ::actix_web::App::new()
+            .app_data(actix_web::web::Data::new(state.tile_manager))
+            .app_data(actix_web::web::Data::new(state.mbtiles_writers))
+            .app_data(actix_web::web::Data::new(SrvConfig::default()))
+            .configure(|c| ::martin::srv::router(c, &SrvConfig::default())),
+    )
+    .await;
+    let auth = (AUTHORIZATION, "Bearer secret");
+    // An empty vector tile, gzip-compressed like the other tiles of the source
+    let empty_tile = martin_tile_utils::encode_gzip(b"").unwrap();
+
+    let req = TestRequest::put()
+        .uri("/basemap/0/0/0")
+        .set_payload(empty_tile.clone())
+        .to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), 401);
+    assert!(response.headers().contains_key("www-authenticate"));
+
+    let req = TestRequest::put()
+        .uri("/basemap/0/0/0")
+        .insert_header((AUTHORIZATION, "Bearer wrong"))
+        .set_payload(empty_tile.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 401);
+
+    let req = TestRequest::put()
+        .uri("/basemap/0/0/0")
+        .insert_header(auth.clone())
+        .set_payload(&b"{\"not\": \"a vector tile\"}"[..])
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 400);
+
+    let req = TestRequest::put()
+        .uri("/basemap/0/0/0")
+        .insert_header(auth.clone())
+        .set_payload(empty_tile.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 204);
+
+    let req = test_get("/basemap/0/0/0")
+        .insert_header((ACCEPT_ENCODING, "gzip"))
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let body = decode_gzip(&read_body(response).await).unwrap();
+    assert!(body.is_empty());
+
+    let req = TestRequest::delete()
+        .uri("/basemap/0/0/0")
+        .insert_header(auth.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 204);
+    let req = TestRequest::delete()
+        .uri("/basemap/0/0/0")
+        .insert_header(auth.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 404);
+    let response = call_service(&app, test_get("/basemap/0/0/0").to_request()).await;
+    assert_eq!(response.status(), 204);
+
+    let req = TestRequest::put()
+        .uri("/basemap/1/2/0")
+        .insert_header(auth.clone())
+        .set_payload(empty_tile)
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 400);
+    let req = TestRequest::delete()
+        .uri("/m_json/0/0/0")
+        .insert_header(auth.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 404);
+}
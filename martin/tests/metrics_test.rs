@@ -0,0 +1,67 @@
+#![cfg(all(feature = "mbtiles", feature = "metrics"))]
+
+use actix_web::http::StatusCode;
+use actix_web::test::{TestRequest, call_service};
+use indoc::formatdoc;
+use martin::config::file::srv::SrvConfig;
+use mbtiles::temp_named_mbtiles;
+
+pub mod utils;
+pub use utils::*;
+
+macro_rules! create_app {
+    ($sources:expr) => {{
+        let state = mock_sources(mock_cfg($sources).await).await.0;
+        let srv_config = SrvConfig::default();
+        ::actix_web::test::init_service(
+            ::actix_web::App::new()
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::Catalog::new(
+                        #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                        &state,
+                    )
+                    .unwrap(),
+                ))
+                .app_data(actix_web::web::Data::new(state.tile_manager))
+                .app_data(actix_web::web::Data::new(srv_config.clone()))
+                .configure(|c| ::martin::srv::router(c, &srv_config)),
+        )
+        .await
+    }};
+}
+
+/// The lines of the exported metric `name` that are labeled with `source="<source_id>"`
+fn source_metrics(name: &str, source_id: &str) -> Vec<String> {
+    let text = prometheus::TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .expect("metrics are encodable");
+    let label = format!(r#"source="{source_id}""#);
+    text.lines()
+        .filter(|line| line.starts_with(name) && line.contains(&label))
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+#[actix_rt::test]
+async fn per_source_tile_metrics() {
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (_mbt, _conn, file) = temp_named_mbtiles("per_source_tile_metrics", script).await;
+    let app = create_app!(&formatdoc! {"
+        mbtiles:
+            sources:
+                metrics_mvt: {mvt}
+        ",
+        mvt = file.display(),
+    });
+
+    let req = TestRequest::get().uri("/metrics_mvt/0/0/0").to_request();
+    assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+    let generation = source_metrics("martin_tile_generation_seconds_count", "metrics_mvt");
+    assert_eq!(generation.len(), 1, "{generation:?}");
+    assert!(generation[0].ends_with(" 1"), "{generation:?}");
+    let size = source_metrics("martin_tile_size_bytes_sum", "metrics_mvt");
+    assert_eq!(size.len(), 1, "{size:?}");
+    assert!(!size[0].ends_with(" 0"), "{size:?}");
+    assert!(source_metrics("martin_tile_errors_total", "metrics_mvt").is_empty());
+}
@@ -0,0 +1,302 @@
+#![cfg(all(feature = "ogcapi", feature = "mbtiles"))]
+
+use actix_web::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+use actix_web::test::{TestRequest, call_service, read_body, read_body_json};
+use indoc::formatdoc;
+use martin::config::file::srv::SrvConfig;
+use mbtiles::temp_named_mbtiles;
+use serde_json::Value;
+
+pub mod utils;
+pub use utils::*;
+
+macro_rules! create_app {
+    ($sources:expr, $srv_config:expr) => {{
+        let state = mock_sources(mock_cfg($sources).await).await.0;
+        ::actix_web::test::init_service(
+            ::actix_web::App::new()
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::Catalog::new(
+                        #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                        &state,
+                    )
+                    .unwrap(),
+                ))
+                .app_data(actix_web::web::Data::new(state.tile_manager))
+                .app_data(actix_web::web::Data::new($srv_config.clone()))
+                .configure(|c| ::martin::srv::router(c, &$srv_config)),
+        )
+        .await
+    }};
+}
+
+async fn config(
+    test_name: &str,
+) -> (
+    String,
+    (
+        (mbtiles::Mbtiles, mbtiles::sqlx::SqliteConnection),
+        (mbtiles::Mbtiles, mbtiles::sqlx::SqliteConnection),
+    ),
+) {
+    let mvt_script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (mvt_mbt, mvt_conn, mvt_file) =
+        temp_named_mbtiles(&format!("{test_name}_mvt"), mvt_script).await;
+    let png_script = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+    let (png_mbt, png_conn, png_file) =
+        temp_named_mbtiles(&format!("{test_name}_png"), png_script).await;
+
+    (
+        formatdoc! {"
+            mbtiles:
+                sources:
+                    m_mvt: {mvt}
+                    m_png: {png}
+            ",
+            mvt = mvt_file.display(),
+            png = png_file.display(),
+        },
+        ((mvt_mbt, mvt_conn), (png_mbt, png_conn)),
+    )
+}
+
+async fn get_json<S>(app: &S, path: &str) -> Value
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let req = TestRequest::get().uri(path).to_request();
+    let response = assert_response(call_service(app, req).await).await;
+    read_body_json(response).await
+}
+
+fn names(items: &Value) -> Vec<&str> {
+    let mut names: Vec<&str> = items["features"]
+        .as_array()
+        .expect("features array")
+        .iter()
+        .map(|f| f["properties"]["name"].as_str().expect("name property"))
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn ogc_landing_and_collections() {
+    let (config, _conns) = config("ogc_landing").await;
+    let app = create_app!(&config, SrvConfig::default());
+
+    let landing = get_json(&app, "/ogc").await;
+    let hrefs: Vec<&str> = landing["links"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| l["href"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        hrefs,
+        [
+            "http://localhost:8080/ogc",
+            "http://localhost:8080/ogc/conformance",
+            "http://localhost:8080/ogc/collections",
+            "http://localhost:8080/ogc/tileMatrixSets",
+        ]
+    );
+
+    let conformance = get_json(&app, "/ogc/conformance").await;
+    let classes = conformance["conformsTo"].as_array().unwrap();
+    assert!(classes.contains(&"http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/core".into()));
+
+    let collections = get_json(&app, "/ogc/collections").await;
+    let collections = collections["collections"].as_array().unwrap();
+    assert_eq!(collections.len(), 2);
+    assert_eq!(collections[0]["id"], "m_mvt");
+    assert_eq!(collections[0]["dataType"], "vector");
+    assert_eq!(collections[0]["itemType"], "feature");
+    assert_eq!(
+        collections[0]["extent"]["spatial"]["bbox"][0],
+        serde_json::json!([-123.123_59, -37.818_085, 174.763_027, 59.352_706])
+    );
+    assert_eq!(collections[1]["id"], "m_png");
+    assert_eq!(collections[1]["dataType"], "map");
+    assert!(collections[1].get("itemType").is_none());
+
+    let collection = get_json(&app, "/ogc/collections/m_mvt").await;
+    assert_eq!(collection["id"], "m_mvt");
+
+    let req = TestRequest::get()
+        .uri("/ogc/collections/missing")
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 404);
+}
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn ogc_tiles() {
+    let (config, _conns) = config("ogc_tiles").await;
+    let srv_config = SrvConfig {
+        route_prefix: Some("/tiles".to_owned()),
+        ..Default::default()
+    };
+    let app = create_app!(&config, srv_config);
+
+    let tilesets = get_json(&app, "/tiles/ogc/collections/m_mvt/tiles").await;
+    assert_eq!(
+        tilesets["tilesets"][0]["links"][0]["href"],
+        "http://localhost:8080/tiles/ogc/collections/m_mvt/tiles/WebMercatorQuad"
+    );
+
+    let tileset = get_json(&app, "/tiles/ogc/collections/m_mvt/tiles/WebMercatorQuad").await;
+    assert_eq!(tileset["dataType"], "vector");
+    let limits = tileset["tileMatrixSetLimits"].as_array().unwrap();
+    assert_eq!(limits.len(), 7);
+    assert_eq!(
+        limits[6],
+        serde_json::json!({
+            "tileMatrix": "6",
+            "minTileRow": 18,
+            "maxTileRow": 39,
+            "minTileCol": 10,
+            "maxTileCol": 63,
+        })
+    );
+    let item = tileset["links"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["rel"] == "item")
+        .unwrap();
+    assert_eq!(
+        item["href"],
+        "http://localhost:8080/tiles/ogc/collections/m_mvt/tiles/WebMercatorQuad/{tileMatrix}/{tileRow}/{tileCol}"
+    );
+    assert_eq!(item["type"], "application/x-protobuf");
+
+    // tile row is y, tile column is x: same tile as /m_mvt/2/3/2
+    let req = TestRequest::get()
+        .uri("/tiles/ogc/collections/m_mvt/tiles/WebMercatorQuad/2/2/3")
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/x-protobuf"
+    );
+    let ogc_tile = read_body(response).await;
+
+    let req = TestRequest::get().uri("/tiles/m_mvt/2/3/2").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert_eq!(read_body(response).await, ogc_tile);
+
+    for (uri, status) in [
+        ("/tiles/ogc/collections/m_mvt/tiles/WorldCRS84Quad", 404),
+        (
+            "/tiles/ogc/collections/m_mvt/tiles/WebMercatorQuad/6/64/0",
+            404,
+        ),
+        (
+            "/tiles/ogc/collections/m_mvt/tiles/WebMercatorQuad/7/0/0",
+            404,
+        ),
+        (
+            "/tiles/ogc/collections/m_mvt,m_png/tiles/WebMercatorQuad/0/0/0",
+            404,
+        ),
+    ] {
+        let req = TestRequest::get().uri(uri).to_request();
+        assert_eq!(call_service(&app, req).await.status(), status, "{uri}");
+    }
+
+    let tms = get_json(&app, "/tiles/ogc/tileMatrixSets").await;
+    assert_eq!(tms["tileMatrixSets"][0]["id"], "WebMercatorQuad");
+    let tms = get_json(&app, "/tiles/ogc/tileMatrixSets/WebMercatorQuad").await;
+    assert_eq!(tms["tileMatrices"][6]["matrixWidth"], 64);
+}
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn ogc_features() {
+    let (config, _conns) = config("ogc_features").await;
+    let app = create_app!(&config, SrvConfig::default());
+
+    // The features are decoded from gzip-compressed tiles
+    let req = TestRequest::get()
+        .uri("/m_mvt/0/0/0")
+        .insert_header((ACCEPT_ENCODING, "gzip"))
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+
+    let req = TestRequest::get()
+        .uri("/ogc/collections/m_mvt/items?zoom=0&limit=1000")
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/geo+json"
+    );
+    let all: Value = read_body_json(response).await;
+    assert_eq!(all["type"], "FeatureCollection");
+    assert_eq!(all["numberMatched"], 68);
+    assert_eq!(all["numberReturned"], 68);
+    let paris = all["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["properties"]["name"] == "Paris")
+        .unwrap();
+    assert_eq!(paris["geometry"]["type"], "Point");
+    assert_eq!(paris["properties"]["_layer"], "cities");
+
+    // bbox around western Europe
+    let europe = get_json(
+        &app,
+        "/ogc/collections/m_mvt/items?bbox=-5,40,15,55&zoom=0&limit=100",
+    )
+    .await;
+    assert_eq!(
+        names(&europe),
+        [
+            "Amsterdam",
+            "Berlin",
+            "Geneva",
+            "London",
+            "Madrid",
+            "Paris",
+            "Rome"
+        ]
+    );
+
+    // paging
+    let page = get_json(
+        &app,
+        "/ogc/collections/m_mvt/items?bbox=-5,40,15,55&zoom=0&limit=3",
+    )
+    .await;
+    assert_eq!(page["numberReturned"], 3);
+    assert_eq!(page["numberMatched"], 7);
+    let next = page["links"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["rel"] == "next")
+        .unwrap();
+    assert_eq!(
+        next["href"],
+        "http://localhost:8080/ogc/collections/m_mvt/items?bbox=-5%2C40%2C15%2C55&zoom=0&limit=3&offset=3"
+    );
+
+    for (uri, status) in [
+        ("/ogc/collections/m_png/items", 404),
+        ("/ogc/collections/m_mvt/items?bbox=1,2,3", 400),
+        ("/ogc/collections/m_mvt/items?zoom=7", 400),
+        ("/ogc/collections/m_mvt/items?zoom=6", 400),
+    ] {
+        let req = TestRequest::get().uri(uri).to_request();
+        assert_eq!(call_service(&app, req).await.status(), status, "{uri}");
+    }
+}
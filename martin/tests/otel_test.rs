@@ -0,0 +1,98 @@
+#![cfg(all(feature = "opentelemetry", feature = "mbtiles"))]
+//! Exporting spans via OTLP, with a mock collector ([`wiremock`]) receiving them.
+
+use actix_web::test::{TestRequest, call_service};
+use indoc::formatdoc;
+use martin::config::file::srv::{OtlpProtocol, TracingConfig};
+use martin::logging::{LogFormat, init_tracing, otel};
+use mbtiles::temp_named_mbtiles;
+use tracing_actix_web::TracingLogger;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub mod utils;
+pub use utils::*;
+
+/// Trace ID of the `traceparent` header sent with the request
+const TRACE_ID: [u8; 16] = [
+    0x0a, 0xf7, 0x65, 0x19, 0x16, 0xcd, 0x43, 0xdd, 0x84, 0x48, 0xeb, 0x21, 0x1c, 0x80, 0x31, 0x9c,
+];
+const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[actix_rt::test]
+async fn exports_tile_request_spans() {
+    init_tracing("warn", LogFormat::Bare, false);
+    let collector = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/traces"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&collector)
+        .await;
+    otel::init(&TracingConfig {
+        endpoint: Some(format!("{}/v1/traces", collector.uri())),
+        protocol: OtlpProtocol::Http,
+        service_name: Some("martin-test".to_owned()),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (_mbt, _conn, file) = temp_named_mbtiles("test_otel_export", script).await;
+    let cfg = mock_cfg(&formatdoc! {"
+        mbtiles:
+          sources:
+            cities: {}
+    ", file.display()})
+    .await;
+    let state = mock_sources(cfg.clone()).await.0;
+    let srv_config = cfg.srv;
+    let app = actix_web::test::init_service(
+        actix_web::App::new()
+            .app_data(actix_web::web::Data::new(
+                martin::srv::Catalog::new(
+                    #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                    &state,
+                )
+                .unwrap(),
+            ))
+            .app_data(actix_web::web::Data::new(state.tile_manager))
+            .app_data(actix_web::web::Data::new(srv_config.clone()))
+            .wrap(TracingLogger::default())
+            .configure(|c| martin::srv::router(c, &srv_config)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/cities/6/38/20")
+        .insert_header(("traceparent", TRACEPARENT))
+        .to_request();
+    let response = call_service(&app, req).await;
+    assert!(response.status().is_success(), "{}", response.status());
+    drop(response);
+
+    // flushing blocks until the collector responded
+    actix_rt::task::spawn_blocking(otel::shutdown)
+        .await
+        .unwrap();
+
+    let requests = collector.received_requests().await.unwrap();
+    let body: Vec<u8> = requests.into_iter().flat_map(|r| r.body).collect();
+    assert!(contains(&body, b"martin-test"), "service name not exported");
+    assert!(
+        contains(&body, &TRACE_ID),
+        "spans are not part of the trace from the traceparent header"
+    );
+    for span in [
+        // the request span is named after the route
+        "GET /{source_id}/{z}/{x}/{y}",
+        "get_tile",
+        "get_or_insert",
+        "source_get_tile",
+    ] {
+        assert!(contains(&body, span.as_bytes()), "span {span} not exported");
+    }
+}
@@ -3,14 +3,16 @@
 //! End-to-end tests for the `passthrough` tile source driven through martin's HTTP API
 //! against a mock upstream tile server ([`wiremock`]).
 
+use actix_web::dev::ServiceResponse;
 use actix_web::http::header::{
-    ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+    ACCEPT, ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+    LAST_MODIFIED,
 };
 use actix_web::test::{TestRequest, call_service, read_body};
 use indoc::formatdoc;
 use martin::srv::Catalog;
 use martin_tile_utils::encode_gzip;
-use wiremock::matchers::{method, path};
+use wiremock::matchers::{header, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 pub mod utils;
@@ -235,3 +237,122 @@ async fn cache_hit_avoids_second_upstream_fetch() {
         "the second request must be served from cache, not refetched"
     );
 }
+
+/// The `max-age` of a response's `Cache-Control` header.
+fn max_age(response: &ServiceResponse) -> u64 {
+    let cache_control = response
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|v| v.to_str().ok())
+        .expect("a Cache-Control header");
+    cache_control
+        .strip_prefix("max-age=")
+        .and_then(|v| v.parse().ok())
+        .expect("a max-age directive")
+}
+
+/// Counts the upstream requests for the tile `0/0/0`.
+async fn tile_requests(server: &MockServer) -> usize {
+    let requests = server
+        .received_requests()
+        .await
+        .expect("recording is enabled");
+    requests
+        .iter()
+        .filter(|r| r.url.path() == "/0/0/0.pbf")
+        .count()
+}
+
+#[actix_rt::test]
+async fn upstream_caching_headers_are_forwarded() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/0/0/0.pbf"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Content-Type", "application/x-protobuf")
+                .insert_header("Cache-Control", "public, max-age=600")
+                .insert_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+                .set_body_bytes(mvt_tile()),
+        )
+        .mount(&server)
+        .await;
+
+    let app = create_app!(&template_config(&server));
+    let req = TestRequest::get().uri("/proxy/0/0/0").to_request();
+    let response = call_service(&app, req).await;
+    let response = assert_response(response).await;
+
+    assert!((590..=600).contains(&max_age(&response)));
+    assert_eq!(
+        response.headers().get(LAST_MODIFIED).unwrap(),
+        "Wed, 21 Oct 2015 07:28:00 GMT"
+    );
+}
+
+#[actix_rt::test]
+async fn upstream_no_cache_tiles_are_not_cached() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/0/0/0.pbf"))
+        .respond_with(ResponseTemplate::new(404).insert_header("Cache-Control", "no-cache"))
+        .mount(&server)
+        .await;
+
+    let app = create_app!(&template_config(&server));
+    for _ in 0..2 {
+        let req = TestRequest::get().uri("/proxy/0/0/0").to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status().as_u16(), 204);
+        assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "max-age=0");
+    }
+    assert_eq!(tile_requests(&server).await, 2);
+}
+
+#[actix_rt::test]
+async fn expired_tiles_are_revalidated() {
+    let server = MockServer::start().await;
+    let mvt = mvt_tile();
+    Mock::given(method("GET"))
+        .and(path("/0/0/0.pbf"))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304).insert_header("Cache-Control", "max-age=600"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/0/0/0.pbf"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("ETag", "\"v1\"")
+                .insert_header("Content-Type", "application/x-protobuf")
+                .insert_header("Cache-Control", "max-age=0")
+                .set_body_bytes(mvt.clone()),
+        )
+        .mount(&server)
+        .await;
+
+    let url = format!("{}/{{z}}/{{x}}/{{y}}.pbf", server.uri());
+    let app = create_app!(&formatdoc! {"
+        passthrough:
+          sources:
+            proxy:
+              url: \"{url}\"
+              serve_stale: {{}}
+    "});
+    // the first tile expires right away, the revalidated one is fresh for 10 minutes
+    for fresh in [0..=0, 590..=600] {
+        let req = TestRequest::get().uri("/proxy/0/0/0").to_request();
+        let response = call_service(&app, req).await;
+        let response = assert_response(response).await;
+        assert!(fresh.contains(&max_age(&response)));
+        assert_eq!(response.headers().get(ETAG).unwrap(), "\"v1\"");
+        assert_eq!(read_body(response).await, mvt.as_slice());
+    }
+
+    let requests = server
+        .received_requests()
+        .await
+        .expect("recording is enabled");
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1].headers.contains_key("if-none-match"));
+}
@@ -0,0 +1,147 @@
+#![cfg(feature = "mbtiles")]
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CACHE_CONTROL, RETRY_AFTER};
+use actix_web::test::{TestRequest, call_service};
+use indoc::{formatdoc, indoc};
+use martin::config::file::rate_limit::RateLimitConfig;
+use martin::config::file::srv::SrvConfig;
+use mbtiles::temp_named_mbtiles;
+
+pub mod utils;
+pub use utils::*;
+
+macro_rules! create_app {
+    ($sources:expr, $rate_limit:expr) => {{
+        let state = mock_sources(mock_cfg($sources).await).await.0;
+        let srv_config = SrvConfig::default();
+        let rate_limit: RateLimitConfig = serde_saphyr::from_str($rate_limit).unwrap();
+        ::actix_web::test::init_service(
+            ::actix_web::App::new()
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::Catalog::new(
+                        #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                        &state,
+                    )
+                    .unwrap(),
+                ))
+                .app_data(actix_web::web::Data::new(state.tile_manager))
+                .app_data(actix_web::web::Data::new(srv_config.clone()))
+                .app_data(actix_web::web::Data::new(::martin::srv::RateLimits::new(
+                    rate_limit,
+                )))
+                .wrap(::actix_web::middleware::from_fn(
+                    ::martin::srv::limit_requests,
+                ))
+                .configure(|c| ::martin::srv::router(c, &srv_config)),
+        )
+        .await
+    }};
+}
+
+async fn config(test_name: &str) -> (String, (mbtiles::Mbtiles, mbtiles::sqlx::SqliteConnection)) {
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (mbt, conn, file) = temp_named_mbtiles(&format!("{test_name}_mvt"), script).await;
+    (
+        formatdoc! {"
+            mbtiles:
+                sources:
+                    m_mvt: {mvt}
+            ",
+            mvt = file.display(),
+        },
+        (mbt, conn),
+    )
+}
+
+fn request_from(ip: &str, path: &str) -> actix_http::Request {
+    TestRequest::get()
+        .uri(path)
+        .peer_addr(format!("{ip}:1234").parse().expect("a valid socket address"))
+        .to_request()
+}
+
+#[actix_rt::test]
+async fn client_rate_limit() {
+    let (config, _conn) = config("client_rate_limit").await;
+    let app = create_app!(
+        &config,
+        indoc! {"
+            requests_per_second: 1
+            burst: 2
+        "}
+    );
+
+    for _ in 0..2 {
+        let response = call_service(&app, request_from("10.0.0.1", "/catalog")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = call_service(&app, request_from("10.0.0.1", "/catalog")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+
+    // health checks and other clients are not affected
+    let response = call_service(&app, request_from("10.0.0.1", "/health")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = call_service(&app, request_from("10.0.0.2", "/catalog")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn client_header_rate_limit() {
+    let (config, _conn) = config("client_header_rate_limit").await;
+    let app = create_app!(
+        &config,
+        indoc! {"
+            client_header: X-Api-Key
+            trusted_proxies: [10.0.0.0/8]
+            requests_per_second: 1
+        "}
+    );
+
+    let request = |ip: &str, key: &str| {
+        TestRequest::get()
+            .uri("/catalog")
+            .peer_addr(format!("{ip}:1234").parse().unwrap())
+            .insert_header(("X-Api-Key", key.to_owned()))
+            .to_request()
+    };
+    let response = call_service(&app, request("10.0.0.1", "first")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = call_service(&app, request("10.0.0.1", "first")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = call_service(&app, request("10.0.0.1", "second")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // the header of clients which are not trusted proxies is ignored
+    let response = call_service(&app, request("192.168.0.1", "third")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = call_service(&app, request("192.168.0.1", "fourth")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_rt::test]
+async fn source_rate_limit() {
+    let (config, _conn) = config("source_rate_limit").await;
+    let app = create_app!(
+        &config,
+        indoc! {"
+            sources:
+              m_mvt:
+                requests_per_second: 1
+        "}
+    );
+
+    let response = call_service(&app, request_from("10.0.0.1", "/m_mvt/0/0/0")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = call_service(&app, request_from("10.0.0.1", "/m_mvt/0/0/0")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(RETRY_AFTER));
+
+    // only tile requests of the source are limited
+    let response = call_service(&app, request_from("10.0.0.1", "/m_mvt")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = call_service(&app, request_from("10.0.0.2", "/m_mvt/0/0/0")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
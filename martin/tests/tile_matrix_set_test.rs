@@ -0,0 +1,182 @@
+#![cfg(feature = "geojson")]
+
+use actix_web::test::{TestRequest, call_service, read_body, read_body_json};
+use indoc::indoc;
+use martin::config::file::srv::SrvConfig;
+use tilejson::TileJSON;
+
+pub mod utils;
+pub use utils::*;
+
+macro_rules! create_app {
+    ($sources:expr) => {{
+        let state = mock_sources(mock_cfg($sources).await).await.0;
+        ::actix_web::test::init_service(
+            ::actix_web::App::new()
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::Catalog::new(
+                        #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                        &state,
+                    )
+                    .unwrap(),
+                ))
+                .app_data(actix_web::web::Data::new(state.tile_manager))
+                .app_data(actix_web::web::Data::new(SrvConfig::default()))
+                .configure(|c| ::martin::srv::router(c, &SrvConfig::default())),
+        )
+        .await
+    }};
+}
+
+const CONFIG: &str = indoc! {"
+        geojson:
+            tile_matrix_set: WorldCRS84Quad
+            sources:
+                geo1: ../tests/fixtures/geojson/feature_collection_1.geojson
+                geo2: ../tests/fixtures/geojson/feature_collection_2.geojson
+    "};
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn tilejson_links_tile_matrix_set_route() {
+    let app = create_app! { CONFIG };
+    let req = TestRequest::get().uri("/geo1").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let body: TileJSON = read_body_json(response).await;
+    assert_eq!(
+        body.tiles,
+        ["http://localhost:8080/geo1/WorldCRS84Quad/{z}/{x}/{y}"]
+    );
+}
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn tiles_in_tile_matrix_set() {
+    let app = create_app! { CONFIG };
+
+    // The first tile matrix of WorldCRS84Quad is 2x1 tiles, the features are in the western one
+    let req = TestRequest::get()
+        .uri("/geo1/WorldCRS84Quad/0/0/0")
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let tms_tile = read_body(response).await;
+    assert!(!tms_tile.is_empty());
+
+    let req = TestRequest::get().uri("/geo1/0/0/0").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert_eq!(read_body(response).await, tms_tile);
+
+    let req = TestRequest::get()
+        .uri("/geo1,geo2/WorldCRS84Quad/0/1/0")
+        .to_request();
+    assert_response(call_service(&app, req).await).await;
+
+    for uri in [
+        "/geo1/WebMercatorQuad/0/0/0",
+        "/geo1/Missing/0/0/0",
+        "/geo1/WorldCRS84Quad/0/0/1",
+        "/geo1/0/2/0",
+    ] {
+        let req = TestRequest::get().uri(uri).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 404, "{uri}");
+    }
+}
+
+#[cfg(feature = "mbtiles")]
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn composite_requires_same_tile_matrix_set() {
+    let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (_mbt, _conn, file) = mbtiles::temp_named_mbtiles("tms_composite", script).await;
+    let config = format!(
+        "{CONFIG}\nmbtiles:\n    sources:\n        m_mvt: {}\n",
+        file.display()
+    );
+    let app = create_app! { &config };
+
+    let req = TestRequest::get().uri("/m_mvt,geo1/0/0/0").to_request();
+    assert_eq!(call_service(&app, req).await.status(), 404);
+    let req = TestRequest::get()
+        .uri("/m_mvt/WebMercatorQuad/0/0/0")
+        .to_request();
+    assert_response(call_service(&app, req).await).await;
+}
+
+#[cfg(feature = "ogcapi")]
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn ogc_tiles_in_tile_matrix_set() {
+    let app = create_app! { CONFIG };
+
+    let req = TestRequest::get()
+        .uri("/ogc/collections/geo1/tiles")
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let tilesets: serde_json::Value = read_body_json(response).await;
+    assert_eq!(
+        tilesets["tilesets"][0]["links"][0]["href"],
+        "http://localhost:8080/ogc/collections/geo1/tiles/WorldCRS84Quad"
+    );
+    assert_eq!(
+        tilesets["tilesets"][0]["crs"],
+        "http://www.opengis.net/def/crs/OGC/1.3/CRS84"
+    );
+
+    let req = TestRequest::get()
+        .uri("/ogc/collections/geo1/tiles/WorldCRS84Quad/0/0/0")
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert!(!read_body(response).await.is_empty());
+
+    let req = TestRequest::get().uri("/ogc/tileMatrixSets").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let sets: serde_json::Value = read_body_json(response).await;
+    let ids: Vec<&str> = sets["tileMatrixSets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tms| tms["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["WebMercatorQuad", "WorldCRS84Quad"]);
+
+    for uri in [
+        "/ogc/collections/geo1/tiles/WebMercatorQuad",
+        "/ogc/collections/geo1/tiles/WorldCRS84Quad/0/0/2",
+        "/ogc/collections/geo1/items",
+    ] {
+        let req = TestRequest::get().uri(uri).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 404, "{uri}");
+    }
+}
+
+#[cfg(feature = "wmts")]
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn wmts_tiles_in_tile_matrix_set() {
+    let app = create_app! { CONFIG };
+
+    let req = TestRequest::get()
+        .uri("/wmts/1.0.0/WMTSCapabilities.xml")
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let xml = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+    assert!(xml.contains("<TileMatrixSet>WorldCRS84Quad</TileMatrixSet>"));
+    assert!(xml.contains("<ows:SupportedCRS>urn:ogc:def:crs:OGC:1.3:CRS84</ows:SupportedCRS>"));
+    assert!(!xml.contains("GoogleMapsCompatible"));
+
+    let req = TestRequest::get()
+        .uri("/wmts/1.0.0/geo1/default/WorldCRS84Quad/0/0/0")
+        .to_request();
+    assert_response(call_service(&app, req).await).await;
+
+    for uri in [
+        "/wmts/1.0.0/geo1/default/GoogleMapsCompatible/0/0/0",
+        "/wmts/1.0.0/geo1/default/WorldCRS84Quad/0/1/0",
+    ] {
+        let req = TestRequest::get().uri(uri).to_request();
+        assert!(
+            call_service(&app, req).await.status().is_client_error(),
+            "{uri}"
+        );
+    }
+}
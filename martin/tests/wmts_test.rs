@@ -0,0 +1,214 @@
+#![cfg(all(feature = "wmts", feature = "mbtiles"))]
+
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{TestRequest, call_service, read_body};
+use indoc::formatdoc;
+use martin::config::file::srv::SrvConfig;
+use mbtiles::temp_named_mbtiles;
+
+pub mod utils;
+pub use utils::*;
+
+macro_rules! create_app {
+    ($sources:expr, $srv_config:expr) => {{
+        let state = mock_sources(mock_cfg($sources).await).await.0;
+        ::actix_web::test::init_service(
+            ::actix_web::App::new()
+                .app_data(actix_web::web::Data::new(
+                    ::martin::srv::Catalog::new(
+                        #[cfg(any(feature = "sprites", feature = "fonts", feature = "styles"))]
+                        &state,
+                    )
+                    .unwrap(),
+                ))
+                .app_data(actix_web::web::Data::new(state.tile_manager))
+                .app_data(actix_web::web::Data::new($srv_config.clone()))
+                .configure(|c| ::martin::srv::router(c, &$srv_config)),
+        )
+        .await
+    }};
+}
+
+async fn config(
+    test_name: &str,
+) -> (
+    String,
+    (
+        (mbtiles::Mbtiles, mbtiles::sqlx::SqliteConnection),
+        (mbtiles::Mbtiles, mbtiles::sqlx::SqliteConnection),
+    ),
+) {
+    let mvt_script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+    let (mvt_mbt, mvt_conn, mvt_file) =
+        temp_named_mbtiles(&format!("{test_name}_mvt"), mvt_script).await;
+    let png_script = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+    let (png_mbt, png_conn, png_file) =
+        temp_named_mbtiles(&format!("{test_name}_png"), png_script).await;
+
+    (
+        formatdoc! {"
+            mbtiles:
+                sources:
+                    m_mvt: {mvt}
+                    m_png: {png}
+            ",
+            mvt = mvt_file.display(),
+            png = png_file.display(),
+        },
+        ((mvt_mbt, mvt_conn), (png_mbt, png_conn)),
+    )
+}
+
+async fn get_text<S>(app: &S, path: &str) -> String
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let req = TestRequest::get().uri(path).to_request();
+    let response = assert_response(call_service(app, req).await).await;
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).expect("content type"),
+        "text/xml"
+    );
+    String::from_utf8(read_body(response).await.to_vec()).expect("capabilities are UTF-8")
+}
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn wmts_capabilities() {
+    let (config, _conns) = config("wmts_capabilities").await;
+    let srv_config = SrvConfig {
+        route_prefix: Some("/tiles".to_owned()),
+        ..Default::default()
+    };
+    let app = create_app!(&config, srv_config);
+
+    let kvp = get_text(&app, "/tiles/wmts?service=WMTS&request=GetCapabilities").await;
+    let rest = get_text(&app, "/tiles/wmts/1.0.0/WMTSCapabilities.xml").await;
+    assert_eq!(kvp, rest);
+
+    assert!(kvp.contains("<ows:Identifier>m_mvt</ows:Identifier>"));
+    assert!(kvp.contains("<ows:Identifier>m_png</ows:Identifier>"));
+    assert!(kvp.contains("<Format>application/x-protobuf</Format>"));
+    assert!(kvp.contains("<Format>image/png</Format>"));
+    assert!(kvp.contains(r#"<ows:Get xlink:href="http://localhost:8080/tiles/wmts?">"#));
+    assert!(kvp.contains(
+        r#"template="http://localhost:8080/tiles/wmts/1.0.0/m_mvt/{Style}/{TileMatrixSet}/{TileMatrix}/{TileRow}/{TileCol}""#
+    ));
+    assert!(kvp.contains(
+        "<WellKnownScaleSet>urn:ogc:def:wkss:OGC:1.0:GoogleMapsCompatible</WellKnownScaleSet>"
+    ));
+    assert!(kvp.contains(
+        "<TileMatrix>6</TileMatrix>
+            <MinTileRow>18</MinTileRow>
+            <MaxTileRow>39</MaxTileRow>
+            <MinTileCol>10</MinTileCol>
+            <MaxTileCol>63</MaxTileCol>"
+    ));
+}
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn wmts_get_tile() {
+    let (config, _conns) = config("wmts_get_tile").await;
+    let app = create_app!(&config, SrvConfig::default());
+
+    let req = TestRequest::get().uri("/m_mvt/2/3/2").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let expected = read_body(response).await;
+
+    // tile row is y, tile column is x
+    for uri in [
+        "/wmts/1.0.0/m_mvt/default/GoogleMapsCompatible/2/2/3",
+        "/wmts?SERVICE=WMTS&REQUEST=GetTile&LAYER=m_mvt&STYLE=default&TILEMATRIXSET=GoogleMapsCompatible&TILEMATRIX=2&TILEROW=2&TILECOL=3",
+        "/wmts?service=wmts&request=GetTile&layer=m_mvt&style=default&tilematrixset=GoogleMapsCompatible&tilematrix=2&tilerow=2&tilecol=3&format=application/x-protobuf",
+    ] {
+        let req = TestRequest::get().uri(uri).to_request();
+        let response = assert_response(call_service(&app, req).await).await;
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/x-protobuf",
+            "{uri}"
+        );
+        assert_eq!(read_body(response).await, expected, "{uri}");
+    }
+
+    let req = TestRequest::get()
+        .uri("/wmts/1.0.0/m_png/default/GoogleMapsCompatible/0/0/0")
+        .to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "image/png");
+}
+
+#[actix_rt::test]
+#[tracing_test::traced_test]
+async fn wmts_exceptions() {
+    let (config, _conns) = config("wmts_exceptions").await;
+    let app = create_app!(&config, SrvConfig::default());
+
+    for (uri, status, code) in [
+        (
+            "/wmts?REQUEST=GetCapabilities",
+            400,
+            "MissingParameterValue",
+        ),
+        (
+            "/wmts?SERVICE=WMS&REQUEST=GetCapabilities",
+            400,
+            "InvalidParameterValue",
+        ),
+        (
+            "/wmts?SERVICE=WMTS&REQUEST=GetFeatureInfo",
+            400,
+            "OperationNotSupported",
+        ),
+        (
+            "/wmts?SERVICE=WMTS&REQUEST=GetTile&LAYER=m_mvt&STYLE=default&TILEMATRIXSET=GoogleMapsCompatible&TILEMATRIX=2&TILEROW=2",
+            400,
+            "MissingParameterValue",
+        ),
+        (
+            "/wmts?SERVICE=WMTS&REQUEST=GetTile&LAYER=m_mvt&STYLE=default&TILEMATRIXSET=GoogleMapsCompatible&TILEMATRIX=2&TILEROW=2&TILECOL=3&FORMAT=text/html",
+            400,
+            "InvalidParameterValue",
+        ),
+        (
+            "/wmts/1.0.0/missing/default/GoogleMapsCompatible/0/0/0",
+            400,
+            "InvalidParameterValue",
+        ),
+        (
+            "/wmts/1.0.0/m_mvt,m_png/default/GoogleMapsCompatible/0/0/0",
+            400,
+            "InvalidParameterValue",
+        ),
+        (
+            "/wmts/1.0.0/m_mvt/fancy/GoogleMapsCompatible/0/0/0",
+            400,
+            "InvalidParameterValue",
+        ),
+        (
+            "/wmts/1.0.0/m_mvt/default/WorldCRS84Quad/0/0/0",
+            400,
+            "InvalidParameterValue",
+        ),
+        (
+            "/wmts/1.0.0/m_mvt/default/GoogleMapsCompatible/2/4/0",
+            404,
+            "TileOutOfRange",
+        ),
+    ] {
+        let req = TestRequest::get().uri(uri).to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), status, "{uri}");
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+        assert!(body.contains("<ows:ExceptionReport"), "{uri}: {body}");
+        assert!(
+            body.contains(&format!(r#"exceptionCode="{code}""#)),
+            "{uri}: {body}"
+        );
+    }
+}
@@ -4,6 +4,8 @@
 )]
 
 use std::io::IsTerminal as _;
+#[cfg(feature = "transcode")]
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 
 use clap::builder::Styles;
@@ -13,8 +15,16 @@ use enum_display::EnumDisplay;
 use mbtiles::{
     AggHashType, CopyDuplicateMode, CopyType, IntegrityCheckType, MbtError, MbtResult, MbtTypeCli,
     Mbtiles, MbtilesCopier, PackCompression, PatchTypeCli, TileScheme, UnixSeconds, UpdateZoomType,
-    apply_patch, pack, unpack,
+    apply_patches, pack, rebase_patch, squash_patches, unpack,
 };
+#[cfg(feature = "transcode")]
+use mbtiles::{
+    DEFAULT_TRANSCODE_QUALITY, LayerFilter, MergeOptions, OverviewFilter, OverviewOptions,
+    TranscodeCompression, TranscodeFormat, TranscodeOptions, build_overviews, merge,
+    parse_layer_attributes, parse_layer_zooms, transcode,
+};
+#[cfg(feature = "transcode")]
+use mlt_core::encoder::EncoderConfig;
 use serde::{Deserialize, Serialize};
 use tilejson::Bounds;
 use tracing::error;
@@ -63,6 +73,14 @@ enum Commands {
         file: PathBuf,
         #[arg(short, long, value_enum, default_value_t=OutputFormat::default())]
         format: OutputFormat,
+        /// Decode all vector tiles to show per-layer feature counts, sizes, geometry types and attribute cardinality
+        #[cfg(feature = "transcode")]
+        #[arg(long)]
+        layers: bool,
+        /// Number of largest tiles to list with `--layers`
+        #[cfg(feature = "transcode")]
+        #[arg(long, default_value_t = 10, requires = "layers")]
+        top: usize,
     },
     /// Prints all values in the metadata table in a free-style, unstable YAML format
     #[command(name = "meta-all")]
@@ -94,17 +112,40 @@ enum Commands {
     /// Copy tiles from one mbtiles file to another.
     #[command(name = "copy", alias = "cp")]
     Copy(CopyArgs),
+    /// Copy tiles from one mbtiles file to another, converting them to another format or compression,
+    /// e.g. MVT to MLT, or PNG to WebP.
+    #[cfg(feature = "transcode")]
+    #[command(name = "transcode")]
+    Transcode(TranscodeArgs),
+    /// Merge several mbtiles files into one, combining the layers of vector tiles that exist in more than one file,
+    /// and drawing such raster tiles on top of each other.
+    #[cfg(feature = "transcode")]
+    #[command(name = "merge", alias = "join")]
+    Merge(MergeArgs),
+    /// Build the lower zoom levels of a raster tileset in place, by stitching every four tiles
+    /// of the zoom level above and downsampling them.
+    #[cfg(feature = "transcode")]
+    #[command(name = "build-overviews", alias = "overviews")]
+    BuildOverviews(OverviewArgs),
     /// Apply diff file generated from 'copy' command
     #[command(name = "apply-patch", alias = "apply-diff")]
     ApplyPatch {
         /// `MBTiles` file to apply diff to
         base_file: PathBuf,
-        /// Diff file
-        patch_file: PathBuf,
+        /// Diff files, applied in the given order. Each of them must have been generated from the result of the previous one.
+        #[arg(required = true)]
+        patch_files: Vec<PathBuf>,
         /// Force patching operation, ignoring some warnings that otherwise would prevent the operation. Use with caution.
         #[arg(short, long)]
         force: bool,
     },
+    /// Combine a chain of consecutive diff files into a single diff file.
+    #[command(name = "squash-patches", alias = "squash")]
+    SquashPatches(SquashArgs),
+    /// Rebase a diff file on top of another diff file generated from the same base file,
+    /// failing if both of them change the same tiles in different ways.
+    #[command(name = "rebase-patch", alias = "rebase")]
+    RebasePatch(RebaseArgs),
     /// Update metadata to match the content of the file
     #[command(name = "meta-update", alias = "update-meta")]
     UpdateMetadata {
@@ -128,6 +169,14 @@ enum Commands {
         /// How should the aggregate tiles hash be checked or updated.
         #[arg(long, value_enum)]
         agg_hash: Option<AggHashType>,
+        /// Also decode every tile, check its format and geometries, and compare its layers with the `vector_layers` metadata.
+        #[cfg(feature = "transcode")]
+        #[arg(long)]
+        deep: bool,
+        /// Maximum number of tile problems to print with `--deep`.
+        #[cfg(feature = "transcode")]
+        #[arg(long, default_value_t = 100, requires = "deep")]
+        max_problems: usize,
     },
     /// Pack a directory tree of tiles into an `MBTiles` file
     #[command(name = "pack")]
@@ -188,6 +237,220 @@ pub struct CopyArgs {
     /// Specify the type of patch file to generate.
     #[arg(long, requires("diff_with_file"), default_value_t=PatchTypeCli::default())]
     patch_type: PatchTypeCli,
+    #[cfg(feature = "transcode")]
+    #[command(flatten)]
+    layers: LayerFilterArgs,
+}
+
+/// Vector tile layers and attributes to keep while copying.
+#[cfg(feature = "transcode")]
+/// Tiles are decoded and encoded again if any of these is set.
+#[derive(Clone, Default, PartialEq, Debug, clap::Args)]
+#[command(next_help_heading = "Layer filtering")]
+pub struct LayerFilterArgs {
+    /// Only copy these vector tile layers, as a comma-separated list. Can be used multiple times.
+    #[arg(long, value_name = "LAYER", value_delimiter = ',')]
+    include_layers: Vec<String>,
+    /// Remove these vector tile layers, as a comma-separated list. Can be used multiple times.
+    #[arg(long, value_name = "LAYER", value_delimiter = ',')]
+    exclude_layers: Vec<String>,
+    /// Only keep these attributes of a layer's features, e.g. `roads=name,class`. Can be used multiple times.
+    #[arg(long, value_name = "LAYER=ATTRS", value_parser = parse_layer_attributes)]
+    include_attributes: Vec<(String, Vec<String>)>,
+    /// Remove these attributes of a layer's features, e.g. `pois=wikidata`. Can be used multiple times.
+    #[arg(long, value_name = "LAYER=ATTRS", value_parser = parse_layer_attributes)]
+    exclude_attributes: Vec<(String, Vec<String>)>,
+    /// Only keep a layer in tiles of these zoom levels, e.g. `buildings=13-` or `roads=4-14`. Can be used multiple times.
+    #[arg(long, value_name = "LAYER=MIN-MAX", value_parser = parse_layer_zooms)]
+    layer_zoom: Vec<(String, RangeInclusive<u8>)>,
+}
+
+#[cfg(feature = "transcode")]
+impl From<LayerFilterArgs> for LayerFilter {
+    fn from(args: LayerFilterArgs) -> Self {
+        Self::new(
+            args.include_layers,
+            args.exclude_layers,
+            args.include_attributes,
+            args.exclude_attributes,
+            args.layer_zoom,
+        )
+    }
+}
+
+#[cfg(feature = "transcode")]
+#[derive(Clone, PartialEq, Debug, clap::Args)]
+pub struct TranscodeArgs {
+    /// `MBTiles` file to read from
+    src_file: PathBuf,
+    /// `MBTiles` file to write to
+    dst_file: PathBuf,
+    /// Format to convert the tiles to. If not specified, tiles keep their format.
+    #[arg(long, value_enum)]
+    format: Option<TranscodeFormat>,
+    /// Compression of vector tiles. If not specified, tiles keep their compression unless the format changes:
+    /// MVT tiles are then gzip-compressed, and MLT tiles are not compressed.
+    #[arg(long, value_enum)]
+    compression: Option<TranscodeCompression>,
+    /// Quality of lossy JPEG and AVIF images, from 1 to 100
+    #[arg(long, default_value_t = DEFAULT_TRANSCODE_QUALITY, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+    /// Output format of the destination file. If not specified, defaults to the type of source
+    #[arg(long, alias = "dst-type", alias = "dst_type", value_name = "SCHEMA")]
+    mbtiles_type: Option<MbtTypeCli>,
+    #[command(flatten)]
+    mlt: MltEncoderArgs,
+}
+
+#[cfg(feature = "transcode")]
+#[derive(Clone, PartialEq, Debug, clap::Args)]
+pub struct MergeArgs {
+    /// `MBTiles` files to merge. Tiles are stored in the format of the first file,
+    /// and raster tiles of later files are drawn over the earlier ones.
+    #[arg(required = true)]
+    src_files: Vec<PathBuf>,
+    /// New `MBTiles` file to write the merged tiles to
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Rename a vector tile layer before merging, e.g. `streets=roads`. Can be used multiple times.
+    #[arg(long, value_name = "OLD=NEW", value_parser = parse_layer_rename)]
+    rename_layer: Vec<(String, String)>,
+    /// Leave out a vector tile layer, by its original name. Can be used multiple times.
+    #[arg(long, value_name = "LAYER")]
+    drop_layer: Vec<String>,
+    /// Quality of merged JPEG images, from 1 to 100
+    #[arg(long, default_value_t = DEFAULT_TRANSCODE_QUALITY, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+    /// Output format of the destination file. Defaults to `flat`
+    #[arg(long, alias = "dst-type", alias = "dst_type", value_name = "SCHEMA")]
+    mbtiles_type: Option<MbtTypeCli>,
+}
+
+#[derive(Clone, PartialEq, Debug, clap::Args)]
+pub struct SquashArgs {
+    /// `MBTiles` file the first diff file applies to
+    base_file: PathBuf,
+    /// Consecutive diff files, in the order they are applied
+    #[arg(required = true)]
+    patch_files: Vec<PathBuf>,
+    /// New diff file to write the combined changes to
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Specify the type of patch file to generate.
+    #[arg(long, default_value_t=PatchTypeCli::default())]
+    patch_type: PatchTypeCli,
+    /// Force the operation, ignoring some warnings that otherwise would prevent it. Use with caution.
+    #[arg(short, long)]
+    force: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, clap::Args)]
+pub struct RebaseArgs {
+    /// `MBTiles` file both diff files were generated from
+    base_file: PathBuf,
+    /// Diff file to rebase onto. The rebased diff file applies to the result of this one.
+    onto_file: PathBuf,
+    /// Diff file to rebase
+    patch_file: PathBuf,
+    /// New diff file to write the rebased changes to
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Specify the type of patch file to generate.
+    #[arg(long, default_value_t=PatchTypeCli::default())]
+    patch_type: PatchTypeCli,
+    /// Keep the tiles of the rebased diff file when both diff files change them in different ways.
+    #[arg(short, long)]
+    force: bool,
+}
+
+#[cfg(feature = "transcode")]
+#[derive(Clone, PartialEq, Debug, clap::Args)]
+pub struct OverviewArgs {
+    /// `MBTiles` file with PNG, JPEG or WebP tiles to add the overviews to
+    file: PathBuf,
+    /// Lowest zoom level to build
+    #[arg(long, default_value_t = 0)]
+    min_zoom: u8,
+    /// Zoom level to build the overviews from. Defaults to the highest zoom level of the file
+    #[arg(long)]
+    max_zoom: Option<u8>,
+    /// Resampling filter used to downsample the tiles
+    #[arg(long, value_enum, default_value_t = OverviewFilter::default())]
+    filter: OverviewFilter,
+    /// Quality of JPEG tiles, from 1 to 100
+    #[arg(long, default_value_t = DEFAULT_TRANSCODE_QUALITY, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+    /// Only rebuild the tiles covering the tiles changed by this patch file, after it was applied with `mbtiles apply-patch`
+    #[arg(long)]
+    patch: Option<PathBuf>,
+}
+
+#[cfg(feature = "transcode")]
+fn parse_layer_rename(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+            Ok((old.to_owned(), new.to_owned()))
+        }
+        _ => Err(format!("expected OLD=NEW, got `{value}`")),
+    }
+}
+
+#[cfg(feature = "transcode")]
+/// MLT encoder settings, the same as the `convert_to_mlt` settings of the Martin configuration.
+/// Unset values use the encoder defaults.
+#[derive(Clone, Default, PartialEq, Debug, clap::Args)]
+#[command(next_help_heading = "MLT encoding")]
+pub struct MltEncoderArgs {
+    /// Generate tessellation data for polygons and multi-polygons
+    #[arg(long, value_name = "BOOL")]
+    mlt_tessellate: Option<bool>,
+    /// Try sorting features by Z-order (Morton) curve index of their first vertex
+    #[arg(long, value_name = "BOOL")]
+    mlt_try_spatial_morton_sort: Option<bool>,
+    /// Try sorting features by Hilbert curve index of their first vertex
+    #[arg(long, value_name = "BOOL")]
+    mlt_try_spatial_hilbert_sort: Option<bool>,
+    /// Try sorting features by their feature ID in ascending order
+    #[arg(long, value_name = "BOOL")]
+    mlt_try_id_sort: Option<bool>,
+    /// Allow FSST string compression
+    #[arg(long, value_name = "BOOL")]
+    mlt_allow_fsst: Option<bool>,
+    /// Allow `FastPFOR` integer compression
+    #[arg(long, value_name = "BOOL")]
+    mlt_allow_fastpfor: Option<bool>,
+    /// Allow string grouping into shared dictionaries
+    #[arg(long, value_name = "BOOL")]
+    mlt_allow_shared_dict: Option<bool>,
+}
+
+#[cfg(feature = "transcode")]
+impl From<MltEncoderArgs> for EncoderConfig {
+    fn from(args: MltEncoderArgs) -> Self {
+        let mut cfg = Self::default();
+        if let Some(v) = args.mlt_tessellate {
+            cfg = cfg.with_tessellation(v);
+        }
+        if let Some(v) = args.mlt_try_spatial_morton_sort {
+            cfg = cfg.with_spatial_morton_sort(v);
+        }
+        if let Some(v) = args.mlt_try_spatial_hilbert_sort {
+            cfg = cfg.with_spatial_hilbert_sort(v);
+        }
+        if let Some(v) = args.mlt_try_id_sort {
+            cfg = cfg.with_id_sort(v);
+        }
+        if let Some(v) = args.mlt_allow_fsst {
+            cfg = cfg.with_fsst(v);
+        }
+        if let Some(v) = args.mlt_allow_fastpfor {
+            cfg = cfg.with_fastpfor(v);
+        }
+        if let Some(v) = args.mlt_allow_shared_dict {
+            cfg = cfg.with_shared_dict(v);
+        }
+        cfg
+    }
 }
 
 #[derive(Clone, Default, PartialEq, Debug, clap::Args)]
@@ -278,6 +541,8 @@ impl SharedCopyOpts {
             strict: self.strict,
             // Constants
             dst_type: None, // Taken from dst_type_cli
+            #[cfg(feature = "transcode")]
+            layer_filter: LayerFilter::default(),
         }
     }
 }
@@ -302,6 +567,7 @@ async fn main() {
     }
 }
 
+#[expect(clippy::too_many_lines)]
 async fn main_int() -> anyhow::Result<()> {
     let args = Args::parse();
     match args.command {
@@ -322,6 +588,11 @@ async fn main_int() -> anyhow::Result<()> {
                 args.apply_patch,
                 args.patch_type,
             );
+            #[cfg(feature = "transcode")]
+            let copier = MbtilesCopier {
+                layer_filter: args.layers.into(),
+                ..copier
+            };
             copier.run().await?;
         }
         Commands::Diff(args) => {
@@ -334,12 +605,79 @@ async fn main_int() -> anyhow::Result<()> {
             );
             copier.run().await?;
         }
+        #[cfg(feature = "transcode")]
+        Commands::Transcode(args) => {
+            let options = TranscodeOptions {
+                format: args.format,
+                compression: args.compression,
+                quality: args.quality,
+                mlt: args.mlt.into(),
+                dst_type: args.mbtiles_type.map(Into::into),
+            };
+            let stats = transcode(&args.src_file, &args.dst_file, &options).await?;
+            println!(
+                "Transcoded {} tiles ({} unique tiles encoded)",
+                stats.tiles_written, stats.cache_encoded
+            );
+        }
+        #[cfg(feature = "transcode")]
+        Commands::Merge(args) => {
+            let options = MergeOptions {
+                rename_layers: args.rename_layer.into_iter().collect(),
+                drop_layers: args.drop_layer.into_iter().collect(),
+                dst_type: args.mbtiles_type.map(Into::into),
+                quality: args.quality,
+            };
+            let stats = merge(&args.src_files, &args.output, &options).await?;
+            println!(
+                "Merged {} files into {} tiles ({} tiles were in more than one file)",
+                args.src_files.len(),
+                stats.tiles_written,
+                stats.tiles_merged
+            );
+        }
+        #[cfg(feature = "transcode")]
+        Commands::BuildOverviews(args) => {
+            let options = OverviewOptions {
+                min_zoom: args.min_zoom,
+                max_zoom: args.max_zoom,
+                filter: args.filter,
+                quality: args.quality,
+                patch_file: args.patch,
+            };
+            let stats = build_overviews(&args.file, &options).await?;
+            println!(
+                "Wrote {} overview tiles and deleted {} tiles without children",
+                stats.tiles_written, stats.tiles_deleted
+            );
+        }
         Commands::ApplyPatch {
             base_file,
-            patch_file,
+            patch_files,
             force,
         } => {
-            apply_patch(base_file, patch_file, force).await?;
+            apply_patches(base_file, patch_files, force).await?;
+        }
+        Commands::SquashPatches(args) => {
+            squash_patches(
+                &args.base_file,
+                &args.patch_files,
+                &args.output,
+                args.patch_type.into(),
+                args.force,
+            )
+            .await?;
+        }
+        Commands::RebasePatch(args) => {
+            rebase_patch(
+                &args.base_file,
+                &args.onto_file,
+                &args.patch_file,
+                &args.output,
+                args.patch_type.into(),
+                args.force,
+            )
+            .await?;
         }
         Commands::UpdateMetadata { file, update_zoom } => {
             let mbt = Mbtiles::new(file.as_path())?;
@@ -351,6 +689,10 @@ async fn main_int() -> anyhow::Result<()> {
             integrity_check,
             update_agg_tiles_hash,
             agg_hash,
+            #[cfg(feature = "transcode")]
+            deep,
+            #[cfg(feature = "transcode")]
+            max_problems,
         } => {
             if update_agg_tiles_hash && agg_hash.is_some() {
                 anyhow::bail!("Cannot use both --agg-hash and --update-agg-tiles-hash");
@@ -364,11 +706,32 @@ async fn main_int() -> anyhow::Result<()> {
             });
             let mbt = Mbtiles::new(file.as_path())?;
             mbt.open_and_validate(integrity_check, agg_hash).await?;
+            #[cfg(feature = "transcode")]
+            if deep {
+                let mut conn = mbt.open_readonly().await?;
+                let report = mbt.validate_tiles(&mut conn, max_problems).await?;
+                if !report.is_valid() {
+                    anyhow::bail!("Invalid tiles in {}:\n{report}", file.display());
+                }
+                println!("All {} tiles are valid", report.tile_count);
+            }
         }
-        Commands::Summary { file, format } => {
+        Commands::Summary {
+            file,
+            format,
+            #[cfg(feature = "transcode")]
+            layers,
+            #[cfg(feature = "transcode")]
+            top,
+        } => {
             let mbt = Mbtiles::new(file.as_path())?;
             let mut conn = mbt.open_readonly().await?;
-            let summary = mbt.summary(&mut conn).await?;
+            #[cfg_attr(not(feature = "transcode"), expect(unused_mut))]
+            let mut summary = mbt.summary(&mut conn).await?;
+            #[cfg(feature = "transcode")]
+            if layers {
+                summary.layers = Some(mbt.layer_summary(&mut conn, top).await?);
+            }
             match format {
                 OutputFormat::Text => println!("{summary}"),
                 OutputFormat::Json => println!("{}", serde_json::to_string(&summary)?),
@@ -461,8 +824,11 @@ mod tests {
 
     use super::*;
     use crate::Commands::{
-        ApplyPatch, Copy, Diff, MetaGetValue, MetaSetValue, Pack, Unpack, Validate,
+        ApplyPatch, Copy, Diff, MetaGetValue, MetaSetValue, Pack, RebasePatch, SquashPatches,
+        Unpack, Validate,
     };
+    #[cfg(feature = "transcode")]
+    use crate::Commands::{BuildOverviews, Merge, Summary, Transcode};
     use crate::{Args, IntegrityCheckType};
 
     #[test]
@@ -673,6 +1039,48 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn copy_layer_filter() {
+        let args = Args::parse_from([
+            "mbtiles",
+            "copy",
+            "src_file",
+            "dst_file",
+            "--exclude-layers",
+            "water,landuse",
+            "--include-attributes",
+            "roads=name,class",
+            "--include-attributes",
+            "roads=ref",
+            "--layer-zoom",
+            "buildings=13-",
+        ]);
+        let Copy(args) = args.command else {
+            panic!("unexpected command {:?}", args.command);
+        };
+        assert_eq!(
+            LayerFilter::from(args.layers),
+            LayerFilter::new(
+                [],
+                ["water".to_owned(), "landuse".to_owned()],
+                [(
+                    "roads".to_owned(),
+                    vec!["name".to_owned(), "class".to_owned(), "ref".to_owned()],
+                )],
+                [],
+                [("buildings".to_owned(), 13..=30)],
+            )
+        );
+
+        assert_eq!(
+            Args::try_parse_from(["mbtiles", "copy", "src", "dst", "--layer-zoom", "roads"])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ValueValidation
+        );
+    }
+
     #[test]
     fn diff() {
         assert_eq!(
@@ -773,13 +1181,69 @@ mod tests {
                 verbose: false,
                 command: ApplyPatch {
                     base_file: PathBuf::from("src_file"),
-                    patch_file: PathBuf::from("diff_file"),
+                    patch_files: vec![PathBuf::from("diff_file")],
+                    force: false,
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn apply_patch_chain() {
+        assert_eq!(
+            Args::parse_from(["mbtiles", "apply-patch", "src_file", "diff1", "diff2"]),
+            Args {
+                verbose: false,
+                command: ApplyPatch {
+                    base_file: PathBuf::from("src_file"),
+                    patch_files: vec![PathBuf::from("diff1"), PathBuf::from("diff2")],
                     force: false,
                 }
             }
         );
     }
 
+    #[test]
+    fn squash_and_rebase_args() {
+        assert_eq!(
+            Args::parse_from([
+                "mbtiles",
+                "squash",
+                "base",
+                "diff1",
+                "diff2",
+                "-o",
+                "out",
+                "--patch-type",
+                "bin-diff-raw",
+            ]),
+            Args {
+                verbose: false,
+                command: SquashPatches(SquashArgs {
+                    base_file: PathBuf::from("base"),
+                    patch_files: vec![PathBuf::from("diff1"), PathBuf::from("diff2")],
+                    output: PathBuf::from("out"),
+                    patch_type: PatchTypeCli::BinDiffRaw,
+                    force: false,
+                })
+            }
+        );
+        assert_eq!(
+            Args::parse_from(["mbtiles", "rebase", "base", "diff1", "diff2", "-o", "out"]),
+            Args {
+                verbose: false,
+                command: RebasePatch(RebaseArgs {
+                    base_file: PathBuf::from("base"),
+                    onto_file: PathBuf::from("diff1"),
+                    patch_file: PathBuf::from("diff2"),
+                    output: PathBuf::from("out"),
+                    patch_type: PatchTypeCli::Whole,
+                    force: false,
+                })
+            }
+        );
+    }
+
     #[test]
     fn validate() {
         assert_eq!(
@@ -791,6 +1255,10 @@ mod tests {
                     integrity_check: IntegrityCheckType::Quick,
                     update_agg_tiles_hash: false,
                     agg_hash: Some(AggHashType::Off),
+                    #[cfg(feature = "transcode")]
+                    deep: false,
+                    #[cfg(feature = "transcode")]
+                    max_problems: 100,
                 }
             }
         );
@@ -874,4 +1342,157 @@ mod tests {
             }
         );
     }
+
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn transcode_to_mlt() {
+        assert_eq!(
+            Args::parse_from([
+                "mbtiles",
+                "transcode",
+                "src.mbtiles",
+                "dst.mbtiles",
+                "--format",
+                "mlt",
+                "--mlt-tessellate",
+                "true",
+            ]),
+            Args {
+                verbose: false,
+                command: Transcode(TranscodeArgs {
+                    src_file: PathBuf::from("src.mbtiles"),
+                    dst_file: PathBuf::from("dst.mbtiles"),
+                    format: Some(TranscodeFormat::Mlt),
+                    compression: None,
+                    quality: DEFAULT_TRANSCODE_QUALITY,
+                    mbtiles_type: None,
+                    mlt: MltEncoderArgs {
+                        mlt_tessellate: Some(true),
+                        ..Default::default()
+                    },
+                })
+            }
+        );
+    }
+
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn transcode_quality_range() {
+        let args = ["mbtiles", "transcode", "src", "dst", "--format", "avif"];
+        let Transcode(TranscodeArgs { quality, .. }) =
+            Args::parse_from(args.into_iter().chain(["--quality", "60"])).command
+        else {
+            panic!("expected a transcode command");
+        };
+        assert_eq!(quality, 60);
+        assert_eq!(
+            Args::try_parse_from(args.into_iter().chain(["--quality", "0"]))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn merge_layer_options() {
+        assert_eq!(
+            Args::parse_from([
+                "mbtiles",
+                "merge",
+                "a.mbtiles",
+                "b.mbtiles",
+                "-o",
+                "out.mbtiles",
+                "--rename-layer",
+                "streets=roads",
+                "--drop-layer",
+                "tmp",
+            ]),
+            Args {
+                verbose: false,
+                command: Merge(MergeArgs {
+                    src_files: vec![PathBuf::from("a.mbtiles"), PathBuf::from("b.mbtiles")],
+                    output: PathBuf::from("out.mbtiles"),
+                    rename_layer: vec![("streets".to_owned(), "roads".to_owned())],
+                    drop_layer: vec!["tmp".to_owned()],
+                    quality: DEFAULT_TRANSCODE_QUALITY,
+                    mbtiles_type: None,
+                })
+            }
+        );
+        assert_eq!(
+            Args::try_parse_from([
+                "mbtiles",
+                "merge",
+                "a.mbtiles",
+                "-o",
+                "out.mbtiles",
+                "--rename-layer",
+                "streets"
+            ])
+            .unwrap_err()
+            .kind(),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn summary_layers() {
+        assert_eq!(
+            Args::parse_from([
+                "mbtiles",
+                "summary",
+                "src.mbtiles",
+                "--layers",
+                "--top",
+                "5"
+            ]),
+            Args {
+                verbose: false,
+                command: Summary {
+                    file: PathBuf::from("src.mbtiles"),
+                    format: OutputFormat::Text,
+                    layers: true,
+                    top: 5,
+                }
+            }
+        );
+        assert_eq!(
+            Args::try_parse_from(["mbtiles", "summary", "src.mbtiles", "--top", "5"])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn build_overviews_args() {
+        assert_eq!(
+            Args::parse_from([
+                "mbtiles",
+                "build-overviews",
+                "src.mbtiles",
+                "--max-zoom",
+                "12",
+                "--filter",
+                "bilinear",
+                "--patch",
+                "diff.mbtiles",
+            ]),
+            Args {
+                verbose: false,
+                command: BuildOverviews(OverviewArgs {
+                    file: PathBuf::from("src.mbtiles"),
+                    min_zoom: 0,
+                    max_zoom: Some(12),
+                    filter: OverviewFilter::Bilinear,
+                    quality: DEFAULT_TRANSCODE_QUALITY,
+                    patch: Some(PathBuf::from("diff.mbtiles")),
+                })
+            }
+        );
+    }
 }
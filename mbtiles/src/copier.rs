@@ -81,6 +81,9 @@ pub struct MbtilesCopier {
     pub validate: bool,
     /// Use `SQLite` `STRICT` tables when creating a new destination schema.
     pub strict: bool,
+    /// Layers and attributes of vector tiles to keep. Tiles are decoded and re-encoded if not empty.
+    #[cfg(feature = "transcode")]
+    pub layer_filter: crate::LayerFilter,
 }
 
 #[derive(Clone, Debug)]
@@ -93,21 +96,21 @@ struct MbtileCopierInt {
 impl MbtilesCopier {
     #[hotpath::measure]
     pub async fn run(self) -> MbtResult<SqliteConnection> {
+        #[cfg(feature = "transcode")]
+        if !self.layer_filter.is_empty() {
+            return crate::layer_filter::copy_filtered(self).await;
+        }
+        self.run_unfiltered().await
+    }
+
+    /// Copies the tiles without decoding them, ignoring the layer filter.
+    pub(crate) async fn run_unfiltered(self) -> MbtResult<SqliteConnection> {
         MbtileCopierInt::new(self)?.run().await
     }
 
     pub(crate) fn dst_type(&self) -> Option<MbtType> {
-        self.dst_type.or_else(|| {
-            self.dst_type_cli.map(|t| match t {
-                MbtTypeCli::Flat => Flat,
-                MbtTypeCli::FlatWithHash => FlatWithHash,
-                MbtTypeCli::Normalized => Normalized {
-                    hash_view: true,
-                    schema: NormalizedSchema::Hash,
-                },
-                MbtTypeCli::Cache => Cache,
-            })
-        })
+        self.dst_type
+            .or_else(|| self.dst_type_cli.map(MbtType::from))
     }
 }
 
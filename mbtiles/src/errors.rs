@@ -131,6 +131,11 @@ pub enum MbtError {
         desired: MbtType,
     },
 
+    #[error(
+        "Writing single tiles is only supported for the flat, flat-with-hash and normalized (map/images) schemas, but MBTiles file {0} uses the {1} schema"
+    )]
+    UnsupportedTileWrite(String, &'static str),
+
     #[error(
         "Unless  --on-duplicate (override|ignore|abort)  is set, writing tiles to an existing non-empty MBTiles file is disabled. Either set --on-duplicate flag, or delete {0}"
     )]
@@ -179,6 +184,29 @@ pub enum MbtError {
         agg_hash: String,
     },
 
+    #[error(
+        "The {AGG_TILES_HASH_BEFORE_APPLY}='{before_apply_hash}' in patch file {patch_file} does not match {AGG_TILES_HASH_AFTER_APPLY}='{after_apply_hash}' in the previous patch file {previous_file}, so the patches cannot be applied one after another"
+    )]
+    BrokenPatchChain {
+        patch_file: String,
+        before_apply_hash: String,
+        previous_file: String,
+        after_apply_hash: String,
+    },
+
+    #[error(
+        "Patch files {onto_file} and {patch_file} change {count} tiles in different ways, e.g. {tiles}. Use `--force` to keep the tiles of {patch_file}"
+    )]
+    ConflictingPatches {
+        patch_file: String,
+        onto_file: String,
+        count: usize,
+        tiles: String,
+    },
+
+    #[error("No patch files were given")]
+    NoPatchFiles,
+
     #[error(
         "MBTile of type {0} is not supported when using bin-diff.  The bin-diff format only works with flat and flat-with-hash MBTiles files."
     )]
@@ -227,6 +255,68 @@ pub enum MbtError {
     #[cfg(feature = "transcode")]
     #[error("Transcoding error: {0}")]
     TranscodeError(String),
+
+    #[cfg(feature = "transcode")]
+    #[error("Transcoding {src} tiles to {dst} is not supported")]
+    UnsupportedTranscode { src: TileInfo, dst: TileInfo },
+
+    #[cfg(feature = "transcode")]
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+
+    #[cfg(feature = "transcode")]
+    #[error("Invalid vector tile: {0}")]
+    InvalidVectorTile(&'static str),
+
+    #[cfg(feature = "transcode")]
+    #[error("Cannot merge the {layer} layers with the extents {extent} and {other}")]
+    IncompatibleMergeLayers {
+        layer: String,
+        extent: u64,
+        other: u64,
+    },
+
+    #[cfg(feature = "transcode")]
+    #[error(
+        "Cannot merge the {info} tiles of {path}, only MVT, PNG, JPEG and WebP tiles can be merged"
+    )]
+    UnsupportedMergeFormat { path: PathBuf, info: TileInfo },
+
+    #[cfg(feature = "transcode")]
+    #[error("The {info} tiles of {path} cannot be merged with {expected} tiles")]
+    IncompatibleMergeFormats {
+        path: PathBuf,
+        info: TileInfo,
+        expected: TileInfo,
+    },
+
+    #[cfg(feature = "transcode")]
+    #[error("No files to merge")]
+    NoMergeInputs,
+
+    #[cfg(feature = "transcode")]
+    #[error("Only MVT and MLT tiles have layers, but {path} has {info} tiles")]
+    NoVectorTiles { path: PathBuf, info: TileInfo },
+
+    #[cfg(feature = "transcode")]
+    #[error("Unable to decode tile {0:#}: {1}")]
+    InvalidTileData(martin_tile_utils::TileCoord, String),
+
+    #[cfg(feature = "transcode")]
+    #[error("Invalid layer filter: {0}")]
+    InvalidLayerFilter(String),
+
+    #[cfg(feature = "transcode")]
+    #[error(
+        "Overviews can only be built from PNG, JPEG or WebP tiles, but {path} has {info} tiles"
+    )]
+    NoRasterTiles { path: PathBuf, info: TileInfo },
+
+    #[cfg(feature = "transcode")]
+    #[error(
+        "Overviews are built below zoom {max_zoom}, so the minimum zoom {min_zoom} must be lower"
+    )]
+    InvalidOverviewZooms { min_zoom: u8, max_zoom: u8 },
 }
 
 pub type MbtResult<T> = Result<T, MbtError>;
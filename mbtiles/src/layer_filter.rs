@@ -0,0 +1,533 @@
+//! Remove layers and attributes of vector tiles while copying them.
+//!
+//! A [`LayerFilter`] decodes each MVT or MLT tile, drops the layers and attributes it does not keep,
+//! and encodes the tile again. The `vector_layers` and `tilestats` metadata is rewritten to match.
+
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use bytes::Bytes;
+use martin_tile_utils::{Format, MAX_ZOOM, TileInfo};
+use mlt_core::encoder::EncoderConfig;
+use serde_json::Value;
+use sqlx::{Connection as _, SqliteConnection};
+use tilejson::VectorLayer;
+use tracing::info;
+
+use crate::MbtType::Normalized;
+use crate::mvt::{decode_tile, encode_tile};
+use crate::queries::is_empty_database;
+use crate::transcode::{compress, decompress, mlt_to_mvt, mvt_to_mlt};
+use crate::{
+    MbtError, MbtResult, MbtType, Mbtiles, MbtilesCopier, MbtilesTranscoder, NormalizedSchema,
+    TileTransform,
+};
+
+/// Which layers of vector tiles to keep, with which attributes, and at which zoom levels.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LayerFilter {
+    /// Layers to keep. All layers are kept if empty.
+    pub include_layers: HashSet<String>,
+    /// Layers to remove.
+    pub exclude_layers: HashSet<String>,
+    /// Attributes to keep, by layer. Layers not listed keep all their attributes.
+    pub include_attributes: HashMap<String, HashSet<String>>,
+    /// Attributes to remove, by layer.
+    pub exclude_attributes: HashMap<String, HashSet<String>>,
+    /// Zoom levels at which a layer is kept, by layer. Layers not listed are kept at all zoom levels.
+    pub layer_zooms: HashMap<String, RangeInclusive<u8>>,
+}
+
+impl LayerFilter {
+    /// Creates a filter from lists of layers, `(layer, attributes)` and `(layer, zooms)` pairs,
+    /// e.g. as parsed by [`parse_layer_attributes`] and [`parse_layer_zooms`].
+    /// Attributes listed several times for the same layer are combined.
+    pub fn new(
+        include_layers: impl IntoIterator<Item = String>,
+        exclude_layers: impl IntoIterator<Item = String>,
+        include_attributes: impl IntoIterator<Item = (String, Vec<String>)>,
+        exclude_attributes: impl IntoIterator<Item = (String, Vec<String>)>,
+        layer_zooms: impl IntoIterator<Item = (String, RangeInclusive<u8>)>,
+    ) -> Self {
+        Self {
+            include_layers: include_layers.into_iter().collect(),
+            exclude_layers: exclude_layers.into_iter().collect(),
+            include_attributes: group_attributes(include_attributes),
+            exclude_attributes: group_attributes(exclude_attributes),
+            layer_zooms: layer_zooms.into_iter().collect(),
+        }
+    }
+
+    /// Whether the filter keeps all layers and attributes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.include_layers.is_empty()
+            && self.exclude_layers.is_empty()
+            && self.include_attributes.is_empty()
+            && self.exclude_attributes.is_empty()
+            && self.layer_zooms.is_empty()
+    }
+
+    /// Whether the `layer` is kept in tiles of the given `zoom` level.
+    #[must_use]
+    pub fn keeps_layer(&self, layer: &str, zoom: u8) -> bool {
+        self.keeps_layer_name(layer)
+            && self
+                .layer_zooms
+                .get(layer)
+                .is_none_or(|zooms| zooms.contains(&zoom))
+    }
+
+    /// Whether the attribute `key` of the features in `layer` is kept.
+    #[must_use]
+    pub fn keeps_attribute(&self, layer: &str, key: &str) -> bool {
+        self.include_attributes
+            .get(layer)
+            .is_none_or(|keys| keys.contains(key))
+            && self
+                .exclude_attributes
+                .get(layer)
+                .is_none_or(|keys| !keys.contains(key))
+    }
+
+    fn keeps_layer_name(&self, layer: &str) -> bool {
+        (self.include_layers.is_empty() || self.include_layers.contains(layer))
+            && !self.exclude_layers.contains(layer)
+    }
+
+    /// Filters a single MVT or MLT tile of the given `zoom` level.
+    /// Tiles that do not change are returned as-is, and tiles without any layer left are returned empty.
+    pub fn apply(&self, data: Vec<u8>, info: TileInfo, zoom: u8) -> MbtResult<Vec<u8>> {
+        if self.is_empty() {
+            return Ok(data);
+        }
+        let tile = decompress(data.clone(), info.encoding)?;
+        let tile = match info.format {
+            Format::Mvt => tile,
+            Format::Mlt => mlt_to_mvt(&tile)?,
+            _ => {
+                return Err(MbtError::TranscodeError(format!(
+                    "layers of {info} tiles cannot be filtered"
+                )));
+            }
+        };
+
+        let mut layers = decode_tile(&tile)?;
+        let count = layers.len();
+        layers.retain(|layer| self.keeps_layer(&layer.name, zoom));
+        let mut changed = layers.len() != count;
+        for layer in &mut layers {
+            let name = layer.name.clone();
+            changed |= layer.retain_attributes(|key| self.keeps_attribute(&name, key))?;
+        }
+        if !changed {
+            return Ok(data);
+        }
+        if layers.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let tile = encode_tile(&layers);
+        let tile = match info.format {
+            Format::Mlt => mvt_to_mlt(tile, EncoderConfig::default())?,
+            _ => tile,
+        };
+        compress(tile, info.encoding)
+    }
+
+    /// Removes the layers and fields that are not kept from the `vector_layers` metadata,
+    /// and limits the zoom levels of the layers to the ones they are kept at.
+    pub fn filter_vector_layers(&self, layers: &mut Vec<VectorLayer>) {
+        layers.retain_mut(|layer| {
+            if !self.keeps_layer_name(&layer.id) {
+                return false;
+            }
+            layer
+                .fields
+                .retain(|key, _| self.keeps_attribute(&layer.id, key));
+            let Some(zooms) = self.layer_zooms.get(&layer.id) else {
+                return true;
+            };
+            let minzoom = layer.minzoom.unwrap_or(0).max(*zooms.start());
+            let maxzoom = layer.maxzoom.unwrap_or(MAX_ZOOM).min(*zooms.end());
+            layer.minzoom = Some(minzoom);
+            layer.maxzoom = Some(maxzoom);
+            minzoom <= maxzoom
+        });
+    }
+
+    /// Removes the layers and attributes that are not kept from
+    /// [`tilestats`](https://github.com/mapbox/mapbox-geostats) metadata.
+    fn filter_tilestats(&self, tilestats: &mut Value) {
+        let Some(Value::Array(layers)) = tilestats.get_mut("layers") else {
+            return;
+        };
+        layers.retain_mut(|layer| {
+            let Some(name) = layer
+                .get("layer")
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+            else {
+                return true;
+            };
+            if !self.keeps_layer_name(&name) {
+                return false;
+            }
+            if let Some(Value::Array(attributes)) = layer.get_mut("attributes") {
+                attributes.retain(|attr| {
+                    attr.get("attribute")
+                        .and_then(Value::as_str)
+                        .is_none_or(|key| self.keeps_attribute(&name, key))
+                });
+                let count = attributes.len();
+                if let Some(value) = layer.get_mut("attributeCount") {
+                    *value = count.into();
+                }
+            }
+            true
+        });
+        let count = layers.len();
+        if let Some(value) = tilestats.get_mut("layerCount") {
+            *value = count.into();
+        }
+    }
+
+    /// Rewrites the `json` metadata value of `mbt` to match the filtered tiles.
+    async fn filter_metadata(&self, mbt: &Mbtiles, conn: &mut SqliteConnection) -> MbtResult<()> {
+        let Some(json) = mbt.get_metadata_value(&mut *conn, "json").await? else {
+            return Ok(());
+        };
+        let Ok(mut json) = serde_json::from_str::<Value>(&json) else {
+            // Invalid JSON is reported by `mbtiles validate`, not fixed here
+            return Ok(());
+        };
+        if let Some(value) = json.get_mut("vector_layers") {
+            let mut layers: Vec<VectorLayer> = serde_json::from_value(value.take())?;
+            self.filter_vector_layers(&mut layers);
+            *value = serde_json::to_value(layers)?;
+        }
+        if let Some(tilestats) = json.get_mut("tilestats") {
+            self.filter_tilestats(tilestats);
+        }
+        mbt.set_metadata_value(&mut *conn, "json", &serde_json::to_string(&json)?)
+            .await
+    }
+}
+
+fn group_attributes(
+    items: impl IntoIterator<Item = (String, Vec<String>)>,
+) -> HashMap<String, HashSet<String>> {
+    let mut attributes: HashMap<String, HashSet<String>> = HashMap::new();
+    for (layer, keys) in items {
+        attributes.entry(layer).or_default().extend(keys);
+    }
+    attributes
+}
+
+/// Parses a `LAYER=ATTR,ATTR,...` list of attributes of a layer.
+pub fn parse_layer_attributes(value: &str) -> MbtResult<(String, Vec<String>)> {
+    let (layer, attributes) = split_layer(value)?;
+    let attributes = attributes
+        .split(',')
+        .map(str::trim)
+        .filter(|attr| !attr.is_empty())
+        .map(str::to_owned)
+        .collect();
+    Ok((layer, attributes))
+}
+
+/// Parses the `LAYER=MIN-MAX` zoom levels of a layer. Either bound may be omitted, as in `LAYER=5-`,
+/// and a single zoom level like `LAYER=5` keeps the layer only at that zoom.
+pub fn parse_layer_zooms(value: &str) -> MbtResult<(String, RangeInclusive<u8>)> {
+    let (layer, zooms) = split_layer(value)?;
+    let zoom = |v: &str, default: u8| -> MbtResult<u8> {
+        let v = v.trim();
+        if v.is_empty() {
+            return Ok(default);
+        }
+        match v.parse() {
+            Ok(zoom) if zoom <= MAX_ZOOM => Ok(zoom),
+            _ => Err(MbtError::InvalidLayerFilter(format!(
+                "'{v}' is not a zoom level between 0 and {MAX_ZOOM} in '{value}'"
+            ))),
+        }
+    };
+    let (min, max) = if let Some((min, max)) = zooms.split_once('-') {
+        (zoom(min, 0)?, zoom(max, MAX_ZOOM)?)
+    } else {
+        let z = zoom(zooms, 0)?;
+        (z, z)
+    };
+    if min > max {
+        return Err(MbtError::InvalidLayerFilter(format!(
+            "the minimum zoom is above the maximum zoom in '{value}'"
+        )));
+    }
+    Ok((layer, min..=max))
+}
+
+fn split_layer(value: &str) -> MbtResult<(String, &str)> {
+    match value.split_once('=') {
+        Some((layer, rest)) if !layer.trim().is_empty() => Ok((layer.trim().to_owned(), rest)),
+        _ => Err(MbtError::InvalidLayerFilter(format!(
+            "expected LAYER=VALUE, got '{value}'"
+        ))),
+    }
+}
+
+/// Applies a [`LayerFilter`] to the tiles in [`MbtilesTranscoder`].
+struct FilterTransform {
+    filter: LayerFilter,
+    info: TileInfo,
+}
+
+impl TileTransform for FilterTransform {
+    fn transform(
+        &self,
+        zoom: Option<u8>,
+        data: Vec<u8>,
+    ) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(zoom) = zoom else {
+            return Err("the zoom level of the tile is unknown".into());
+        };
+        Ok(Bytes::from(self.filter.apply(data, self.info, zoom)?))
+    }
+
+    fn uses_zoom(&self) -> bool {
+        true
+    }
+}
+
+/// Copies the tiles selected by `copier` into a temporary file next to the destination,
+/// and filters their layers while transcoding them into the new destination file.
+pub(crate) async fn copy_filtered(mut copier: MbtilesCopier) -> MbtResult<SqliteConnection> {
+    if copier.diff_with_file.is_some() || copier.apply_patch.is_some() {
+        return Err(MbtError::UnsupportedCopyOperation {
+            reason: "layers cannot be filtered while creating or applying a diff".to_owned(),
+        });
+    }
+    let filter = mem::take(&mut copier.layer_filter);
+
+    let src = Mbtiles::new(&copier.src_file)?;
+    let mut conn = src.open_readonly().await?;
+    let metadata = src.get_metadata(&mut conn).await?;
+    let info = src.detect_format(&metadata.tilejson, &mut conn).await?;
+    let src_type = src.detect_type(&mut conn).await?;
+    conn.close().await?;
+    let Some(info) = info else {
+        return Err(MbtError::NoFormatInMetadata(copier.src_file));
+    };
+    if !matches!(info.format, Format::Mvt | Format::Mlt) {
+        return Err(MbtError::NoVectorTiles {
+            path: copier.src_file,
+            info,
+        });
+    }
+
+    let dst = Mbtiles::new(&copier.dst_file)?;
+    if !copier.copy.copy_tiles() {
+        let mut conn = copier.run_unfiltered().await?;
+        filter.filter_metadata(&dst, &mut conn).await?;
+        return Ok(conn);
+    }
+
+    let mut conn = dst.open_or_new().await?;
+    if !is_empty_database(&mut conn).await? {
+        return Err(MbtError::DestinationFileExists(copier.dst_file));
+    }
+    conn.close().await?;
+
+    let mut dst_type = copier.dst_type().unwrap_or(src_type);
+    // When copying from a DedupId source, always create standard Hash schema in destination
+    if let Normalized {
+        hash_view,
+        schema: NormalizedSchema::DedupId,
+    } = dst_type
+    {
+        dst_type = Normalized {
+            hash_view,
+            schema: NormalizedSchema::Hash,
+        };
+    }
+
+    let dir = copier
+        .dst_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let temp = tempfile::Builder::new()
+        .prefix(".mbtiles-filter-")
+        .suffix(".mbtiles")
+        .tempfile_in(dir)?;
+    info!("Copying {src} to filter the layers of its {info} tiles");
+    MbtilesCopier {
+        dst_file: temp.path().to_path_buf(),
+        dst_type_cli: None,
+        dst_type: Some(MbtType::Flat),
+        on_duplicate: None,
+        skip_agg_tiles_hash: true,
+        ..copier.clone()
+    }
+    .run_unfiltered()
+    .await?
+    .close()
+    .await?;
+
+    let transform = FilterTransform {
+        filter: filter.clone(),
+        info,
+    };
+    let stats = MbtilesTranscoder::with_transform(temp.path(), &copier.dst_file, transform)
+        .dst_type(dst_type)
+        .run()
+        .await?;
+    info!("Wrote {} filtered tiles to {dst}", stats.tiles_written);
+
+    let mut conn = dst.open().await?;
+    filter.filter_metadata(&dst, &mut conn).await?;
+    if !copier.skip_agg_tiles_hash {
+        dst.update_agg_tiles_hash(&mut conn).await?;
+    }
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use martin_tile_utils::Encoding;
+    use mlt_core::mvt::mvt_to_tile_layers;
+
+    use super::*;
+    use crate::anonymous_mbtiles;
+
+    const WORLD_CITIES: &str = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+
+    async fn first_tile() -> Vec<u8> {
+        let (_mbt, mut conn) = anonymous_mbtiles(WORLD_CITIES).await;
+        sqlx::query_scalar("SELECT tile_data FROM tiles WHERE zoom_level = 0")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap()
+    }
+
+    fn layers(data: &[u8]) -> Vec<(String, Vec<String>)> {
+        let tile = decompress(data.to_vec(), Encoding::Gzip).unwrap();
+        mvt_to_tile_layers(tile)
+            .unwrap()
+            .iter()
+            .map(|l| (l.name().to_owned(), l.property_names().to_vec()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn filters_tiles() {
+        let data = first_tile().await;
+        let info = TileInfo::new(Format::Mvt, Encoding::Gzip);
+        let names = vec![("cities".to_owned(), vec!["name".to_owned()])];
+        assert_eq!(layers(&data), names);
+
+        let filter = LayerFilter::default();
+        assert_eq!(filter.apply(data.clone(), info, 0).unwrap(), data);
+        let filter = LayerFilter::new([], ["roads".to_owned()], [], [], []);
+        assert_eq!(filter.apply(data.clone(), info, 0).unwrap(), data);
+
+        let filter = LayerFilter::new(["roads".to_owned()], [], [], [], []);
+        assert!(filter.apply(data.clone(), info, 0).unwrap().is_empty());
+        let filter = LayerFilter::new([], [], [], [], [("cities".to_owned(), 3..=5)]);
+        assert!(filter.apply(data.clone(), info, 0).unwrap().is_empty());
+        assert_eq!(filter.apply(data.clone(), info, 3).unwrap(), data);
+
+        let filter = LayerFilter::new([], [], [], names, []);
+        let filtered = filter.apply(data, info, 0).unwrap();
+        assert_eq!(layers(&filtered), vec![("cities".to_owned(), vec![])]);
+    }
+
+    #[test]
+    fn filters_vector_layers() {
+        let layer = |id: &str, minzoom, maxzoom| VectorLayer {
+            id: id.to_owned(),
+            fields: BTreeMap::from([
+                ("name".to_owned(), "String".to_owned()),
+                ("rank".to_owned(), "Number".to_owned()),
+            ]),
+            description: None,
+            minzoom,
+            maxzoom,
+            other: BTreeMap::default(),
+        };
+        let filter = LayerFilter::new(
+            [],
+            ["water".to_owned()],
+            [("roads".to_owned(), vec!["name".to_owned()])],
+            [],
+            [("roads".to_owned(), 4..=20), ("poi".to_owned(), 0..=3)],
+        );
+        let mut layers = vec![
+            layer("roads", Some(2), Some(14)),
+            layer("water", None, None),
+            layer("poi", Some(10), Some(14)),
+        ];
+        filter.filter_vector_layers(&mut layers);
+
+        let mut roads = layer("roads", Some(4), Some(14));
+        roads.fields.remove("rank");
+        assert_eq!(layers, vec![roads]);
+    }
+
+    #[test]
+    fn filters_tilestats() {
+        let filter = LayerFilter::new(
+            [],
+            ["water".to_owned()],
+            [],
+            [("roads".to_owned(), vec!["rank".to_owned()])],
+            [],
+        );
+        let mut tilestats = serde_json::json!({
+            "layerCount": 2,
+            "layers": [
+                {"layer": "roads", "attributeCount": 2, "attributes": [{"attribute": "name"}, {"attribute": "rank"}]},
+                {"layer": "water", "attributeCount": 0, "attributes": []},
+            ],
+        });
+        filter.filter_tilestats(&mut tilestats);
+        assert_eq!(
+            tilestats,
+            serde_json::json!({
+                "layerCount": 1,
+                "layers": [
+                    {"layer": "roads", "attributeCount": 1, "attributes": [{"attribute": "name"}]},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn parses_arguments() {
+        assert_eq!(
+            parse_layer_attributes("roads=name, class").unwrap(),
+            (
+                "roads".to_owned(),
+                vec!["name".to_owned(), "class".to_owned()]
+            )
+        );
+        assert_eq!(
+            parse_layer_attributes("roads=").unwrap(),
+            ("roads".to_owned(), vec![])
+        );
+        parse_layer_attributes("roads").unwrap_err();
+        parse_layer_attributes("=name").unwrap_err();
+
+        let zooms = |v| parse_layer_zooms(v).unwrap().1;
+        assert_eq!(zooms("roads=4-10"), 4..=10);
+        assert_eq!(zooms("roads=4-"), 4..=MAX_ZOOM);
+        assert_eq!(zooms("roads=-10"), 0..=10);
+        assert_eq!(zooms("roads=7"), 7..=7);
+        parse_layer_zooms("roads=10-4").unwrap_err();
+        parse_layer_zooms("roads=40").unwrap_err();
+        parse_layer_zooms("roads=a-b").unwrap_err();
+    }
+}
@@ -0,0 +1,444 @@
+//! Per-layer statistics of the vector tiles in a file, shown by `mbtiles summary --layers`.
+//!
+//! Every MVT or MLT tile is decoded to count its features, geometry types and attribute values,
+//! and to measure the encoded size of each of its layers.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use futures::TryStreamExt as _;
+use martin_tile_utils::protobuf::Reader;
+use martin_tile_utils::{Format, TileCoord, TileInfo};
+use mlt_core::geo_types::{CoordNum, Geometry};
+use mlt_core::mvt::mvt_to_tile_layers;
+use mlt_core::{Decoder, Layer, Parser, PropValue, TileLayer};
+use serde::Serialize;
+use size_format::SizeFormatterSI;
+use sqlx::SqliteExecutor;
+
+use crate::mvt::layer_sizes;
+use crate::transcode::decompress;
+use crate::{MbtError, MbtResult, Mbtiles};
+
+/// Number of distinct values of an attribute to track.
+/// Attributes with more values, e.g. IDs or names, are reported as having at least this many.
+const MAX_DISTINCT_VALUES: usize = 1000;
+
+/// Per-layer statistics of the vector tiles of a file.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LayerSummary {
+    /// Layers found at each zoom level
+    pub zoom_info: Vec<ZoomLayers>,
+    /// Attributes of each layer, across all zoom levels
+    pub attributes: Vec<LayerAttributes>,
+    /// Largest tiles of the file, the largest first
+    pub largest_tiles: Vec<TileSize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ZoomLayers {
+    pub zoom: u8,
+    pub layers: Vec<LayerInfo>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LayerInfo {
+    pub layer: String,
+    /// Number of tiles containing the layer
+    pub tile_count: u64,
+    pub feature_count: u64,
+    /// Uncompressed size of the layer in all tiles
+    pub size: u64,
+    /// Uncompressed size of the layer in the tile where it is the largest
+    pub max_size: u64,
+    /// Number of features of each geometry type
+    pub geometry_types: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LayerAttributes {
+    pub layer: String,
+    pub attributes: Vec<AttributeInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AttributeInfo {
+    pub name: String,
+    /// Number of features with a value for this attribute
+    pub feature_count: u64,
+    /// Number of distinct values, at most [`MAX_DISTINCT_VALUES`]
+    pub distinct_values: usize,
+    /// True if the attribute has more distinct values than were counted
+    pub more_values: bool,
+}
+
+/// Stored size of a tile, with its XYZ coordinates.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct TileSize {
+    pub size: u64,
+    pub zoom: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Display for LayerSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .attributes
+            .iter()
+            .map(|l| l.layer.len())
+            .chain(["Layer".len()])
+            .max()
+            .unwrap_or_default();
+
+        if self.zoom_info.is_empty() {
+            writeln!(f, "   There are no layers in this mbtiles file")?;
+        } else {
+            writeln!(
+                f,
+                " {:^4} | {:^width$} | {:^9} | {:^9} | {:^9} | {:^9} | Geometry types",
+                "Zoom", "Layer", "Tiles", "Features", "Size", "Largest"
+            )?;
+        }
+        for z in &self.zoom_info {
+            for l in &z.layers {
+                let size = SizeFormatterSI::new(l.size);
+                let max = SizeFormatterSI::new(l.max_size);
+                let geometry_types = l
+                    .geometry_types
+                    .iter()
+                    .map(|(name, count)| format!("{name}: {count}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(
+                    f,
+                    " {:>4} | {:<width$} | {:>9} | {:>9} | {:>9} | {:>9} | {geometry_types}",
+                    z.zoom,
+                    l.layer,
+                    l.tile_count,
+                    l.feature_count,
+                    format!("{size:.1}B"),
+                    format!("{max:.1}B"),
+                )?;
+            }
+        }
+
+        let attributes = self
+            .attributes
+            .iter()
+            .flat_map(|l| l.attributes.iter().map(move |a| (&l.layer, a)));
+        let name_width = attributes
+            .clone()
+            .map(|(_, a)| a.name.len())
+            .chain(["Attribute".len()])
+            .max()
+            .unwrap_or_default();
+        if self.attributes.iter().any(|l| !l.attributes.is_empty()) {
+            writeln!(f)?;
+            writeln!(
+                f,
+                " {:^width$} | {:^name_width$} | {:^9} | {:^9}",
+                "Layer", "Attribute", "Features", "Values"
+            )?;
+        }
+        for (layer, a) in attributes {
+            let values = if a.more_values {
+                format!("{}+", a.distinct_values)
+            } else {
+                a.distinct_values.to_string()
+            };
+            writeln!(
+                f,
+                " {layer:<width$} | {:<name_width$} | {:>9} | {values:>9}",
+                a.name, a.feature_count,
+            )?;
+        }
+
+        if !self.largest_tiles.is_empty() {
+            writeln!(f)?;
+            writeln!(f, " {:^15} | {:^9}", "Largest tiles", "Size")?;
+        }
+        for t in &self.largest_tiles {
+            let size = SizeFormatterSI::new(t.size);
+            writeln!(
+                f,
+                " {:>15} | {:>9}",
+                format!("{}/{}/{}", t.zoom, t.x, t.y),
+                format!("{size:.1}B"),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Mbtiles {
+    /// Decode all vector tiles of the file to compute per-layer statistics,
+    /// keeping the `top_tiles` largest tiles.
+    #[hotpath::measure]
+    pub async fn layer_summary<T>(&self, conn: &mut T, top_tiles: usize) -> MbtResult<LayerSummary>
+    where
+        for<'e> &'e mut T: SqliteExecutor<'e>,
+    {
+        let metadata = self.get_metadata(&mut *conn).await?;
+        let info = self
+            .detect_format(&metadata.tilejson, &mut *conn)
+            .await?
+            .ok_or_else(|| MbtError::NoFormatInMetadata(PathBuf::from(self.filepath())))?;
+        if !matches!(info.format, Format::Mvt | Format::Mlt) {
+            return Err(MbtError::NoVectorTiles {
+                path: PathBuf::from(self.filepath()),
+                info,
+            });
+        }
+
+        let mut stats = LayerStats::default();
+        let mut largest = BinaryHeap::with_capacity(top_tiles + 1);
+        let mut tiles = self.stream_tiles(&mut *conn);
+        while let Some((coord, data)) = tiles.try_next().await? {
+            let Some(data) = data else { continue };
+            if top_tiles > 0 {
+                largest.push(Reverse(TileSize {
+                    size: data.len() as u64,
+                    zoom: coord.z,
+                    x: coord.x,
+                    y: coord.y,
+                }));
+                if largest.len() > top_tiles {
+                    largest.pop();
+                }
+            }
+            let layers = decode_layers(data, info)
+                .map_err(|reason| MbtError::InvalidTileData(coord, reason))?;
+            stats.add_tile(coord, &layers);
+        }
+
+        let largest_tiles = largest.into_sorted_vec().into_iter().map(|Reverse(t)| t);
+        Ok(stats.finish(largest_tiles.collect()))
+    }
+}
+
+/// Accumulates the statistics while the tiles are read.
+#[derive(Default)]
+struct LayerStats {
+    zooms: BTreeMap<u8, BTreeMap<String, LayerInfo>>,
+    attributes: BTreeMap<String, BTreeMap<String, AttributeValues>>,
+}
+
+#[derive(Default)]
+struct AttributeValues {
+    feature_count: u64,
+    values: HashSet<String>,
+    more_values: bool,
+}
+
+impl LayerStats {
+    fn add_tile(&mut self, coord: TileCoord, layers: &[(usize, TileLayer)]) {
+        let zoom = self.zooms.entry(coord.z).or_default();
+        for (size, layer) in layers {
+            let size = *size as u64;
+            let info = zoom.entry(layer.name().to_owned()).or_default();
+            info.tile_count += 1;
+            info.feature_count += layer.features().len() as u64;
+            info.size += size;
+            info.max_size = info.max_size.max(size);
+
+            let attributes = self.attributes.entry(layer.name().to_owned()).or_default();
+            for feature in layer.features() {
+                *info
+                    .geometry_types
+                    .entry(geometry_type(feature.geometry()).to_owned())
+                    .or_default() += 1;
+                for (name, value) in layer.property_names().iter().zip(feature.properties()) {
+                    let Some(value) = prop_value_key(value) else {
+                        continue;
+                    };
+                    let attribute = attributes.entry(name.clone()).or_default();
+                    attribute.feature_count += 1;
+                    if attribute.values.len() < MAX_DISTINCT_VALUES {
+                        attribute.values.insert(value);
+                    } else if !attribute.values.contains(&value) {
+                        attribute.more_values = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(self, largest_tiles: Vec<TileSize>) -> LayerSummary {
+        LayerSummary {
+            zoom_info: self
+                .zooms
+                .into_iter()
+                .map(|(zoom, layers)| ZoomLayers {
+                    zoom,
+                    layers: layers
+                        .into_iter()
+                        .map(|(layer, info)| LayerInfo { layer, ..info })
+                        .collect(),
+                })
+                .collect(),
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(|(layer, attributes)| LayerAttributes {
+                    layer,
+                    attributes: attributes
+                        .into_iter()
+                        .map(|(name, a)| AttributeInfo {
+                            name,
+                            feature_count: a.feature_count,
+                            distinct_values: a.values.len(),
+                            more_values: a.more_values,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            largest_tiles,
+        }
+    }
+}
+
+/// Decodes the layers of a tile, together with their uncompressed size.
+pub(crate) fn decode_layers(
+    data: Vec<u8>,
+    info: TileInfo,
+) -> Result<Vec<(usize, TileLayer)>, String> {
+    let data = decompress(data, info.encoding).map_err(|e| e.to_string())?;
+    if info.format == Format::Mlt {
+        let sizes = mlt_layer_sizes(&data).map_err(|e| e.to_string())?;
+        let layers = Parser::default()
+            .parse_layers(&data)
+            .map_err(|e| format!("MLT parse failed: {e}"))?;
+        let mut decoder = Decoder::default();
+        let mut result = Vec::with_capacity(layers.len());
+        for (size, layer) in sizes.into_iter().zip(layers) {
+            // Layers with unknown tags cannot be decoded
+            if let Layer::Tag01(layer) = layer {
+                let layer = layer
+                    .into_tile(&mut decoder)
+                    .map_err(|e| format!("MLT decode failed: {e}"))?;
+                result.push((size, layer));
+            }
+        }
+        Ok(result)
+    } else {
+        let sizes = layer_sizes(&data).map_err(|e| e.to_string())?;
+        let layers = mvt_to_tile_layers(data).map_err(|e| format!("MVT decode failed: {e}"))?;
+        Ok(sizes.into_iter().zip(layers).collect())
+    }
+}
+
+/// Encoded size of each layer of an MLT tile. Every layer starts with its varint length.
+fn mlt_layer_sizes(data: &[u8]) -> MbtResult<Vec<usize>> {
+    let mut sizes = Vec::new();
+    let mut reader = Reader::new(data);
+    while !reader.is_empty() {
+        let start = reader.position();
+        let len = usize::try_from(reader.varint()?)
+            .map_err(|_err| MbtError::InvalidVectorTile("layer is too long"))?;
+        reader.take(len)?;
+        sizes.push(reader.position() - start);
+    }
+    Ok(sizes)
+}
+
+pub(crate) fn geometry_type<T: CoordNum>(geometry: &Geometry<T>) -> &'static str {
+    match geometry {
+        Geometry::Point(_) => "Point",
+        Geometry::MultiPoint(_) => "MultiPoint",
+        Geometry::Line(_) | Geometry::LineString(_) => "LineString",
+        Geometry::MultiLineString(_) => "MultiLineString",
+        Geometry::Polygon(_) | Geometry::Rect(_) | Geometry::Triangle(_) => "Polygon",
+        Geometry::MultiPolygon(_) => "MultiPolygon",
+        Geometry::GeometryCollection(_) => "GeometryCollection",
+    }
+}
+
+/// The value of an attribute as a string, to count distinct values.
+/// Returns `None` if the feature has no value for the attribute.
+fn prop_value_key(value: &PropValue) -> Option<String> {
+    Some(match value {
+        PropValue::Bool(v) => (*v)?.to_string(),
+        PropValue::I8(v) => (*v)?.to_string(),
+        PropValue::U8(v) => (*v)?.to_string(),
+        PropValue::I32(v) => (*v)?.to_string(),
+        PropValue::U32(v) => (*v)?.to_string(),
+        PropValue::I64(v) => (*v)?.to_string(),
+        PropValue::U64(v) => (*v)?.to_string(),
+        PropValue::F32(v) => (*v)?.to_string(),
+        PropValue::F64(v) => (*v)?.to_string(),
+        PropValue::Str(v) => v.clone()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::anonymous_mbtiles;
+
+    #[actix_rt::test]
+    async fn layer_summary() {
+        let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+        let (mbt, mut conn) = anonymous_mbtiles(script).await;
+        let res = mbt.layer_summary(&mut conn, 3).await.unwrap();
+
+        assert_eq!(res.zoom_info.len(), 7);
+        for layer in res.zoom_info.iter().flat_map(|z| &z.layers) {
+            assert_eq!(layer.layer, "cities");
+        }
+        let z0 = &res.zoom_info[0].layers[0];
+        assert_eq!(z0.tile_count, 1);
+        assert!(z0.feature_count > 0);
+        assert!(z0.size > 0);
+        assert_eq!(z0.size, z0.max_size);
+        assert_eq!(
+            z0.geometry_types,
+            BTreeMap::from([
+                ("MultiPoint".to_owned(), 1),
+                ("Point".to_owned(), z0.feature_count - 1),
+            ])
+        );
+
+        assert_eq!(res.attributes.len(), 1);
+        assert_eq!(res.attributes[0].layer, "cities");
+        let name = res.attributes[0]
+            .attributes
+            .iter()
+            .find(|a| a.name == "name")
+            .unwrap();
+        assert!(name.distinct_values > 0);
+        assert!(!name.more_values);
+
+        assert_eq!(res.largest_tiles.len(), 3);
+        assert_eq!(
+            res.largest_tiles[0],
+            TileSize {
+                size: 1107,
+                zoom: 0,
+                x: 0,
+                y: 0
+            }
+        );
+        assert!(res.largest_tiles[1].size >= res.largest_tiles[2].size);
+        assert!(!res.to_string().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn layer_summary_of_raster_tiles() {
+        let script = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+        let (mbt, mut conn) = anonymous_mbtiles(script).await;
+        let res = mbt.layer_summary(&mut conn, 10).await;
+        assert!(matches!(res, Err(MbtError::NoVectorTiles { .. })));
+    }
+
+    #[test]
+    fn measures_mlt_layers() {
+        // two layers of 3 and 1 bytes, each preceded by its length
+        let tile = [3, 1, 0xaa, 0xbb, 1, 1];
+        assert_eq!(mlt_layer_sizes(&tile).unwrap(), vec![4, 2]);
+        mlt_layer_sizes(&[5, 1]).unwrap_err();
+    }
+}
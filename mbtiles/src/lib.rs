@@ -16,15 +16,43 @@ pub use copier::{CopyDuplicateMode, MbtilesCopier};
 mod errors;
 pub use errors::{MbtError, MbtResult};
 
+#[cfg(feature = "transcode")]
+mod layer_filter;
+#[cfg(feature = "transcode")]
+pub use layer_filter::{LayerFilter, parse_layer_attributes, parse_layer_zooms};
+
+#[cfg(feature = "transcode")]
+mod layer_summary;
+#[cfg(feature = "transcode")]
+pub use layer_summary::{
+    AttributeInfo, LayerAttributes, LayerInfo, LayerSummary, TileSize, ZoomLayers,
+};
+
 mod mbtiles;
 pub use mbtiles::{CopyType, MbtTypeCli, Mbtiles};
 
+#[cfg(feature = "transcode")]
+mod merge;
+#[cfg(feature = "transcode")]
+pub use merge::{MergeOptions, MergeStats, merge};
+
 mod metadata;
 pub use metadata::{Metadata, anonymous_mbtiles, temp_named_mbtiles};
 
+#[cfg(feature = "transcode")]
+mod mvt;
+
+#[cfg(feature = "transcode")]
+mod overviews;
+#[cfg(feature = "transcode")]
+pub use overviews::{OverviewFilter, OverviewOptions, OverviewStats, build_overviews};
+
 mod pack;
 pub use pack::{PackCompression, TileScheme, pack, unpack};
 
+mod patch_chain;
+pub use patch_chain::{apply_patches, rebase_patch, squash_patches};
+
 mod patcher;
 pub use patcher::apply_patch;
 
@@ -45,13 +73,28 @@ pub use update::UpdateZoomType;
 mod bindiff;
 pub use bindiff::get_patch_type;
 
+#[cfg(feature = "transcode")]
+mod tile_validation;
+#[cfg(feature = "transcode")]
+pub use tile_validation::{TileProblem, TileValidationReport};
+
+#[cfg(feature = "transcode")]
+mod transcode;
+#[cfg(feature = "transcode")]
+pub use transcode::{
+    DEFAULT_TRANSCODE_QUALITY, TileTranscode, TranscodeCompression, TranscodeFormat,
+    TranscodeOptions, transcode,
+};
+
 #[cfg(feature = "transcode")]
 mod transcoder;
 #[cfg(feature = "transcode")]
-pub use transcoder::{MbtilesTranscoder, TranscodeStats};
+pub use transcoder::{MbtilesTranscoder, TileTransform, TranscodeStats};
 
 mod validation;
 
+mod writer;
+
 pub use martin_tile_utils::{Tile, TileCoord};
 pub use validation::{
     AGG_TILES_HASH, AGG_TILES_HASH_AFTER_APPLY, AGG_TILES_HASH_BEFORE_APPLY, AggHashType,
@@ -30,6 +30,20 @@ pub enum MbtTypeCli {
     Cache,
 }
 
+impl From<MbtTypeCli> for MbtType {
+    fn from(value: MbtTypeCli) -> Self {
+        match value {
+            MbtTypeCli::Flat => Self::Flat,
+            MbtTypeCli::FlatWithHash => Self::FlatWithHash,
+            MbtTypeCli::Normalized => Self::Normalized {
+                hash_view: true,
+                schema: NormalizedSchema::Hash,
+            },
+            MbtTypeCli::Cache => Self::Cache,
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, EnumDisplay)]
 #[enum_display(case = "Kebab")]
 #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
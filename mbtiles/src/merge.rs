@@ -0,0 +1,374 @@
+//! Join several `MBTiles` files into one, like `tile-join` does.
+//!
+//! Tiles only found in one file are copied. When several files have a tile at the same
+//! coordinates, the layers of vector tiles are merged, and raster tiles are drawn on top
+//! of each other in the order of the files. The metadata of the files is merged as well.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use futures::TryStreamExt as _;
+use image::DynamicImage;
+use image::imageops::{FilterType, overlay, resize};
+use martin_tile_utils::{Format, TileInfo};
+use tilejson::{Bounds, TileJSON, VectorLayer};
+use tracing::{debug, info};
+
+use crate::mvt::{MvtLayer, decode_tile, encode_tile};
+use crate::queries::{init_mbtiles_schema, is_empty_database};
+use crate::transcode::{compress, decompress, encode_image, is_raster};
+use crate::{
+    CopyDuplicateMode, DEFAULT_TRANSCODE_QUALITY, MbtError, MbtResult, MbtType, Mbtiles, Metadata,
+};
+
+/// Number of merged tiles written to the destination file in one batch.
+const MERGE_BATCH_SIZE: usize = 1000;
+
+/// How [`merge`] combines the files.
+#[derive(Clone, Debug)]
+pub struct MergeOptions {
+    /// Vector tile layers renamed before merging, from their original to their new name.
+    pub rename_layers: HashMap<String, String>,
+    /// Vector tile layers left out of the merged file, by their original name.
+    pub drop_layers: HashSet<String>,
+    /// Schema of the merged file, defaults to [`MbtType::Flat`].
+    pub dst_type: Option<MbtType>,
+    /// Quality of lossy raster output, from 1 to 100.
+    pub quality: u8,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            rename_layers: HashMap::new(),
+            drop_layers: HashSet::new(),
+            dst_type: None,
+            quality: DEFAULT_TRANSCODE_QUALITY,
+        }
+    }
+}
+
+/// Statistics returned after merging completes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    /// Tiles written to the merged file, including merged ones.
+    pub tiles_written: usize,
+    /// Tiles that were found in more than one file and merged.
+    pub tiles_merged: usize,
+}
+
+/// A source file with the format of its tiles.
+struct MergeSource {
+    mbt: Mbtiles,
+    info: TileInfo,
+    metadata: Metadata,
+}
+
+/// Merges all tiles and metadata of `src_files` into the new or empty `dst_file`.
+/// Tiles are stored in the format and encoding of the first file.
+pub async fn merge(
+    src_files: &[PathBuf],
+    dst_file: &Path,
+    options: &MergeOptions,
+) -> MbtResult<MergeStats> {
+    let mut sources = Vec::with_capacity(src_files.len());
+    for path in src_files {
+        let mbt = Mbtiles::new(path)?;
+        let mut conn = mbt.open_readonly().await?;
+        let metadata = mbt.get_metadata(&mut conn).await?;
+        let Some(info) = mbt.detect_format(&metadata.tilejson, &mut conn).await? else {
+            return Err(MbtError::NoFormatInMetadata(path.clone()));
+        };
+        sources.push(MergeSource {
+            mbt,
+            info,
+            metadata,
+        });
+    }
+    let Some(first) = sources.first() else {
+        return Err(MbtError::NoMergeInputs);
+    };
+    let dst_info = first.info;
+    for src in &sources {
+        // Only raster formats the `image` crate can decode
+        if !matches!(
+            src.info.format,
+            Format::Mvt | Format::Png | Format::Jpeg | Format::Webp
+        ) {
+            return Err(MbtError::UnsupportedMergeFormat {
+                path: PathBuf::from(src.mbt.filepath()),
+                info: src.info,
+            });
+        }
+        if is_raster(src.info.format) != is_raster(dst_info.format) {
+            return Err(MbtError::IncompatibleMergeFormats {
+                path: PathBuf::from(src.mbt.filepath()),
+                info: src.info,
+                expected: dst_info,
+            });
+        }
+    }
+
+    let dst = Mbtiles::new(dst_file)?;
+    let mut dst_conn = dst.open_or_new().await?;
+    if !is_empty_database(&mut dst_conn).await? {
+        return Err(MbtError::NonEmptyTargetFile(dst_file.to_path_buf()));
+    }
+    let dst_type = options.dst_type.unwrap_or(MbtType::Flat);
+    init_mbtiles_schema(&mut dst_conn, dst_type, false).await?;
+
+    let merger = TileMerger {
+        options,
+        dst: dst_info,
+    };
+    let mut stats = MergeStats::default();
+    for src in &sources {
+        info!("Merging {} tiles of {} into {dst}", src.info, src.mbt);
+        let mut src_conn = src.mbt.open_readonly().await?;
+        let mut tiles = src.mbt.stream_tiles(&mut src_conn);
+        let mut batch = Vec::with_capacity(MERGE_BATCH_SIZE);
+        while let Some((coord, data)) = tiles.try_next().await? {
+            let Some(data) = data else { continue };
+            let existing = dst
+                .get_tile(&mut dst_conn, coord.z, coord.x, coord.y)
+                .await?;
+            if existing.is_some() {
+                stats.tiles_merged += 1;
+            }
+            if let Some(tile) = merger.merge(existing, data, src.info)? {
+                batch.push((coord.z, coord.x, coord.y, tile));
+            }
+            if batch.len() >= MERGE_BATCH_SIZE {
+                stats.tiles_written += batch.len();
+                dst.insert_tiles(&mut dst_conn, dst_type, CopyDuplicateMode::Override, &batch)
+                    .await?;
+                batch.clear();
+            }
+        }
+        stats.tiles_written += batch.len();
+        dst.insert_tiles(&mut dst_conn, dst_type, CopyDuplicateMode::Override, &batch)
+            .await?;
+    }
+    // Merged tiles were written more than once
+    stats.tiles_written -= stats.tiles_merged;
+
+    let metadata: Vec<_> = sources.iter().map(|src| &src.metadata).collect();
+    let tilejson = merge_metadata(&metadata, options);
+    dst.insert_metadata(&mut dst_conn, &tilejson).await?;
+    dst.set_metadata_value(
+        &mut dst_conn,
+        "format",
+        dst_info.format.metadata_format_value(),
+    )
+    .await?;
+    if let Some(compression) = dst_info.encoding.compression() {
+        dst.set_metadata_value(&mut dst_conn, "compression", compression)
+            .await?;
+    } else {
+        dst.delete_metadata_value(&mut dst_conn, "compression")
+            .await?;
+    }
+    dst.update_agg_tiles_hash(&mut dst_conn).await?;
+
+    Ok(stats)
+}
+
+/// Combines the tiles of the source files into tiles of the `dst` format.
+struct TileMerger<'a> {
+    options: &'a MergeOptions,
+    dst: TileInfo,
+}
+
+impl TileMerger<'_> {
+    /// Returns the tile to store, given the tile already stored at its coordinates, if any.
+    /// Returns `None` if all layers of a vector tile were dropped.
+    fn merge(
+        &self,
+        existing: Option<Vec<u8>>,
+        data: Vec<u8>,
+        info: TileInfo,
+    ) -> MbtResult<Option<Vec<u8>>> {
+        if is_raster(self.dst.format) {
+            return self.merge_raster(existing, data, info).map(Some);
+        }
+        let has_layer_options =
+            !self.options.rename_layers.is_empty() || !self.options.drop_layers.is_empty();
+        if existing.is_none() && !has_layer_options && info.encoding == self.dst.encoding {
+            return Ok(Some(data));
+        }
+
+        // Tiles in the destination already had their layers renamed and dropped
+        let mut layers = match existing {
+            Some(existing) => decode_tile(&decompress(existing, self.dst.encoding)?)?,
+            None => Vec::new(),
+        };
+        for mut layer in decode_tile(&decompress(data, info.encoding)?)? {
+            if self.options.drop_layers.contains(&layer.name) {
+                continue;
+            }
+            if let Some(name) = self.options.rename_layers.get(&layer.name) {
+                layer.name.clone_from(name);
+            }
+            add_layer(&mut layers, layer)?;
+        }
+        if layers.is_empty() {
+            return Ok(None);
+        }
+        compress(encode_tile(&layers), self.dst.encoding).map(Some)
+    }
+
+    /// Draws the tile over the existing one, if any.
+    fn merge_raster(
+        &self,
+        existing: Option<Vec<u8>>,
+        data: Vec<u8>,
+        info: TileInfo,
+    ) -> MbtResult<Vec<u8>> {
+        let Some(existing) = existing else {
+            if info.format == self.dst.format {
+                return Ok(data);
+            }
+            let image = image::load_from_memory(&data)?;
+            return encode_image(&image, self.dst.format, self.options.quality);
+        };
+        let mut bottom = image::load_from_memory(&existing)?.to_rgba8();
+        let mut top = image::load_from_memory(&data)?.to_rgba8();
+        if top.dimensions() != bottom.dimensions() {
+            debug!(
+                "Resizing a {:?} tile to {:?} to merge it",
+                top.dimensions(),
+                bottom.dimensions()
+            );
+            top = resize(&top, bottom.width(), bottom.height(), FilterType::Lanczos3);
+        }
+        overlay(&mut bottom, &top, 0, 0);
+        encode_image(
+            &DynamicImage::ImageRgba8(bottom),
+            self.dst.format,
+            self.options.quality,
+        )
+    }
+}
+
+/// Adds a layer to a tile, merging it with the layer of the same name if there is one.
+fn add_layer(layers: &mut Vec<MvtLayer>, layer: MvtLayer) -> MbtResult<()> {
+    if let Some(existing) = layers.iter_mut().find(|l| l.name == layer.name) {
+        existing.merge(layer)
+    } else {
+        layers.push(layer);
+        Ok(())
+    }
+}
+
+/// Merges the metadata of the files: the first file provides the name, attribution and other
+/// values, bounds and zoom levels cover all files, and the vector layers of all files are combined.
+fn merge_metadata(metadata: &[&Metadata], options: &MergeOptions) -> TileJSON {
+    let mut tilejson = metadata[0].tilejson.clone();
+    let all = || metadata.iter().map(|m| &m.tilejson);
+
+    tilejson.bounds = all().filter_map(|tj| tj.bounds).reduce(|a, b| Bounds {
+        left: a.left.min(b.left),
+        bottom: a.bottom.min(b.bottom),
+        right: a.right.max(b.right),
+        top: a.top.max(b.top),
+    });
+    tilejson.minzoom = all().filter_map(|tj| tj.minzoom).min();
+    tilejson.maxzoom = all().filter_map(|tj| tj.maxzoom).max();
+
+    let mut vector_layers: BTreeMap<String, VectorLayer> = BTreeMap::new();
+    for mut layer in all().filter_map(|tj| tj.vector_layers.clone()).flatten() {
+        if options.drop_layers.contains(&layer.id) {
+            continue;
+        }
+        if let Some(name) = options.rename_layers.get(&layer.id) {
+            layer.id.clone_from(name);
+        }
+        if let Some(existing) = vector_layers.get_mut(&layer.id) {
+            for (field, kind) in layer.fields {
+                existing.fields.entry(field).or_insert(kind);
+            }
+            existing.minzoom = existing.minzoom.zip(layer.minzoom).map(|(a, b)| a.min(b));
+            existing.maxzoom = existing.maxzoom.zip(layer.maxzoom).map(|(a, b)| a.max(b));
+            if existing.description.is_none() {
+                existing.description = layer.description;
+            }
+        } else {
+            vector_layers.insert(layer.id.clone(), layer);
+        }
+    }
+    tilejson.vector_layers =
+        (!vector_layers.is_empty()).then(|| vector_layers.into_values().collect());
+
+    tilejson
+}
+
+#[cfg(test)]
+mod tests {
+    use tilejson::tilejson;
+
+    use super::*;
+
+    fn metadata(tilejson: TileJSON) -> Metadata {
+        Metadata {
+            id: "test".to_owned(),
+            layer_type: None,
+            tilejson,
+            json: None,
+            agg_tiles_hash: None,
+        }
+    }
+
+    fn vector_layer(id: &str, field: &str, minzoom: u8, maxzoom: u8) -> VectorLayer {
+        VectorLayer {
+            id: id.to_owned(),
+            fields: BTreeMap::from([(field.to_owned(), "String".to_owned())]),
+            description: None,
+            minzoom: Some(minzoom),
+            maxzoom: Some(maxzoom),
+            other: BTreeMap::default(),
+        }
+    }
+
+    #[test]
+    fn merges_metadata() {
+        let mut a = tilejson! {
+            tiles: vec![],
+            name: "a".to_owned(),
+            bounds: Bounds::new(0.0, 0.0, 10.0, 10.0),
+            minzoom: 2,
+            maxzoom: 10,
+        };
+        a.vector_layers = Some(vec![
+            vector_layer("roads", "class", 2, 10),
+            vector_layer("tmp", "x", 2, 10),
+        ]);
+        let mut b = tilejson! {
+            tiles: vec![],
+            name: "b".to_owned(),
+            bounds: Bounds::new(-5.0, 5.0, 5.0, 20.0),
+            minzoom: 0,
+            maxzoom: 8,
+        };
+        b.vector_layers = Some(vec![
+            vector_layer("streets", "name", 0, 14),
+            vector_layer("water", "kind", 0, 8),
+        ]);
+        let options = MergeOptions {
+            rename_layers: HashMap::from([("streets".to_owned(), "roads".to_owned())]),
+            drop_layers: HashSet::from(["tmp".to_owned()]),
+            ..MergeOptions::default()
+        };
+
+        let merged = merge_metadata(&[&metadata(a), &metadata(b)], &options);
+        assert_eq!(merged.name.as_deref(), Some("a"));
+        assert_eq!(merged.bounds, Some(Bounds::new(-5.0, 0.0, 10.0, 20.0)));
+        assert_eq!((merged.minzoom, merged.maxzoom), (Some(0), Some(10)));
+
+        let mut roads = vector_layer("roads", "class", 0, 14);
+        roads.fields.insert("name".to_owned(), "String".to_owned());
+        assert_eq!(
+            merged.vector_layers,
+            Some(vec![roads, vector_layer("water", "kind", 0, 8)])
+        );
+    }
+}
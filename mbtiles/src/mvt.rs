@@ -0,0 +1,417 @@
+//! Just enough of the [Mapbox Vector Tile](https://github.com/mapbox/vector-tile-spec) protobuf
+//! encoding to merge and measure the layers of tiles without decoding their features.
+//!
+//! Features and values are kept as their encoded protobuf messages.
+//! Only the `tags` of features are rewritten when the keys and values of two layers are merged,
+//! or when attributes are removed.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+use martin_tile_utils::protobuf::{
+    ProtobufError, Reader, Value, write_bytes, write_varint, write_varint_field,
+};
+
+use crate::{MbtError, MbtResult};
+
+/// Extent of layers that do not set one, as defined by the specification.
+const DEFAULT_EXTENT: u64 = 4096;
+
+// Field numbers of the `Tile`, `Layer` and `Feature` messages
+const TILE_LAYERS: u32 = 3;
+const LAYER_NAME: u32 = 1;
+const LAYER_FEATURES: u32 = 2;
+const LAYER_KEYS: u32 = 3;
+const LAYER_VALUES: u32 = 4;
+const LAYER_EXTENT: u32 = 5;
+const LAYER_VERSION: u32 = 15;
+const FEATURE_TAGS: u32 = 2;
+
+/// A layer of a vector tile.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MvtLayer {
+    pub(crate) name: String,
+    version: Option<u64>,
+    extent: Option<u64>,
+    keys: Vec<String>,
+    /// Encoded `Value` messages
+    values: Vec<Vec<u8>>,
+    /// Encoded `Feature` messages
+    features: Vec<Vec<u8>>,
+}
+
+impl MvtLayer {
+    pub(crate) fn extent(&self) -> u64 {
+        self.extent.unwrap_or(DEFAULT_EXTENT)
+    }
+
+    /// Appends the features of `other`, which must have the same extent.
+    /// Keys and values already in this layer are reused, the others are appended.
+    pub(crate) fn merge(&mut self, other: Self) -> MbtResult<()> {
+        if self.extent() != other.extent() {
+            return Err(MbtError::IncompatibleMergeLayers {
+                layer: self.name.clone(),
+                extent: self.extent(),
+                other: other.extent(),
+            });
+        }
+        let key_index = index_of(&mut self.keys, other.keys);
+        let value_index = index_of(&mut self.values, other.values);
+        for feature in other.features {
+            self.features.push(retag_feature(&feature, |key, value| {
+                Ok(Some((
+                    lookup(&key_index, key)?,
+                    lookup(&value_index, value)?,
+                )))
+            })?);
+        }
+        Ok(())
+    }
+
+    /// Removes the attributes whose key is not accepted by `keep`, as well as the keys and values
+    /// that are no longer used. Returns `false` if no attribute was removed.
+    pub(crate) fn retain_attributes(&mut self, keep: impl Fn(&str) -> bool) -> MbtResult<bool> {
+        if self.keys.iter().all(|key| keep(key)) {
+            return Ok(false);
+        }
+        let mut keys = Vec::new();
+        let mut key_index = Vec::with_capacity(self.keys.len());
+        for key in mem::take(&mut self.keys) {
+            if keep(&key) {
+                key_index.push(Some(keys.len() as u64));
+                keys.push(key);
+            } else {
+                key_index.push(None);
+            }
+        }
+        self.keys = keys;
+
+        let mut used = vec![false; self.values.len()];
+        for feature in &self.features {
+            let (_, _, tags) = split_tags(feature)?;
+            for pair in tags.chunks_exact(2) {
+                if lookup(&key_index, pair[0])?.is_some() {
+                    let value = usize::try_from(pair[1])
+                        .ok()
+                        .and_then(|value| used.get_mut(value))
+                        .ok_or(MbtError::InvalidVectorTile("feature tag out of range"))?;
+                    *value = true;
+                }
+            }
+        }
+        let mut values = Vec::new();
+        let mut value_index = Vec::with_capacity(self.values.len());
+        for (value, used) in mem::take(&mut self.values).into_iter().zip(used) {
+            if used {
+                value_index.push(Some(values.len() as u64));
+                values.push(value);
+            } else {
+                value_index.push(None);
+            }
+        }
+        self.values = values;
+
+        for feature in &mut self.features {
+            *feature = retag_feature(feature, |key, value| {
+                let Some(key) = lookup(&key_index, key)? else {
+                    return Ok(None);
+                };
+                let value = lookup(&value_index, value)?
+                    .ok_or(MbtError::InvalidVectorTile("feature tag out of range"))?;
+                Ok(Some((key, value)))
+            })?;
+        }
+        Ok(true)
+    }
+
+    fn decode(data: &[u8]) -> MbtResult<Self> {
+        let mut layer = Self {
+            name: String::new(),
+            version: None,
+            extent: None,
+            keys: Vec::new(),
+            values: Vec::new(),
+            features: Vec::new(),
+        };
+        let mut reader = Reader::new(data);
+        while let Some((field, value, _)) = reader.next_field()? {
+            match (field, value) {
+                (LAYER_NAME, Value::Bytes(v)) => layer.name = utf8(v)?,
+                (LAYER_FEATURES, Value::Bytes(v)) => layer.features.push(v.to_vec()),
+                (LAYER_KEYS, Value::Bytes(v)) => layer.keys.push(utf8(v)?),
+                (LAYER_VALUES, Value::Bytes(v)) => layer.values.push(v.to_vec()),
+                (LAYER_EXTENT, Value::Varint(v)) => layer.extent = Some(v),
+                (LAYER_VERSION, Value::Varint(v)) => layer.version = Some(v),
+                _ => {}
+            }
+        }
+        Ok(layer)
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_bytes(out, LAYER_NAME, self.name.as_bytes());
+        for feature in &self.features {
+            write_bytes(out, LAYER_FEATURES, feature);
+        }
+        for key in &self.keys {
+            write_bytes(out, LAYER_KEYS, key.as_bytes());
+        }
+        for value in &self.values {
+            write_bytes(out, LAYER_VALUES, value);
+        }
+        if let Some(extent) = self.extent {
+            write_varint_field(out, LAYER_EXTENT, extent);
+        }
+        write_varint_field(out, LAYER_VERSION, self.version.unwrap_or(2));
+    }
+}
+
+/// Decodes the layers of an uncompressed vector tile.
+pub(crate) fn decode_tile(data: &[u8]) -> MbtResult<Vec<MvtLayer>> {
+    let mut layers = Vec::new();
+    let mut reader = Reader::new(data);
+    while let Some((field, value, _)) = reader.next_field()? {
+        if let (TILE_LAYERS, Value::Bytes(v)) = (field, value) {
+            layers.push(MvtLayer::decode(v)?);
+        }
+    }
+    Ok(layers)
+}
+
+/// Encodes layers as an uncompressed vector tile.
+pub(crate) fn encode_tile(layers: &[MvtLayer]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut layer_buf = Vec::new();
+    for layer in layers {
+        layer_buf.clear();
+        layer.encode(&mut layer_buf);
+        write_bytes(&mut out, TILE_LAYERS, &layer_buf);
+    }
+    out
+}
+
+/// Encoded size of each layer of an uncompressed vector tile, in the order of the layers.
+pub(crate) fn layer_sizes(data: &[u8]) -> MbtResult<Vec<usize>> {
+    let mut sizes = Vec::new();
+    let mut reader = Reader::new(data);
+    while let Some((field, _, raw)) = reader.next_field()? {
+        if field == TILE_LAYERS {
+            sizes.push(raw.len());
+        }
+    }
+    Ok(sizes)
+}
+
+/// Adds the `items` missing from `existing`, and returns the new index of each item.
+fn index_of<T: Eq + Hash + Clone>(existing: &mut Vec<T>, items: Vec<T>) -> Vec<u64> {
+    let mut positions: HashMap<T, u64> = existing.iter().cloned().zip(0..).collect();
+    items
+        .into_iter()
+        .map(|item| {
+            *positions.entry(item.clone()).or_insert_with(|| {
+                existing.push(item);
+                existing.len() as u64 - 1
+            })
+        })
+        .collect()
+}
+
+/// Re-encodes a feature with its tags mapped by `remap`, which gets the key and value index
+/// of each tag, and returns their new indexes, or `None` to remove the tag.
+fn retag_feature(
+    feature: &[u8],
+    mut remap: impl FnMut(u64, u64) -> MbtResult<Option<(u64, u64)>>,
+) -> MbtResult<Vec<u8>> {
+    let (mut out, tags_pos, tags) = split_tags(feature)?;
+    if let Some(pos) = tags_pos {
+        let mut packed = Vec::with_capacity(tags.len() * 2);
+        for pair in tags.chunks_exact(2) {
+            if let Some((key, value)) = remap(pair[0], pair[1])? {
+                write_varint(&mut packed, key);
+                write_varint(&mut packed, value);
+            }
+        }
+        let rest = out.split_off(pos);
+        write_bytes(&mut out, FEATURE_TAGS, &packed);
+        out.extend_from_slice(&rest);
+    }
+    Ok(out)
+}
+
+/// Splits a feature into its other fields, the position of its `tags` among them, and its tags.
+fn split_tags(feature: &[u8]) -> MbtResult<(Vec<u8>, Option<usize>, Vec<u64>)> {
+    let mut out = Vec::with_capacity(feature.len());
+    let mut tags = Vec::new();
+    // Tags are written back where they first appeared, so untouched features stay byte-identical
+    let mut tags_pos = None;
+    let mut reader = Reader::new(feature);
+    while let Some((field, value, raw)) = reader.next_field()? {
+        match (field, value) {
+            (FEATURE_TAGS, Value::Bytes(packed)) => {
+                tags_pos.get_or_insert(out.len());
+                let mut packed = Reader::new(packed);
+                while !packed.is_empty() {
+                    tags.push(packed.varint()?);
+                }
+            }
+            (FEATURE_TAGS, Value::Varint(tag)) => {
+                tags_pos.get_or_insert(out.len());
+                tags.push(tag);
+            }
+            _ => out.extend_from_slice(raw),
+        }
+    }
+    if tags.len() % 2 != 0 {
+        return Err(MbtError::InvalidVectorTile("odd number of feature tags"));
+    }
+    Ok((out, tags_pos, tags))
+}
+
+/// The entry of `index` for a key or value index of a feature tag.
+fn lookup<T: Copy>(index: &[T], tag: u64) -> MbtResult<T> {
+    usize::try_from(tag)
+        .ok()
+        .and_then(|tag| index.get(tag).copied())
+        .ok_or(MbtError::InvalidVectorTile("feature tag out of range"))
+}
+
+fn utf8(value: &[u8]) -> MbtResult<String> {
+    String::from_utf8(value.to_vec())
+        .map_err(|_err| MbtError::InvalidVectorTile("string is not valid UTF-8"))
+}
+
+impl From<ProtobufError> for MbtError {
+    fn from(e: ProtobufError) -> Self {
+        Self::InvalidVectorTile(e.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `Value` message with a string
+    fn string_value(value: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_bytes(&mut out, 1, value.as_bytes());
+        out
+    }
+
+    /// A point feature with the given tags
+    fn feature(id: u64, tags: &[u64]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_varint_field(&mut out, 1, id);
+        let mut packed = Vec::new();
+        for tag in tags {
+            write_varint(&mut packed, *tag);
+        }
+        write_bytes(&mut out, FEATURE_TAGS, &packed);
+        write_varint_field(&mut out, 3, 1);
+        write_bytes(&mut out, 4, &[9, 50, 34]);
+        out
+    }
+
+    fn layer(name: &str, keys: &[&str], values: &[&str], features: Vec<Vec<u8>>) -> MvtLayer {
+        MvtLayer {
+            name: name.to_owned(),
+            version: Some(2),
+            extent: Some(4096),
+            keys: keys.iter().map(|k| (*k).to_owned()).collect(),
+            values: values.iter().map(|v| string_value(v)).collect(),
+            features,
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let layers = vec![
+            layer("roads", &["class"], &["primary"], vec![feature(1, &[0, 0])]),
+            layer("water", &[], &[], vec![feature(2, &[])]),
+        ];
+        assert_eq!(decode_tile(&encode_tile(&layers)).unwrap(), layers);
+    }
+
+    #[test]
+    fn measures_layers() {
+        let roads = layer("roads", &["class"], &["primary"], vec![feature(1, &[0, 0])]);
+        let water = layer("water", &[], &[], vec![]);
+        let tile = encode_tile(&[roads.clone(), water.clone()]);
+        let sizes = layer_sizes(&tile).unwrap();
+        assert_eq!(
+            sizes,
+            vec![encode_tile(&[roads]).len(), encode_tile(&[water]).len()]
+        );
+        assert_eq!(sizes.iter().sum::<usize>(), tile.len());
+    }
+
+    #[test]
+    fn merges_keys_and_values() {
+        let mut roads = layer(
+            "roads",
+            &["class", "name"],
+            &["primary", "Main St"],
+            vec![feature(1, &[0, 0, 1, 1])],
+        );
+        roads
+            .merge(layer(
+                "roads",
+                &["name", "class"],
+                &["secondary", "primary"],
+                vec![feature(2, &[1, 1, 0, 0])],
+            ))
+            .unwrap();
+
+        let expected = layer(
+            "roads",
+            &["class", "name"],
+            &["primary", "Main St", "secondary"],
+            vec![feature(1, &[0, 0, 1, 1]), feature(2, &[0, 0, 1, 2])],
+        );
+        assert_eq!(roads, expected);
+    }
+
+    #[test]
+    fn removes_attributes() {
+        let mut roads = layer(
+            "roads",
+            &["class", "name", "ref"],
+            &["primary", "Main St", "A1"],
+            vec![feature(1, &[0, 0, 1, 1, 2, 2]), feature(2, &[1, 1])],
+        );
+        assert!(roads.retain_attributes(|key| key != "name").unwrap());
+        let expected = layer(
+            "roads",
+            &["class", "ref"],
+            &["primary", "A1"],
+            vec![feature(1, &[0, 0, 1, 1]), feature(2, &[])],
+        );
+        assert_eq!(roads, expected);
+        assert!(!roads.retain_attributes(|_| true).unwrap());
+        assert_eq!(roads, expected);
+    }
+
+    #[test]
+    fn rejects_different_extents() {
+        let mut roads = layer("roads", &[], &[], vec![]);
+        let mut other = layer("roads", &[], &[], vec![]);
+        other.extent = Some(512);
+        assert!(matches!(
+            roads.merge(other),
+            Err(MbtError::IncompatibleMergeLayers {
+                extent: 4096,
+                other: 512,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_tiles() {
+        let tile = encode_tile(&[layer("roads", &[], &[], vec![feature(1, &[])])]);
+        assert!(matches!(
+            decode_tile(&tile[..tile.len() - 1]),
+            Err(MbtError::InvalidVectorTile(_))
+        ));
+    }
+}
@@ -0,0 +1,394 @@
+//! Build the lower zoom levels of a raster tileset from its highest zoom level.
+//!
+//! Each tile of a lower zoom level is made by stitching its four child tiles into an image
+//! twice the tile size, and downsampling it. Missing children are left transparent.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use image::imageops::{FilterType, replace, resize};
+use image::{DynamicImage, RgbaImage};
+use martin_tile_utils::{Format, TileInfo};
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+use sqlx::{AssertSqlSafe, Connection as _, SqliteConnection, query, query_as};
+use tokio::task::spawn_blocking;
+use tracing::info;
+
+use crate::bindiff::get_bsdiff_tbl_name;
+use crate::mbtiles::parse_tile_index;
+use crate::transcode::encode_image;
+use crate::{
+    CopyDuplicateMode, DEFAULT_TRANSCODE_QUALITY, MbtError, MbtResult, MbtType, Mbtiles,
+    UpdateZoomType, get_patch_type, invert_y_value,
+};
+
+/// Number of overview tiles built and written in one batch.
+const OVERVIEW_BATCH_SIZE: usize = 256;
+
+/// Offsets of the four child tiles of a tile: top left, top right, bottom left and bottom right.
+const CHILDREN: [(u32, u32); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+
+/// Resampling filter used to downsample the stitched child tiles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OverviewFilter {
+    /// Nearest neighbor, keeps the exact colors of the child tiles
+    #[cfg_attr(feature = "cli", value(name = "nearest"))]
+    Nearest,
+    /// Linear interpolation, averages neighboring pixels
+    #[cfg_attr(feature = "cli", value(name = "bilinear", alias = "triangle"))]
+    Bilinear,
+    /// Cubic interpolation (Catmull-Rom)
+    #[cfg_attr(feature = "cli", value(name = "bicubic", alias = "catmull-rom"))]
+    Bicubic,
+    /// Gaussian filter, smooths the result
+    #[cfg_attr(feature = "cli", value(name = "gaussian"))]
+    Gaussian,
+    /// Lanczos filter with a window of 3, the sharpest result
+    #[default]
+    #[cfg_attr(feature = "cli", value(name = "lanczos3", alias = "lanczos"))]
+    Lanczos3,
+}
+
+impl From<OverviewFilter> for FilterType {
+    fn from(filter: OverviewFilter) -> Self {
+        match filter {
+            OverviewFilter::Nearest => Self::Nearest,
+            OverviewFilter::Bilinear => Self::Triangle,
+            OverviewFilter::Bicubic => Self::CatmullRom,
+            OverviewFilter::Gaussian => Self::Gaussian,
+            OverviewFilter::Lanczos3 => Self::Lanczos3,
+        }
+    }
+}
+
+/// How [`build_overviews`] builds the lower zoom levels.
+#[derive(Clone, Debug)]
+pub struct OverviewOptions {
+    /// Lowest zoom level to build.
+    pub min_zoom: u8,
+    /// Zoom level the overviews are built from, defaults to the highest zoom level of the file.
+    pub max_zoom: Option<u8>,
+    /// Resampling filter used to downsample the tiles.
+    pub filter: OverviewFilter,
+    /// Quality of JPEG tiles, from 1 to 100.
+    pub quality: u8,
+    /// Patch file that was applied to the file, see [`crate::apply_patch`].
+    /// If set, only the tiles covering the tiles changed by the patch are rebuilt.
+    pub patch_file: Option<PathBuf>,
+}
+
+impl Default for OverviewOptions {
+    fn default() -> Self {
+        Self {
+            min_zoom: 0,
+            max_zoom: None,
+            filter: OverviewFilter::default(),
+            quality: DEFAULT_TRANSCODE_QUALITY,
+            patch_file: None,
+        }
+    }
+}
+
+/// Statistics returned after the overviews are built.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OverviewStats {
+    /// Overview tiles written to the file.
+    pub tiles_written: usize,
+    /// Overview tiles deleted because none of their children exist anymore.
+    pub tiles_deleted: usize,
+}
+
+/// Builds the zoom levels from `options.max_zoom - 1` down to `options.min_zoom` of the
+/// PNG, JPEG or WebP tiles in `file`, replacing any tiles already stored at these zoom levels.
+pub async fn build_overviews(file: &Path, options: &OverviewOptions) -> MbtResult<OverviewStats> {
+    let mbt = Mbtiles::new(file)?;
+    let mut conn = mbt.open().await?;
+    let metadata = mbt.get_metadata(&mut conn).await?;
+    let Some(info) = mbt.detect_format(&metadata.tilejson, &mut conn).await? else {
+        return Err(MbtError::NoFormatInMetadata(file.to_path_buf()));
+    };
+    if !matches!(info.format, Format::Png | Format::Jpeg | Format::Webp) {
+        return Err(MbtError::NoRasterTiles {
+            path: file.to_path_buf(),
+            info,
+        });
+    }
+    let mbt_type = mbt.detect_type(&mut conn).await?;
+
+    let max_zoom = match options.max_zoom {
+        Some(zoom) => zoom,
+        None => match compute_max_zoom(&mut conn).await? {
+            Some(zoom) => zoom,
+            None => return Ok(OverviewStats::default()),
+        },
+    };
+    if options.min_zoom >= max_zoom {
+        return Err(MbtError::InvalidOverviewZooms {
+            min_zoom: options.min_zoom,
+            max_zoom,
+        });
+    }
+    let patched = match &options.patch_file {
+        Some(patch_file) => Some(read_patched_tiles(patch_file).await?),
+        None => None,
+    };
+
+    let builder = OverviewBuilder {
+        info,
+        filter: options.filter.into(),
+        quality: options.quality.clamp(1, 100),
+    };
+    let mut stats = OverviewStats::default();
+    // Tiles of the zoom level above that were changed by the patch or rebuilt
+    let mut changed = BTreeSet::new();
+    for zoom in (options.min_zoom..max_zoom).rev() {
+        let parents: Vec<(u32, u32)> = if let Some(patched) = &patched {
+            changed.extend(
+                patched
+                    .iter()
+                    .filter(|(z, _, _)| *z == zoom + 1)
+                    .map(|(_, x, y)| (*x, *y)),
+            );
+            let parents = changed.iter().map(|(x, y)| (x / 2, y / 2)).collect();
+            changed = parents;
+            changed.iter().copied().collect()
+        } else {
+            parents_of_zoom(&mut conn, zoom + 1).await?
+        };
+        info!(
+            "Building {} tiles of zoom {zoom} from zoom {} of {mbt}",
+            parents.len(),
+            zoom + 1
+        );
+
+        for batch in parents.chunks(OVERVIEW_BATCH_SIZE) {
+            let mut children = Vec::with_capacity(batch.len());
+            for &(x, y) in batch {
+                let mut tiles: [Option<Vec<u8>>; 4] = Default::default();
+                for (tile, (dx, dy)) in tiles.iter_mut().zip(CHILDREN) {
+                    *tile = mbt
+                        .get_tile(&mut conn, zoom + 1, 2 * x + dx, 2 * y + dy)
+                        .await?;
+                }
+                children.push((x, y, tiles));
+            }
+
+            let built: Vec<_> = spawn_blocking(move || {
+                children
+                    .into_par_iter()
+                    .map(|(x, y, tiles)| Ok((x, y, builder.build(tiles)?)))
+                    .collect::<MbtResult<Vec<_>>>()
+            })
+            .await
+            .map_err(|e| MbtError::TranscodeError(format!("join error: {e}")))??;
+
+            let mut tiles = Vec::with_capacity(built.len());
+            let mut deleted = Vec::new();
+            for (x, y, tile) in built {
+                match tile {
+                    Some(tile) => tiles.push((zoom, x, y, tile)),
+                    None => deleted.push((x, y)),
+                }
+            }
+            mbt.insert_tiles(&mut conn, mbt_type, CopyDuplicateMode::Override, &tiles)
+                .await?;
+            delete_tiles(&mut conn, mbt_type, zoom, &deleted).await?;
+            stats.tiles_written += tiles.len();
+            stats.tiles_deleted += deleted.len();
+        }
+    }
+
+    mbt.update_metadata(&mut conn, UpdateZoomType::GrowOnly)
+        .await?;
+    mbt.update_agg_tiles_hash(&mut conn).await?;
+
+    Ok(stats)
+}
+
+/// Stitches and downsamples four child tiles into their parent tile.
+#[derive(Clone, Copy)]
+struct OverviewBuilder {
+    info: TileInfo,
+    filter: FilterType,
+    quality: u8,
+}
+
+impl OverviewBuilder {
+    /// Builds the tile from its children, ordered as [`CHILDREN`].
+    /// Returns `None` if none of the children exist.
+    fn build(self, children: [Option<Vec<u8>>; 4]) -> MbtResult<Option<Vec<u8>>> {
+        let mut images = Vec::with_capacity(children.len());
+        for (child, offset) in children.into_iter().zip(CHILDREN) {
+            if let Some(child) = child {
+                images.push((image::load_from_memory(&child)?.to_rgba8(), offset));
+            }
+        }
+        // All tiles are expected to be the same size as the first one
+        let Some((width, height)) = images.first().map(|(image, _)| image.dimensions()) else {
+            return Ok(None);
+        };
+
+        let mut canvas = RgbaImage::new(2 * width, 2 * height);
+        for (mut image, (dx, dy)) in images {
+            if image.dimensions() != (width, height) {
+                image = resize(&image, width, height, self.filter);
+            }
+            replace(
+                &mut canvas,
+                &image,
+                i64::from(dx * width),
+                i64::from(dy * height),
+            );
+        }
+        let tile = resize(&canvas, width, height, self.filter);
+        encode_image(
+            &DynamicImage::ImageRgba8(tile),
+            self.info.format,
+            self.quality,
+        )
+        .map(Some)
+    }
+}
+
+/// Highest zoom level of the tiles, if there are any.
+async fn compute_max_zoom(conn: &mut SqliteConnection) -> MbtResult<Option<u8>> {
+    let zoom: Option<i64> = sqlx::query_scalar("SELECT MAX(zoom_level) FROM tiles")
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(zoom.and_then(|zoom| u8::try_from(zoom).ok()))
+}
+
+/// XYZ coordinates of the tiles at `child_zoom - 1` that have at least one child tile.
+async fn parents_of_zoom(
+    conn: &mut SqliteConnection,
+    child_zoom: u8,
+) -> MbtResult<Vec<(u32, u32)>> {
+    let zoom = i64::from(child_zoom - 1);
+    let rows: Vec<(i64, i64)> = query_as(
+        "SELECT DISTINCT tile_column / 2, tile_row / 2 FROM tiles WHERE zoom_level = ? ORDER BY 1, 2",
+    )
+    .bind(child_zoom)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(x, y)| parse_tile_index(Some(zoom), Some(x), Some(y)))
+        .map(|coord| (coord.x, coord.y))
+        .collect())
+}
+
+/// XYZ coordinates of the tiles added, changed or deleted by a patch file.
+async fn read_patched_tiles(patch_file: &Path) -> MbtResult<BTreeSet<(u8, u32, u32)>> {
+    let patch = Mbtiles::new(patch_file)?;
+    let mut conn = patch.open_readonly().await?;
+    let mut tables = vec!["tiles"];
+    if let Some(patch_type) = get_patch_type(&mut conn).await? {
+        tables.push(get_bsdiff_tbl_name(patch_type));
+    }
+    let mut coords = BTreeSet::new();
+    for table in tables {
+        let sql = format!("SELECT zoom_level, tile_column, tile_row FROM {table}");
+        let rows: Vec<(i64, i64, i64)> = query_as(AssertSqlSafe(sql)).fetch_all(&mut conn).await?;
+        coords.extend(
+            rows.into_iter()
+                .filter_map(|(z, x, y)| parse_tile_index(Some(z), Some(x), Some(y)))
+                .map(|coord| (coord.z, coord.x, coord.y)),
+        );
+    }
+    conn.close().await?;
+    Ok(coords)
+}
+
+/// Deletes the tiles at the XYZ coordinates of the given zoom level.
+/// Tile contents of normalized files are left in place, the same way patches delete tiles.
+async fn delete_tiles(
+    conn: &mut SqliteConnection,
+    mbt_type: MbtType,
+    zoom: u8,
+    coords: &[(u32, u32)],
+) -> MbtResult<()> {
+    if coords.is_empty() {
+        return Ok(());
+    }
+    let table = match mbt_type {
+        MbtType::Flat => "tiles",
+        MbtType::FlatWithHash => "tiles_with_hash",
+        MbtType::Normalized { schema, .. } => schema.map_table(),
+        MbtType::Cache => "tile_cache",
+    };
+    let sql =
+        format!("DELETE FROM {table} WHERE zoom_level = ? AND tile_column = ? AND tile_row = ?");
+    let mut tx = conn.begin().await?;
+    for &(x, y) in coords {
+        query(AssertSqlSafe(sql.clone()))
+            .bind(zoom)
+            .bind(x)
+            .bind(invert_y_value(zoom, y))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(color: [u8; 4], format: Format) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(4, 4, image::Rgba(color));
+        encode_image(&DynamicImage::ImageRgba8(image), format, 100).unwrap()
+    }
+
+    fn builder(format: Format) -> OverviewBuilder {
+        OverviewBuilder {
+            info: TileInfo::from(format),
+            filter: FilterType::Nearest,
+            quality: 100,
+        }
+    }
+
+    #[test]
+    fn stitches_children() {
+        let red = tile([255, 0, 0, 255], Format::Png);
+        let blue = tile([0, 0, 255, 255], Format::Png);
+        let parent = builder(Format::Png)
+            .build([Some(red), None, None, Some(blue)])
+            .unwrap()
+            .unwrap();
+        let image = image::load_from_memory(&parent).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (4, 4));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(
+            image.get_pixel(3, 0).0[3],
+            0,
+            "missing children are transparent"
+        );
+        assert_eq!(
+            image.get_pixel(0, 3).0[3],
+            0,
+            "missing children are transparent"
+        );
+        assert_eq!(image.get_pixel(3, 3).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn keeps_format() {
+        let child = tile([0, 128, 0, 255], Format::Jpeg);
+        let parent = builder(Format::Jpeg)
+            .build([
+                Some(child.clone()),
+                Some(child.clone()),
+                Some(child.clone()),
+                Some(child),
+            ])
+            .unwrap()
+            .unwrap();
+        assert_eq!(TileInfo::detect(&parent).format, Format::Jpeg);
+        assert_eq!(
+            builder(Format::Webp).build(Default::default()).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! Working with several patch files at once: applying a chain of consecutive patches,
+//! squashing such a chain into a single patch, and rebasing a patch on top of another one
+//! made from the same base file.
+//!
+//! Squashing and rebasing apply the patches to copies of the base file, and compute the new
+//! patch from the results. This works the same way for whole-tile and bin-diff patches,
+//! and verifies the `agg_tiles_hash` of every intermediate file along the way.
+
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools as _;
+use sqlx::{AssertSqlSafe, Connection as _, SqliteConnection, query, query_as};
+use tempfile::TempDir;
+use tracing::{info, warn};
+
+use crate::mbtiles::{PatchFileInfo, parse_tile_index};
+use crate::queries::detach_db;
+use crate::{MbtError, MbtResult, MbtTypeCli, Mbtiles, MbtilesCopier, PatchType, apply_patch};
+
+/// Number of conflicting tiles listed in [`MbtError::ConflictingPatches`]
+const MAX_LISTED_CONFLICTS: usize = 5;
+
+/// Apply a sequence of patch files in place, in the given order.
+///
+/// All patches are checked before the first one is applied: each of them must have been made
+/// from the result of the previous one, i.e. its `agg_tiles_hash_before_apply` must match the
+/// `agg_tiles_hash_after_apply` of the previous patch. Bin-diff patches cannot be applied in place.
+#[hotpath::measure]
+pub async fn apply_patches(
+    base_file: PathBuf,
+    patch_files: Vec<PathBuf>,
+    force: bool,
+) -> MbtResult<()> {
+    let infos = read_patch_chain(&patch_files, force).await?;
+    if infos.iter().any(|info| info.patch_type.is_some()) {
+        return Err(MbtError::UnsupportedPatchType);
+    }
+    let count = patch_files.len();
+    for (idx, patch_file) in patch_files.into_iter().enumerate() {
+        info!("Applying patch {} of {count}", idx + 1);
+        apply_patch(base_file.clone(), patch_file, force).await?;
+    }
+    Ok(())
+}
+
+/// Combine a chain of consecutive patch files into a single patch file `dst_file`.
+///
+/// `base_file` is the file the first patch applies to. The patches are applied to copies of it
+/// in order, and the resulting patch is the difference between `base_file` and the final copy.
+#[hotpath::measure]
+pub async fn squash_patches(
+    base_file: &Path,
+    patch_files: &[PathBuf],
+    dst_file: &Path,
+    patch_type: Option<PatchType>,
+    force: bool,
+) -> MbtResult<()> {
+    let infos = read_patch_chain(patch_files, force).await?;
+    let base = Mbtiles::new(base_file)?;
+    check_base_hash(&base, &patch_files[0], &infos[0], force).await?;
+
+    let dir = work_dir(dst_file)?;
+    let mut current = base_file.to_path_buf();
+    for (idx, patch_file) in patch_files.iter().enumerate() {
+        info!(
+            "Applying patch {} of {} ({})",
+            idx + 1,
+            patch_files.len(),
+            patch_file.display()
+        );
+        let next = dir.path().join(format!("step-{idx}.mbtiles"));
+        apply_to_copy(&current, patch_file, next.clone(), force).await?;
+        current = next;
+    }
+
+    info!("Writing the squashed patch to {}", dst_file.display());
+    write_diff(base_file, &current, dst_file, patch_type, force).await
+}
+
+/// Rebase `patch_file` on top of `onto_file`, two patches made from the same `base_file`.
+///
+/// The resulting patch `dst_file` applies to the result of `onto_file`, and adds the changes of
+/// `patch_file` to it. Tiles changed by both patches in different ways are conflicts, and fail
+/// the rebase unless `force` is set, in which case the tiles of `patch_file` win.
+/// Metadata changes of `patch_file` always win.
+#[hotpath::measure]
+pub async fn rebase_patch(
+    base_file: &Path,
+    onto_file: &Path,
+    patch_file: &Path,
+    dst_file: &Path,
+    patch_type: Option<PatchType>,
+    force: bool,
+) -> MbtResult<()> {
+    let base = Mbtiles::new(base_file)?;
+    for file in [onto_file, patch_file] {
+        let mbt = Mbtiles::new(file)?;
+        let mut conn = mbt.open_readonly().await?;
+        let info = mbt.examine_diff(&mut conn).await?;
+        conn.close().await?;
+        mbt.validate_diff_info(&info, force)?;
+        check_base_hash(&base, file, &info, force).await?;
+    }
+
+    let dir = work_dir(dst_file)?;
+    let ours = dir.path().join("onto.mbtiles");
+    let theirs = dir.path().join("patch.mbtiles");
+    apply_to_copy(base_file, onto_file, ours.clone(), force).await?;
+    apply_to_copy(base_file, patch_file, theirs.clone(), force).await?;
+
+    // Changes of both patches combined, in a flat file so they can be written with plain SQL
+    let merged = dir.path().join("merged.mbtiles");
+    let mut conn = MbtilesCopier {
+        src_file: ours.clone(),
+        dst_file: merged.clone(),
+        dst_type_cli: Some(MbtTypeCli::Flat),
+        force,
+        ..Default::default()
+    }
+    .run()
+    .await?;
+    base.attach_to(&mut conn, "baseDb").await?;
+    Mbtiles::new(&ours)?.attach_to(&mut conn, "oursDb").await?;
+    Mbtiles::new(&theirs)?
+        .attach_to(&mut conn, "theirsDb")
+        .await?;
+
+    check_conflicts(&mut conn, onto_file, patch_file, force).await?;
+    info!(
+        "Adding the changes of {} to the changes of {}",
+        patch_file.display(),
+        onto_file.display()
+    );
+    add_their_changes(&mut conn).await?;
+    Mbtiles::new(&merged)?
+        .update_agg_tiles_hash(&mut conn)
+        .await?;
+
+    detach_db(&mut conn, "theirsDb").await?;
+    detach_db(&mut conn, "oursDb").await?;
+    detach_db(&mut conn, "baseDb").await?;
+    conn.close().await?;
+
+    info!("Writing the rebased patch to {}", dst_file.display());
+    write_diff(&ours, &merged, dst_file, patch_type, force).await
+}
+
+/// Fail if both patches change the same tiles in different ways, unless `force` is set.
+///
+/// `conn` must have the base file and the results of both patches attached
+/// as `baseDb`, `oursDb` and `theirsDb`.
+async fn check_conflicts(
+    conn: &mut SqliteConnection,
+    onto_file: &Path,
+    patch_file: &Path,
+    force: bool,
+) -> MbtResult<()> {
+    let sql = format!(
+        "
+    WITH ours AS ({ours}), theirs AS ({theirs})
+    SELECT zoom_level, tile_column, tile_row
+    FROM ours JOIN theirs USING (zoom_level, tile_column, tile_row)
+    WHERE (SELECT tile_data FROM oursDb.tiles AS t
+           WHERE t.zoom_level = ours.zoom_level
+             AND t.tile_column = ours.tile_column
+             AND t.tile_row = ours.tile_row)
+       IS NOT
+          (SELECT tile_data FROM theirsDb.tiles AS t
+           WHERE t.zoom_level = ours.zoom_level
+             AND t.tile_column = ours.tile_column
+             AND t.tile_row = ours.tile_row)
+    ORDER BY zoom_level, tile_column, tile_row",
+        ours = changed_tiles_sql("oursDb"),
+        theirs = changed_tiles_sql("theirsDb"),
+    );
+    let conflicts: Vec<(i64, i64, i64)> =
+        query_as(AssertSqlSafe(sql)).fetch_all(&mut *conn).await?;
+    if !conflicts.is_empty() {
+        let err = MbtError::ConflictingPatches {
+            patch_file: patch_file.display().to_string(),
+            onto_file: onto_file.display().to_string(),
+            count: conflicts.len(),
+            tiles: conflicts
+                .into_iter()
+                .take(MAX_LISTED_CONFLICTS)
+                .filter_map(|(z, x, y)| parse_tile_index(Some(z), Some(x), Some(y)))
+                .map(|coord| format!("{coord:#}"))
+                .join(", "),
+        };
+        if !force {
+            return Err(err);
+        }
+        warn!("{err} (force mode)");
+    }
+    Ok(())
+}
+
+/// Add the tile and metadata changes from `baseDb` to `theirsDb` to the file of `conn`,
+/// replacing the tiles and metadata values changed by both.
+async fn add_their_changes(conn: &mut SqliteConnection) -> MbtResult<()> {
+    let sql = "
+    DELETE FROM tiles
+    WHERE (zoom_level, tile_column, tile_row) IN (
+        SELECT zoom_level, tile_column, tile_row FROM baseDb.tiles
+        EXCEPT
+        SELECT zoom_level, tile_column, tile_row FROM theirsDb.tiles)";
+    query(sql).execute(&mut *conn).await?;
+    let sql = "
+    INSERT OR REPLACE INTO tiles (zoom_level, tile_column, tile_row, tile_data)
+    SELECT zoom_level, tile_column, tile_row, tile_data FROM theirsDb.tiles
+    EXCEPT
+    SELECT zoom_level, tile_column, tile_row, tile_data FROM baseDb.tiles";
+    query(sql).execute(&mut *conn).await?;
+    let sql = "
+    DELETE FROM metadata
+    WHERE name IN (SELECT name FROM baseDb.metadata EXCEPT SELECT name FROM theirsDb.metadata)";
+    query(sql).execute(&mut *conn).await?;
+    let sql = "
+    INSERT OR REPLACE INTO metadata (name, value)
+    SELECT name, value FROM theirsDb.metadata
+    EXCEPT
+    SELECT name, value FROM baseDb.metadata";
+    query(sql).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Read the hashes of a sequence of patch files, checking that each of them
+/// was made from the result of the previous one.
+async fn read_patch_chain(patch_files: &[PathBuf], force: bool) -> MbtResult<Vec<PatchFileInfo>> {
+    if patch_files.is_empty() {
+        return Err(MbtError::NoPatchFiles);
+    }
+    let mut infos: Vec<PatchFileInfo> = Vec::with_capacity(patch_files.len());
+    for (idx, patch_file) in patch_files.iter().enumerate() {
+        let mbt = Mbtiles::new(patch_file)?;
+        let mut conn = mbt.open_readonly().await?;
+        let info = mbt.examine_diff(&mut conn).await?;
+        conn.close().await?;
+        mbt.validate_diff_info(&info, force)?;
+
+        if let Some(previous) = infos.last()
+            && let (Some(after_apply_hash), Some(before_apply_hash)) = (
+                &previous.agg_tiles_hash_after_apply,
+                &info.agg_tiles_hash_before_apply,
+            )
+            && after_apply_hash != before_apply_hash
+        {
+            let err = MbtError::BrokenPatchChain {
+                patch_file: mbt.filepath().to_owned(),
+                before_apply_hash: before_apply_hash.clone(),
+                previous_file: patch_files[idx - 1].display().to_string(),
+                after_apply_hash: after_apply_hash.clone(),
+            };
+            if !force {
+                return Err(err);
+            }
+            warn!("{err} (force mode)");
+        }
+        infos.push(info);
+    }
+    Ok(infos)
+}
+
+/// Check that a patch was made from the base file, the same way `apply-patch` does.
+async fn check_base_hash(
+    base: &Mbtiles,
+    patch_file: &Path,
+    info: &PatchFileInfo,
+    force: bool,
+) -> MbtResult<()> {
+    let mut conn = base.open_readonly().await?;
+    let base_hash = base.get_agg_tiles_hash(&mut conn).await?;
+    conn.close().await?;
+    if let (Some(agg_hash), Some(before_apply_hash)) =
+        (base_hash, &info.agg_tiles_hash_before_apply)
+        && agg_hash != *before_apply_hash
+    {
+        let err = MbtError::AggHashMismatchWithDiff {
+            patch_file: patch_file.display().to_string(),
+            before_apply_hash: before_apply_hash.clone(),
+            file: base.filepath().to_owned(),
+            agg_hash,
+        };
+        if !force {
+            return Err(err);
+        }
+        warn!("{err} (force mode)");
+    }
+    Ok(())
+}
+
+/// Tiles added, changed or deleted in the attached database `db` compared to `baseDb`
+fn changed_tiles_sql(db: &str) -> String {
+    format!(
+        "
+        SELECT zoom_level, tile_column, tile_row FROM (
+            SELECT zoom_level, tile_column, tile_row, tile_data FROM {db}.tiles
+            EXCEPT
+            SELECT zoom_level, tile_column, tile_row, tile_data FROM baseDb.tiles)
+        UNION
+        SELECT zoom_level, tile_column, tile_row FROM (
+            SELECT zoom_level, tile_column, tile_row FROM baseDb.tiles
+            EXCEPT
+            SELECT zoom_level, tile_column, tile_row FROM {db}.tiles)"
+    )
+}
+
+/// A directory for the intermediate files, next to the file being written.
+/// It is removed with its content when dropped.
+fn work_dir(dst_file: &Path) -> MbtResult<TempDir> {
+    let parent = dst_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Ok(tempfile::Builder::new()
+        .prefix(".mbtiles-patch-")
+        .tempdir_in(parent)?)
+}
+
+/// Apply a patch file to `src_file`, writing the result to a new file `dst_file`
+async fn apply_to_copy(
+    src_file: &Path,
+    patch_file: &Path,
+    dst_file: PathBuf,
+    force: bool,
+) -> MbtResult<()> {
+    let conn = MbtilesCopier {
+        src_file: src_file.to_path_buf(),
+        dst_file,
+        apply_patch: Some(patch_file.to_path_buf()),
+        force,
+        ..Default::default()
+    }
+    .run()
+    .await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// Write the patch turning `src_file` into `target_file` to a new file `dst_file`
+async fn write_diff(
+    src_file: &Path,
+    target_file: &Path,
+    dst_file: &Path,
+    patch_type: Option<PatchType>,
+    force: bool,
+) -> MbtResult<()> {
+    let conn = MbtilesCopier {
+        src_file: src_file.to_path_buf(),
+        dst_file: dst_file.to_path_buf(),
+        diff_with_file: Some((target_file.to_path_buf(), patch_type)),
+        force,
+        ..Default::default()
+    }
+    .run()
+    .await?;
+    conn.close().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Column and row of a tile at zoom 2
+    type Coord = (i64, i64);
+
+    /// A tile and its content, or `None` if the file does not have it
+    type Tile = (Coord, Option<u8>);
+
+    /// Tiles of the base file, ours and theirs
+    const TILES: &[(Coord, [Option<u8>; 3])] = &[
+        // changed differently by both
+        ((0, 0), [Some(1), Some(2), Some(3)]),
+        // changed the same way by both
+        ((1, 0), [Some(1), Some(2), Some(2)]),
+        // deleted by ours only
+        ((2, 0), [Some(1), None, Some(1)]),
+        // changed by ours, deleted by theirs
+        ((3, 0), [Some(1), Some(2), None]),
+        // deleted by both
+        ((0, 1), [Some(1), None, None]),
+        // added the same way by both
+        ((1, 1), [None, Some(7), Some(7)]),
+        // added differently by both
+        ((2, 1), [None, Some(8), Some(9)]),
+        // changed by theirs only
+        ((3, 1), [Some(1), Some(1), Some(4)]),
+    ];
+
+    fn tiles_of(file: usize) -> Vec<Tile> {
+        TILES
+            .iter()
+            .map(|&(coord, content)| (coord, content[file]))
+            .collect()
+    }
+
+    async fn create(conn: &mut SqliteConnection, db: &str, tiles: &[Tile], name: &str) {
+        let sql = format!(
+            "CREATE TABLE {db}.tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER,
+                                      tile_data BLOB, PRIMARY KEY(zoom_level, tile_column, tile_row));
+             CREATE TABLE {db}.metadata (name TEXT PRIMARY KEY, value TEXT);
+             INSERT INTO {db}.metadata VALUES ('name', '{name}')"
+        );
+        query(AssertSqlSafe(sql)).execute(&mut *conn).await.unwrap();
+        for &((x, y), data) in tiles {
+            if let Some(data) = data {
+                let sql = format!("INSERT INTO {db}.tiles VALUES (2, ?, ?, ?)");
+                query(AssertSqlSafe(sql))
+                    .bind(x)
+                    .bind(y)
+                    .bind(vec![data])
+                    .execute(&mut *conn)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    /// A copy of the tiles of ours, with the base file and both patch results attached
+    async fn rebase_connection() -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        for db in ["baseDb", "oursDb", "theirsDb"] {
+            let sql = format!("ATTACH DATABASE ':memory:' AS {db}");
+            query(AssertSqlSafe(sql)).execute(&mut conn).await.unwrap();
+        }
+        create(&mut conn, "main", &tiles_of(1), "ours").await;
+        create(&mut conn, "baseDb", &tiles_of(0), "base").await;
+        create(&mut conn, "oursDb", &tiles_of(1), "ours").await;
+        create(&mut conn, "theirsDb", &tiles_of(2), "theirs").await;
+        conn
+    }
+
+    #[actix_rt::test]
+    async fn conflicts_are_tiles_changed_differently() {
+        let mut conn = rebase_connection().await;
+        let onto = Path::new("onto.mbtiles");
+        let patch = Path::new("patch.mbtiles");
+
+        let err = check_conflicts(&mut conn, onto, patch, false)
+            .await
+            .unwrap_err();
+        let MbtError::ConflictingPatches { count, tiles, .. } = err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(count, 3);
+        // TMS rows 0 and 1 are rows 3 and 2 of the XYZ scheme
+        assert_eq!(tiles, "2/0/3, 2/2/2, 2/3/3");
+
+        check_conflicts(&mut conn, onto, patch, true).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn their_changes_replace_ours() {
+        let mut conn = rebase_connection().await;
+        let sql = "INSERT INTO baseDb.metadata VALUES ('description', 'base')";
+        query(sql).execute(&mut conn).await.unwrap();
+        let sql =
+            "INSERT INTO main.metadata VALUES ('description', 'base'), ('attribution', 'ours')";
+        query(sql).execute(&mut conn).await.unwrap();
+
+        add_their_changes(&mut conn).await.unwrap();
+
+        let tiles: Vec<(i64, i64, Vec<u8>)> = query_as(
+            "SELECT tile_column, tile_row, tile_data FROM tiles ORDER BY tile_column, tile_row",
+        )
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!(
+            tiles,
+            vec![
+                (0, 0, vec![3]),
+                (1, 0, vec![2]),
+                (1, 1, vec![7]),
+                (2, 1, vec![9]),
+                (3, 1, vec![4]),
+            ]
+        );
+        let metadata: Vec<(String, String)> =
+            query_as("SELECT name, value FROM metadata ORDER BY name")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(
+            metadata,
+            vec![
+                ("attribution".to_owned(), "ours".to_owned()),
+                ("name".to_owned(), "theirs".to_owned()),
+            ]
+        );
+    }
+}
@@ -1,13 +1,14 @@
 use std::path::Path;
 
 use martin_tile_utils::TileInfo;
-use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 use sqlx::{Pool, Sqlite, SqlitePool};
 use tilejson::TileJSON;
 
 #[cfg(test)]
 use crate::NormalizedSchema;
 use crate::errors::MbtResult;
+use crate::mbtiles::attach_sqlite_fn;
 use crate::{MbtType, Mbtiles, Metadata};
 
 /// Connection pool for concurrent read access to an `MBTiles` file.
@@ -123,6 +124,35 @@ impl MbtilesPool {
         Ok(Self { mbtiles, pool })
     }
 
+    /// Opens an existing `MBTiles` file in read-write mode, to modify single tiles with
+    /// [`put_tile`](Self::put_tile) and [`delete_tile`](Self::delete_tile).
+    ///
+    /// The pool has a single connection, so writes happen one after another.
+    /// Other connections to the file, e.g. a read-only pool serving its tiles,
+    /// wait for a running write to finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file does not exist, or cannot be opened for writing.
+    #[hotpath::measure]
+    pub async fn open_readwrite<P: AsRef<Path>>(filepath: P) -> MbtResult<Self> {
+        let mbtiles = Mbtiles::new(filepath)?;
+        let opt = SqliteConnectOptions::new().filename(mbtiles.filepath());
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            // `put_tile` hashes the tiles with the md5 functions
+            .after_connect(|conn, _meta| {
+                Box::pin(async move {
+                    attach_sqlite_fn(conn)
+                        .await
+                        .map_err(|e| sqlx::Error::Configuration(Box::new(e)))
+                })
+            })
+            .connect_with(opt)
+            .await?;
+        Ok(Self { mbtiles, pool })
+    }
+
     /// Retrieves the metadata for the `MBTiles` file.
     ///
     /// Returns a [`Metadata`] struct containing:
@@ -313,6 +343,41 @@ impl MbtilesPool {
         let mut conn = self.pool.acquire().await?;
         self.mbtiles.contains(&mut conn, mbt_type, z, x, y).await
     }
+
+    /// Writes a single tile, replacing any tile with the same coordinates.
+    ///
+    /// The pool must be opened with [`open_readwrite`](Self::open_readwrite).
+    /// See [`Mbtiles::put_tile`] for how the schemas are kept consistent.
+    #[hotpath::measure]
+    pub async fn put_tile(&self, z: u8, x: u32, y: u32, data: &[u8]) -> MbtResult<()> {
+        let mut conn = self.pool.acquire().await?;
+        let mbt_type = self.mbtiles.detect_type(&mut *conn).await?;
+        self.mbtiles
+            .put_tile(&mut conn, mbt_type, z, x, y, data)
+            .await
+    }
+
+    /// Deletes a single tile, and returns `false` if there was no tile with these coordinates.
+    ///
+    /// The pool must be opened with [`open_readwrite`](Self::open_readwrite).
+    /// See [`Mbtiles::delete_tile`].
+    #[hotpath::measure]
+    pub async fn delete_tile(&self, z: u8, x: u32, y: u32) -> MbtResult<bool> {
+        let mut conn = self.pool.acquire().await?;
+        let mbt_type = self.mbtiles.detect_type(&mut *conn).await?;
+        self.mbtiles.delete_tile(&mut conn, mbt_type, z, x, y).await
+    }
+
+    /// Computes and stores the `agg_tiles_hash` of all tiles, e.g. after a batch of
+    /// [`put_tile`](Self::put_tile) and [`delete_tile`](Self::delete_tile) calls.
+    ///
+    /// This reads every tile of the file. The pool must be opened with
+    /// [`open_readwrite`](Self::open_readwrite).
+    #[hotpath::measure]
+    pub async fn update_agg_tiles_hash(&self) -> MbtResult<String> {
+        let mut conn = self.pool.acquire().await?;
+        self.mbtiles.update_agg_tiles_hash(&mut *conn).await
+    }
 }
 
 #[cfg(test)]
@@ -41,6 +41,10 @@ pub struct Summary {
     pub min_zoom: Option<u8>,
     pub max_zoom: Option<u8>,
     pub zoom_info: Vec<ZoomInfo>,
+    /// Per-layer statistics, see [`Mbtiles::layer_summary`]
+    #[cfg(feature = "transcode")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layers: Option<crate::LayerSummary>,
 }
 
 impl Display for Summary {
@@ -109,6 +113,12 @@ impl Display for Summary {
             )?;
         }
 
+        #[cfg(feature = "transcode")]
+        if let Some(layers) = &self.layers {
+            writeln!(f)?;
+            write!(f, "{layers}")?;
+        }
+
         Ok(())
     }
 }
@@ -206,6 +216,8 @@ impl Mbtiles {
             min_zoom: zoom_info.iter().map(|l| l.zoom).reduce(u8::min),
             max_zoom: zoom_info.iter().map(|l| l.zoom).reduce(u8::max),
             zoom_info,
+            #[cfg(feature = "transcode")]
+            layers: None,
         })
     }
 }
@@ -0,0 +1,558 @@
+//! Deep validation of the tile contents of a file, used by `mbtiles validate --deep`.
+//!
+//! Every tile is decompressed and decoded, its format is compared with the other tiles,
+//! and the geometries of vector tiles are checked.
+//! The layers and attributes found in the tiles are compared with the `vector_layers` metadata.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::mem;
+
+use futures::TryStreamExt as _;
+use image::ImageFormat;
+use martin_tile_utils::{Encoding, Format, TileCoord, TileInfo};
+use mlt_core::TileLayer;
+use mlt_core::geo_types::{Coord, Geometry, LineString, Polygon};
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+use serde_json::Value;
+use sqlx::SqliteExecutor;
+use tilejson::VectorLayer;
+use tokio::task::spawn_blocking;
+
+use crate::layer_summary::{decode_layers, geometry_type};
+use crate::transcode::decompress;
+use crate::{MbtError, MbtResult, Mbtiles};
+
+/// Number of tiles decoded in parallel at once.
+const VALIDATION_BATCH_SIZE: usize = 1000;
+
+/// A problem found in the tiles of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileProblem {
+    /// The tile with the problem, or `None` for problems of the whole file
+    pub tile: Option<TileCoord>,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl Display for TileProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.tile {
+            Some(tile) => write!(f, "{tile:#}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Result of [`Mbtiles::validate_tiles`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TileValidationReport {
+    /// Number of tiles that were decoded
+    pub tile_count: u64,
+    /// Number of problems found, including the ones not kept in `problems`
+    pub problem_count: u64,
+    /// The first problems found
+    pub problems: Vec<TileProblem>,
+    max_problems: usize,
+}
+
+impl TileValidationReport {
+    /// True if no problems were found
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.problem_count == 0
+    }
+
+    fn add(&mut self, tile: Option<TileCoord>, message: String) {
+        self.problem_count += 1;
+        if self.problems.len() < self.max_problems {
+            self.problems.push(TileProblem { tile, message });
+        }
+    }
+}
+
+impl Display for TileValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for problem in &self.problems {
+            writeln!(f, "{problem}")?;
+        }
+        let hidden = self.problem_count - self.problems.len() as u64;
+        if hidden > 0 {
+            writeln!(f, "... and {hidden} more problems")?;
+        }
+        write!(
+            f,
+            "Found {} problems in {} tiles",
+            self.problem_count, self.tile_count
+        )
+    }
+}
+
+impl Mbtiles {
+    /// Decode every tile of the file, and check its format, its geometries and its layers
+    /// against the metadata. At most `max_problems` problems are kept in the report.
+    #[hotpath::measure]
+    pub async fn validate_tiles<T>(
+        &self,
+        conn: &mut T,
+        max_problems: usize,
+    ) -> MbtResult<TileValidationReport>
+    where
+        for<'e> &'e mut T: SqliteExecutor<'e>,
+    {
+        let metadata = self.get_metadata(&mut *conn).await?;
+        let metadata_format = match metadata.tilejson.other.get("format") {
+            Some(Value::String(fmt)) => Format::parse(fmt),
+            _ => None,
+        };
+
+        let mut report = TileValidationReport {
+            max_problems,
+            ..TileValidationReport::default()
+        };
+        let mut layers = BTreeMap::new();
+        let mut checker = None;
+        let mut batch = Vec::with_capacity(VALIDATION_BATCH_SIZE);
+        let mut tiles = self.stream_tiles(&mut *conn);
+        while let Some((coord, data)) = tiles.try_next().await? {
+            let Some(data) = data else {
+                report.add(Some(coord), "tile has no data".to_owned());
+                continue;
+            };
+            let checker = *checker.get_or_insert_with(|| TileChecker::new(metadata_format, &data));
+            batch.push((coord, data));
+            if batch.len() >= VALIDATION_BATCH_SIZE {
+                check_batch(checker, mem::take(&mut batch), &mut report, &mut layers).await?;
+            }
+        }
+        drop(tiles);
+        if let Some(checker) = checker
+            && !batch.is_empty()
+        {
+            check_batch(checker, batch, &mut report, &mut layers).await?;
+        }
+
+        if checker.is_some_and(|c| matches!(c.format, Format::Mvt | Format::Mlt)) {
+            check_vector_layers(
+                metadata.tilejson.vector_layers.as_deref(),
+                &layers,
+                &mut report,
+            );
+        }
+        Ok(report)
+    }
+}
+
+/// Where a layer was found in the tiles.
+struct LayerUsage {
+    tile: TileCoord,
+    min_zoom: u8,
+    max_zoom: u8,
+    /// Attributes of the layer, with a tile containing them
+    fields: BTreeMap<String, TileCoord>,
+}
+
+/// Problems and layers of one tile.
+struct TileCheck {
+    coord: TileCoord,
+    problems: Vec<String>,
+    /// Names of the layers and of their attributes
+    layers: Vec<(String, Vec<String>)>,
+}
+
+async fn check_batch(
+    checker: TileChecker,
+    batch: Vec<(TileCoord, Vec<u8>)>,
+    report: &mut TileValidationReport,
+    layers: &mut BTreeMap<String, LayerUsage>,
+) -> MbtResult<()> {
+    let tiles_checked: Vec<_> = spawn_blocking(move || {
+        batch
+            .into_par_iter()
+            .map(|(coord, data)| checker.check(coord, data))
+            .collect()
+    })
+    .await
+    .map_err(|e| MbtError::TranscodeError(format!("join error: {e}")))?;
+
+    for tile in tiles_checked {
+        report.tile_count += 1;
+        for problem in tile.problems {
+            report.add(Some(tile.coord), problem);
+        }
+        for (name, fields) in tile.layers {
+            let usage = layers.entry(name).or_insert_with(|| LayerUsage {
+                tile: tile.coord,
+                min_zoom: tile.coord.z,
+                max_zoom: tile.coord.z,
+                fields: BTreeMap::new(),
+            });
+            usage.min_zoom = usage.min_zoom.min(tile.coord.z);
+            usage.max_zoom = usage.max_zoom.max(tile.coord.z);
+            for field in fields {
+                usage.fields.entry(field).or_insert(tile.coord);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compares the layers found in the tiles with the `vector_layers` metadata.
+fn check_vector_layers(
+    metadata: Option<&[VectorLayer]>,
+    layers: &BTreeMap<String, LayerUsage>,
+    report: &mut TileValidationReport,
+) {
+    let Some(metadata) = metadata else {
+        if !layers.is_empty() {
+            report.add(None, "vector_layers metadata is missing".to_owned());
+        }
+        return;
+    };
+    for (name, usage) in layers {
+        let Some(meta) = metadata.iter().find(|l| &l.id == name) else {
+            report.add(
+                Some(usage.tile),
+                format!("layer {name} is not in the vector_layers metadata"),
+            );
+            continue;
+        };
+        let (min_zoom, max_zoom) = (meta.minzoom.unwrap_or(0), meta.maxzoom.unwrap_or(u8::MAX));
+        if usage.min_zoom < min_zoom || usage.max_zoom > max_zoom {
+            report.add(
+                Some(usage.tile),
+                format!(
+                    "layer {name} is in zoom levels {}-{}, but the vector_layers metadata sets {min_zoom}-{max_zoom}",
+                    usage.min_zoom, usage.max_zoom,
+                ),
+            );
+        }
+        for (field, tile) in &usage.fields {
+            if !meta.fields.contains_key(field) {
+                report.add(
+                    Some(*tile),
+                    format!(
+                        "attribute {field} of layer {name} is not in the vector_layers metadata"
+                    ),
+                );
+            }
+        }
+    }
+    for meta in metadata {
+        if !layers.contains_key(&meta.id) {
+            report.add(
+                None,
+                format!(
+                    "layer {} of the vector_layers metadata is in none of the tiles",
+                    meta.id
+                ),
+            );
+        }
+    }
+}
+
+/// Checks single tiles, which are expected to have the format set in the metadata,
+/// or else the format of the first tile.
+#[derive(Clone, Copy)]
+struct TileChecker {
+    format: Format,
+    /// Encoding of the first tile, unless its format differs from the metadata
+    encoding: Option<Encoding>,
+}
+
+impl TileChecker {
+    fn new(metadata_format: Option<Format>, first_tile: &[u8]) -> Self {
+        let detected = TileInfo::detect(first_tile);
+        match metadata_format {
+            Some(format) if format != detected.format => Self {
+                format,
+                encoding: None,
+            },
+            _ => Self {
+                format: detected.format,
+                encoding: Some(detected.encoding),
+            },
+        }
+    }
+
+    fn check(self, coord: TileCoord, data: Vec<u8>) -> TileCheck {
+        let mut check = TileCheck {
+            coord,
+            problems: Vec::new(),
+            layers: Vec::new(),
+        };
+        let info = TileInfo::detect(&data);
+        if info.format != self.format {
+            check.problems.push(format!(
+                "tile is {info}, but the file has {} tiles",
+                self.format
+            ));
+        } else if let Some(encoding) = self.encoding
+            && info.encoding.compression() != encoding.compression()
+        {
+            check.problems.push(format!(
+                "tile is {info}, but the other tiles are {}",
+                TileInfo::new(self.format, encoding)
+            ));
+        }
+
+        match info.format {
+            Format::Mvt | Format::Mlt => match decode_layers(data, info) {
+                Ok(layers) => {
+                    for (_, layer) in layers {
+                        check_layer(&layer, &mut check.problems);
+                        check
+                            .layers
+                            .push((layer.name().to_owned(), layer.property_names().to_vec()));
+                    }
+                }
+                Err(e) => check.problems.push(e),
+            },
+            Format::Png | Format::Jpeg | Format::Webp => {
+                let image_format = match info.format {
+                    Format::Png => ImageFormat::Png,
+                    Format::Jpeg => ImageFormat::Jpeg,
+                    _ => ImageFormat::WebP,
+                };
+                if let Err(e) = image::load_from_memory_with_format(&data, image_format) {
+                    check
+                        .problems
+                        .push(format!("unable to decode {} image: {e}", info.format));
+                }
+            }
+            Format::Json => {
+                let json = decompress(data, info.encoding)
+                    .map_err(|e| e.to_string())
+                    .and_then(|data| {
+                        serde_json::from_slice::<Value>(&data).map_err(|e| e.to_string())
+                    });
+                if let Err(e) = json {
+                    check.problems.push(format!("invalid JSON tile: {e}"));
+                }
+            }
+            // Not supported by the image decoders
+            Format::Gif | Format::Avif => {}
+        }
+        check
+    }
+}
+
+/// Checks the geometries of the features of a vector tile layer.
+fn check_layer(layer: &TileLayer, problems: &mut Vec<String>) {
+    let extent = i64::from(layer.extent().get());
+    for (index, feature) in layer.features().iter().enumerate() {
+        if let Err(problem) = check_geometry(feature.geometry(), extent) {
+            let feature = feature
+                .id()
+                .map_or_else(|| format!("#{index}"), |id| format!("with id {id}"));
+            problems.push(format!(
+                "feature {feature} of layer {}: {problem}",
+                layer.name()
+            ));
+        }
+    }
+}
+
+fn check_geometry(geometry: &Geometry<i32>, extent: i64) -> Result<(), String> {
+    match geometry {
+        Geometry::Point(p) => check_coord(p.0, extent),
+        Geometry::MultiPoint(mp) if mp.0.is_empty() => Err("empty MultiPoint".to_owned()),
+        Geometry::MultiPoint(mp) => mp.0.iter().try_for_each(|p| check_coord(p.0, extent)),
+        Geometry::LineString(l) => check_line(l, extent),
+        Geometry::MultiLineString(ml) if ml.0.is_empty() => Err("empty MultiLineString".to_owned()),
+        Geometry::MultiLineString(ml) => ml.0.iter().try_for_each(|l| check_line(l, extent)),
+        Geometry::Polygon(p) => check_polygon(p, extent),
+        Geometry::MultiPolygon(mp) if mp.0.is_empty() => Err("empty MultiPolygon".to_owned()),
+        Geometry::MultiPolygon(mp) => mp.0.iter().try_for_each(|p| check_polygon(p, extent)),
+        geometry => Err(format!(
+            "{} geometries are not supported",
+            geometry_type(geometry)
+        )),
+    }
+}
+
+/// Coordinates are expected within the tile, or its buffer, which is at most the size of the tile.
+fn check_coord(c: Coord<i32>, extent: i64) -> Result<(), String> {
+    let range = -extent..=2 * extent;
+    if range.contains(&i64::from(c.x)) && range.contains(&i64::from(c.y)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "coordinate ({}, {}) is far outside of the tile extent {extent}",
+            c.x, c.y
+        ))
+    }
+}
+
+fn check_line(line: &LineString<i32>, extent: i64) -> Result<(), String> {
+    if line.0.len() < 2 {
+        return Err("LineString with less than 2 points".to_owned());
+    }
+    line.0.iter().try_for_each(|c| check_coord(*c, extent))
+}
+
+fn check_polygon(polygon: &Polygon<i32>, extent: i64) -> Result<(), String> {
+    for ring in std::iter::once(polygon.exterior()).chain(polygon.interiors()) {
+        if ring.0.len() < 4 {
+            return Err("polygon ring with less than 4 points".to_owned());
+        }
+        let area: i64 = ring
+            .0
+            .windows(2)
+            .map(|w| i64::from(w[0].x) * i64::from(w[1].y) - i64::from(w[1].x) * i64::from(w[0].y))
+            .sum();
+        if area == 0 {
+            return Err("polygon ring without area".to_owned());
+        }
+        ring.0.iter().try_for_each(|c| check_coord(*c, extent))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mlt_core::geo_types::{MultiPolygon, Point};
+
+    use super::*;
+    use crate::metadata::anonymous_mbtiles;
+    use crate::{CopyDuplicateMode, MbtType};
+
+    #[actix_rt::test]
+    async fn valid_tiles() {
+        let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+        let (mbt, mut conn) = anonymous_mbtiles(script).await;
+        let report = mbt.validate_tiles(&mut conn, 10).await.unwrap();
+        assert!(report.is_valid(), "{report}");
+        assert!(report.tile_count > 0);
+    }
+
+    #[actix_rt::test]
+    async fn invalid_tiles() {
+        let script = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+        let (mbt, mut conn) = anonymous_mbtiles(script).await;
+        let truncated_gzip = vec![0x1f, 0x8b, 0x08, 0x00];
+        let png_header = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        mbt.insert_tiles(
+            &mut conn,
+            MbtType::Flat,
+            CopyDuplicateMode::Override,
+            &[(7, 0, 0, truncated_gzip), (7, 1, 0, png_header)],
+        )
+        .await
+        .unwrap();
+
+        let report = mbt.validate_tiles(&mut conn, 10).await.unwrap();
+        assert!(!report.is_valid());
+        let tile = |x| Some(TileCoord { z: 7, x, y: 0 });
+        let mut problems: Vec<_> = report.problems.iter().map(|p| p.tile).collect();
+        problems.sort_by_key(|t| t.map(|t| t.x));
+        assert_eq!(problems, vec![tile(0), tile(1), tile(1)]);
+        assert_eq!(report.problem_count, 3);
+
+        let report = mbt.validate_tiles(&mut conn, 1).await.unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.to_string().contains("... and 2 more problems"));
+    }
+
+    #[actix_rt::test]
+    async fn raster_tiles() {
+        let script = "CREATE TABLE metadata (name text, value text);
+            CREATE TABLE tiles (zoom_level integer, tile_column integer, tile_row integer, tile_data blob);
+            INSERT INTO metadata VALUES ('format', 'png');";
+        let (mbt, mut conn) = anonymous_mbtiles(script).await;
+        let mut png = Vec::new();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let truncated = png[..png.len() / 2].to_vec();
+        mbt.insert_tiles(
+            &mut conn,
+            MbtType::Flat,
+            CopyDuplicateMode::Override,
+            &[(0, 0, 0, png), (1, 0, 0, truncated)],
+        )
+        .await
+        .unwrap();
+
+        let report = mbt.validate_tiles(&mut conn, 10).await.unwrap();
+        assert_eq!(report.tile_count, 2);
+        let problems: Vec<_> = report.problems.iter().map(|p| p.tile).collect();
+        assert_eq!(problems, vec![Some(TileCoord { z: 1, x: 0, y: 0 })]);
+    }
+
+    #[test]
+    fn geometries() {
+        let extent = 4096;
+        let square = || LineString::from(vec![(0, 0), (10, 0), (10, 10), (0, 10)]);
+        let valid = [
+            Geometry::Point(Point::new(-100, 5000)),
+            Geometry::LineString(LineString::from(vec![(0, 0), (10, 10)])),
+            Geometry::Polygon(Polygon::new(square(), vec![])),
+        ];
+        for geometry in &valid {
+            check_geometry(geometry, extent).unwrap();
+        }
+        let invalid = [
+            Geometry::Point(Point::new(0, 3 * 4096)),
+            Geometry::LineString(LineString::from(vec![(0, 0)])),
+            Geometry::Polygon(Polygon::new(
+                LineString::from(vec![(0, 0), (10, 10), (20, 20)]),
+                vec![],
+            )),
+            Geometry::MultiPolygon(MultiPolygon(vec![])),
+        ];
+        for geometry in &invalid {
+            check_geometry(geometry, extent).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn vector_layers() {
+        let coord = TileCoord { z: 3, x: 1, y: 2 };
+        let layer = |id: &str, fields: &[&str]| VectorLayer {
+            id: id.to_owned(),
+            fields: fields
+                .iter()
+                .map(|f| ((*f).to_owned(), "String".to_owned()))
+                .collect(),
+            description: None,
+            maxzoom: Some(5),
+            minzoom: Some(0),
+            other: BTreeMap::default(),
+        };
+        let usage = |fields: &[&str]| LayerUsage {
+            tile: coord,
+            min_zoom: 3,
+            max_zoom: 6,
+            fields: fields.iter().map(|f| ((*f).to_owned(), coord)).collect(),
+        };
+        let metadata = [layer("roads", &["name"]), layer("water", &[])];
+        let layers = BTreeMap::from([
+            ("roads".to_owned(), usage(&["name", "kind"])),
+            ("poi".to_owned(), usage(&[])),
+        ]);
+
+        let mut report = TileValidationReport {
+            max_problems: 10,
+            ..TileValidationReport::default()
+        };
+        check_vector_layers(Some(&metadata), &layers, &mut report);
+        let messages: Vec<_> = report.problems.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "3/1/2: layer poi is not in the vector_layers metadata",
+                "3/1/2: layer roads is in zoom levels 3-6, but the vector_layers metadata sets 0-5",
+                "3/1/2: attribute kind of layer roads is not in the vector_layers metadata",
+                "layer water of the vector_layers metadata is in none of the tiles",
+            ]
+        );
+
+        let mut report = TileValidationReport {
+            max_problems: 10,
+            ..TileValidationReport::default()
+        };
+        check_vector_layers(None, &layers, &mut report);
+        assert_eq!(report.problem_count, 1);
+    }
+}
@@ -0,0 +1,430 @@
+//! Built-in tile transforms for [`MbtilesTranscoder`], used by the `mbtiles transcode` command:
+//! MVT/MLT conversion, changing the tile compression, and re-encoding raster tiles.
+//!
+//! [`transcode`] detects the tile format of the source file, converts every tile with a
+//! [`TileTranscode`], and updates the `format`/`compression` metadata of the destination.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use bytes::Bytes;
+use image::DynamicImage;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use martin_tile_utils::{
+    Encoding, Format, TileInfo, decode_brotli, decode_gzip, decode_zlib, decode_zstd,
+    encode_brotli, encode_gzip, encode_zlib, encode_zstd,
+};
+use mlt_core::encoder::EncoderConfig;
+use mlt_core::mvt::{mvt_to_tile_layers, tile_layers_to_mvt};
+use mlt_core::{Decoder, Layer, Parser};
+use tracing::{info, warn};
+
+use crate::{MbtError, MbtResult, MbtType, Mbtiles, MbtilesTranscoder, TranscodeStats};
+
+/// Default quality of lossy (JPEG and AVIF) raster output.
+pub const DEFAULT_TRANSCODE_QUALITY: u8 = 80;
+
+/// AVIF encoder speed, from 1 (slowest, smallest) to 10. Tiles are small and numerous,
+/// so this trades a little size for a much faster encoding.
+const AVIF_SPEED: u8 = 6;
+
+/// Tile format to transcode to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum TranscodeFormat {
+    /// Mapbox Vector Tiles
+    #[cfg_attr(feature = "cli", value(name = "mvt", alias = "pbf"))]
+    Mvt,
+    /// `MapLibre` Tiles
+    #[cfg_attr(feature = "cli", value(name = "mlt"))]
+    Mlt,
+    /// Lossless PNG images
+    #[cfg_attr(feature = "cli", value(name = "png"))]
+    Png,
+    /// Lossy JPEG images, see `quality`
+    #[cfg_attr(feature = "cli", value(name = "jpeg", alias = "jpg"))]
+    Jpeg,
+    /// Lossless `WebP` images
+    #[cfg_attr(feature = "cli", value(name = "webp"))]
+    Webp,
+    /// Lossy AVIF images, see `quality`
+    #[cfg_attr(feature = "cli", value(name = "avif"))]
+    Avif,
+}
+
+impl From<TranscodeFormat> for Format {
+    fn from(format: TranscodeFormat) -> Self {
+        match format {
+            TranscodeFormat::Mvt => Self::Mvt,
+            TranscodeFormat::Mlt => Self::Mlt,
+            TranscodeFormat::Png => Self::Png,
+            TranscodeFormat::Jpeg => Self::Jpeg,
+            TranscodeFormat::Webp => Self::Webp,
+            TranscodeFormat::Avif => Self::Avif,
+        }
+    }
+}
+
+/// Compression of transcoded vector tiles.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum TranscodeCompression {
+    /// Store tiles uncompressed
+    #[cfg_attr(feature = "cli", value(name = "none"))]
+    None,
+    #[cfg_attr(feature = "cli", value(name = "gzip", alias = "gz"))]
+    Gzip,
+    #[cfg_attr(feature = "cli", value(name = "brotli", alias = "br"))]
+    Brotli,
+    #[cfg_attr(feature = "cli", value(name = "zstd"))]
+    Zstd,
+}
+
+/// What [`transcode`] converts the tiles to.
+#[derive(Clone, Debug)]
+pub struct TranscodeOptions {
+    /// Format of the transcoded tiles, `None` keeps the source format.
+    pub format: Option<TranscodeFormat>,
+    /// Compression of the transcoded vector tiles. If `None`, tiles keep their compression
+    /// unless the format changes: MVT tiles are then gzip-compressed, MLT tiles are not compressed.
+    pub compression: Option<TranscodeCompression>,
+    /// Quality of lossy raster output, from 1 to 100.
+    pub quality: u8,
+    /// Encoder settings of MLT output.
+    pub mlt: EncoderConfig,
+    /// Schema of a new destination file, defaults to the schema of the source.
+    pub dst_type: Option<MbtType>,
+}
+
+impl Default for TranscodeOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            compression: None,
+            quality: DEFAULT_TRANSCODE_QUALITY,
+            mlt: EncoderConfig::default(),
+            dst_type: None,
+        }
+    }
+}
+
+/// Converts the tiles of one [`TileInfo`] into another.
+#[derive(Clone, Debug)]
+pub struct TileTranscode {
+    src: TileInfo,
+    dst: TileInfo,
+    quality: u8,
+    mlt: EncoderConfig,
+}
+
+impl TileTranscode {
+    /// Resolves the target of the `options` for tiles of the `src` type,
+    /// failing if the conversion is not supported.
+    pub fn new(src: TileInfo, options: &TranscodeOptions) -> MbtResult<Self> {
+        let format = options.format.map_or(src.format, Format::from);
+        let encoding = match options.compression {
+            _ if is_raster(format) => Encoding::Internal,
+            None if format == src.format => src.encoding,
+            None if format == Format::Mvt => Encoding::Gzip,
+            None | Some(TranscodeCompression::None) => TileInfo::from(format).encoding,
+            Some(TranscodeCompression::Gzip) => Encoding::Gzip,
+            Some(TranscodeCompression::Brotli) => Encoding::Brotli,
+            Some(TranscodeCompression::Zstd) => Encoding::Zstd,
+        };
+        let dst = TileInfo::new(format, encoding);
+
+        let compressed = !matches!(options.compression, None | Some(TranscodeCompression::None));
+        let supported = match (src.format, format) {
+            (Format::Mvt | Format::Mlt, Format::Mvt | Format::Mlt) => true,
+            // Only formats the `image` crate can decode
+            (Format::Png | Format::Jpeg | Format::Webp, dst) => is_raster(dst) && !compressed,
+            (src, dst) => src == dst && (!is_raster(src) || !compressed),
+        };
+        if !supported {
+            return Err(MbtError::UnsupportedTranscode { src, dst });
+        }
+
+        Ok(Self {
+            src,
+            dst,
+            quality: options.quality.clamp(1, 100),
+            mlt: options.mlt,
+        })
+    }
+
+    /// Format and encoding of the transcoded tiles.
+    #[must_use]
+    pub fn dst(&self) -> TileInfo {
+        self.dst
+    }
+
+    /// Converts a single tile. Tiles already in the target format and encoding are returned as-is.
+    pub fn apply(&self, data: Vec<u8>) -> MbtResult<Vec<u8>> {
+        if self.src == self.dst {
+            return Ok(data);
+        }
+        let data = decompress(data, self.src.encoding)?;
+        let data = match (self.src.format, self.dst.format) {
+            (src, dst) if src == dst => data,
+            (Format::Mvt, Format::Mlt) => mvt_to_mlt(data, self.mlt)?,
+            (Format::Mlt, Format::Mvt) => mlt_to_mvt(&data)?,
+            (_, dst) => reencode_image(&data, dst, self.quality)?,
+        };
+        compress(data, self.dst.encoding)
+    }
+}
+
+/// Transcodes all tiles of `src_file` into `dst_file`, copying the metadata and updating
+/// its `format`, `compression` and `agg_tiles_hash` values.
+pub async fn transcode(
+    src_file: &Path,
+    dst_file: &Path,
+    options: &TranscodeOptions,
+) -> MbtResult<TranscodeStats> {
+    let src = Mbtiles::new(src_file)?;
+    let mut src_conn = src.open_readonly().await?;
+    let metadata = src.get_metadata(&mut src_conn).await?;
+    let Some(src_info) = src.detect_format(&metadata.tilejson, &mut src_conn).await? else {
+        return Err(MbtError::NoFormatInMetadata(src_file.to_path_buf()));
+    };
+    drop(src_conn);
+
+    let tile_transcode = TileTranscode::new(src_info, options)?;
+    let dst_info = tile_transcode.dst();
+    if src_info == dst_info {
+        warn!("Tiles of {src} are already {dst_info}, they will be copied as-is");
+    }
+    info!("Transcoding {src_info} tiles of {src} to {dst_info}");
+
+    let mut transcoder = MbtilesTranscoder::new(src_file, dst_file, move |data| {
+        Ok(Bytes::from(tile_transcode.apply(data)?))
+    });
+    if let Some(dst_type) = options.dst_type {
+        transcoder = transcoder.dst_type(dst_type);
+    }
+    let stats = transcoder.run().await?;
+
+    let dst = Mbtiles::new(dst_file)?;
+    let mut conn = dst.open().await?;
+    dst.set_metadata_value(&mut conn, "format", dst_info.format.metadata_format_value())
+        .await?;
+    if let Some(compression) = dst_info.encoding.compression() {
+        dst.set_metadata_value(&mut conn, "compression", compression)
+            .await?;
+    } else {
+        dst.delete_metadata_value(&mut conn, "compression").await?;
+    }
+    dst.update_agg_tiles_hash(&mut conn).await?;
+
+    Ok(stats)
+}
+
+pub(crate) fn is_raster(format: Format) -> bool {
+    Format::IMAGE_FORMATS.contains(&format)
+}
+
+pub(crate) fn decompress(data: Vec<u8>, encoding: Encoding) -> MbtResult<Vec<u8>> {
+    Ok(match encoding {
+        Encoding::Uncompressed | Encoding::Internal => data,
+        Encoding::Gzip => decode_gzip(&data)?,
+        Encoding::Zlib => decode_zlib(&data)?,
+        Encoding::Brotli => decode_brotli(&data)?,
+        Encoding::Zstd => decode_zstd(&data)?,
+    })
+}
+
+pub(crate) fn compress(data: Vec<u8>, encoding: Encoding) -> MbtResult<Vec<u8>> {
+    Ok(match encoding {
+        Encoding::Uncompressed | Encoding::Internal => data,
+        Encoding::Gzip => encode_gzip(&data)?,
+        Encoding::Zlib => encode_zlib(&data)?,
+        Encoding::Brotli => encode_brotli(&data)?,
+        Encoding::Zstd => encode_zstd(&data)?,
+    })
+}
+
+pub(crate) fn mvt_to_mlt(mvt: Vec<u8>, cfg: EncoderConfig) -> MbtResult<Vec<u8>> {
+    let layers = mvt_to_tile_layers(mvt)
+        .map_err(|e| MbtError::TranscodeError(format!("MVT decode failed: {e}")))?;
+    let mut mlt = Vec::new();
+    for layer in layers {
+        let encoded = layer
+            .encode(cfg)
+            .map_err(|e| MbtError::TranscodeError(format!("MLT encode failed: {e}")))?;
+        mlt.extend_from_slice(&encoded);
+    }
+    Ok(mlt)
+}
+
+pub(crate) fn mlt_to_mvt(mlt: &[u8]) -> MbtResult<Vec<u8>> {
+    let layers = Parser::default()
+        .parse_layers(mlt)
+        .map_err(|e| MbtError::TranscodeError(format!("MLT parse failed: {e}")))?;
+    let mut decoder = Decoder::default();
+    let mut tile_layers = Vec::with_capacity(layers.len());
+    for layer in layers {
+        // Unknown layer tags have no MVT analogue
+        if let Layer::Tag01(layer) = layer {
+            tile_layers.push(
+                layer
+                    .into_tile(&mut decoder)
+                    .map_err(|e| MbtError::TranscodeError(format!("MLT decode failed: {e}")))?,
+            );
+        }
+    }
+    tile_layers_to_mvt(tile_layers)
+        .map_err(|e| MbtError::TranscodeError(format!("MVT encode failed: {e}")))
+}
+
+fn reencode_image(data: &[u8], format: Format, quality: u8) -> MbtResult<Vec<u8>> {
+    encode_image(&image::load_from_memory(data)?, format, quality)
+}
+
+/// Encodes an image as a tile of the given raster `format`.
+pub(crate) fn encode_image(
+    image: &DynamicImage,
+    format: Format,
+    quality: u8,
+) -> MbtResult<Vec<u8>> {
+    let mut out = Cursor::new(Vec::new());
+    match format {
+        Format::Png => image.write_with_encoder(PngEncoder::new(&mut out))?,
+        Format::Webp => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(WebPEncoder::new_lossless(&mut out))?,
+        // JPEG has no alpha channel
+        Format::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, quality))?,
+        Format::Avif => DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(
+            AvifEncoder::new_with_speed_quality(&mut out, AVIF_SPEED, quality),
+        )?,
+        other => {
+            return Err(MbtError::TranscodeError(format!(
+                "Cannot encode {other} images"
+            )));
+        }
+    }
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn options(
+        format: Option<TranscodeFormat>,
+        compression: Option<TranscodeCompression>,
+    ) -> TranscodeOptions {
+        TranscodeOptions {
+            format,
+            compression,
+            ..TranscodeOptions::default()
+        }
+    }
+
+    #[rstest]
+    #[case::keep(
+        TileInfo::new(Format::Mvt, Encoding::Gzip),
+        None,
+        None,
+        Some(Encoding::Gzip)
+    )]
+    #[case::to_mlt(
+        TileInfo::new(Format::Mvt, Encoding::Gzip),
+        Some(TranscodeFormat::Mlt),
+        None,
+        Some(Encoding::Internal)
+    )]
+    #[case::to_mvt(
+        TileInfo::new(Format::Mlt, Encoding::Internal),
+        Some(TranscodeFormat::Mvt),
+        None,
+        Some(Encoding::Gzip)
+    )]
+    #[case::recompress(
+        TileInfo::new(Format::Mvt, Encoding::Gzip),
+        None,
+        Some(TranscodeCompression::Zstd),
+        Some(Encoding::Zstd)
+    )]
+    #[case::decompress(
+        TileInfo::new(Format::Mvt, Encoding::Gzip),
+        None,
+        Some(TranscodeCompression::None),
+        Some(Encoding::Uncompressed)
+    )]
+    #[case::to_webp(
+        TileInfo::new(Format::Png, Encoding::Internal),
+        Some(TranscodeFormat::Webp),
+        None,
+        Some(Encoding::Internal)
+    )]
+    #[case::compressed_raster(
+        TileInfo::new(Format::Png, Encoding::Internal),
+        Some(TranscodeFormat::Webp),
+        Some(TranscodeCompression::Gzip),
+        None
+    )]
+    #[case::vector_to_raster(
+        TileInfo::new(Format::Mvt, Encoding::Gzip),
+        Some(TranscodeFormat::Png),
+        None,
+        None
+    )]
+    #[case::undecodable_raster(
+        TileInfo::new(Format::Avif, Encoding::Internal),
+        Some(TranscodeFormat::Png),
+        None,
+        None
+    )]
+    fn resolves_target(
+        #[case] src: TileInfo,
+        #[case] format: Option<TranscodeFormat>,
+        #[case] compression: Option<TranscodeCompression>,
+        #[case] expected: Option<Encoding>,
+    ) {
+        let result = TileTranscode::new(src, &options(format, compression));
+        let expected = expected
+            .map(|encoding| TileInfo::new(format.map_or(src.format, Format::from), encoding));
+        assert_eq!(result.ok().as_ref().map(TileTranscode::dst), expected);
+    }
+
+    #[test]
+    fn recompresses_tiles() {
+        let mvt = b"not really a vector tile".to_vec();
+        let transcode = TileTranscode::new(
+            TileInfo::new(Format::Mvt, Encoding::Gzip),
+            &options(None, Some(TranscodeCompression::Brotli)),
+        )
+        .unwrap();
+        let tile = transcode.apply(encode_gzip(&mvt).unwrap()).unwrap();
+        assert_eq!(decode_brotli(&tile).unwrap(), mvt);
+    }
+
+    #[rstest]
+    #[case(TranscodeFormat::Webp, Format::Webp)]
+    #[case(TranscodeFormat::Jpeg, Format::Jpeg)]
+    #[case(TranscodeFormat::Avif, Format::Avif)]
+    fn reencodes_images(#[case] target: TranscodeFormat, #[case] format: Format) {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::new_rgba8(256, 256)
+            .write_with_encoder(PngEncoder::new(&mut png))
+            .unwrap();
+        let transcode = TileTranscode::new(
+            TileInfo::new(Format::Png, Encoding::Internal),
+            &options(Some(target), None),
+        )
+        .unwrap();
+        let tile = transcode.apply(png.into_inner()).unwrap();
+        if format == Format::Avif {
+            // AVIF is not detected by its magic bytes
+            assert!(tile.windows(4).any(|w| w == b"avif"));
+        } else {
+            assert_eq!(TileInfo::detect(&tile).format, format);
+        }
+    }
+}
@@ -12,7 +12,7 @@ use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
 use sqlx::{AssertSqlSafe, Connection as _, Row as _, SqliteConnection};
 use tokio::task::spawn_blocking;
 use tracing::{debug, info, warn};
-use xxhash_rust::xxh3::xxh3_128;
+use xxhash_rust::xxh3::{xxh3_128, xxh3_128_with_seed};
 
 use crate::errors::MbtResult;
 use crate::mbtiles::parse_tile_index;
@@ -76,6 +76,45 @@ impl DedupStats {
     }
 }
 
+/// A transform applied to every tile by [`MbtilesTranscoder`].
+///
+/// It is implemented for all `Fn(Vec<u8>) -> Result<Bytes, _>` closures,
+/// whose result only depends on the tile data.
+pub trait TileTransform: Send + Sync + 'static {
+    /// Transforms the data of a tile. `zoom` is the zoom level of the tile
+    /// if the transform [uses it](Self::uses_zoom), and `None` otherwise.
+    fn transform(
+        &self,
+        zoom: Option<u8>,
+        data: Vec<u8>,
+    ) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Whether the result depends on the zoom level of the tile.
+    ///
+    /// Such transforms run on each tile of the source, even for normalized sources,
+    /// and identical tiles are only shared within a zoom level.
+    /// Tiles they return as empty data are not written.
+    fn uses_zoom(&self) -> bool {
+        false
+    }
+}
+
+impl<F> TileTransform for F
+where
+    F: Fn(Vec<u8>) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn transform(
+        &self,
+        _zoom: Option<u8>,
+        data: Vec<u8>,
+    ) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+        self(data)
+    }
+}
+
 /// Builder for a parallelized mbtiles-to-mbtiles transcoding pipeline.
 ///
 /// The transform closure is applied to every unique tile payload. The pipeline
@@ -85,8 +124,8 @@ impl DedupStats {
 /// - **Normalized source**: encodes only the deduplicated `tiles` table, then
 ///   fans out to any destination type by joining encoded tiles against the
 ///   source map table inside the writer's INSERT.
-/// - **Flat/FlatWithHash source**: uses a weighted dedup cache keyed by content
-///   hash to avoid redundant transforms.
+/// - **Flat/FlatWithHash source**, or a transform that [uses the zoom level](TileTransform::uses_zoom):
+///   uses a weighted dedup cache keyed by content hash to avoid redundant transforms.
 ///
 /// CPU-bound work runs on a rayon thread pool via [`tokio::task::spawn_blocking`].
 pub struct MbtilesTranscoder<F> {
@@ -110,6 +149,20 @@ where
 {
     /// Create a new transcoder with required parameters and sensible defaults.
     pub fn new(src_file: impl AsRef<Path>, dst_file: impl AsRef<Path>, transform: F) -> Self {
+        Self::with_transform(src_file, dst_file, transform)
+    }
+}
+
+impl<F> MbtilesTranscoder<F>
+where
+    F: TileTransform,
+{
+    /// Create a new transcoder from any [`TileTransform`], e.g. one that depends on the zoom level.
+    pub fn with_transform(
+        src_file: impl AsRef<Path>,
+        dst_file: impl AsRef<Path>,
+        transform: F,
+    ) -> Self {
         Self {
             src_file: src_file.as_ref().to_path_buf(),
             dst_file: dst_file.as_ref().to_path_buf(),
@@ -200,7 +253,10 @@ where
 
         // Attach source ONCE and reuse it for both metadata copy and the
         // normalized writer's join. The general path doesn't need it.
-        let needs_src_attached = src_type.normalized_schema().is_some() || self.copy_metadata;
+        let normalized_src = src_type
+            .normalized_schema()
+            .filter(|_| !self.transform.uses_zoom());
+        let needs_src_attached = normalized_src.is_some() || self.copy_metadata;
         if needs_src_attached {
             src.attach_to(&mut dst_conn, "srcDb").await?;
         }
@@ -211,7 +267,7 @@ where
                 .await?;
         }
 
-        let stats = if let Some(src_schema) = src_type.normalized_schema() {
+        let stats = if let Some(src_schema) = normalized_src {
             self.run_normalized_path(&mut src_conn, src_schema, &mut dst_conn, dst_type)
                 .await?
         } else {
@@ -369,14 +425,14 @@ async fn normalized_reader(
             raw_tx
                 .send_async(full)
                 .await
-                .map_err(|_| MbtError::TranscodeError("compute stage closed".into()))?;
+                .map_err(|_err| MbtError::TranscodeError("compute stage closed".into()))?;
         }
     }
     if !batch.is_empty() {
         raw_tx
             .send_async(batch)
             .await
-            .map_err(|_| MbtError::TranscodeError("compute stage closed".into()))?;
+            .map_err(|_err| MbtError::TranscodeError("compute stage closed".into()))?;
     }
     Ok(())
 }
@@ -388,17 +444,14 @@ async fn normalized_compute<F>(
     transform: Arc<F>,
 ) -> MbtResult<()>
 where
-    F: Fn(Vec<u8>) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>>
-        + Send
-        + Sync
-        + 'static,
+    F: TileTransform,
 {
     while let Ok(batch) = raw_rx.recv_async().await {
         let transform = Arc::clone(&transform);
         let enc_batch: NormEncBatch = spawn_blocking(move || {
             batch
                 .into_par_iter()
-                .filter_map(|(tile_id, data)| match (transform)(data) {
+                .filter_map(|(tile_id, data)| match transform.transform(None, data) {
                     Ok(encoded) => Some((tile_id, encoded)),
                     Err(e) => {
                         warn!(tile.id = %tile_id, error = ?e, "Skipping image");
@@ -414,7 +467,7 @@ where
             enc_tx
                 .send_async(enc_batch)
                 .await
-                .map_err(|_| MbtError::TranscodeError("writer stage closed".into()))?;
+                .map_err(|_err| MbtError::TranscodeError("writer stage closed".into()))?;
         }
     }
     Ok(())
@@ -611,11 +664,14 @@ async fn general_reader(
 ) -> MbtResult<()> {
     let is_flat_with_hash = matches!(src_type, MbtType::FlatWithHash);
     let sql = match src_type {
-        MbtType::Flat => "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles",
+        // Only transforms using the zoom level read normalized sources tile by tile,
+        // through their `tiles` view
+        MbtType::Flat | MbtType::Normalized { .. } => {
+            "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles"
+        }
         MbtType::FlatWithHash => {
             "SELECT zoom_level, tile_column, tile_row, tile_data, tile_hash FROM tiles_with_hash"
         }
-        MbtType::Normalized { .. } => unreachable!("general_reader called with normalized source"),
         MbtType::Cache => unreachable!("cache files are rejected before transcoding starts"),
     };
 
@@ -647,14 +703,14 @@ async fn general_reader(
             raw_tx
                 .send_async(full)
                 .await
-                .map_err(|_| MbtError::TranscodeError("compute stage closed".into()))?;
+                .map_err(|_err| MbtError::TranscodeError("compute stage closed".into()))?;
         }
     }
     if !batch.is_empty() {
         raw_tx
             .send_async(batch)
             .await
-            .map_err(|_| MbtError::TranscodeError("compute stage closed".into()))?;
+            .map_err(|_err| MbtError::TranscodeError("compute stage closed".into()))?;
     }
     Ok(())
 }
@@ -669,10 +725,7 @@ async fn general_compute<F>(
     max_tile_track_size: usize,
 ) -> MbtResult<()>
 where
-    F: Fn(Vec<u8>) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>>
-        + Send
-        + Sync
-        + 'static,
+    F: TileTransform,
 {
     while let Ok(batch) = raw_rx.recv_async().await {
         let transform = Arc::clone(&transform);
@@ -702,7 +755,7 @@ where
             enc_tx
                 .send_async(enc_batch)
                 .await
-                .map_err(|_| MbtError::TranscodeError("writer stage closed".into()))?;
+                .map_err(|_err| MbtError::TranscodeError("writer stage closed".into()))?;
         }
     }
     Ok(())
@@ -719,15 +772,19 @@ fn transcode_cached<F>(
     max_tile_track_size: usize,
 ) -> Option<(TileCoord, Bytes)>
 where
-    F: Fn(Vec<u8>) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>>,
+    F: TileTransform,
 {
+    let zoom = transform.uses_zoom().then_some(coord.z);
+    // Transforms using the zoom level may drop a tile by emptying it
+    let keep = |encoded: &Bytes| zoom.is_none() || !encoded.is_empty();
+
     // Skip the cache for large tiles - they are almost certainly unique, so
     // caching them just evicts smaller, more valuable entries.
     if data.len() > max_tile_track_size {
-        return match (transform)(data) {
+        return match transform.transform(zoom, data) {
             Ok(encoded) => {
                 stats.record_encode();
-                Some((coord, encoded))
+                keep(&encoded).then_some((coord, encoded))
             }
             Err(e) => {
                 warn!(tile.coord = %coord, error = ?e, "Skipping tile");
@@ -737,12 +794,18 @@ where
     }
 
     // FlatWithHash provides a content hash we can reuse; otherwise compute one.
-    let key = key.unwrap_or_else(|| xxh3_128(&data));
+    // The same tile may be transformed differently at each zoom level.
+    let key = match zoom {
+        Some(zoom) => xxh3_128_with_seed(&data, u64::from(zoom)),
+        None => key.unwrap_or_else(|| xxh3_128(&data)),
+    };
 
     let entry = cache
         .entry(key)
         .or_try_insert_with(
-            || -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> { (transform)(data) },
+            || -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+                transform.transform(zoom, data)
+            },
         )
         .inspect_err(|e| warn!(tile.coord = %coord, error = ?e, "Skipping tile"))
         .ok()?;
@@ -754,7 +817,7 @@ where
     } else {
         stats.record_hit();
     }
-    Some((coord, encoded))
+    keep(&encoded).then_some((coord, encoded))
 }
 
 /// Writer: batch-insert encoded tiles into the destination.
@@ -810,7 +873,6 @@ async fn general_writer(
 }
 
 #[cfg(test)]
-#[expect(clippy::unwrap_used)]
 mod tests {
     use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -936,6 +998,53 @@ mod tests {
         assert_eq!(count, 6);
     }
 
+    /// Appends the zoom level to each tile, and drops the tiles of zoom 0.
+    struct AppendZoom;
+
+    impl TileTransform for AppendZoom {
+        fn transform(
+            &self,
+            zoom: Option<u8>,
+            mut data: Vec<u8>,
+        ) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+            let zoom = zoom.ok_or("missing zoom level")?;
+            if zoom == 0 {
+                return Ok(Bytes::new());
+            }
+            data.push(zoom);
+            Ok(Bytes::from(data))
+        }
+
+        fn uses_zoom(&self) -> bool {
+            true
+        }
+    }
+
+    #[actix_rt::test]
+    async fn transcode_normalized_with_zoom() {
+        let script = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+        let (_mbt, _conn, src_file) = temp_named_mbtiles("tc_norm_zoom", script).await;
+        let dst_file = NamedTempFile::with_suffix("mbtiles").unwrap();
+        let stats = MbtilesTranscoder::with_transform(&src_file, &dst_file, AppendZoom)
+            .dst_type(MbtType::Flat)
+            .run()
+            .await
+            .unwrap();
+
+        // The zoom 0 tile is dropped, and the same tile at zoom 1 and 2 is transformed twice
+        assert_eq!(stats.tiles_written, 5);
+        let dst_mbt = Mbtiles::new(dst_file.path()).unwrap();
+        let mut conn = dst_mbt.open_readonly().await.unwrap();
+        let tiles: Vec<(u8, Vec<u8>)> = sqlx::query_as("SELECT zoom_level, tile_data FROM tiles")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(tiles.len(), 5);
+        for (zoom, data) in tiles {
+            assert_eq!(data.last(), Some(&zoom));
+        }
+    }
+
     #[actix_rt::test]
     async fn transcode_dedup_id_to_hash_normalized() {
         let script = include_str!("../../tests/fixtures/mbtiles/normalized-dedup-id.sql");
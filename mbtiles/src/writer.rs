@@ -0,0 +1,265 @@
+//! Writing and deleting single tiles, e.g. to replace individual tiles with corrected versions.
+//!
+//! Unlike the batch tools, every write is its own transaction, which also removes tile blobs
+//! no longer used by the normalized schema. Recomputing `agg_tiles_hash` reads every tile,
+//! so writes remove it instead, and [`Mbtiles::update_agg_tiles_hash`] adds it back
+//! once after a batch of writes.
+
+use sqlx::{AssertSqlSafe, Connection as _, SqliteConnection, query, query_scalar};
+
+use crate::errors::{MbtError, MbtResult};
+use crate::{AGG_TILES_HASH, MbtType, Mbtiles, NormalizedSchema, invert_y_value};
+
+impl Mbtiles {
+    /// Writes a single tile, replacing any tile with the same coordinates.
+    ///
+    /// Coordinates use the XYZ scheme. The `tile_hash` column and the de-duplicated `images`
+    /// table are kept consistent.
+    ///
+    /// > [!NOTE]
+    /// > The `agg_tiles_hash` metadata value no longer matches the tiles, and is removed.
+    /// > Call [`Mbtiles::update_agg_tiles_hash`] after the last write of a batch to add it back.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MbtError::UnsupportedTileWrite`] for the `tiles_shallow`/`tiles_data`
+    /// normalized schema and for tile-cache files.
+    #[hotpath::measure]
+    pub async fn put_tile(
+        &self,
+        conn: &mut SqliteConnection,
+        mbt_type: MbtType,
+        z: u8,
+        x: u32,
+        y: u32,
+        data: &[u8],
+    ) -> MbtResult<()> {
+        self.check_writable(mbt_type)?;
+        let y = invert_y_value(z, y);
+        let mut tx = conn.begin().await?;
+        let old_tile_id = delete_tile_row(&mut tx, mbt_type, z, x, y).await?.1;
+        match mbt_type {
+            MbtType::FlatWithHash => {
+                query(
+                    "INSERT INTO tiles_with_hash (zoom_level, tile_column, tile_row, tile_data, tile_hash)
+                     VALUES (?1, ?2, ?3, ?4, md5_hex(?4))",
+                )
+                .bind(z)
+                .bind(x)
+                .bind(y)
+                .bind(data)
+                .execute(&mut *tx)
+                .await?;
+            }
+            MbtType::Normalized { .. } => {
+                query(
+                    "INSERT INTO images (tile_id, tile_data)
+                     SELECT md5_hex(?1), ?1
+                     WHERE NOT EXISTS (SELECT 1 FROM images WHERE tile_id = md5_hex(?1))",
+                )
+                .bind(data)
+                .execute(&mut *tx)
+                .await?;
+                query(
+                    "INSERT INTO map (zoom_level, tile_column, tile_row, tile_id)
+                     VALUES (?1, ?2, ?3, md5_hex(?4))",
+                )
+                .bind(z)
+                .bind(x)
+                .bind(y)
+                .bind(data)
+                .execute(&mut *tx)
+                .await?;
+            }
+            _ => {
+                query(
+                    "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data)
+                     VALUES (?1, ?2, ?3, ?4)",
+                )
+                .bind(z)
+                .bind(x)
+                .bind(y)
+                .bind(data)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        if let Some(tile_id) = old_tile_id {
+            delete_unused_image(&mut tx, &tile_id).await?;
+        }
+        self.delete_metadata_value(&mut *tx, AGG_TILES_HASH).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes a single tile, and returns `false` if there was no tile with these coordinates.
+    ///
+    /// Coordinates use the XYZ scheme. Tile blobs of the normalized schema that no other tile uses
+    /// are deleted too. See [`Mbtiles::put_tile`] for the supported schemas and `agg_tiles_hash`.
+    #[hotpath::measure]
+    pub async fn delete_tile(
+        &self,
+        conn: &mut SqliteConnection,
+        mbt_type: MbtType,
+        z: u8,
+        x: u32,
+        y: u32,
+    ) -> MbtResult<bool> {
+        self.check_writable(mbt_type)?;
+        let mut tx = conn.begin().await?;
+        let (deleted, old_tile_id) =
+            delete_tile_row(&mut tx, mbt_type, z, x, invert_y_value(z, y)).await?;
+        if let Some(tile_id) = old_tile_id {
+            delete_unused_image(&mut tx, &tile_id).await?;
+        }
+        if deleted {
+            self.delete_metadata_value(&mut *tx, AGG_TILES_HASH).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    fn check_writable(&self, mbt_type: MbtType) -> MbtResult<()> {
+        let schema = match mbt_type {
+            MbtType::Flat | MbtType::FlatWithHash => return Ok(()),
+            MbtType::Normalized { schema, .. } => match schema {
+                NormalizedSchema::Hash => return Ok(()),
+                NormalizedSchema::DedupId => "tiles_shallow/tiles_data normalized",
+            },
+            MbtType::Cache => "tile-cache",
+        };
+        Err(MbtError::UnsupportedTileWrite(
+            self.filepath().to_owned(),
+            schema,
+        ))
+    }
+}
+
+/// Deletes the tile with the TMS coordinates, returning whether it existed,
+/// and the `tile_id` it used in the normalized schema.
+async fn delete_tile_row(
+    conn: &mut SqliteConnection,
+    mbt_type: MbtType,
+    z: u8,
+    x: u32,
+    tms_y: u32,
+) -> MbtResult<(bool, Option<String>)> {
+    let (table, old_tile_id) = match mbt_type {
+        MbtType::Normalized { .. } => {
+            let tile_id: Option<Option<String>> = query_scalar(
+                "SELECT tile_id FROM map WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            )
+            .bind(z)
+            .bind(x)
+            .bind(tms_y)
+            .fetch_optional(&mut *conn)
+            .await?;
+            ("map", tile_id.flatten())
+        }
+        MbtType::FlatWithHash => ("tiles_with_hash", None),
+        _ => ("tiles", None),
+    };
+    let sql =
+        format!("DELETE FROM {table} WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3");
+    let deleted = query(AssertSqlSafe(sql))
+        .bind(z)
+        .bind(x)
+        .bind(tms_y)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    Ok((deleted > 0, old_tile_id))
+}
+
+/// Deletes a tile blob of the normalized schema if no tile uses it anymore.
+async fn delete_unused_image(conn: &mut SqliteConnection, tile_id: &str) -> MbtResult<()> {
+    query(
+        "DELETE FROM images
+         WHERE tile_id = ?1 AND NOT EXISTS (SELECT 1 FROM map WHERE tile_id = ?1)",
+    )
+    .bind(tile_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::anonymous_mbtiles;
+    use crate::{MbtTypeCli, init_mbtiles_schema};
+
+    async fn new_file(mbt_type: MbtType) -> (Mbtiles, SqliteConnection) {
+        let (mbt, mut conn) = anonymous_mbtiles("").await;
+        init_mbtiles_schema(&mut conn, mbt_type, true)
+            .await
+            .unwrap();
+        mbt.put_tile(&mut conn, mbt_type, 1, 0, 0, b"first")
+            .await
+            .unwrap();
+        mbt.put_tile(&mut conn, mbt_type, 1, 1, 0, b"shared")
+            .await
+            .unwrap();
+        mbt.put_tile(&mut conn, mbt_type, 1, 1, 1, b"shared")
+            .await
+            .unwrap();
+        mbt.update_agg_tiles_hash(&mut conn).await.unwrap();
+        (mbt, conn)
+    }
+
+    async fn image_count(conn: &mut SqliteConnection) -> i64 {
+        query_scalar("SELECT COUNT(*) FROM images")
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn write_tiles() {
+        for mbt_type in [
+            MbtTypeCli::Flat,
+            MbtTypeCli::FlatWithHash,
+            MbtTypeCli::Normalized,
+        ] {
+            let mbt_type = MbtType::from(mbt_type);
+            let (mbt, mut conn) = new_file(mbt_type).await;
+            let old_hash = mbt.get_agg_tiles_hash(&mut conn).await.unwrap();
+
+            mbt.put_tile(&mut conn, mbt_type, 1, 0, 0, b"fixed")
+                .await
+                .unwrap();
+            let tile = mbt.get_tile(&mut conn, 1, 0, 0).await.unwrap();
+            assert_eq!(tile.as_deref(), Some(&b"fixed"[..]), "{mbt_type}");
+            // The stale hash is removed until the batch of writes is done
+            assert_eq!(mbt.get_agg_tiles_hash(&mut conn).await.unwrap(), None);
+            mbt.check_each_tile_hash(&mut conn).await.unwrap();
+
+            assert!(mbt.delete_tile(&mut conn, mbt_type, 1, 1, 0).await.unwrap());
+            assert!(!mbt.delete_tile(&mut conn, mbt_type, 1, 1, 0).await.unwrap());
+            assert_eq!(mbt.get_tile(&mut conn, 1, 1, 0).await.unwrap(), None);
+            let new_hash = mbt.update_agg_tiles_hash(&mut conn).await.unwrap();
+            assert_ne!(old_hash, Some(new_hash), "{mbt_type}");
+            mbt.check_agg_tiles_hashes(&mut conn).await.unwrap();
+
+            if matches!(mbt_type, MbtType::Normalized { .. }) {
+                // "first" was replaced, "shared" is still used by 1/1/1
+                assert_eq!(image_count(&mut conn).await, 2);
+                assert!(mbt.delete_tile(&mut conn, mbt_type, 1, 1, 1).await.unwrap());
+                assert_eq!(image_count(&mut conn).await, 1);
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn unsupported_schema() {
+        let (mbt, mut conn) = anonymous_mbtiles("").await;
+        init_mbtiles_schema(&mut conn, MbtType::Cache, true)
+            .await
+            .unwrap();
+        let err = mbt
+            .put_tile(&mut conn, MbtType::Cache, 0, 0, 0, b"tile")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MbtError::UnsupportedTileWrite(..)), "{err}");
+    }
+}
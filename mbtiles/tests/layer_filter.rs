@@ -0,0 +1,113 @@
+#![cfg(feature = "transcode")]
+#![allow(clippy::unwrap_used)]
+
+use martin_tile_utils::decode_gzip;
+use mbtiles::{
+    AggHashType, IntegrityCheckType, LayerFilter, MbtError, MbtTypeCli, Mbtiles, MbtilesCopier,
+    temp_named_mbtiles,
+};
+use mlt_core::mvt::mvt_to_tile_layers;
+use serde_json::Value;
+use sqlx::query_scalar;
+use tempfile::NamedTempFile;
+use tilejson::VectorLayer;
+
+const WORLD_CITIES: &str = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+const GEOGRAPHY_CLASS: &str = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+
+async fn tile_count(mbt: &Mbtiles, min_zoom: u8) -> i64 {
+    let mut conn = mbt.open_readonly().await.unwrap();
+    query_scalar("SELECT COUNT(*) FROM tiles WHERE zoom_level >= ?")
+        .bind(min_zoom)
+        .fetch_one(&mut conn)
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keeps_layers_at_zoom_levels() {
+    let (src, _src_conn, src_file) = temp_named_mbtiles("filter_zoom", WORLD_CITIES).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let mut conn = MbtilesCopier {
+        src_file,
+        dst_file: dst_file.path().to_path_buf(),
+        dst_type_cli: Some(MbtTypeCli::Normalized),
+        layer_filter: LayerFilter::new([], [], [], [], [("cities".to_owned(), 3..=30)]),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+
+    let dst = Mbtiles::new(dst_file.path()).unwrap();
+    // Tiles below zoom 3 lose their only layer and are dropped,
+    // except for the empty zoom 1 tile of the fixture, which the filter does not change
+    assert_eq!(tile_count(&dst, 0).await, tile_count(&src, 3).await + 1);
+    // The stored value, as reading the metadata widens the zooms to the ones with tiles
+    let json = dst.get_metadata_value(&mut conn, "json").await.unwrap();
+    let json: Value = serde_json::from_str(&json.unwrap()).unwrap();
+    let layers: Vec<VectorLayer> = serde_json::from_value(json["vector_layers"].clone()).unwrap();
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].minzoom, Some(3));
+    assert_eq!(layers[0].maxzoom, Some(6));
+    dst.validate(
+        &mut conn,
+        IntegrityCheckType::default(),
+        AggHashType::default(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn removes_attributes() {
+    let (src, _src_conn, src_file) = temp_named_mbtiles("filter_attrs", WORLD_CITIES).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let mut conn = MbtilesCopier {
+        src_file,
+        dst_file: dst_file.path().to_path_buf(),
+        layer_filter: LayerFilter::new(
+            [],
+            [],
+            [],
+            [("cities".to_owned(), vec!["name".to_owned()])],
+            [],
+        ),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+
+    let dst = Mbtiles::new(dst_file.path()).unwrap();
+    assert_eq!(tile_count(&dst, 0).await, tile_count(&src, 0).await);
+    let tile: Vec<u8> = query_scalar("SELECT tile_data FROM tiles WHERE zoom_level = 0")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    let layers = mvt_to_tile_layers(decode_gzip(&tile).unwrap()).unwrap();
+    assert_eq!(layers[0].name(), "cities");
+    assert!(layers[0].property_names().is_empty());
+    assert!(!layers[0].features().is_empty());
+
+    let metadata = dst.get_metadata(&mut conn).await.unwrap();
+    let layers = metadata.tilejson.vector_layers.unwrap();
+    assert!(layers[0].fields.is_empty());
+    let tilestats = &metadata.json.unwrap()["tilestats"];
+    assert_eq!(tilestats["layers"][0]["attributeCount"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_raster_tiles() {
+    let (_src, _src_conn, src_file) = temp_named_mbtiles("filter_raster", GEOGRAPHY_CLASS).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let result = MbtilesCopier {
+        src_file,
+        dst_file: dst_file.path().to_path_buf(),
+        layer_filter: LayerFilter::new(["roads".to_owned()], [], [], [], []),
+        ..Default::default()
+    }
+    .run()
+    .await;
+    assert!(matches!(result, Err(MbtError::NoVectorTiles { .. })));
+}
@@ -0,0 +1,166 @@
+#![cfg(feature = "transcode")]
+#![allow(clippy::unwrap_used)]
+
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use martin_tile_utils::{Format, TileInfo, decode_gzip};
+use mbtiles::{
+    AggHashType, IntegrityCheckType, MbtError, Mbtiles, MergeOptions, merge, temp_named_mbtiles,
+};
+use mlt_core::mvt::mvt_to_tile_layers;
+use sqlx::{SqliteConnection, query, query_scalar};
+use tempfile::NamedTempFile;
+
+const WORLD_CITIES: &str = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+const GEOGRAPHY_CLASS: &str = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+
+/// Replaces the placeholder images of the geography class fixture with real PNGs.
+async fn use_real_images(conn: &mut SqliteConnection) {
+    let tile_ids: Vec<String> = query_scalar("SELECT tile_id FROM images ORDER BY tile_id")
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+    for (shade, tile_id) in (0_u8..).step_by(40).zip(tile_ids) {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(256, 256, Rgba([shade, 0, 255 - shade, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        query("UPDATE images SET tile_data = ? WHERE tile_id = ?")
+            .bind(png)
+            .bind(tile_id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+}
+
+async fn first_tile(mbt: &Mbtiles) -> Vec<u8> {
+    let mut conn = mbt.open_readonly().await.unwrap();
+    query_scalar("SELECT tile_data FROM tiles ORDER BY zoom_level, tile_column, tile_row LIMIT 1")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap()
+}
+
+async fn tile_count(mbt: &Mbtiles) -> usize {
+    let mut conn = mbt.open_readonly().await.unwrap();
+    let count: i64 = query_scalar("SELECT COUNT(*) FROM tiles")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    usize::try_from(count).unwrap()
+}
+
+/// Number of vector tiles with at least one layer, the others are dropped by merging.
+async fn non_empty_tile_count(mbt: &Mbtiles) -> usize {
+    let mut conn = mbt.open_readonly().await.unwrap();
+    let tiles: Vec<Vec<u8>> = query_scalar("SELECT tile_data FROM tiles")
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+    tiles
+        .into_iter()
+        .filter(|tile| {
+            !mvt_to_tile_layers(decode_gzip(tile).unwrap())
+                .unwrap()
+                .is_empty()
+        })
+        .count()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merges_vector_layers() {
+    let (src, _src_conn, src_file) = temp_named_mbtiles("merge_vector", WORLD_CITIES).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let options = MergeOptions {
+        rename_layers: HashMap::from([("cities".to_owned(), "places".to_owned())]),
+        ..MergeOptions::default()
+    };
+    let stats = merge(&[src_file.clone(), src_file], dst_file.path(), &options)
+        .await
+        .unwrap();
+    let count = non_empty_tile_count(&src).await;
+    assert_eq!(stats.tiles_written, count);
+    assert_eq!(stats.tiles_merged, count);
+
+    let dst = Mbtiles::new(dst_file.path()).unwrap();
+    assert_eq!(tile_count(&dst).await, count);
+    let src_layers = mvt_to_tile_layers(decode_gzip(&first_tile(&src).await).unwrap()).unwrap();
+    let dst_layers = mvt_to_tile_layers(decode_gzip(&first_tile(&dst).await).unwrap()).unwrap();
+    // Both copies of the layer were merged into a single renamed layer
+    assert_eq!(dst_layers.len(), 1);
+    assert_eq!(dst_layers[0].name(), "places");
+    assert_eq!(
+        dst_layers[0].features().len(),
+        2 * src_layers[0].features().len()
+    );
+
+    let mut conn = dst.open_readonly().await.unwrap();
+    let metadata = dst.get_metadata(&mut conn).await.unwrap();
+    let layers = metadata.tilejson.vector_layers.unwrap();
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].id, "places");
+    dst.validate(
+        &mut conn,
+        IntegrityCheckType::default(),
+        AggHashType::default(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drops_empty_tiles() {
+    let (_src, _src_conn, src_file) = temp_named_mbtiles("merge_drop", WORLD_CITIES).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let options = MergeOptions {
+        drop_layers: HashSet::from(["cities".to_owned()]),
+        ..MergeOptions::default()
+    };
+    let stats = merge(&[src_file], dst_file.path(), &options).await.unwrap();
+    assert_eq!(stats.tiles_written, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn composites_raster_tiles() {
+    let (src, mut src_conn, src_file) = temp_named_mbtiles("merge_raster", GEOGRAPHY_CLASS).await;
+    use_real_images(&mut src_conn).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let stats = merge(
+        &[src_file.clone(), src_file],
+        dst_file.path(),
+        &MergeOptions::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(stats.tiles_merged, tile_count(&src).await);
+
+    let dst = Mbtiles::new(dst_file.path()).unwrap();
+    let tile = first_tile(&dst).await;
+    assert_eq!(TileInfo::detect(&tile).format, Format::Png);
+    // An opaque image drawn over itself does not change
+    let expected = image::load_from_memory(&first_tile(&src).await).unwrap();
+    let actual = image::load_from_memory(&tile).unwrap();
+    assert_eq!(actual.to_rgba8(), expected.to_rgba8());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_mixed_formats() {
+    let (_vector, _vector_conn, vector_file) =
+        temp_named_mbtiles("merge_mixed_vector", WORLD_CITIES).await;
+    let (_raster, _raster_conn, raster_file) =
+        temp_named_mbtiles("merge_mixed_raster", GEOGRAPHY_CLASS).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let result = merge(
+        &[vector_file, raster_file],
+        dst_file.path(),
+        &MergeOptions::default(),
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(MbtError::IncompatibleMergeFormats { .. })
+    ));
+}
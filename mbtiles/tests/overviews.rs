@@ -0,0 +1,175 @@
+#![cfg(feature = "transcode")]
+#![allow(clippy::unwrap_used)]
+
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use martin_tile_utils::{Format, TileInfo};
+use mbtiles::{
+    AggHashType, IntegrityCheckType, MbtError, MbtTypeCli, Mbtiles, MbtilesCopier, OverviewOptions,
+    apply_patch, build_overviews, temp_named_mbtiles,
+};
+use sqlx::{SqliteConnection, query, query_scalar};
+use tempfile::NamedTempFile;
+
+const WORLD_CITIES: &str = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+const GEOGRAPHY_CLASS: &str = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+
+/// Replaces the placeholder images of the geography class fixture with real PNGs.
+async fn use_real_images(conn: &mut SqliteConnection) {
+    let tile_ids: Vec<String> = query_scalar("SELECT tile_id FROM images ORDER BY tile_id")
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+    for (shade, tile_id) in (0_u8..).step_by(40).zip(tile_ids) {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(256, 256, Rgba([shade, 0, 255 - shade, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        query("UPDATE images SET tile_data = ? WHERE tile_id = ?")
+            .bind(png)
+            .bind(tile_id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+}
+
+/// Copies the geography class fixture with real images into a flat file
+async fn flat_copy(name: &str) -> NamedTempFile {
+    let (_src, mut src_conn, src_file) = temp_named_mbtiles(name, GEOGRAPHY_CLASS).await;
+    use_real_images(&mut src_conn).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    MbtilesCopier {
+        src_file,
+        dst_file: dst_file.path().to_path_buf(),
+        dst_type_cli: Some(MbtTypeCli::Flat),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+    dst_file
+}
+
+async fn z0_tile(file: &Path) -> Option<Vec<u8>> {
+    let mbt = Mbtiles::new(file).unwrap();
+    let mut conn = mbt.open_readonly().await.unwrap();
+    mbt.get_tile(&mut conn, 0, 0, 0).await.unwrap()
+}
+
+/// Creates a patch deleting the given zoom 1 tiles (TMS rows) and applies it to `base`
+async fn delete_and_patch(base: &Path, name: &str, tiles: &[(u32, u32)]) -> NamedTempFile {
+    let edited = flat_copy(name).await;
+    let mbt = Mbtiles::new(edited.path()).unwrap();
+    let mut conn = mbt.open().await.unwrap();
+    for (x, y) in tiles {
+        query("DELETE FROM tiles WHERE zoom_level = 1 AND tile_column = ? AND tile_row = ?")
+            .bind(x)
+            .bind(y)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+    mbt.update_agg_tiles_hash(&mut conn).await.unwrap();
+
+    let patch = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    MbtilesCopier {
+        src_file: base.to_path_buf(),
+        dst_file: patch.path().to_path_buf(),
+        diff_with_file: Some((edited.path().to_path_buf(), None)),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+    apply_patch(base.to_path_buf(), patch.path().to_path_buf(), false)
+        .await
+        .unwrap();
+    patch
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebuilds_lower_zooms() {
+    let file = flat_copy("overviews_full").await;
+    let original = z0_tile(file.path()).await.unwrap();
+    let options = OverviewOptions {
+        max_zoom: Some(1),
+        ..OverviewOptions::default()
+    };
+    let stats = build_overviews(file.path(), &options).await.unwrap();
+    assert_eq!(stats.tiles_written, 1);
+    assert_eq!(stats.tiles_deleted, 0);
+
+    let tile = z0_tile(file.path()).await.unwrap();
+    assert_ne!(tile, original);
+    assert_eq!(TileInfo::detect(&tile).format, Format::Png);
+    let image = image::load_from_memory(&tile).unwrap();
+    let child = image::load_from_memory(&original).unwrap();
+    assert_eq!(image.width(), child.width());
+
+    let mbt = Mbtiles::new(file.path()).unwrap();
+    let mut conn = mbt.open_readonly().await.unwrap();
+    mbt.validate(
+        &mut conn,
+        IntegrityCheckType::default(),
+        AggHashType::default(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebuilds_patched_tiles() {
+    let file = flat_copy("overviews_patched").await;
+    let original = z0_tile(file.path()).await.unwrap();
+    let patch = delete_and_patch(file.path(), "overviews_patched_edit", &[(0, 0)]).await;
+
+    let options = OverviewOptions {
+        max_zoom: Some(1),
+        patch_file: Some(patch.path().to_path_buf()),
+        ..OverviewOptions::default()
+    };
+    let stats = build_overviews(file.path(), &options).await.unwrap();
+    assert_eq!(stats.tiles_written, 1);
+    assert_eq!(stats.tiles_deleted, 0);
+    assert_ne!(z0_tile(file.path()).await.unwrap(), original);
+
+    // Zoom levels above the max zoom are not touched
+    let mbt = Mbtiles::new(file.path()).unwrap();
+    let mut conn = mbt.open_readonly().await.unwrap();
+    let count: i64 = query_scalar("SELECT COUNT(*) FROM tiles WHERE zoom_level = 2")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deletes_tiles_without_children() {
+    let file = flat_copy("overviews_deleted").await;
+    let patch = delete_and_patch(
+        file.path(),
+        "overviews_deleted_edit",
+        &[(0, 0), (0, 1), (1, 0), (1, 1)],
+    )
+    .await;
+
+    let options = OverviewOptions {
+        max_zoom: Some(1),
+        patch_file: Some(patch.path().to_path_buf()),
+        ..OverviewOptions::default()
+    };
+    let stats = build_overviews(file.path(), &options).await.unwrap();
+    assert_eq!(stats.tiles_written, 0);
+    assert_eq!(stats.tiles_deleted, 1);
+    assert_eq!(z0_tile(file.path()).await, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_vector_tiles() {
+    let (_src, _src_conn, src_file) = temp_named_mbtiles("overviews_vector", WORLD_CITIES).await;
+    let result = build_overviews(&src_file, &OverviewOptions::default()).await;
+    assert!(matches!(result, Err(MbtError::NoRasterTiles { .. })));
+}
@@ -0,0 +1,277 @@
+#![allow(clippy::unwrap_used)]
+
+use std::path::Path;
+
+use mbtiles::{
+    MbtError, MbtTypeCli, Mbtiles, MbtilesCopier, PatchTypeCli, apply_patches, rebase_patch,
+    squash_patches, temp_named_mbtiles,
+};
+use rstest::rstest;
+use sqlx::{Executor as _, query_scalar};
+use tempfile::NamedTempFile;
+
+const TILES_V1: &str = "
+    CREATE TABLE metadata (name text, value text);
+    CREATE UNIQUE INDEX name ON metadata (name);
+    CREATE TABLE tiles (zoom_level integer, tile_column integer, tile_row integer, tile_data blob);
+    CREATE UNIQUE INDEX tile_index ON tiles (zoom_level, tile_column, tile_row);
+    INSERT INTO metadata VALUES ('name', 'v1');
+    INSERT INTO tiles VALUES
+        (1, 0, 0, cast('a' as blob)),
+        (1, 0, 1, cast('b' as blob)),
+        (1, 1, 0, cast('c' as blob)),
+        (1, 1, 1, cast('d' as blob));";
+
+/// Copies the test tiles into a flat file
+async fn base(name: &str) -> NamedTempFile {
+    let (_mbt, _conn, src_file) = temp_named_mbtiles(name, TILES_V1).await;
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    MbtilesCopier {
+        src_file,
+        dst_file: dst_file.path().to_path_buf(),
+        dst_type_cli: Some(MbtTypeCli::Flat),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+    dst_file
+}
+
+async fn copy(file: &Path) -> NamedTempFile {
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    MbtilesCopier {
+        src_file: file.to_path_buf(),
+        dst_file: dst_file.path().to_path_buf(),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+    dst_file
+}
+
+/// Copies `file` and runs the `edits` SQL on the copy
+async fn edited(file: &Path, edits: &'static str) -> NamedTempFile {
+    let dst_file = copy(file).await;
+    let mbt = Mbtiles::new(dst_file.path()).unwrap();
+    let mut conn = mbt.open().await.unwrap();
+    conn.execute(edits).await.unwrap();
+    mbt.update_agg_tiles_hash(&mut conn).await.unwrap();
+    dst_file
+}
+
+async fn diff(file1: &Path, file2: &Path, patch_type: PatchTypeCli) -> NamedTempFile {
+    let patch = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    MbtilesCopier {
+        src_file: file1.to_path_buf(),
+        dst_file: patch.path().to_path_buf(),
+        diff_with_file: Some((file2.to_path_buf(), patch_type.into())),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+    patch
+}
+
+/// Applies a patch to a copy of `file`
+async fn patched(file: &Path, patch: &Path) -> NamedTempFile {
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    MbtilesCopier {
+        src_file: file.to_path_buf(),
+        dst_file: dst_file.path().to_path_buf(),
+        apply_patch: Some(patch.to_path_buf()),
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+    dst_file
+}
+
+async fn agg_hash(file: &Path) -> String {
+    let mbt = Mbtiles::new(file).unwrap();
+    let mut conn = mbt.open_readonly().await.unwrap();
+    mbt.get_agg_tiles_hash(&mut conn).await.unwrap().unwrap()
+}
+
+async fn tile_count(file: &Path) -> i64 {
+    let mbt = Mbtiles::new(file).unwrap();
+    let mut conn = mbt.open_readonly().await.unwrap();
+    query_scalar("SELECT COUNT(*) FROM tiles")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn applies_patch_chain() {
+    let v1 = base("chain_v1").await;
+    let v2 = edited(
+        v1.path(),
+        "UPDATE tiles SET tile_data = cast('a2' as blob) WHERE tile_column = 0 AND tile_row = 0",
+    )
+    .await;
+    let v3 = edited(
+        v2.path(),
+        "DELETE FROM tiles WHERE tile_column = 1 AND tile_row = 1",
+    )
+    .await;
+    let p12 = diff(v1.path(), v2.path(), PatchTypeCli::Whole).await;
+    let p23 = diff(v2.path(), v3.path(), PatchTypeCli::Whole).await;
+
+    // Patches in the wrong order are rejected before anything is applied
+    let target = copy(v1.path()).await;
+    let files = vec![p23.path().to_path_buf(), p12.path().to_path_buf()];
+    let result = apply_patches(target.path().to_path_buf(), files, false).await;
+    assert!(matches!(result, Err(MbtError::BrokenPatchChain { .. })));
+    assert_eq!(agg_hash(target.path()).await, agg_hash(v1.path()).await);
+
+    let files = vec![p12.path().to_path_buf(), p23.path().to_path_buf()];
+    apply_patches(target.path().to_path_buf(), files, false)
+        .await
+        .unwrap();
+    assert_eq!(agg_hash(target.path()).await, agg_hash(v3.path()).await);
+    assert_eq!(tile_count(target.path()).await, 3);
+}
+
+#[rstest]
+#[case::whole(PatchTypeCli::Whole)]
+#[case::bin_diff_raw(PatchTypeCli::BinDiffRaw)]
+#[tokio::test(flavor = "multi_thread")]
+async fn squashes_patch_chain(#[case] patch_type: PatchTypeCli) {
+    let v1 = base(&format!("squash_v1_{patch_type}")).await;
+    let v2 = edited(
+        v1.path(),
+        "UPDATE tiles SET tile_data = cast('a2' as blob) WHERE tile_column = 0 AND tile_row = 0",
+    )
+    .await;
+    let v3 = edited(
+        v2.path(),
+        "
+        UPDATE tiles SET tile_data = cast('a3' as blob) WHERE tile_column = 0 AND tile_row = 0;
+        DELETE FROM tiles WHERE tile_column = 1 AND tile_row = 1;",
+    )
+    .await;
+    let v4 = edited(
+        v3.path(),
+        "INSERT INTO tiles VALUES (2, 0, 0, cast('e' as blob))",
+    )
+    .await;
+    let patches = [
+        diff(v1.path(), v2.path(), patch_type).await,
+        diff(v2.path(), v3.path(), patch_type).await,
+        diff(v3.path(), v4.path(), patch_type).await,
+    ];
+    let patch_files: Vec<_> = patches.iter().map(|p| p.path().to_path_buf()).collect();
+
+    let squashed = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    squash_patches(
+        v1.path(),
+        &patch_files,
+        squashed.path(),
+        patch_type.into(),
+        false,
+    )
+    .await
+    .unwrap();
+
+    let result = patched(v1.path(), squashed.path()).await;
+    assert_eq!(agg_hash(result.path()).await, agg_hash(v4.path()).await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebases_patch() {
+    let v1 = base("rebase_v1").await;
+    let ours = edited(
+        v1.path(),
+        "UPDATE tiles SET tile_data = cast('x' as blob) WHERE tile_column = 0 AND tile_row = 0",
+    )
+    .await;
+    let theirs = edited(
+        v1.path(),
+        "
+        DELETE FROM tiles WHERE tile_column = 1 AND tile_row = 0;
+        INSERT INTO tiles VALUES (2, 0, 0, cast('e' as blob));
+        UPDATE metadata SET value = 'v2' WHERE name = 'name';",
+    )
+    .await;
+    let both = edited(
+        ours.path(),
+        "
+        DELETE FROM tiles WHERE tile_column = 1 AND tile_row = 0;
+        INSERT INTO tiles VALUES (2, 0, 0, cast('e' as blob));",
+    )
+    .await;
+    let onto = diff(v1.path(), ours.path(), PatchTypeCli::Whole).await;
+    let patch = diff(v1.path(), theirs.path(), PatchTypeCli::Whole).await;
+
+    let rebased = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    rebase_patch(
+        v1.path(),
+        onto.path(),
+        patch.path(),
+        rebased.path(),
+        None,
+        false,
+    )
+    .await
+    .unwrap();
+
+    let result = patched(ours.path(), rebased.path()).await;
+    assert_eq!(agg_hash(result.path()).await, agg_hash(both.path()).await);
+    let mbt = Mbtiles::new(result.path()).unwrap();
+    let mut conn = mbt.open_readonly().await.unwrap();
+    let name = mbt.get_metadata_value(&mut conn, "name").await.unwrap();
+    assert_eq!(name.as_deref(), Some("v2"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebase_detects_conflicts() {
+    let v1 = base("rebase_conflict_v1").await;
+    let ours = edited(
+        v1.path(),
+        "UPDATE tiles SET tile_data = cast('x' as blob) WHERE tile_column = 0 AND tile_row = 0",
+    )
+    .await;
+    let theirs = edited(
+        v1.path(),
+        "
+        UPDATE tiles SET tile_data = cast('y' as blob) WHERE tile_column = 0 AND tile_row = 0;
+        UPDATE tiles SET tile_data = cast('z' as blob) WHERE tile_column = 0 AND tile_row = 1;",
+    )
+    .await;
+    let onto = diff(v1.path(), ours.path(), PatchTypeCli::Whole).await;
+    let patch = diff(v1.path(), theirs.path(), PatchTypeCli::Whole).await;
+
+    let rebased = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let result = rebase_patch(
+        v1.path(),
+        onto.path(),
+        patch.path(),
+        rebased.path(),
+        None,
+        false,
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(MbtError::ConflictingPatches { count: 1, .. })
+    ));
+
+    // With force, the tiles of the rebased patch win
+    let rebased = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    rebase_patch(
+        v1.path(),
+        onto.path(),
+        patch.path(),
+        rebased.path(),
+        None,
+        true,
+    )
+    .await
+    .unwrap();
+    let result = patched(ours.path(), rebased.path()).await;
+    assert_eq!(agg_hash(result.path()).await, agg_hash(theirs.path()).await);
+}
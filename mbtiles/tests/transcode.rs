@@ -0,0 +1,135 @@
+#![cfg(feature = "transcode")]
+#![allow(clippy::unwrap_used)]
+
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use martin_tile_utils::{Format, TileInfo, decode_gzip, decode_zstd};
+use mbtiles::{
+    Mbtiles, TranscodeCompression, TranscodeFormat, TranscodeOptions, temp_named_mbtiles, transcode,
+};
+use sqlx::{SqliteConnection, query, query_as, query_scalar};
+use tempfile::NamedTempFile;
+
+const WORLD_CITIES: &str = include_str!("../../tests/fixtures/mbtiles/world_cities.sql");
+const GEOGRAPHY_CLASS: &str = include_str!("../../tests/fixtures/mbtiles/geography-class-png.sql");
+
+/// Replaces the placeholder images of the geography class fixture with real PNGs.
+async fn use_real_images(conn: &mut SqliteConnection) {
+    let tile_ids: Vec<String> = query_scalar("SELECT tile_id FROM images ORDER BY tile_id")
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+    for (shade, tile_id) in (0_u8..).step_by(40).zip(tile_ids) {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(256, 256, Rgba([shade, 0, 255 - shade, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        query("UPDATE images SET tile_data = ? WHERE tile_id = ?")
+            .bind(png)
+            .bind(tile_id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+}
+
+/// The first tile of the file, with its TMS coordinates.
+async fn first_tile(conn: &mut SqliteConnection) -> (i64, i64, i64, Vec<u8>) {
+    query_as(
+        "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles
+         ORDER BY zoom_level, tile_column, tile_row LIMIT 1",
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap()
+}
+
+/// Transcodes `src_file` into a temporary file, which is deleted once the returned handle is dropped.
+async fn run(
+    src_file: &Path,
+    options: &TranscodeOptions,
+) -> (NamedTempFile, Mbtiles, SqliteConnection) {
+    let dst_file = NamedTempFile::with_suffix(".mbtiles").unwrap();
+    let stats = transcode(src_file, dst_file.path(), options).await.unwrap();
+    assert!(stats.tiles_written > 0);
+
+    let dst = Mbtiles::new(dst_file.path()).unwrap();
+    let conn = dst.open().await.unwrap();
+    (dst_file, dst, conn)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recompress_vector_tiles() {
+    let options = TranscodeOptions {
+        compression: Some(TranscodeCompression::Zstd),
+        ..TranscodeOptions::default()
+    };
+    let (_src, mut src_conn, src_file) = temp_named_mbtiles("transcode_zstd", WORLD_CITIES).await;
+    let (_file, dst, mut conn) = run(&src_file, &options).await;
+    assert_eq!(
+        dst.get_metadata_value(&mut conn, "compression")
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("zstd")
+    );
+
+    let original = first_tile(&mut src_conn).await;
+    let transcoded = first_tile(&mut conn).await;
+    assert_eq!(
+        (original.0, original.1, original.2),
+        (transcoded.0, transcoded.1, transcoded.2)
+    );
+    assert_eq!(
+        decode_zstd(&transcoded.3).unwrap(),
+        decode_gzip(&original.3).unwrap()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn convert_mvt_to_mlt() {
+    let options = TranscodeOptions {
+        format: Some(TranscodeFormat::Mlt),
+        ..TranscodeOptions::default()
+    };
+    let (_src, _src_conn, src_file) = temp_named_mbtiles("transcode_mlt", WORLD_CITIES).await;
+    let (_file, dst, mut conn) = run(&src_file, &options).await;
+    assert_eq!(
+        dst.get_metadata_value(&mut conn, "format")
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("mlt")
+    );
+    assert_eq!(
+        dst.get_metadata_value(&mut conn, "compression")
+            .await
+            .unwrap(),
+        None
+    );
+    let (.., tile) = first_tile(&mut conn).await;
+    assert_eq!(TileInfo::detect(&tile).format, Format::Mlt);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reencode_png_to_webp() {
+    let options = TranscodeOptions {
+        format: Some(TranscodeFormat::Webp),
+        ..TranscodeOptions::default()
+    };
+    let (_src, mut src_conn, src_file) =
+        temp_named_mbtiles("transcode_webp", GEOGRAPHY_CLASS).await;
+    use_real_images(&mut src_conn).await;
+    let (_file, dst, mut conn) = run(&src_file, &options).await;
+    assert_eq!(
+        dst.get_metadata_value(&mut conn, "format")
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("webp")
+    );
+    let (.., tile) = first_tile(&mut conn).await;
+    assert_eq!(TileInfo::detect(&tile).format, Format::Webp);
+}